@@ -1,8 +1,68 @@
-use std::env;
+use std::{env, fs, path::Path, process::Command, time::SystemTime};
 
 fn main() {
+    emit_build_info();
+
     // Run tauri-build only when the desktop binary is being built.
     if env::var("CARGO_FEATURE_DESKTOP").is_ok() {
         tauri_build::build();
     }
 }
+
+/// Embed build metadata read by `app_info` via `env!`.
+fn emit_build_info() {
+    // Honor SOURCE_DATE_EPOCH so reproducible builds get a stable date.
+    let timestamp = env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0)
+        });
+    println!("cargo:rustc-env=CTM_BUILD_TIMESTAMP={timestamp}");
+
+    // CI checkouts may be shallow or lack git; GITHUB_SHA covers Actions.
+    let commit = env::var("GITHUB_SHA")
+        .ok()
+        .or_else(|| {
+            Command::new("git")
+                .args(["rev-parse", "HEAD"])
+                .output()
+                .ok()
+                .filter(|out| out.status.success())
+                .and_then(|out| String::from_utf8(out.stdout).ok())
+        })
+        .map(|sha| sha.trim().chars().take(12).collect::<String>())
+        .filter(|sha| !sha.is_empty())
+        .unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=CTM_GIT_COMMIT={commit}");
+
+    let channel = env::var("CTM_UPDATE_CHANNEL").unwrap_or_else(|_| "stable".to_string());
+    println!("cargo:rustc-env=CTM_UPDATE_CHANNEL={channel}");
+
+    println!("cargo:rerun-if-changed=changelog.json");
+    watch_git_head();
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+    println!("cargo:rerun-if-env-changed=GITHUB_SHA");
+    println!("cargo:rerun-if-env-changed=CTM_UPDATE_CHANNEL");
+}
+
+/// Rerun when a commit moves HEAD, not just when HEAD switches branches.
+fn watch_git_head() {
+    let git_dir = Path::new("../.git");
+    let head = git_dir.join("HEAD");
+    println!("cargo:rerun-if-changed={}", head.display());
+    // A missing path counts as always changed, so only watch what exists.
+    let head_ref = fs::read_to_string(&head)
+        .ok()
+        .and_then(|head| head.strip_prefix("ref: ").map(|r| r.trim().to_string()));
+    let watched = head_ref
+        .map(|r| git_dir.join(r))
+        .into_iter()
+        .chain([git_dir.join("packed-refs")]);
+    for path in watched.filter(|path| path.exists()) {
+        println!("cargo:rerun-if-changed={}", path.display());
+    }
+}
@@ -0,0 +1,237 @@
+/*!
+ * Scoped access tokens for automation (scripts, a future local server).
+ *
+ * Every automation request must present a token, and each token carries
+ * only the scopes it was created with: a token that can list chats can't
+ * export them, and one that can export can't upload, so a leaked or
+ * over-eager script can't quietly ship off a full export. Tokens are
+ * created and revoked from settings.
+ *
+ * Only a SHA-256 hash of each token is kept, in
+ * `<app_local_data_dir>/access_tokens.json`; the token itself is shown
+ * once, when it is created. Anyone who can read the file learns the
+ * tokens' names and scopes but can't use them.
+ */
+
+use std::{collections::BTreeSet, path::Path};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+const TOKENS_FILENAME: &str = "access_tokens.json";
+
+/// Prefix that makes tokens easy to spot (e.g. in a leaked script)
+const TOKEN_PREFIX: &str = "ctm_";
+
+/// What a token may do
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Scope {
+    /// List chats and their metadata (no message text)
+    ListChats,
+    /// Build export zips of message text
+    Export,
+    /// Send exports to the ChatToMap server
+    Upload,
+}
+
+/// A token as stored and listed in settings
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenRecord {
+    pub id: String,
+    pub name: String,
+    pub scopes: BTreeSet<Scope>,
+    pub created_at: String,
+    #[serde(skip)]
+    token_hash: String,
+}
+
+/// On-disk form of a record; the hash is never sent to the frontend
+#[derive(Serialize, Deserialize)]
+struct StoredToken {
+    #[serde(flatten)]
+    record: TokenRecord,
+    token_hash: String,
+}
+
+/// Why a request was refused
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AccessError {
+    /// Unknown or revoked token
+    InvalidToken,
+    /// The token is valid but wasn't granted this scope
+    MissingScope { token: String, scope: Scope },
+}
+
+impl std::fmt::Display for AccessError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidToken => f.write_str("Invalid or revoked access token"),
+            Self::MissingScope { token, scope } => {
+                write!(f, "Access token \"{token}\" does not allow {scope:?}")
+            }
+        }
+    }
+}
+
+/// The tokens created in settings
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TokenStore {
+    tokens: Vec<TokenRecord>,
+}
+
+impl TokenStore {
+    /// Read tokens from `app_local_data_dir`. A missing file means no
+    /// tokens; an unreadable one is an error, since falling back to no
+    /// tokens and saving would revoke them all.
+    pub fn load(app_local_data_dir: &Path) -> Result<Self, String> {
+        let path = app_local_data_dir.join(TOKENS_FILENAME);
+        let contents = match std::fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(format!("Failed to read {path:?}: {e}")),
+        };
+        let stored: Vec<StoredToken> = serde_json::from_str(&contents)
+            .map_err(|e| format!("Failed to parse {path:?}: {e}"))?;
+        let tokens = stored
+            .into_iter()
+            .map(|stored| TokenRecord {
+                token_hash: stored.token_hash,
+                ..stored.record
+            })
+            .collect();
+        Ok(Self { tokens })
+    }
+
+    /// Write tokens to `app_local_data_dir`, creating it if needed
+    pub fn save(&self, app_local_data_dir: &Path) -> Result<(), String> {
+        std::fs::create_dir_all(app_local_data_dir)
+            .map_err(|e| format!("Failed to create {app_local_data_dir:?}: {e}"))?;
+        let stored: Vec<StoredToken> = self
+            .tokens
+            .iter()
+            .map(|record| StoredToken {
+                record: record.clone(),
+                token_hash: record.token_hash.clone(),
+            })
+            .collect();
+        let contents = serde_json::to_string_pretty(&stored)
+            .map_err(|e| format!("Failed to serialize access tokens: {e}"))?;
+        std::fs::write(app_local_data_dir.join(TOKENS_FILENAME), contents)
+            .map_err(|e| format!("Failed to write access tokens: {e}"))
+    }
+
+    pub fn tokens(&self) -> &[TokenRecord] {
+        &self.tokens
+    }
+
+    /// Create a token; returns its record and the token itself, which
+    /// can't be recovered later
+    pub fn create(&mut self, name: &str, scopes: BTreeSet<Scope>) -> (TokenRecord, String) {
+        let token = format!(
+            "{TOKEN_PREFIX}{}{}",
+            uuid::Uuid::new_v4().simple(),
+            uuid::Uuid::new_v4().simple()
+        );
+        let record = TokenRecord {
+            id: uuid::Uuid::new_v4().to_string(),
+            name: name.trim().to_string(),
+            scopes,
+            created_at: chrono::Utc::now().to_rfc3339(),
+            token_hash: hash_token(&token),
+        };
+        self.tokens.push(record.clone());
+        (record, token)
+    }
+
+    /// Revoke a token by ID; `false` if there was no such token
+    pub fn revoke(&mut self, id: &str) -> bool {
+        let before = self.tokens.len();
+        self.tokens.retain(|record| record.id != id);
+        self.tokens.len() != before
+    }
+
+    /// The token's record if `token` exists and was granted `scope`
+    pub fn authorize(&self, token: &str, scope: Scope) -> Result<&TokenRecord, AccessError> {
+        let token_hash = hash_token(token);
+        let record = self
+            .tokens
+            .iter()
+            .find(|record| record.token_hash == token_hash)
+            .ok_or(AccessError::InvalidToken)?;
+        if record.scopes.contains(&scope) {
+            Ok(record)
+        } else {
+            Err(AccessError::MissingScope {
+                token: record.name.clone(),
+                scope,
+            })
+        }
+    }
+}
+
+fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn tokens_only_allow_their_scopes_until_revoked() {
+        let mut store = TokenStore::default();
+        let (lister, list_token) = store.create("Shortcuts", BTreeSet::from([Scope::ListChats]));
+        let (_, export_token) = store.create("Backup", BTreeSet::from([Scope::Export]));
+
+        assert_eq!(store.authorize(&list_token, Scope::ListChats), Ok(&lister));
+        assert_eq!(
+            store.authorize(&list_token, Scope::Export),
+            Err(AccessError::MissingScope {
+                token: "Shortcuts".to_string(),
+                scope: Scope::Export
+            })
+        );
+        assert!(store.authorize(&export_token, Scope::Upload).is_err());
+        assert_eq!(
+            store.authorize("ctm_guess", Scope::ListChats),
+            Err(AccessError::InvalidToken)
+        );
+
+        assert!(store.revoke(&lister.id));
+        assert!(!store.revoke(&lister.id));
+        assert_eq!(
+            store.authorize(&list_token, Scope::ListChats),
+            Err(AccessError::InvalidToken)
+        );
+    }
+
+    #[test]
+    fn only_token_hashes_are_saved() {
+        let dir = TempDir::new().unwrap();
+        assert_eq!(TokenStore::load(dir.path()), Ok(TokenStore::default()));
+
+        let mut store = TokenStore::default();
+        let (record, token) = store.create("Backup", BTreeSet::from([Scope::Export]));
+        store.save(dir.path()).unwrap();
+
+        let contents = std::fs::read_to_string(dir.path().join(TOKENS_FILENAME)).unwrap();
+        assert!(!contents.contains(&token));
+        assert!(contents.contains(&hash_token(&token)));
+        // Listing a record never reveals the hash
+        assert!(serde_json::to_value(&record)
+            .unwrap()
+            .get("token_hash")
+            .is_none());
+
+        let loaded = TokenStore::load(dir.path()).unwrap();
+        assert_eq!(loaded, store);
+        assert!(loaded.authorize(&token, Scope::Export).is_ok());
+
+        std::fs::write(dir.path().join(TOKENS_FILENAME), "{not json").unwrap();
+        assert!(TokenStore::load(dir.path()).is_err());
+    }
+}
@@ -0,0 +1,122 @@
+/*!
+ * The Mac owner's own Messages accounts.
+ *
+ * With more than one Apple ID (or a phone number and an email) signed in to
+ * Messages, the owner's other addresses show up as ordinary handles: group
+ * chats list "you" as a participant, and messages between the accounts are
+ * attributed to that handle instead of "Me". The owner's addresses are the
+ * local side of every message (`message.destination_caller_id`) plus, on
+ * databases that have them, `chat.account_login` and
+ * `chat.last_addressed_handle`.
+ */
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use rusqlite::Connection;
+
+use crate::contacts::phone_keys;
+
+/// Queries yielding the owner's addresses; columns missing from older
+/// databases just make a query fail and contribute nothing
+const OWNER_ADDRESS_QUERIES: [&str; 3] = [
+    "SELECT DISTINCT destination_caller_id FROM message WHERE destination_caller_id != ''",
+    "SELECT DISTINCT account_login FROM chat WHERE account_login != ''",
+    "SELECT DISTINCT last_addressed_handle FROM chat WHERE last_addressed_handle != ''",
+];
+
+/// Handles that belong to the owner rather than another participant
+#[derive(Debug, Default)]
+pub struct OwnerAccounts {
+    handle_ids: HashSet<i32>,
+}
+
+impl OwnerAccounts {
+    /// Find the owner's addresses in chat.db and the handles matching them
+    pub fn load(db: &Connection, handles: &HashMap<i32, String>) -> Self {
+        let addresses: Vec<String> = OWNER_ADDRESS_QUERIES
+            .iter()
+            .flat_map(|sql| query_strings(db, sql))
+            .collect();
+        let accounts = Self::from_addresses(&addresses, handles);
+        tracing::debug!(
+            "[accounts] {} owner addresses, {} owner handles",
+            addresses.len(),
+            accounts.handle_ids.len()
+        );
+        accounts
+    }
+
+    /// Match handles against owner addresses (`E:`/`P:` prefixes allowed)
+    pub fn from_addresses(addresses: &[String], handles: &HashMap<i32, String>) -> Self {
+        let owner_keys: HashSet<String> = addresses
+            .iter()
+            .flat_map(|address| address_keys(address))
+            .collect();
+        let handle_ids = handles
+            .iter()
+            .filter(|(_, id)| address_keys(id).iter().any(|key| owner_keys.contains(key)))
+            .map(|(&handle_id, _)| handle_id)
+            .collect();
+        Self { handle_ids }
+    }
+
+    pub fn is_owner(&self, handle_id: i32) -> bool {
+        self.handle_ids.contains(&handle_id)
+    }
+
+    /// Drop the owner's handles from per-chat participant sets
+    pub fn remove_from(&self, chat_participants: &mut HashMap<i32, BTreeSet<i32>>) {
+        for participants in chat_participants.values_mut() {
+            participants.retain(|handle_id| !self.is_owner(*handle_id));
+        }
+    }
+}
+
+/// Lookup keys for an address: lowercased email, or every phone variant
+fn address_keys(address: &str) -> Vec<String> {
+    let address = address.trim();
+    let address = address
+        .strip_prefix("E:")
+        .or_else(|| address.strip_prefix("P:"))
+        .unwrap_or(address);
+    if address.contains('@') {
+        vec![address.to_lowercase()]
+    } else {
+        phone_keys(address)
+    }
+}
+
+fn query_strings(db: &Connection, sql: &str) -> Vec<String> {
+    let result = db.prepare(sql).and_then(|mut stmt| {
+        stmt.query_map([], |row| row.get::<_, Option<String>>(0))?
+            .collect::<Result<Vec<_>, _>>()
+    });
+    match result {
+        Ok(values) => values.into_iter().flatten().collect(),
+        Err(e) => {
+            tracing::debug!("[accounts] Skipping owner address query: {e}");
+            Vec::new()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn handles_matching_owner_addresses_in_any_format() {
+        let handles = HashMap::from([
+            (1, "+15551234567".to_string()),
+            (2, "Me@iCloud.com".to_string()),
+            (3, "+15559999999".to_string()),
+        ]);
+        let addresses = vec!["P:5551234567".to_string(), "E:me@icloud.com".to_string()];
+
+        let accounts = OwnerAccounts::from_addresses(&addresses, &handles);
+
+        assert!(accounts.is_owner(1));
+        assert!(accounts.is_owner(2));
+        assert!(!accounts.is_owner(3));
+    }
+}
@@ -0,0 +1,265 @@
+/*!
+ * Address-candidate pre-scan.
+ *
+ * How good a map a chat makes depends on how often people in it mention
+ * places. Before anything is exported or uploaded, [`scan_addresses`] runs
+ * the selected chats' message text through cheap local heuristics and
+ * counts three kinds of candidate:
+ *
+ * - Street addresses: a house number, up to three words and a street type
+ *   ("12 Oak Street", "221B Baker St")
+ * - Place names: capitalized words after "at", "in", "near", ... ("dinner
+ *   at Lupa", "staying in Lisbon"), skipping days and months
+ * - Map links: Google/Apple Maps, Waze, and listing sites like Airbnb,
+ *   Booking.com and Yelp
+ *
+ * The counts only rank chats for the selection screen; nothing leaves the
+ * machine and the patterns favour English.
+ */
+
+use std::{collections::HashMap, path::Path};
+
+use imessage_database::tables::{messages::Message, table::Table};
+use regex::Regex;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+use crate::shared_links::extract_urls;
+
+/// Hosts (or host and path prefixes) of map and listing links
+const MAP_LINK_HOSTS: &[&str] = &[
+    "maps.google.",
+    "google.com/maps",
+    "goo.gl/maps",
+    "maps.app.goo.gl",
+    "maps.apple.com",
+    "waze.com",
+    "airbnb.",
+    "booking.com",
+    "tripadvisor.",
+    "yelp.",
+    "opentable.",
+];
+
+/// Capitalized words after a preposition that are not places
+const NOT_PLACES: &[&str] = &[
+    "I",
+    "Me",
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+    "Sunday",
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+    "Christmas",
+    "Easter",
+];
+
+/// Candidate counts for one chat
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChatAddressScan {
+    pub chat_id: i32,
+    pub name: String,
+    /// Messages with text
+    pub messages_scanned: usize,
+    /// Messages with at least one candidate of any kind
+    pub messages_with_candidates: usize,
+    pub street_addresses: usize,
+    pub place_names: usize,
+    pub map_links: usize,
+}
+
+/// Scan the selected chats, most promising first (by messages with
+/// candidates)
+pub fn scan_addresses(
+    chat_ids: &[i32],
+    custom_db_path: Option<&Path>,
+) -> Result<Vec<ChatAddressScan>, String> {
+    let chat_db = crate::db_snapshot::open_chat_db(custom_db_path)?;
+    let db = &chat_db.conn;
+    let names = crate::export::preflight::chat_names(db)?;
+
+    let mut scans = scan_db(db, chat_ids)?;
+    for scan in &mut scans {
+        scan.name = names
+            .get(&scan.chat_id)
+            .cloned()
+            .unwrap_or_else(|| format!("Chat {}", scan.chat_id));
+    }
+    Ok(scans)
+}
+
+/// Count candidates in an open database
+fn scan_db(db: &Connection, chat_ids: &[i32]) -> Result<Vec<ChatAddressScan>, String> {
+    let patterns = AddressPatterns::new();
+    let mut scans: HashMap<i32, ChatAddressScan> = chat_ids
+        .iter()
+        .map(|&chat_id| {
+            let scan = ChatAddressScan {
+                chat_id,
+                ..Default::default()
+            };
+            (chat_id, scan)
+        })
+        .collect();
+
+    Message::stream(db, |message_result| {
+        let Ok(mut message) = message_result else {
+            return Ok::<(), String>(());
+        };
+        let Some(scan) = message.chat_id.and_then(|id| scans.get_mut(&id)) else {
+            return Ok(());
+        };
+        let _ = message.generate_text(db);
+        let Some(text) = message.text.as_deref().filter(|t| !t.trim().is_empty()) else {
+            return Ok(());
+        };
+        let found = patterns.count(text);
+        scan.messages_scanned += 1;
+        if found.total() > 0 {
+            scan.messages_with_candidates += 1;
+        }
+        scan.street_addresses += found.street_addresses;
+        scan.place_names += found.place_names;
+        scan.map_links += found.map_links;
+        Ok(())
+    })
+    .map_err(|e| format!("Failed to scan messages: {e}"))?;
+
+    let mut scans: Vec<ChatAddressScan> = scans.into_values().collect();
+    scans.sort_by(|a, b| {
+        (b.messages_with_candidates, a.chat_id).cmp(&(a.messages_with_candidates, b.chat_id))
+    });
+    Ok(scans)
+}
+
+/// Candidates found in one message
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Candidates {
+    street_addresses: usize,
+    place_names: usize,
+    map_links: usize,
+}
+
+impl Candidates {
+    fn total(&self) -> usize {
+        self.street_addresses + self.place_names + self.map_links
+    }
+}
+
+struct AddressPatterns {
+    street: Regex,
+    place: Regex,
+}
+
+impl AddressPatterns {
+    fn new() -> Self {
+        Self {
+            street: Regex::new(
+                r"(?i)\b\d{1,5}[a-z]?,?\s+(?:[a-z][\w'.-]*\s+){1,3}(?:street|st|road|rd|avenue|ave|boulevard|blvd|lane|ln|drive|dr|way|place|pl|court|ct|terrace|tce|crescent|cres|highway|hwy|parade|square|sq)\b",
+            )
+            .unwrap(),
+            place: Regex::new(
+                r"\b(?:at|in|near|to|from|visit|visiting)\s+(?:the\s+)?([A-Z][\w'&-]+(?:\s+[A-Z][\w'&-]+){0,3})",
+            )
+            .unwrap(),
+        }
+    }
+
+    fn count(&self, text: &str) -> Candidates {
+        let place_names = self
+            .place
+            .captures_iter(text)
+            .filter(|captures| {
+                let first_word = captures[1].split_whitespace().next().unwrap_or_default();
+                !NOT_PLACES.contains(&first_word)
+            })
+            .count();
+        let map_links = extract_urls(text)
+            .iter()
+            .filter(|url| MAP_LINK_HOSTS.iter().any(|host| url.contains(host)))
+            .count();
+        Candidates {
+            street_addresses: self.street.find_iter(text).count(),
+            place_names,
+            map_links,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{ChatBuilder, MessageBuilder, TestIMessageDb};
+
+    #[test]
+    fn counts_addresses_places_and_map_links() {
+        let patterns = AddressPatterns::new();
+        assert_eq!(
+            patterns.count(
+                "Meet me at 12 Oak Street, then dinner at Lupa Osteria \
+                 https://maps.app.goo.gl/abc"
+            ),
+            Candidates {
+                street_addresses: 1,
+                place_names: 1,
+                map_links: 1,
+            }
+        );
+        assert_eq!(patterns.count("221B Baker St").street_addresses, 1);
+        // Days, months and plain numbers aren't places or addresses
+        assert_eq!(
+            patterns
+                .count("See you in March, at 8 on Friday. I have 3 cats")
+                .total(),
+            0
+        );
+        assert_eq!(
+            patterns
+                .count("https://example.com/maps and https://airbnb.com/rooms/1")
+                .map_links,
+            1
+        );
+    }
+
+    #[test]
+    fn ranks_selected_chats_by_messages_with_candidates() {
+        let mut db = TestIMessageDb::new().unwrap();
+        let quiet = db.chat(ChatBuilder::new("+15551234567")).unwrap();
+        let travel = db.chat(ChatBuilder::new("+15557654321")).unwrap();
+        let ignored = db.chat(ChatBuilder::new("+15550000000")).unwrap();
+        for (chat, text) in [
+            (quiet, "How was your day?"),
+            (travel, "Staying in Lisbon"),
+            (travel, "The flat is at 5 Rua Augusta Street"),
+            (travel, "ok"),
+            (ignored, "Dinner at Lupa"),
+        ] {
+            db.message(MessageBuilder::new().text(text).chat(chat))
+                .unwrap();
+        }
+
+        let scans = scan_db(db.conn(), &[quiet, travel]).unwrap();
+
+        let counts: Vec<(i32, usize, usize)> = scans
+            .iter()
+            .map(|s| (s.chat_id, s.messages_scanned, s.messages_with_candidates))
+            .collect();
+        assert_eq!(counts, [(travel, 3, 2), (quiet, 1, 0)]);
+        assert_eq!(scans[0].street_addresses, 1);
+        assert_eq!(scans[0].place_names, 1);
+    }
+}
@@ -1,26 +1,36 @@
 /*!
  * Hand-written API client for the ChatToMap SaaS upload endpoints.
  *
- * The desktop touches exactly two routes (`/api/upload/presign` and
- * `/api/upload/complete`) plus a direct PUT to a Convex storage URL. Replacing
+ * The desktop touches exactly four upload routes (`/api/upload/presign`,
+ * `/api/upload/complete`, plus `/api/upload/usage` for the plan's limits
+ * and `/api/upload/validate` to check a manifest before uploading), a
+ * direct PUT to a Convex storage URL, and `/api/telemetry/errors` for
+ * opt-in error reports (see telemetry.rs). Replacing
  * the previous progenitor codegen with ~150 lines of `reqwest` removes the
  * dependency on a hand-maintained OpenAPI spec.
  *
  * Authentication: each request carries `X-Desktop-Signature` and
  * `X-Desktop-Timestamp` headers computed from
  * `HMAC-SHA256(DESKTOP_UPLOAD_SHARED_SECRET, "<timestamp>:<bound_value>")`.
- * For `presign`, the bound value is `content_length`. For `complete`, it is
- * the `storage_id` returned by the Convex storage upload. The server skips
- * Turnstile when the signature validates.
+ * For `presign` and `validate`, the bound value is `content_length`. For
+ * `complete`, it is the `storage_id` returned by the Convex storage
+ * upload. For `usage`, it is the `visitor_id`, and for `errors`, the
+ * number of reports. The server skips Turnstile when the signature
+ * validates.
  */
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 use hmac::{Hmac, Mac};
 use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
 use serde::{Deserialize, Serialize};
 use sha2::Sha256;
 
+use crate::{
+    telemetry::ErrorReport,
+    upload::{check_rate_limit, AccountUsage, UploadError, UploadTimeouts, ValidateData},
+};
+
 type HmacSha256 = Hmac<Sha256>;
 
 pub const DESKTOP_SIGNATURE_HEADER: &str = "X-Desktop-Signature";
@@ -54,6 +64,12 @@ pub struct UploadCompleteRequest {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub client_locale: Option<ClientLocale>,
     pub visitor_id: String,
+    /// SHA-256 of the uploaded zip (lowercase hex), checked by the server
+    /// before processing starts
+    pub zip_sha256: String,
+    /// Caller-supplied export metadata, shown on the SaaS side
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, String>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -80,6 +96,7 @@ pub struct ConvexStorageUploadResponse {
 pub struct ApiClient {
     base_url: String,
     http: reqwest::Client,
+    timeouts: UploadTimeouts,
     secret: String,
     extra_headers: HeaderMap,
 }
@@ -92,7 +109,8 @@ impl ApiClient {
     pub fn with_secret(base_url: impl Into<String>, secret: String) -> Self {
         Self {
             base_url: base_url.into(),
-            http: reqwest::Client::new(),
+            http: UploadTimeouts::default().client(),
+            timeouts: UploadTimeouts::default(),
             secret,
             extra_headers: HeaderMap::new(),
         }
@@ -111,7 +129,14 @@ impl ApiClient {
         self
     }
 
-    pub async fn upload_presign(&self, content_length: u64) -> Result<PresignData, String> {
+    /// Give up on requests after `timeouts` instead of the defaults
+    pub fn with_timeouts(mut self, timeouts: &UploadTimeouts) -> Self {
+        self.http = timeouts.client();
+        self.timeouts = timeouts.clone();
+        self
+    }
+
+    pub async fn upload_presign(&self, content_length: u64) -> Result<PresignData, UploadError> {
         let timestamp = current_unix_timestamp();
         let signature = sign_payload(&self.secret, &format!("{timestamp}:{content_length}"))
             .map_err(|e| format!("Failed to sign request: {e}"))?;
@@ -121,14 +146,15 @@ impl ApiClient {
         let response = self
             .post(&url, &body, &timestamp, &signature)
             .await
-            .map_err(|e| format!("presign request failed: {e}"))?;
-        unwrap_api_response(response, "presign").await
+            .map_err(|e| UploadError::request(e, "presign", &self.timeouts))?;
+        check_rate_limit(&response, "presign")?;
+        Ok(unwrap_api_response(response, "presign").await?)
     }
 
     pub async fn upload_complete(
         &self,
         body: UploadCompleteRequest,
-    ) -> Result<UploadCompleteData, String> {
+    ) -> Result<UploadCompleteData, UploadError> {
         let timestamp = current_unix_timestamp();
         let signature = sign_payload(&self.secret, &format!("{timestamp}:{}", body.storage_id))
             .map_err(|e| format!("Failed to sign request: {e}"))?;
@@ -136,8 +162,61 @@ impl ApiClient {
         let response = self
             .post(&url, &body, &timestamp, &signature)
             .await
-            .map_err(|e| format!("complete request failed: {e}"))?;
-        unwrap_api_response(response, "complete").await
+            .map_err(|e| UploadError::request(e, "complete", &self.timeouts))?;
+        check_rate_limit(&response, "complete")?;
+        Ok(unwrap_api_response(response, "complete").await?)
+    }
+
+    pub async fn upload_validate(
+        &self,
+        manifest: &serde_json::Value,
+        content_length: u64,
+        upload_platform: &str,
+    ) -> Result<ValidateData, UploadError> {
+        let timestamp = current_unix_timestamp();
+        let signature = sign_payload(&self.secret, &format!("{timestamp}:{content_length}"))
+            .map_err(|e| format!("Failed to sign request: {e}"))?;
+        let body = serde_json::json!({
+            "content_length": content_length,
+            "upload_platform": upload_platform,
+            "manifest": manifest,
+        });
+        let url = format!("{}/api/upload/validate", self.base_url);
+        let response = self
+            .post(&url, &body, &timestamp, &signature)
+            .await
+            .map_err(|e| UploadError::request(e, "validate", &self.timeouts))?;
+        check_rate_limit(&response, "validate")?;
+        Ok(unwrap_api_response(response, "validate").await?)
+    }
+
+    pub async fn upload_usage(&self, visitor_id: &str) -> Result<AccountUsage, UploadError> {
+        let timestamp = current_unix_timestamp();
+        let signature = sign_payload(&self.secret, &format!("{timestamp}:{visitor_id}"))
+            .map_err(|e| format!("Failed to sign request: {e}"))?;
+        let body = serde_json::json!({ "visitor_id": visitor_id });
+        let url = format!("{}/api/upload/usage", self.base_url);
+        let response = self
+            .post(&url, &body, &timestamp, &signature)
+            .await
+            .map_err(|e| UploadError::request(e, "usage", &self.timeouts))?;
+        check_rate_limit(&response, "usage")?;
+        Ok(unwrap_api_response(response, "usage").await?)
+    }
+
+    pub async fn report_errors(&self, reports: &[ErrorReport]) -> Result<(), UploadError> {
+        let timestamp = current_unix_timestamp();
+        let signature = sign_payload(&self.secret, &format!("{timestamp}:{}", reports.len()))
+            .map_err(|e| format!("Failed to sign request: {e}"))?;
+        let body = serde_json::json!({ "reports": reports });
+        let url = format!("{}/api/telemetry/errors", self.base_url);
+        let response = self
+            .post(&url, &body, &timestamp, &signature)
+            .await
+            .map_err(|e| UploadError::request(e, "errors", &self.timeouts))?;
+        check_rate_limit(&response, "errors")?;
+        unwrap_api_response::<serde_json::Value>(response, "errors").await?;
+        Ok(())
     }
 
     async fn post<B: Serialize + ?Sized>(
@@ -268,6 +347,8 @@ mod tests {
                 language: Some("en-NZ".to_string()),
             }),
             visitor_id: "visitor-abc".to_string(),
+            zip_sha256: "ab12".to_string(),
+            metadata: BTreeMap::from([("trip".to_string(), "Italy 2024".to_string())]),
         };
         let json = serde_json::to_value(&req).unwrap();
         assert_eq!(json["storage_id"], "store-123");
@@ -275,6 +356,8 @@ mod tests {
         assert_eq!(json["original_filename"], "export.zip");
         assert_eq!(json["client_locale"]["timezone"], "Pacific/Auckland");
         assert_eq!(json["visitor_id"], "visitor-abc");
+        assert_eq!(json["zip_sha256"], "ab12");
+        assert_eq!(json["metadata"]["trip"], "Italy 2024");
     }
 
     #[test]
@@ -285,9 +368,12 @@ mod tests {
             original_filename: None,
             client_locale: None,
             visitor_id: "v".to_string(),
+            zip_sha256: String::new(),
+            metadata: BTreeMap::new(),
         };
         let json = serde_json::to_value(&req).unwrap();
         assert!(json.get("original_filename").is_none());
         assert!(json.get("client_locale").is_none());
+        assert!(json.get("metadata").is_none());
     }
 }
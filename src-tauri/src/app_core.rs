@@ -0,0 +1,119 @@
+/*!
+ * Shared state behind the desktop app's Tauri commands.
+ *
+ * The app manages a single `Arc<AppCore>`; commands borrow it and spawned
+ * tasks clone the `Arc`. The locking rules:
+ *
+ * 1. Anything fixed at startup (the screenshot config) is a plain field.
+ * 2. Every lock is private and only taken inside an `AppCore` method that
+ *    either returns a clone or runs a synchronous closure, so a guard can
+ *    never outlive the call or be held across an `.await`.
+ * 3. No method holds two locks at once. One that must would take them in
+ *    field order (`overrides`, then `export_queue`). `ExportManager` keeps
+ *    its own lock and is never called while another is held.
+ * 4. Locks are `std::sync`, not tokio's: the critical sections are short
+ *    and never wait. Waiting (for a cancellation, for the pipeline to come
+ *    free) uses tokio primitives such as the `Notify` in `ExportManager`.
+ * 5. A poisoned lock is recovered rather than unwrapped, so a panic in one
+ *    command can't wedge every later one.
+ */
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, PoisonError, RwLock},
+};
+
+use crate::{
+    export::{queue::ExportQueue, state::ExportManager},
+    screenshot::ScreenshotConfig,
+};
+
+/// Debug panel overrides read by every upload
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HostOverrides {
+    /// WEB host (results page; chattomap.com)
+    pub server_host_override: Option<String>,
+    /// API host (Convex HTTP actions; *.convex.site)
+    pub api_host_override: Option<String>,
+    /// Extra headers for API requests (e.g. Cloudflare Access tokens)
+    pub custom_headers: HashMap<String, String>,
+}
+
+/// State shared by all commands; see the module docs for the locking rules
+#[derive(Debug, Default)]
+pub struct AppCore {
+    screenshot_config: ScreenshotConfig,
+    overrides: RwLock<HostOverrides>,
+    export_queue: Mutex<ExportQueue>,
+    export_manager: ExportManager,
+}
+
+impl AppCore {
+    pub fn new(screenshot_config: ScreenshotConfig) -> Arc<Self> {
+        Arc::new(Self {
+            screenshot_config,
+            ..Default::default()
+        })
+    }
+
+    pub fn screenshot_config(&self) -> &ScreenshotConfig {
+        &self.screenshot_config
+    }
+
+    /// A consistent snapshot of the host overrides and custom headers
+    pub fn host_overrides(&self) -> HostOverrides {
+        self.overrides
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    pub fn update_host_overrides(&self, update: impl FnOnce(&mut HostOverrides)) {
+        update(
+            &mut self
+                .overrides
+                .write()
+                .unwrap_or_else(PoisonError::into_inner),
+        );
+    }
+
+    /// Run `f` with the export queue locked
+    pub fn with_export_queue<R>(&self, f: impl FnOnce(&mut ExportQueue) -> R) -> R {
+        f(&mut self
+            .export_queue
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner))
+    }
+
+    /// The running export, if any; rejects a second concurrent export
+    pub fn export_manager(&self) -> &ExportManager {
+        &self.export_manager
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_panic_while_locked_does_not_wedge_later_commands() {
+        let core = AppCore::new(ScreenshotConfig::default());
+
+        let panicking = Arc::clone(&core);
+        let _ = std::thread::spawn(move || {
+            panicking.update_host_overrides(|overrides| {
+                overrides.api_host_override = Some("http://127.0.0.1:3211".to_string());
+                panic!("command failed mid-update");
+            });
+        })
+        .join();
+
+        assert_eq!(
+            core.host_overrides().api_host_override.as_deref(),
+            Some("http://127.0.0.1:3211")
+        );
+        core.update_host_overrides(|overrides| overrides.api_host_override = None);
+        assert_eq!(core.host_overrides(), HostOverrides::default());
+        assert!(core.with_export_queue(|queue| queue.jobs().is_empty()));
+    }
+}
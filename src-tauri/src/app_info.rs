@@ -0,0 +1,99 @@
+/*!
+ * Build and release metadata for the About and update screens.
+ *
+ * Everything here is embedded at compile time: the version from Cargo,
+ * build date / git commit / update channel from build.rs, and the
+ * changelog from `changelog.json`. The frontend reads it through a single
+ * command so nothing about the build is hard-coded in TypeScript, and
+ * support can confirm exactly which build a user is running.
+ */
+
+use chrono::{TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Machine-readable changelog, newest release first
+const CHANGELOG_JSON: &str = include_str!("../changelog.json");
+
+/// How many releases `app_info` includes
+const RECENT_CHANGELOG_ENTRIES: usize = 5;
+
+/// One release in `changelog.json`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChangelogEntry {
+    pub version: String,
+    /// Release date (YYYY-MM-DD)
+    pub date: String,
+    pub changes: Vec<String>,
+}
+
+/// App build information returned to the frontend
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AppInfo {
+    pub version: String,
+    /// RFC 3339 UTC timestamp of the build
+    pub build_date: String,
+    /// Short git commit hash, or "unknown" when built outside a checkout
+    pub git_commit: String,
+    /// Update channel the build was produced for (e.g. "stable", "beta")
+    pub update_channel: String,
+    /// Most recent releases, newest first
+    pub changelog: Vec<ChangelogEntry>,
+}
+
+/// Parse the embedded changelog
+pub fn changelog() -> Result<Vec<ChangelogEntry>, String> {
+    serde_json::from_str(CHANGELOG_JSON).map_err(|e| format!("Invalid changelog.json: {e}"))
+}
+
+/// Collect build metadata and recent changelog entries
+pub fn app_info() -> Result<AppInfo, String> {
+    let mut changelog = changelog()?;
+    changelog.truncate(RECENT_CHANGELOG_ENTRIES);
+
+    Ok(AppInfo {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        build_date: format_build_timestamp(env!("CTM_BUILD_TIMESTAMP")),
+        git_commit: env!("CTM_GIT_COMMIT").to_string(),
+        update_channel: env!("CTM_UPDATE_CHANNEL").to_string(),
+        changelog,
+    })
+}
+
+/// Convert the unix timestamp emitted by build.rs to RFC 3339
+fn format_build_timestamp(raw: &str) -> String {
+    raw.parse::<i64>()
+        .ok()
+        .and_then(|secs| Utc.timestamp_opt(secs, 0).single())
+        .map(|dt| dt.to_rfc3339())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn changelog_parses_and_covers_current_version() {
+        let entries = changelog().unwrap();
+        assert!(!entries.is_empty());
+        assert_eq!(entries[0].version, env!("CARGO_PKG_VERSION"));
+    }
+
+    #[test]
+    fn app_info_reports_build_metadata() {
+        let info = app_info().unwrap();
+        assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+        assert!(!info.git_commit.is_empty());
+        assert!(!info.update_channel.is_empty());
+        assert!(info.changelog.len() <= RECENT_CHANGELOG_ENTRIES);
+    }
+
+    #[test]
+    fn format_build_timestamp_handles_invalid_input() {
+        assert_eq!(
+            format_build_timestamp("1700000000"),
+            "2023-11-14T22:13:20+00:00"
+        );
+        assert_eq!(format_build_timestamp("not-a-number"), "unknown");
+    }
+}
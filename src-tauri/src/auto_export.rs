@@ -0,0 +1,224 @@
+/*!
+ * Scheduled automatic exports.
+ *
+ * With an interval set in settings ([`AutoExportSettings`]), the app checks
+ * every [`CHECK_INTERVAL`] whether an export of the chats selected at the
+ * last manual export is due, and queues one in the background. Each run is
+ * incremental: it only exports messages sent since the last successful run
+ * started (`ExportOptions::since`; the first run exports everything), and
+ * either uploads or keeps its zip in the outbox to upload later.
+ *
+ * [`AutoExportState`], in `<app_local_data_dir>/auto_export.json`,
+ * remembers when runs started, and which queue job is the one running.
+ * Once that job is gone from the queue (cleared, or lost to a restart), how
+ * it ended is read from the job history (see job_history.rs). A run that
+ * failed (or never finished) is tried again after [`RETRY_INTERVAL`] rather
+ * than at every check.
+ */
+
+use std::{path::Path, time::Duration};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    export::{
+        queue::{ExportRequest, JobStatus},
+        ExportOptions,
+    },
+    job_history,
+    settings::Settings,
+};
+
+const STATE_FILENAME: &str = "auto_export.json";
+
+/// How often the app checks whether an export is due
+pub const CHECK_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+/// How long after a failed run the next is tried
+pub const RETRY_INTERVAL: Duration = Duration::from_secs(6 * 60 * 60);
+
+/// When and how exports run by themselves
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AutoExportSettings {
+    /// Days between exports, 7 for weekly (`None` or 0 = off)
+    pub interval_days: Option<u32>,
+    /// Upload each export when it's done (else keep it in the outbox)
+    pub upload: bool,
+}
+
+/// Where the schedule is, kept between launches
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AutoExportState {
+    /// When the last successful run started; the next exports from here
+    pub last_export_at: Option<DateTime<Utc>>,
+    /// When the last run started, however it ended
+    pub last_attempt_at: Option<DateTime<Utc>>,
+    /// Queue job ID of the run in progress
+    pub running_job_id: Option<String>,
+}
+
+impl AutoExportState {
+    /// Read the state from `app_local_data_dir`; none yet if it's missing
+    /// or unreadable
+    pub fn load(app_local_data_dir: &Path) -> Self {
+        std::fs::read_to_string(app_local_data_dir.join(STATE_FILENAME))
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, app_local_data_dir: &Path) -> Result<(), String> {
+        std::fs::create_dir_all(app_local_data_dir)
+            .map_err(|e| format!("Failed to create {app_local_data_dir:?}: {e}"))?;
+        let contents = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize auto export state: {e}"))?;
+        std::fs::write(app_local_data_dir.join(STATE_FILENAME), contents)
+            .map_err(|e| format!("Failed to write auto export state: {e}"))
+    }
+
+    /// Whether a run should start at `now`
+    pub fn is_due(&self, settings: &AutoExportSettings, now: DateTime<Utc>) -> bool {
+        let Some(days) = settings.interval_days.filter(|days| *days > 0) else {
+            return false;
+        };
+        let elapsed = |at: Option<DateTime<Utc>>, wait: chrono::Duration| {
+            at.map_or(true, |at| now - at >= wait)
+        };
+        let retry_wait = chrono::Duration::from_std(RETRY_INTERVAL).unwrap();
+        self.running_job_id.is_none()
+            && elapsed(self.last_export_at, chrono::Duration::days(days.into()))
+            && elapsed(self.last_attempt_at, retry_wait)
+    }
+
+    /// The export for a run starting now: the chats selected at the last
+    /// manual export, since the last successful run; `None` without any
+    pub fn request(&self, settings: &Settings) -> Option<ExportRequest> {
+        if settings.last_selected_chat_ids.is_empty() {
+            return None;
+        }
+        Some(ExportRequest {
+            chat_ids: settings.last_selected_chat_ids.clone(),
+            options: ExportOptions {
+                since: self.last_export_at,
+                ..Default::default()
+            },
+            label: Some("Scheduled export".to_string()),
+            keep_in_outbox: !settings.auto_export.upload,
+            ..Default::default()
+        })
+    }
+
+    /// Record that a run started at `now` as queue job `job_id`
+    pub fn started(&mut self, job_id: String, now: DateTime<Utc>) {
+        self.last_attempt_at = Some(now);
+        self.running_job_id = Some(job_id);
+    }
+
+    /// Record that the running run ended; a successful one moves the next
+    /// run's start to when it began
+    pub fn finished(&mut self, succeeded: bool) {
+        if succeeded {
+            self.last_export_at = self.last_attempt_at;
+        }
+        self.running_job_id = None;
+    }
+
+    /// Record that the running run ended, for one whose queue job is gone:
+    /// it succeeded if the job history in `app_local_data_dir` says so
+    pub fn finished_from_history(&mut self, app_local_data_dir: &Path) -> Result<(), String> {
+        let Some(job_id) = &self.running_job_id else {
+            return Ok(());
+        };
+        let history = job_history::load(app_local_data_dir)?;
+        let succeeded = history
+            .iter()
+            .rev()
+            .find(|record| &record.export_id == job_id)
+            .is_some_and(|record| matches!(record.status, JobStatus::Completed { .. }));
+        self.finished(succeeded);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::job_history::JobRecord;
+
+    #[test]
+    fn runs_are_due_each_interval_and_retried_after_failures() {
+        let weekly = AutoExportSettings {
+            interval_days: Some(7),
+            upload: false,
+        };
+        let start = DateTime::parse_from_rfc3339("2024-03-01T09:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let days = |n| start + chrono::Duration::days(n);
+
+        let mut state = AutoExportState::default();
+        assert!(!state.is_due(&AutoExportSettings::default(), start));
+        assert!(state.is_due(&weekly, start));
+
+        state.started("job-1".to_string(), start);
+        assert!(!state.is_due(&weekly, days(8)));
+        state.finished(true);
+        assert_eq!(state.last_export_at, Some(start));
+        assert!(!state.is_due(&weekly, days(6)));
+        assert!(state.is_due(&weekly, days(7)));
+
+        // A failed run keeps the old start, and waits before retrying
+        state.started("job-2".to_string(), days(7));
+        state.finished(false);
+        assert_eq!(state.last_export_at, Some(start));
+        assert!(!state.is_due(&weekly, days(7) + chrono::Duration::hours(1)));
+        assert!(state.is_due(&weekly, days(7) + chrono::Duration::hours(6)));
+
+        let dir = TempDir::new().unwrap();
+        state.save(dir.path()).unwrap();
+        assert_eq!(AutoExportState::load(dir.path()), state);
+    }
+
+    #[test]
+    fn runs_whose_jobs_were_cleared_end_as_the_job_history_says() {
+        let dir = TempDir::new().unwrap();
+        let start = Utc::now();
+        let completed = JobStatus::Completed { results_url: None };
+        job_history::append(dir.path(), &JobRecord::new("job-1", completed, None)).unwrap();
+
+        let mut state = AutoExportState::default();
+        state.started("job-1".to_string(), start);
+        state.finished_from_history(dir.path()).unwrap();
+        assert_eq!(state.last_export_at, Some(start));
+        assert_eq!(state.running_job_id, None);
+
+        // Lost to a restart before it finished: no record
+        state.started("job-2".to_string(), start + chrono::Duration::days(7));
+        state.finished_from_history(dir.path()).unwrap();
+        assert_eq!(state.last_export_at, Some(start));
+        assert_eq!(state.running_job_id, None);
+    }
+
+    #[test]
+    fn runs_export_the_last_selection_since_the_last_success() {
+        let state = AutoExportState {
+            last_export_at: Some(Utc::now()),
+            ..AutoExportState::default()
+        };
+        assert!(state.request(&Settings::default()).is_none());
+
+        let settings = Settings {
+            last_selected_chat_ids: vec![4, 2],
+            ..Settings::default()
+        };
+        let request = state.request(&settings).unwrap();
+        assert_eq!(request.chat_ids, [4, 2]);
+        assert_eq!(request.options.since, state.last_export_at);
+        assert!(request.keep_in_outbox);
+    }
+}
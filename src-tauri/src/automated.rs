@@ -0,0 +1,84 @@
+/*!
+ * Spotting chats with automated senders.
+ *
+ * Two-factor codes, delivery notices and marketing texts each get a chat of
+ * their own and crowd the people out of the chat list. [`is_automated`]
+ * flags a one-on-one chat as automated when the other side is
+ *
+ * - a short code (3–6 digits), as in [`ExclusionRule::ShortCode`];
+ * - an email sender whose name says it isn't read (`no-reply@`, `alerts@`
+ *   and the like); or
+ * - only ever talking: at least [`ONE_WAY_MIN_MESSAGES`] messages received
+ *   and none sent.
+ *
+ * Group chats are never flagged. The flag only hides chats in the list
+ * (`ChatListQuery::hide_automated`); unlike an exclusion rule it doesn't
+ * stop them being exported.
+ *
+ * [`ExclusionRule::ShortCode`]: crate::exclusions::ExclusionRule::ShortCode
+ */
+
+use crate::exclusions::is_short_code;
+
+/// Fewest received messages (with none sent) for a chat to count as one-way
+pub const ONE_WAY_MIN_MESSAGES: usize = 3;
+
+/// Email local parts (before the `@`, ignoring `-`, `_` and `.`) of senders
+/// nobody reads
+const AUTOMATED_EMAIL_NAMES: &[&str] = &[
+    "noreply",
+    "donotreply",
+    "notification",
+    "notifications",
+    "notify",
+    "alert",
+    "alerts",
+    "mailerdaemon",
+    "automated",
+];
+
+/// Whether a chat looks like it's with a machine rather than a person
+pub fn is_automated(
+    chat_identifier: &str,
+    participant_count: usize,
+    message_count: usize,
+    sent_count: usize,
+) -> bool {
+    if participant_count > 1 {
+        return false;
+    }
+    is_short_code(chat_identifier)
+        || is_automated_email(chat_identifier)
+        || (sent_count == 0 && message_count >= ONE_WAY_MIN_MESSAGES)
+}
+
+fn is_automated_email(identifier: &str) -> bool {
+    let Some((name, _domain)) = identifier.split_once('@') else {
+        return false;
+    };
+    let name = name
+        .chars()
+        .filter(|c| !matches!(c, '-' | '_' | '.'))
+        .collect::<String>()
+        .to_lowercase();
+    AUTOMATED_EMAIL_NAMES.contains(&name.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flags_short_codes_no_reply_senders_and_one_way_chats() {
+        assert!(is_automated("72345", 1, 1, 1));
+        assert!(is_automated("No-Reply@example.com", 1, 1, 0));
+        assert!(is_automated("do_not_reply@bank.example", 1, 1, 0));
+        assert!(is_automated("+15551234567", 1, ONE_WAY_MIN_MESSAGES, 0));
+
+        assert!(!is_automated("alice@example.com", 1, 1, 0));
+        assert!(!is_automated("+15551234567", 1, ONE_WAY_MIN_MESSAGES, 1));
+        assert!(!is_automated("+15551234567", 1, 2, 0));
+        // A group chat is people, whatever its identifier
+        assert!(!is_automated("72345", 3, 10, 0));
+    }
+}
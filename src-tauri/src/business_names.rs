@@ -0,0 +1,156 @@
+/*!
+ * Names of businesses messaged through Apple Messages for Business.
+ *
+ * A business's handle is an opaque `urn:biz:…` ID that no contact can
+ * match (`phone_keys` skips it), so its chats showed the URN. Messages
+ * keeps the brand's name with the business's chats instead: as the chat's
+ * `display_name`, or in its `properties` plist, under a key like
+ * `brandName` or in a brand dictionary's `name`. [`load`] reads those, and
+ * [`fill_names`] names the business handles in a participants map, which
+ * names the chat in the list and its messages' sender in the export.
+ */
+
+use std::{collections::HashMap, io::Cursor};
+
+use plist::Value;
+use rusqlite::Connection;
+
+use crate::contacts::Name;
+
+/// Prefix of a business handle's ID
+const BUSINESS_PREFIX: &str = "urn:biz:";
+
+/// Brand name of each business handle ID with a chat that names it
+pub fn load(db: &Connection) -> HashMap<String, String> {
+    let mut names = HashMap::new();
+    let Ok(mut stmt) = db.prepare(
+        "SELECT chat_identifier, display_name, properties FROM chat
+         WHERE chat_identifier LIKE 'urn:biz:%'",
+    ) else {
+        return names;
+    };
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, Option<String>>(1)?,
+            row.get::<_, Option<Vec<u8>>>(2)?,
+        ))
+    });
+    let Ok(rows) = rows else {
+        return names;
+    };
+    for (urn, display_name, properties) in rows.flatten() {
+        let brand = display_name
+            .filter(|name| !name.trim().is_empty())
+            .or_else(|| {
+                let plist = Value::from_reader(Cursor::new(properties?)).ok()?;
+                find_brand_name(&plist, false)
+            });
+        if let Some(brand) = brand {
+            names.entry(urn).or_insert_with(|| brand.trim().to_string());
+        }
+    }
+    names
+}
+
+/// Name the business handles in `participants_map` that no contact named
+pub(crate) fn fill_names(
+    participants_map: &mut HashMap<i32, Name>,
+    brands: &HashMap<String, String>,
+) {
+    if brands.is_empty() {
+        return;
+    }
+    for name in participants_map.values_mut() {
+        if !name.full.is_empty() {
+            continue;
+        }
+        let brand = name
+            .details
+            .split_whitespace()
+            .filter(|id| id.starts_with(BUSINESS_PREFIX))
+            .find_map(|urn| brands.get(urn));
+        if let Some(brand) = brand.and_then(|brand| Name::from_opt(Some(brand.clone()), None)) {
+            name.first = brand.first;
+            name.full = brand.full;
+        }
+    }
+}
+
+/// A brand name anywhere in a chat's properties: a `brandName`-like key,
+/// or `name` inside a brand or business dictionary
+fn find_brand_name(value: &Value, in_brand: bool) -> Option<String> {
+    match value {
+        Value::Dictionary(dict) => dict.iter().find_map(|(key, value)| {
+            let key = key.to_lowercase();
+            let names_brand = key.contains("brand") || key.contains("business");
+            match value.as_string().map(str::trim) {
+                Some("") => None,
+                Some(name) if (names_brand || in_brand) && key.ends_with("name") => {
+                    Some(name.to_string())
+                }
+                Some(_) => None,
+                None => find_brand_name(value, in_brand || names_brand),
+            }
+        }),
+        Value::Array(items) => items
+            .iter()
+            .find_map(|item| find_brand_name(item, in_brand)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use plist::Dictionary;
+
+    use super::*;
+    use crate::test_fixtures::{ChatBuilder, TestIMessageDb};
+
+    fn properties(brand: &str) -> Vec<u8> {
+        let mut info = Dictionary::new();
+        info.insert("name".into(), brand.into());
+        let mut plist = Dictionary::new();
+        plist.insert("shouldForceToSMS".into(), false.into());
+        plist.insert("CKBrandInfo".into(), Value::Dictionary(info));
+        let mut bytes = Vec::new();
+        Value::Dictionary(plist)
+            .to_writer_binary(&mut bytes)
+            .unwrap();
+        bytes
+    }
+
+    #[test]
+    fn business_handles_get_the_brand_name() {
+        let mut db = TestIMessageDb::new().unwrap();
+        db.conn()
+            .execute("ALTER TABLE chat ADD COLUMN properties BLOB", [])
+            .unwrap();
+        let airline = db.chat(ChatBuilder::new("urn:biz:1111-air")).unwrap();
+        db.conn()
+            .execute(
+                "UPDATE chat SET properties = ?1 WHERE ROWID = ?2",
+                (properties("Delta Air Lines"), airline),
+            )
+            .unwrap();
+        db.chat(ChatBuilder::new("urn:biz:2222-bank").display_name("Example Bank"))
+            .unwrap();
+        db.chat(ChatBuilder::new("urn:biz:3333-unknown")).unwrap();
+
+        let brands = load(db.conn());
+        assert_eq!(brands["urn:biz:1111-air"], "Delta Air Lines");
+        assert_eq!(brands["urn:biz:2222-bank"], "Example Bank");
+        assert!(!brands.contains_key("urn:biz:3333-unknown"));
+
+        let mut participants_map = HashMap::from([
+            (1, Name::from_details("urn:biz:1111-air")),
+            (2, Name::from_details("urn:biz:3333-unknown")),
+        ]);
+        fill_names(&mut participants_map, &brands);
+        assert_eq!(participants_map[&1].get_display_name(), "Delta Air Lines");
+        assert_eq!(
+            participants_map[&2].get_display_name(),
+            "urn:biz:3333-unknown"
+        );
+    }
+}
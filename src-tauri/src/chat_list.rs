@@ -0,0 +1,170 @@
+/*!
+ * Sorting, filtering and paging the chat list.
+ *
+ * [`crate::list_chats`] reads every chat, newest first. With thousands of
+ * chats that is a large payload for the frontend to hold and render, so
+ * the `list_chats` command takes a [`ChatListQuery`] and returns one
+ * [`ChatPage`] of the result, with the number of chats matching the filter
+ * for the pager. The filter is a case-insensitive match on the name or
+ * identifier, as in `ctm-cli list-chats --filter`; `hide_automated` drops
+ * the chats flagged `automated` as well. `group_by_contact` first folds
+ * each person's chats into one entry (see `chat_merge::group_by_contact`),
+ * whose `chat_ids` lists the threads behind it.
+ */
+
+use std::cmp::Reverse;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{chat_merge, ChatInfo};
+
+/// Order of the chat list
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename_all = "snake_case")]
+pub enum ChatSort {
+    /// Most recent message first
+    #[default]
+    Recency,
+    /// By display name, A to Z
+    Name,
+    /// Most messages first
+    MessageCount,
+}
+
+/// Which chats to return, in what order
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChatListQuery {
+    pub sort: ChatSort,
+    /// Only chats whose name or identifier contains this (any case)
+    pub filter: Option<String>,
+    /// Leave out short codes, no-reply senders and other automated chats
+    pub hide_automated: bool,
+    /// One entry per contact, across services and handles
+    pub group_by_contact: bool,
+    /// Chats to skip, after filtering and sorting
+    pub offset: usize,
+    /// Most chats to return; `None` returns the rest
+    pub limit: Option<usize>,
+}
+
+/// One page of the chat list
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatPage {
+    pub chats: Vec<ChatInfo>,
+    /// Chats matching the filter, across all pages
+    pub total_count: usize,
+}
+
+impl ChatListQuery {
+    /// Filter, sort and page `chats`, which come newest first from
+    /// `list_chats`
+    pub fn apply(&self, mut chats: Vec<ChatInfo>) -> ChatPage {
+        if self.group_by_contact {
+            chats = chat_merge::group_by_contact(chats);
+        }
+        if self.hide_automated {
+            chats.retain(|chat| !chat.automated);
+        }
+        if let Some(filter) = self.filter.as_deref().filter(|f| !f.trim().is_empty()) {
+            let filter = filter.trim().to_lowercase();
+            chats.retain(|chat| {
+                chat.display_name.to_lowercase().contains(&filter)
+                    || chat.chat_identifier.to_lowercase().contains(&filter)
+            });
+        }
+
+        // Stable sorts, so ties stay newest first
+        match self.sort {
+            ChatSort::Recency => {}
+            ChatSort::Name => chats.sort_by_cached_key(|chat| chat.display_name.to_lowercase()),
+            ChatSort::MessageCount => chats.sort_by_key(|chat| Reverse(chat.message_count)),
+        }
+
+        let total_count = chats.len();
+        let chats = chats
+            .into_iter()
+            .skip(self.offset)
+            .take(self.limit.unwrap_or(usize::MAX))
+            .collect();
+        ChatPage { chats, total_count }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chat(id: i32, display_name: &str, message_count: usize) -> ChatInfo {
+        ChatInfo {
+            id,
+            chat_ids: vec![id],
+            display_name: display_name.to_string(),
+            chat_identifier: format!("+1555000{id:04}"),
+            service: "iMessage".to_string(),
+            participant_count: 1,
+            participants: Vec::new(),
+            message_count,
+            last_message_date: String::new(),
+            last_message_preview: None,
+            excluded: false,
+            automated: false,
+        }
+    }
+
+    fn ids(page: &ChatPage) -> Vec<i32> {
+        page.chats.iter().map(|chat| chat.id).collect()
+    }
+
+    #[test]
+    fn chats_are_filtered_sorted_and_paged() {
+        let chats = vec![
+            chat(1, "bob", 5),
+            chat(2, "Alice", 50),
+            chat(3, "Carol", 5),
+            chat(4, "Alice & Bob", 1),
+        ];
+
+        let page = ChatListQuery::default().apply(chats.clone());
+        assert_eq!((ids(&page), page.total_count), (vec![1, 2, 3, 4], 4));
+
+        let by_name = ChatListQuery {
+            sort: ChatSort::Name,
+            ..Default::default()
+        };
+        assert_eq!(ids(&by_name.apply(chats.clone())), [2, 4, 1, 3]);
+
+        let page = ChatListQuery {
+            sort: ChatSort::MessageCount,
+            offset: 1,
+            limit: Some(2),
+            ..Default::default()
+        }
+        .apply(chats.clone());
+        assert_eq!((ids(&page), page.total_count), (vec![1, 3], 4));
+
+        let page = ChatListQuery {
+            filter: Some(" BOB".to_string()),
+            limit: Some(1),
+            ..Default::default()
+        }
+        .apply(chats.clone());
+        assert_eq!((ids(&page), page.total_count), (vec![1], 2));
+
+        let past_the_end = ChatListQuery {
+            offset: 10,
+            ..Default::default()
+        };
+        assert_eq!(past_the_end.apply(chats.clone()).chats.len(), 0);
+
+        let mut chats = chats;
+        chats[2].automated = true;
+        let page = ChatListQuery {
+            hide_automated: true,
+            ..Default::default()
+        }
+        .apply(chats);
+        assert_eq!((ids(&page), page.total_count), (vec![1, 2, 4], 3));
+    }
+}
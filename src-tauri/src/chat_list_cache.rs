@@ -0,0 +1,174 @@
+/*!
+ * On-disk cache of the chat list.
+ *
+ * Listing chats reads every chat, handle and message count and builds the
+ * contacts index, which takes seconds on a large database, and the
+ * selection screen lists them every time it opens. The finished
+ * `Vec<ChatInfo>` is saved to `<app_local_data_dir>/chat_list_cache.json`
+ * with a [`CacheKey`]: the size and modification time of chat.db and its
+ * WAL, and of each contacts database, plus the exclusion rules and app
+ * version. The cached list is used while the key still matches; any write
+ * to those files, or a rule change, makes the next listing read the
+ * database again. `refresh` skips the cache regardless.
+ */
+
+use std::{
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    contacts, db_snapshot::default_chat_db_path, exclusions::ExclusionRule,
+    list_chats_with_progress, ChatInfo, ChatListStage,
+};
+
+const CACHE_FILENAME: &str = "chat_list_cache.json";
+
+/// SQLite's write-ahead log, which takes a live database's recent writes
+const WAL_SUFFIX: &str = "-wal";
+
+/// Size and modification time of one source file; both `None` when the
+/// file is missing or unreadable
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct FileStamp {
+    path: PathBuf,
+    size: Option<u64>,
+    modified_ms: Option<u64>,
+}
+
+impl FileStamp {
+    fn of(path: PathBuf) -> Self {
+        let meta = std::fs::metadata(&path).ok();
+        let modified = meta.as_ref().and_then(|meta| meta.modified().ok());
+        Self {
+            size: meta.map(|meta| meta.len()),
+            modified_ms: modified
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map(|since| since.as_millis() as u64),
+            path,
+        }
+    }
+}
+
+/// What a cached chat list was computed from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheKey {
+    files: Vec<FileStamp>,
+    exclusion_rules: Vec<ExclusionRule>,
+    app_version: String,
+}
+
+impl CacheKey {
+    /// The key for listing `db_path` with names from `contacts_paths`
+    pub fn new(db_path: &Path, contacts_paths: &[PathBuf], rules: &[ExclusionRule]) -> Self {
+        let files = std::iter::once(db_path)
+            .chain(contacts_paths.iter().map(PathBuf::as_path))
+            .flat_map(|path| [path.to_path_buf(), wal_path(path)])
+            .map(FileStamp::of)
+            .collect();
+        Self {
+            files,
+            exclusion_rules: rules.to_vec(),
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+        }
+    }
+}
+
+fn wal_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(WAL_SUFFIX);
+    PathBuf::from(name)
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedChatList {
+    key: CacheKey,
+    chats: Vec<ChatInfo>,
+}
+
+/// The cached chat list in `dir`, if it was computed under `key`
+pub fn load(dir: &Path, key: &CacheKey) -> Option<Vec<ChatInfo>> {
+    let contents = std::fs::read(dir.join(CACHE_FILENAME)).ok()?;
+    let cached: CachedChatList = serde_json::from_slice(&contents).ok()?;
+    (cached.key == *key).then_some(cached.chats)
+}
+
+/// Save `chats`, computed under `key`, to the cache in `dir`
+pub fn store(dir: &Path, key: &CacheKey, chats: &[ChatInfo]) -> Result<(), String> {
+    std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {dir:?}: {e}"))?;
+    let cached = CachedChatList {
+        key: key.clone(),
+        chats: chats.to_vec(),
+    };
+    let json =
+        serde_json::to_vec(&cached).map_err(|e| format!("Failed to encode chat list: {e}"))?;
+    let path = dir.join(CACHE_FILENAME);
+    std::fs::write(&path, json).map_err(|e| format!("Failed to write {path:?}: {e}"))
+}
+
+/// [`list_chats_with_progress`] through the cache in `cache_dir`: the
+/// cached list when it is still current and `refresh` is false, otherwise
+/// a fresh listing, which is then cached
+pub fn list_chats_cached(
+    cache_dir: &Path,
+    custom_db_path: Option<&Path>,
+    exclusion_rules: &[ExclusionRule],
+    refresh: bool,
+    on_progress: impl Fn(ChatListStage),
+) -> Result<Vec<ChatInfo>, String> {
+    let db_path = custom_db_path.map_or_else(default_chat_db_path, Path::to_path_buf);
+    let key = CacheKey::new(&db_path, &contacts::source_paths(), exclusion_rules);
+    if !refresh {
+        if let Some(chats) = load(cache_dir, &key) {
+            tracing::debug!("[list_chats] Using {} cached chats", chats.len());
+            return Ok(chats);
+        }
+    }
+
+    let chats = list_chats_with_progress(custom_db_path, exclusion_rules, on_progress)?;
+    if let Err(e) = store(cache_dir, &key, &chats) {
+        tracing::warn!("[list_chats] Failed to cache the chat list: {e}");
+    }
+    Ok(chats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{ChatBuilder, TestIMessageDb};
+
+    #[test]
+    fn cached_list_is_used_until_the_database_changes() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let db_path = dir.path().join("chat.db");
+        let mut db = TestIMessageDb::new().unwrap();
+        db.chat(ChatBuilder::new("+15551234567")).unwrap();
+        db.save_to(&db_path).unwrap();
+        let cache_dir = dir.path().join("cache");
+        let list = |refresh| {
+            list_chats_cached(&cache_dir, Some(&db_path), &[], refresh, |_| {})
+                .unwrap()
+                .len()
+        };
+
+        assert_eq!(list(false), 1);
+        let key_now = || CacheKey::new(&db_path, &contacts::source_paths(), &[]);
+        let key = key_now();
+        assert!(load(&cache_dir, &key).is_some());
+
+        // A stale list is served while the key still matches...
+        store(&cache_dir, &key, &[]).unwrap();
+        assert_eq!(list(false), 0);
+        assert_eq!(list(true), 1);
+
+        // ...but not once the database was written to
+        store(&cache_dir, &key, &[]).unwrap();
+        db.chat(ChatBuilder::new("+15557654321")).unwrap();
+        std::fs::remove_file(&db_path).unwrap();
+        db.save_to(&db_path).unwrap();
+        assert_ne!(key_now(), key);
+        assert_eq!(list(false), 2);
+    }
+}
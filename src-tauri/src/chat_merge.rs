@@ -0,0 +1,199 @@
+/*!
+ * Merging of duplicate chats for the same people.
+ *
+ * Messages keeps separate chats per service and per handle, so the same
+ * person often shows up twice (an SMS thread and an iMessage thread, or an
+ * old and new phone number that the contacts index resolves to one person).
+ * `list_chats` folds these into a single [`ChatInfo`] keyed by the set of
+ * deduped participant handles; `ChatInfo::chat_ids` lists the underlying
+ * chat ROWIDs so the export can put them back together as one conversation
+ * (see `ExportOptions::chat_groups`).
+ *
+ * Chats with a custom display name are never merged: a named group is a
+ * deliberate, separate conversation even when the members are the same.
+ *
+ * [`group_by_contact`] goes further, for `ChatListQuery::group_by_contact`:
+ * one-on-one chats whose participant resolves to the same contact name are
+ * folded into one entry per person, even across handles the deduping kept
+ * apart (a phone number and an email address).
+ */
+
+use std::collections::{BTreeSet, HashMap};
+
+use crate::ChatInfo;
+
+/// Identity used to detect duplicates: the chat's deduped participant IDs
+pub type MergeKey = BTreeSet<i32>;
+
+/// Compute the merge key for a chat, or `None` if it must stay separate
+pub fn merge_key(
+    display_name: Option<&str>,
+    chat_participants: Option<&BTreeSet<i32>>,
+    deduped_handles: &HashMap<i32, i32>,
+) -> Option<MergeKey> {
+    if display_name.is_some_and(|name| !name.is_empty()) {
+        return None;
+    }
+    let key: MergeKey = chat_participants?
+        .iter()
+        .filter_map(|handle_id| deduped_handles.get(handle_id).copied())
+        .collect();
+    (!key.is_empty()).then_some(key)
+}
+
+/// Merge chats that share a merge key.
+///
+/// Input entries are `(chat, last_message_date, key)`. Within a group the
+/// most recent chat is kept as the primary (its ID, name and preview win),
+/// message counts are summed and services are combined ("iMessage + SMS").
+/// Output keeps the `(chat, last_message_date)` shape `list_chats` sorts by.
+pub fn merge_duplicate_chats(
+    entries: Vec<(ChatInfo, i64, Option<MergeKey>)>,
+) -> Vec<(ChatInfo, i64)> {
+    let mut merged: Vec<(ChatInfo, i64)> = Vec::with_capacity(entries.len());
+    let mut index_by_key: HashMap<MergeKey, usize> = HashMap::new();
+
+    for (chat, last_date, key) in entries {
+        let Some(key) = key else {
+            merged.push((chat, last_date));
+            continue;
+        };
+        match index_by_key.get(&key) {
+            Some(&index) => {
+                let (existing, existing_date) = &mut merged[index];
+                absorb(existing, existing_date, chat, last_date);
+            }
+            None => {
+                index_by_key.insert(key, merged.len());
+                merged.push((chat, last_date));
+            }
+        }
+    }
+
+    merged
+}
+
+/// One entry per resolved contact: one-on-one chats with the same contact
+/// name folded into the first, which `chats` (newest first, as from
+/// `list_chats`) makes the most recent. Group chats and chats whose
+/// participant has no contact name are left as they are.
+pub fn group_by_contact(chats: Vec<ChatInfo>) -> Vec<ChatInfo> {
+    let mut grouped: Vec<ChatInfo> = Vec::with_capacity(chats.len());
+    let mut index_by_name: HashMap<String, usize> = HashMap::new();
+
+    for chat in chats {
+        let resolved = chat.participant_count == 1 && chat.display_name != chat.chat_identifier;
+        if !resolved {
+            grouped.push(chat);
+            continue;
+        }
+        match index_by_name.get(&chat.display_name) {
+            Some(&index) => fold(&mut grouped[index], chat),
+            None => {
+                index_by_name.insert(chat.display_name.clone(), grouped.len());
+                grouped.push(chat);
+            }
+        }
+    }
+
+    grouped
+}
+
+/// Fold `other` into `primary`, swapping roles if `other` is more recent
+fn absorb(primary: &mut ChatInfo, primary_date: &mut i64, mut other: ChatInfo, other_date: i64) {
+    if other_date > *primary_date {
+        std::mem::swap(primary, &mut other);
+        *primary_date = other_date;
+    }
+    fold(primary, other);
+}
+
+/// Add `other`'s messages, chats and services to `primary`
+fn fold(primary: &mut ChatInfo, other: ChatInfo) {
+    primary.message_count += other.message_count;
+    primary.chat_ids.extend(other.chat_ids);
+    primary.excluded |= other.excluded;
+    // One side with a conversation means a person
+    primary.automated &= other.automated;
+    if !primary.service.split(" + ").any(|s| s == other.service) {
+        primary.service = format!("{} + {}", primary.service, other.service);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chat(id: i32, service: &str, message_count: usize) -> ChatInfo {
+        ChatInfo {
+            id,
+            chat_ids: vec![id],
+            display_name: "Alice".to_string(),
+            chat_identifier: format!("+1555000{id}"),
+            service: service.to_string(),
+            participant_count: 1,
+            participants: vec!["Alice".to_string()],
+            message_count,
+            last_message_date: String::new(),
+            last_message_preview: None,
+            excluded: false,
+            automated: false,
+        }
+    }
+
+    #[test]
+    fn merge_key_skips_named_and_empty_chats() {
+        let participants = BTreeSet::from([10, 11]);
+        let deduped = HashMap::from([(10, 1), (11, 1)]);
+
+        assert_eq!(
+            merge_key(None, Some(&participants), &deduped),
+            Some(BTreeSet::from([1]))
+        );
+        assert_eq!(merge_key(Some("Trip"), Some(&participants), &deduped), None);
+        assert_eq!(merge_key(None, None, &deduped), None);
+    }
+
+    #[test]
+    fn chats_with_same_key_merge_into_most_recent() {
+        let key = Some(BTreeSet::from([1]));
+        let merged = merge_duplicate_chats(vec![
+            (chat(1, "SMS", 10), 100, key.clone()),
+            (chat(2, "iMessage", 5), 200, key.clone()),
+            (chat(3, "iMessage", 7), 150, None),
+        ]);
+
+        assert_eq!(merged.len(), 2);
+        let (primary, last_date) = &merged[0];
+        assert_eq!(primary.id, 2);
+        assert_eq!(*last_date, 200);
+        assert_eq!(primary.message_count, 15);
+        assert_eq!(primary.chat_ids, vec![2, 1]);
+        assert_eq!(primary.service, "iMessage + SMS");
+        assert_eq!(merged[1].0.chat_ids, vec![3]);
+    }
+
+    #[test]
+    fn group_by_contact_folds_one_on_one_chats_by_name() {
+        let mut email = chat(2, "iMessage", 5);
+        email.chat_identifier = "alice@example.com".to_string();
+        let mut unresolved = chat(4, "SMS", 1);
+        unresolved.display_name = unresolved.chat_identifier.clone();
+        let mut group = chat(5, "iMessage", 3);
+        group.participant_count = 2;
+
+        let grouped = group_by_contact(vec![
+            chat(1, "SMS", 10),
+            email,
+            chat(3, "iMessage", 7),
+            unresolved,
+            group,
+        ]);
+
+        let ids: Vec<Vec<i32>> = grouped.iter().map(|c| c.chat_ids.clone()).collect();
+        assert_eq!(ids, [vec![1, 2, 3], vec![4], vec![5]]);
+        assert_eq!(grouped[0].id, 1);
+        assert_eq!(grouped[0].message_count, 22);
+        assert_eq!(grouped[0].service, "SMS + iMessage");
+    }
+}
@@ -0,0 +1,185 @@
+/*!
+ * Chat display names, resolved the same way everywhere a chat is shown:
+ * the chat list, search results, preflight warnings and the exported
+ * `ExportedChatMeta::name`.
+ *
+ * A custom group name wins. Otherwise a 1:1 chat is named after the
+ * contact, and an unnamed group lists its participants. Without a contact
+ * match the chat falls back to its identifier (phone, email or group ID).
+ */
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use imessage_database::tables::chat::Chat;
+
+use crate::contacts::Name;
+
+/// Participant names listed before the rest collapse into "+N more"
+const MAX_LISTED_PARTICIPANTS: usize = 4;
+
+/// Resolve participant names for a chat, using contacts if available.
+///
+/// Handles that dedupe to the same person are listed once. Beyond
+/// `MAX_LISTED_PARTICIPANTS` names, the remainder is summarized as a final
+/// "+N more" entry.
+pub fn resolve_participant_names(
+    chat_participants: Option<&BTreeSet<i32>>,
+    participants_map: &HashMap<i32, Name>,
+    deduped_handles: &HashMap<i32, i32>,
+) -> Vec<String> {
+    let Some(participant_ids) = chat_participants else {
+        return Vec::new();
+    };
+
+    let mut seen = HashSet::new();
+    let mut names: Vec<String> = participant_ids
+        .iter()
+        .filter_map(|handle_id| deduped_handles.get(handle_id))
+        .filter(|&&deduped_id| seen.insert(deduped_id))
+        .filter_map(|deduped_id| participants_map.get(deduped_id))
+        .map(|name| name.get_display_name().to_string())
+        .filter(|name| !name.is_empty())
+        .collect();
+
+    if names.len() > MAX_LISTED_PARTICIPANTS {
+        let remaining = names.len() - MAX_LISTED_PARTICIPANTS;
+        names.truncate(MAX_LISTED_PARTICIPANTS);
+        names.push(format!("+{remaining} more"));
+    }
+    names
+}
+
+/// Resolve a display name for a chat, using contacts if available
+pub fn resolve_chat_display_name(
+    chat: &Chat,
+    chat_participants: Option<&BTreeSet<i32>>,
+    participants_map: &HashMap<i32, Name>,
+    deduped_handles: &HashMap<i32, i32>,
+) -> String {
+    // If chat has a custom display_name, use it
+    if let Some(name) = chat.display_name.as_ref() {
+        if !name.is_empty() {
+            return name.clone();
+        }
+    }
+
+    // For 1:1 chats, try to resolve the participant's name
+    if let Some(participant_ids) = chat_participants {
+        if participant_ids.len() == 1 {
+            if let Some(&handle_id) = participant_ids.iter().next() {
+                // FIX: Translate handle_id to deduped_id before lookup
+                if let Some(&deduped_id) = deduped_handles.get(&handle_id) {
+                    if let Some(name) = participants_map.get(&deduped_id) {
+                        let display = name.get_display_name();
+                        if !display.is_empty() {
+                            return display.to_string();
+                        }
+                    }
+                }
+            }
+        }
+
+        // For unnamed group chats, list the participants instead of `chat1234`
+        if participant_ids.len() > 1 {
+            let names =
+                resolve_participant_names(chat_participants, participants_map, deduped_handles);
+            if !names.is_empty() {
+                return names.join(", ");
+            }
+        }
+    }
+
+    // Fallback to chat_identifier
+    chat.chat_identifier.clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use imessage_database::tables::{
+        chat_handle::ChatToHandle,
+        handle::Handle,
+        table::{Cacheable, Deduplicate},
+    };
+
+    use super::*;
+    use crate::contacts::{phone_keys, ContactsIndex};
+    use crate::test_fixtures::{ChatBuilder, HandleBuilder, TestIMessageDb};
+
+    #[test]
+    fn unnamed_group_chat_is_named_after_participants() {
+        let mut db = TestIMessageDb::new().unwrap();
+        let chat_id = db.chat(ChatBuilder::new("chat1234")).unwrap();
+        for i in 0..6 {
+            let handle = db
+                .handle(HandleBuilder::new(format!("+1555000000{i}")))
+                .unwrap();
+            db.chat_handle(chat_id, handle).unwrap();
+        }
+
+        let chats = Chat::cache(db.conn()).unwrap();
+        let handles = Handle::cache(db.conn()).unwrap();
+        let deduped_handles = Handle::dedupe(&handles);
+        let participants_map =
+            ContactsIndex::default().build_participants_map(&handles, &deduped_handles);
+        let chat_participants = ChatToHandle::cache(db.conn()).unwrap();
+        let participants = chat_participants.get(&chat_id);
+
+        let names = resolve_participant_names(participants, &participants_map, &deduped_handles);
+        assert_eq!(names.len(), MAX_LISTED_PARTICIPANTS + 1);
+        assert_eq!(names.last().map(String::as_str), Some("+2 more"));
+
+        let display_name = resolve_chat_display_name(
+            &chats[&chat_id],
+            participants,
+            &participants_map,
+            &deduped_handles,
+        );
+        assert!(display_name.starts_with("+15550000000, "));
+        assert!(display_name.ends_with(", +2 more"));
+    }
+
+    #[test]
+    fn direct_chat_is_named_after_the_contact_unless_renamed() {
+        let mut db = TestIMessageDb::new().unwrap();
+        let handle = db.handle(HandleBuilder::new("+15551234567")).unwrap();
+        let direct = db
+            .chat(ChatBuilder::new("iMessage;-;+15551234567"))
+            .unwrap();
+        let renamed = db
+            .chat(ChatBuilder::new("SMS;-;+15551234567").display_name("Mum"))
+            .unwrap();
+        for chat in [direct, renamed] {
+            db.chat_handle(chat, handle).unwrap();
+        }
+
+        let alice = Name {
+            first: "Alice".to_string(),
+            last: "Johnson".to_string(),
+            full: "Alice Johnson".to_string(),
+            details: String::new(),
+            handle_ids: HashSet::new(),
+            phone_label: None,
+        };
+        let index = phone_keys("+15551234567")
+            .into_iter()
+            .map(|key| (key, alice.clone()))
+            .collect();
+        let chats = Chat::cache(db.conn()).unwrap();
+        let handles = Handle::cache(db.conn()).unwrap();
+        let deduped_handles = Handle::dedupe(&handles);
+        let participants_map =
+            ContactsIndex::from_index(index).build_participants_map(&handles, &deduped_handles);
+        let chat_participants = ChatToHandle::cache(db.conn()).unwrap();
+        let name = |chat: i32| {
+            resolve_chat_display_name(
+                &chats[&chat],
+                chat_participants.get(&chat),
+                &participants_map,
+                &deduped_handles,
+            )
+        };
+
+        assert_eq!(name(direct), "Alice Johnson");
+        assert_eq!(name(renamed), "Mum");
+    }
+}
@@ -8,19 +8,50 @@
  *   cargo run --bin ctm-cli -- list-chats
  *   cargo run --bin ctm-cli -- list-chats --verbose
  *   cargo run --bin ctm-cli -- list-chats --limit 20
+ *   cargo run --bin ctm-cli -- contacts --filter smith --limit 20
+ *   cargo run --bin ctm-cli -- export --chat-ids 1,5,12 --output export.zip --meta trip="Italy 2024"
+ *   cargo run --bin ctm-cli -- export --chat-ids 1,5 --output export.zip --format csv
+ *   cargo run --bin ctm-cli -- import-telegram result.json --output export.zip
+ *   cargo run --bin ctm-cli -- diff-exports may.zip june.zip --messages
+ *   cargo run --bin ctm-cli -- diff may.zip june.zip --json
+ *   cargo run --bin ctm-cli -- merge mac.zip iphone.zip --output merged.zip
+ *   cargo run --bin ctm-cli -- validate-export old-export.zip
+ *   cargo run --bin ctm-cli -- jobs --metrics --limit 10
+ *   cargo run --bin ctm-cli -- --debug sql --query "SELECT COUNT(*) FROM message"
  */
 
-use clap::{Parser, Subcommand};
+mod cli_contacts;
+mod cli_export;
+
+use std::path::PathBuf;
+
+use chat_to_map_desktop::chat_list::{ChatListQuery, ChatSort};
+use clap::{Parser, Subcommand, ValueEnum};
 
 #[derive(Parser)]
 #[command(name = "ctm-cli")]
 #[command(about = "ChatToMap CLI - iMessage debugging tool")]
 #[command(version)]
 struct Cli {
+    /// Enable support/debug commands (e.g. `sql`)
+    #[arg(long, global = true)]
+    debug: bool,
+
+    /// Also read contact names from this vCard (.vcf) file
+    #[arg(long, global = true)]
+    contacts_vcf: Option<PathBuf>,
+
     #[command(subcommand)]
     command: Commands,
 }
 
+/// Output format for `sql`
+#[derive(Clone, Copy, ValueEnum)]
+enum SqlFormat {
+    Json,
+    Csv,
+}
+
 #[derive(Subcommand)]
 enum Commands {
     /// List all iMessage chats with contact resolution
@@ -37,59 +68,214 @@ enum Commands {
         #[arg(short, long)]
         filter: Option<String>,
 
+        /// Order: recency (default), name or message-count
+        #[arg(long, value_enum, default_value = "recency")]
+        sort: ChatSort,
+
+        /// Leave out short codes, no-reply senders and one-way chats
+        #[arg(long)]
+        hide_automated: bool,
+
+        /// One entry per contact, merging their SMS, iMessage and email chats
+        #[arg(long)]
+        group_by_contact: bool,
+
         /// Output as JSON
         #[arg(long)]
         json: bool,
     },
 
-    /// Show contacts index statistics
-    Contacts {
-        /// Show all contacts (verbose)
-        #[arg(short, long)]
-        verbose: bool,
-    },
+    /// Show the contacts index and how many iMessage handles it names
+    Contacts(cli_contacts::ContactsArgs),
 
     /// Check Full Disk Access permission
     CheckAccess,
+
+    /// Export selected chats to a zip (what the desktop app uploads)
+    Export(cli_export::ExportArgs),
+
+    /// Convert a Telegram Desktop JSON export (result.json) into an export zip
+    ImportTelegram {
+        /// Path to Telegram's result.json
+        path: PathBuf,
+
+        /// Write the export zip here (default: only print a summary)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Compare two export zips: chats added/removed, messages missing, new
+    /// or changed (matched by GUID), options
+    #[command(visible_alias = "diff")]
+    DiffExports {
+        /// Earlier export zip
+        before: PathBuf,
+
+        /// Later export zip
+        after: PathBuf,
+
+        /// List the messages missing from, new in or changed in each chat
+        #[arg(short, long)]
+        messages: bool,
+
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Merge two or more export zips into one, keeping each message once
+    Merge {
+        /// Export zips, in order of preference for chat names
+        #[arg(required = true, num_args = 2..)]
+        exports: Vec<PathBuf>,
+
+        /// Write the merged zip here
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// Write chat files in the first export's format rather than JSON
+        /// (what the server reads)
+        #[arg(long)]
+        keep_format: bool,
+    },
+
+    /// Check an export zip: manifest, checksums and every chat file
+    ValidateExport {
+        /// Export zip to check
+        path: PathBuf,
+
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// List the desktop app's recent export runs
+    Jobs {
+        /// Show throughput (messages/s decoded, MB/s compressed and uploaded)
+        #[arg(short, long)]
+        metrics: bool,
+
+        /// Only the most recent N runs
+        #[arg(short, long)]
+        limit: Option<usize>,
+
+        /// App data directory holding the history (default: the app's)
+        #[arg(long)]
+        data_dir: Option<PathBuf>,
+
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Run a read-only SQL query against chat.db (requires --debug)
+    #[command(hide = true)]
+    Sql {
+        /// SQL statement to run (must be read-only)
+        #[arg(short, long)]
+        query: String,
+
+        /// Database to query (default: the live chat.db, via a snapshot)
+        #[arg(long)]
+        db: Option<PathBuf>,
+
+        /// Maximum number of rows to print
+        #[arg(short, long, default_value_t = chat_to_map_desktop::sql_query::DEFAULT_ROW_LIMIT)]
+        limit: usize,
+
+        /// Output format
+        #[arg(long, value_enum, default_value = "json")]
+        format: SqlFormat,
+    },
 }
 
 fn main() {
     let cli = Cli::parse();
 
+    // Library diagnostics go to stderr; set CTM_LOG=debug for more detail
+    if let Err(e) = chat_to_map_desktop::logging::init(
+        None,
+        chat_to_map_desktop::logging::effective_level(None),
+    ) {
+        eprintln!("{e}");
+    }
+    if let Some(path) = cli.contacts_vcf {
+        chat_to_map_desktop::contacts::use_vcard(path);
+    }
+
     match cli.command {
         Commands::ListChats {
             verbose,
             limit,
             filter,
+            sort,
+            hide_automated,
+            group_by_contact,
             json,
         } => {
-            cmd_list_chats(verbose, limit, filter, json);
-        }
-        Commands::Contacts { verbose } => {
-            cmd_contacts(verbose);
+            let query = ChatListQuery {
+                sort,
+                filter,
+                hide_automated,
+                group_by_contact,
+                limit,
+                ..Default::default()
+            };
+            cmd_list_chats(verbose, &query, json);
         }
+        Commands::Contacts(args) => cli_contacts::cmd_contacts(args),
         Commands::CheckAccess => {
             cmd_check_access();
         }
+        Commands::Export(args) => cli_export::cmd_export(args),
+        Commands::ImportTelegram { path, output } => {
+            cmd_import_telegram(&path, output.as_deref());
+        }
+        Commands::DiffExports {
+            before,
+            after,
+            messages,
+            json,
+        } => {
+            cmd_diff_exports(&before, &after, messages, json);
+        }
+        Commands::Merge {
+            exports,
+            output,
+            keep_format,
+        } => {
+            cmd_merge(&exports, &output, keep_format);
+        }
+        Commands::ValidateExport { path, json } => {
+            cmd_validate_export(&path, json);
+        }
+        Commands::Jobs {
+            metrics,
+            limit,
+            data_dir,
+            json,
+        } => {
+            cmd_jobs(metrics, limit, data_dir, json);
+        }
+        Commands::Sql {
+            query,
+            db,
+            limit,
+            format,
+        } => {
+            if !cli.debug {
+                eprintln!("Error: the sql command requires --debug");
+                std::process::exit(2);
+            }
+            cmd_sql(&query, db.as_deref(), limit, format);
+        }
     }
 }
 
-fn cmd_list_chats(verbose: bool, limit: Option<usize>, filter: Option<String>, json: bool) {
-    match chat_to_map_desktop::list_chats(None) {
-        Ok(mut chats) => {
-            // Apply filter if provided
-            if let Some(ref filter_str) = filter {
-                let filter_lower = filter_str.to_lowercase();
-                chats.retain(|c| {
-                    c.display_name.to_lowercase().contains(&filter_lower)
-                        || c.chat_identifier.to_lowercase().contains(&filter_lower)
-                });
-            }
-
-            // Apply limit if provided
-            if let Some(limit) = limit {
-                chats.truncate(limit);
-            }
+fn cmd_list_chats(verbose: bool, query: &ChatListQuery, json: bool) {
+    match chat_to_map_desktop::list_chats(None, &[]) {
+        Ok(chats) => {
+            let chats = query.apply(chats).chats;
 
             if json {
                 println!("{}", serde_json::to_string_pretty(&chats).unwrap());
@@ -117,12 +303,17 @@ fn cmd_list_chats(verbose: bool, limit: Option<usize>, filter: Option<String>, j
                         chat.message_count
                     );
                 } else {
+                    let threads = match chat.chat_ids.len() {
+                        1 => String::new(),
+                        n => format!("{n} threads, "),
+                    };
                     println!(
-                        "{:3}. {}{} ({}) - {} messages",
+                        "{:3}. {}{} ({}) - {}{} messages",
                         i + 1,
                         chat.display_name,
                         resolved,
                         chat.service,
+                        threads,
                         chat.message_count
                     );
                 }
@@ -140,48 +331,144 @@ fn cmd_list_chats(verbose: bool, limit: Option<usize>, filter: Option<String>, j
     }
 }
 
-fn cmd_contacts(verbose: bool) {
-    use chat_to_map_desktop::contacts::ContactsIndex;
+fn cmd_check_access() {
+    use chat_to_map_desktop::{
+        db_snapshot::default_chat_db_path,
+        permissions::{check_full_disk_access, FullDiskAccess},
+    };
 
-    match ContactsIndex::build(None) {
-        Ok(index) => {
-            println!("Contacts index: {} entries", index.len());
+    let db_path = default_chat_db_path();
+    println!("iMessage database path: {:?}", db_path);
 
-            if verbose {
-                println!("\nNote: Verbose contact listing not yet implemented");
-                println!("The index maps phone numbers and emails to contact names.");
-            }
+    let access = check_full_disk_access(&db_path);
+    println!("Status: {}", access.describe());
+    if access != FullDiskAccess::Granted {
+        std::process::exit(1);
+    }
+}
+
+fn cmd_import_telegram(path: &std::path::Path, output: Option<&std::path::Path>) {
+    use chat_to_map_desktop::sources::telegram;
+
+    let started = std::time::Instant::now();
+    let chats = match telegram::parse_telegram_export(path) {
+        Ok(chats) => chats,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
         }
+    };
+    let decode = started.elapsed();
+
+    println!("Found {} Telegram chats\n", chats.len());
+    for (i, chat) in chats.iter().enumerate() {
+        println!(
+            "{:3}. {} ({}) - {} messages",
+            i + 1,
+            chat.meta.name,
+            chat.meta.identifier,
+            chat.meta.message_count
+        );
+    }
+
+    let Some(output) = output else {
+        return;
+    };
+    println!();
+    let result = telegram::export_chats(&chats).map(|mut result| {
+        result.metrics.record_decode(decode);
+        result
+    });
+    cli_export::save_export(result, output);
+}
+
+fn cmd_diff_exports(before: &std::path::Path, after: &std::path::Path, messages: bool, json: bool) {
+    let diff = match chat_to_map_desktop::export::diff::diff_exports(before, after) {
+        Ok(diff) => diff,
         Err(e) => {
-            eprintln!("Error building contacts index: {}", e);
+            eprintln!("Error: {}", e);
             std::process::exit(1);
         }
+    };
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&diff).unwrap());
+        return;
     }
+    print!("{}", diff.to_text(messages));
 }
 
-fn cmd_check_access() {
-    use imessage_database::{tables::table::get_connection, util::dirs::default_db_path};
+fn cmd_merge(exports: &[PathBuf], output: &std::path::Path, keep_format: bool) {
+    let format = (!keep_format).then_some(chat_to_map_desktop::export::ExportFormat::Json);
+    let merged = chat_to_map_desktop::export::merge::merge_exports(exports, format).map(|merged| {
+        if merged.duplicate_messages > 0 {
+            println!(
+                "Left out {} messages found in more than one export",
+                merged.duplicate_messages
+            );
+        }
+        merged.result
+    });
+    cli_export::save_export(merged, output);
+}
 
-    let db_path = default_db_path();
-    println!("iMessage database path: {:?}", db_path);
+fn cmd_validate_export(path: &std::path::Path, json: bool) {
+    let report = match chat_to_map_desktop::export::verify::verify_export(path) {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
 
-    if !db_path.exists() {
-        println!("Status: Database file not found");
-        println!("This may be a non-macOS system or Messages has never been used.");
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report).unwrap());
+    } else {
+        print!("{}", report.to_text());
+    }
+    if !report.is_valid() {
         std::process::exit(1);
     }
+}
 
-    match get_connection(&db_path) {
-        Ok(_) => {
-            println!("Status: Full Disk Access GRANTED");
-            println!("The CLI can read the iMessage database.");
+fn cmd_jobs(metrics: bool, limit: Option<usize>, data_dir: Option<PathBuf>, json: bool) {
+    use chat_to_map_desktop::job_history;
+
+    let records = match job_history::load_recent(data_dir.as_deref(), limit) {
+        Ok(records) => records,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&records).unwrap());
+        return;
+    }
+    print!("{}", job_history::to_text(&records, metrics));
+}
+
+fn cmd_sql(query: &str, db: Option<&std::path::Path>, limit: usize, format: SqlFormat) {
+    use chat_to_map_desktop::{db_snapshot::open_chat_db, sql_query};
+
+    let result =
+        open_chat_db(db).and_then(|chat_db| sql_query::run_query(&chat_db.conn, query, limit));
+    match result {
+        Ok(result) => {
+            match format {
+                SqlFormat::Json => println!(
+                    "{}",
+                    serde_json::to_string_pretty(&sql_query::to_json(&result)).unwrap()
+                ),
+                SqlFormat::Csv => print!("{}", sql_query::to_csv(&result)),
+            }
+            if result.truncated {
+                eprintln!("(truncated to {} rows; use --limit to see more)", limit);
+            }
         }
         Err(e) => {
-            println!("Status: Full Disk Access DENIED");
-            println!("Error: {}", e);
-            println!("\nTo grant access:");
-            println!("1. Open System Preferences > Privacy & Security > Full Disk Access");
-            println!("2. Add your terminal application (Terminal, iTerm2, etc.)");
+            eprintln!("Error: {}", e);
             std::process::exit(1);
         }
     }
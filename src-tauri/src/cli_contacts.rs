@@ -0,0 +1,173 @@
+/*!
+ * `ctm-cli contacts`: what the contacts index holds, and how many of the
+ * iMessage handles it names
+ */
+
+use std::path::PathBuf;
+
+use chat_to_map_desktop::{contacts::ContactsIndex, db_snapshot::open_chat_db};
+use imessage_database::tables::{handle::Handle, table::Cacheable};
+use serde::Serialize;
+
+/// Unresolved handles listed as examples in the text output
+const UNRESOLVED_EXAMPLES: usize = 10;
+
+#[derive(clap::Args)]
+pub struct ContactsArgs {
+    /// List the index entries (phone/email key → name)
+    #[arg(short, long)]
+    verbose: bool,
+
+    /// Only list entries whose key or name contains this (case-insensitive;
+    /// implies --verbose)
+    #[arg(long)]
+    filter: Option<String>,
+
+    /// List at most this many entries (implies --verbose)
+    #[arg(long)]
+    limit: Option<usize>,
+
+    /// Output as JSON
+    #[arg(long)]
+    json: bool,
+
+    /// CSV of `identifier,name` overrides (default: name_overrides.csv
+    /// in the app data directory)
+    #[arg(long)]
+    overrides: Option<PathBuf>,
+}
+
+#[derive(Serialize)]
+struct Entry<'a> {
+    key: &'a str,
+    name: &'a str,
+    phone_label: Option<&'a str>,
+}
+
+/// How many iMessage handles the index names
+#[derive(Serialize)]
+struct HandleStats {
+    total: usize,
+    resolved: usize,
+    unresolved: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct Report<'a> {
+    entries_total: usize,
+    entries: Vec<Entry<'a>>,
+    /// `None` when chat.db can't be read (e.g. no Full Disk Access)
+    handles: Option<HandleStats>,
+}
+
+pub fn cmd_contacts(args: ContactsArgs) {
+    let index = match ContactsIndex::build_with_overrides(None, args.overrides.as_deref()) {
+        Ok(index) => index,
+        Err(e) => {
+            eprintln!("Error building contacts index: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let listed = args.verbose || args.json || args.filter.is_some() || args.limit.is_some();
+    let entries = if listed {
+        matching_entries(&index, args.filter.as_deref(), args.limit)
+    } else {
+        Vec::new()
+    };
+    let handles = match handle_stats(&index) {
+        Ok(stats) => Some(stats),
+        Err(e) => {
+            eprintln!("Skipping iMessage handle stats: {}", e);
+            None
+        }
+    };
+    let report = Report {
+        entries_total: index.len(),
+        entries,
+        handles,
+    };
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&report).unwrap());
+        return;
+    }
+
+    println!("Contacts index: {} entries", report.entries_total);
+    for entry in &report.entries {
+        match entry.phone_label {
+            Some(label) => println!("  {} → {} ({})", entry.key, entry.name, label),
+            None => println!("  {} → {}", entry.key, entry.name),
+        }
+    }
+    if let Some(stats) = &report.handles {
+        println!(
+            "\niMessage handles: {} of {} resolve to a contact",
+            stats.resolved, stats.total
+        );
+        for handle in stats.unresolved.iter().take(UNRESOLVED_EXAMPLES) {
+            println!("  unresolved: {}", handle);
+        }
+        if stats.unresolved.len() > UNRESOLVED_EXAMPLES {
+            println!(
+                "  ... and {} more (--json lists them all)",
+                stats.unresolved.len() - UNRESOLVED_EXAMPLES
+            );
+        }
+    }
+    if !listed {
+        println!("\nUse --verbose to list entries, --filter/--limit to narrow them");
+    }
+}
+
+/// Index entries sorted by key, narrowed by `filter` and `limit`
+fn matching_entries<'a>(
+    index: &'a ContactsIndex,
+    filter: Option<&str>,
+    limit: Option<usize>,
+) -> Vec<Entry<'a>> {
+    let filter = filter.map(str::to_lowercase);
+    let mut entries: Vec<Entry> = index
+        .entries()
+        .filter(|(key, name)| {
+            filter.as_deref().map_or(true, |filter| {
+                key.to_lowercase().contains(filter) || name.full.to_lowercase().contains(filter)
+            })
+        })
+        .map(|(key, name)| Entry {
+            key,
+            name: &name.full,
+            phone_label: name.phone_label.as_deref(),
+        })
+        .collect();
+    entries.sort_by(|a, b| a.key.cmp(b.key));
+    entries.truncate(limit.unwrap_or(usize::MAX));
+    entries
+}
+
+/// Look up every handle in chat.db
+fn handle_stats(index: &ContactsIndex) -> Result<HandleStats, String> {
+    let chat_db = open_chat_db(None)?;
+    let handles =
+        Handle::cache(&chat_db.conn).map_err(|e| format!("Failed to load handles: {e}"))?;
+    let mut ids: Vec<&String> = handles
+        .iter()
+        // Handle 0 is the device owner
+        .filter(|(&rowid, _)| rowid != 0)
+        .map(|(_, id)| id)
+        .collect();
+    ids.sort();
+    ids.dedup();
+
+    let total = ids.len();
+    let unresolved: Vec<String> = ids
+        .into_iter()
+        .filter(|id| index.lookup(id).is_none())
+        .cloned()
+        .collect();
+    Ok(HandleStats {
+        total,
+        resolved: total - unresolved.len(),
+        unresolved,
+    })
+}
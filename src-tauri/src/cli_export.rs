@@ -0,0 +1,155 @@
+/*!
+ * `ctm-cli export`: the export options as flags, and writing the zip out
+ */
+
+use std::path::{Path, PathBuf};
+
+use chat_to_map_desktop::export::{
+    export_chats, filters::MessageFilter, redaction::RedactionConfig, CompressionProfile,
+    ExportFormat, ExportOptions, ExportResult, ExportTimezone,
+};
+
+#[derive(clap::Args)]
+pub struct ExportArgs {
+    /// Chat IDs to export (from `list-chats --json`), comma-separated
+    #[arg(long, value_delimiter = ',', required = true)]
+    chat_ids: Vec<i32>,
+
+    /// Write the export zip here
+    #[arg(short, long)]
+    output: PathBuf,
+
+    /// Custom metadata for the manifest, as key=value (repeatable)
+    #[arg(long = "meta", value_parser = parse_key_value)]
+    meta: Vec<(String, String)>,
+
+    /// Include "Shared with You" links
+    #[arg(long)]
+    shared_links: bool,
+
+    /// Cut message text longer than this many characters
+    #[arg(long)]
+    max_text_length: Option<usize>,
+
+    /// Split chats with more messages than this into numbered part files
+    #[arg(long)]
+    max_messages_per_file: Option<usize>,
+
+    /// Add each message's service, delivered/read times and edited flag
+    #[arg(long)]
+    message_metadata: bool,
+
+    /// Chat file format (NDJSON for huge chats, CSV for spreadsheets, TXT
+    /// transcripts; the server reads JSON)
+    #[arg(long, value_enum, default_value = "json")]
+    format: ExportFormat,
+
+    /// Zip compression: fast, balanced or smallest (photos, video and
+    /// other compressed files are always stored as-is)
+    #[arg(long, value_enum, default_value = "balanced")]
+    compression: CompressionProfile,
+
+    /// Also export messages in Recently Deleted, marked `deleted`
+    #[arg(long)]
+    include_recently_deleted: bool,
+
+    /// Keep message text as stored (attachment placeholders, stray
+    /// formatting bytes) instead of cleaning it
+    #[arg(long)]
+    raw_text: bool,
+
+    /// Write timestamps in the machine's timezone or in UTC
+    #[arg(long, value_enum, default_value = "local")]
+    timezone: ExportTimezone,
+
+    #[command(flatten)]
+    filter: MessageFilter,
+
+    #[command(flatten)]
+    redaction: RedactionConfig,
+
+    /// Database to export from (default: the live chat.db)
+    #[arg(long)]
+    db: Option<PathBuf>,
+}
+
+pub fn cmd_export(args: ExportArgs) {
+    let options = ExportOptions {
+        include_shared_links: args.shared_links,
+        metadata: args.meta.into_iter().collect(),
+        max_text_length: args.max_text_length,
+        max_messages_per_file: args.max_messages_per_file,
+        include_metadata: args.message_metadata,
+        format: args.format,
+        compression: args.compression,
+        timezone: args.timezone,
+        raw_text: args.raw_text,
+        include_recently_deleted: args.include_recently_deleted,
+        filter: args.filter,
+        redaction: args.redaction,
+        ..Default::default()
+    };
+    save_export(
+        export_chats(&args.chat_ids, &options, None, args.db.as_deref()).map_err(|e| e.to_string()),
+        &args.output,
+    );
+}
+
+/// Copy a finished export zip to `output`, exiting on failure
+pub fn save_export(result: Result<ExportResult, String>, output: &Path) {
+    let result = result.and_then(|result| {
+        std::fs::copy(&result.zip_path, output)
+            .map(|_| result)
+            .map_err(|e| format!("Failed to write {:?}: {e}", output))
+    });
+    match result {
+        Ok(result) => {
+            println!(
+                "Wrote {} messages from {} chats to {:?}",
+                result.total_messages, result.chat_count, output
+            );
+            println!("{}", result.metrics.summary());
+            if result.truncated_messages > 0 {
+                println!("Truncated {} long messages", result.truncated_messages);
+            }
+            if result.deleted_messages > 0 {
+                println!(
+                    "Included {} recently deleted messages",
+                    result.deleted_messages
+                );
+            }
+            if result.orphans.recovered > 0 {
+                println!(
+                    "Recovered {} messages that had lost their chat",
+                    result.orphans.recovered
+                );
+            }
+            if result.orphans.unassignable > 0 {
+                eprintln!(
+                    "{} messages had lost their chat and fit no other, so were left out",
+                    result.orphans.unassignable
+                );
+            }
+            if !result.warnings.is_empty() {
+                eprintln!(
+                    "{} message rows could only be partly read (see \"warnings\" in manifest.json)",
+                    result.warnings.len()
+                );
+            }
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Parse a `key=value` argument
+fn parse_key_value(arg: &str) -> Result<(String, String), String> {
+    match arg.split_once('=') {
+        Some((key, value)) if !key.trim().is_empty() => {
+            Ok((key.trim().to_string(), value.to_string()))
+        }
+        _ => Err(format!("expected key=value, got {arg:?}")),
+    }
+}
@@ -0,0 +1,202 @@
+/*!
+ * Contact photos for the chat list.
+ *
+ * The macOS Contacts database keeps a thumbnail of each contact's photo in
+ * `ZABCDRECORD.ZTHUMBNAILIMAGEDATA`. [`contact_avatar`] finds the contact
+ * behind a one-on-one chat's handle (by the same phone and email keys as
+ * [`ContactsIndex`](crate::contacts::ContactsIndex)) and returns the photo
+ * shrunk to [`AVATAR_SIZE`] pixels as a base64 PNG. Group chats, contacts
+ * without a photo and the iPhone backup's address book give `None`.
+ *
+ * The column is a Core Data "external storage" blob: a `0x01` byte and the
+ * image itself, or a `0x02` byte and the name of a file in the database's
+ * `.AddressBook-v22_SUPPORT/_EXTERNAL_DATA` directory.
+ */
+
+use std::{
+    io::Cursor,
+    path::{Path, PathBuf},
+};
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use image::ImageFormat;
+use rusqlite::Connection;
+
+use crate::contacts::{self, identifier_keys, parse_email_list, phone_keys};
+
+/// Width and height (in pixels) the photo is shrunk to fit
+pub const AVATAR_SIZE: u32 = 64;
+
+/// Where Core Data puts blobs too large to keep in the database
+const EXTERNAL_DATA_DIR: &str = ".AddressBook-v22_SUPPORT/_EXTERNAL_DATA";
+
+/// Photo of the person in a one-on-one chat, as a base64 PNG
+pub fn contact_avatar(
+    chat_id: i32,
+    custom_db_path: Option<&Path>,
+) -> Result<Option<String>, String> {
+    let chat_db = crate::db_snapshot::open_chat_db(custom_db_path)?;
+    let Some(identifier) = only_participant(&chat_db.conn, chat_id)? else {
+        return Ok(None);
+    };
+    find_avatar(&contacts::source_paths(), &identifier)
+}
+
+/// The handle identifier of a chat's only participant
+fn only_participant(db: &Connection, chat_id: i32) -> Result<Option<String>, String> {
+    let error = |e: rusqlite::Error| format!("Failed to read chat participants: {e}");
+    let mut stmt = db
+        .prepare(
+            "SELECT h.id FROM chat_handle_join chj
+             JOIN handle h ON h.ROWID = chj.handle_id
+             WHERE chj.chat_id = ?1
+             LIMIT 2",
+        )
+        .map_err(error)?;
+    let ids = stmt
+        .query_map([chat_id], |row| row.get::<_, String>(0))
+        .map_err(error)?
+        .collect::<rusqlite::Result<Vec<_>>>()
+        .map_err(error)?;
+    Ok(match <[String; 1]>::try_from(ids) {
+        Ok([id]) => Some(id),
+        Err(_) => None,
+    })
+}
+
+/// The photo of the first contact in `address_books` with `identifier`
+fn find_avatar(address_books: &[PathBuf], identifier: &str) -> Result<Option<String>, String> {
+    let keys = identifier_keys(identifier);
+    if keys.is_empty() {
+        return Ok(None);
+    }
+    for path in address_books {
+        // The iPhone backup's address book and the NickNameCache stores
+        // have no ZABCDRECORD
+        let Some(image) = Connection::open(path)
+            .ok()
+            .and_then(|conn| thumbnail(&conn, path, &keys))
+        else {
+            continue;
+        };
+        return encode_avatar(&image).map(Some);
+    }
+    Ok(None)
+}
+
+/// The thumbnail of the contact with a phone number or email in `keys`
+fn thumbnail(conn: &Connection, db_path: &Path, keys: &[String]) -> Option<Vec<u8>> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT r.ZTHUMBNAILIMAGEDATA, p.ZFULLNUMBER, e.ZADDRESSNORMALIZED
+             FROM ZABCDRECORD AS r
+             LEFT JOIN ZABCDPHONENUMBER AS p ON r.Z_PK = p.ZOWNER
+             LEFT JOIN ZABCDEMAILADDRESS AS e ON r.Z_PK = e.ZOWNER
+             WHERE r.ZTHUMBNAILIMAGEDATA IS NOT NULL",
+        )
+        .ok()?;
+    let mut rows = stmt.query([]).ok()?;
+    while let Ok(Some(row)) = rows.next() {
+        let phone = row.get::<_, Option<String>>(1).ok().flatten();
+        let email = row.get::<_, Option<String>>(2).ok().flatten();
+        let contact_keys = phone
+            .as_deref()
+            .map(phone_keys)
+            .unwrap_or_default()
+            .into_iter()
+            .chain(email.as_deref().map(parse_email_list).unwrap_or_default());
+        if contact_keys.into_iter().any(|key| keys.contains(&key)) {
+            let blob = row.get::<_, Vec<u8>>(0).ok()?;
+            return image_bytes(&blob, db_path);
+        }
+    }
+    None
+}
+
+/// Unwrap the image from a Core Data external storage blob
+fn image_bytes(blob: &[u8], db_path: &Path) -> Option<Vec<u8>> {
+    match blob.split_first()? {
+        (1, image) => Some(image.to_vec()),
+        (2, name) => {
+            let name = String::from_utf8_lossy(name);
+            let dir = db_path.parent()?.join(EXTERNAL_DATA_DIR);
+            std::fs::read(dir.join(name.trim_end_matches('\0'))).ok()
+        }
+        _ => Some(blob.to_vec()),
+    }
+}
+
+/// Shrink an image (JPEG, PNG, etc.) to fit [`AVATAR_SIZE`] and base64 it
+/// as a PNG
+fn encode_avatar(image: &[u8]) -> Result<String, String> {
+    let image = image::load_from_memory(image)
+        .map_err(|e| format!("Failed to decode contact photo: {e}"))?;
+    // `thumbnail` would also grow a smaller photo
+    let image = if image.width().max(image.height()) > AVATAR_SIZE {
+        image.thumbnail(AVATAR_SIZE, AVATAR_SIZE)
+    } else {
+        image
+    };
+    let mut png = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .map_err(|e| format!("Failed to encode contact photo: {e}"))?;
+    Ok(BASE64.encode(png))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png(size: u32) -> Vec<u8> {
+        let mut png = Vec::new();
+        image::RgbImage::new(size, size)
+            .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+            .unwrap();
+        png
+    }
+
+    #[test]
+    fn finds_the_photo_by_phone_or_email_and_shrinks_it() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("AddressBook-v22.abcddb");
+        let conn = Connection::open(&path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE ZABCDRECORD (Z_PK INTEGER PRIMARY KEY, ZTHUMBNAILIMAGEDATA BLOB);
+             CREATE TABLE ZABCDPHONENUMBER (ZOWNER INTEGER, ZFULLNUMBER TEXT);
+             CREATE TABLE ZABCDEMAILADDRESS (ZOWNER INTEGER, ZADDRESSNORMALIZED TEXT);
+             INSERT INTO ZABCDPHONENUMBER VALUES (1, '(555) 123-4567');
+             INSERT INTO ZABCDEMAILADDRESS VALUES (2, 'bob@example.com');
+             INSERT INTO ZABCDPHONENUMBER VALUES (3, '+1 555 000 0000');",
+        )
+        .unwrap();
+        let inline = [&[1u8][..], &png(200)].concat();
+        conn.execute("INSERT INTO ZABCDRECORD VALUES (1, ?1)", [&inline])
+            .unwrap();
+        let external_dir = dir.path().join(EXTERNAL_DATA_DIR);
+        std::fs::create_dir_all(&external_dir).unwrap();
+        std::fs::write(external_dir.join("0A1B-photo"), png(32)).unwrap();
+        conn.execute(
+            "INSERT INTO ZABCDRECORD VALUES (2, ?1)",
+            [b"\x020A1B-photo\0"],
+        )
+        .unwrap();
+        conn.execute("INSERT INTO ZABCDRECORD VALUES (3, NULL)", [])
+            .unwrap();
+        drop(conn);
+
+        let books = [path];
+        let decode = |base64: String| {
+            let png = BASE64.decode(base64).unwrap();
+            image::load_from_memory(&png).unwrap()
+        };
+        let alice = decode(find_avatar(&books, "+15551234567").unwrap().unwrap());
+        assert_eq!((alice.width(), alice.height()), (AVATAR_SIZE, AVATAR_SIZE));
+        let bob = decode(find_avatar(&books, "Bob@Example.com").unwrap().unwrap());
+        assert_eq!(bob.width(), 32);
+
+        // No photo, and no contact at all
+        assert_eq!(find_avatar(&books, "+15550000000").unwrap(), None);
+        assert_eq!(find_avatar(&books, "+15559999999").unwrap(), None);
+    }
+}
@@ -16,6 +16,8 @@ use imessage_database::{
 };
 use rusqlite::{Connection, Result};
 
+use crate::{shared_names, sources::ios_backup};
+
 // MARK: Name
 #[derive(Clone, Debug, PartialEq, Eq)]
 /// Simple first/last name struct
@@ -30,11 +32,14 @@ pub struct Name {
     pub details: String,
     /// Set of original handle IDs that map to this name
     pub handle_ids: HashSet<i32>,
+    /// Label of the phone number this name was matched by (e.g. "Mobile"),
+    /// kept for diagnostics; `None` for email and unlabeled matches
+    pub phone_label: Option<String>,
 }
 
 impl Name {
     /// Create from optional first/last name
-    fn from_opt(first: Option<String>, last: Option<String>) -> Option<Self> {
+    pub(crate) fn from_opt(first: Option<String>, last: Option<String>) -> Option<Self> {
         // Return None if both are None
         if first.is_none() && last.is_none() {
             return None;
@@ -58,6 +63,7 @@ impl Name {
             full,
             details: String::new(),
             handle_ids: HashSet::new(),
+            phone_label: None,
         })
     }
 
@@ -66,6 +72,16 @@ impl Name {
         u8::from(!self.first.is_empty()) + u8::from(!self.last.is_empty())
     }
 
+    /// Ranking when several contacts share a key: a mobile/iPhone match
+    /// wins, then [`Name::score`]
+    fn preference(&self) -> (bool, u8) {
+        let mobile = self
+            .phone_label
+            .as_deref()
+            .is_some_and(|label| phone_label_kind(label) == PhoneLabelKind::Mobile);
+        (mobile, self.score())
+    }
+
     /// Get the contact's full name, falling back to details if full name is empty
     pub fn get_display_name(&self) -> &str {
         if self.full.is_empty() {
@@ -83,6 +99,7 @@ impl Name {
             full: String::new(),
             details: details.into(),
             handle_ids: HashSet::new(),
+            phone_label: None,
         }
     }
 }
@@ -93,17 +110,24 @@ impl Name {
 pub struct ContactsIndex {
     /// Map of identifier (phone/email) to [`Name`]
     index: HashMap<String, Name>,
+    /// Fallback for phone numbers that match no key in `index`
+    suffixes: SuffixIndex,
+}
+
+impl From<HashMap<String, Name>> for ContactsIndex {
+    /// An index without the suffix fallback, which `build` adds last
+    fn from(index: HashMap<String, Name>) -> Self {
+        Self {
+            index,
+            suffixes: SuffixIndex::default(),
+        }
+    }
 }
 
 impl ContactsIndex {
-    /// Build a contacts index
-    ///
-    /// - If `path` is `Some`, we only look at that database.
-    /// - If `path` is `None`, scans macOS Contacts sources under
-    ///   `~/Library/Application Support/AddressBook/Sources/*/AddressBook-v22.abcddb`
-    ///
-    /// Supports building from both macOS (`AddressBook-v22.abcddb`) and iOS (`AddressBook.sqlitedb`) databases.
-    pub fn build(path: Option<&Path>) -> Result<Self, TableError> {
+    /// The index from the contacts databases alone, without name overrides
+    /// (see [`ContactsIndex::build`])
+    fn build_from_sources(path: Option<&Path>) -> Result<Self, TableError> {
         if let Some(path) = path {
             let conn = get_connection(path)?;
             if table_exists(&conn, "ABPersonFullTextSearch_content") {
@@ -112,6 +136,13 @@ impl ContactsIndex {
             return Ok(Self::build_from_macos(&conn)?);
         }
 
+        if cfg!(target_os = "windows") {
+            return match ios_backup::default_backup().and_then(|b| b.address_book()) {
+                Some(path) => Self::build_from_sources(Some(&path)),
+                None => Ok(Self::default()),
+            };
+        }
+
         let mut idx: HashMap<String, Name> = HashMap::new();
 
         for db_path in find_macos_addressbook_db_paths() {
@@ -123,14 +154,15 @@ impl ContactsIndex {
                 }
             }
         }
+        shared_names::fill_gaps(&mut idx, shared_names::load(&shared_names::source_paths()));
 
-        Ok(Self { index: idx })
+        Ok(idx.into())
     }
 
     /// Build from an in-memory index (for testing)
     #[cfg(test)]
     pub fn from_index(index: HashMap<String, Name>) -> Self {
-        Self { index }
+        index.into()
     }
 
     // MARK: macOS
@@ -140,7 +172,7 @@ impl ContactsIndex {
         let mut index = HashMap::new();
 
         let mut stmt = conn.prepare(
-            "SELECT r.ZFIRSTNAME, r.ZLASTNAME, p.ZFULLNUMBER, e.ZADDRESSNORMALIZED
+            "SELECT r.ZFIRSTNAME, r.ZLASTNAME, p.ZFULLNUMBER, e.ZADDRESSNORMALIZED, p.ZLABEL
              FROM ZABCDRECORD AS r
              LEFT JOIN ZABCDPHONENUMBER AS p ON r.Z_PK = p.ZOWNER
              LEFT JOIN ZABCDEMAILADDRESS AS e ON r.Z_PK = e.ZOWNER",
@@ -162,14 +194,23 @@ impl ContactsIndex {
                 }
 
                 if let Some(phone_raw) = row.get::<_, Option<String>>(2)? {
-                    for key in phone_keys(&phone_raw) {
-                        upsert_best(&mut index, key, &name);
+                    let label = row.get::<_, Option<String>>(4)?;
+                    let label = label.as_deref().map(clean_phone_label);
+                    // Fax and pager numbers never send messages
+                    if label.map(phone_label_kind) != Some(PhoneLabelKind::Skipped) {
+                        let name = Name {
+                            phone_label: label.map(str::to_string),
+                            ..name.clone()
+                        };
+                        for key in phone_keys(&phone_raw) {
+                            upsert_best(&mut index, key, &name);
+                        }
                     }
                 }
             }
         }
 
-        Ok(Self { index })
+        Ok(index.into())
     }
 
     // MARK: iOS
@@ -209,23 +250,27 @@ impl ContactsIndex {
             }
         }
 
-        Ok(Self { index })
+        Ok(index.into())
     }
 
     /// Returns first/last name if found
     pub fn lookup(&self, id: &str) -> Option<Name> {
         // Handle details can be space-separated list of emails/phones from the iMessage database
         for id_part in id.split_whitespace() {
-            if looks_like_email(id_part) {
-                return normalize_email(id_part).and_then(|k| self.index.get(&k).cloned());
-            }
-            for k in phone_keys(id_part) {
+            let keys = match looks_like_email(id_part) {
+                true => normalize_email(id_part).into_iter().collect(),
+                false => phone_keys(id_part),
+            };
+            for k in keys {
                 if let Some(n) = self.index.get(&k) {
                     return Some(n.clone());
                 }
             }
         }
-        None
+        let mut phones = id.split_whitespace();
+        phones
+            .find_map(|phone| self.suffixes.lookup(phone))
+            .cloned()
     }
 
     /// Build a map of participant handle IDs to Names
@@ -274,6 +319,11 @@ impl ContactsIndex {
     pub fn is_empty(&self) -> bool {
         self.index.is_empty()
     }
+
+    /// Every key (phone number variant or email) and its name, unordered
+    pub fn entries(&self) -> impl Iterator<Item = (&str, &Name)> {
+        self.index.iter().map(|(key, name)| (key.as_str(), name))
+    }
 }
 
 /// Check if a table or view exists in the database
@@ -286,11 +336,11 @@ fn table_exists(conn: &Connection, name: &str) -> bool {
     .is_ok()
 }
 
-/// Upsert a [`Name`] into the map if it has a better [`Name::score`] than existing
+/// Upsert a [`Name`] into the map if it has a better [`Name::preference`] than existing
 fn upsert_best(map: &mut HashMap<String, Name>, key: String, incoming: &Name) {
     match map.get_mut(&key) {
         Some(existing) => {
-            if incoming.score() > existing.score() {
+            if incoming.preference() > existing.preference() {
                 *existing = incoming.clone();
             }
         }
@@ -307,7 +357,7 @@ fn looks_like_email(s: &str) -> bool {
 }
 
 /// Normalize email: trim, lowercase, remove angle-brackets
-fn normalize_email(s: &str) -> Option<String> {
+pub(crate) fn normalize_email(s: &str) -> Option<String> {
     let s = s.trim();
     if s.is_empty() {
         return None;
@@ -320,8 +370,17 @@ fn normalize_email(s: &str) -> Option<String> {
     Some(s.to_lowercase())
 }
 
+/// The index keys a single phone number or email is looked up by
+pub(crate) fn identifier_keys(id: &str) -> Vec<String> {
+    if looks_like_email(id) {
+        normalize_email(id).into_iter().collect()
+    } else {
+        phone_keys(id)
+    }
+}
+
 /// Parse a space-separated list of emails
-fn parse_email_list(raw: &str) -> Vec<String> {
+pub(crate) fn parse_email_list(raw: &str) -> Vec<String> {
     // macOS may store a single value; guard for angle-brackets
     if raw.contains(' ') {
         raw.split_whitespace().filter_map(normalize_email).collect()
@@ -330,53 +389,56 @@ fn parse_email_list(raw: &str) -> Vec<String> {
     }
 }
 
-// MARK: Phone
-/// Generate possible phone number keys from a raw phone number
-///
-/// - If the number contains "urn:", returns an empty vector
-/// - Returns keys with and without '+' prefix
-/// - For US numbers starting with +1 and 11 digits, also adds variants without the `+1` country code
-pub fn phone_keys(raw: &str) -> Vec<String> {
-    // Skip iMessage business accounts
-    if raw.contains("urn:") {
-        return vec![];
-    }
-
-    // The digits include the country code portion of the number
-    let digits = to_phone_digits(raw);
-    if digits.is_empty() {
-        return vec![];
-    }
+// MARK: Phone Labels
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum PhoneLabelKind {
+    /// Mobile or iPhone: preferred when contacts share a number
+    Mobile,
+    /// Fax or pager: left out of the index
+    Skipped,
+    Other,
+}
 
-    // Create keys with and without '+' prefix for country code
-    let mut keys = vec![digits.clone(), format!("+{digits}")];
+/// Strip the `_$!<...>!$_` wrapper AddressBook puts around built-in labels
+/// (`_$!<Mobile>!$_` → `Mobile`); custom labels are stored as typed
+fn clean_phone_label(raw: &str) -> &str {
+    raw.strip_prefix("_$!<")
+        .and_then(|label| label.strip_suffix(">!$_"))
+        .unwrap_or(raw)
+}
 
-    // If the original was 12 chars starting with +1, add a variant without the `+1` (USA) country code
-    if digits.len() == 11 && raw.starts_with("+1") {
-        let last_10 = &digits[digits.len() - 10..];
-        keys.push(last_10.to_string());
-        keys.push(format!("+{last_10}"));
+fn phone_label_kind(label: &str) -> PhoneLabelKind {
+    let label = label.to_lowercase();
+    if label.contains("fax") || label == "pager" {
+        PhoneLabelKind::Skipped
+    } else if label == "mobile" || label == "iphone" {
+        PhoneLabelKind::Mobile
+    } else {
+        PhoneLabelKind::Other
     }
-
-    keys.dedup();
-    keys
 }
 
-/// Extract digits from a raw phone number string
-fn to_phone_digits(raw: &str) -> String {
-    let mut out = String::with_capacity(raw.len());
-    for ch in raw.chars() {
-        if ch.is_ascii_digit() {
-            out.push(ch);
-        }
-    }
-    out
+/// The files [`ContactsIndex::build`] reads with no `path`
+pub(crate) fn source_paths() -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = if cfg!(target_os = "windows") {
+        ios_backup::default_backup()
+            .and_then(|backup| backup.address_book())
+            .into_iter()
+            .collect()
+    } else {
+        let mut paths = find_macos_addressbook_db_paths();
+        paths.extend(shared_names::source_paths());
+        paths
+    };
+    paths.extend(vcard::configured_path());
+    paths.extend(overrides::default_path());
+    paths
 }
 
 // MARK: macOS Dirs
 /// Scans the macOS Contacts Sources directory (`~/Library/Application Support/AddressBook/Sources`)
 /// for AddressBook-v22.abcddb database files.
-fn find_macos_addressbook_db_paths() -> Vec<PathBuf> {
+pub(crate) fn find_macos_addressbook_db_paths() -> Vec<PathBuf> {
     let mut results = Vec::new();
     if let Ok(entries) = fs::read_dir(macos_sources_dir()) {
         for entry in entries.flatten() {
@@ -401,6 +463,19 @@ fn macos_sources_dir() -> PathBuf {
         .join("Sources")
 }
 
+#[path = "contacts_links.rs"]
+mod links;
+#[path = "contacts_phone.rs"]
+mod phone;
+use phone::SuffixIndex;
+pub use phone::{phone_keys, DEFAULT_SUFFIX_DIGITS};
+#[path = "contacts_overrides.rs"]
+mod overrides;
+pub use overrides::{load_overrides, NameOverride, OVERRIDES_FILENAME};
+#[path = "contacts_vcard.rs"]
+mod vcard;
+pub use vcard::use_vcard;
+
 #[cfg(test)]
 #[path = "contacts_tests.rs"]
 mod tests;
@@ -0,0 +1,186 @@
+/*!
+ * Contacts permission status, read from the system rather than guessed.
+ *
+ * Building the contacts index can't tell "permission denied" from "no
+ * contacts": both give an empty index. On macOS the Contacts framework
+ * reports the TCC authorization status directly
+ * (`+[CNContactStore authorizationStatusForEntityType:]`), and
+ * `-[CNContactStore requestAccessForEntityType:completionHandler:]` shows
+ * the system prompt while the status is still undetermined. The prompt
+ * text comes from `NSContactsUsageDescription` in src-tauri/Info.plist.
+ *
+ * Windows reads contacts from the iPhone backup (see
+ * [`crate::sources::ios_backup`]), which needs no permission.
+ */
+
+use serde::{Deserialize, Serialize};
+
+/// Whether the app may read contacts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContactsAccess {
+    /// The user hasn't been asked yet; requesting access shows the prompt
+    NotDetermined,
+    /// Blocked by a device management profile or parental controls
+    Restricted,
+    /// The user declined; only System Settings can change it now
+    Denied,
+    /// Contacts can be read
+    Authorized,
+    /// No contacts source on this platform (or no backup address book)
+    Unavailable,
+}
+
+impl ContactsAccess {
+    /// Map a `CNAuthorizationStatus` value
+    pub fn from_authorization_status(status: isize) -> Self {
+        match status {
+            0 => Self::NotDetermined,
+            1 => Self::Restricted,
+            2 => Self::Denied,
+            // 3 = authorized, 4 = limited (a subset the user picked)
+            3 | 4 => Self::Authorized,
+            _ => Self::Denied,
+        }
+    }
+
+    pub fn is_authorized(self) -> bool {
+        self == Self::Authorized
+    }
+}
+
+/// The current Contacts permission; never prompts
+pub fn contacts_access() -> ContactsAccess {
+    #[cfg(target_os = "macos")]
+    {
+        ContactsAccess::from_authorization_status(macos::authorization_status())
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        use crate::sources::ios_backup;
+
+        match ios_backup::default_backup().and_then(|backup| backup.address_book()) {
+            Some(_) => ContactsAccess::Authorized,
+            None => ContactsAccess::Unavailable,
+        }
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    {
+        ContactsAccess::Unavailable
+    }
+}
+
+/// Show the system Contacts prompt if the user hasn't been asked yet, and
+/// return the resulting permission. Otherwise returns the current status
+/// without prompting (macOS only asks once).
+pub async fn request_contacts_access() -> ContactsAccess {
+    let current = contacts_access();
+    if current != ContactsAccess::NotDetermined {
+        return current;
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        if let Err(e) = macos::request_access().await {
+            tracing::warn!("[contacts_access] Access request failed: {e}");
+        }
+        contacts_access()
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    {
+        current
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod macos {
+    use std::sync::Mutex;
+
+    use block2::RcBlock;
+    use objc2::{
+        class, msg_send,
+        rc::Retained,
+        runtime::{AnyObject, Bool},
+    };
+
+    /// `CNEntityTypeContacts`
+    const ENTITY_TYPE_CONTACTS: isize = 0;
+
+    #[link(name = "Contacts", kind = "framework")]
+    extern "C" {}
+
+    pub fn authorization_status() -> isize {
+        // SAFETY: a class method taking and returning NSInteger
+        unsafe {
+            msg_send![
+                class!(CNContactStore),
+                authorizationStatusForEntityType: ENTITY_TYPE_CONTACTS
+            ]
+        }
+    }
+
+    /// Ask for access and wait for the user's answer. The completion
+    /// handler runs on an arbitrary queue, so the answer comes back over a
+    /// channel.
+    pub async fn request_access() -> Result<(), String> {
+        let (sender, receiver) = tokio::sync::oneshot::channel();
+        // Objective-C objects aren't Send, so none may live across the await
+        start_request(sender);
+        receiver
+            .await
+            .map_err(|_| "Contacts access request was dropped".to_string())
+    }
+
+    fn start_request(sender: tokio::sync::oneshot::Sender<()>) {
+        let sender = Mutex::new(Some(sender));
+        // SAFETY: `new` returns a retained CNContactStore; the handler
+        // matches `void (^)(BOOL granted, NSError *error)` and is copied by
+        // the framework, so it may outlive this call
+        unsafe {
+            let store: Retained<AnyObject> = msg_send![class!(CNContactStore), new];
+            // The handler keeps the store alive until the user answers
+            let request_store = store.clone();
+            let handler = RcBlock::new(move |_granted: Bool, _error: *mut AnyObject| {
+                let _ = &request_store;
+                if let Some(sender) = sender.lock().ok().and_then(|mut s| s.take()) {
+                    let _ = sender.send(());
+                }
+            });
+            let _: () = msg_send![
+                &store,
+                requestAccessForEntityType: ENTITY_TYPE_CONTACTS,
+                completionHandler: &*handler
+            ];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn authorization_status_values_map_to_access() {
+        assert_eq!(
+            ContactsAccess::from_authorization_status(0),
+            ContactsAccess::NotDetermined
+        );
+        assert_eq!(
+            ContactsAccess::from_authorization_status(1),
+            ContactsAccess::Restricted
+        );
+        assert_eq!(
+            ContactsAccess::from_authorization_status(2),
+            ContactsAccess::Denied
+        );
+        assert!(ContactsAccess::from_authorization_status(3).is_authorized());
+        assert!(ContactsAccess::from_authorization_status(4).is_authorized());
+        assert_eq!(
+            serde_json::to_value(ContactsAccess::NotDetermined).unwrap(),
+            "not_determined"
+        );
+    }
+}
@@ -0,0 +1,139 @@
+/*!
+ * Linked contact cards.
+ *
+ * A person with a card in each account (iCloud, Google, Exchange) is one
+ * unified contact in Contacts: the cards share a `ZABCDRECORD.ZLINKID`.
+ * The cards can disagree ("Sam" on one, "Samantha Lee" on the other), so
+ * each number or email used to get whichever card's name ranked highest
+ * for it alone. [`ContactsIndex::unify_linked`] gives every number and
+ * email of the linked cards the fullest of their names.
+ */
+
+use std::{collections::HashMap, path::PathBuf};
+
+use rusqlite::Connection;
+
+use super::{
+    clean_phone_label, parse_email_list, phone_keys, phone_label_kind, ContactsIndex,
+    PhoneLabelKind,
+};
+
+/// The cards behind one unified contact
+#[derive(Default)]
+struct LinkGroup {
+    /// `(address book, Z_PK)` of each card
+    cards: Vec<(usize, i64)>,
+    keys: Vec<String>,
+}
+
+impl ContactsIndex {
+    /// Give every number and email of linked cards in `address_books` the
+    /// same name
+    pub(super) fn unify_linked(&mut self, address_books: &[PathBuf]) {
+        let mut groups: HashMap<String, LinkGroup> = HashMap::new();
+        for (book, path) in address_books.iter().enumerate() {
+            if let Ok(conn) = Connection::open(path) {
+                read_links(&conn, book, &mut groups);
+            }
+        }
+
+        for group in groups.into_values().filter(|group| group.cards.len() > 1) {
+            let best = group
+                .keys
+                .iter()
+                .filter_map(|key| self.index.get(key))
+                .max_by(|a, b| (a.score(), &a.full).cmp(&(b.score(), &b.full)))
+                .cloned();
+            let Some(best) = best else {
+                continue;
+            };
+            for key in group.keys {
+                let name = self.index.entry(key).or_insert_with(|| best.clone());
+                name.first.clone_from(&best.first);
+                name.last.clone_from(&best.last);
+                name.full.clone_from(&best.full);
+            }
+        }
+    }
+}
+
+/// Add each linked card's numbers and emails in one address book to its
+/// group
+fn read_links(conn: &Connection, book: usize, groups: &mut HashMap<String, LinkGroup>) {
+    // Older address books have no ZLINKID
+    let Ok(mut stmt) = conn.prepare(
+        "SELECT r.ZLINKID, r.Z_PK, p.ZFULLNUMBER, p.ZLABEL, e.ZADDRESSNORMALIZED
+         FROM ZABCDRECORD AS r
+         LEFT JOIN ZABCDPHONENUMBER AS p ON r.Z_PK = p.ZOWNER
+         LEFT JOIN ZABCDEMAILADDRESS AS e ON r.Z_PK = e.ZOWNER
+         WHERE r.ZLINKID IS NOT NULL",
+    ) else {
+        return;
+    };
+    let Ok(mut rows) = stmt.query([]) else {
+        return;
+    };
+    while let Ok(Some(row)) = rows.next() {
+        let (Ok(link_id), Ok(record)) = (row.get::<_, String>(0), row.get::<_, i64>(1)) else {
+            continue;
+        };
+        let group = groups.entry(link_id).or_default();
+        if !group.cards.contains(&(book, record)) {
+            group.cards.push((book, record));
+        }
+
+        let phone = row.get::<_, Option<String>>(2).ok().flatten();
+        let label = row.get::<_, Option<String>>(3).ok().flatten();
+        let skipped = label.as_deref().is_some_and(|label| {
+            phone_label_kind(clean_phone_label(label)) == PhoneLabelKind::Skipped
+        });
+        if let Some(phone) = phone.filter(|_| !skipped) {
+            group.keys.extend(phone_keys(&phone));
+        }
+        if let Some(email) = row.get::<_, Option<String>>(4).ok().flatten() {
+            group.keys.extend(parse_email_list(&email));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{ContactBuilder, TestAddressBookDb};
+
+    #[test]
+    fn linked_cards_share_the_fullest_name() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let icloud = dir.path().join("icloud.abcddb");
+        let google = dir.path().join("google.abcddb");
+        for (path, first, last, phone, link) in [
+            (&icloud, "Sam", None, "+15551234567", "LINK-1"),
+            (&google, "Samantha", Some("Lee"), "+15559876543", "LINK-1"),
+        ] {
+            let mut db = TestAddressBookDb::default();
+            let mut contact = ContactBuilder::new().first_name(first).phone(phone);
+            if let Some(last) = last {
+                contact = contact.last_name(last);
+            }
+            db.contact(contact).unwrap();
+            db.conn()
+                .execute_batch(&format!(
+                    "ALTER TABLE ZABCDRECORD ADD COLUMN ZLINKID VARCHAR;
+                     UPDATE ZABCDRECORD SET ZLINKID = '{link}';"
+                ))
+                .unwrap();
+            db.conn()
+                .execute("VACUUM INTO ?1", [path.to_str().unwrap()])
+                .unwrap();
+        }
+
+        let mut index =
+            ContactsIndex::build_from_macos(&Connection::open(&icloud).unwrap()).unwrap();
+        index.merge(ContactsIndex::build_from_macos(&Connection::open(&google).unwrap()).unwrap());
+        assert_eq!(index.lookup("+15551234567").unwrap().full, "Sam");
+
+        index.unify_linked(&[icloud, google]);
+        assert_eq!(index.lookup("+15551234567").unwrap().full, "Samantha Lee");
+        assert_eq!(index.lookup("+15559876543").unwrap().full, "Samantha Lee");
+    }
+}
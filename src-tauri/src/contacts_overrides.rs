@@ -0,0 +1,197 @@
+/*!
+ * Name overrides from a CSV file.
+ *
+ * Not everyone keeps their contacts in macOS Contacts. A CSV of
+ * `identifier,name` rows (a phone number or email, then the name to show)
+ * saved as [`OVERRIDES_FILENAME`] in the app data directory, or passed to
+ * [`ContactsIndex::build_with_overrides`], is merged into the index last, so
+ * its names win over Contacts and shared names. Fields may be quoted
+ * (`"+1 555 123 4567","Smith, Sam"`); a header row, blank lines and lines
+ * starting with `#` are skipped.
+ */
+
+use std::path::{Path, PathBuf};
+
+use imessage_database::error::table::TableError;
+
+use super::{identifier_keys, ContactsIndex, Name, SuffixIndex, DEFAULT_SUFFIX_DIGITS};
+use crate::settings::Settings;
+
+/// The overrides file's name in the app data directory
+pub const OVERRIDES_FILENAME: &str = "name_overrides.csv";
+
+/// One row of the overrides file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameOverride {
+    /// Phone number or email
+    pub identifier: String,
+    pub name: String,
+}
+
+impl ContactsIndex {
+    /// Build a contacts index
+    ///
+    /// - If `path` is `Some`, we only look at that database.
+    /// - If `path` is `None`, scans macOS Contacts sources under
+    ///   `~/Library/Application Support/AddressBook/Sources/*/AddressBook-v22.abcddb`
+    ///   (plus names shared in Messages), or on Windows reads the newest
+    ///   iPhone backup's address book; either way, plus the configured vCard
+    ///
+    /// Supports building from both macOS (`AddressBook-v22.abcddb`) and iOS (`AddressBook.sqlitedb`) databases.
+    /// Linked cards share one name (see `contacts_links.rs`), and names in the
+    /// app data directory's overrides file, if there is one, win. Numbers
+    /// matching no contact fall back to their last digits (see
+    /// `contacts_phone.rs`).
+    pub fn build(path: Option<&Path>) -> Result<Self, TableError> {
+        Self::build_with_overrides(path, None)
+    }
+
+    /// [`ContactsIndex::build`], taking overrides from `overrides` instead
+    /// of the app data directory
+    pub fn build_with_overrides(
+        path: Option<&Path>,
+        overrides: Option<&Path>,
+    ) -> Result<Self, TableError> {
+        let mut index = Self::build_from_sources(path)?;
+        if path.is_none() {
+            index.unify_linked(&super::find_macos_addressbook_db_paths());
+        }
+        if let Some(vcf) = path.is_none().then(super::vcard::configured_path).flatten() {
+            match Self::build_from_vcard(&vcf) {
+                Ok(cards) => index.merge(cards),
+                Err(e) => tracing::warn!("[contacts] Skipping vCard contacts: {e}"),
+            }
+        }
+        let csv = match overrides {
+            Some(csv) => Some(csv.to_path_buf()),
+            None => default_path().filter(|csv| csv.is_file()),
+        };
+        if let Some(csv) = csv {
+            match load_overrides(&csv) {
+                Ok(overrides) => index.apply_overrides(overrides),
+                Err(e) => tracing::warn!("[contacts] Skipping name overrides: {e}"),
+            }
+        }
+        index.suffixes = SuffixIndex::build(&index.index, suffix_digits());
+        Ok(index)
+    }
+
+    fn apply_overrides(&mut self, overrides: Vec<NameOverride>) {
+        for NameOverride { identifier, name } in overrides {
+            let Some(name) = Name::from_opt(Some(name), None) else {
+                continue;
+            };
+            for key in identifier_keys(&identifier) {
+                self.index.insert(key, name.clone());
+            }
+        }
+    }
+}
+
+/// Digits the phone suffix fallback compares, from settings
+fn suffix_digits() -> usize {
+    crate::job_history::default_data_dir()
+        .and_then(|dir| Settings::load(&dir).phone_suffix_digits)
+        .unwrap_or(DEFAULT_SUFFIX_DIGITS)
+}
+
+/// The overrides file in the desktop app's data directory
+pub(super) fn default_path() -> Option<PathBuf> {
+    crate::job_history::default_data_dir().map(|dir| dir.join(OVERRIDES_FILENAME))
+}
+
+/// Read the overrides in the CSV at `path`
+pub fn load_overrides(path: &Path) -> Result<Vec<NameOverride>, String> {
+    let text =
+        std::fs::read_to_string(path).map_err(|e| format!("Failed to read {path:?}: {e}"))?;
+    Ok(parse_overrides(&text))
+}
+
+fn parse_overrides(text: &str) -> Vec<NameOverride> {
+    let mut overrides = Vec::new();
+    // Spreadsheet exports often start with a byte order mark
+    let lines = text.trim_start_matches('\u{feff}').lines();
+    for (index, line) in lines.enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut fields = split_fields(line).into_iter();
+        let (Some(identifier), Some(name)) = (fields.next(), fields.next()) else {
+            continue;
+        };
+        let is_header = index == 0 && identifier.eq_ignore_ascii_case("identifier");
+        if is_header || identifier.is_empty() || name.is_empty() {
+            continue;
+        }
+        overrides.push(NameOverride { identifier, name });
+    }
+    overrides
+}
+
+/// Split a CSV line on commas outside double quotes, trimming each field
+fn split_fields(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            // `""` inside quotes is a literal quote
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field).trim().to_string()),
+            _ => field.push(c),
+        }
+    }
+    fields.push(field.trim().to_string());
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn csv_rows_are_parsed_with_quotes_and_a_header() {
+        let csv = "\u{feff}identifier,name\n\
+                   +15551234567, Sam Climbing\n\
+                   \n\
+                   # work\n\
+                   \"alex@example.com\",\"Smith, \"\"Alex\"\"\"\n\
+                   no-name-here\n\
+                   ,Nobody\n";
+        let overrides = parse_overrides(csv);
+        let rows: Vec<(&str, &str)> = overrides
+            .iter()
+            .map(|o| (o.identifier.as_str(), o.name.as_str()))
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                ("+15551234567", "Sam Climbing"),
+                ("alex@example.com", "Smith, \"Alex\"")
+            ]
+        );
+    }
+
+    #[test]
+    fn overrides_win_over_contacts() {
+        let contact = Name::from_opt(Some("Samantha".to_string()), None).unwrap();
+        let mut index = ContactsIndex::from_index(HashMap::from([
+            ("+15551234567".to_string(), contact.clone()),
+            ("5551234567".to_string(), contact),
+        ]));
+        index.apply_overrides(vec![NameOverride {
+            identifier: "+1 (555) 123-4567".to_string(),
+            name: "Sam Climbing".to_string(),
+        }]);
+
+        assert_eq!(index.lookup("+15551234567").unwrap().full, "Sam Climbing");
+        assert_eq!(index.lookup("5551234567").unwrap().full, "Sam Climbing");
+    }
+}
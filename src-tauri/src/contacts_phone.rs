@@ -0,0 +1,164 @@
+/*!
+ * Phone number keys, and matching numbers by their last digits.
+ *
+ * Every number in the index is stored under the [`phone_keys`] variants
+ * of it, so `+15551234567`, `15551234567` and `5551234567` all match.
+ * Outside the US a handle and the contact card can still disagree about
+ * the country code (Messages has `+447700900123` where the card says
+ * `07700 900123`), so when a number matches no key [`SuffixIndex`] tries
+ * its last few digits: 9 by default, 7 to 9 with
+ * `Settings::phone_suffix_digits`, or 0 to turn it off. A suffix that
+ * contacts with different names share is ambiguous and never matches.
+ */
+
+use std::collections::{hash_map::Entry, HashMap};
+
+use super::Name;
+
+/// Digits compared when [`Settings::phone_suffix_digits`] is unset
+///
+/// [`Settings::phone_suffix_digits`]: crate::settings::Settings::phone_suffix_digits
+pub const DEFAULT_SUFFIX_DIGITS: usize = 9;
+
+/// Fewer digits than this could match numbers that only share a local
+/// exchange; more would miss a dropped trunk prefix and country code
+const SUFFIX_DIGITS_RANGE: std::ops::RangeInclusive<usize> = 7..=9;
+
+/// Generate possible phone number keys from a raw phone number
+///
+/// - If the number contains "urn:", returns an empty vector
+/// - Returns keys with and without '+' prefix
+/// - For US numbers starting with +1 and 11 digits, also adds variants without the `+1` country code
+pub fn phone_keys(raw: &str) -> Vec<String> {
+    // Skip iMessage business accounts
+    if raw.contains("urn:") {
+        return vec![];
+    }
+
+    // The digits include the country code portion of the number
+    let digits = to_phone_digits(raw);
+    if digits.is_empty() {
+        return vec![];
+    }
+
+    // Create keys with and without '+' prefix for country code
+    let mut keys = vec![digits.clone(), format!("+{digits}")];
+
+    // If the original was 12 chars starting with +1, add a variant without the `+1` (USA) country code
+    if digits.len() == 11 && raw.starts_with("+1") {
+        let last_10 = &digits[digits.len() - 10..];
+        keys.push(last_10.to_string());
+        keys.push(format!("+{last_10}"));
+    }
+
+    keys.dedup();
+    keys
+}
+
+/// Extract digits from a raw phone number string
+fn to_phone_digits(raw: &str) -> String {
+    let mut out = String::with_capacity(raw.len());
+    for ch in raw.chars() {
+        if ch.is_ascii_digit() {
+            out.push(ch);
+        }
+    }
+    out
+}
+
+/// Names by the last digits of their phone numbers, the fallback when a
+/// number matches no key
+#[derive(Debug, Default)]
+pub(super) struct SuffixIndex {
+    /// Digits compared; 0 when the fallback is off
+    digits: usize,
+    /// Suffix → name, or `None` when contacts with different names share it
+    names: HashMap<String, Option<Name>>,
+}
+
+impl SuffixIndex {
+    /// Index the phone keys in `index` by their last `digits` digits
+    /// (clamped to 7 to 9; 0 turns matching off)
+    pub(super) fn build(index: &HashMap<String, Name>, digits: usize) -> Self {
+        if digits == 0 {
+            return Self::default();
+        }
+        let digits = digits.clamp(*SUFFIX_DIGITS_RANGE.start(), *SUFFIX_DIGITS_RANGE.end());
+        let mut names: HashMap<String, Option<Name>> = HashMap::new();
+        for (key, name) in index {
+            if key.contains('@') {
+                continue;
+            }
+            let Some(suffix) = suffix(key, digits) else {
+                continue;
+            };
+            match names.entry(suffix) {
+                Entry::Vacant(slot) => {
+                    slot.insert(Some(name.clone()));
+                }
+                Entry::Occupied(mut shared) => {
+                    if shared.get().as_ref().is_some_and(|n| n.full != name.full) {
+                        shared.insert(None);
+                    }
+                }
+            }
+        }
+        Self { digits, names }
+    }
+
+    /// The one name whose number ends like `phone`
+    pub(super) fn lookup(&self, phone: &str) -> Option<&Name> {
+        if self.digits == 0 || phone.contains('@') || phone.contains("urn:") {
+            return None;
+        }
+        self.names.get(&suffix(phone, self.digits)?)?.as_ref()
+    }
+}
+
+/// The last `digits` digits of a phone number, if it has that many
+fn suffix(phone: &str, digits: usize) -> Option<String> {
+    let all = to_phone_digits(phone);
+    (all.len() >= digits).then(|| all[all.len() - digits..].to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index(contacts: &[(&str, &str)]) -> HashMap<String, Name> {
+        let mut index = HashMap::new();
+        for (phone, full) in contacts {
+            let name = Name::from_opt(Some(full.to_string()), None).unwrap();
+            for key in phone_keys(phone) {
+                index.insert(key, name.clone());
+            }
+        }
+        index
+    }
+
+    #[test]
+    fn numbers_match_by_their_last_digits() {
+        let contacts = index(&[("07700 900123", "Olivia"), ("021 555 0199", "Sam")]);
+        let suffixes = SuffixIndex::build(&contacts, DEFAULT_SUFFIX_DIGITS);
+
+        assert_eq!(suffixes.lookup("+447700900123").unwrap().full, "Olivia");
+        assert_eq!(suffixes.lookup("+64215550199").unwrap().full, "Sam");
+        assert!(suffixes.lookup("+447700900999").is_none());
+        assert!(suffixes.lookup("0123").is_none());
+        assert!(SuffixIndex::build(&contacts, 0)
+            .lookup("+447700900123")
+            .is_none());
+    }
+
+    #[test]
+    fn ambiguous_suffixes_never_match() {
+        let contacts = index(&[("+447700900123", "Olivia"), ("+337700900123", "Chloé")]);
+        let suffixes = SuffixIndex::build(&contacts, 7);
+        assert!(suffixes.lookup("07700 900123").is_none());
+
+        // The same person under two keys is not a collision
+        let contacts = index(&[("+447700900123", "Olivia"), ("07700900123", "Olivia")]);
+        let suffixes = SuffixIndex::build(&contacts, 7);
+        assert_eq!(suffixes.lookup("7700900123").unwrap().full, "Olivia");
+    }
+}
@@ -19,6 +19,7 @@ fn build_test_contacts_index() -> ContactsIndex {
         full: "Alice Johnson".to_string(),
         details: String::new(),
         handle_ids: HashSet::new(),
+        phone_label: None,
     };
     for key in phone_keys("+15551234567") {
         index.insert(key, alice.clone());
@@ -31,6 +32,7 @@ fn build_test_contacts_index() -> ContactsIndex {
         full: "Bob Williams".to_string(),
         details: String::new(),
         handle_ids: HashSet::new(),
+        phone_label: None,
     };
     for key in phone_keys("+6421555123") {
         index.insert(key, bob.clone());
@@ -43,6 +45,7 @@ fn build_test_contacts_index() -> ContactsIndex {
         full: "Charlie Brown".to_string(),
         details: String::new(),
         handle_ids: HashSet::new(),
+        phone_label: None,
     };
     if let Some(normalized) = normalize_email("charlie@example.com") {
         index.insert(normalized, charlie);
@@ -329,6 +332,53 @@ mod integration {
         assert_eq!(alice2.unwrap().full, "Alice Johnson");
     }
 
+    #[test]
+    fn test_fax_and_pager_numbers_skipped() {
+        let mut db = TestAddressBookDb::default();
+
+        db.contact(
+            ContactBuilder::new()
+                .first_name("Acme")
+                .labeled_phone("+15551110000", "_$!<WorkFAX>!$_")
+                .labeled_phone("+15552220000", "_$!<Pager>!$_")
+                .labeled_phone("+15553330000", "_$!<Work>!$_"),
+        )
+        .unwrap();
+
+        let index = ContactsIndex::build_from_macos(db.conn()).unwrap();
+
+        assert!(index.lookup("+15551110000").is_none());
+        assert!(index.lookup("+15552220000").is_none());
+        let work = index.lookup("+15553330000").unwrap();
+        assert_eq!(work.phone_label.as_deref(), Some("Work"));
+    }
+
+    #[test]
+    fn test_shared_number_prefers_mobile_label() {
+        let mut db = TestAddressBookDb::default();
+
+        // Office main line saved on a colleague's card, with a full name
+        db.contact(
+            ContactBuilder::new()
+                .first_name("Dana")
+                .last_name("Office")
+                .labeled_phone("+15551234567", "_$!<Work>!$_"),
+        )
+        .unwrap();
+        db.contact(
+            ContactBuilder::new()
+                .first_name("Alice")
+                .labeled_phone("+15551234567", "iPhone"),
+        )
+        .unwrap();
+
+        let index = ContactsIndex::build_from_macos(db.conn()).unwrap();
+
+        let name = index.lookup("+15551234567").unwrap();
+        assert_eq!(name.full, "Alice");
+        assert_eq!(name.phone_label.as_deref(), Some("iPhone"));
+    }
+
     #[test]
     fn test_contact_phone_and_email_real_db() {
         let mut db = TestAddressBookDb::default();
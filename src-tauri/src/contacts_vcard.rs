@@ -0,0 +1,333 @@
+/*!
+ * vCard (`.vcf`) files as a contacts source.
+ *
+ * Google Contacts, Outlook and phones can all export a `.vcf`. The file
+ * picked in settings (`Settings::contacts_vcf_path`), or passed to
+ * `ctm-cli --contacts-vcf` (see [`use_vcard`]), is read alongside the
+ * macOS Contacts databases. Each card's `TEL` and `EMAIL` map to its name
+ * from `N` (falling back to `FN`), ranked like any other contact.
+ *
+ * Handles vCard 2.1 to 4.0: folded lines, `item1.TEL` groups, `tel:` URIs,
+ * backslash escapes and quoted-printable names. `TEL;TYPE=CELL` counts as
+ * a mobile number; fax and pager numbers are skipped.
+ */
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use super::{
+    normalize_email, phone_keys, phone_label_kind, upsert_best, ContactsIndex, Name, PhoneLabelKind,
+};
+use crate::settings::Settings;
+
+/// A vCard chosen for this process, over the one in settings
+static VCARD_PATH: OnceLock<PathBuf> = OnceLock::new();
+
+/// Read contacts from `path` for the rest of this process, whatever the
+/// settings say (for the CLI)
+pub fn use_vcard(path: PathBuf) {
+    let _ = VCARD_PATH.set(path);
+}
+
+/// The vCard to read: [`use_vcard`]'s, else the one in settings (the
+/// selected source's, see db_sources.rs)
+pub(super) fn configured_path() -> Option<PathBuf> {
+    if let Some(path) = VCARD_PATH.get() {
+        return Some(path.clone());
+    }
+    let settings = Settings::load(&crate::job_history::default_data_dir()?);
+    settings.vcard_path().map(PathBuf::from)
+}
+
+impl ContactsIndex {
+    /// Build a contacts index from a vCard file of one or more cards
+    pub fn build_from_vcard(path: &Path) -> Result<Self, String> {
+        let bytes = std::fs::read(path).map_err(|e| format!("Failed to read {path:?}: {e}"))?;
+        Ok(Self::parse_vcard(&String::from_utf8_lossy(&bytes)))
+    }
+
+    fn parse_vcard(text: &str) -> Self {
+        let mut index = HashMap::new();
+        let mut card: Option<Card> = None;
+        for line in unfold(text) {
+            let Some(property) = Property::parse(&line) else {
+                continue;
+            };
+            match (property.name.as_str(), card.as_mut()) {
+                ("BEGIN", _) if property.value.eq_ignore_ascii_case("VCARD") => {
+                    card = Some(Card::default());
+                }
+                ("END", Some(_)) => {
+                    if let Some(card) = card.take() {
+                        card.add_to(&mut index);
+                    }
+                }
+                ("FN", Some(card)) => card.formatted_name = Some(property.text()),
+                ("N", Some(card)) => {
+                    let parts: Vec<String> = split_unescaped(&property.decoded(), ';')
+                        .iter()
+                        .map(|p| unescape(p))
+                        .collect();
+                    card.last = parts.first().cloned();
+                    card.first = parts.get(1).cloned();
+                }
+                ("TEL", Some(card)) => {
+                    let value = property.text();
+                    let number = value.strip_prefix("tel:").unwrap_or(&value).to_string();
+                    card.phones.push((number, property.phone_label()));
+                }
+                ("EMAIL", Some(card)) => card.emails.push(property.text()),
+                _ => {}
+            }
+        }
+        index.into()
+    }
+
+    /// Add the names in `other` that rank higher than this index's
+    pub(super) fn merge(&mut self, other: ContactsIndex) {
+        for (key, name) in other.index {
+            upsert_best(&mut self.index, key, &name);
+        }
+    }
+}
+
+/// The fields of one card the index needs
+#[derive(Default)]
+struct Card {
+    formatted_name: Option<String>,
+    first: Option<String>,
+    last: Option<String>,
+    phones: Vec<(String, Option<String>)>,
+    emails: Vec<String>,
+}
+
+impl Card {
+    fn add_to(self, index: &mut HashMap<String, Name>) {
+        let non_empty = |part: Option<String>| part.filter(|p| !p.trim().is_empty());
+        let name = Name::from_opt(non_empty(self.first), non_empty(self.last))
+            .or_else(|| Name::from_opt(non_empty(self.formatted_name), None));
+        let Some(name) = name else {
+            return;
+        };
+        for email in self
+            .emails
+            .iter()
+            .filter_map(|email| normalize_email(email))
+        {
+            upsert_best(index, email, &name);
+        }
+        for (number, label) in self.phones {
+            if label.as_deref().map(phone_label_kind) == Some(PhoneLabelKind::Skipped) {
+                continue;
+            }
+            let name = Name {
+                phone_label: label,
+                ..name.clone()
+            };
+            for key in phone_keys(&number) {
+                upsert_best(index, key, &name);
+            }
+        }
+    }
+}
+
+/// One `[group.]NAME;PARAMS:value` line
+struct Property {
+    name: String,
+    params: Vec<String>,
+    value: String,
+}
+
+impl Property {
+    fn parse(line: &str) -> Option<Self> {
+        let (head, value) = line.split_once(':')?;
+        let mut head = head.split(';');
+        let name = head.next()?;
+        // `item1.TEL`: the group only ties properties together
+        let name = name
+            .rsplit('.')
+            .next()
+            .unwrap_or(name)
+            .trim()
+            .to_uppercase();
+        Some(Self {
+            name,
+            params: head.map(str::to_uppercase).collect(),
+            value: value.to_string(),
+        })
+    }
+
+    fn has_param(&self, param: &str) -> bool {
+        self.params.iter().any(|p| {
+            p == param
+                || p.split_once('=').is_some_and(|(_, values)| {
+                    values.split(',').any(|v| v.trim_matches('"') == param)
+                })
+        })
+    }
+
+    /// The raw value, with quoted-printable decoded
+    fn decoded(&self) -> String {
+        if self.has_param("QUOTED-PRINTABLE") {
+            decode_quoted_printable(&self.value)
+        } else {
+            self.value.clone()
+        }
+    }
+
+    /// The value as plain text
+    fn text(&self) -> String {
+        unescape(&self.decoded()).trim().to_string()
+    }
+
+    /// A Contacts-style label for a `TEL`'s type
+    fn phone_label(&self) -> Option<String> {
+        let labels = [
+            ("CELL", "Mobile"),
+            ("IPHONE", "iPhone"),
+            ("FAX", "Fax"),
+            ("PAGER", "Pager"),
+        ];
+        let (_, label) = labels.into_iter().find(|(kind, _)| self.has_param(kind))?;
+        Some(label.to_string())
+    }
+}
+
+/// Logical lines: folded lines (continued by a leading space or tab, or by
+/// a trailing `=` in quoted-printable) joined back together
+fn unfold(text: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for raw in text.lines() {
+        let raw = raw.trim_end_matches('\r');
+        match lines.last_mut() {
+            Some(last) if raw.starts_with([' ', '\t']) => last.push_str(&raw[1..]),
+            Some(last)
+                if last.ends_with('=') && last.to_uppercase().contains("QUOTED-PRINTABLE") =>
+            {
+                last.pop();
+                last.push_str(raw);
+            }
+            _ => lines.push(raw.to_string()),
+        }
+    }
+    lines
+}
+
+/// Split on `separator`, except where it is backslash-escaped
+fn split_unescaped(value: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let (mut start, mut escaped) = (0, false);
+    for (i, c) in value.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            c if c == separator => {
+                parts.push(&value[start..i]);
+                start = i + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&value[start..]);
+    parts
+}
+
+fn unescape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => out.push(' '),
+            Some(other) => out.push(other),
+            None => {}
+        }
+    }
+    out
+}
+
+fn decode_quoted_printable(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'=', Some(byte)) => {
+                out.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CARDS: &str = "BEGIN:VCARD\r\n\
+        VERSION:3.0\r\n\
+        N:Johnson;Alice;;;\r\n\
+        FN:Alice Johnson\r\n\
+        TEL;TYPE=CELL:+1 (555) 123-4567\r\n\
+        TEL;TYPE=FAX:+1 555 999 0000\r\n\
+        item1.EMAIL;TYPE=INTERNET:Alice@Example.com\r\n\
+        END:VCARD\r\n\
+        BEGIN:VCARD\r\n\
+        VERSION:4.0\r\n\
+        FN:Sam from the\r\n  climbing gym\r\n\
+        TEL;VALUE=uri;TYPE=\"voice,home\":tel:+64-21-555-0199\r\n\
+        END:VCARD\r\n\
+        BEGIN:VCARD\r\n\
+        VERSION:2.1\r\n\
+        N;CHARSET=UTF-8;ENCODING=QUOTED-PRINTABLE:M=C3=BCller;J=\r\n\
+        =C3=BCrgen\r\n\
+        EMAIL:juergen@example.de\r\n\
+        END:VCARD\r\n";
+
+    #[test]
+    fn cards_map_phones_and_emails_to_names() {
+        let index = ContactsIndex::parse_vcard(CARDS);
+
+        let alice = index.lookup("+15551234567").unwrap();
+        assert_eq!(alice.full, "Alice Johnson");
+        assert_eq!(alice.phone_label.as_deref(), Some("Mobile"));
+        assert_eq!(
+            index.lookup("alice@example.com").unwrap().full,
+            "Alice Johnson"
+        );
+        assert!(index.lookup("+15559990000").is_none());
+
+        let sam = index.lookup("+64215550199").unwrap();
+        assert_eq!(sam.full, "Sam from the climbing gym");
+        assert_eq!(
+            index.lookup("juergen@example.de").unwrap().full,
+            "Jürgen Müller"
+        );
+    }
+
+    #[test]
+    fn escaped_separators_stay_in_the_name() {
+        let index = ContactsIndex::parse_vcard(
+            "BEGIN:VCARD\nN:Smith\\; Jones;Pat\nEMAIL:pat@example.com\nEND:VCARD\n",
+        );
+        assert_eq!(
+            index.lookup("pat@example.com").unwrap().full,
+            "Pat Smith; Jones"
+        );
+    }
+}
@@ -0,0 +1,97 @@
+/*!
+ * Riding out locks held by Messages.app.
+ *
+ * A database opened directly (a custom path, or the iPhone backup on
+ * Windows) may be written to while it is read. Connections from
+ * `crate::db_snapshot` wait up to [`BUSY_TIMEOUT`] for a lock before
+ * SQLite reports `SQLITE_BUSY`; [`retry_on_busy`] re-runs a whole query a
+ * few times, with a growing pause, when that still isn't enough. Only busy
+ * and locked errors are retried, never a broken query or a bad row.
+ */
+
+use std::{thread, time::Duration};
+
+use imessage_database::error::table::{TableConnectError, TableError};
+use rusqlite::ErrorCode;
+
+/// How long a statement waits for another connection's lock
+pub const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Runs of a query that keeps failing with a busy error, including the first
+const BUSY_ATTEMPTS: u32 = 3;
+
+/// Pause before the first retry; doubled for each one after
+const BUSY_BACKOFF: Duration = Duration::from_millis(250);
+
+/// An error that may mean the database was locked by another connection
+pub trait BusyError {
+    fn is_busy(&self) -> bool;
+}
+
+impl BusyError for rusqlite::Error {
+    fn is_busy(&self) -> bool {
+        matches!(
+            self.sqlite_error_code(),
+            Some(ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked)
+        )
+    }
+}
+
+impl BusyError for TableError {
+    fn is_busy(&self) -> bool {
+        match self {
+            Self::QueryError(error)
+            | Self::CannotConnect(TableConnectError::Permissions(error)) => error.is_busy(),
+            _ => false,
+        }
+    }
+}
+
+/// Run `query`, running it again while it fails because the database is
+/// locked
+pub fn retry_on_busy<T, E: BusyError>(mut query: impl FnMut() -> Result<T, E>) -> Result<T, E> {
+    let mut backoff = BUSY_BACKOFF;
+    for _ in 1..BUSY_ATTEMPTS {
+        match query() {
+            Err(error) if error.is_busy() => {
+                tracing::warn!("[db] Database busy, retrying in {backoff:?}");
+                thread::sleep(backoff);
+                backoff *= 2;
+            }
+            result => return result,
+        }
+    }
+    query()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn busy() -> rusqlite::Error {
+        rusqlite::Error::SqliteFailure(rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_BUSY), None)
+    }
+
+    #[test]
+    fn only_busy_errors_are_retried() {
+        let mut runs = 0;
+        let result = retry_on_busy(|| {
+            runs += 1;
+            if runs < BUSY_ATTEMPTS {
+                Err(busy())
+            } else {
+                Ok(runs)
+            }
+        });
+        assert_eq!(result.unwrap(), BUSY_ATTEMPTS);
+
+        let mut runs = 0;
+        let result: Result<(), _> = retry_on_busy(|| {
+            runs += 1;
+            Err(TableError::QueryError(rusqlite::Error::InvalidQuery))
+        });
+        assert!(result.is_err());
+        assert_eq!(runs, 1);
+        assert!(TableError::QueryError(busy()).is_busy());
+    }
+}
@@ -0,0 +1,266 @@
+/*!
+ * Finding older chat.db copies to recover deleted messages from.
+ *
+ * Messages deleted from the live database may survive in a Time Machine
+ * backup or in a copy made by hand. [`discover_databases`] looks in:
+ *
+ * - Time Machine backups on mounted disks, both the HFS+ layout
+ *   (`Backups.backupdb/<Mac>/<date>/<volume>/Users/...`) and the APFS one
+ *   (`<date>.backup/<date>.backup/<volume>/Users/...`), for every user;
+ * - `~/Library/Messages`, for copies next to the live database;
+ * - folders the user names, a few levels deep.
+ *
+ * Anything named like a chat database (`chat.db`, `chat-2019.db`, ...)
+ * that isn't the live one is validated, and the readable ones are
+ * returned with their message counts and date ranges, to be added as
+ * sources (see db_sources.rs).
+ */
+
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::validation::{validate_chat_db, ValidationResult};
+
+/// How deep a named folder is searched
+const FOLDER_SEARCH_DEPTH: usize = 4;
+
+/// Home folders in Time Machine backups, as patterns under `/Volumes`
+/// (see [`expand`])
+const TIME_MACHINE_PATTERNS: [&[&str]; 2] = [
+    // HFS+: <disk>/Backups.backupdb/<Mac>/<date>/<volume>/Users/<user>/...
+    &["*", "Backups.backupdb", "*", "*", "*", "Users", "*"],
+    // APFS: <disk>/<date>.backup/<date>.backup/<volume>/Users/<user>/...
+    &["*", "*.backup", "*.backup", "*", "Users", "*"],
+];
+
+/// Where a found database lives
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiscoveryLocation {
+    TimeMachine,
+    /// Next to the live database in `~/Library/Messages`
+    MessagesFolder,
+    /// A folder the user asked to search
+    Folder,
+}
+
+/// A readable chat.db other than the live one
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FoundDatabase {
+    pub path: String,
+    pub location: DiscoveryLocation,
+    /// A source name to offer, like "Time Machine (2023-05-01)"
+    pub suggested_name: String,
+    /// Message count and date range, among others
+    pub validation: ValidationResult,
+}
+
+/// Where to look
+#[derive(Debug, Clone)]
+pub struct DiscoveryRoots {
+    pub home: PathBuf,
+    /// Where disks are mounted
+    pub volumes: PathBuf,
+    pub folders: Vec<PathBuf>,
+}
+
+impl DiscoveryRoots {
+    /// This Mac's home folder and mounted disks, plus `folders`
+    pub fn system(folders: Vec<PathBuf>) -> Option<Self> {
+        Some(Self {
+            home: dirs::home_dir()?,
+            volumes: PathBuf::from("/Volumes"),
+            folders,
+        })
+    }
+}
+
+/// Readable chat databases under `roots`, newest messages first
+pub fn discover_databases(roots: &DiscoveryRoots) -> Vec<FoundDatabase> {
+    let live = roots.home.join("Library/Messages/chat.db");
+    let mut candidates = Vec::new();
+    for pattern in TIME_MACHINE_PATTERNS {
+        for user_dir in expand(&roots.volumes, pattern) {
+            let path = user_dir.join("Library/Messages/chat.db");
+            candidates.push((path, DiscoveryLocation::TimeMachine));
+        }
+    }
+    for path in find_named(&roots.home.join("Library/Messages"), 1) {
+        candidates.push((path, DiscoveryLocation::MessagesFolder));
+    }
+    for folder in &roots.folders {
+        for path in find_named(folder, FOLDER_SEARCH_DEPTH) {
+            candidates.push((path, DiscoveryLocation::Folder));
+        }
+    }
+
+    let mut seen = HashSet::from([canonical(&live)]);
+    let mut found: Vec<FoundDatabase> = candidates
+        .into_iter()
+        .filter(|(path, _)| path.is_file() && seen.insert(canonical(path)))
+        .filter_map(|(path, location)| {
+            let validation = validate_chat_db(&path);
+            validation.valid.then(|| FoundDatabase {
+                suggested_name: suggested_name(&path, location, &validation),
+                path: path.to_string_lossy().into_owned(),
+                location,
+                validation,
+            })
+        })
+        .collect();
+    found.sort_by(|a, b| {
+        (b.validation.last_message_date.as_ref()).cmp(&a.validation.last_message_date.as_ref())
+    });
+    found
+}
+
+/// Directories under `root` matching `pattern`, one segment per level:
+/// `*` matches any name, `*.suffix` names ending in `.suffix`
+fn expand(root: &Path, pattern: &[&str]) -> Vec<PathBuf> {
+    let Some((segment, rest)) = pattern.split_first() else {
+        return vec![root.to_path_buf()];
+    };
+    let children: Vec<PathBuf> = match segment.strip_prefix('*') {
+        Some(suffix) => subdirs(root)
+            .into_iter()
+            .filter(|dir| file_name(dir).ends_with(suffix))
+            .collect(),
+        None => vec![root.join(segment)],
+    };
+    children
+        .iter()
+        .filter(|dir| dir.is_dir())
+        .flat_map(|dir| expand(dir, rest))
+        .collect()
+}
+
+/// Files named like a chat database under `dir`, `depth` levels deep
+fn find_named(dir: &Path, depth: usize) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut found = Vec::new();
+    for path in entries.flatten().map(|entry| entry.path()) {
+        if path.is_dir() {
+            if depth > 1 && !file_name(&path).starts_with('.') {
+                found.extend(find_named(&path, depth - 1));
+            }
+        } else if is_chat_db_name(&file_name(&path)) {
+            found.push(path);
+        }
+    }
+    found
+}
+
+fn is_chat_db_name(name: &str) -> bool {
+    let name = name.to_lowercase();
+    name.starts_with("chat") && name.ends_with(".db")
+}
+
+fn subdirs(dir: &Path) -> Vec<PathBuf> {
+    std::fs::read_dir(dir)
+        .map(|entries| entries.flatten().map(|entry| entry.path()).collect())
+        .unwrap_or_default()
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn suggested_name(
+    path: &Path,
+    location: DiscoveryLocation,
+    validation: &ValidationResult,
+) -> String {
+    let newest = validation
+        .last_message_date
+        .as_deref()
+        .and_then(|date| date.get(..10))
+        .unwrap_or("no messages");
+    match location {
+        DiscoveryLocation::TimeMachine => format!("Time Machine ({newest})"),
+        DiscoveryLocation::MessagesFolder | DiscoveryLocation::Folder => {
+            format!("{} ({newest})", file_name(path))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::test_fixtures::{ChatBuilder, MessageBuilder, TestIMessageDb};
+
+    /// Save a database with one message `tenths` × ~3 years after 2001
+    fn save_db(path: &Path, tenths: i64) {
+        let mut db = TestIMessageDb::new().unwrap();
+        let chat = db
+            .chat(ChatBuilder::new("iMessage;-;+15551234567"))
+            .unwrap();
+        db.message(
+            MessageBuilder::new()
+                .text("Hi")
+                .chat(chat)
+                .date(tenths * 100_000_000_000_000_000),
+        )
+        .unwrap();
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        db.save_to(path).unwrap();
+    }
+
+    #[test]
+    fn finds_backups_and_copies_but_not_the_live_database() {
+        let dir = TempDir::new().unwrap();
+        let home = dir.path().join("home");
+        let volumes = dir.path().join("Volumes");
+        let downloads = home.join("Downloads");
+
+        save_db(&home.join("Library/Messages/chat.db"), 3);
+        let copy = home.join("Library/Messages/chat-2019.db");
+        save_db(&copy, 2);
+        let hfs = volumes.join(
+            "Backup/Backups.backupdb/Mac/2020-01-01-120000/Macintosh HD/Users/sam/Library/Messages/chat.db",
+        );
+        save_db(&hfs, 1);
+        let apfs = volumes.join(
+            "Backups of Mac/2023-05-01-090000.backup/2023-05-01-090000.backup/Macintosh HD - Data/Users/sam/Library/Messages/chat.db",
+        );
+        save_db(&apfs, 4);
+        let nested = downloads.join("old mac/Messages/chat.db");
+        save_db(&nested, 0);
+        std::fs::write(downloads.join("chat-notes.db"), "not sqlite").unwrap();
+
+        let roots = DiscoveryRoots {
+            home,
+            volumes,
+            folders: vec![downloads],
+        };
+        let found = discover_databases(&roots);
+        let paths: Vec<&str> = found.iter().map(|db| db.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            [
+                apfs.to_str().unwrap(),
+                copy.to_str().unwrap(),
+                hfs.to_str().unwrap(),
+                nested.to_str().unwrap(),
+            ]
+        );
+        assert_eq!(found[0].location, DiscoveryLocation::TimeMachine);
+        assert!(found[0].suggested_name.starts_with("Time Machine (2013-"));
+        assert_eq!(found[1].location, DiscoveryLocation::MessagesFolder);
+        assert!(found[1].suggested_name.starts_with("chat-2019.db ("));
+        assert_eq!(found[3].validation.message_count, 1);
+    }
+}
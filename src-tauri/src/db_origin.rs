@@ -0,0 +1,164 @@
+/*!
+ * Detection of which Messages version produced a database.
+ *
+ * Each macOS release changes the Messages schema, and with it the quirks we
+ * have to handle (text moving into `attributedBody`, edited messages,
+ * "Shared with You" ranges, emoji tapbacks). The database doesn't record
+ * the OS version directly, so we infer the earliest matching release from
+ * the schema features present and report Messages' own `_ClientVersion`
+ * from `_SqliteDatabaseProperties` alongside. The result goes into
+ * validation diagnostics and the export manifest so the server can branch
+ * on known quirks too.
+ */
+
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+/// A schema feature that arrived in a known macOS release
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SchemaFeature {
+    /// Inline replies (`message.thread_originator_guid`), macOS 11
+    ThreadedReplies,
+    /// Edit / unsend (`message.date_edited`), macOS 13
+    EditedMessages,
+    /// "Shared with You" (`message.syndication_ranges`), macOS 13
+    SharedWithYou,
+    /// Recently Deleted (`chat_recoverable_message_join`), macOS 13
+    RecentlyDeleted,
+    /// Any-emoji tapbacks (`message.associated_message_emoji`), macOS 15
+    EmojiTapbacks,
+    /// Send Later (`message.schedule_type`), macOS 15
+    ScheduledMessages,
+}
+
+impl SchemaFeature {
+    /// macOS major version that introduced the feature
+    fn macos_version(self) -> u32 {
+        match self {
+            Self::ThreadedReplies => 11,
+            Self::EditedMessages | Self::SharedWithYou | Self::RecentlyDeleted => 13,
+            Self::EmojiTapbacks | Self::ScheduledMessages => 15,
+        }
+    }
+
+    /// `(table, column)` whose presence marks the feature; `None` column
+    /// means the table itself
+    fn marker(self) -> (&'static str, Option<&'static str>) {
+        match self {
+            Self::ThreadedReplies => ("message", Some("thread_originator_guid")),
+            Self::EditedMessages => ("message", Some("date_edited")),
+            Self::SharedWithYou => ("message", Some("syndication_ranges")),
+            Self::RecentlyDeleted => ("chat_recoverable_message_join", None),
+            Self::EmojiTapbacks => ("message", Some("associated_message_emoji")),
+            Self::ScheduledMessages => ("message", Some("schedule_type")),
+        }
+    }
+}
+
+const ALL_FEATURES: [SchemaFeature; 6] = [
+    SchemaFeature::ThreadedReplies,
+    SchemaFeature::EditedMessages,
+    SchemaFeature::SharedWithYou,
+    SchemaFeature::RecentlyDeleted,
+    SchemaFeature::EmojiTapbacks,
+    SchemaFeature::ScheduledMessages,
+];
+
+/// Best guess at where a database came from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DatabaseOrigin {
+    /// Earliest macOS major version whose schema matches (e.g. 13), or
+    /// `None` for databases older than any feature we track
+    pub min_macos_version: Option<u32>,
+    /// `_ClientVersion` from `_SqliteDatabaseProperties`, when present
+    pub client_version: Option<String>,
+    /// Schema features present, in release order
+    pub features: Vec<SchemaFeature>,
+}
+
+impl DatabaseOrigin {
+    /// Human-readable summary, e.g. "macOS 13+ (Messages client 17001)"
+    pub fn describe(&self) -> String {
+        let os = match self.min_macos_version {
+            Some(version) => format!("macOS {version}+"),
+            None => "macOS 10.x".to_string(),
+        };
+        match &self.client_version {
+            Some(client) => format!("{os} (Messages client {client})"),
+            None => os,
+        }
+    }
+}
+
+/// Inspect the schema of an open message database
+pub fn detect_origin(db: &Connection) -> DatabaseOrigin {
+    let features: Vec<SchemaFeature> = ALL_FEATURES
+        .into_iter()
+        .filter(|feature| has_marker(db, feature.marker()))
+        .collect();
+    DatabaseOrigin {
+        min_macos_version: features.iter().map(|f| f.macos_version()).max(),
+        client_version: client_version(db),
+        features,
+    }
+}
+
+fn has_marker(db: &Connection, (table, column): (&str, Option<&str>)) -> bool {
+    let result = match column {
+        Some(column) => db.query_row(
+            "SELECT 1 FROM pragma_table_info(?1) WHERE name = ?2",
+            [table, column],
+            |_| Ok(()),
+        ),
+        None => db.query_row(
+            "SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1",
+            [table],
+            |_| Ok(()),
+        ),
+    };
+    result.is_ok()
+}
+
+/// Messages' own schema/client version, if the properties table exists
+fn client_version(db: &Connection) -> Option<String> {
+    db.query_row(
+        "SELECT value FROM _SqliteDatabaseProperties WHERE key = '_ClientVersion'",
+        [],
+        |row| row.get(0),
+    )
+    .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::TestIMessageDb;
+
+    #[test]
+    fn fixture_schema_matches_macos_15() {
+        let db = TestIMessageDb::new().unwrap();
+        let origin = detect_origin(db.conn());
+
+        assert_eq!(origin.min_macos_version, Some(15));
+        assert!(origin.features.contains(&SchemaFeature::EmojiTapbacks));
+        assert!(!origin.features.contains(&SchemaFeature::ScheduledMessages));
+        assert_eq!(origin.client_version, None);
+    }
+
+    #[test]
+    fn old_schema_reports_client_version() {
+        let db = Connection::open_in_memory().unwrap();
+        db.execute_batch(
+            "CREATE TABLE message (ROWID INTEGER PRIMARY KEY, text TEXT,
+                                   thread_originator_guid TEXT);
+             CREATE TABLE _SqliteDatabaseProperties (key TEXT, value TEXT, UNIQUE(key));
+             INSERT INTO _SqliteDatabaseProperties VALUES ('_ClientVersion', '14006');",
+        )
+        .unwrap();
+        let origin = detect_origin(&db);
+
+        assert_eq!(origin.features, vec![SchemaFeature::ThreadedReplies]);
+        assert_eq!(origin.describe(), "macOS 11+ (Messages client 14006)");
+    }
+}
@@ -0,0 +1,258 @@
+/*!
+ * Point-in-time snapshots of the live chat.db.
+ *
+ * Messages.app keeps chat.db open in WAL mode and writes to it constantly.
+ * Reading it in place can fail with SQLITE_BUSY or observe a half-written
+ * WAL, so list_chats and export_chats read from a private copy instead:
+ *
+ * 1. Copy `chat.db`, `chat.db-wal` and `chat.db-shm` into a temp dir.
+ * 2. Open the copy read-write once to fold the WAL into the main file
+ *    (`immutable=1` would otherwise ignore the WAL and lose recent messages).
+ * 3. Reopen it read-only with `immutable=1`, so SQLite skips locking and
+ *    change detection entirely.
+ *
+ * The temp dir is removed when the [`ChatDbSnapshot`] is dropped. Other
+ * databases are opened in place, read-only. Every connection waits up to
+ * `db_busy::BUSY_TIMEOUT` for a lock instead of failing with "database is
+ * locked" straight away.
+ */
+
+use std::path::{Path, PathBuf};
+
+use imessage_database::util::dirs::default_db_path;
+use rusqlite::{Connection, OpenFlags};
+use tempfile::TempDir;
+
+use crate::{db_busy::BUSY_TIMEOUT, sources::ios_backup};
+
+/// SQLite sidecar files copied alongside the main database
+const SIDECAR_SUFFIXES: [&str; 2] = ["-wal", "-shm"];
+
+/// A private copy of chat.db, deleted on drop
+pub struct ChatDbSnapshot {
+    _temp_dir: TempDir,
+    db_path: PathBuf,
+}
+
+impl ChatDbSnapshot {
+    /// Copy `source` (and its WAL/SHM files, if present) into a temp dir
+    pub fn create(source: &Path) -> Result<Self, String> {
+        let temp_dir = TempDir::new().map_err(|e| format!("Failed to create temp dir: {e}"))?;
+        let file_name = source
+            .file_name()
+            .ok_or_else(|| format!("Invalid database path: {}", source.display()))?;
+        let db_path = temp_dir.path().join(file_name);
+
+        std::fs::copy(source, &db_path)
+            .map_err(|e| format!("Failed to snapshot {}: {e}", source.display()))?;
+        for suffix in SIDECAR_SUFFIXES {
+            let sidecar = sidecar_path(source, suffix);
+            if sidecar.exists() {
+                std::fs::copy(&sidecar, sidecar_path(&db_path, suffix))
+                    .map_err(|e| format!("Failed to snapshot {}: {e}", sidecar.display()))?;
+            }
+        }
+
+        checkpoint(&db_path).map_err(|e| format!("Failed to prepare snapshot: {e}"))?;
+
+        Ok(Self {
+            _temp_dir: temp_dir,
+            db_path,
+        })
+    }
+
+    /// Path of the copied database
+    pub fn path(&self) -> &Path {
+        &self.db_path
+    }
+
+    /// Open the snapshot read-only with `immutable=1`
+    pub fn connect(&self) -> Result<Connection, String> {
+        open_uri(&self.db_path, "immutable=1")
+    }
+}
+
+/// A chat.db connection, plus the snapshot backing it when there is one.
+///
+/// Keep this alive for as long as `conn` is used; dropping it removes the
+/// snapshot files.
+pub struct ChatDb {
+    pub conn: Connection,
+    /// The file `conn` reads (a snapshot's copy, if there is one)
+    path: PathBuf,
+    _snapshot: Option<ChatDbSnapshot>,
+}
+
+impl ChatDb {
+    /// Another connection to the same database, for a worker thread
+    /// (connections can't be shared between threads)
+    pub fn connect_again(&self) -> Result<Connection, String> {
+        match &self._snapshot {
+            Some(snapshot) => snapshot.connect(),
+            None => open_directly(&self.path).map(|db| db.conn),
+        }
+    }
+}
+
+/// The database read when no custom path is given: the live chat.db on
+/// macOS, the newest iPhone backup's sms.db on Windows
+pub fn default_chat_db_path() -> PathBuf {
+    if cfg!(target_os = "windows") {
+        if let Some(backup) = ios_backup::default_backup() {
+            return backup.sms_db();
+        }
+    }
+    default_db_path()
+}
+
+/// Bytes `open_chat_db(custom_db_path)` copies into the temp dir: the live
+/// database and its sidecars when it is snapshotted, otherwise none
+pub fn snapshot_bytes(custom_db_path: Option<&Path>) -> u64 {
+    if custom_db_path.is_some() || cfg!(target_os = "windows") {
+        return 0;
+    }
+    let db_path = default_db_path();
+    let size = |path: &Path| std::fs::metadata(path).map_or(0, |meta| meta.len());
+    size(&db_path)
+        + SIDECAR_SUFFIXES
+            .iter()
+            .map(|suffix| size(&sidecar_path(&db_path, suffix)))
+            .sum::<u64>()
+}
+
+/// Open chat.db for reading.
+///
+/// The live default database is always read through a snapshot. A custom
+/// path (a backup or a copy the user picked) is not being written to, so it
+/// is opened directly; so is the iPhone backup used by default on Windows.
+pub fn open_chat_db(custom_db_path: Option<&Path>) -> Result<ChatDb, String> {
+    match custom_db_path {
+        Some(path) => open_directly(path),
+        None if cfg!(target_os = "windows") => {
+            let backup = ios_backup::default_backup().ok_or_else(|| {
+                "No iPhone backup found. Back up your iPhone to this computer with iTunes \
+                 or Apple Devices (unencrypted), then try again."
+                    .to_string()
+            })?;
+            open_directly(&backup.sms_db())
+        }
+        None => {
+            let snapshot = ChatDbSnapshot::create(&default_db_path())?;
+            Ok(ChatDb {
+                conn: snapshot.connect()?,
+                path: snapshot.path().to_path_buf(),
+                _snapshot: Some(snapshot),
+            })
+        }
+    }
+}
+
+/// Open a database that may be written to while it is read, read-only and
+/// waiting out other connections' locks
+fn open_directly(path: &Path) -> Result<ChatDb, String> {
+    if !path.is_file() {
+        return Err(format!(
+            "Failed to connect to database: no database file at {}",
+            path.display()
+        ));
+    }
+    // The flag is `mode=ro` without a URI, whose paths differ on Windows
+    let flags = OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX;
+    Ok(ChatDb {
+        conn: with_busy_timeout(Connection::open_with_flags(path, flags))?,
+        path: path.to_path_buf(),
+        _snapshot: None,
+    })
+}
+
+/// Open `path` read-only as a SQLite URI with `params`
+fn open_uri(path: &Path, params: &str) -> Result<Connection, String> {
+    // Escape the characters that are significant in SQLite URIs
+    let path = path
+        .display()
+        .to_string()
+        .replace('%', "%25")
+        .replace('?', "%3f")
+        .replace('#', "%23");
+    with_busy_timeout(Connection::open_with_flags(
+        format!("file:{path}?{params}"),
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_URI,
+    ))
+}
+
+fn with_busy_timeout(conn: rusqlite::Result<Connection>) -> Result<Connection, String> {
+    conn.and_then(|conn| conn.busy_timeout(BUSY_TIMEOUT).map(|()| conn))
+        .map_err(|e| format!("Failed to connect to database: {e}"))
+}
+
+/// `chat.db` + `-wal` -> `chat.db-wal`
+fn sidecar_path(db_path: &Path, suffix: &str) -> PathBuf {
+    let mut name = db_path.as_os_str().to_os_string();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// Fold the copied WAL into the main file and leave WAL mode, so the
+/// immutable read-only open sees every committed message.
+fn checkpoint(db_path: &Path) -> rusqlite::Result<()> {
+    let conn = Connection::open(db_path)?;
+    conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))?;
+    conn.query_row("PRAGMA journal_mode = DELETE", [], |_| Ok(()))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn count_rows(conn: &Connection) -> i64 {
+        conn.query_row("SELECT COUNT(*) FROM t", [], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn snapshot_includes_uncheckpointed_wal_data() {
+        let dir = TempDir::new().unwrap();
+        let source = dir.path().join("chat.db");
+        let live = Connection::open(&source).unwrap();
+        live.query_row("PRAGMA journal_mode = WAL", [], |_| Ok(()))
+            .unwrap();
+        live.execute_batch(
+            "PRAGMA wal_autocheckpoint = 0;
+             CREATE TABLE t (x INTEGER);
+             INSERT INTO t VALUES (1), (2), (3);",
+        )
+        .unwrap();
+        // `live` stays open, like Messages.app, so the rows are only in the WAL
+        assert!(sidecar_path(&source, "-wal").exists());
+
+        let snapshot = ChatDbSnapshot::create(&source).unwrap();
+        assert_eq!(count_rows(&snapshot.connect().unwrap()), 3);
+    }
+
+    #[test]
+    fn snapshot_is_read_only_and_removed_on_drop() {
+        let dir = TempDir::new().unwrap();
+        let source = dir.path().join("chat.db");
+        Connection::open(&source)
+            .unwrap()
+            .execute_batch("CREATE TABLE t (x INTEGER); INSERT INTO t VALUES (1);")
+            .unwrap();
+
+        let snapshot = ChatDbSnapshot::create(&source).unwrap();
+        let snapshot_path = snapshot.path().to_path_buf();
+        let conn = snapshot.connect().unwrap();
+        assert!(conn.execute("INSERT INTO t VALUES (2)", []).is_err());
+        drop(conn);
+
+        drop(snapshot);
+        assert!(!snapshot_path.exists());
+        assert!(source.exists());
+    }
+
+    #[test]
+    fn snapshot_of_missing_file_fails() {
+        let dir = TempDir::new().unwrap();
+        assert!(ChatDbSnapshot::create(&dir.path().join("missing.db")).is_err());
+    }
+}
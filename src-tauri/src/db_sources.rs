@@ -0,0 +1,173 @@
+/*!
+ * Named chat.db sources: other Mac users' databases, or archived copies.
+ *
+ * Settings keep a list of [`DbSource`]s, each a database path with the
+ * vCard its contact names come from, and which one is selected (none =
+ * this Mac user's own chat.db, or `Settings::custom_db_path`). The chat
+ * list and exports read the selected source's database (see
+ * [`Settings::chat_db_path`]), and contact names come from its vCard as
+ * well as Contacts (see [`Settings::vcard_path`]).
+ */
+
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::settings::Settings;
+
+/// A chat.db to read instead of the Mac's own
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DbSource {
+    pub id: String,
+    /// Shown in the source picker ("Mum's Mac", "2019 backup")
+    pub name: String,
+    pub db_path: String,
+    /// vCard (`.vcf`) naming this database's contacts
+    #[serde(default)]
+    pub contacts_vcf_path: Option<String>,
+}
+
+/// The saved sources and the selected one, for `list_sources`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DbSourceList {
+    pub sources: Vec<DbSource>,
+    /// `None` = the Mac's own chat.db
+    pub selected_id: Option<String>,
+}
+
+impl Settings {
+    pub fn source_list(&self) -> DbSourceList {
+        DbSourceList {
+            sources: self.db_sources.clone(),
+            selected_id: self.selected_source_id.clone(),
+        }
+    }
+
+    /// The selected source, if it still exists
+    pub fn selected_source(&self) -> Option<&DbSource> {
+        let id = self.selected_source_id.as_deref()?;
+        self.db_sources.iter().find(|source| source.id == id)
+    }
+
+    /// The chat.db to read: the selected source's, else the saved custom
+    /// path (`None` = the Mac's own)
+    pub fn chat_db_path(&self) -> Option<&str> {
+        self.selected_source()
+            .map(|source| source.db_path.as_str())
+            .or(self.custom_db_path.as_deref())
+    }
+
+    /// The chat.db a command reads: the path the frontend passed, else
+    /// [`Self::chat_db_path`] (the frontend passes none with a source
+    /// selected)
+    pub fn db_path_or_saved(&self, custom_db_path: Option<String>) -> Option<PathBuf> {
+        custom_db_path
+            .or_else(|| self.chat_db_path().map(str::to_string))
+            .map(PathBuf::from)
+    }
+
+    /// The vCard to read contact names from: the selected source's, else
+    /// the saved one
+    pub fn vcard_path(&self) -> Option<&str> {
+        self.selected_source()
+            .and_then(|source| source.contacts_vcf_path.as_deref())
+            .or(self.contacts_vcf_path.as_deref())
+    }
+
+    /// Save a new source and return it (not selected)
+    pub fn add_source(
+        &mut self,
+        name: &str,
+        db_path: String,
+        contacts_vcf_path: Option<String>,
+    ) -> Result<DbSource, String> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err("Give the source a name".to_string());
+        }
+        if self.db_sources.iter().any(|source| source.name == name) {
+            return Err(format!("There is already a source named {name:?}"));
+        }
+        let source = DbSource {
+            id: uuid::Uuid::new_v4().to_string(),
+            name: name.to_string(),
+            db_path,
+            contacts_vcf_path,
+        };
+        self.db_sources.push(source.clone());
+        Ok(source)
+    }
+
+    /// Read the source with this ID from now on (`None` = the Mac's own)
+    pub fn select_source(&mut self, id: Option<&str>) -> Result<(), String> {
+        if let Some(id) = id {
+            if !self.db_sources.iter().any(|source| source.id == id) {
+                return Err(format!("No source with ID {id}"));
+            }
+        }
+        self.selected_source_id = id.map(str::to_string);
+        Ok(())
+    }
+
+    /// Forget a source, going back to the Mac's own if it was selected
+    pub fn remove_source(&mut self, id: &str) {
+        self.db_sources.retain(|source| source.id != id);
+        if self.selected_source_id.as_deref() == Some(id) {
+            self.selected_source_id = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_selected_source_picks_the_database_and_vcard() {
+        let mut settings = Settings {
+            custom_db_path: Some("/tmp/copy/chat.db".to_string()),
+            contacts_vcf_path: Some("/tmp/mine.vcf".to_string()),
+            ..Settings::default()
+        };
+        assert_eq!(settings.chat_db_path(), Some("/tmp/copy/chat.db"));
+
+        let archive = settings
+            .add_source(" 2019 archive ", "/tmp/2019/chat.db".to_string(), None)
+            .unwrap();
+        assert_eq!(archive.name, "2019 archive");
+        assert!(settings
+            .add_source("2019 archive", "/tmp/other.db".to_string(), None)
+            .is_err());
+        let other_user = settings
+            .add_source(
+                "Sam",
+                "/Users/sam/Library/Messages/chat.db".to_string(),
+                Some("/tmp/sam.vcf".to_string()),
+            )
+            .unwrap();
+        // Adding doesn't select
+        assert_eq!(settings.selected_source(), None);
+
+        settings.select_source(Some(&archive.id)).unwrap();
+        assert_eq!(settings.chat_db_path(), Some("/tmp/2019/chat.db"));
+        assert_eq!(settings.vcard_path(), Some("/tmp/mine.vcf"));
+        settings.select_source(Some(&other_user.id)).unwrap();
+        assert_eq!(settings.vcard_path(), Some("/tmp/sam.vcf"));
+        assert!(settings.select_source(Some("missing")).is_err());
+
+        // Commands read the selected source when the frontend passes no path
+        assert_eq!(
+            settings.db_path_or_saved(None),
+            Some(PathBuf::from("/Users/sam/Library/Messages/chat.db"))
+        );
+        assert_eq!(
+            settings.db_path_or_saved(Some("/tmp/other.db".to_string())),
+            Some(PathBuf::from("/tmp/other.db"))
+        );
+
+        settings.remove_source(&other_user.id);
+        assert_eq!(settings.source_list().selected_id, None);
+        assert_eq!(settings.source_list().sources, [archive]);
+        assert_eq!(settings.chat_db_path(), Some("/tmp/copy/chat.db"));
+    }
+}
@@ -5,8 +5,8 @@
 //! pointed at staging/local without rebuilding. Custom HTTP headers can also
 //! be injected for things like Cloudflare Access tokens.
 //!
-//! All commands operate on `crate::AppState` which is held by Tauri's managed
-//! state container.
+//! All commands operate on the host overrides in `crate::AppState`, which is
+//! held by Tauri's managed state container.
 
 use std::collections::HashMap;
 
@@ -15,18 +15,16 @@ use crate::AppState;
 /// Set the WEB host URL override — affects the results page link only.
 #[tauri::command]
 pub fn set_server_host(state: tauri::State<AppState>, host: Option<String>) {
-    let mut override_host = state.server_host_override.lock().unwrap();
-    eprintln!("[set_server_host] Setting host override to: {:?}", host);
-    *override_host = host;
+    tracing::info!("[set_server_host] Setting host override to: {:?}", host);
+    state.update_host_overrides(|overrides| overrides.server_host_override = host);
 }
 
 /// Get the current WEB host URL (with override applied) — the host the
 /// browser is opened to for the results page (chattomap.com).
 #[tauri::command]
 pub fn get_server_host(state: tauri::State<AppState>) -> String {
-    let override_host = state.server_host_override.lock().unwrap();
-    match override_host.as_ref() {
-        Some(host) if !host.is_empty() => host.clone(),
+    match state.host_overrides().server_host_override {
+        Some(host) if !host.is_empty() => host,
         _ => chat_to_map_desktop::upload::WEB_BASE_URL.to_string(),
     }
 }
@@ -34,17 +32,15 @@ pub fn get_server_host(state: tauri::State<AppState>) -> String {
 /// Set the API host URL override (Convex HTTP actions; for debugging).
 #[tauri::command]
 pub fn set_api_host(state: tauri::State<AppState>, host: Option<String>) {
-    let mut override_host = state.api_host_override.lock().unwrap();
-    eprintln!("[set_api_host] Setting API host override to: {:?}", host);
-    *override_host = host;
+    tracing::info!("[set_api_host] Setting API host override to: {:?}", host);
+    state.update_host_overrides(|overrides| overrides.api_host_override = host);
 }
 
 /// Get the current API host URL (with override applied) — Convex HTTP actions.
 #[tauri::command]
 pub fn get_api_host(state: tauri::State<AppState>) -> String {
-    let override_host = state.api_host_override.lock().unwrap();
-    match override_host.as_ref() {
-        Some(host) if !host.is_empty() => host.clone(),
+    match state.host_overrides().api_host_override {
+        Some(host) if !host.is_empty() => host,
         _ => chat_to_map_desktop::upload::API_BASE_URL.to_string(),
     }
 }
@@ -52,7 +48,6 @@ pub fn get_api_host(state: tauri::State<AppState>) -> String {
 /// Set custom headers for API requests (for debugging).
 #[tauri::command]
 pub fn set_custom_headers(state: tauri::State<AppState>, headers: HashMap<String, String>) {
-    let mut custom_headers = state.custom_headers.lock().unwrap();
-    eprintln!("[set_custom_headers] Setting {} headers", headers.len());
-    *custom_headers = headers;
+    tracing::info!("[set_custom_headers] Setting {} headers", headers.len());
+    state.update_host_overrides(|overrides| overrides.custom_headers = headers);
 }
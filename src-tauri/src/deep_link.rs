@@ -0,0 +1,88 @@
+/*!
+ * `chattomap://` links, which let the website hand control back to the app.
+ *
+ * The scheme is registered in the macOS bundle (src-tauri/Info.plist);
+ * macOS delivers opened links to the running app, which parses them here
+ * and routes each one to a command or the frontend (see main.rs).
+ *
+ * Supported links:
+ * - `chattomap://select-chats` - show the chat selection screen
+ * - `chattomap://job/<job_id>/retry` - queue the export behind an upload
+ *   again, by the processing job ID the website knows it by (the one in
+ *   the results page URL; see upload_history.rs)
+ */
+
+use serde::{Deserialize, Serialize};
+
+/// URL scheme registered for the app
+pub const SCHEME: &str = "chattomap";
+
+/// A parsed `chattomap://` link
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DeepLink {
+    SelectChats,
+    RetryJob { job_id: String },
+}
+
+/// Parse a `chattomap://` URL. Query strings, fragments and trailing
+/// slashes are ignored; anything else unrecognized is an error.
+pub fn parse_deep_link(url: &str) -> Result<DeepLink, String> {
+    let rest = url
+        .split_once("://")
+        .filter(|(scheme, _)| scheme.eq_ignore_ascii_case(SCHEME))
+        .map(|(_, rest)| rest)
+        .ok_or_else(|| format!("Not a {SCHEME}:// link: {url}"))?;
+    let path = rest.split(['?', '#']).next().unwrap_or_default();
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+
+    match segments.as_slice() {
+        ["select-chats"] => Ok(DeepLink::SelectChats),
+        ["job", job_id, "retry"] if is_valid_job_id(job_id) => Ok(DeepLink::RetryJob {
+            job_id: job_id.to_string(),
+        }),
+        _ => Err(format!("Unsupported link: {url}")),
+    }
+}
+
+/// Job IDs are server document IDs; accept only their character set
+fn is_valid_job_id(job_id: &str) -> bool {
+    job_id
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_supported_links() {
+        assert_eq!(
+            parse_deep_link("chattomap://select-chats"),
+            Ok(DeepLink::SelectChats)
+        );
+        assert_eq!(
+            parse_deep_link("ChatToMap://job/abc-123/retry/?from=web#top"),
+            Ok(DeepLink::RetryJob {
+                job_id: "abc-123".to_string()
+            })
+        );
+        assert_eq!(
+            serde_json::to_value(DeepLink::SelectChats).unwrap()["kind"],
+            "select_chats"
+        );
+    }
+
+    #[test]
+    fn rejects_other_links() {
+        for url in [
+            "https://chattomap.com/select-chats",
+            "chattomap://job/abc123",
+            "chattomap://job/abc%2F..%2F/retry",
+            "chattomap://upload-everything",
+        ] {
+            assert!(parse_deep_link(url).is_err(), "{url}");
+        }
+    }
+}
@@ -0,0 +1,66 @@
+//! Bridge from `chattomap://` links to the app. macOS delivers links
+//! opened while the app runs (or that launch it) as `RunEvent::Opened`;
+//! each is parsed (see `chat_to_map_desktop::deep_link`) and routed to the
+//! command it stands for. A link that can't be followed is reported to the
+//! frontend with a `deep-link-error` event. Links aren't delivered on other
+//! platforms.
+
+#[cfg(target_os = "macos")]
+use chat_to_map_desktop::{
+    deep_link::{parse_deep_link, DeepLink},
+    upload_history,
+};
+#[cfg(target_os = "macos")]
+use tauri::{Emitter, Manager};
+
+#[cfg(target_os = "macos")]
+use crate::{queue_commands, settings_commands::app_local_data_dir, AppState};
+
+/// App-level events passed to `App::run`
+#[cfg(target_os = "macos")]
+pub fn on_run_event(app: &tauri::AppHandle, event: tauri::RunEvent) {
+    if let tauri::RunEvent::Opened { urls } = event {
+        for url in urls {
+            handle_deep_link(app, url.as_str());
+        }
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn on_run_event(_app: &tauri::AppHandle, _event: tauri::RunEvent) {}
+
+/// Route a `chattomap://` link (see `chat_to_map_desktop::deep_link`):
+/// bring the window forward, run the command it stands for, and tell the
+/// frontend with a `deep-link` event (or `deep-link-error` if it failed)
+#[cfg(target_os = "macos")]
+fn handle_deep_link(app: &tauri::AppHandle, url: &str) {
+    let link = match parse_deep_link(url) {
+        Ok(link) => link,
+        Err(e) => {
+            tracing::warn!("[deep-link] Ignoring link: {e}");
+            return;
+        }
+    };
+    tracing::info!("[deep-link] Opened {link:?}");
+    let Some(window) = app.get_webview_window("main") else {
+        tracing::warn!("[deep-link] No window to route {link:?} to");
+        return;
+    };
+    let _ = window.set_focus();
+    if let DeepLink::RetryJob { job_id } = &link {
+        let request =
+            app_local_data_dir(app).and_then(|dir| upload_history::rerun_request(&dir, job_id));
+        match request {
+            Ok(request) => {
+                let state = app.state::<AppState>();
+                queue_commands::enqueue(app, &state, window.as_ref().window(), request);
+            }
+            Err(e) => {
+                tracing::warn!("[deep-link] {e}");
+                let _ = window.emit("deep-link-error", e);
+                return;
+            }
+        }
+    }
+    let _ = window.emit("deep-link", &link);
+}
@@ -0,0 +1,84 @@
+//! Opt-in anonymous error reports (see the library's telemetry.rs).
+//!
+//! At startup the saved setting turns reporting on or off, the panic hook
+//! is installed, and reports left from the last session are sent. Failed
+//! exports are reported as `record_run` sees them (see run_history.rs).
+//! `set_error_reporting` is the toggle in settings.
+
+use chat_to_map_desktop::{
+    export::state::ExportError,
+    settings::Settings,
+    telemetry::{self, ErrorReport},
+    upload::UploadTimeouts,
+};
+use tauri::Manager;
+
+use crate::{
+    settings_commands::{app_local_data_dir, load_settings},
+    AppState,
+};
+
+/// Apply the saved setting, catch panics and send last session's reports
+pub(crate) fn init(app_handle: &tauri::AppHandle, settings: &Settings) {
+    telemetry::set_enabled(settings.error_reporting);
+    let Ok(dir) = app_local_data_dir(app_handle) else {
+        return;
+    };
+    telemetry::install_panic_hook(dir);
+    send_pending(app_handle);
+}
+
+/// Queue a report for a failed export and send it
+pub(crate) fn report_export_error(app_handle: &tauri::AppHandle, error: &ExportError) {
+    let Some(report) = ErrorReport::export_error(error) else {
+        return;
+    };
+    let recorded = app_local_data_dir(app_handle).and_then(|dir| telemetry::record(&dir, &report));
+    if let Err(e) = recorded {
+        tracing::debug!("[telemetry] Failed to queue report: {e}");
+    }
+    send_pending(app_handle);
+}
+
+/// Send anonymous crash and error reports from now on (or stop), and drop
+/// any still queued when turned off
+#[tauri::command]
+pub fn set_error_reporting(enabled: bool, app_handle: tauri::AppHandle) -> Result<(), String> {
+    tracing::info!("[set_error_reporting] {enabled}");
+    let dir = app_local_data_dir(&app_handle)?;
+    let mut settings = Settings::load(&dir);
+    settings.error_reporting = enabled;
+    settings.save(&dir)?;
+    telemetry::set_enabled(enabled);
+    if !enabled {
+        telemetry::clear_pending(&dir)?;
+    }
+    Ok(())
+}
+
+fn send_pending(app_handle: &tauri::AppHandle) {
+    if !telemetry::is_enabled() {
+        return;
+    }
+    let app_handle = app_handle.clone();
+    tauri::async_runtime::spawn(async move {
+        let overrides = app_handle.state::<AppState>().host_overrides();
+        let sent = match (app_local_data_dir(&app_handle), load_settings(&app_handle)) {
+            (Ok(dir), Ok(settings)) => telemetry::send_pending(
+                &dir,
+                overrides.api_host_override.as_deref(),
+                &overrides.custom_headers,
+                &UploadTimeouts::from_settings(&settings),
+            )
+            .await
+            .map_err(|e| e.to_string()),
+            (Err(e), _) | (_, Err(e)) => Err(e),
+        };
+        match sent {
+            Ok(0) => {}
+            Ok(count) => tracing::info!("[telemetry] Sent {count} error reports"),
+            // Stays queued for next time
+            Err(e) => tracing::debug!("[telemetry] Failed to send error reports: {e}"),
+        }
+    });
+}
@@ -0,0 +1,139 @@
+/*!
+ * Chat-level export exclusion rules.
+ *
+ * Some chats should never be uploaded: bank and carrier short codes, OTP
+ * senders, a particular person. Users define rules once (persisted in
+ * [`crate::settings::Settings`]); `list_chats` flags matching chats as
+ * `excluded` and `export_chats` skips them even if they are selected.
+ */
+
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::ChatInfo;
+
+/// Longest all-digit identifier treated as a short code
+const MAX_SHORT_CODE_DIGITS: usize = 6;
+
+/// A rule matching chats to exclude
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ExclusionRule {
+    /// Short-code senders: identifiers of 3–6 digits (OTPs, banks, carriers)
+    ShortCode,
+    /// Chats whose display name contains `text` (case-insensitive)
+    NameContains { text: String },
+    /// Chat identifiers matching `pattern`; `*` matches any run of
+    /// characters, e.g. `+1555*` or `*@example.com` (case-insensitive)
+    Identifier { pattern: String },
+}
+
+impl ExclusionRule {
+    /// Whether a chat with this identifier and display name matches
+    pub fn matches(&self, chat_identifier: &str, display_name: &str) -> bool {
+        match self {
+            Self::ShortCode => is_short_code(chat_identifier),
+            Self::NameContains { text } => {
+                !text.trim().is_empty()
+                    && display_name
+                        .to_lowercase()
+                        .contains(&text.trim().to_lowercase())
+            }
+            Self::Identifier { pattern } => glob_matches(
+                &pattern.trim().to_lowercase(),
+                &chat_identifier.to_lowercase(),
+            ),
+        }
+    }
+}
+
+/// Whether any rule excludes the chat
+pub fn is_excluded(rules: &[ExclusionRule], chat_identifier: &str, display_name: &str) -> bool {
+    rules
+        .iter()
+        .any(|rule| rule.matches(chat_identifier, display_name))
+}
+
+/// Chats that `rule` currently matches, for previewing a rule before saving it
+pub fn matching_chats(
+    rule: &ExclusionRule,
+    custom_db_path: Option<&Path>,
+) -> Result<Vec<ChatInfo>, String> {
+    let chats = crate::list_chats(custom_db_path, std::slice::from_ref(rule))?;
+    Ok(chats.into_iter().filter(|chat| chat.excluded).collect())
+}
+
+pub(crate) fn is_short_code(identifier: &str) -> bool {
+    (3..=MAX_SHORT_CODE_DIGITS).contains(&identifier.len())
+        && identifier.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Match `text` against `pattern`, where `*` matches any run of characters
+fn glob_matches(pattern: &str, text: &str) -> bool {
+    if pattern.is_empty() {
+        return false;
+    }
+    let mut parts = pattern.split('*');
+    // `split` always yields at least one part
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        // No `*`: exact match
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_codes_are_three_to_six_digits() {
+        let rule = ExclusionRule::ShortCode;
+        assert!(rule.matches("72345", "72345"));
+        assert!(rule.matches("262966", "262966"));
+        assert!(!rule.matches("+15551234567", "Alice"));
+        assert!(!rule.matches("chat123456", "Trip"));
+    }
+
+    #[test]
+    fn name_and_identifier_rules_are_case_insensitive() {
+        let otp = ExclusionRule::NameContains {
+            text: "OTP".to_string(),
+        };
+        assert!(otp.matches("12345", "Bank otp codes"));
+        assert!(!otp.matches("12345", "Alice"));
+
+        let domain = ExclusionRule::Identifier {
+            pattern: "*@Example.com".to_string(),
+        };
+        assert!(domain.matches("alerts@example.COM", "Alerts"));
+        assert!(!domain.matches("alice@example.org", "Alice"));
+
+        let exact = ExclusionRule::Identifier {
+            pattern: "+15551234567".to_string(),
+        };
+        assert!(exact.matches("+15551234567", "Alice"));
+        assert!(!exact.matches("+155512345678", "Alice"));
+    }
+
+    #[test]
+    fn glob_handles_multiple_wildcards() {
+        assert!(glob_matches("+1*55*", "+1415559999"));
+        assert!(glob_matches("a*a", "aa"));
+        assert!(!glob_matches("a*a", "a"));
+        assert!(!glob_matches("", "anything"));
+        assert!(glob_matches("*", "anything"));
+    }
+}
@@ -5,76 +5,69 @@
  * compatible with the ChatToMap SaaS processing pipeline.
  */
 
-use std::{
-    collections::{BTreeSet, HashMap},
-    fs::File,
-    io::{BufWriter, Write},
-    path::PathBuf,
+pub mod activity;
+pub mod archive;
+pub mod chat_files;
+mod chats;
+mod cleaning;
+mod compression;
+mod content;
+pub mod diff;
+pub mod disk_space;
+pub mod estimate;
+pub mod events;
+pub mod filenames;
+pub mod filters;
+mod format;
+mod groups;
+mod links;
+pub mod merge;
+pub mod metrics;
+mod options;
+pub mod orphans;
+mod parts;
+pub mod preflight;
+pub mod preview;
+pub mod queue;
+pub mod readme;
+mod recovery;
+pub mod redaction;
+pub mod schedule;
+mod senders;
+pub mod state;
+mod stream;
+mod timestamps;
+mod truncation;
+pub mod verify;
+
+pub use chat_files::ExportFormat;
+pub use compression::CompressionProfile;
+pub use events::{GroupEvent, GroupEventKind};
+pub use format::{
+    ExportedAttachment, ExportedChat, ExportedChatMeta, ExportedMessage, ExportedParticipant,
+    LinkPreview, MessageMetadata,
 };
-
-use chrono::{DateTime, Local, TimeZone};
-use imessage_database::{
-    tables::{
-        chat::Chat,
-        chat_handle::ChatToHandle,
-        handle::Handle,
-        messages::Message,
-        table::{get_connection, Cacheable, Deduplicate, Table},
-    },
-    util::{dirs::default_db_path, query_context::QueryContext},
+pub use options::ExportOptions;
+pub(crate) use senders::SenderNames;
+pub use timestamps::ExportTimezone;
+pub(crate) use timestamps::{
+    format_timestamp, format_timestamp_in, format_unix_timestamp, to_imessage_timestamp,
+    TimestampUnit,
 };
+
+use std::{collections::BTreeSet, path::PathBuf, time::Instant};
+
+use imessage_database::{tables::messages::Message, util::query_context::QueryContext};
 use serde::{Deserialize, Serialize};
 use tempfile::TempDir;
-use zip::{write::SimpleFileOptions, ZipWriter};
 
-use crate::contacts::{ContactsIndex, Name};
+use crate::{exclusions::is_excluded, shared_links::read_shared_links};
+use chats::ChatDirectory;
 
 // =============================================================================
 // Types
 // =============================================================================
 
-/// A single exported message in our JSON format
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ExportedMessage {
-    /// ISO 8601 timestamp
-    pub timestamp: String,
-    /// Sender name or phone/email
-    pub sender: String,
-    /// Whether this message is from the device owner
-    pub is_from_me: bool,
-    /// Message text content
-    pub text: String,
-}
-
-/// Metadata about an exported chat.
-///
-/// `participant_count` is the number of distinct people in the chat OTHER
-/// than the device owner (1 for a 1:1 chat, N for a group of N+1 people).
-/// The SaaS uses this to format the display title — see
-/// `convex/uploadPlatform.ts:deriveIMessageDisplayTitle`.
-#[derive(Debug, Serialize, Deserialize)]
-pub struct ExportedChatMeta {
-    /// Resolved chat display name. Falls back from custom group name → 1:1
-    /// contact name → identifier → "Chat <id>". Same resolution as the
-    /// chat list UI.
-    pub name: String,
-    /// Raw chat identifier (phone number, email, or group ID)
-    pub identifier: String,
-    /// Service (iMessage, SMS)
-    pub service: String,
-    /// Number of messages exported
-    pub message_count: usize,
-    /// Number of OTHER participants (excludes device owner). 1 = 1:1 chat.
-    pub participant_count: usize,
-}
-
-/// Complete export data for a single chat
-#[derive(Debug, Serialize, Deserialize)]
-pub struct ExportedChat {
-    pub meta: ExportedChatMeta,
-    pub messages: Vec<ExportedMessage>,
-}
-
 /// Progress callback signature
 pub type ProgressCallback = Box<dyn Fn(ExportProgress) + Send + Sync>;
 
@@ -84,6 +77,9 @@ pub struct ExportProgress {
     pub stage: String,
     pub percent: u8,
     pub message: String,
+    /// Export the update belongs to; set by the app, `None` from the library
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub export_id: Option<String>,
 }
 
 /// Export result
@@ -91,23 +87,36 @@ pub struct ExportProgress {
 pub struct ExportResult {
     /// Path to the zip file
     pub zip_path: PathBuf,
+    /// SHA-256 of the finished zip (lowercase hex), sent with the upload so
+    /// the server can reject a corrupted transfer before processing
+    pub zip_sha256: String,
     /// Temporary directory (kept alive until result is dropped)
     pub _temp_dir: TempDir,
     /// Total messages exported
     pub total_messages: usize,
     /// Number of chats exported
     pub chat_count: usize,
+    /// Messages whose text was cut to `ExportOptions::max_text_length`
+    pub truncated_messages: usize,
+    /// Messages from Recently Deleted, with
+    /// `ExportOptions::include_recently_deleted`
+    pub deleted_messages: usize,
+    /// Rows that could only be partly read (lossy text, undecodable body,
+    /// unloadable row); also listed in the manifest
+    pub warnings: Vec<String>,
+    /// Messages without a chat that were placed in an exported one, and
+    /// those (in the whole database) that fit no chat
+    pub orphans: orphans::OrphanCounts,
+    /// Decode and compression throughput
+    pub metrics: metrics::ExportMetrics,
 }
 
 // =============================================================================
 // Constants
 // =============================================================================
 
-/// iMessage timestamp epoch offset (2001-01-01 vs 1970-01-01)
-const APPLE_EPOCH_OFFSET: i64 = 978_307_200;
-
-/// Nanoseconds factor for iMessage timestamps
-const TIMESTAMP_FACTOR: i64 = 1_000_000_000;
+/// Platform name sent to the server (and written to the manifest) for iMessage exports
+pub const UPLOAD_PLATFORM: &str = "imessage";
 
 // =============================================================================
 // Export Implementation
@@ -117,15 +126,20 @@ const TIMESTAMP_FACTOR: i64 = 1_000_000_000;
 ///
 /// # Arguments
 /// * `chat_ids` - List of chat ROWIDs to export
+/// * `options` - What to include beyond the message text
 /// * `progress_callback` - Optional callback for progress updates
 ///
 /// # Returns
 /// * `ExportResult` containing the zip file path and metadata
+/// * `ExportError::InsufficientDiskSpace` before the snapshot or the zip
+///   would fill the temp volume; `ExportError::Failed` otherwise
 pub fn export_chats(
     chat_ids: &[i32],
+    options: &ExportOptions,
     progress_callback: Option<ProgressCallback>,
     custom_db_path: Option<&std::path::Path>,
-) -> Result<ExportResult, String> {
+) -> Result<ExportResult, state::ExportError> {
+    let started = Instant::now();
     let emit_progress = |progress: ExportProgress| {
         if let Some(ref cb) = progress_callback {
             cb(progress);
@@ -136,195 +150,237 @@ pub fn export_chats(
         stage: "Initializing".to_string(),
         percent: 0,
         message: "Connecting to iMessage database...".to_string(),
+        export_id: None,
     });
 
-    // Connect to database
-    let db_path = custom_db_path
-        .map(|p| p.to_path_buf())
-        .unwrap_or_else(default_db_path);
-    let db = get_connection(&db_path).map_err(|e| format!("Failed to connect to database: {e}"))?;
-
-    // Build contacts index for name resolution
-    let contacts_index = ContactsIndex::build(None).unwrap_or_default();
+    // Connect to database (the live default DB is read through a snapshot)
+    let temp_dir = std::env::temp_dir();
+    let snapshot_bytes = crate::db_snapshot::snapshot_bytes(custom_db_path);
+    disk_space::ensure_space(&temp_dir, snapshot_bytes)?;
+    let chat_db = crate::db_snapshot::open_chat_db(custom_db_path)?;
+    let db = &chat_db.conn;
+    let unit = TimestampUnit::detect(db);
 
-    // Cache handles for participant name lookup
-    let handles = Handle::cache(&db).map_err(|e| format!("Failed to load handles: {e}"))?;
-    let deduped_handles = Handle::dedupe(&handles);
-    let participants_map = contacts_index.build_participants_map(&handles, &deduped_handles);
+    // Contacts-aware sender names
+    let senders = SenderNames::load(db)?;
 
     // Cache chats for metadata
-    let chats = Chat::cache(&db).map_err(|e| format!("Failed to load chats: {e}"))?;
-    // Per-chat participant handle IDs — used to resolve 1:1 chat display
-    // names from the contact's name (instead of falling back to the chat ID)
-    // and to count other-participants for the title (e.g. "and N others").
-    let chat_participants =
-        ChatToHandle::cache(&db).map_err(|e| format!("Failed to load chat participants: {e}"))?;
+    let directory = ChatDirectory::load(db, &senders)?;
 
     emit_progress(ExportProgress {
         stage: "Preparing".to_string(),
         percent: 5,
         message: "Counting messages...".to_string(),
+        export_id: None,
     });
 
+    // Every chat in a merged group is exported under the group's first ID
+    let canonical_ids = groups::canonical_chat_ids(&options.chat_groups);
+    let mut selected_ids: BTreeSet<i32> = chat_ids
+        .iter()
+        .chain(canonical_ids.keys())
+        .copied()
+        .collect();
+
+    // Chats matching an exclusion rule are never exported, even if selected
+    let selected_count = selected_ids.len();
+    selected_ids.retain(|id| {
+        let Some(chat) = directory.chats.get(id) else {
+            return true;
+        };
+        let name = directory.name(chat, *id, &senders);
+        !is_excluded(&options.exclusion_rules, &chat.chat_identifier, &name)
+    });
+    let excluded_chats = selected_count - selected_ids.len();
+    // An empty selection would make the query match every chat
+    if excluded_chats > 0 && selected_ids.is_empty() {
+        return Err("All selected chats are excluded by exclusion rules"
+            .to_string()
+            .into());
+    }
+
+    let archive_bytes = estimate::archive_bytes(db, selected_ids.iter().copied(), unit)?;
+    disk_space::ensure_space(&temp_dir, disk_space::with_margin(archive_bytes))?;
+
     // Set up query context with selected chat IDs
     let mut query_context = QueryContext::default();
-    query_context.set_selected_chat_ids(chat_ids.iter().copied().collect::<BTreeSet<_>>());
+    query_context.set_selected_chat_ids(selected_ids.clone());
 
     // Get total message count for progress tracking
-    let total_messages = Message::get_count(&db, &query_context)
+    let total_messages = Message::get_count(db, &query_context)
         .map_err(|e| format!("Failed to count messages: {e}"))?;
 
     emit_progress(ExportProgress {
         stage: "Exporting".to_string(),
         percent: 10,
         message: format!("Exporting {} messages...", total_messages),
+        export_id: None,
     });
 
-    // Stream messages and group by chat
-    let mut messages_by_chat: HashMap<i32, Vec<ExportedMessage>> = HashMap::new();
-    let mut processed: usize = 0;
-
-    Message::stream(&db, |message_result| {
-        match message_result {
-            Ok(mut message) => {
-                // Filter to selected chats
-                if let Some(chat_id) = message.chat_id {
-                    if chat_ids.contains(&chat_id) {
-                        // Generate text content (deserializes protobuf/plist)
-                        let _ = message.generate_text(&db);
-
-                        // Get sender name
-                        let sender = get_sender_name(
-                            &message,
-                            &handles,
-                            &deduped_handles,
-                            &participants_map,
-                        );
-
-                        // Convert timestamp
-                        let timestamp = format_timestamp(message.date);
-
-                        // Get message text (skip empty messages)
-                        if let Some(text) = message.text.as_ref() {
-                            if !text.is_empty() {
-                                let exported = ExportedMessage {
-                                    timestamp,
-                                    sender,
-                                    is_from_me: message.is_from_me,
-                                    text: text.clone(),
-                                };
-
-                                messages_by_chat.entry(chat_id).or_default().push(exported);
-                            }
-                        }
-
-                        processed += 1;
-
-                        // Update progress every 100 messages
-                        if processed % 100 == 0 {
-                            let percent =
-                                10 + (processed as u64 * 70 / total_messages.max(1)) as u8;
-                            emit_progress(ExportProgress {
-                                stage: "Exporting".to_string(),
-                                percent: percent.min(80),
-                                message: format!(
-                                    "Processed {} of {} messages",
-                                    processed, total_messages
-                                ),
-                            });
-                        }
-                    }
-                }
-            }
-            Err(e) => {
-                eprintln!("Error reading message: {:?}", e);
-            }
+    // Stream messages and group by chat, one worker per batch of chats
+    let ctx = stream::StreamContext {
+        options,
+        senders: &senders,
+        canonical_ids: &canonical_ids,
+        unit,
+        since: options
+            .since
+            .map(|since| to_imessage_timestamp(since.timestamp(), unit)),
+    };
+    let on_progress = |processed: usize| {
+        let percent = 10 + (processed as u64 * 70 / total_messages.max(1)) as u8;
+        emit_progress(ExportProgress {
+            stage: "Exporting".to_string(),
+            percent: percent.min(80),
+            message: format!("Processed {} of {} messages", processed, total_messages),
+            export_id: None,
+        });
+    };
+    let mut streamed = stream::stream_messages(&chat_db, &selected_ids, &ctx, None, &on_progress)?;
+
+    // Rows that failed to load are re-read from their raw columns, and
+    // messages that lost their chat are placed back in one
+    let mut recovered_chats = BTreeSet::new();
+    if streamed.unreadable_rows > 0 {
+        for row in recovery::unread_rows(db, &selected_ids, &streamed.seen_rowids)? {
+            streamed.warnings.push(format!(
+                "Message {}: row could not be read and was recovered from raw columns",
+                row.rowid
+            ));
+            recovered_chats.extend(streamed.add_raw(row, &ctx));
+        }
+    }
+    let found = orphans::find_orphans(db, unit)?;
+    let mut orphan_counts = orphans::OrphanCounts {
+        recovered: 0,
+        unassignable: found.unassignable,
+    };
+    for row in found.assigned {
+        if selected_ids.contains(&row.chat_id) {
+            orphan_counts.recovered += 1;
+            recovered_chats.extend(streamed.add_raw(row, &ctx));
         }
-        Ok::<(), String>(())
-    })
-    .map_err(|e| format!("Failed to stream messages: {e}"))?;
+    }
+    if !orphan_counts.is_empty() {
+        tracing::info!("[export] Orphaned messages: {:?}", orphan_counts);
+    }
+    // Recovered messages were appended; put them back in date order
+    for chat_id in recovered_chats {
+        if let Some(messages) = streamed.messages_by_chat.get_mut(&chat_id) {
+            messages.sort_by_key(|m| chrono::DateTime::parse_from_rfc3339(&m.timestamp).ok());
+        }
+    }
+    let stream::Streamed {
+        messages_by_chat,
+        processed,
+        filtered_out,
+        warnings,
+        ..
+    } = streamed;
+    if !warnings.is_empty() {
+        tracing::warn!("[export] {} message rows had read warnings", warnings.len());
+    }
 
     emit_progress(ExportProgress {
         stage: "Packaging".to_string(),
         percent: 85,
         message: "Creating export package...".to_string(),
+        export_id: None,
     });
 
-    // Create temp directory for export
-    let temp_dir = TempDir::new().map_err(|e| format!("Failed to create temp directory: {e}"))?;
-
     // Build exported chats
-    let mut exported_chats = Vec::new();
-    for (&chat_id, messages) in &messages_by_chat {
-        let chat = chats.get(&chat_id);
-        let participants = chat_participants.get(&chat_id);
-        let identifier = chat.map(|c| c.chat_identifier.clone()).unwrap_or_default();
-        let resolved_name = chat
-            .map(|c| {
-                crate::resolve_chat_display_name(
-                    c,
-                    participants,
-                    &participants_map,
-                    &deduped_handles,
-                )
-            })
-            .filter(|s| !s.is_empty())
-            // Last-resort fallbacks: identifier (the phone/email/group ID),
-            // then a stable synthetic name. Both should be rare — the
-            // resolver almost always returns something useful.
-            .or_else(|| (!identifier.is_empty()).then(|| identifier.clone()))
-            .unwrap_or_else(|| format!("Chat {}", chat_id));
-        let meta = ExportedChatMeta {
-            name: resolved_name,
-            identifier,
-            service: chat
-                .and_then(|c| c.service_name.clone())
-                .unwrap_or_else(|| "Unknown".to_string()),
-            message_count: messages.len(),
-            participant_count: participants.map(|p| p.len()).unwrap_or(0),
-        };
+    let mut exported_chats: Vec<ExportedChat> = messages_by_chat
+        .into_iter()
+        .map(|(chat_id, messages)| {
+            directory.exported_chat(chat_id, messages, &senders, &options.chat_groups)
+        })
+        .collect();
+
+    // Sort by message count descending; ties by GUID, so file names are stable
+    exported_chats
+        .sort_by(|a, b| (b.messages.len(), &a.meta.guid).cmp(&(a.messages.len(), &b.meta.guid)));
+
+    let mut shared_links = if options.include_shared_links {
+        let selected_ids: Vec<i32> = selected_ids.iter().copied().collect();
+        Some(read_shared_links(
+            db,
+            &selected_ids,
+            |message| senders.name(message.is_from_me, message.handle_id),
+            |date| format_timestamp_in(date, unit, options.timezone),
+        )?)
+    } else {
+        None
+    };
 
-        exported_chats.push(ExportedChat {
-            meta,
-            messages: messages.clone(),
-        });
+    let redaction = redaction::redact(
+        &mut exported_chats,
+        shared_links.as_mut(),
+        &options.redaction,
+    );
+    let processed =
+        processed - filtered_out - redaction.as_ref().map_or(0, |r| r.dropped_message_count);
+
+    let mut manifest = archive::new_manifest(
+        UPLOAD_PLATFORM,
+        &exported_chats,
+        processed,
+        options.format,
+        options.timezone,
+    );
+    manifest["compression"] = serde_json::to_value(options.compression).unwrap();
+    if let Some(max_messages_per_file) = options.max_messages_per_file {
+        manifest["max_messages_per_file"] = max_messages_per_file.into();
     }
-
-    // Sort by message count descending
-    exported_chats.sort_by_key(|c| std::cmp::Reverse(c.messages.len()));
-
-    // Write each chat to a separate JSON file and create zip
-    let zip_path = temp_dir.path().join("export.zip");
-    let zip_file = File::create(&zip_path).map_err(|e| format!("Failed to create zip: {e}"))?;
-    let mut zip = ZipWriter::new(BufWriter::new(zip_file));
-
-    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
-
-    // Write manifest
-    let manifest = serde_json::json!({
-        "version": "1.0",
-        "source": "imessage",
-        "export_date": chrono::Utc::now().to_rfc3339(),
-        "chat_count": exported_chats.len(),
-        "total_messages": processed,
-    });
-
-    zip.start_file("manifest.json", options)
-        .map_err(|e| format!("Failed to write manifest: {e}"))?;
-    zip.write_all(serde_json::to_string_pretty(&manifest).unwrap().as_bytes())
-        .map_err(|e| format!("Failed to write manifest: {e}"))?;
-
-    // Write each chat
-    for (i, chat) in exported_chats.iter().enumerate() {
-        let filename = format!("chat_{:03}.json", i);
-        zip.start_file(&filename, options)
-            .map_err(|e| format!("Failed to write chat: {e}"))?;
-        zip.write_all(serde_json::to_string_pretty(&chat).unwrap().as_bytes())
-            .map_err(|e| format!("Failed to write chat: {e}"))?;
+    if let Some(filters) = options.filter.summary(filtered_out) {
+        manifest["filters"] = serde_json::to_value(filters).unwrap();
     }
-
-    zip.finish()
-        .map_err(|e| format!("Failed to finalize zip: {e}"))?;
+    if let Some(since) = options.since {
+        manifest["since"] = since.to_rfc3339().into();
+    }
+    if let Some(redaction) = &redaction {
+        manifest["redaction"] = serde_json::to_value(redaction).unwrap();
+    }
+    manifest["database"] = serde_json::to_value(crate::db_origin::detect_origin(db)).unwrap();
+    if !options.metadata.is_empty() {
+        manifest["metadata"] = serde_json::to_value(&options.metadata).unwrap();
+    }
+    if let Some(max_text_length) = options.max_text_length {
+        manifest["max_text_length"] = max_text_length.into();
+        manifest["truncated_message_count"] = archive::truncated_messages(&exported_chats).into();
+    }
+    if options.include_metadata {
+        manifest["message_metadata"] = true.into();
+    }
+    if options.raw_text {
+        manifest["raw_text"] = true.into();
+    }
+    if options.include_recently_deleted {
+        manifest["include_recently_deleted"] = true.into();
+        manifest["recently_deleted_count"] = archive::deleted_messages(&exported_chats).into();
+    }
+    if excluded_chats > 0 {
+        manifest["excluded_chat_count"] = excluded_chats.into();
+    }
+    if !orphan_counts.is_empty() {
+        manifest["orphaned_messages"] = serde_json::to_value(orphan_counts).unwrap();
+    }
+    if !warnings.is_empty() {
+        manifest["warning_count"] = warnings.len().into();
+        manifest["warnings"] = serde_json::to_value(&warnings).unwrap();
+    }
+    let mut extra_files = Vec::new();
+    if let Some(links) = &shared_links {
+        manifest["shared_link_count"] = links.len().into();
+        extra_files.push((
+            "shared_links.json".to_string(),
+            serde_json::to_string_pretty(links).unwrap(),
+        ));
+    }
+    let decode = started.elapsed();
+    let mut result = archive::write_archive(&manifest, &exported_chats, &extra_files, processed)?;
+    result.warnings = warnings;
+    result.orphans = orphan_counts;
+    result.metrics.record_decode(decode);
 
     emit_progress(ExportProgress {
         stage: "Complete".to_string(),
@@ -334,63 +390,10 @@ pub fn export_chats(
             processed,
             exported_chats.len()
         ),
+        export_id: None,
     });
 
-    Ok(ExportResult {
-        zip_path,
-        _temp_dir: temp_dir,
-        total_messages: processed,
-        chat_count: exported_chats.len(),
-    })
-}
-
-// =============================================================================
-// Helper Functions
-// =============================================================================
-
-/// Get sender name for a message
-fn get_sender_name(
-    message: &Message,
-    handles: &HashMap<i32, String>,
-    deduped_handles: &HashMap<i32, i32>,
-    participants_map: &HashMap<i32, Name>,
-) -> String {
-    if message.is_from_me {
-        return "Me".to_string();
-    }
-
-    if let Some(handle_id) = message.handle_id {
-        // Look up deduped ID first
-        if let Some(&deduped_id) = deduped_handles.get(&handle_id) {
-            if let Some(name) = participants_map.get(&deduped_id) {
-                let display = name.get_display_name();
-                if !display.is_empty() {
-                    return display.to_string();
-                }
-            }
-        }
-
-        // Fall back to raw handle ID (phone/email)
-        if let Some(handle_id_str) = handles.get(&handle_id) {
-            return handle_id_str.clone();
-        }
-    }
-
-    "Unknown".to_string()
-}
-
-/// Convert iMessage timestamp to ISO 8601 string
-fn format_timestamp(imessage_timestamp: i64) -> String {
-    // iMessage timestamps are nanoseconds since 2001-01-01
-    let unix_timestamp = (imessage_timestamp / TIMESTAMP_FACTOR) + APPLE_EPOCH_OFFSET;
-
-    match DateTime::from_timestamp(unix_timestamp, 0) {
-        Some(dt) => {
-            let local: DateTime<Local> = Local.from_utc_datetime(&dt.naive_utc());
-            local.to_rfc3339()
-        }
-        None => chrono::Utc::now().to_rfc3339(),
-    }
+    Ok(result)
 }
 
 // =============================================================================
@@ -398,31 +401,9 @@ fn format_timestamp(imessage_timestamp: i64) -> String {
 // =============================================================================
 
 #[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_format_timestamp() {
-        // 2024-01-01 00:00:00 UTC in iMessage timestamp format
-        // Unix: 1704067200, iMessage: (1704067200 - 978307200) * 1_000_000_000
-        let imessage_ts = (1704067200_i64 - APPLE_EPOCH_OFFSET) * TIMESTAMP_FACTOR;
-        let result = format_timestamp(imessage_ts);
-
-        // Should contain 2024-01-01
-        assert!(result.contains("2024-01-01") || result.contains("2023-12-31"));
-    }
+#[path = "export_tests.rs"]
+mod tests;
 
-    #[test]
-    fn test_exported_message_serialization() {
-        let msg = ExportedMessage {
-            timestamp: "2024-01-01T12:00:00+00:00".to_string(),
-            sender: "Alice".to_string(),
-            is_from_me: false,
-            text: "Hello world".to_string(),
-        };
-
-        let json = serde_json::to_string(&msg).unwrap();
-        assert!(json.contains("Alice"));
-        assert!(json.contains("Hello world"));
-    }
-}
+#[cfg(test)]
+#[path = "export_content_tests.rs"]
+mod content_tests;
@@ -0,0 +1,217 @@
+/*!
+ * Activity summary of one chat, for the selection screen.
+ *
+ * Alongside the message preview, the selection screen draws a small chart
+ * of how busy a chat was over time. [`chat_activity`] counts the chat's
+ * messages per calendar month (local time, with empty months in between
+ * included so the chart has no gaps) and per sender, and its attachments.
+ * Every message row counts, tapbacks included, as in `ChatInfo`'s
+ * `message_count`.
+ */
+
+use std::{collections::HashMap, path::Path};
+
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+use super::{format_timestamp, SenderNames, TimestampUnit};
+
+/// Messages and attachments of a chat, over time and by sender
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChatActivity {
+    pub message_count: usize,
+    /// ISO 8601 date of the oldest message (`None` for an empty chat)
+    pub first_message_at: Option<String>,
+    /// ISO 8601 date of the newest message
+    pub last_message_at: Option<String>,
+    /// Oldest month first, from the first message's month to the last's
+    pub messages_per_month: Vec<MonthCount>,
+    /// Busiest sender first
+    pub senders: Vec<SenderCount>,
+    pub attachment_count: usize,
+}
+
+/// Messages sent in one month
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MonthCount {
+    /// `YYYY-MM`
+    pub month: String,
+    pub message_count: usize,
+}
+
+/// Messages from one sender
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SenderCount {
+    pub name: String,
+    pub is_from_me: bool,
+    pub message_count: usize,
+}
+
+/// Activity of the chat `chat_id`
+pub fn chat_activity(chat_id: i32, custom_db_path: Option<&Path>) -> Result<ChatActivity, String> {
+    let chat_db = crate::db_snapshot::open_chat_db(custom_db_path)?;
+    let db = &chat_db.conn;
+
+    let senders = SenderNames::load(db)?;
+
+    read_activity(db, chat_id, |is_from_me, handle_id| {
+        senders.name(is_from_me, handle_id)
+    })
+}
+
+/// Read the activity from an open database
+fn read_activity(
+    db: &Connection,
+    chat_id: i32,
+    sender_name: impl Fn(bool, Option<i32>) -> String,
+) -> Result<ChatActivity, String> {
+    let error = |e: rusqlite::Error| format!("Failed to query chat activity: {e}");
+    let mut stmt = db
+        .prepare(
+            "SELECT m.date, m.is_from_me, m.handle_id,
+                    (SELECT COUNT(*) FROM message_attachment_join a WHERE a.message_id = m.ROWID)
+             FROM message m
+             JOIN chat_message_join cmj ON cmj.message_id = m.ROWID
+             WHERE cmj.chat_id = ?1
+             ORDER BY m.date",
+        )
+        .map_err(error)?;
+    let rows = stmt
+        .query_map([chat_id], |row| {
+            Ok((
+                row.get::<_, i64>(0).unwrap_or(0),
+                row.get::<_, bool>(1).unwrap_or(false),
+                row.get::<_, Option<i32>>(2).unwrap_or(None),
+                row.get::<_, usize>(3)?,
+            ))
+        })
+        .map_err(error)?;
+
+    let unit = TimestampUnit::detect(db);
+    let mut activity = ChatActivity::default();
+    let mut months: Vec<MonthCount> = Vec::new();
+    let mut by_sender: HashMap<String, SenderCount> = HashMap::new();
+    for (date, is_from_me, handle_id, attachments) in rows.flatten() {
+        let timestamp = format_timestamp(date, unit);
+        let month = timestamp.get(..7).unwrap_or_default();
+        match months.last_mut() {
+            Some(last) if last.month == month => last.message_count += 1,
+            _ => months.push(MonthCount {
+                month: month.to_string(),
+                message_count: 1,
+            }),
+        }
+
+        let name = sender_name(is_from_me, handle_id);
+        let sender = by_sender.entry(name.clone()).or_insert(SenderCount {
+            name,
+            is_from_me,
+            message_count: 0,
+        });
+        sender.message_count += 1;
+
+        activity.message_count += 1;
+        activity.attachment_count += attachments;
+        activity
+            .first_message_at
+            .get_or_insert_with(|| timestamp.clone());
+        activity.last_message_at = Some(timestamp);
+    }
+
+    activity.messages_per_month = with_empty_months(months);
+    activity.senders = by_sender.into_values().collect();
+    activity
+        .senders
+        .sort_by(|a, b| (b.message_count, &a.name).cmp(&(a.message_count, &b.name)));
+    Ok(activity)
+}
+
+/// `months` (in order) with a zero count for each month missing in between
+fn with_empty_months(months: Vec<MonthCount>) -> Vec<MonthCount> {
+    let mut filled: Vec<MonthCount> = Vec::with_capacity(months.len());
+    for month in months {
+        while let Some(next) = filled.last().and_then(|last| next_month(&last.month)) {
+            if next >= month.month {
+                break;
+            }
+            filled.push(MonthCount {
+                month: next,
+                message_count: 0,
+            });
+        }
+        filled.push(month);
+    }
+    filled
+}
+
+/// The month after `YYYY-MM`
+fn next_month(month: &str) -> Option<String> {
+    let (year, month) = month.split_once('-')?;
+    let (year, month): (i32, u32) = (year.parse().ok()?, month.parse().ok()?);
+    Some(match month {
+        12 => format!("{:04}-01", year + 1),
+        _ => format!("{year:04}-{:02}", month + 1),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::timestamps::to_imessage_timestamp;
+    use crate::test_fixtures::{ChatBuilder, HandleBuilder, MessageBuilder, TestIMessageDb};
+
+    #[test]
+    fn counts_messages_by_month_and_sender() {
+        let mut db = TestIMessageDb::new().unwrap();
+        let handle = db.handle(HandleBuilder::new("+15551234567")).unwrap();
+        let chat = db
+            .chat(ChatBuilder::new("iMessage;-;+15551234567"))
+            .unwrap();
+        // Mid-month, so the local timezone doesn't move them
+        let (november, january) = (1_699_963_200, 1_705_320_000);
+        for (unix, from_me) in [(november, false), (november, true), (january, false)] {
+            let builder = MessageBuilder::new()
+                .text("hi")
+                .chat(chat)
+                .date(to_imessage_timestamp(unix, TimestampUnit::Nanoseconds));
+            let builder = if from_me {
+                builder.from_me()
+            } else {
+                builder.handle(handle)
+            };
+            db.message(builder).unwrap();
+        }
+        db.conn()
+            .execute_batch(
+                "INSERT INTO attachment (ROWID, guid) VALUES (1, 'att-1');
+                 INSERT INTO message_attachment_join (message_id, attachment_id) VALUES (1, 1);",
+            )
+            .unwrap();
+
+        let sender =
+            |is_from_me: bool, _: Option<i32>| if is_from_me { "Me" } else { "Alice" }.to_string();
+        let activity = read_activity(db.conn(), chat, sender).unwrap();
+
+        assert_eq!(activity.message_count, 3);
+        assert_eq!(activity.attachment_count, 1);
+        assert!(activity.first_message_at.unwrap().starts_with("2023-11-14"));
+        assert!(activity.last_message_at.unwrap().starts_with("2024-01-15"));
+        let months: Vec<(&str, usize)> = activity
+            .messages_per_month
+            .iter()
+            .map(|m| (m.month.as_str(), m.message_count))
+            .collect();
+        assert_eq!(months, [("2023-11", 2), ("2023-12", 0), ("2024-01", 1)]);
+        let senders: Vec<(&str, bool, usize)> = activity
+            .senders
+            .iter()
+            .map(|s| (s.name.as_str(), s.is_from_me, s.message_count))
+            .collect();
+        assert_eq!(senders, [("Alice", false, 2), ("Me", true, 1)]);
+
+        assert_eq!(
+            read_activity(db.conn(), 999, sender).unwrap(),
+            ChatActivity::default()
+        );
+    }
+}
@@ -0,0 +1,386 @@
+/*!
+ * Zip packaging for exports.
+ *
+ * Shared by the iMessage exporter and the third-party importers in
+ * `crate::sources`, so every upload has the same layout: one file per chat
+ * (named by [`chat_filenames`], in the manifest's `format`), any extra
+ * files such as `shared_links.json`, a `README.txt` describing it all, and
+ * `manifest.json`.
+ *
+ * The manifest is written last so it can list a SHA-256 of every other
+ * entry under `checksums`, letting the server and importers validate an
+ * upload. `schema_version` is the manifest's own layout (2 added the
+ * checksums, app version and per-chat index; 3 the `parts` of chats split
+ * across several files); `version` describes the chat files (see
+ * [`ExportFormat::manifest_version`]). Entries are compressed per the
+ * manifest's `compression` (see [`CompressionProfile`]).
+ */
+
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+    time::Instant,
+};
+
+use chrono::DateTime;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tempfile::TempDir;
+use zip::ZipWriter;
+
+use super::{
+    compression::CompressionProfile,
+    filenames::chat_filenames,
+    metrics::ExportMetrics,
+    parts,
+    readme::{render_readme, README_FILENAME},
+    ExportFormat, ExportResult, ExportTimezone, ExportedChat, ExportedMessage,
+};
+
+/// Layout version of `manifest.json` itself
+pub const MANIFEST_SCHEMA_VERSION: u32 = 3;
+
+/// Build the base manifest shared by every export source
+pub fn new_manifest(
+    source: &str,
+    chats: &[ExportedChat],
+    total_messages: usize,
+    format: ExportFormat,
+    timezone: ExportTimezone,
+) -> Value {
+    serde_json::json!({
+        "schema_version": MANIFEST_SCHEMA_VERSION,
+        "version": format.manifest_version(),
+        "source": source,
+        "format": format,
+        "app_version": env!("CARGO_PKG_VERSION"),
+        "os": std::env::consts::OS,
+        "export_date": chrono::Utc::now().to_rfc3339(),
+        "timezone": timezone.iana_name(),
+        "chat_count": chats.len(),
+        "total_messages": total_messages,
+        "chat_files": chat_filenames(chats, format)
+            .into_iter()
+            .zip(chats)
+            .map(|(file, chat)| chat_entry(file, chat))
+            .collect::<Vec<_>>(),
+    })
+}
+
+/// The `chat_files` entry for `chat`, written as `file`
+fn chat_entry(file: String, chat: &ExportedChat) -> Value {
+    let (first, last) = date_range(&chat.messages);
+    serde_json::json!({
+        "file": file,
+        "name": chat.meta.name,
+        "guid": chat.meta.guid,
+        "message_count": chat.messages.len(),
+        "first_message_at": first,
+        "last_message_at": last,
+    })
+}
+
+/// Timestamps of the earliest and latest of `messages`
+fn date_range(messages: &[ExportedMessage]) -> (Option<&String>, Option<&String>) {
+    let dates: Vec<_> = messages
+        .iter()
+        .filter_map(|message| {
+            let date = DateTime::parse_from_rfc3339(&message.timestamp).ok()?;
+            Some((date, &message.timestamp))
+        })
+        .collect();
+    let first = dates
+        .iter()
+        .min_by_key(|(date, _)| *date)
+        .map(|(_, ts)| *ts);
+    let last = dates
+        .iter()
+        .max_by_key(|(date, _)| *date)
+        .map(|(_, ts)| *ts);
+    (first, last)
+}
+
+/// Number of messages across `chats` from Recently Deleted
+pub fn deleted_messages(chats: &[ExportedChat]) -> usize {
+    chats
+        .iter()
+        .flat_map(|chat| &chat.messages)
+        .filter(|message| message.deleted)
+        .count()
+}
+
+/// Number of messages across `chats` whose text was truncated
+pub fn truncated_messages(chats: &[ExportedChat]) -> usize {
+    chats
+        .iter()
+        .flat_map(|chat| &chat.messages)
+        .filter(|message| message.truncated)
+        .count()
+}
+
+/// Write `chats`, `extra_files` (name, contents), a README and `manifest`
+/// (with `checksums` added) into `export.zip` inside a fresh temp
+/// directory, timing the compression
+pub fn write_archive(
+    manifest: &Value,
+    chats: &[ExportedChat],
+    extra_files: &[(String, String)],
+    total_messages: usize,
+) -> Result<ExportResult, String> {
+    let started = Instant::now();
+    let temp_dir = TempDir::new().map_err(|e| format!("Failed to create temp directory: {e}"))?;
+    let zip_path = temp_dir.path().join("export.zip");
+    let zip_file = File::create(&zip_path).map_err(|e| format!("Failed to create zip: {e}"))?;
+    let format = ExportFormat::of_manifest(manifest);
+    let compression = CompressionProfile::of_manifest(manifest);
+    let mut zip = ArchiveWriter {
+        zip: ZipWriter::new(BufWriter::new(zip_file)),
+        checksums: BTreeMap::new(),
+        compression,
+    };
+    let mut manifest = manifest.clone();
+    let max_messages = parts::max_messages_per_file(&manifest);
+    for (i, (chat, filename)) in chats.iter().zip(chat_filenames(chats, format)).enumerate() {
+        let part_names = parts::part_filenames(&filename, chat.messages.len(), max_messages);
+        if part_names.is_empty() {
+            zip.entry(&filename, |out| format.write_chat(chat, out))?;
+            continue;
+        }
+        let max_messages = max_messages.unwrap_or(1);
+        let part_entries = zip.chat_parts(chat, part_names, max_messages, format)?;
+        let entry = &mut manifest["chat_files"][i];
+        if let Some(entry) = entry.as_object_mut() {
+            entry.remove("file");
+            entry.insert("parts".to_string(), part_entries.into());
+        }
+    }
+    for (name, contents) in extra_files {
+        zip.entry(name, |out| out.write_all(contents.as_bytes()))?;
+    }
+    let extra_names: Vec<&str> = extra_files.iter().map(|(name, _)| name.as_str()).collect();
+    let readme = render_readme(&manifest, chats, &extra_names);
+    zip.entry(README_FILENAME, |out| out.write_all(readme.as_bytes()))?;
+
+    manifest["checksums"] = serde_json::to_value(&zip.checksums).unwrap();
+    zip.entry("manifest.json", |out| {
+        serde_json::to_writer_pretty(out, &manifest).map_err(io::Error::from)
+    })?;
+
+    zip.zip
+        .finish()
+        .map_err(|e| format!("Failed to finalize zip: {e}"))?;
+    let archive_bytes = std::fs::metadata(&zip_path)
+        .map_err(|e| format!("Failed to stat zip: {e}"))?
+        .len();
+    let zip_sha256 = sha256_file(&zip_path)?;
+
+    Ok(ExportResult {
+        zip_path,
+        zip_sha256,
+        _temp_dir: temp_dir,
+        total_messages,
+        chat_count: chats.len(),
+        truncated_messages: truncated_messages(chats),
+        deleted_messages: deleted_messages(chats),
+        warnings: Vec::new(),
+        orphans: Default::default(),
+        metrics: ExportMetrics::compressed(total_messages, archive_bytes, started.elapsed()),
+    })
+}
+
+/// The `manifest.json` of the export zip at `zip_path`
+pub fn read_manifest(zip_path: &Path) -> Result<Value, String> {
+    let file = File::open(zip_path).map_err(|e| format!("Failed to open {zip_path:?}: {e}"))?;
+    let mut zip =
+        zip::ZipArchive::new(file).map_err(|e| format!("Not an export zip {zip_path:?}: {e}"))?;
+    let entry = zip
+        .by_name("manifest.json")
+        .map_err(|e| format!("Failed to read manifest.json from {zip_path:?}: {e}"))?;
+    serde_json::from_reader(entry).map_err(|e| format!("Invalid manifest.json: {e}"))
+}
+
+/// SHA-256 of `bytes` as lowercase hex, as listed in `checksums`
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    hex::encode(Sha256::digest(bytes))
+}
+
+/// SHA-256 of the file at `path` as lowercase hex, read in chunks
+fn sha256_file(path: &Path) -> Result<String, String> {
+    let mut file = File::open(path).map_err(|e| format!("Failed to open zip: {e}"))?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher).map_err(|e| format!("Failed to hash zip: {e}"))?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// A zip being written, with the checksum of each finished entry
+struct ArchiveWriter {
+    zip: ZipWriter<BufWriter<File>>,
+    checksums: BTreeMap<String, String>,
+    compression: CompressionProfile,
+}
+
+impl ArchiveWriter {
+    /// Add entry `name`, its contents written by `write`
+    fn entry(
+        &mut self,
+        name: &str,
+        write: impl FnOnce(&mut HashingWriter<'_>) -> io::Result<()>,
+    ) -> Result<(), String> {
+        self.zip
+            .start_file(name, self.compression.file_options(name))
+            .map_err(|e| format!("Failed to write {name}: {e}"))?;
+        let mut out = HashingWriter {
+            inner: &mut self.zip,
+            hasher: Sha256::new(),
+        };
+        write(&mut out).map_err(|e| format!("Failed to write {name}: {e}"))?;
+        let checksum = hex::encode(out.hasher.finalize());
+        self.checksums.insert(name.to_string(), checksum);
+        Ok(())
+    }
+
+    /// Add `chat` as the files `names`, `max_messages` messages each,
+    /// returning their manifest entries
+    fn chat_parts(
+        &mut self,
+        chat: &ExportedChat,
+        names: Vec<String>,
+        max_messages: usize,
+        format: ExportFormat,
+    ) -> Result<Vec<Value>, String> {
+        let mut entries = Vec::new();
+        for (name, messages) in names.into_iter().zip(chat.messages.chunks(max_messages)) {
+            let part = ExportedChat {
+                meta: chat.meta.clone(),
+                messages: messages.to_vec(),
+            };
+            self.entry(&name, |out| format.write_chat(&part, out))?;
+            let (first, last) = date_range(messages);
+            entries.push(serde_json::json!({
+                "file": name,
+                "message_count": messages.len(),
+                "first_message_at": first,
+                "last_message_at": last,
+            }));
+        }
+        Ok(entries)
+    }
+}
+
+/// Passes writes through to the zip, hashing them on the way
+struct HashingWriter<'a> {
+    inner: &'a mut ZipWriter<BufWriter<File>>,
+    hasher: Sha256,
+}
+
+impl Write for HashingWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use zip::write::SimpleFileOptions;
+
+    use super::*;
+    use crate::export::{diff::read_archive, ExportedChatMeta};
+
+    fn chat(timestamps: &[&str]) -> ExportedChat {
+        ExportedChat {
+            meta: ExportedChatMeta {
+                name: "Alice".to_string(),
+                identifier: "+15551234567".to_string(),
+                guid: "iMessage;-;+15551234567".to_string(),
+                service: "iMessage".to_string(),
+                message_count: timestamps.len(),
+                participant_count: 1,
+                participants: Vec::new(),
+            },
+            messages: timestamps
+                .iter()
+                .map(|timestamp| ExportedMessage {
+                    guid: String::new(),
+                    timestamp: timestamp.to_string(),
+                    sender: "Alice".to_string(),
+                    is_from_me: false,
+                    text: "hi".to_string(),
+                    truncated: false,
+                    deleted: false,
+                    metadata: None,
+                    attachments: Vec::new(),
+                    event: None,
+                    urls: Vec::new(),
+                    link_preview: None,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn manifest_indexes_chats_and_checksums_every_file() {
+        let chats = [chat(&[
+            "2024-03-02T09:00:00+13:00",
+            "2024-01-05T18:30:00+00:00",
+        ])];
+        let manifest = new_manifest(
+            "imessage",
+            &chats,
+            2,
+            ExportFormat::Json,
+            ExportTimezone::Utc,
+        );
+        assert_eq!(manifest["schema_version"], MANIFEST_SCHEMA_VERSION);
+        assert_eq!(manifest["app_version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(manifest["timezone"], "UTC");
+        let entry = &manifest["chat_files"][0];
+        assert_eq!(entry["guid"], "iMessage;-;+15551234567");
+        assert_eq!(entry["message_count"], 2);
+        assert_eq!(entry["first_message_at"], "2024-01-05T18:30:00+00:00");
+        assert_eq!(entry["last_message_at"], "2024-03-02T09:00:00+13:00");
+
+        let result = write_archive(&manifest, &chats, &[], 2).unwrap();
+        let mut zip = zip::ZipArchive::new(File::open(&result.zip_path).unwrap()).unwrap();
+        let mut read = |name: &str| {
+            let mut bytes = Vec::new();
+            zip.by_name(name).unwrap().read_to_end(&mut bytes).unwrap();
+            bytes
+        };
+        let written: Value = serde_json::from_slice(&read("manifest.json")).unwrap();
+        let checksums = written["checksums"].as_object().unwrap();
+        for name in [entry["file"].as_str().unwrap(), README_FILENAME] {
+            assert_eq!(checksums[name], sha256_hex(&read(name)), "{name}");
+        }
+        assert!(!checksums.contains_key("manifest.json"));
+        assert_eq!(read_manifest(&result.zip_path).unwrap(), written);
+        let zip_bytes = std::fs::read(&result.zip_path).unwrap();
+        assert_eq!(result.zip_sha256, sha256_hex(&zip_bytes));
+
+        // A chat file that doesn't match its checksum is rejected
+        let damaged = result._temp_dir.path().join("damaged.zip");
+        let mut zip = ZipWriter::new(File::create(&damaged).unwrap());
+        let options = SimpleFileOptions::default();
+        zip.start_file("chat.json", options).unwrap();
+        zip.write_all(serde_json::to_string(&chats[0]).unwrap().as_bytes())
+            .unwrap();
+        zip.start_file("manifest.json", options).unwrap();
+        let manifest = serde_json::json!({
+            "chat_files": [{ "file": "chat.json" }],
+            "checksums": { "chat.json": sha256_hex(b"something else") },
+        });
+        zip.write_all(manifest.to_string().as_bytes()).unwrap();
+        zip.finish().unwrap();
+        let error = read_archive(&damaged).unwrap_err();
+        assert!(error.contains("checksum"), "{error}");
+    }
+}
@@ -0,0 +1,321 @@
+/*!
+ * The file each chat is written to in an export zip.
+ *
+ * JSON (one pretty-printed [`ExportedChat`]) is what the server reads and
+ * the default. NDJSON carries the same data as one JSON object per line,
+ * the chat's `meta` first and then each message, so very large chats can
+ * be written and read without holding one huge document. CSV (for a
+ * spreadsheet) and TXT (a WhatsApp-style transcript) are for people.
+ *
+ * Every format is written straight into the zip, line by line, rather than
+ * built as a string first. The manifest records the format as `format`;
+ * anything other than JSON also bumps its `version` to 1.1, so a reader
+ * that only knows one-document-per-chat files fails clearly.
+ */
+
+use std::io::{self, Write};
+
+use chrono::DateTime;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{ExportedChat, ExportedChatMeta, ExportedMessage};
+
+/// Date and time in transcript lines (WhatsApp's day-first layout)
+const TRANSCRIPT_DATE_FORMAT: &str = "%d/%m/%Y, %H:%M:%S";
+
+/// How each chat is written into the export zip
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    /// An [`ExportedChat`]: chat metadata and every message field
+    #[default]
+    Json,
+    /// `{"meta": ...}`, then one [`ExportedMessage`](super::ExportedMessage)
+    /// per line
+    Ndjson,
+    /// One row per message (timestamp, sender, is_from_me, text); chat
+    /// metadata is only in the manifest
+    Csv,
+    /// `[05/01/2024, 18:30:00] Alice: text` lines, laid out like a WhatsApp
+    /// (iOS) chat export
+    Txt,
+}
+
+/// First line of an NDJSON chat file
+#[derive(Serialize, Deserialize)]
+struct MetaLine<T> {
+    meta: T,
+}
+
+impl ExportFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Ndjson => "ndjson",
+            Self::Csv => "csv",
+            Self::Txt => "txt",
+        }
+    }
+
+    /// Manifest `version` for an export in this format
+    pub fn manifest_version(self) -> &'static str {
+        match self {
+            Self::Json => "1.0",
+            _ => "1.1",
+        }
+    }
+
+    /// The format an export's manifest records (JSON for older exports)
+    pub fn of_manifest(manifest: &Value) -> Self {
+        serde_json::from_value(manifest["format"].clone()).unwrap_or_default()
+    }
+
+    /// Write the chat file for `chat` to `out`
+    pub fn write_chat(self, chat: &ExportedChat, out: &mut impl Write) -> io::Result<()> {
+        match self {
+            Self::Json => serde_json::to_writer_pretty(out, chat).map_err(io::Error::from),
+            Self::Ndjson => write_ndjson(chat, out),
+            Self::Csv => write_csv(chat, out),
+            Self::Txt => write_transcript(chat, out),
+        }
+    }
+
+    /// Parse a chat file written by [`Self::write_chat`]; CSV and TXT files
+    /// don't carry enough to rebuild the chat
+    pub fn read_chat(self, contents: &str) -> Result<ExportedChat, String> {
+        match self {
+            Self::Json => serde_json::from_str(contents).map_err(|e| e.to_string()),
+            Self::Ndjson => read_ndjson(contents),
+            Self::Csv | Self::Txt => Err(format!(
+                "{} chat files can't be read back",
+                self.extension().to_uppercase()
+            )),
+        }
+    }
+}
+
+fn write_ndjson(chat: &ExportedChat, out: &mut impl Write) -> io::Result<()> {
+    serde_json::to_writer(&mut *out, &MetaLine { meta: &chat.meta })?;
+    out.write_all(b"\n")?;
+    for message in &chat.messages {
+        serde_json::to_writer(&mut *out, message)?;
+        out.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+fn read_ndjson(contents: &str) -> Result<ExportedChat, String> {
+    let mut lines = contents.lines().filter(|line| !line.trim().is_empty());
+    let first = lines.next().ok_or("Empty chat file")?;
+    let MetaLine::<ExportedChatMeta> { meta } =
+        serde_json::from_str(first).map_err(|e| format!("Invalid chat meta line: {e}"))?;
+    let messages = lines
+        .enumerate()
+        .map(|(i, line)| {
+            serde_json::from_str(line)
+                .map_err(|e| format!("Invalid message on line {}: {e}", i + 2))
+        })
+        .collect::<Result<_, _>>()?;
+    Ok(ExportedChat { meta, messages })
+}
+
+/// RFC 4180 CSV with a header row. Starts with a byte order mark so Excel
+/// reads it as UTF-8 rather than mangling emoji and accents.
+fn write_csv(chat: &ExportedChat, out: &mut impl Write) -> io::Result<()> {
+    out.write_all("\u{FEFF}timestamp,sender,is_from_me,text\r\n".as_bytes())?;
+    for message in &chat.messages {
+        let is_from_me = if message.is_from_me { "true" } else { "false" };
+        // Group events have no text; describe them instead, and give an
+        // audio message's transcription
+        let text = match &message.event {
+            _ if !message.text.is_empty() => message.text.clone(),
+            Some(event) => event.describe(),
+            None => transcriptions(message).collect::<Vec<_>>().join("\n"),
+        };
+        let fields = [
+            message.timestamp.as_str(),
+            message.sender.as_str(),
+            is_from_me,
+            text.as_str(),
+        ];
+        let row: Vec<String> = fields.into_iter().map(csv_field).collect();
+        write!(out, "{}\r\n", row.join(","))?;
+    }
+    Ok(())
+}
+
+/// Transcriptions of `message`'s audio attachments
+fn transcriptions(message: &ExportedMessage) -> impl Iterator<Item = &str> {
+    message
+        .attachments
+        .iter()
+        .filter_map(|attachment| attachment.transcription.as_deref())
+}
+
+/// Quote a field that holds a comma, quote or line break, doubling quotes
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// One line per message, in the time zone of its timestamp; lines after
+/// the first of a multi-line message follow as-is, uncaptioned attachments
+/// are `<attached: name>` (followed by any transcription) and group events
+/// have no sender, as WhatsApp writes them
+fn write_transcript(chat: &ExportedChat, out: &mut impl Write) -> io::Result<()> {
+    for message in &chat.messages {
+        let date = DateTime::parse_from_rfc3339(&message.timestamp)
+            .map(|date| date.format(TRANSCRIPT_DATE_FORMAT).to_string())
+            .unwrap_or_else(|_| message.timestamp.clone());
+        if let Some(event) = message.event.as_ref().filter(|_| message.text.is_empty()) {
+            writeln!(out, "[{date}] {}", event.describe())?;
+            continue;
+        }
+        let text = if message.text.is_empty() {
+            let attached: Vec<String> = message
+                .attachments
+                .iter()
+                .map(|attachment| match &attachment.transcription {
+                    Some(transcription) => {
+                        format!("<attached: {}> {transcription}", attachment.name)
+                    }
+                    None => format!("<attached: {}>", attachment.name),
+                })
+                .collect();
+            attached.join(" ")
+        } else {
+            message.text.clone()
+        };
+        writeln!(out, "[{date}] {}: {text}", message.sender)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::{ExportedAttachment, ExportedMessage, GroupEvent, GroupEventKind};
+
+    fn render(format: ExportFormat, chat: &ExportedChat) -> String {
+        let mut out = Vec::new();
+        format.write_chat(chat, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    fn chat() -> ExportedChat {
+        let message = |sender: &str, is_from_me: bool, text: &str| ExportedMessage {
+            guid: String::new(),
+            timestamp: "2024-01-05T18:30:00+00:00".to_string(),
+            sender: sender.to_string(),
+            is_from_me,
+            text: text.to_string(),
+            truncated: false,
+            deleted: false,
+            metadata: None,
+            attachments: Vec::new(),
+            event: None,
+            urls: Vec::new(),
+            link_preview: None,
+        };
+        ExportedChat {
+            meta: ExportedChatMeta {
+                name: "Trip".to_string(),
+                identifier: "chat123456".to_string(),
+                guid: String::new(),
+                service: "iMessage".to_string(),
+                message_count: 4,
+                participant_count: 1,
+                participants: Vec::new(),
+            },
+            messages: vec![
+                message("Smith, Alice", false, "Meet at \"Lupa\"\nat 8"),
+                message("Me", true, "Sounds good 🍝"),
+                ExportedMessage {
+                    attachments: vec![
+                        ExportedAttachment {
+                            name: "IMG_0042.jpg".to_string(),
+                            mime_type: Some("image/jpeg".to_string()),
+                            transcription: None,
+                        },
+                        ExportedAttachment {
+                            name: "Audio Message.caf".to_string(),
+                            mime_type: Some("audio/x-caf".to_string()),
+                            transcription: Some("Meet me at 12 Oak Street".to_string()),
+                        },
+                    ],
+                    ..message("Me", true, "")
+                },
+                ExportedMessage {
+                    event: Some(GroupEvent {
+                        kind: GroupEventKind::Renamed,
+                        actor: "Smith, Alice".to_string(),
+                        participant: None,
+                        name: Some("Trip to Portugal 2022".to_string()),
+                    }),
+                    ..message("Smith, Alice", false, "")
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn csv_and_transcript_render_every_message() {
+        let chat = chat();
+        assert_eq!(
+            render(ExportFormat::Csv, &chat),
+            "\u{FEFF}timestamp,sender,is_from_me,text\r\n\
+             2024-01-05T18:30:00+00:00,\"Smith, Alice\",false,\"Meet at \"\"Lupa\"\"\nat 8\"\r\n\
+             2024-01-05T18:30:00+00:00,Me,true,Sounds good 🍝\r\n\
+             2024-01-05T18:30:00+00:00,Me,true,Meet me at 12 Oak Street\r\n\
+             2024-01-05T18:30:00+00:00,\"Smith, Alice\",false,\"\
+             Smith, Alice named the conversation “Trip to Portugal 2022”\"\r\n"
+        );
+        assert_eq!(
+            render(ExportFormat::Txt, &chat),
+            "[05/01/2024, 18:30:00] Smith, Alice: Meet at \"Lupa\"\nat 8\n\
+             [05/01/2024, 18:30:00] Me: Sounds good 🍝\n\
+             [05/01/2024, 18:30:00] Me: <attached: IMG_0042.jpg> \
+             <attached: Audio Message.caf> Meet me at 12 Oak Street\n\
+             [05/01/2024, 18:30:00] Smith, Alice named the conversation “Trip to Portugal 2022”\n"
+        );
+        assert_eq!(
+            ExportFormat::of_manifest(&serde_json::json!({ "format": "csv" })),
+            ExportFormat::Csv
+        );
+        assert_eq!(
+            ExportFormat::of_manifest(&serde_json::json!({})),
+            ExportFormat::Json
+        );
+    }
+
+    #[test]
+    fn ndjson_writes_a_line_per_message_and_reads_back() {
+        let chat = chat();
+        let ndjson = render(ExportFormat::Ndjson, &chat);
+        let lines: Vec<&str> = ndjson.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert!(
+            lines[0].starts_with(r#"{"meta":{"name":"Trip""#),
+            "{}",
+            lines[0]
+        );
+        assert!(
+            lines[1].contains(r#""text":"Meet at \"Lupa\"\nat 8""#),
+            "{}",
+            lines[1]
+        );
+
+        let read = ExportFormat::Ndjson.read_chat(&ndjson).unwrap();
+        assert_eq!(read.meta.name, "Trip");
+        assert_eq!(read.messages.len(), 4);
+        assert_eq!(read.messages[1].text, "Sounds good 🍝");
+        assert_eq!(read.messages[2].attachments[0].name, "IMG_0042.jpg");
+        assert!(ExportFormat::Ndjson.read_chat("{\"meta\":{}}\n").is_err());
+        assert!(ExportFormat::Csv.read_chat("").is_err());
+    }
+}
@@ -0,0 +1,79 @@
+/*!
+ * The chats an export describes: names, GUIDs and participants, loaded once
+ * and used to turn each chat's streamed messages into an [`ExportedChat`].
+ */
+
+use std::collections::{BTreeSet, HashMap};
+
+use imessage_database::tables::{chat::Chat, chat_handle::ChatToHandle, table::Cacheable};
+use rusqlite::Connection;
+
+use super::{filenames, groups, ExportedChat, ExportedChatMeta, ExportedMessage, SenderNames};
+
+/// Chat rows and participants for naming exported chats
+pub(super) struct ChatDirectory {
+    pub chats: HashMap<i32, Chat>,
+    guids: HashMap<i32, String>,
+    /// Per-chat participant handle IDs, without the owner's — used to
+    /// resolve 1:1 chat display names from the contact's name (instead of
+    /// falling back to the chat ID) and to count other participants for
+    /// the title (e.g. "and N others")
+    participants: HashMap<i32, BTreeSet<i32>>,
+}
+
+impl ChatDirectory {
+    pub fn load(db: &Connection, senders: &SenderNames) -> Result<Self, String> {
+        let chats = Chat::cache(db).map_err(|e| format!("Failed to load chats: {e}"))?;
+        let guids = filenames::chat_guids(db)?;
+        let mut participants = ChatToHandle::cache(db)
+            .map_err(|e| format!("Failed to load chat participants: {e}"))?;
+        senders.owner.remove_from(&mut participants);
+        Ok(Self {
+            chats,
+            guids,
+            participants,
+        })
+    }
+
+    /// Display name of a chat, as the chat list shows it (may be empty)
+    pub fn name(&self, chat: &Chat, chat_id: i32, senders: &SenderNames) -> String {
+        senders.chat_name(chat, self.participants.get(&chat_id))
+    }
+
+    /// The exported chat for `chat_id`; `chat_groups` are
+    /// `ExportOptions::chat_groups`, for the service of merged chats
+    pub fn exported_chat(
+        &self,
+        chat_id: i32,
+        messages: Vec<ExportedMessage>,
+        senders: &SenderNames,
+        chat_groups: &[Vec<i32>],
+    ) -> ExportedChat {
+        let chat = self.chats.get(&chat_id);
+        let participants = self.participants.get(&chat_id);
+        let identifier = chat.map(|c| c.chat_identifier.clone()).unwrap_or_default();
+        let name = chat
+            .map(|c| self.name(c, chat_id, senders))
+            .filter(|s| !s.is_empty())
+            // Last-resort fallbacks: identifier (the phone/email/group ID),
+            // then a stable synthetic name. Both should be rare — the
+            // resolver almost always returns something useful.
+            .or_else(|| (!identifier.is_empty()).then(|| identifier.clone()))
+            .unwrap_or_else(|| format!("Chat {}", chat_id));
+        let group = chat_groups
+            .iter()
+            .find(|group| group.first() == Some(&chat_id))
+            .map(Vec::as_slice)
+            .unwrap_or(std::slice::from_ref(&chat_id));
+        let meta = ExportedChatMeta {
+            name,
+            identifier,
+            guid: self.guids.get(&chat_id).cloned().unwrap_or_default(),
+            service: groups::combined_service(&self.chats, group),
+            message_count: messages.len(),
+            participant_count: participants.map(|p| p.len()).unwrap_or(0),
+            participants: senders.participants(participants),
+        };
+        ExportedChat { meta, messages }
+    }
+}
@@ -0,0 +1,195 @@
+/*!
+ * Message text cleaning.
+ *
+ * Text decoded from `attributedBody` can carry leftovers of its container:
+ * U+FFFC (object replacement) where an attachment sat, U+FFFD for bytes
+ * that weren't valid UTF-8, and, when a body was only partly parsed, the
+ * typedstream class names and length bytes around the string. Unless
+ * `ExportOptions::raw_text` is set, each placeholder becomes
+ * `[attachment: IMG_0042.jpg]`, the rest is dropped and line breaks and
+ * invisible characters are normalized, so the server sees what was typed.
+ * A photo sent without a caption is left with empty text; the message's
+ * `attachments` describe it. Audio messages carry Apple's transcription,
+ * when the sending or receiving device made one, on their attachment:
+ * "meet me at 12 Oak Street" is as useful as anything typed.
+ */
+
+use std::path::Path;
+
+use imessage_database::tables::{
+    attachment::Attachment,
+    messages::{models::BubbleComponent, Message},
+};
+use rusqlite::Connection;
+
+use super::ExportedAttachment;
+
+/// Where iMessage puts each attachment in the text
+const OBJECT_REPLACEMENT: char = '\u{FFFC}';
+
+/// Written by lossy UTF-8 decoding for each invalid byte
+const REPLACEMENT: char = '\u{FFFD}';
+
+/// Header of an archived (typedstream) `NSAttributedString`
+const TYPEDSTREAM_HEADER: &str = "streamtyped";
+
+/// Class the message string follows inside a typedstream, with its
+/// length byte
+const TYPEDSTREAM_STRING_CLASS: &str = "\u{8}NSString";
+
+/// How far after the class name the string's `+` marker may be, in
+/// characters
+const MAX_STRING_MARKER_OFFSET: usize = 6;
+
+/// The attachments of `message`, in placeholder order; empty if they
+/// can't be read
+pub(super) fn attachments(db: &Connection, message: &Message) -> Vec<ExportedAttachment> {
+    if !message.has_attachments() {
+        return Vec::new();
+    }
+    let attachments = Attachment::from_message(db, message).unwrap_or_default();
+    attachments
+        .iter()
+        .map(|attachment| ExportedAttachment {
+            // `filename` is a full path (~/Library/Messages/Attachments/...)
+            name: attachment
+                .filename()
+                .and_then(|name| Path::new(name).file_name())
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            mime_type: attachment.mime_type.clone(),
+            transcription: None,
+        })
+        .collect()
+}
+
+/// Copy audio transcriptions from `message`'s parsed body onto
+/// `attachments`, which are in the same order as the body's attachment
+/// parts. Call after the text has been generated; before, the body isn't
+/// parsed.
+pub(super) fn add_transcriptions(attachments: &mut [ExportedAttachment], message: &Message) {
+    let parts = message
+        .components
+        .iter()
+        .filter_map(|component| match component {
+            BubbleComponent::Attachment(meta) => Some(meta),
+            _ => None,
+        });
+    for (attachment, meta) in attachments.iter_mut().zip(parts) {
+        attachment.transcription = meta
+            .transcription
+            .as_deref()
+            .map(str::trim)
+            .filter(|text| !text.is_empty())
+            .map(String::from);
+    }
+}
+
+/// Clean `text`, labelling its attachment placeholders with `attachments`
+/// in order. Text that is nothing but placeholders becomes empty: the
+/// message's `attachments` say it all.
+pub(super) fn clean_text(text: &str, attachments: &[ExportedAttachment]) -> String {
+    let text = typedstream_string(text).unwrap_or(text);
+    if text
+        .chars()
+        .all(|c| c == OBJECT_REPLACEMENT || c.is_whitespace())
+    {
+        return String::new();
+    }
+    let mut names = attachments
+        .iter()
+        .map(|attachment| attachment.name.as_str());
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            OBJECT_REPLACEMENT => match names.next() {
+                Some(name) if !name.is_empty() => {
+                    out.push_str(&format!("[attachment: {name}]"));
+                }
+                _ => out.push_str("[attachment]"),
+            },
+            '\r' => {
+                chars.next_if_eq(&'\n');
+                out.push('\n');
+            }
+            '\u{2028}' | '\u{2029}' => out.push('\n'),
+            '\u{A0}' | '\u{202F}' => out.push(' '),
+            // Zero-width space and byte-order mark; zero-width joiners stay,
+            // emoji sequences need them
+            REPLACEMENT | '\u{200B}' | '\u{FEFF}' => {}
+            c if c.is_control() && c != '\n' && c != '\t' => {}
+            c => out.push(c),
+        }
+    }
+    out.trim().to_string()
+}
+
+/// The message string inside a raw typedstream dump: shortly after the
+/// `NSString` class name come `+`, a length byte and the text, which runs to
+/// the next control or undecodable character
+fn typedstream_string(text: &str) -> Option<&str> {
+    if !text.contains(TYPEDSTREAM_HEADER) {
+        return None;
+    }
+    let class_end = text.find(TYPEDSTREAM_STRING_CLASS)? + TYPEDSTREAM_STRING_CLASS.len();
+    let after_class = &text[class_end..];
+    let marker = after_class
+        .char_indices()
+        .take(MAX_STRING_MARKER_OFFSET)
+        .find(|(_, c)| *c == '+')?
+        .0;
+    let mut string = after_class[marker + 1..].chars();
+    string.next()?;
+    let string = string.as_str();
+    let end = string
+        .find(|c: char| (c.is_control() && c != '\n') || c == REPLACEMENT)
+        .unwrap_or(string.len());
+    Some(&string[..end]).filter(|s| !s.trim().is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn placeholders_are_named_and_artifacts_dropped() {
+        let attachment = |name: &str| ExportedAttachment {
+            name: name.to_string(),
+            mime_type: None,
+            transcription: None,
+        };
+        let attachments = [attachment("IMG_0042.jpg"), attachment("")];
+        assert_eq!(
+            clean_text("\u{FFFC}Look at this\u{FFFC}\u{FFFC}", &attachments),
+            "[attachment: IMG_0042.jpg]Look at this[attachment][attachment]"
+        );
+        assert_eq!(clean_text("\u{FFFC} \u{FFFC}", &attachments), "");
+        assert_eq!(
+            clean_text(
+                " caf\u{FFFD}\u{200B} line\r\nnext\u{2028}last\u{A0}one\u{1} ",
+                &[]
+            ),
+            "caf line\nnext\nlast one"
+        );
+        // Zero-width joiners hold emoji sequences together
+        assert_eq!(
+            clean_text("👨\u{200D}👩\u{200D}👧", &[]),
+            "👨\u{200D}👩\u{200D}👧"
+        );
+    }
+
+    #[test]
+    fn typedstream_dumps_are_reduced_to_the_message_string() {
+        let dump = "\u{4}\u{b}streamtyped\u{FFFD}\u{FFFD}\u{3}\u{FFFD}\u{FFFD}\u{12}\
+                    NSAttributedString\u{0}\u{FFFD}\u{FFFD}\u{8}NSObject\u{0}\u{FFFD}\u{FFFD}\
+                    \u{FFFD}\u{FFFD}\u{8}NSString\u{1}\u{FFFD}\u{FFFD}\u{1}+\u{d}See you at 8!\
+                    \u{FFFD}\u{FFFD}\u{2}iI\u{1}\u{d}NSDictionary";
+        assert_eq!(clean_text(dump, &[]), "See you at 8!");
+        // Ordinary text mentioning the words is left alone
+        assert_eq!(
+            clean_text("NSString + streamtyped", &[]),
+            "NSString + streamtyped"
+        );
+    }
+}
@@ -0,0 +1,102 @@
+/*!
+ * How hard the export zip is compressed.
+ *
+ * Chat files are text and shrink well, so they are always deflated; the
+ * profile picks the level. Files that are already compressed (images,
+ * video, audio, archives) gain nothing from deflate and are stored as-is
+ * under every profile. The manifest records the profile as `compression`.
+ */
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use zip::{write::SimpleFileOptions, CompressionMethod};
+
+/// Extensions of files stored without compression
+const COMPRESSED_EXTENSIONS: &[&str] = &[
+    "jpg", "jpeg", "png", "gif", "heic", "heif", "webp", "mp4", "mov", "m4v", "m4a", "mp3", "aac",
+    "caf", "zip", "gz", "pdf",
+];
+
+/// Speed versus size of the export zip
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum CompressionProfile {
+    /// Deflate level 1: quickest, a little larger
+    Fast,
+    /// Deflate's default level (6)
+    #[default]
+    Balanced,
+    /// Deflate level 9: slowest, smallest
+    Smallest,
+}
+
+impl CompressionProfile {
+    /// The profile an export's manifest records (balanced when absent)
+    pub fn of_manifest(manifest: &Value) -> Self {
+        serde_json::from_value(manifest["compression"].clone()).unwrap_or_default()
+    }
+
+    /// Zip options for the entry `name`
+    pub fn file_options(self, name: &str) -> SimpleFileOptions {
+        let options = SimpleFileOptions::default();
+        if is_compressed(name) {
+            return options.compression_method(CompressionMethod::Stored);
+        }
+        let level = match self {
+            Self::Fast => Some(1),
+            Self::Balanced => None,
+            Self::Smallest => Some(9),
+        };
+        options
+            .compression_method(CompressionMethod::Deflated)
+            .compression_level(level)
+    }
+}
+
+/// Whether `name` is a format that is already compressed
+fn is_compressed(name: &str) -> bool {
+    name.rsplit_once('.').is_some_and(|(_, extension)| {
+        COMPRESSED_EXTENSIONS
+            .iter()
+            .any(|known| known.eq_ignore_ascii_case(extension))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compressed_files_are_stored_and_text_is_deflated_at_the_profile_level() {
+        let text = CompressionProfile::Smallest.file_options("chat.json");
+        let photo = CompressionProfile::Smallest.file_options("attachments/IMG_0001.HEIC");
+        assert_eq!(
+            format!("{text:?}"),
+            format!(
+                "{:?}",
+                SimpleFileOptions::default()
+                    .compression_method(CompressionMethod::Deflated)
+                    .compression_level(Some(9))
+            )
+        );
+        assert_eq!(
+            format!("{photo:?}"),
+            format!(
+                "{:?}",
+                SimpleFileOptions::default().compression_method(CompressionMethod::Stored)
+            )
+        );
+        assert!(!is_compressed("README.txt"));
+        assert!(!is_compressed("jpg"));
+
+        assert_eq!(
+            CompressionProfile::of_manifest(&serde_json::json!({ "compression": "fast" })),
+            CompressionProfile::Fast
+        );
+        assert_eq!(
+            CompressionProfile::of_manifest(&serde_json::json!({})),
+            CompressionProfile::Balanced
+        );
+    }
+}
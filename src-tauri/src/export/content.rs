@@ -0,0 +1,59 @@
+/*!
+ * What a streamed message says: its cleaned text (see [`super::cleaning`]),
+ * attachments, group event and link preview. Sender, time and metadata are
+ * added by the export loop.
+ */
+
+use imessage_database::tables::messages::Message;
+use rusqlite::Connection;
+
+use super::{
+    cleaning, links, recovery, ExportOptions, ExportedAttachment, GroupEvent, LinkPreview,
+    SenderNames,
+};
+
+/// The content of one message, before it becomes an `ExportedMessage`
+pub(super) struct MessageContent {
+    /// `None` when the message has no text
+    pub text: Option<String>,
+    pub attachments: Vec<ExportedAttachment>,
+    pub event: Option<GroupEvent>,
+    pub link_preview: Option<LinkPreview>,
+}
+
+impl MessageContent {
+    /// Read `message`'s content; decode problems are pushed to `warnings`
+    pub fn read(
+        db: &Connection,
+        message: &mut Message,
+        options: &ExportOptions,
+        senders: &SenderNames,
+        warnings: &mut Vec<String>,
+    ) -> Self {
+        let mut attachments = cleaning::attachments(db, message);
+        let mut text = recovery::message_text(db, message, warnings);
+        cleaning::add_transcriptions(&mut attachments, message);
+        if !options.raw_text {
+            text = text.map(|text| cleaning::clean_text(&text, &attachments));
+        }
+        let event = GroupEvent::from_message(
+            message,
+            senders.name(message.is_from_me, message.handle_id),
+            |handle| senders.name(false, Some(handle)),
+        );
+        Self {
+            text: text.filter(|t| !t.is_empty()),
+            attachments,
+            event,
+            link_preview: links::link_preview(db, message),
+        }
+    }
+
+    /// Nothing worth exporting: no text, attachments, event or link
+    pub fn is_empty(&self) -> bool {
+        self.text.is_none()
+            && self.attachments.is_empty()
+            && self.event.is_none()
+            && self.link_preview.is_none()
+    }
+}
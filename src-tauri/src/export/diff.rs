@@ -0,0 +1,441 @@
+/*!
+ * Comparison of two export archives.
+ *
+ * Answers "what changed since last month's export?": chats added or
+ * removed, per-chat message count deltas with the messages that appeared or
+ * disappeared, and manifest option differences (truncation, exclusions,
+ * Shared with You links). Chats are matched by identifier. Messages are
+ * matched by GUID when both exports have one for every message in the
+ * chat, which also shows messages that changed (edited, or moved to
+ * Recently Deleted); otherwise, as with older exports, by timestamp +
+ * sender + text.
+ */
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fmt::Write as _,
+    fs::File,
+    io::Read,
+    path::Path,
+};
+
+use serde::Serialize;
+use serde_json::Value;
+
+use super::{archive::sha256_hex, ExportFormat, ExportedChat, ExportedMessage};
+
+/// Manifest keys that differ on every export, or are covered by the chat diff
+const IGNORED_MANIFEST_KEYS: [&str; 6] = [
+    "export_date",
+    "chat_count",
+    "total_messages",
+    "warnings",
+    "chat_files",
+    "checksums",
+];
+
+/// A chat present in only one archive
+#[derive(Debug, Clone, Serialize)]
+pub struct ChatSummary {
+    pub identifier: String,
+    pub name: String,
+    pub message_count: usize,
+}
+
+/// A chat in both archives whose messages differ
+#[derive(Debug, Clone, Serialize)]
+pub struct ChatDelta {
+    pub identifier: String,
+    pub name: String,
+    pub before: usize,
+    pub after: usize,
+    /// In the first archive but not the second
+    pub missing_messages: Vec<ExportedMessage>,
+    /// In the second archive but not the first
+    pub new_messages: Vec<ExportedMessage>,
+    /// In both with the same GUID, but different
+    pub changed_messages: Vec<MessageChange>,
+}
+
+/// A message exported differently by each archive
+#[derive(Debug, Clone, Serialize)]
+pub struct MessageChange {
+    pub before: ExportedMessage,
+    pub after: ExportedMessage,
+}
+
+/// A manifest key whose value differs (`None` = absent)
+#[derive(Debug, Clone, Serialize)]
+pub struct ManifestChange {
+    pub key: String,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+}
+
+/// Differences from the first archive to the second
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ExportDiff {
+    pub added_chats: Vec<ChatSummary>,
+    pub removed_chats: Vec<ChatSummary>,
+    pub changed_chats: Vec<ChatDelta>,
+    pub manifest_changes: Vec<ManifestChange>,
+}
+
+impl ExportDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added_chats.is_empty()
+            && self.removed_chats.is_empty()
+            && self.changed_chats.is_empty()
+            && self.manifest_changes.is_empty()
+    }
+
+    /// Human-readable report; `show_messages` lists each missing/new message
+    pub fn to_text(&self, show_messages: bool) -> String {
+        if self.is_empty() {
+            return "No differences\n".to_string();
+        }
+        let mut out = String::new();
+        for (label, chats) in [
+            ("Added", &self.added_chats),
+            ("Removed", &self.removed_chats),
+        ] {
+            if chats.is_empty() {
+                continue;
+            }
+            let _ = writeln!(out, "{label} chats ({}):", chats.len());
+            for chat in chats {
+                let _ = writeln!(
+                    out,
+                    "  {} ({}) - {} messages",
+                    chat.name, chat.identifier, chat.message_count
+                );
+            }
+            out.push('\n');
+        }
+
+        if !self.changed_chats.is_empty() {
+            let _ = writeln!(out, "Changed chats ({}):", self.changed_chats.len());
+            for chat in &self.changed_chats {
+                let _ = writeln!(
+                    out,
+                    "  {} ({}): {} -> {} messages ({:+}), {} missing, {} new, {} changed",
+                    chat.name,
+                    chat.identifier,
+                    chat.before,
+                    chat.after,
+                    chat.after as i64 - chat.before as i64,
+                    chat.missing_messages.len(),
+                    chat.new_messages.len(),
+                    chat.changed_messages.len()
+                );
+                if !show_messages {
+                    continue;
+                }
+                for (marker, list) in [("-", &chat.missing_messages), ("+", &chat.new_messages)] {
+                    for message in list {
+                        let _ = writeln!(
+                            out,
+                            "      {marker} [{}] {}: {}",
+                            message.timestamp, message.sender, message.text
+                        );
+                    }
+                }
+                for change in &chat.changed_messages {
+                    let (before, after) = (&change.before, &change.after);
+                    let _ = writeln!(
+                        out,
+                        "      ~ [{}] {}: {} -> {}",
+                        after.timestamp,
+                        after.sender,
+                        describe(before),
+                        describe(after)
+                    );
+                }
+            }
+            out.push('\n');
+        }
+
+        if !self.manifest_changes.is_empty() {
+            out.push_str("Manifest changes:\n");
+            let show = |value: &Option<Value>| {
+                value
+                    .as_ref()
+                    .map_or_else(|| "(absent)".to_string(), Value::to_string)
+            };
+            for change in &self.manifest_changes {
+                let _ = writeln!(
+                    out,
+                    "  {}: {} -> {}",
+                    change.key,
+                    show(&change.before),
+                    show(&change.after)
+                );
+            }
+        }
+        out
+    }
+}
+
+/// An export zip's manifest and chats
+#[derive(Debug)]
+pub struct ArchiveContents {
+    pub manifest: Value,
+    pub chats: Vec<ExportedChat>,
+}
+
+/// Read `manifest.json` and every chat file from an export zip. Chat files
+/// are the ones the manifest lists in `chat_files` (a split chat's parts
+/// are joined back into one chat); without that list
+/// (older exports named them `chat_NNN.json`), every other JSON file.
+/// Exports with CSV or TXT chat files can't be read. Files the manifest has
+/// a checksum for are verified against it.
+pub fn read_archive(path: &Path) -> Result<ArchiveContents, String> {
+    let file = File::open(path).map_err(|e| format!("Failed to open {:?}: {e}", path))?;
+    let mut zip =
+        zip::ZipArchive::new(file).map_err(|e| format!("Not an export zip {:?}: {e}", path))?;
+    let mut read_entry = |name: &str| -> Result<String, String> {
+        let mut contents = String::new();
+        zip.by_name(name)
+            .map_err(|e| format!("Failed to read {name} from {:?}: {e}", path))?
+            .read_to_string(&mut contents)
+            .map_err(|e| format!("Failed to read {name}: {e}"))?;
+        Ok(contents)
+    };
+
+    let manifest: Value = serde_json::from_str(&read_entry("manifest.json")?)
+        .map_err(|e| format!("Invalid manifest.json: {e}"))?;
+    let format = ExportFormat::of_manifest(&manifest);
+    // Each chat's file, or the parts it was split into
+    let chat_files: Vec<Vec<String>> = match manifest["chat_files"].as_array() {
+        Some(files) => files
+            .iter()
+            .map(|file| match file["parts"].as_array() {
+                Some(parts) => parts
+                    .iter()
+                    .filter_map(|part| part["file"].as_str().map(String::from))
+                    .collect(),
+                None => file["file"]
+                    .as_str()
+                    .map(String::from)
+                    .into_iter()
+                    .collect(),
+            })
+            .filter(|names: &Vec<String>| !names.is_empty())
+            .collect(),
+        None => unlisted_chat_files(path)?
+            .into_iter()
+            .map(|name| vec![name])
+            .collect(),
+    };
+    let mut chats = Vec::new();
+    for names in chat_files {
+        let mut chat: Option<ExportedChat> = None;
+        for name in names {
+            let contents = read_entry(&name)?;
+            if let Some(expected) = manifest["checksums"][&name].as_str() {
+                if sha256_hex(contents.as_bytes()) != expected {
+                    return Err(format!(
+                        "{name} doesn't match its checksum; the export is damaged"
+                    ));
+                }
+            }
+            let part = format
+                .read_chat(&contents)
+                .map_err(|e| format!("Invalid {name}: {e}"))?;
+            match &mut chat {
+                Some(chat) => chat.messages.extend(part.messages),
+                None => chat = Some(part),
+            }
+        }
+        chats.extend(chat);
+    }
+    Ok(ArchiveContents { manifest, chats })
+}
+
+/// JSON entries other than the manifest and shared links, by name
+fn unlisted_chat_files(path: &Path) -> Result<Vec<String>, String> {
+    let file = File::open(path).map_err(|e| format!("Failed to open {:?}: {e}", path))?;
+    let zip =
+        zip::ZipArchive::new(file).map_err(|e| format!("Not an export zip {:?}: {e}", path))?;
+    let mut names: Vec<String> = zip
+        .file_names()
+        .filter(|name| name.ends_with(".json"))
+        .filter(|name| !matches!(*name, "manifest.json" | "shared_links.json"))
+        .map(String::from)
+        .collect();
+    names.sort();
+    Ok(names)
+}
+
+/// Compare two export zips
+pub fn diff_exports(before: &Path, after: &Path) -> Result<ExportDiff, String> {
+    Ok(diff_archives(&read_archive(before)?, &read_archive(after)?))
+}
+
+/// Compare two read archives
+pub fn diff_archives(before: &ArchiveContents, after: &ArchiveContents) -> ExportDiff {
+    let before_chats = chats_by_key(&before.chats);
+    let after_chats = chats_by_key(&after.chats);
+    let mut diff = ExportDiff::default();
+
+    for (key, chat) in &before_chats {
+        match after_chats.get(key) {
+            None => diff.removed_chats.push(summary(chat)),
+            Some(after_chat) => {
+                let by_guid = all_have_guids(&chat.messages, &after_chat.messages);
+                let missing_messages =
+                    messages_not_in(&chat.messages, &after_chat.messages, by_guid);
+                let new_messages = messages_not_in(&after_chat.messages, &chat.messages, by_guid);
+                let changed_messages = if by_guid {
+                    changed_messages(&chat.messages, &after_chat.messages)
+                } else {
+                    Vec::new()
+                };
+                if !missing_messages.is_empty()
+                    || !new_messages.is_empty()
+                    || !changed_messages.is_empty()
+                {
+                    diff.changed_chats.push(ChatDelta {
+                        identifier: after_chat.meta.identifier.clone(),
+                        name: after_chat.meta.name.clone(),
+                        before: chat.messages.len(),
+                        after: after_chat.messages.len(),
+                        missing_messages,
+                        new_messages,
+                        changed_messages,
+                    });
+                }
+            }
+        }
+    }
+    diff.added_chats = after_chats
+        .iter()
+        .filter(|(key, _)| !before_chats.contains_key(*key))
+        .map(|(_, chat)| summary(chat))
+        .collect();
+    diff.manifest_changes = manifest_changes(&before.manifest, &after.manifest);
+    diff
+}
+
+/// Chats keyed by identifier (name when there is none); repeats get `#N`
+fn chats_by_key(chats: &[ExportedChat]) -> BTreeMap<String, &ExportedChat> {
+    let mut by_key = BTreeMap::new();
+    for chat in chats {
+        let base = if chat.meta.identifier.is_empty() {
+            chat.meta.name.clone()
+        } else {
+            chat.meta.identifier.clone()
+        };
+        let mut key = base.clone();
+        let mut repeat = 1;
+        while by_key.contains_key(&key) {
+            repeat += 1;
+            key = format!("{base}#{repeat}");
+        }
+        by_key.insert(key, chat);
+    }
+    by_key
+}
+
+fn summary(chat: &ExportedChat) -> ChatSummary {
+    ChatSummary {
+        identifier: chat.meta.identifier.clone(),
+        name: chat.meta.name.clone(),
+        message_count: chat.messages.len(),
+    }
+}
+
+/// Whether every message on both sides has a GUID to be matched by
+fn all_have_guids(messages: &[ExportedMessage], other: &[ExportedMessage]) -> bool {
+    messages
+        .iter()
+        .chain(other)
+        .all(|message| !message.guid.is_empty())
+}
+
+/// Messages of `messages` with no match in `other`: by GUID, or by content
+/// counting duplicates
+fn messages_not_in(
+    messages: &[ExportedMessage],
+    other: &[ExportedMessage],
+    by_guid: bool,
+) -> Vec<ExportedMessage> {
+    if by_guid {
+        let guids: HashSet<&str> = other.iter().map(|message| message.guid.as_str()).collect();
+        return messages
+            .iter()
+            .filter(|message| !guids.contains(message.guid.as_str()))
+            .cloned()
+            .collect();
+    }
+    let mut remaining: HashMap<(&str, &str, &str), usize> = HashMap::new();
+    for message in other {
+        *remaining.entry(message_key(message)).or_default() += 1;
+    }
+    messages
+        .iter()
+        .filter(|message| match remaining.get_mut(&message_key(message)) {
+            Some(count) if *count > 0 => {
+                *count -= 1;
+                false
+            }
+            _ => true,
+        })
+        .cloned()
+        .collect()
+}
+
+fn message_key(message: &ExportedMessage) -> (&str, &str, &str) {
+    (&message.timestamp, &message.sender, &message.text)
+}
+
+/// Messages with the same GUID in both whose exported fields differ
+fn changed_messages(before: &[ExportedMessage], after: &[ExportedMessage]) -> Vec<MessageChange> {
+    let after_by_guid: HashMap<&str, &ExportedMessage> = after
+        .iter()
+        .map(|message| (message.guid.as_str(), message))
+        .collect();
+    before
+        .iter()
+        .filter_map(|message| {
+            let after = after_by_guid.get(message.guid.as_str())?;
+            let differs = serde_json::to_value(message).ok() != serde_json::to_value(after).ok();
+            differs.then(|| MessageChange {
+                before: message.clone(),
+                after: (*after).clone(),
+            })
+        })
+        .collect()
+}
+
+/// A message's text, and whether it's deleted, for the report
+fn describe(message: &ExportedMessage) -> String {
+    if message.deleted {
+        format!("{} (deleted)", message.text)
+    } else {
+        message.text.clone()
+    }
+}
+
+fn manifest_changes(before: &Value, after: &Value) -> Vec<ManifestChange> {
+    let keys: BTreeSet<&String> = [before, after]
+        .into_iter()
+        .filter_map(Value::as_object)
+        .flat_map(|object| object.keys())
+        .filter(|key| !IGNORED_MANIFEST_KEYS.contains(&key.as_str()))
+        .collect();
+    keys.into_iter()
+        .filter_map(|key| {
+            let (before, after) = (before.get(key), after.get(key));
+            (before != after).then(|| ManifestChange {
+                key: key.clone(),
+                before: before.cloned(),
+                after: after.cloned(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+#[path = "diff_tests.rs"]
+mod tests;
@@ -0,0 +1,143 @@
+/*!
+ * Tests for the export diff
+ */
+
+use serde_json::json;
+
+use super::*;
+use crate::export::{archive::write_archive, ExportedChatMeta};
+
+fn message(timestamp: &str, text: &str) -> ExportedMessage {
+    ExportedMessage {
+        guid: String::new(),
+        timestamp: timestamp.to_string(),
+        sender: "Alice".to_string(),
+        is_from_me: false,
+        text: text.to_string(),
+        truncated: false,
+        deleted: false,
+        metadata: None,
+        attachments: Vec::new(),
+        event: None,
+        urls: Vec::new(),
+        link_preview: None,
+    }
+}
+
+fn chat(identifier: &str, messages: Vec<ExportedMessage>) -> ExportedChat {
+    ExportedChat {
+        meta: ExportedChatMeta {
+            name: identifier.to_string(),
+            identifier: identifier.to_string(),
+            guid: String::new(),
+            service: "iMessage".to_string(),
+            message_count: messages.len(),
+            participant_count: 1,
+            participants: Vec::new(),
+        },
+        messages,
+    }
+}
+
+#[test]
+fn reports_added_removed_and_changed_chats() {
+    let before = ArchiveContents {
+        manifest: json!({"export_date": "2024-05-01", "max_text_length": 100}),
+        chats: vec![
+            chat(
+                "+15551234567",
+                vec![message("2024-04-01", "hi"), message("2024-04-02", "bye")],
+            ),
+            chat("+15559999999", vec![message("2024-04-03", "old")]),
+        ],
+    };
+    let after = ArchiveContents {
+        manifest: json!({"export_date": "2024-06-01"}),
+        chats: vec![
+            chat(
+                "+15551234567",
+                vec![message("2024-04-01", "hi"), message("2024-05-20", "new")],
+            ),
+            chat("chat123", vec![message("2024-05-21", "group")]),
+        ],
+    };
+
+    let diff = diff_archives(&before, &after);
+
+    assert_eq!(diff.added_chats[0].identifier, "chat123");
+    assert_eq!(diff.removed_chats[0].identifier, "+15559999999");
+    let delta = &diff.changed_chats[0];
+    assert_eq!((delta.before, delta.after), (2, 2));
+    assert_eq!(delta.missing_messages[0].text, "bye");
+    assert_eq!(delta.new_messages[0].text, "new");
+    assert_eq!(diff.manifest_changes.len(), 1);
+    assert_eq!(diff.manifest_changes[0].key, "max_text_length");
+    assert_eq!(diff.manifest_changes[0].after, None);
+
+    let report = diff.to_text(true);
+    assert!(report.contains("+15551234567 (+15551234567): 2 -> 2 messages (+0)"));
+    assert!(report.contains("- [2024-04-02] Alice: bye"));
+    assert!(report.contains("max_text_length: 100 -> (absent)"));
+}
+
+#[test]
+fn identical_archives_have_no_differences() {
+    let chats = vec![chat("+15551234567", vec![message("2024-04-01", "hi")])];
+    let manifest = json!({"version": "1.0"});
+    let result = write_archive(&manifest, &chats, &[], 1).unwrap();
+
+    let archive = read_archive(&result.zip_path).unwrap();
+    assert_eq!(archive.chats.len(), 1);
+    let checksums = archive.manifest["checksums"].as_object().unwrap();
+    assert_eq!(
+        checksums.len(),
+        2,
+        "the chat file and README: {checksums:?}"
+    );
+    assert!(diff_exports(&result.zip_path, &result.zip_path)
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn messages_with_guids_are_matched_by_guid_and_changes_reported() {
+    let sent = |guid: &str, timestamp: &str, text: &str| ExportedMessage {
+        guid: guid.to_string(),
+        ..message(timestamp, text)
+    };
+    let before = ArchiveContents {
+        manifest: json!({}),
+        chats: vec![chat(
+            "+15551234567",
+            vec![
+                sent("m1", "2024-04-01", "hi"),
+                sent("m2", "2024-04-02", "see you at 5"),
+                sent("m3", "2024-04-03", "bye"),
+            ],
+        )],
+    };
+    let after = ArchiveContents {
+        manifest: json!({}),
+        chats: vec![chat(
+            "+15551234567",
+            vec![
+                sent("m1", "2024-04-01", "hi"),
+                // Edited after the first export
+                sent("m2", "2024-04-02", "see you at 6"),
+                // The same text as m3, but another message
+                sent("m4", "2024-04-03", "bye"),
+            ],
+        )],
+    };
+
+    let diff = diff_archives(&before, &after);
+    let delta = &diff.changed_chats[0];
+    assert_eq!(delta.missing_messages[0].guid, "m3");
+    assert_eq!(delta.new_messages[0].guid, "m4");
+    assert_eq!(delta.changed_messages.len(), 1);
+    assert_eq!(delta.changed_messages[0].after.text, "see you at 6");
+
+    let report = diff.to_text(true);
+    assert!(report.contains("1 missing, 1 new, 1 changed"), "{report}");
+    assert!(report.contains("~ [2024-04-02] Alice: see you at 5 -> see you at 6"));
+}
@@ -0,0 +1,125 @@
+/*!
+ * Free-space check before an export.
+ *
+ * An export writes into a temp dir on the system volume: the chat.db
+ * snapshot (see `crate::db_snapshot`) and then the zip. When the volume
+ * fills up midway, the failure is an opaque write error. [`ensure_space`]
+ * compares what a step will write against the free bytes on the temp
+ * volume first, and fails with [`InsufficientDiskSpace`] instead. The zip's
+ * size comes from the export estimate, with [`SAFETY_MARGIN`] on top
+ * because it is only approximate. When free space can't be read, the check
+ * passes.
+ */
+
+use std::{fmt, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use super::metrics::BYTES_PER_MB;
+
+/// Estimated sizes are multiplied by this before comparing
+pub const SAFETY_MARGIN: f64 = 1.5;
+
+/// Not enough free space on the temp volume for an export
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InsufficientDiskSpace {
+    pub required_bytes: u64,
+    pub available_bytes: u64,
+}
+
+impl fmt::Display for InsufficientDiskSpace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Not enough disk space for the export: it needs about {:.0} MB, but only {:.0} MB \
+             is free",
+            self.required_bytes as f64 / BYTES_PER_MB,
+            self.available_bytes as f64 / BYTES_PER_MB
+        )
+    }
+}
+
+/// Fail if writing `required_bytes` into `dir` would not fit
+pub fn ensure_space(dir: &Path, required_bytes: u64) -> Result<(), InsufficientDiskSpace> {
+    match available_bytes(dir) {
+        Some(available_bytes) if available_bytes < required_bytes => Err(InsufficientDiskSpace {
+            required_bytes,
+            available_bytes,
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// `estimated_bytes` with the safety margin added
+pub fn with_margin(estimated_bytes: u64) -> u64 {
+    (estimated_bytes as f64 * SAFETY_MARGIN) as u64
+}
+
+/// Bytes free for this user on the volume holding `dir`
+#[cfg(unix)]
+pub fn available_bytes(dir: &Path) -> Option<u64> {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt};
+
+    let path = CString::new(dir.as_os_str().as_bytes()).ok()?;
+    // SAFETY: `statvfs` is plain data, valid when zeroed
+    let mut stats: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: `path` is NUL-terminated and `stats` is a valid out pointer
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stats) } != 0 {
+        return None;
+    }
+    #[allow(clippy::unnecessary_cast)] // the field types differ by platform
+    Some(stats.f_bavail as u64 * stats.f_frsize as u64)
+}
+
+/// Bytes free for this user on the volume holding `dir`
+#[cfg(windows)]
+pub fn available_bytes(dir: &Path) -> Option<u64> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Storage::FileSystem::GetDiskFreeSpaceExW;
+
+    let path: Vec<u16> = dir.as_os_str().encode_wide().chain([0]).collect();
+    let mut available = 0u64;
+    // SAFETY: `path` is NUL-terminated; the unused totals may be null
+    let ok = unsafe {
+        GetDiskFreeSpaceExW(
+            path.as_ptr(),
+            &mut available,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+        )
+    };
+    (ok != 0).then_some(available)
+}
+
+/// Bytes free for this user on the volume holding `dir`
+#[cfg(not(any(unix, windows)))]
+pub fn available_bytes(_dir: &Path) -> Option<u64> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exports_that_do_not_fit_fail_with_both_sizes() {
+        let dir = std::env::temp_dir();
+        let available = available_bytes(&dir).unwrap();
+        assert!(available > 0);
+        assert_eq!(ensure_space(&dir, 0), Ok(()));
+
+        let error = ensure_space(&dir, u64::MAX).unwrap_err();
+        assert_eq!(error.required_bytes, u64::MAX);
+        assert!(error.available_bytes < u64::MAX);
+
+        let error = InsufficientDiskSpace {
+            required_bytes: 300 * 1024 * 1024,
+            available_bytes: 120 * 1024 * 1024,
+        };
+        assert_eq!(
+            error.to_string(),
+            "Not enough disk space for the export: it needs about 300 MB, but only 120 MB is free"
+        );
+        assert_eq!(with_margin(100), 150);
+    }
+}
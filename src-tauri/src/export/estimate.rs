@@ -0,0 +1,285 @@
+/*!
+ * Export size and duration estimates.
+ *
+ * A selection of a few large chats can produce a zip of a gigabyte or more,
+ * and the user should know that before committing to it. [`estimate_export`]
+ * runs counting queries only (no text decoding, no JSON) and scales the
+ * totals by how big and how fast past exports were: throughput comes from
+ * the job history's metrics when there are recent runs, otherwise from
+ * conservative defaults.
+ */
+
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    metrics::ExportMetrics,
+    metrics::BYTES_PER_MB,
+    timestamps::{to_imessage_timestamp, TimestampUnit},
+};
+
+/// Compressed size of the JSON around each message's text: timestamp,
+/// sender and keys are highly repetitive (about 6 bytes), the random GUID
+/// is not (about 24)
+const COMPRESSED_BYTES_PER_MESSAGE: f64 = 30.0;
+
+/// Compressed size of message text relative to its raw size
+const TEXT_COMPRESSION_RATIO: f64 = 0.5;
+
+/// Per-archive overhead: manifest, README and zip directory
+const BASE_ARCHIVE_BYTES: u64 = 2 * 1024;
+
+/// Fallback rates when there are no recent runs to learn from
+const DEFAULT_MESSAGES_PER_SEC: f64 = 10_000.0;
+const DEFAULT_COMPRESSED_MB_PER_SEC: f64 = 5.0;
+const DEFAULT_UPLOADED_MB_PER_SEC: f64 = 1.0;
+
+/// Only export messages within these dates (either end may be open)
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DateRange {
+    pub start: Option<DateTime<Utc>>,
+    pub end: Option<DateTime<Utc>>,
+}
+
+/// What an export of a selection is expected to produce
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExportEstimate {
+    /// Messages with text (what the export carries)
+    pub message_count: usize,
+    /// Selected chats that have any of those messages
+    pub chat_count: usize,
+    /// Approximate size of the export zip
+    pub archive_bytes: u64,
+    /// Reading the database and writing the zip
+    pub export_secs: f64,
+    pub upload_secs: f64,
+    /// Recent runs the rates were taken from; 0 means defaults were used
+    pub based_on_runs: usize,
+}
+
+/// Estimate an export of `chat_ids` (every ROWID of a merged chat) limited
+/// to `date_range`, using `recent_runs` (e.g. from the job history) for
+/// throughput
+pub fn estimate_export(
+    chat_ids: &[i32],
+    date_range: &DateRange,
+    recent_runs: &[ExportMetrics],
+    custom_db_path: Option<&Path>,
+) -> Result<ExportEstimate, String> {
+    let chat_db = crate::db_snapshot::open_chat_db(custom_db_path)?;
+    let unit = TimestampUnit::detect(&chat_db.conn);
+    let mut totals = Vec::new();
+    for &chat_id in chat_ids {
+        totals.push(
+            count_chat(&chat_db.conn, chat_id, date_range, unit)
+                .map_err(|e| format!("Failed to count chat {chat_id}: {e}"))?,
+        );
+    }
+    Ok(estimate_from_counts(&totals, recent_runs))
+}
+
+/// Approximate zip size of an export of `chat_ids`, with default rates
+pub(super) fn archive_bytes(
+    db: &Connection,
+    chat_ids: impl IntoIterator<Item = i32>,
+    unit: TimestampUnit,
+) -> Result<u64, String> {
+    let mut totals = Vec::new();
+    for chat_id in chat_ids {
+        totals.push(
+            count_chat(db, chat_id, &DateRange::default(), unit)
+                .map_err(|e| format!("Failed to count chat {chat_id}: {e}"))?,
+        );
+    }
+    Ok(estimate_from_counts(&totals, &[]).archive_bytes)
+}
+
+/// Exportable messages and their approximate text bytes for one chat
+fn count_chat(
+    db: &Connection,
+    chat_id: i32,
+    date_range: &DateRange,
+    unit: TimestampUnit,
+) -> rusqlite::Result<(usize, u64)> {
+    let start = date_range.start.map_or(i64::MIN, |date| {
+        to_imessage_timestamp(date.timestamp(), unit)
+    });
+    let end = date_range.end.map_or(i64::MAX, |date| {
+        to_imessage_timestamp(date.timestamp(), unit)
+    });
+    // Text in `attributedBody` (macOS 13+) is wrapped in a typedstream;
+    // roughly half of the blob is the text itself
+    db.query_row(
+        "SELECT
+            COUNT(*),
+            SUM(CASE WHEN m.text IS NOT NULL AND m.text != '' THEN LENGTH(CAST(m.text AS BLOB))
+                     ELSE LENGTH(m.attributedBody) / 2 END)
+         FROM chat_message_join cmj
+         JOIN message m ON m.ROWID = cmj.message_id
+         WHERE cmj.chat_id = ?1
+           AND m.date BETWEEN ?2 AND ?3
+           AND ((m.text IS NOT NULL AND m.text != '') OR m.attributedBody IS NOT NULL)",
+        rusqlite::params![chat_id, start, end],
+        |row| {
+            Ok((
+                row.get::<_, usize>(0)?,
+                row.get::<_, Option<u64>>(1)?.unwrap_or(0),
+            ))
+        },
+    )
+}
+
+/// Scale per-chat (messages, text bytes) counts into an estimate
+fn estimate_from_counts(counts: &[(usize, u64)], recent_runs: &[ExportMetrics]) -> ExportEstimate {
+    let message_count: usize = counts.iter().map(|(messages, _)| messages).sum();
+    let text_bytes: u64 = counts.iter().map(|(_, bytes)| bytes).sum();
+    let chat_count = counts.iter().filter(|(messages, _)| *messages > 0).count();
+
+    let archive_bytes = BASE_ARCHIVE_BYTES
+        + (message_count as f64 * COMPRESSED_BYTES_PER_MESSAGE
+            + text_bytes as f64 * TEXT_COMPRESSION_RATIO) as u64;
+    let archive_mb = archive_bytes as f64 / BYTES_PER_MB;
+
+    let runs: Vec<&ExportMetrics> = recent_runs.iter().filter(|run| run.messages > 0).collect();
+    let rate = |rates: Vec<f64>, default: f64| median(rates).unwrap_or(default);
+    let messages_per_sec = rate(
+        runs.iter().map(|run| run.messages_per_sec).collect(),
+        DEFAULT_MESSAGES_PER_SEC,
+    );
+    let compressed_mb_per_sec = rate(
+        runs.iter().map(|run| run.compressed_mb_per_sec).collect(),
+        DEFAULT_COMPRESSED_MB_PER_SEC,
+    );
+    let uploaded_mb_per_sec = rate(
+        runs.iter()
+            .filter_map(|run| run.uploaded_mb_per_sec)
+            .collect(),
+        DEFAULT_UPLOADED_MB_PER_SEC,
+    );
+
+    ExportEstimate {
+        message_count,
+        chat_count,
+        archive_bytes,
+        export_secs: message_count as f64 / messages_per_sec + archive_mb / compressed_mb_per_sec,
+        upload_secs: archive_mb / uploaded_mb_per_sec,
+        based_on_runs: runs.len(),
+    }
+}
+
+/// Middle of the positive `values`, so one odd run doesn't skew the rate
+fn median(mut values: Vec<f64>) -> Option<f64> {
+    values.retain(|value| *value > 0.0);
+    values.sort_by(f64::total_cmp);
+    values.get(values.len() / 2).copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::export::{export_chats, ExportOptions};
+    use crate::test_fixtures::{ChatBuilder, HandleBuilder, MessageBuilder, TestIMessageDb};
+
+    #[test]
+    fn estimate_counts_messages_in_range_and_approximates_the_zip() {
+        let dir = TempDir::new().unwrap();
+        let mut db = TestIMessageDb::new().unwrap();
+        let handle = db.handle(HandleBuilder::new("+15551234567")).unwrap();
+        let chat = db
+            .chat(ChatBuilder::new("iMessage;-;+15551234567"))
+            .unwrap();
+        // One message a day through 2024, of 3-20 random "words" (random
+        // letters compress a little worse than real text)
+        let mut seed: u64 = 42;
+        let mut random = |n: u64| {
+            seed = seed.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1);
+            (seed >> 33) % n
+        };
+        let jan_1 = 1_704_067_200;
+        for day in 0..300 {
+            let words: Vec<String> = (0..3 + random(18))
+                .map(|_| {
+                    (0..2 + random(7))
+                        .map(|_| (b'a' + random(26) as u8) as char)
+                        .collect()
+                })
+                .collect();
+            let guid = format!(
+                "{:08X}-{:04X}-{:04X}-{:04X}-{:012X}",
+                random(1 << 32),
+                random(1 << 16),
+                random(1 << 16),
+                random(1 << 16),
+                random(1 << 32) << 16 | random(1 << 16)
+            );
+            db.message(
+                MessageBuilder::new()
+                    .guid(guid)
+                    .text(words.join(" "))
+                    .handle(handle)
+                    .chat(chat)
+                    .date(to_imessage_timestamp(
+                        jan_1 + day * 86_400,
+                        TimestampUnit::Nanoseconds,
+                    )),
+            )
+            .unwrap();
+        }
+        let db_path = dir.path().join("chat.db");
+        db.save_to(&db_path).unwrap();
+
+        let everything =
+            estimate_export(&[chat], &DateRange::default(), &[], Some(&db_path)).unwrap();
+        assert_eq!(everything.message_count, 300);
+        assert_eq!(everything.chat_count, 1);
+        assert_eq!(everything.based_on_runs, 0);
+
+        // The estimate should be the right order of magnitude
+        let export =
+            export_chats(&[chat], &ExportOptions::default(), None, Some(&db_path)).unwrap();
+        let actual = export.metrics.archive_bytes as f64;
+        let ratio = everything.archive_bytes as f64 / actual;
+        assert!(
+            (0.5..2.0).contains(&ratio),
+            "estimated {everything:?}, actual {actual}"
+        );
+
+        let february = DateRange {
+            start: Some("2024-02-01T00:00:00Z".parse().unwrap()),
+            end: Some("2024-02-29T23:59:59Z".parse().unwrap()),
+        };
+        let estimate = estimate_export(&[chat], &february, &[], Some(&db_path)).unwrap();
+        assert_eq!(estimate.message_count, 29);
+    }
+
+    #[test]
+    fn durations_use_the_median_of_recent_runs() {
+        let run = |messages_per_sec: f64, uploaded: Option<f64>| ExportMetrics {
+            messages: 1000,
+            messages_per_sec,
+            compressed_mb_per_sec: 10.0,
+            uploaded_mb_per_sec: uploaded,
+            ..ExportMetrics::compressed(1000, 1024, Duration::from_secs(1))
+        };
+        let runs = [
+            run(1000.0, Some(2.0)),
+            run(2000.0, None),
+            run(900_000.0, Some(2.0)),
+            ExportMetrics::default(),
+        ];
+        let estimate = estimate_from_counts(&[(20_000, 0), (0, 0)], &runs);
+        assert_eq!(estimate.chat_count, 1);
+        assert_eq!(estimate.based_on_runs, 3);
+        let archive_mb = estimate.archive_bytes as f64 / BYTES_PER_MB;
+        assert_eq!(estimate.export_secs, 10.0 + archive_mb / 10.0);
+        assert_eq!(estimate.upload_secs, archive_mb / 2.0);
+    }
+}
@@ -0,0 +1,97 @@
+/*!
+ * Group chat events: renames, participants joining and leaving, and group
+ * photo and background changes.
+ *
+ * iMessage stores these as messages with an `item_type` (and for most a
+ * `group_action_type`) instead of text. They are exported as messages with
+ * empty text and an `event`, so the server can use them as context: a group
+ * renamed "Trip to Portugal 2022" dates the trip.
+ */
+
+use imessage_database::tables::messages::{models::GroupAction, Message};
+use serde::{Deserialize, Serialize};
+
+/// What happened in the group
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GroupEventKind {
+    Renamed,
+    ParticipantAdded,
+    ParticipantRemoved,
+    ParticipantLeft,
+    PhotoChanged,
+    PhotoRemoved,
+    BackgroundChanged,
+    BackgroundRemoved,
+}
+
+/// A group chat event, carried by an exported message with empty text
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GroupEvent {
+    #[serde(rename = "type")]
+    pub kind: GroupEventKind,
+    /// Who did it, named like a sender ("Me" for the device owner)
+    pub actor: String,
+    /// Who was added or removed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub participant: Option<String>,
+    /// The group's new name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+impl GroupEvent {
+    /// The event `message` records, if it is one. `actor` is the message's
+    /// sender; `participant_name` names a handle ROWID.
+    pub(super) fn from_message(
+        message: &Message,
+        actor: String,
+        participant_name: impl Fn(i32) -> String,
+    ) -> Option<Self> {
+        let event = |kind| Self {
+            kind,
+            actor: actor.clone(),
+            participant: None,
+            name: None,
+        };
+        Some(match message.group_action()? {
+            GroupAction::NameChange(name) => Self {
+                name: Some(name.to_string()),
+                ..event(GroupEventKind::Renamed)
+            },
+            GroupAction::ParticipantAdded(handle) => Self {
+                participant: Some(participant_name(handle)),
+                ..event(GroupEventKind::ParticipantAdded)
+            },
+            GroupAction::ParticipantRemoved(handle) => Self {
+                participant: Some(participant_name(handle)),
+                ..event(GroupEventKind::ParticipantRemoved)
+            },
+            GroupAction::ParticipantLeft => event(GroupEventKind::ParticipantLeft),
+            GroupAction::GroupIconChanged => event(GroupEventKind::PhotoChanged),
+            GroupAction::GroupIconRemoved => event(GroupEventKind::PhotoRemoved),
+            GroupAction::ChatBackgroundChanged => event(GroupEventKind::BackgroundChanged),
+            GroupAction::ChatBackgroundRemoved => event(GroupEventKind::BackgroundRemoved),
+        })
+    }
+
+    /// One line for people, as a chat app shows it: "Alice named the
+    /// conversation “Trip to Portugal 2022”"
+    pub fn describe(&self) -> String {
+        let actor = &self.actor;
+        let participant = self.participant.as_deref().unwrap_or("someone");
+        match self.kind {
+            GroupEventKind::Renamed => format!(
+                "{actor} named the conversation “{}”",
+                self.name.as_deref().unwrap_or_default()
+            ),
+            GroupEventKind::ParticipantAdded => format!("{actor} added {participant}"),
+            GroupEventKind::ParticipantRemoved => format!("{actor} removed {participant}"),
+            GroupEventKind::ParticipantLeft => format!("{actor} left the conversation"),
+            GroupEventKind::PhotoChanged => format!("{actor} changed the group photo"),
+            GroupEventKind::PhotoRemoved => format!("{actor} removed the group photo"),
+            GroupEventKind::BackgroundChanged => format!("{actor} changed the background"),
+            GroupEventKind::BackgroundRemoved => format!("{actor} removed the background"),
+        }
+    }
+}
@@ -0,0 +1,153 @@
+/*!
+ * File names for the chats in an export zip.
+ *
+ * Each chat is written as `<slug>.<chat id>.json` (or `.csv`), e.g.
+ * `alice-johnson.iMessage-+15551234567.json`: the slugified chat name, so
+ * the zip is readable by eye, and the chat GUID (the identifier for sources
+ * without one), so the same chat gets the same name in every export. Names
+ * that still collide get `-2`, `-3`, ... in the order the chats are written.
+ * The mapping is listed in `manifest.json` as `chat_files`.
+ */
+
+use std::collections::{HashMap, HashSet};
+
+use rusqlite::Connection;
+
+use super::{ExportFormat, ExportedChat};
+
+/// Longest slug taken from a chat name, in characters
+const MAX_SLUG_CHARS: usize = 40;
+
+/// The zip entry name for each of `chats`, in order
+pub fn chat_filenames(chats: &[ExportedChat], format: ExportFormat) -> Vec<String> {
+    let extension = format.extension();
+    let mut used = HashSet::new();
+    chats
+        .iter()
+        .map(|chat| {
+            let id = if chat.meta.guid.is_empty() {
+                &chat.meta.identifier
+            } else {
+                &chat.meta.guid
+            };
+            let stem = format!("{}.{}", slugify(&chat.meta.name), sanitize_id(id));
+            let mut filename = format!("{stem}.{extension}");
+            let mut n = 2;
+            while !used.insert(filename.clone()) {
+                filename = format!("{stem}-{n}.{extension}");
+                n += 1;
+            }
+            filename
+        })
+        .collect()
+}
+
+/// Chat ROWID → GUID (e.g. `iMessage;-;+15551234567`)
+pub(crate) fn chat_guids(db: &Connection) -> Result<HashMap<i32, String>, String> {
+    let mut stmt = db
+        .prepare("SELECT ROWID, guid FROM chat")
+        .map_err(|e| format!("Failed to load chat GUIDs: {e}"))?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get(0)?,
+                row.get::<_, Option<String>>(1)?.unwrap_or_default(),
+            ))
+        })
+        .map_err(|e| format!("Failed to load chat GUIDs: {e}"))?;
+    rows.collect::<Result<_, _>>()
+        .map_err(|e| format!("Failed to load chat GUIDs: {e}"))
+}
+
+/// "Alice Johnson 🎉" → "alice-johnson"; "chat" if nothing is left
+fn slugify(name: &str) -> String {
+    let mut slug = String::new();
+    for c in name.chars().flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+        if slug.chars().count() >= MAX_SLUG_CHARS {
+            break;
+        }
+    }
+    let slug = slug.trim_end_matches('-');
+    if slug.is_empty() {
+        "chat".to_string()
+    } else {
+        slug.to_string()
+    }
+}
+
+/// Keep the characters of a GUID that are safe in a file name, dropping the
+/// 1:1 / group marker: `iMessage;-;+15551234567` → `iMessage-+15551234567`,
+/// `iMessage;+;chat123456` → `iMessage-chat123456`
+fn sanitize_id(id: &str) -> String {
+    let mut out = String::new();
+    for part in id.split(';').filter(|part| !matches!(*part, "-" | "+")) {
+        for c in part.chars() {
+            if c.is_ascii_alphanumeric() || matches!(c, '+' | '@' | '.' | '_') {
+                out.push(c);
+            } else if !out.is_empty() && !out.ends_with('-') {
+                out.push('-');
+            }
+        }
+        if !out.is_empty() && !out.ends_with('-') {
+            out.push('-');
+        }
+    }
+    let out = out.trim_matches(|c| c == '-' || c == '.');
+    if out.is_empty() {
+        "chat".to_string()
+    } else {
+        out.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::ExportedChatMeta;
+
+    fn chat(name: &str, identifier: &str, guid: &str) -> ExportedChat {
+        ExportedChat {
+            meta: ExportedChatMeta {
+                name: name.to_string(),
+                identifier: identifier.to_string(),
+                guid: guid.to_string(),
+                service: "iMessage".to_string(),
+                message_count: 0,
+                participant_count: 1,
+                participants: Vec::new(),
+            },
+            messages: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn filenames_combine_slug_and_guid_and_resolve_collisions() {
+        let chats = [
+            chat("Alice Johnson", "+15551234567", "iMessage;-;+15551234567"),
+            chat("Trip Planning 🇮🇹!", "chat123456", "iMessage;+;chat123456"),
+            chat("Alice Johnson", "+15551234567", "iMessage;-;+15551234567"),
+            chat("José", "telegram:42", ""),
+            chat("🎉", "", ""),
+        ];
+        assert_eq!(
+            chat_filenames(&chats, ExportFormat::Json),
+            [
+                "alice-johnson.iMessage-+15551234567.json",
+                "trip-planning.iMessage-chat123456.json",
+                "alice-johnson.iMessage-+15551234567-2.json",
+                "josé.telegram-42.json",
+                "chat.chat.json",
+            ]
+        );
+        assert_eq!(
+            chat_filenames(&chats[..1], ExportFormat::Csv),
+            ["alice-johnson.iMessage-+15551234567.csv"]
+        );
+        assert_eq!(slugify(&"a".repeat(100)).len(), MAX_SLUG_CHARS);
+    }
+}
@@ -0,0 +1,110 @@
+/*!
+ * Sender and keyword filters: export only messages from Mom, or only
+ * messages mentioning "house".
+ *
+ * Filters are applied while messages are streamed, before redaction, to
+ * the sender name as exported (contact name, or phone/email without one)
+ * and the full text. A message must pass every filter that is set. The
+ * manifest's `filters` records how many senders and messages were filtered,
+ * not which senders or what keyword.
+ */
+
+use serde::{Deserialize, Serialize};
+
+/// Which messages to export; the default keeps every message
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "cli", derive(clap::Args))]
+#[serde(default)]
+pub struct MessageFilter {
+    /// Keep only messages from these senders (case-insensitive; "Me" for
+    /// the device owner)
+    #[cfg_attr(feature = "cli", arg(long = "include-sender"))]
+    pub include_senders: Vec<String>,
+    /// Leave out messages from these senders
+    #[cfg_attr(feature = "cli", arg(long = "exclude-sender"))]
+    pub exclude_senders: Vec<String>,
+    /// Keep only messages whose text contains this (case-insensitive)
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub keyword: Option<String>,
+}
+
+impl MessageFilter {
+    pub fn is_enabled(&self) -> bool {
+        !self.include_senders.is_empty()
+            || !self.exclude_senders.is_empty()
+            || self.keyword().is_some()
+    }
+
+    /// Whether a message from `sender` with `text` is exported
+    pub fn matches(&self, sender: &str, text: &str) -> bool {
+        let listed = |names: &[String]| {
+            names
+                .iter()
+                .any(|name| name.trim().eq_ignore_ascii_case(sender.trim()))
+        };
+        if !self.include_senders.is_empty() && !listed(&self.include_senders) {
+            return false;
+        }
+        if listed(&self.exclude_senders) {
+            return false;
+        }
+        self.keyword()
+            .map_or(true, |keyword| text.to_lowercase().contains(&keyword))
+    }
+
+    /// The keyword, lowercased; `None` when unset or blank
+    fn keyword(&self) -> Option<String> {
+        self.keyword
+            .as_deref()
+            .map(str::trim)
+            .filter(|keyword| !keyword.is_empty())
+            .map(str::to_lowercase)
+    }
+
+    /// What the filter did, for the manifest; `None` when it is off
+    pub fn summary(&self, filtered_message_count: usize) -> Option<FilterSummary> {
+        self.is_enabled().then(|| FilterSummary {
+            include_sender_count: self.include_senders.len(),
+            exclude_sender_count: self.exclude_senders.len(),
+            keyword: self.keyword().is_some(),
+            filtered_message_count,
+        })
+    }
+}
+
+/// What filtering changed, written to the manifest as `filters`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FilterSummary {
+    pub include_sender_count: usize,
+    pub exclude_sender_count: usize,
+    /// Whether a keyword filter was set
+    pub keyword: bool,
+    /// Messages left out by the filters
+    pub filtered_message_count: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_must_pass_every_filter() {
+        let filter = MessageFilter {
+            include_senders: vec!["Mom ".to_string(), "me".to_string()],
+            exclude_senders: vec!["Me".to_string()],
+            keyword: Some(" House".to_string()),
+        };
+        assert!(filter.matches("mom", "The HOUSE is lovely"));
+        assert!(!filter.matches("Mom", "The flat is lovely"));
+        assert!(!filter.matches("Dad", "The house is lovely"));
+        assert!(!filter.matches("Me", "The house is lovely"));
+
+        assert!(!MessageFilter::default().is_enabled());
+        assert!(MessageFilter::default().matches("Anyone", ""));
+        let blank = MessageFilter {
+            keyword: Some("  ".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(blank.summary(0), None);
+    }
+}
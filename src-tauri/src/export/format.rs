@@ -0,0 +1,159 @@
+/*!
+ * The data written for each chat in an export zip (see
+ * [`super::chat_files`] for the file formats).
+ */
+
+use imessage_database::tables::messages::Message;
+use serde::{Deserialize, Serialize};
+
+use super::{format_timestamp_in, ExportTimezone, GroupEvent, TimestampUnit};
+
+/// A single exported message in our JSON format
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportedMessage {
+    /// Message GUID, the same in every export of the message, so the server
+    /// can drop duplicates when exports overlap; empty when unknown
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub guid: String,
+    /// ISO 8601 timestamp
+    pub timestamp: String,
+    /// Sender name or phone/email
+    pub sender: String,
+    /// Whether this message is from the device owner
+    pub is_from_me: bool,
+    /// Message text content
+    pub text: String,
+    /// Set when `text` was cut to `ExportOptions::max_text_length`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub truncated: bool,
+    /// Set for a message in Recently Deleted, exported with
+    /// `ExportOptions::include_recently_deleted`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub deleted: bool,
+    /// Service and delivery details, with `ExportOptions::include_metadata`
+    #[serde(flatten, default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<MessageMetadata>,
+    /// Photos and files sent with the message; a message with attachments
+    /// but no caption is exported with empty `text`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<ExportedAttachment>,
+    /// Set (with empty `text`) when the message is a group rename,
+    /// participant change or group photo change rather than something said
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub event: Option<GroupEvent>,
+    /// http(s) links in the text and link preview, in order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub urls: Vec<String>,
+    /// The preview Messages showed for a sent link
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub link_preview: Option<LinkPreview>,
+}
+
+/// A link preview as Messages fetched it when the link was sent; the page
+/// may have changed or gone since
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LinkPreview {
+    /// Where the link ended up, after redirects
+    pub url: String,
+    /// The page's title (`og:title`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// The page's description (`og:description`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub site_name: Option<String>,
+}
+
+/// A file sent with a message (the file itself is not exported)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExportedAttachment {
+    /// File name as sent, e.g. `IMG_0042.jpg`; empty when unknown
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub name: String,
+    /// e.g. `image/jpeg`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+    /// What was said in an audio message, as transcribed by Apple
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transcription: Option<String>,
+}
+
+/// Per-message details from the `message` table, written alongside the
+/// message's other fields
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MessageMetadata {
+    /// Service the message went over (iMessage, SMS, RCS)
+    pub service: String,
+    /// ISO 8601; unset until the message was delivered
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub date_delivered: Option<String>,
+    /// ISO 8601; unset until the message was read (or read receipts are off)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub date_read: Option<String>,
+    /// Whether the sender edited the message after sending it
+    pub is_edited: bool,
+}
+
+impl MessageMetadata {
+    pub(super) fn from_message(
+        message: &Message,
+        unit: TimestampUnit,
+        timezone: ExportTimezone,
+    ) -> Self {
+        // Unset dates are stored as 0
+        let date = |date: i64| (date != 0).then(|| format_timestamp_in(date, unit, timezone));
+        Self {
+            service: message.service.clone().unwrap_or_default(),
+            date_delivered: date(message.date_delivered),
+            date_read: date(message.date_read),
+            is_edited: message.is_edited(),
+        }
+    }
+}
+
+/// Metadata about an exported chat.
+///
+/// `participant_count` is the number of distinct people in the chat OTHER
+/// than the device owner (1 for a 1:1 chat, N for a group of N+1 people).
+/// The SaaS uses this to format the display title — see
+/// `convex/uploadPlatform.ts:deriveIMessageDisplayTitle`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportedChatMeta {
+    /// Resolved chat display name. Falls back from custom group name → 1:1
+    /// contact name → identifier → "Chat <id>". Same resolution as the
+    /// chat list UI.
+    pub name: String,
+    /// Raw chat identifier (phone number, email, or group ID)
+    pub identifier: String,
+    /// Chat GUID (e.g. `iMessage;-;+15551234567`); empty for sources
+    /// without one
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub guid: String,
+    /// Service (iMessage, SMS)
+    pub service: String,
+    /// Number of messages exported
+    pub message_count: usize,
+    /// Number of OTHER participants (excludes device owner). 1 = 1:1 chat.
+    pub participant_count: usize,
+    /// Everyone in the chat, the device owner first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub participants: Vec<ExportedParticipant>,
+}
+
+/// One person in an exported chat
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExportedParticipant {
+    /// Contact name, or the identifier without one; "Me" for the owner
+    pub name: String,
+    /// Handle (phone number or email); empty for the owner
+    pub identifier: String,
+    pub is_me: bool,
+}
+
+/// Complete export data for a single chat
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportedChat {
+    pub meta: ExportedChatMeta,
+    pub messages: Vec<ExportedMessage>,
+}
@@ -0,0 +1,33 @@
+/*!
+ * Merged chat groups: duplicate chats for the same people that `list_chats`
+ * folded together (see `crate::chat_merge`), exported as one conversation
+ * under the group's first chat ID.
+ */
+
+use std::collections::HashMap;
+
+use imessage_database::tables::chat::Chat;
+
+/// Map each chat in a merged group to the group's first (canonical) chat ID
+pub(super) fn canonical_chat_ids(chat_groups: &[Vec<i32>]) -> HashMap<i32, i32> {
+    chat_groups
+        .iter()
+        .filter_map(|group| Some((*group.first()?, group)))
+        .flat_map(|(canonical, group)| group.iter().map(move |&id| (id, canonical)))
+        .collect()
+}
+
+/// Service name for an exported chat; merged groups list each service once
+pub(super) fn combined_service(chats: &HashMap<i32, Chat>, chat_ids: &[i32]) -> String {
+    let mut services: Vec<&str> = Vec::new();
+    for id in chat_ids {
+        let service = chats
+            .get(id)
+            .and_then(|c| c.service_name.as_deref())
+            .unwrap_or("Unknown");
+        if !services.contains(&service) {
+            services.push(service);
+        }
+    }
+    services.join(" + ")
+}
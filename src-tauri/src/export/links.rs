@@ -0,0 +1,107 @@
+/*!
+ * Links in exported messages.
+ *
+ * Shared Google Maps, Airbnb and restaurant links are some of the best
+ * mapping inputs, so each message carries its `urls` ready-made instead of
+ * leaving the server to re-parse them from text. They come from the text
+ * and from the link preview Messages stores in `payload_data` when a link
+ * is sent; the preview's resolved URL (after redirects) is added when the
+ * text doesn't already have it. The preview's title, description and site
+ * name are exported as `link_preview`: they often name the place when the
+ * URL itself is an opaque short link.
+ */
+
+use imessage_database::{
+    message_types::{
+        url::URLMessage,
+        variants::{BalloonProvider, URLOverride},
+    },
+    tables::messages::Message,
+    util::plist::parse_ns_keyed_archiver,
+};
+use rusqlite::Connection;
+
+use super::LinkPreview;
+use crate::shared_links::extract_urls;
+
+/// The URLs in `text` followed by the preview's, without duplicates
+/// (ignoring a trailing `/`)
+pub(super) fn message_urls(text: Option<&str>, preview: Option<&LinkPreview>) -> Vec<String> {
+    let mut urls: Vec<String> = Vec::new();
+    let candidates = text
+        .map(extract_urls)
+        .unwrap_or_default()
+        .into_iter()
+        .chain(preview.map(|preview| preview.url.clone()));
+    for url in candidates {
+        let key = url.trim_end_matches('/');
+        if !urls.iter().any(|seen| seen.trim_end_matches('/') == key) {
+            urls.push(url);
+        }
+    }
+    urls
+}
+
+/// The link preview of a URL balloon message, if it has one with a URL
+pub(super) fn link_preview(db: &Connection, message: &Message) -> Option<LinkPreview> {
+    if !message.is_url() {
+        return None;
+    }
+    let payload = parse_ns_keyed_archiver(&message.payload_data(db)?).ok()?;
+    let owned = |text: Option<&str>| {
+        text.map(str::trim)
+            .filter(|text| !text.is_empty())
+            .map(String::from)
+    };
+    match URLMessage::get_url_message_override(&payload).ok()? {
+        URLOverride::Normal(balloon) => Some(LinkPreview {
+            url: owned(balloon.get_url())?,
+            title: owned(balloon.title),
+            summary: owned(balloon.summary),
+            site_name: owned(balloon.site_name),
+        }),
+        // A place shared from Maps; its title is the address
+        URLOverride::SharedPlacemark(place) => Some(LinkPreview {
+            url: owned(place.url.or(place.original_url))?,
+            title: owned(place.place_name),
+            summary: None,
+            site_name: None,
+        }),
+        _ => URLMessage::from_map(&payload)
+            .ok()
+            .and_then(|balloon| owned(balloon.get_url()))
+            .map(|url| LinkPreview {
+                url,
+                title: None,
+                summary: None,
+                site_name: None,
+            }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn urls_come_from_text_then_preview_without_duplicates() {
+        let preview = LinkPreview {
+            url: "https://maps.app.goo.gl/abc/".to_string(),
+            title: None,
+            summary: None,
+            site_name: None,
+        };
+        assert_eq!(
+            message_urls(
+                Some("Here https://maps.app.goo.gl/abc and https://airbnb.com/rooms/1."),
+                Some(&preview)
+            ),
+            ["https://maps.app.goo.gl/abc", "https://airbnb.com/rooms/1"]
+        );
+        assert_eq!(
+            message_urls(None, Some(&preview)),
+            ["https://maps.app.goo.gl/abc/"]
+        );
+        assert!(message_urls(Some("no links"), None).is_empty());
+    }
+}
@@ -0,0 +1,429 @@
+/*!
+ * Merging several export zips into one upload.
+ *
+ * For messages split across sources (this Mac's chat.db and an old iPhone
+ * backup, or a run of incremental scheduled exports), [`merge_exports`]
+ * reads each zip and writes one with every chat in any of them. Chats are
+ * matched by identifier, like the diff, and a chat's messages are joined
+ * in time order. A message in more than one export is kept once: with a
+ * GUID, it's the same message when the GUID and time match; without one
+ * (Telegram), when the time, sender and text do. Times are compared as
+ * instants, so exports in different time zones still match. Only copies in
+ * different exports are duplicates; repeats within one export (two "ok"s
+ * in the same second) are all kept.
+ *
+ * The merged zip is written as JSON, what the server reads, unless the
+ * caller asks to keep the first export's format. Its manifest takes its
+ * compression and time zone from the first export, and lists every input
+ * under `merged_from`. Shared links are combined too; other per-export
+ * details (filters, redaction, warnings) aren't carried over.
+ */
+
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+
+use super::{
+    archive,
+    diff::{read_archive, ArchiveContents},
+    ExportFormat, ExportResult, ExportTimezone, ExportedChat, ExportedMessage,
+};
+
+/// The manifest `source` when the exports came from different sources
+pub const MERGED_SOURCE: &str = "merged";
+
+/// A merged export zip and what went into it
+#[derive(Debug)]
+pub struct MergedExport {
+    pub result: ExportResult,
+    /// The inputs' shared `source`, or [`MERGED_SOURCE`]
+    pub source: String,
+    /// Messages left out for being in an earlier export already
+    pub duplicate_messages: usize,
+}
+
+/// Chats of every archive, each message once
+#[derive(Debug, Default)]
+pub struct MergedChats {
+    pub chats: Vec<ExportedChat>,
+    pub duplicate_messages: usize,
+}
+
+/// Merge the export zips at `paths` in order (the first one's chat names
+/// win) into a new zip, written as `format` (`None` keeps the first one's)
+pub fn merge_exports(
+    paths: &[PathBuf],
+    format: Option<ExportFormat>,
+) -> Result<MergedExport, String> {
+    if paths.len() < 2 {
+        return Err("Choose at least two exports to merge".to_string());
+    }
+    let archives = paths
+        .iter()
+        .map(|path| read_archive(path))
+        .collect::<Result<Vec<_>, _>>()?;
+    tracing::info!("[merge] Merging {} exports", archives.len());
+    let manifests: Vec<Value> = archives
+        .iter()
+        .map(|archive| archive.manifest.clone())
+        .collect();
+    let shared_links = merge_shared_links(paths)?;
+    let merged = merge_archives(archives);
+
+    let source = common_source(&manifests);
+    let first = &manifests[0];
+    let total_messages = merged.chats.iter().map(|chat| chat.messages.len()).sum();
+    let mut manifest = archive::new_manifest(
+        &source,
+        &merged.chats,
+        total_messages,
+        format.unwrap_or_else(|| ExportFormat::of_manifest(first)),
+        ExportTimezone::Utc,
+    );
+    manifest["timezone"] = first["timezone"].clone();
+    manifest["compression"] = first["compression"].clone();
+    manifest["merged_from"] = manifests.iter().map(merged_from_entry).collect();
+    manifest["duplicate_message_count"] = merged.duplicate_messages.into();
+    let mut extra_files = Vec::new();
+    if let Some(links) = shared_links {
+        manifest["shared_link_count"] = links.len().into();
+        extra_files.push((
+            "shared_links.json".to_string(),
+            serde_json::to_string_pretty(&links).unwrap(),
+        ));
+    }
+    let result = archive::write_archive(&manifest, &merged.chats, &extra_files, total_messages)?;
+    Ok(MergedExport {
+        result,
+        source,
+        duplicate_messages: merged.duplicate_messages,
+    })
+}
+
+/// Join the chats of `archives`, keeping each message once
+pub fn merge_archives(archives: Vec<ArchiveContents>) -> MergedChats {
+    let mut merged = MergedChats::default();
+    let mut index: HashMap<String, usize> = HashMap::new();
+    // The archive each merged chat's messages came from, in order
+    let mut inputs: Vec<Vec<usize>> = Vec::new();
+    for (input, archive) in archives.into_iter().enumerate() {
+        for chat in archive.chats {
+            let from = vec![input; chat.messages.len()];
+            match index.get(&chat_key(&chat)) {
+                Some(&i) => {
+                    inputs[i].extend(from);
+                    join_chat(&mut merged.chats[i], chat);
+                }
+                None => {
+                    index.insert(chat_key(&chat), merged.chats.len());
+                    inputs.push(from);
+                    merged.chats.push(chat);
+                }
+            }
+        }
+    }
+    for (chat, inputs) in merged.chats.iter_mut().zip(inputs) {
+        let before = chat.messages.len();
+        chat.messages = without_duplicates(std::mem::take(&mut chat.messages), &inputs);
+        merged.duplicate_messages += before - chat.messages.len();
+        chat.messages
+            .sort_by_key(|message| sent_at(&message.timestamp));
+        chat.meta.message_count = chat.messages.len();
+    }
+    merged
+}
+
+/// `messages` (from archives `inputs`) without copies of ones in another
+/// archive: each is kept as many times as the archive with the most copies
+/// has it
+fn without_duplicates(messages: Vec<ExportedMessage>, inputs: &[usize]) -> Vec<ExportedMessage> {
+    let mut kept: HashMap<MessageKey, usize> = HashMap::new();
+    let mut seen: HashMap<(MessageKey, usize), usize> = HashMap::new();
+    messages
+        .into_iter()
+        .zip(inputs)
+        .filter(|(message, &input)| {
+            let key = message_key(message);
+            let seen = seen.entry((key.clone(), input)).or_default();
+            *seen += 1;
+            let kept = kept.entry(key).or_default();
+            let new = *seen > *kept;
+            if new {
+                *kept += 1;
+            }
+            new
+        })
+        .map(|(message, _)| message)
+        .collect()
+}
+
+/// Add `other`'s messages and participants to `chat`
+fn join_chat(chat: &mut ExportedChat, other: ExportedChat) {
+    chat.messages.extend(other.messages);
+    for participant in other.meta.participants {
+        if !chat.meta.participants.contains(&participant) {
+            chat.meta.participants.push(participant);
+        }
+    }
+    chat.meta.participant_count = chat
+        .meta
+        .participant_count
+        .max(other.meta.participant_count);
+    if chat.meta.guid.is_empty() {
+        chat.meta.guid = other.meta.guid;
+    }
+}
+
+/// Identifier, else name (as in the diff)
+fn chat_key(chat: &ExportedChat) -> String {
+    if chat.meta.identifier.is_empty() {
+        chat.meta.name.clone()
+    } else {
+        chat.meta.identifier.clone()
+    }
+}
+
+/// When a message was sent, as an instant when the timestamp parses
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum SentAt {
+    At(DateTime<Utc>),
+    Unparsed(String),
+}
+
+fn sent_at(timestamp: &str) -> SentAt {
+    DateTime::parse_from_rfc3339(timestamp)
+        .map(|at| SentAt::At(at.with_timezone(&Utc)))
+        .unwrap_or_else(|_| SentAt::Unparsed(timestamp.to_string()))
+}
+
+/// GUID and time, or time, sender and text for messages without a GUID
+type MessageKey = (SentAt, String, String, String);
+
+fn message_key(message: &ExportedMessage) -> MessageKey {
+    let at = sent_at(&message.timestamp);
+    if message.guid.is_empty() {
+        (
+            at,
+            String::new(),
+            message.sender.clone(),
+            message.text.clone(),
+        )
+    } else {
+        (at, message.guid.clone(), String::new(), String::new())
+    }
+}
+
+/// The sources' shared name, or [`MERGED_SOURCE`]
+fn common_source(manifests: &[Value]) -> String {
+    let sources: HashSet<&str> = manifests
+        .iter()
+        .map(|manifest| manifest["source"].as_str().unwrap_or_default())
+        .collect();
+    match sources.into_iter().collect::<Vec<_>>()[..] {
+        [source] if !source.is_empty() => source.to_string(),
+        _ => MERGED_SOURCE.to_string(),
+    }
+}
+
+fn merged_from_entry(manifest: &Value) -> Value {
+    serde_json::json!({
+        "source": manifest["source"],
+        "export_date": manifest["export_date"],
+        "since": manifest["since"],
+        "chat_count": manifest["chat_count"],
+        "total_messages": manifest["total_messages"],
+    })
+}
+
+/// Every export's shared links, each once; `None` if none had the file
+fn merge_shared_links(paths: &[PathBuf]) -> Result<Option<Vec<Value>>, String> {
+    let mut merged: Option<Vec<Value>> = None;
+    for path in paths {
+        let Some(links) = read_shared_links(path)? else {
+            continue;
+        };
+        let merged = merged.get_or_insert_with(Vec::new);
+        for link in links {
+            if !merged.contains(&link) {
+                merged.push(link);
+            }
+        }
+    }
+    Ok(merged)
+}
+
+fn read_shared_links(path: &Path) -> Result<Option<Vec<Value>>, String> {
+    let file = std::fs::File::open(path).map_err(|e| format!("Failed to open {path:?}: {e}"))?;
+    let mut zip =
+        zip::ZipArchive::new(file).map_err(|e| format!("Not an export zip {path:?}: {e}"))?;
+    let Ok(entry) = zip.by_name("shared_links.json") else {
+        return Ok(None);
+    };
+    serde_json::from_reader(entry)
+        .map(Some)
+        .map_err(|e| format!("Invalid shared_links.json in {path:?}: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::export::{archive::write_archive, ExportFormat, ExportedChatMeta};
+
+    fn message(guid: &str, timestamp: &str, text: &str) -> ExportedMessage {
+        ExportedMessage {
+            guid: guid.to_string(),
+            timestamp: timestamp.to_string(),
+            sender: "Alice".to_string(),
+            is_from_me: false,
+            text: text.to_string(),
+            truncated: false,
+            deleted: false,
+            metadata: None,
+            attachments: Vec::new(),
+            event: None,
+            urls: Vec::new(),
+            link_preview: None,
+        }
+    }
+
+    fn chat(identifier: &str, messages: Vec<ExportedMessage>) -> ExportedChat {
+        ExportedChat {
+            meta: ExportedChatMeta {
+                name: identifier.to_string(),
+                identifier: identifier.to_string(),
+                guid: String::new(),
+                service: "iMessage".to_string(),
+                message_count: messages.len(),
+                participant_count: 1,
+                participants: Vec::new(),
+            },
+            messages,
+        }
+    }
+
+    fn save(source: &str, chats: &[ExportedChat]) -> ExportResult {
+        let total = chats.iter().map(|chat| chat.messages.len()).sum();
+        let manifest = archive::new_manifest(
+            source,
+            chats,
+            total,
+            ExportFormat::Json,
+            ExportTimezone::Utc,
+        );
+        write_archive(&manifest, chats, &[], total).unwrap()
+    }
+
+    #[test]
+    fn messages_in_several_exports_are_kept_once_in_time_order() {
+        let mac = save(
+            "imessage",
+            &[chat(
+                "+15551234567",
+                vec![
+                    message("m2", "2024-05-02T10:00:00+00:00", "still here"),
+                    message("m3", "2024-05-03T10:00:00+00:00", "new"),
+                ],
+            )],
+        );
+        let backup = save(
+            "imessage",
+            &[
+                chat(
+                    "+15551234567",
+                    vec![
+                        message("m1", "2024-05-01T10:00:00+00:00", "deleted on the Mac"),
+                        // The same message, exported in another time zone
+                        message("m2", "2024-05-02T22:00:00+12:00", "still here"),
+                    ],
+                ),
+                chat(
+                    "+15559999999",
+                    vec![message("m9", "2023-01-01T00:00:00Z", "old")],
+                ),
+            ],
+        );
+
+        let paths = [mac.zip_path.clone(), backup.zip_path.clone()];
+        let merged = merge_exports(&paths, Some(ExportFormat::Json)).unwrap();
+        assert_eq!(merged.source, "imessage");
+        assert_eq!(merged.duplicate_messages, 1);
+        assert_eq!(merged.result.total_messages, 4);
+
+        let archive = read_archive(&merged.result.zip_path).unwrap();
+        let texts: Vec<&str> = archive.chats[0]
+            .messages
+            .iter()
+            .map(|message| message.text.as_str())
+            .collect();
+        assert_eq!(texts, ["deleted on the Mac", "still here", "new"]);
+        assert_eq!(archive.chats[0].meta.message_count, 3);
+        assert_eq!(archive.chats[1].meta.identifier, "+15559999999");
+        assert_eq!(archive.manifest["merged_from"].as_array().unwrap().len(), 2);
+        assert_eq!(archive.manifest["duplicate_message_count"], 1);
+
+        assert!(merge_exports(&[mac.zip_path], None).is_err());
+    }
+
+    #[test]
+    fn merged_exports_are_json_unless_the_format_is_kept() {
+        let ndjson_chats = [chat(
+            "+15551234567",
+            vec![message("m1", "2024-05-01T10:00:00Z", "hi")],
+        )];
+        let total = 1;
+        let manifest = archive::new_manifest(
+            "imessage",
+            &ndjson_chats,
+            total,
+            ExportFormat::Ndjson,
+            ExportTimezone::Utc,
+        );
+        let ndjson = write_archive(&manifest, &ndjson_chats, &[], total).unwrap();
+        let json = save("imessage", &[chat("+15559999999", Vec::new())]);
+        let paths = [ndjson.zip_path.clone(), json.zip_path.clone()];
+
+        let merged = merge_exports(&paths, Some(ExportFormat::Json)).unwrap();
+        let manifest = read_archive(&merged.result.zip_path).unwrap().manifest;
+        assert_eq!(ExportFormat::of_manifest(&manifest), ExportFormat::Json);
+
+        let kept = merge_exports(&paths, None).unwrap();
+        let manifest = read_archive(&kept.result.zip_path).unwrap().manifest;
+        assert_eq!(ExportFormat::of_manifest(&manifest), ExportFormat::Ndjson);
+    }
+
+    #[test]
+    fn messages_without_guids_match_on_time_sender_and_text() {
+        let archive = |messages| ArchiveContents {
+            manifest: json!({"source": "telegram"}),
+            chats: vec![chat("telegram:1", messages)],
+        };
+        let merged = merge_archives(vec![
+            archive(vec![message("", "2024-01-01T00:00:00Z", "hi")]),
+            archive(vec![
+                message("", "2024-01-01T00:00:00Z", "hi"),
+                message("", "2024-01-01T00:00:00Z", "hi again"),
+                // Sent twice in the same second: both are kept
+                message("", "2024-01-01T00:00:05Z", "ok"),
+                message("", "2024-01-01T00:00:05Z", "ok"),
+            ]),
+            archive(vec![message("", "2024-01-01T00:00:05Z", "ok")]),
+        ]);
+        assert_eq!(merged.duplicate_messages, 2);
+        let texts: Vec<&str> = merged.chats[0]
+            .messages
+            .iter()
+            .map(|message| message.text.as_str())
+            .collect();
+        assert_eq!(texts, ["hi", "hi again", "ok", "ok"]);
+        assert_eq!(
+            common_source(&[json!({"source": "telegram"}), json!({"source": "imessage"})]),
+            MERGED_SOURCE
+        );
+    }
+}
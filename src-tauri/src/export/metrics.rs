@@ -0,0 +1,103 @@
+/*!
+ * Throughput of an export run.
+ *
+ * Every run reports the same numbers (messages/s decoded from the database,
+ * MB/s written to the zip, MB/s uploaded), so runs can be compared in the
+ * job history (`ctm-cli jobs --metrics`) when a slowdown is reported.
+ */
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+pub(crate) const BYTES_PER_MB: f64 = 1024.0 * 1024.0;
+
+/// Timings and rates for one export run
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ExportMetrics {
+    /// Messages written to the archive
+    pub messages: usize,
+    /// Size of the finished zip
+    pub archive_bytes: u64,
+    /// Time spent reading and decoding messages
+    pub decode_secs: f64,
+    /// Time spent compressing the zip
+    pub compress_secs: f64,
+    /// Time spent uploading; `None` when the zip wasn't uploaded
+    pub upload_secs: Option<f64>,
+    pub messages_per_sec: f64,
+    pub compressed_mb_per_sec: f64,
+    pub uploaded_mb_per_sec: Option<f64>,
+}
+
+impl ExportMetrics {
+    /// Metrics for a freshly written archive; decode time is added by the source
+    pub fn compressed(messages: usize, archive_bytes: u64, compress: Duration) -> Self {
+        let compress_secs = compress.as_secs_f64();
+        Self {
+            messages,
+            archive_bytes,
+            compress_secs,
+            compressed_mb_per_sec: per_sec(archive_bytes as f64 / BYTES_PER_MB, compress_secs),
+            ..Default::default()
+        }
+    }
+
+    pub fn record_decode(&mut self, decode: Duration) {
+        self.decode_secs = decode.as_secs_f64();
+        self.messages_per_sec = per_sec(self.messages as f64, self.decode_secs);
+    }
+
+    pub fn record_upload(&mut self, upload: Duration) {
+        let upload_secs = upload.as_secs_f64();
+        self.upload_secs = Some(upload_secs);
+        self.uploaded_mb_per_sec = Some(per_sec(
+            self.archive_bytes as f64 / BYTES_PER_MB,
+            upload_secs,
+        ));
+    }
+
+    /// One-line summary, e.g. for logs and the CLI
+    pub fn summary(&self) -> String {
+        let uploaded = match self.uploaded_mb_per_sec {
+            Some(rate) => format!("{rate:.2} MB/s uploaded"),
+            None => "not uploaded".to_string(),
+        };
+        format!(
+            "{:.0} msg/s decoded, {:.2} MB/s compressed, {uploaded}",
+            self.messages_per_sec, self.compressed_mb_per_sec
+        )
+    }
+}
+
+/// `amount / secs`, or 0 for runs too quick to time
+fn per_sec(amount: f64, secs: f64) -> f64 {
+    if secs > 0.0 {
+        amount / secs
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rates_are_derived_from_each_stage() {
+        let mut metrics = ExportMetrics::compressed(1000, 2 * 1024 * 1024, Duration::from_secs(4));
+        metrics.record_decode(Duration::from_millis(500));
+        assert_eq!(metrics.messages_per_sec, 2000.0);
+        assert_eq!(metrics.compressed_mb_per_sec, 0.5);
+        assert_eq!(
+            metrics.summary(),
+            "2000 msg/s decoded, 0.50 MB/s compressed, not uploaded"
+        );
+
+        metrics.record_upload(Duration::from_secs(1));
+        assert_eq!(metrics.uploaded_mb_per_sec, Some(2.0));
+
+        let instant = ExportMetrics::compressed(10, 100, Duration::ZERO);
+        assert_eq!(instant.compressed_mb_per_sec, 0.0);
+    }
+}
@@ -0,0 +1,56 @@
+/*!
+ * Export options: what goes into an export and how it is written.
+ */
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::{filters, redaction, CompressionProfile, ExportFormat, ExportTimezone};
+use crate::exclusions::ExclusionRule;
+
+/// Options controlling what goes into an export
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExportOptions {
+    /// Include the "Shared with You" link list as `shared_links.json`
+    pub include_shared_links: bool,
+    /// Free-form key/value metadata (trip name, client reference, notes),
+    /// written to the manifest and forwarded with `complete_upload`
+    pub metadata: BTreeMap<String, String>,
+    /// Groups of chat ROWIDs to export as one conversation (duplicates that
+    /// `list_chats` merged); the first ID in each group names the result
+    pub chat_groups: Vec<Vec<i32>>,
+    /// Cut message text longer than this many characters (marking the
+    /// message `truncated`); `None` keeps full text
+    pub max_text_length: Option<usize>,
+    /// Selected chats matching any of these rules are skipped
+    pub exclusion_rules: Vec<ExclusionRule>,
+    /// Write each chat as JSON (what the server reads), NDJSON, CSV or a
+    /// text transcript
+    pub format: ExportFormat,
+    /// Split chats with more messages than this into numbered part files
+    /// (`<chat>.part1.json`, ...); `None` writes each chat as one file
+    pub max_messages_per_file: Option<usize>,
+    /// How hard to compress the zip (already-compressed files are stored)
+    pub compression: CompressionProfile,
+    /// Add each message's service, delivered/read dates and edited flag
+    /// (not for rows recovered from raw columns)
+    pub include_metadata: bool,
+    /// Only export messages from (or not from) some senders, or
+    /// mentioning a keyword
+    pub filter: filters::MessageFilter,
+    /// Contact details, sender names and participants to remove
+    pub redaction: redaction::RedactionConfig,
+    /// Write timestamps in local time (the default) or UTC
+    pub timezone: ExportTimezone,
+    /// Also export messages in Recently Deleted (kept for up to 30 days
+    /// on macOS 13+), marked `deleted`
+    pub include_recently_deleted: bool,
+    /// Keep message text exactly as stored, skipping the cleaning pass
+    /// (attachment placeholders, typedstream remnants, odd whitespace)
+    pub raw_text: bool,
+    /// Only export messages sent at or after this time (an incremental
+    /// export, picking up where the last one left off)
+    pub since: Option<chrono::DateTime<chrono::Utc>>,
+}
@@ -0,0 +1,204 @@
+/*!
+ * Messages that lost their `chat_message_join` row.
+ *
+ * An iCloud sync glitch can leave a message without the join row that puts
+ * it in a chat. The message stream finds messages through that table, so
+ * such an orphan would be left out of every export. [`find_orphans`] works
+ * out each orphan's chat instead:
+ *
+ * 1. the chat of the nearest message, within [`PROXIMITY_WINDOW_SECS`],
+ *    with the same `handle_id` that still has its join row; or
+ * 2. the handle's only chat, when `chat_handle_join` lists just one.
+ *
+ * Messages in Recently Deleted have no join row either but are not
+ * orphans: they are left to `ExportOptions::include_recently_deleted`.
+ * Orphans matching neither (most often sent group messages, which have no
+ * handle) are unassignable: counted in the manifest, but not exported.
+ */
+
+use rusqlite::{Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    recovery::{raw_row, RawMessageRow},
+    TimestampUnit,
+};
+use crate::db_origin::{detect_origin, SchemaFeature};
+
+/// How far from an orphan the messages that place it in a chat may be
+pub const PROXIMITY_WINDOW_SECS: i64 = 12 * 60 * 60;
+
+/// How many orphaned messages an export placed in a chat, and how many it
+/// could not place anywhere
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OrphanCounts {
+    pub recovered: usize,
+    pub unassignable: usize,
+}
+
+impl OrphanCounts {
+    pub fn is_empty(&self) -> bool {
+        self.recovered == 0 && self.unassignable == 0
+    }
+}
+
+/// Every orphaned message in `db`
+pub(super) struct Orphans {
+    /// Orphans with the chat they were placed in as `chat_id`
+    pub assigned: Vec<RawMessageRow>,
+    pub unassignable: usize,
+}
+
+/// Find the messages without a join row and place them in chats
+pub(super) fn find_orphans(db: &Connection, unit: TimestampUnit) -> Result<Orphans, String> {
+    let error = |e: rusqlite::Error| format!("Failed to query orphaned messages: {e}");
+    let features = detect_origin(db).features;
+    let recently_deleted = if features.contains(&SchemaFeature::RecentlyDeleted) {
+        "AND NOT EXISTS (SELECT 1 FROM chat_recoverable_message_join d
+                         WHERE d.message_id = m.ROWID)"
+    } else {
+        ""
+    };
+    // The columns `raw_row` reads; each orphan's chat is filled in below
+    let mut stmt = db
+        .prepare(&format!(
+            "SELECT m.ROWID, 0, m.date, NULL, m.is_from_me, m.handle_id,
+                    CAST(m.text AS BLOB), m.attributedBody, m.guid
+             FROM message m
+             WHERE NOT EXISTS (SELECT 1 FROM chat_message_join cmj WHERE cmj.message_id = m.ROWID)
+             {recently_deleted}
+             ORDER BY m.ROWID"
+        ))
+        .map_err(error)?;
+    let rows: Vec<RawMessageRow> = stmt
+        .query_map([], raw_row)
+        .map_err(error)?
+        .flatten()
+        .collect();
+
+    let mut orphans = Orphans {
+        assigned: Vec::new(),
+        unassignable: 0,
+    };
+    for mut row in rows {
+        match orphan_chat(db, &row, unit).map_err(error)? {
+            Some(chat_id) => {
+                row.chat_id = chat_id;
+                orphans.assigned.push(row);
+            }
+            None => orphans.unassignable += 1,
+        }
+    }
+    Ok(orphans)
+}
+
+/// The chat an orphan most likely belongs to
+fn orphan_chat(
+    db: &Connection,
+    row: &RawMessageRow,
+    unit: TimestampUnit,
+) -> rusqlite::Result<Option<i32>> {
+    let Some(handle_id) = row.handle_id.filter(|&id| id > 0) else {
+        return Ok(None);
+    };
+    let nearest = db
+        .prepare_cached(
+            "SELECT cmj.chat_id
+             FROM message m
+             JOIN chat_message_join cmj ON cmj.message_id = m.ROWID
+             WHERE m.handle_id = ?1 AND m.date BETWEEN ?2 - ?3 AND ?2 + ?3
+             ORDER BY ABS(m.date - ?2)
+             LIMIT 1",
+        )?
+        .query_row(
+            (handle_id, row.date, unit.span(PROXIMITY_WINDOW_SECS)),
+            |r| r.get(0),
+        )
+        .optional()?;
+    if nearest.is_some() {
+        return Ok(nearest);
+    }
+
+    let chats = db
+        .prepare_cached("SELECT chat_id FROM chat_handle_join WHERE handle_id = ?1 LIMIT 2")?
+        .query_map([handle_id], |r| r.get(0))?
+        .collect::<rusqlite::Result<Vec<i32>>>()?;
+    Ok(match chats[..] {
+        [chat_id] => Some(chat_id),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{ChatBuilder, HandleBuilder, MessageBuilder, TestIMessageDb};
+
+    #[test]
+    fn orphans_are_placed_by_nearby_messages_or_the_handles_only_chat() {
+        let mut db = TestIMessageDb::new().unwrap();
+        let alice = db.handle(HandleBuilder::new("+15551234567")).unwrap();
+        let bob = db.handle(HandleBuilder::new("+15557654321")).unwrap();
+        let direct = db.chat(ChatBuilder::new("+15551234567")).unwrap();
+        let group = db.chat(ChatBuilder::new("chat-group")).unwrap();
+        let bobs = db.chat(ChatBuilder::new("+15557654321")).unwrap();
+        for (chat, handle) in [(direct, alice), (group, alice), (group, bob), (bobs, bob)] {
+            db.chat_handle(chat, handle).unwrap();
+        }
+        let hour = 60 * 60;
+        db.message(
+            MessageBuilder::new()
+                .text("a")
+                .handle(alice)
+                .chat(group)
+                .date(0),
+        )
+        .unwrap();
+        db.message(
+            MessageBuilder::new()
+                .text("b")
+                .handle(alice)
+                .chat(direct)
+                .date(100 * hour),
+        )
+        .unwrap();
+
+        // Near the group message; far from anything, in two chats; Bob's
+        // with no messages but also in two chats; sent without a handle
+        let near = db
+            .message(MessageBuilder::new().text("c").handle(alice).date(hour))
+            .unwrap();
+        db.message(
+            MessageBuilder::new()
+                .text("d")
+                .handle(alice)
+                .date(50 * hour),
+        )
+        .unwrap();
+        db.message(MessageBuilder::new().text("e").handle(bob).date(0))
+            .unwrap();
+        db.message(MessageBuilder::new().text("f").from_me().date(0))
+            .unwrap();
+
+        let orphans = find_orphans(db.conn(), TimestampUnit::Seconds).unwrap();
+        let assigned: Vec<(i32, i32)> = orphans
+            .assigned
+            .iter()
+            .map(|r| (r.rowid, r.chat_id))
+            .collect();
+        assert_eq!(assigned, [(near, group)]);
+        assert_eq!(orphans.unassignable, 3);
+
+        // Once Bob is only in his own chat, that's where his orphan goes
+        db.conn()
+            .execute(
+                "DELETE FROM chat_handle_join WHERE chat_id = ?1 AND handle_id = ?2",
+                (group, bob),
+            )
+            .unwrap();
+        let orphans = find_orphans(db.conn(), TimestampUnit::Seconds).unwrap();
+        assert_eq!(orphans.assigned.len(), 2);
+        assert_eq!(orphans.assigned[1].chat_id, bobs);
+        assert_eq!(orphans.unassignable, 2);
+    }
+}
@@ -0,0 +1,62 @@
+/*!
+ * Splitting very large chats across several files.
+ *
+ * A chat with hundreds of thousands of messages makes a chat file of
+ * hundreds of MB, which the server is slow to load. With
+ * `ExportOptions::max_messages_per_file` set, a chat with more messages
+ * than that is written as numbered parts of at most that many messages,
+ * in date order: `alice.iMessage-+15551234567.part1.json`, `.part2.json`,
+ * ... Each part is a complete chat file with the chat's metadata (and its
+ * total `message_count`). The chat's `chat_files` entry in the manifest
+ * then lists the parts, with their message counts and date ranges, under
+ * `parts` instead of naming one `file`.
+ */
+
+use serde_json::Value;
+
+/// The largest number of messages per chat file a manifest records, if
+/// chats are split
+pub(super) fn max_messages_per_file(manifest: &Value) -> Option<usize> {
+    manifest["max_messages_per_file"]
+        .as_u64()
+        .map(|max| max as usize)
+        .filter(|&max| max > 0)
+}
+
+/// The parts a chat file `filename` with `message_count` messages is split
+/// into; empty when it fits in one file
+pub(super) fn part_filenames(
+    filename: &str,
+    message_count: usize,
+    max_messages: Option<usize>,
+) -> Vec<String> {
+    let Some(max) = max_messages.filter(|&max| message_count > max) else {
+        return Vec::new();
+    };
+    let (stem, extension) = filename.rsplit_once('.').unwrap_or((filename, ""));
+    (1..=(message_count + max - 1) / max)
+        .map(|n| format!("{stem}.part{n}.{extension}"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chats_over_the_limit_are_numbered_parts() {
+        assert_eq!(
+            part_filenames("alice.iMessage-+15551234567.json", 5, Some(2)),
+            [
+                "alice.iMessage-+15551234567.part1.json",
+                "alice.iMessage-+15551234567.part2.json",
+                "alice.iMessage-+15551234567.part3.json"
+            ]
+        );
+        assert!(part_filenames("alice.csv", 2, Some(2)).is_empty());
+        assert!(part_filenames("alice.csv", 5, None).is_empty());
+
+        let manifest = serde_json::json!({ "max_messages_per_file": 0 });
+        assert_eq!(max_messages_per_file(&manifest), None);
+    }
+}
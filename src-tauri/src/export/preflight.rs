@@ -0,0 +1,341 @@
+/*!
+ * Pre-export content analysis.
+ *
+ * A chat that is 95% tapbacks produces a nearly empty export (tapbacks
+ * aren't exported; text and attachment-only messages are), and users
+ * reasonably blame the app. Before exporting, [`preflight_export`] counts
+ * what each selected chat contains using cheap SQL (no text decoding) and
+ * turns those counts into actionable warnings for the selection screen.
+ */
+
+use std::collections::HashMap;
+
+use imessage_database::tables::{chat::Chat, chat_handle::ChatToHandle, table::Cacheable};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+use super::{ExportOptions, SenderNames};
+
+/// Share of non-exportable messages above which a chat gets a warning
+const UNSUPPORTED_WARNING_RATIO: f64 = 0.95;
+
+/// What a single chat contains, by exportability
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatContentSummary {
+    pub chat_id: i32,
+    pub name: String,
+    pub total_messages: usize,
+    /// Messages with text
+    pub text_messages: usize,
+    /// Messages that are only attachments (photos, videos, files), exported
+    /// with their attachment list and empty text
+    pub attachment_only_messages: usize,
+    /// Tapback reactions ("Loved …", "Liked …")
+    pub tapbacks: usize,
+    /// "Shared with You" links in this chat
+    pub shared_links: usize,
+}
+
+impl ChatContentSummary {
+    /// Messages the export carries: text, or attachments without text
+    pub fn exportable_messages(&self) -> usize {
+        self.text_messages + self.attachment_only_messages
+    }
+}
+
+/// Machine-readable warning category
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportWarningKind {
+    /// The chat has messages but none of them would be exported
+    NoExportableMessages,
+    /// Nearly all of the chat is tapbacks, which aren't exported
+    MostlyUnsupportedContent,
+    /// The chat has "Shared with You" links but they are not enabled
+    SharedLinksAvailable,
+}
+
+/// A warning the frontend shows before the user commits to an export
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportWarning {
+    pub chat_id: i32,
+    pub kind: ExportWarningKind,
+    pub message: String,
+}
+
+/// Result of [`preflight_export`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportPreflight {
+    pub chats: Vec<ChatContentSummary>,
+    pub warnings: Vec<ExportWarning>,
+}
+
+/// Analyze the selected chats against `options` without exporting anything
+pub fn preflight_export(
+    chat_ids: &[i32],
+    options: &ExportOptions,
+    custom_db_path: Option<&std::path::Path>,
+) -> Result<ExportPreflight, String> {
+    let chat_db = crate::db_snapshot::open_chat_db(custom_db_path)?;
+    let db = &chat_db.conn;
+    let names = chat_names(db)?;
+
+    let mut chats = Vec::new();
+    for &chat_id in chat_ids {
+        let mut summary = summarize_chat(db, chat_id)
+            .map_err(|e| format!("Failed to analyze chat {chat_id}: {e}"))?;
+        summary.name = names
+            .get(&chat_id)
+            .cloned()
+            .unwrap_or_else(|| format!("Chat {chat_id}"));
+        chats.push(summary);
+    }
+
+    let warnings = chats
+        .iter()
+        .flat_map(|summary| warnings_for(summary, options))
+        .collect();
+    Ok(ExportPreflight { chats, warnings })
+}
+
+/// Resolve display names the same way the chat list does
+pub(crate) fn chat_names(db: &Connection) -> Result<HashMap<i32, String>, String> {
+    let chats = Chat::cache(db).map_err(|e| format!("Failed to load chats: {e}"))?;
+    let senders = SenderNames::load(db)?;
+    let mut chat_participants =
+        ChatToHandle::cache(db).map_err(|e| format!("Failed to load participants: {e}"))?;
+    senders.owner.remove_from(&mut chat_participants);
+
+    Ok(chats
+        .iter()
+        .map(|(&id, chat)| (id, senders.chat_name(chat, chat_participants.get(&id))))
+        .collect())
+}
+
+/// Count a chat's messages by category with a single aggregate query
+fn summarize_chat(db: &Connection, chat_id: i32) -> rusqlite::Result<ChatContentSummary> {
+    // Tapbacks are associated messages of type 2000-3007. Text may live in
+    // `attributedBody` only (macOS 13+), so a non-null body counts as text.
+    let (total, text, attachment_only, tapbacks) = db.query_row(
+        "SELECT
+            COUNT(*),
+            SUM(CASE WHEN NOT is_tapback AND has_text THEN 1 ELSE 0 END),
+            SUM(CASE WHEN NOT is_tapback AND NOT has_text AND has_attachment THEN 1 ELSE 0 END),
+            SUM(CASE WHEN is_tapback THEN 1 ELSE 0 END)
+         FROM (
+            SELECT
+                COALESCE(m.associated_message_type, 0) BETWEEN 2000 AND 3007 AS is_tapback,
+                ((m.text IS NOT NULL AND m.text != '') OR m.attributedBody IS NOT NULL) AS has_text,
+                EXISTS (SELECT 1 FROM message_attachment_join a WHERE a.message_id = m.ROWID)
+                    AS has_attachment
+            FROM chat_message_join cmj
+            JOIN message m ON m.ROWID = cmj.message_id
+            WHERE cmj.chat_id = ?1
+         )",
+        [chat_id],
+        |row| {
+            Ok((
+                row.get::<_, usize>(0)?,
+                row.get::<_, Option<usize>>(1)?.unwrap_or(0),
+                row.get::<_, Option<usize>>(2)?.unwrap_or(0),
+                row.get::<_, Option<usize>>(3)?.unwrap_or(0),
+            ))
+        },
+    )?;
+
+    Ok(ChatContentSummary {
+        chat_id,
+        name: String::new(),
+        total_messages: total,
+        text_messages: text,
+        attachment_only_messages: attachment_only,
+        tapbacks,
+        shared_links: count_shared_links(db, chat_id),
+    })
+}
+
+/// Count "Shared with You" messages; 0 on databases without the column
+fn count_shared_links(db: &Connection, chat_id: i32) -> usize {
+    db.query_row(
+        "SELECT COUNT(*)
+         FROM chat_message_join cmj
+         JOIN message m ON m.ROWID = cmj.message_id
+         WHERE cmj.chat_id = ?1
+           AND m.syndication_ranges IS NOT NULL
+           AND m.syndication_ranges != ''",
+        [chat_id],
+        |row| row.get(0),
+    )
+    .unwrap_or(0)
+}
+
+/// Turn a chat summary into warnings, given the export options
+fn warnings_for(summary: &ChatContentSummary, options: &ExportOptions) -> Vec<ExportWarning> {
+    let mut warnings = Vec::new();
+    let name = &summary.name;
+    let exportable = summary.exportable_messages();
+    let skipped = summary.total_messages - exportable;
+
+    if summary.total_messages > 0 && exportable == 0 {
+        warnings.push(ExportWarning {
+            chat_id: summary.chat_id,
+            kind: ExportWarningKind::NoExportableMessages,
+            message: format!(
+                "'{name}' has no messages to export ({} tapbacks, which aren't exported)",
+                format_count(summary.tapbacks)
+            ),
+        });
+    } else if summary.total_messages > 0
+        && skipped as f64 / summary.total_messages as f64 >= UNSUPPORTED_WARNING_RATIO
+    {
+        warnings.push(ExportWarning {
+            chat_id: summary.chat_id,
+            kind: ExportWarningKind::MostlyUnsupportedContent,
+            message: format!(
+                "Only {} of {} messages in '{name}' will be exported; {} tapbacks will not be",
+                format_count(exportable),
+                format_count(summary.total_messages),
+                format_count(summary.tapbacks)
+            ),
+        });
+    }
+
+    if summary.shared_links > 0 && !options.include_shared_links {
+        warnings.push(ExportWarning {
+            chat_id: summary.chat_id,
+            kind: ExportWarningKind::SharedLinksAvailable,
+            message: format!(
+                "Enable Shared with You links to include {} links from '{name}'",
+                format_count(summary.shared_links)
+            ),
+        });
+    }
+
+    warnings
+}
+
+/// Format a count with thousands separators (1240 -> "1,240")
+fn format_count(n: usize) -> String {
+    let digits = n.to_string();
+    let mut out = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, ch) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i) % 3 == 0 {
+            out.push(',');
+        }
+        out.push(ch);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{ChatBuilder, HandleBuilder, MessageBuilder, TestIMessageDb};
+
+    fn summary(total: usize, text: usize, attachments: usize, links: usize) -> ChatContentSummary {
+        ChatContentSummary {
+            chat_id: 1,
+            name: "Italy 2024".to_string(),
+            total_messages: total,
+            text_messages: text,
+            attachment_only_messages: attachments,
+            tapbacks: total - text - attachments,
+            shared_links: links,
+        }
+    }
+
+    #[test]
+    fn format_count_adds_thousands_separators() {
+        assert_eq!(format_count(0), "0");
+        assert_eq!(format_count(999), "999");
+        assert_eq!(format_count(1240), "1,240");
+        assert_eq!(format_count(1234567), "1,234,567");
+    }
+
+    #[test]
+    fn mostly_tapbacks_chat_is_flagged() {
+        let warnings = warnings_for(&summary(1300, 20, 10, 0), &ExportOptions::default());
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(
+            warnings[0].kind,
+            ExportWarningKind::MostlyUnsupportedContent
+        );
+        assert!(warnings[0].message.contains("Only 30 of 1,300 messages"));
+        assert!(warnings[0].message.contains("1,270 tapbacks"));
+        assert!(warnings[0].message.contains("'Italy 2024'"));
+    }
+
+    #[test]
+    fn attachment_only_chats_are_exportable() {
+        // Photos without captions are exported with their attachment list
+        assert!(warnings_for(&summary(1300, 20, 1240, 0), &ExportOptions::default()).is_empty());
+        assert!(warnings_for(&summary(5, 0, 5, 0), &ExportOptions::default()).is_empty());
+
+        let tapbacks_only = warnings_for(&summary(5, 0, 0, 0), &ExportOptions::default());
+        assert_eq!(
+            tapbacks_only[0].kind,
+            ExportWarningKind::NoExportableMessages
+        );
+        assert!(tapbacks_only[0].message.contains("5 tapbacks"));
+    }
+
+    #[test]
+    fn text_heavy_chat_has_no_warnings() {
+        assert!(warnings_for(&summary(100, 90, 10, 0), &ExportOptions::default()).is_empty());
+    }
+
+    #[test]
+    fn shared_links_warning_depends_on_options() {
+        let disabled = warnings_for(&summary(100, 90, 10, 3), &ExportOptions::default());
+        assert_eq!(disabled[0].kind, ExportWarningKind::SharedLinksAvailable);
+
+        let enabled = ExportOptions {
+            include_shared_links: true,
+            ..Default::default()
+        };
+        assert!(warnings_for(&summary(100, 90, 10, 3), &enabled).is_empty());
+    }
+
+    #[test]
+    fn summarize_chat_counts_categories() {
+        let mut db = TestIMessageDb::new().unwrap();
+        let handle = db.handle(HandleBuilder::new("+15551234567")).unwrap();
+        let chat = db
+            .chat(ChatBuilder::new("iMessage;-;+15551234567"))
+            .unwrap();
+        db.message(MessageBuilder::new().text("hi").handle(handle).chat(chat))
+            .unwrap();
+        let photo = db
+            .message(MessageBuilder::new().handle(handle).chat(chat))
+            .unwrap();
+        db.conn()
+            .execute_batch(&format!(
+                "INSERT INTO attachment (ROWID, guid) VALUES (1, 'att-1');
+                 INSERT INTO message_attachment_join (message_id, attachment_id)
+                 VALUES ({photo}, 1);"
+            ))
+            .unwrap();
+        let tapback = db
+            .message(
+                MessageBuilder::new()
+                    .text("Loved “hi”")
+                    .handle(handle)
+                    .chat(chat),
+            )
+            .unwrap();
+        db.conn()
+            .execute(
+                "UPDATE message SET associated_message_type = 2000 WHERE ROWID = ?1",
+                [tapback],
+            )
+            .unwrap();
+
+        let summary = summarize_chat(db.conn(), chat).unwrap();
+        assert_eq!(summary.total_messages, 3);
+        assert_eq!(summary.text_messages, 1);
+        assert_eq!(summary.attachment_only_messages, 1);
+        assert_eq!(summary.tapbacks, 1);
+        assert_eq!(summary.exportable_messages(), 2);
+        assert_eq!(summary.shared_links, 0);
+    }
+}
@@ -0,0 +1,141 @@
+/*!
+ * Quick peek at a conversation before exporting it.
+ *
+ * The selection screen shows the last few messages of a chat so users can
+ * check it's the conversation they think it is. Messages go through the
+ * same text decoding and sender resolution as the export, so the preview
+ * is exactly what would be uploaded.
+ */
+
+use std::path::Path;
+
+use imessage_database::tables::messages::Message;
+use rusqlite::Connection;
+
+use super::{format_timestamp, recovery, ExportedMessage, SenderNames, TimestampUnit};
+
+/// Messages returned when the caller doesn't pass a limit
+pub const DEFAULT_PREVIEW_LIMIT: usize = 20;
+
+/// Upper bound on preview size, whatever the caller asks for
+const MAX_PREVIEW_LIMIT: usize = 200;
+
+/// The last `limit` messages with text in a chat, oldest first
+pub fn chat_preview(
+    chat_id: i32,
+    limit: Option<usize>,
+    custom_db_path: Option<&Path>,
+) -> Result<Vec<ExportedMessage>, String> {
+    let chat_db = crate::db_snapshot::open_chat_db(custom_db_path)?;
+    let db = &chat_db.conn;
+
+    let senders = SenderNames::load(db)?;
+
+    read_preview(
+        db,
+        chat_id,
+        limit.unwrap_or(DEFAULT_PREVIEW_LIMIT),
+        |is_from_me, handle_id| senders.name(is_from_me, handle_id),
+    )
+}
+
+/// Read the preview from an open database
+fn read_preview(
+    db: &Connection,
+    chat_id: i32,
+    limit: usize,
+    sender_name: impl Fn(bool, Option<i32>) -> String,
+) -> Result<Vec<ExportedMessage>, String> {
+    // Text may live in `attributedBody` only (macOS 13+)
+    let mut stmt = db
+        .prepare(
+            "SELECT m.guid
+             FROM message m
+             JOIN chat_message_join cmj ON cmj.message_id = m.ROWID
+             WHERE cmj.chat_id = ?1
+               AND ((m.text IS NOT NULL AND m.text != '') OR m.attributedBody IS NOT NULL)
+             ORDER BY m.date DESC
+             LIMIT ?2",
+        )
+        .map_err(|e| format!("Failed to query chat preview: {e}"))?;
+    let guids: Vec<String> = stmt
+        .query_map((chat_id, limit.min(MAX_PREVIEW_LIMIT) as i64), |row| {
+            row.get(0)
+        })
+        .map_err(|e| format!("Failed to query chat preview: {e}"))?
+        .flatten()
+        .collect();
+
+    let unit = TimestampUnit::detect(db);
+    let mut warnings = Vec::new();
+    let mut messages: Vec<ExportedMessage> = guids
+        .iter()
+        .filter_map(|guid| {
+            let mut message = Message::from_guid(guid, db).ok()?;
+            let text = recovery::message_text(db, &mut message, &mut warnings)
+                .filter(|t| !t.is_empty())?;
+            Some(ExportedMessage {
+                guid: message.guid.clone(),
+                timestamp: format_timestamp(message.date, unit),
+                sender: sender_name(message.is_from_me, message.handle_id),
+                is_from_me: message.is_from_me,
+                text,
+                truncated: false,
+                deleted: false,
+                metadata: None,
+                attachments: Vec::new(),
+                event: None,
+                urls: Vec::new(),
+                link_preview: None,
+            })
+        })
+        .collect();
+    for warning in &warnings {
+        tracing::warn!("[chat_preview] {warning}");
+    }
+
+    messages.reverse();
+    Ok(messages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{ChatBuilder, HandleBuilder, MessageBuilder, TestIMessageDb};
+
+    #[test]
+    fn returns_last_messages_oldest_first() {
+        let mut db = TestIMessageDb::new().unwrap();
+        let handle = db.handle(HandleBuilder::new("+15551234567")).unwrap();
+        let chat = db
+            .chat(ChatBuilder::new("iMessage;-;+15551234567"))
+            .unwrap();
+        let other = db
+            .chat(ChatBuilder::new("iMessage;-;+15559999999"))
+            .unwrap();
+        for (i, text) in ["one", "two", "three"].into_iter().enumerate() {
+            let builder = MessageBuilder::new()
+                .text(text)
+                .chat(chat)
+                .date(i as i64 * 1_000_000_000);
+            let builder = if i == 2 {
+                builder.from_me()
+            } else {
+                builder.handle(handle)
+            };
+            db.message(builder).unwrap();
+        }
+        db.message(MessageBuilder::new().text("elsewhere").chat(other))
+            .unwrap();
+
+        let sender =
+            |is_from_me: bool, _: Option<i32>| if is_from_me { "Me" } else { "Alice" }.to_string();
+        let preview = read_preview(db.conn(), chat, 2, sender).unwrap();
+
+        let texts: Vec<(&str, &str)> = preview
+            .iter()
+            .map(|m| (m.sender.as_str(), m.text.as_str()))
+            .collect();
+        assert_eq!(texts, vec![("Alice", "two"), ("Me", "three")]);
+    }
+}
@@ -0,0 +1,250 @@
+/*!
+ * Queue of export + upload jobs that run one after another.
+ *
+ * The queue only records jobs and their status; the app's worker takes the
+ * next queued job, runs it through the normal export pipeline (its job ID
+ * doubles as the export ID, so its progress events are attributable), and
+ * reports the outcome back with [`ExportQueue::finish`].
+ */
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::ExportOptions;
+
+/// What to export: the same arguments `export_and_upload` takes
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExportRequest {
+    pub chat_ids: Vec<i32>,
+    pub custom_db_path: Option<String>,
+    pub options: ExportOptions,
+    /// Shown in the queue, e.g. "2023"
+    pub label: Option<String>,
+    /// Export straight away but hold the upload until this time
+    /// (see schedule.rs)
+    pub upload_at: Option<DateTime<Utc>>,
+    /// Save the zip to the outbox without uploading it (to upload later
+    /// from Pending Uploads)
+    pub keep_in_outbox: bool,
+}
+
+impl ExportRequest {
+    /// The same export, to run again now and upload when it's done
+    pub fn rerun(&self) -> Self {
+        Self {
+            upload_at: None,
+            keep_in_outbox: false,
+            ..self.clone()
+        }
+    }
+}
+
+/// Where a queued job is
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Completed { results_url: Option<String> },
+    Failed { error: String },
+    Cancelled,
+}
+
+impl JobStatus {
+    fn is_finished(&self) -> bool {
+        !matches!(self, Self::Queued | Self::Running)
+    }
+}
+
+/// A job as listed by `list_export_queue`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueuedExport {
+    /// Also the export ID on this job's progress events
+    pub job_id: String,
+    pub label: Option<String>,
+    pub chat_count: usize,
+    pub queued_at: String,
+    pub upload_at: Option<DateTime<Utc>>,
+    #[serde(flatten)]
+    pub status: JobStatus,
+    #[serde(skip)]
+    request: ExportRequest,
+}
+
+/// What [`ExportQueue::cancel`] did
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CancelOutcome {
+    /// The job hadn't started and won't run
+    Dequeued,
+    /// The job is running; its export must be cancelled too
+    Running,
+}
+
+/// Jobs in the order they were queued, plus whether a worker is draining them
+#[derive(Debug, Default)]
+pub struct ExportQueue {
+    jobs: Vec<QueuedExport>,
+    worker_running: bool,
+}
+
+impl ExportQueue {
+    /// Add a job and return its ID
+    pub fn enqueue(&mut self, request: ExportRequest) -> String {
+        let job_id = uuid::Uuid::new_v4().to_string();
+        self.jobs.push(QueuedExport {
+            job_id: job_id.clone(),
+            label: request.label.clone(),
+            chat_count: request.chat_ids.len(),
+            queued_at: Utc::now().to_rfc3339(),
+            upload_at: request.upload_at,
+            status: JobStatus::Queued,
+            request,
+        });
+        job_id
+    }
+
+    pub fn jobs(&self) -> &[QueuedExport] {
+        &self.jobs
+    }
+
+    /// Whether the caller should start a worker; at most one runs at a time
+    pub fn claim_worker(&mut self) -> bool {
+        !std::mem::replace(&mut self.worker_running, true)
+    }
+
+    /// Mark the oldest queued job running and return it. With nothing left,
+    /// releases the worker claim in the same step so no job is stranded.
+    pub fn take_next(&mut self) -> Option<(String, ExportRequest)> {
+        let Some(job) = self
+            .jobs
+            .iter_mut()
+            .find(|job| job.status == JobStatus::Queued)
+        else {
+            self.worker_running = false;
+            return None;
+        };
+        job.status = JobStatus::Running;
+        Some((job.job_id.clone(), job.request.clone()))
+    }
+
+    /// Put a job that couldn't start back in the queue
+    pub fn requeue(&mut self, job_id: &str) {
+        self.set_status(job_id, JobStatus::Queued);
+    }
+
+    /// Queue a failed or cancelled job to run again; `false` if it isn't one
+    pub fn retry(&mut self, job_id: &str) -> bool {
+        let Some(job) = self.jobs.iter_mut().find(|job| job.job_id == job_id) else {
+            return false;
+        };
+        if !matches!(job.status, JobStatus::Failed { .. } | JobStatus::Cancelled) {
+            return false;
+        }
+        job.status = JobStatus::Queued;
+        true
+    }
+
+    /// Record how a running job ended
+    pub fn finish(&mut self, job_id: &str, status: JobStatus) {
+        self.set_status(job_id, status);
+    }
+
+    /// Cancel a job that hasn't finished; `None` if it has (or doesn't exist)
+    pub fn cancel(&mut self, job_id: &str) -> Option<CancelOutcome> {
+        let job = self.jobs.iter_mut().find(|job| job.job_id == job_id)?;
+        match job.status {
+            JobStatus::Queued => {
+                job.status = JobStatus::Cancelled;
+                Some(CancelOutcome::Dequeued)
+            }
+            JobStatus::Running => Some(CancelOutcome::Running),
+            _ => None,
+        }
+    }
+
+    /// Drop completed, failed and cancelled jobs from the list
+    pub fn clear_finished(&mut self) {
+        self.jobs.retain(|job| !job.status.is_finished());
+    }
+
+    fn set_status(&mut self, job_id: &str, status: JobStatus) {
+        if let Some(job) = self.jobs.iter_mut().find(|job| job.job_id == job_id) {
+            job.status = status;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(label: &str) -> ExportRequest {
+        ExportRequest {
+            chat_ids: vec![1, 2],
+            label: Some(label.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn jobs_run_in_order_and_cancelled_jobs_are_skipped() {
+        let mut queue = ExportQueue::default();
+        assert!(queue.claim_worker());
+        assert!(!queue.claim_worker());
+
+        let first = queue.enqueue(request("2022"));
+        let second = queue.enqueue(request("2023"));
+        let third = queue.enqueue(request("2024"));
+        assert_eq!(queue.cancel(&second), Some(CancelOutcome::Dequeued));
+
+        let (job_id, next) = queue.take_next().unwrap();
+        assert_eq!(job_id, first);
+        assert_eq!(next.label.as_deref(), Some("2022"));
+        assert_eq!(queue.cancel(&first), Some(CancelOutcome::Running));
+        queue.finish(&first, JobStatus::Cancelled);
+
+        assert_eq!(queue.take_next().unwrap().0, third);
+        queue.finish(
+            &third,
+            JobStatus::Completed {
+                results_url: Some("https://chattomap.com/r/1".to_string()),
+            },
+        );
+        assert_eq!(queue.cancel(&third), None);
+
+        assert!(queue.take_next().is_none());
+        assert!(queue.claim_worker());
+        queue.clear_finished();
+        assert!(queue.jobs().is_empty());
+    }
+
+    #[test]
+    fn only_failed_or_cancelled_jobs_can_be_retried() {
+        let mut queue = ExportQueue::default();
+        let job_id = queue.enqueue(request("2023"));
+        assert!(!queue.retry(&job_id));
+
+        queue.take_next();
+        queue.finish(
+            &job_id,
+            JobStatus::Failed {
+                error: "Upload failed".to_string(),
+            },
+        );
+        assert!(queue.retry(&job_id));
+        assert_eq!(queue.take_next().unwrap().0, job_id);
+        assert!(!queue.retry("missing"));
+    }
+
+    #[test]
+    fn listed_jobs_flatten_their_status() {
+        let mut queue = ExportQueue::default();
+        queue.enqueue(request("2023"));
+        let listed = serde_json::to_value(queue.jobs()).unwrap();
+        assert_eq!(listed[0]["status"], "queued");
+        assert_eq!(listed[0]["label"], "2023");
+        assert_eq!(listed[0]["chat_count"], 2);
+        assert!(listed[0].get("request").is_none());
+    }
+}
@@ -0,0 +1,339 @@
+/*!
+ * `README.txt` for export zips: a plain-language table of contents.
+ *
+ * Someone opening their archive years later, or sending it to support,
+ * shouldn't need tooling to know what's in it. The README lists each chat
+ * file with its message count and date range, and spells out the options
+ * the export was made with. It's generated from the same data as
+ * `manifest.json` and the chat files, so it never disagrees with them.
+ */
+
+use chrono::{DateTime, FixedOffset};
+use serde_json::Value;
+
+use super::{filenames::chat_filenames, parts, ExportFormat, ExportedChat};
+
+pub const README_FILENAME: &str = "README.txt";
+
+/// Render the README for an archive holding `manifest`, `chats` and
+/// `extra_files` (by name)
+pub fn render_readme(manifest: &Value, chats: &[ExportedChat], extra_files: &[&str]) -> String {
+    let mut out = String::from("ChatToMap export\n================\n\n");
+
+    let source = match manifest["source"].as_str() {
+        Some("imessage") => "iMessage",
+        Some("telegram") => "Telegram",
+        Some(other) => other,
+        None => "an unknown source",
+    };
+    let exported = manifest["export_date"]
+        .as_str()
+        .and_then(|date| DateTime::parse_from_rfc3339(date).ok())
+        .map(|date| date.format("%Y-%m-%d %H:%M UTC").to_string())
+        .unwrap_or_else(|| "on an unknown date".to_string());
+    out.push_str(&format!(
+        "Exported from {source} {exported} by ChatToMap Desktop {}.\n",
+        env!("CARGO_PKG_VERSION")
+    ));
+    let total_messages: usize = chats.iter().map(|chat| chat.messages.len()).sum();
+    out.push_str(&format!(
+        "{}, {}{}.\n",
+        plural(chats.len(), "chat"),
+        plural(total_messages, "message"),
+        date_range(chats.iter().flat_map(message_dates))
+            .map(|range| format!(", {range}"))
+            .unwrap_or_default()
+    ));
+
+    let options = options(manifest);
+    if !options.is_empty() {
+        out.push_str("\nOptions\n");
+        for option in options {
+            out.push_str(&format!("- {option}\n"));
+        }
+    }
+
+    out.push_str("\nChats\n");
+    let format = ExportFormat::of_manifest(manifest);
+    let max_messages = parts::max_messages_per_file(manifest);
+    for (chat, mut filename) in chats.iter().zip(chat_filenames(chats, format)) {
+        let part_names = parts::part_filenames(&filename, chat.messages.len(), max_messages);
+        if let (Some(first), Some(last)) = (part_names.first(), part_names.last()) {
+            filename = format!("{first} ... {last}");
+        }
+        let kind = match chat.meta.participant_count {
+            0 | 1 => chat.meta.service.clone(),
+            n => format!("{}, group of {}", chat.meta.service, n + 1),
+        };
+        let range = date_range(message_dates(chat))
+            .map(|range| format!(", {range}"))
+            .unwrap_or_default();
+        out.push_str(&format!(
+            "  {filename}  {} ({kind}) - {}{range}\n",
+            chat.meta.name,
+            plural(chat.messages.len(), "message")
+        ));
+    }
+
+    out.push_str("\nFiles\n");
+    out.push_str(
+        "  manifest.json  Machine-readable summary of this export, with a SHA-256 checksum of \
+         every other file\n",
+    );
+    out.push_str(match format {
+        ExportFormat::Json => {
+            "  <chat>.json    One per chat, as listed above: its name and service, then each \
+             message's time, sender and text\n"
+        }
+        ExportFormat::Ndjson => {
+            "  <chat>.ndjson  One per chat, as listed above: its name and service on the first \
+             line, then a line per message with its time, sender and text\n"
+        }
+        ExportFormat::Csv => {
+            "  <chat>.csv     One per chat, as listed above: a row per message with its time, \
+             sender and text (opens in a spreadsheet)\n"
+        }
+        ExportFormat::Txt => {
+            "  <chat>.txt     One per chat, as listed above: a line per message with its time, \
+             sender and text\n"
+        }
+    });
+    for name in extra_files {
+        let description = match *name {
+            "shared_links.json" => "Links shared in these chats (\"Shared with You\")",
+            _ => "Extra data from the export",
+        };
+        out.push_str(&format!("  {name}  {description}\n"));
+    }
+    out.push_str(&format!("  {README_FILENAME}  This file\n"));
+    out
+}
+
+/// The export options recorded in `manifest`, as sentences
+fn options(manifest: &Value) -> Vec<String> {
+    let mut options = Vec::new();
+    if let Some(max) = manifest["max_text_length"].as_u64() {
+        let cut = manifest["truncated_message_count"].as_u64().unwrap_or(0);
+        options.push(format!(
+            "Message text was cut to {max} characters ({} cut)",
+            plural(cut as usize, "message")
+        ));
+    }
+    if let Some(excluded) = manifest["excluded_chat_count"].as_u64() {
+        options.push(format!(
+            "{} skipped by exclusion rules",
+            plural(excluded as usize, "selected chat")
+        ));
+    }
+    if manifest["include_recently_deleted"].as_bool() == Some(true) {
+        let deleted = manifest["recently_deleted_count"].as_u64().unwrap_or(0);
+        options.push(format!(
+            "Recently deleted messages are included and marked \"deleted\" ({})",
+            plural(deleted as usize, "message")
+        ));
+    }
+    let orphans = &manifest["orphaned_messages"];
+    if orphans.is_object() {
+        options.push(format!(
+            "{} that had lost their chat were placed back in one; {} fit no chat and were \
+             left out",
+            plural(
+                orphans["recovered"].as_u64().unwrap_or(0) as usize,
+                "message"
+            ),
+            orphans["unassignable"].as_u64().unwrap_or(0)
+        ));
+    }
+    if let Some(max) = parts::max_messages_per_file(manifest) {
+        options.push(format!(
+            "Chats with more than {} are split into numbered parts",
+            plural(max, "message")
+        ));
+    }
+    if let Some(timezone) = manifest["timezone"].as_str() {
+        options.push(format!("Times are in {timezone}"));
+    }
+    if manifest["raw_text"] == true {
+        options.push("Message text is exactly as stored (attachment placeholders kept)".into());
+    }
+    if manifest["message_metadata"] == true {
+        options.push("Messages include their service, delivery and read times, and edits".into());
+    }
+    if let Some(links) = manifest["shared_link_count"].as_u64() {
+        options.push(format!(
+            "Shared links included ({} in shared_links.json)",
+            plural(links as usize, "link")
+        ));
+    }
+    if let Some(merged) = manifest["merged_from"].as_array() {
+        let duplicates = manifest["duplicate_message_count"].as_u64().unwrap_or(0);
+        options.push(format!(
+            "Merged from {}, keeping messages found in several once ({} left out)",
+            plural(merged.len(), "export"),
+            plural(duplicates as usize, "duplicate")
+        ));
+    }
+    if let Some(filtered) = manifest["filters"]["filtered_message_count"].as_u64() {
+        options.push(format!(
+            "Only some messages were exported, by sender or keyword ({} left out)",
+            plural(filtered as usize, "message")
+        ));
+    }
+    let redaction = &manifest["redaction"];
+    let count = |field: &str| redaction[field].as_u64().unwrap_or(0) as usize;
+    if redaction["contact_details_stripped"] == true {
+        options.push(format!(
+            "Phone numbers and email addresses were removed ({} replaced)",
+            count("contact_details_removed")
+        ));
+    }
+    if redaction["senders_pseudonymized"] == true {
+        options.push("Sender names were replaced with \"Person A\", \"Person B\", ...".into());
+    }
+    if count("dropped_sender_count") > 0 {
+        options.push(format!(
+            "Messages from {} were left out ({} removed)",
+            plural(count("dropped_sender_count"), "participant"),
+            plural(count("dropped_message_count"), "message")
+        ));
+    }
+    if let Some(metadata) = manifest["metadata"].as_object() {
+        for (key, value) in metadata {
+            options.push(format!("{key}: {}", value.as_str().unwrap_or_default()));
+        }
+    }
+    if let Some(warnings) = manifest["warning_count"].as_u64() {
+        options.push(format!(
+            "{} could only be partly read (listed in manifest.json)",
+            plural(warnings as usize, "message")
+        ));
+    }
+    options
+}
+
+fn message_dates(chat: &ExportedChat) -> impl Iterator<Item = DateTime<FixedOffset>> + '_ {
+    chat.messages
+        .iter()
+        .filter_map(|message| DateTime::parse_from_rfc3339(&message.timestamp).ok())
+}
+
+/// "2024-01-05 to 2024-03-02", or one date if they match
+fn date_range(dates: impl Iterator<Item = DateTime<FixedOffset>>) -> Option<String> {
+    let (first, last) = dates.fold(None, |range, date| match range {
+        None => Some((date, date)),
+        Some((first, last)) => Some((first.min(date), last.max(date))),
+    })?;
+    let day = |date: DateTime<FixedOffset>| date.format("%Y-%m-%d").to_string();
+    let (first, last) = (day(first), day(last));
+    Some(if first == last {
+        first
+    } else {
+        format!("{first} to {last}")
+    })
+}
+
+fn plural(count: usize, noun: &str) -> String {
+    if count == 1 {
+        format!("1 {noun}")
+    } else {
+        format!("{count} {noun}s")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::{ExportedChatMeta, ExportedMessage};
+
+    fn chat(name: &str, participants: usize, timestamps: &[&str]) -> ExportedChat {
+        ExportedChat {
+            meta: ExportedChatMeta {
+                name: name.to_string(),
+                identifier: name.to_string(),
+                guid: String::new(),
+                service: "iMessage".to_string(),
+                message_count: timestamps.len(),
+                participant_count: participants,
+                participants: Vec::new(),
+            },
+            messages: timestamps
+                .iter()
+                .map(|timestamp| ExportedMessage {
+                    guid: String::new(),
+                    timestamp: timestamp.to_string(),
+                    sender: "Alice".to_string(),
+                    is_from_me: false,
+                    text: "Hi".to_string(),
+                    truncated: false,
+                    deleted: false,
+                    metadata: None,
+                    attachments: Vec::new(),
+                    event: None,
+                    urls: Vec::new(),
+                    link_preview: None,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn readme_summarizes_chats_and_options() {
+        let chats = [
+            chat(
+                "Trip Planning",
+                3,
+                &["2024-03-02T09:00:00+00:00", "2024-01-05T18:30:00+00:00"],
+            ),
+            chat("Alice", 1, &["2024-02-01T12:00:00+00:00"]),
+        ];
+        let manifest = serde_json::json!({
+            "source": "imessage",
+            "export_date": "2024-05-01T14:03:00+00:00",
+            "max_text_length": 500,
+            "truncated_message_count": 1,
+            "excluded_chat_count": 2,
+            "include_recently_deleted": true,
+            "recently_deleted_count": 3,
+            "orphaned_messages": { "recovered": 2, "unassignable": 1 },
+            "shared_link_count": 4,
+            "message_metadata": true,
+            "timezone": "Pacific/Auckland",
+            "metadata": { "trip": "Italy 2024" },
+            "redaction": {
+                "contact_details_stripped": true,
+                "senders_pseudonymized": false,
+                "contact_details_removed": 3,
+                "dropped_sender_count": 1,
+                "dropped_message_count": 5,
+            },
+        });
+
+        let readme = render_readme(&manifest, &chats, &["shared_links.json"]);
+        for expected in [
+            "Exported from iMessage 2024-05-01 14:03 UTC",
+            "2 chats, 3 messages, 2024-01-05 to 2024-03-02.",
+            "- Message text was cut to 500 characters (1 message cut)",
+            "- 2 selected chats skipped by exclusion rules",
+            "- Recently deleted messages are included and marked \"deleted\" (3 messages)",
+            "- 2 messages that had lost their chat were placed back in one; 1 fit no chat and \
+             were left out",
+            "- Shared links included (4 links in shared_links.json)",
+            "- Times are in Pacific/Auckland",
+            "- Messages include their service, delivery and read times, and edits",
+            "- trip: Italy 2024",
+            "- Phone numbers and email addresses were removed (3 replaced)",
+            "- Messages from 1 participant were left out (5 messages removed)",
+            "trip-planning.Trip-Planning.json  Trip Planning (iMessage, group of 4) - \
+             2 messages, 2024-01-05 to 2024-03-02",
+            "alice.Alice.json  Alice (iMessage) - 1 message, 2024-02-01",
+            "shared_links.json  Links shared in these chats",
+        ] {
+            assert!(
+                readme.contains(expected),
+                "missing {expected:?} in:\n{readme}"
+            );
+        }
+        assert!(!readme.contains("partly read"));
+        assert!(!readme.contains("Person A"));
+    }
+}
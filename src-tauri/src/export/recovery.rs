@@ -0,0 +1,130 @@
+/*!
+ * Recovery of message rows the normal export path can't read.
+ *
+ * `imessage-database` reads `message.text` as UTF-8 and silently drops it
+ * when the bytes are invalid, and a row with an unexpected column type
+ * (e.g. a non-integer `date`) fails to load at all. Both turn up in old or
+ * partially-migrated databases. Rather than losing those messages — or
+ * aborting the export — we re-read the raw columns, decode text lossily
+ * (invalid sequences become U+FFFD) and record one warning per row.
+ *
+ * Warnings never contain message content, only row IDs and error kinds.
+ */
+
+use std::collections::{BTreeSet, HashSet};
+
+use imessage_database::{
+    error::message::MessageError, tables::messages::Message, util::streamtyped,
+};
+use rusqlite::{types::ValueRef, Connection, Row};
+
+/// A message row read directly from `message`, bypassing `Message::from_row`
+#[derive(Debug)]
+pub(super) struct RawMessageRow {
+    pub rowid: i32,
+    pub chat_id: i32,
+    pub guid: String,
+    pub date: i64,
+    pub is_from_me: bool,
+    pub handle_id: Option<i32>,
+    pub text: Option<String>,
+}
+
+/// Text for a streamed message: the decoded body, falling back to a lossy
+/// decode of the raw `text` column. Decode failures are pushed to `warnings`.
+pub(super) fn message_text(
+    db: &Connection,
+    message: &mut Message,
+    warnings: &mut Vec<String>,
+) -> Option<String> {
+    // Generate text content (deserializes protobuf/plist)
+    if let Err(e) = message.generate_text(db) {
+        if !matches!(e, MessageError::NoText) {
+            warnings.push(format!(
+                "Message {}: could not decode message body ({e})",
+                message.rowid
+            ));
+        }
+    }
+    if message.text.is_some() {
+        return message.text.clone();
+    }
+
+    let text = lossy_text(db, message.rowid)?;
+    warnings.push(format!(
+        "Message {}: text was not valid UTF-8 and was decoded lossily",
+        message.rowid
+    ));
+    Some(text)
+}
+
+/// Read `message.text` as bytes and decode it lossily
+fn lossy_text(db: &Connection, rowid: i32) -> Option<String> {
+    db.query_row(
+        "SELECT CAST(text AS BLOB) FROM message WHERE ROWID = ?1",
+        [rowid],
+        |row| row.get::<_, Option<Vec<u8>>>(0),
+    )
+    .ok()
+    .flatten()
+    .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+    .filter(|text| !text.is_empty())
+}
+
+/// Rows in `chat_ids` that the message stream did not yield (because they
+/// failed to load), read column-by-column with lenient typing
+pub(super) fn unread_rows(
+    db: &Connection,
+    chat_ids: &BTreeSet<i32>,
+    seen_rowids: &HashSet<i32>,
+) -> Result<Vec<RawMessageRow>, String> {
+    if chat_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let placeholders = vec!["?"; chat_ids.len()].join(", ");
+    let sql = format!(
+        "SELECT m.ROWID, cmj.chat_id, m.date, cmj.message_date, m.is_from_me, m.handle_id,
+                CAST(m.text AS BLOB), m.attributedBody, m.guid
+         FROM message m
+         JOIN chat_message_join cmj ON cmj.message_id = m.ROWID
+         WHERE cmj.chat_id IN ({placeholders})
+         ORDER BY m.ROWID"
+    );
+    let mut stmt = db
+        .prepare(&sql)
+        .map_err(|e| format!("Failed to query unreadable messages: {e}"))?;
+    let rows = stmt
+        .query_map(rusqlite::params_from_iter(chat_ids), raw_row)
+        .map_err(|e| format!("Failed to query unreadable messages: {e}"))?
+        .flatten()
+        .filter(|row| !seen_rowids.contains(&row.rowid))
+        .collect();
+    Ok(rows)
+}
+
+/// Map one lenient query row; only ROWID and chat_id are required
+pub(super) fn raw_row(row: &Row) -> rusqlite::Result<RawMessageRow> {
+    let integer = |idx: usize| row.get_ref(idx).ok().and_then(|v| v.as_i64().ok());
+    let bytes = |idx: usize| match row.get_ref(idx) {
+        Ok(ValueRef::Blob(b) | ValueRef::Text(b)) if !b.is_empty() => Some(b.to_vec()),
+        _ => None,
+    };
+
+    let text = bytes(6)
+        .map(|b| String::from_utf8_lossy(&b).into_owned())
+        .or_else(|| bytes(7).and_then(|body| streamtyped::parse(body).ok()));
+
+    Ok(RawMessageRow {
+        rowid: row.get(0)?,
+        chat_id: row.get(1)?,
+        guid: bytes(8)
+            .map(|b| String::from_utf8_lossy(&b).into_owned())
+            .unwrap_or_default(),
+        // Fall back to the join table's copy of the date
+        date: integer(2).or_else(|| integer(3)).unwrap_or(0),
+        is_from_me: integer(4).is_some_and(|v| v != 0),
+        handle_id: integer(5).and_then(|v| i32::try_from(v).ok()),
+        text,
+    })
+}
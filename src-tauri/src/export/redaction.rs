@@ -0,0 +1,429 @@
+/*!
+ * Redaction and anonymization of exported chats.
+ *
+ * Runs over the finished chat list before the archive is written:
+ *
+ * - Phone numbers and email addresses in message text (and in chat names,
+ *   identifiers and GUIDs) become `[phone]` / `[email]`
+ * - Sender and participant names become stable pseudonyms ("Person A",
+ *   "Person B", ...) that are the same across every chat in the export;
+ *   "Me" is kept
+ * - Messages from selected participants are dropped, along with their shared
+ *   links and the group events that name them; chats left empty are dropped
+ *   too
+ *
+ * What was done is summarized in `manifest.json` under `redaction`, as
+ * counts only, so the server knows the text was altered without learning
+ * who was removed. Names mentioned inside message text are not rewritten.
+ */
+
+use std::collections::HashMap;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use super::ExportedChat;
+use crate::shared_links::SharedLink;
+
+/// Sender name the device owner is exported under
+const OWNER_NAME: &str = "Me";
+
+/// Fewest digits in a run that is treated as a phone number
+const MIN_PHONE_DIGITS: usize = 7;
+
+/// How to redact an export; the default leaves it untouched
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "cli", derive(clap::Args))]
+#[serde(default)]
+pub struct RedactionConfig {
+    /// Replace phone numbers and email addresses in message text
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub strip_contact_details: bool,
+    /// Replace sender names with "Person A", "Person B", ...
+    #[cfg_attr(feature = "cli", arg(long = "pseudonymize"))]
+    pub pseudonymize_senders: bool,
+    /// Drop every message from these senders, as named in the export
+    /// (contact name, or phone/email without one; case-insensitive)
+    #[cfg_attr(feature = "cli", arg(long = "drop-sender"))]
+    pub drop_senders: Vec<String>,
+}
+
+impl RedactionConfig {
+    pub fn is_enabled(&self) -> bool {
+        self.strip_contact_details || self.pseudonymize_senders || !self.drop_senders.is_empty()
+    }
+}
+
+/// What redaction changed, written to the manifest as `redaction`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RedactionSummary {
+    pub contact_details_stripped: bool,
+    pub senders_pseudonymized: bool,
+    /// Phone numbers and email addresses replaced
+    pub contact_details_removed: usize,
+    /// Distinct senders given a pseudonym
+    pub pseudonym_count: usize,
+    /// Participants whose messages were dropped
+    pub dropped_sender_count: usize,
+    pub dropped_message_count: usize,
+    pub dropped_chat_count: usize,
+}
+
+/// Apply `config` to `chats` (and their shared links) in place. Returns
+/// `None` when the config asks for nothing.
+pub fn redact(
+    chats: &mut Vec<ExportedChat>,
+    links: Option<&mut Vec<SharedLink>>,
+    config: &RedactionConfig,
+) -> Option<RedactionSummary> {
+    if !config.is_enabled() {
+        return None;
+    }
+    let mut summary = RedactionSummary {
+        contact_details_stripped: config.strip_contact_details,
+        senders_pseudonymized: config.pseudonymize_senders,
+        dropped_sender_count: config.drop_senders.len(),
+        ..Default::default()
+    };
+
+    let dropped = |sender: &str| {
+        config
+            .drop_senders
+            .iter()
+            .any(|name| name.trim().eq_ignore_ascii_case(sender.trim()))
+    };
+    for chat in chats.iter_mut() {
+        let before = chat.messages.len();
+        chat.messages.retain(|message| {
+            let participant = message
+                .event
+                .as_ref()
+                .and_then(|e| e.participant.as_deref());
+            !dropped(&message.sender) && !participant.is_some_and(dropped)
+        });
+        summary.dropped_message_count += before - chat.messages.len();
+        chat.meta.message_count = chat.messages.len();
+        chat.meta.participants.retain(|participant| {
+            participant.is_me || !(dropped(&participant.name) || dropped(&participant.identifier))
+        });
+    }
+    let before = chats.len();
+    chats.retain(|chat| !chat.messages.is_empty());
+    summary.dropped_chat_count = before - chats.len();
+
+    let pseudonyms = if config.pseudonymize_senders {
+        Pseudonyms::assign(chats)
+    } else {
+        Pseudonyms::default()
+    };
+    summary.pseudonym_count = pseudonyms.by_name.len();
+
+    let mut details = ContactDetails::new();
+    for chat in chats.iter_mut() {
+        for message in &mut chat.messages {
+            if config.strip_contact_details {
+                message.text = details.strip(&message.text);
+            }
+            message.sender = pseudonyms.sender(&message.sender);
+            if let Some(event) = &mut message.event {
+                event.actor = pseudonyms.sender(&event.actor);
+                event.participant = event.participant.as_deref().map(|p| pseudonyms.sender(p));
+                if config.strip_contact_details {
+                    event.participant = event.participant.as_deref().map(|p| details.strip(p));
+                    event.name = event.name.as_deref().map(|n| details.strip(n));
+                }
+            }
+        }
+        if config.strip_contact_details || config.pseudonymize_senders {
+            // Names and identifiers of 1:1 chats are the other person's
+            chat.meta.name = details.strip(&pseudonyms.replace_in(&chat.meta.name));
+            chat.meta.identifier = details.strip(&chat.meta.identifier);
+            chat.meta.guid = details.strip(&chat.meta.guid);
+            for participant in chat.meta.participants.iter_mut().filter(|p| !p.is_me) {
+                participant.name = pseudonyms.sender(&participant.name);
+                participant.identifier = details.strip(&participant.identifier);
+            }
+        }
+    }
+    if let Some(links) = links {
+        links.retain(|link| !dropped(&link.sharer));
+        for link in links.iter_mut() {
+            link.sharer = pseudonyms.sender(&link.sharer);
+        }
+    }
+    summary.contact_details_removed = details.removed;
+    Some(summary)
+}
+
+/// Sender name → pseudonym, in order of first appearance
+#[derive(Default)]
+struct Pseudonyms {
+    by_name: HashMap<String, String>,
+}
+
+impl Pseudonyms {
+    /// Senders (and the people group events name) first, then participants
+    /// who never wrote anything
+    fn assign(chats: &[ExportedChat]) -> Self {
+        let senders = chats
+            .iter()
+            .flat_map(|chat| &chat.messages)
+            .flat_map(|message| {
+                let sender = Some(&message.sender).filter(|_| !message.is_from_me);
+                let participant = message.event.as_ref().and_then(|e| e.participant.as_ref());
+                sender.into_iter().chain(participant)
+            })
+            .filter(|name| name.as_str() != OWNER_NAME);
+        let silent = chats
+            .iter()
+            .flat_map(|chat| &chat.meta.participants)
+            .filter(|participant| !participant.is_me)
+            .map(|participant| &participant.name);
+        let mut by_name = HashMap::new();
+        for name in senders.chain(silent) {
+            if !by_name.contains_key(name) {
+                let pseudonym = format!("Person {}", letters(by_name.len()));
+                by_name.insert(name.clone(), pseudonym);
+            }
+        }
+        Self { by_name }
+    }
+
+    fn sender(&self, name: &str) -> String {
+        self.by_name
+            .get(name)
+            .cloned()
+            .unwrap_or_else(|| name.to_string())
+    }
+
+    /// Replace every known sender name inside `text` (e.g. a group named
+    /// "Alice, Bob & Carol"), longest names first so "Ann Lee" wins over "Ann"
+    fn replace_in(&self, text: &str) -> String {
+        if let Some(pseudonym) = self.by_name.get(text) {
+            return pseudonym.clone();
+        }
+        let mut names: Vec<(&String, &String)> = self
+            .by_name
+            .iter()
+            .filter(|(name, _)| name.as_str() != OWNER_NAME)
+            .collect();
+        names.sort_by_key(|(name, _)| std::cmp::Reverse(name.len()));
+        names
+            .into_iter()
+            .fold(text.to_string(), |text, (name, pseudonym)| {
+                text.replace(name.as_str(), pseudonym)
+            })
+    }
+}
+
+/// 0 → "A", 25 → "Z", 26 → "AA", ...
+fn letters(mut index: usize) -> String {
+    let mut out = Vec::new();
+    loop {
+        out.push(b'A' + (index % 26) as u8);
+        if index < 26 {
+            break;
+        }
+        index = index / 26 - 1;
+    }
+    out.reverse();
+    String::from_utf8(out).unwrap()
+}
+
+/// Finds phone numbers and email addresses, counting what it replaces
+struct ContactDetails {
+    email: Regex,
+    phone: Regex,
+    date: Regex,
+    removed: usize,
+}
+
+impl ContactDetails {
+    fn new() -> Self {
+        Self {
+            email: Regex::new(r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}").unwrap(),
+            // Loose on purpose; candidates are then checked for enough digits
+            phone: Regex::new(r"\+?\(?\d[\d\s().-]{5,}\d").unwrap(),
+            date: Regex::new(r"^\d{4}[-./]\d{1,2}[-./]\d{1,2}$").unwrap(),
+            removed: 0,
+        }
+    }
+
+    fn strip(&mut self, text: &str) -> String {
+        let mut removed = 0;
+        let text = self.email.replace_all(text, |_: &regex::Captures| {
+            removed += 1;
+            "[email]".to_string()
+        });
+        let text = self
+            .phone
+            .replace_all(&text, |captures: &regex::Captures| {
+                let candidate = &captures[0];
+                let digits = candidate.chars().filter(char::is_ascii_digit).count();
+                if digits < MIN_PHONE_DIGITS || self.date.is_match(candidate.trim()) {
+                    return candidate.to_string();
+                }
+                removed += 1;
+                "[phone]".to_string()
+            })
+            .into_owned();
+        self.removed += removed;
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::{ExportedChatMeta, ExportedMessage, ExportedParticipant};
+
+    fn chat(name: &str, messages: &[(&str, &str)]) -> ExportedChat {
+        ExportedChat {
+            meta: ExportedChatMeta {
+                name: name.to_string(),
+                identifier: "+15551234567".to_string(),
+                guid: "iMessage;-;+15551234567".to_string(),
+                service: "iMessage".to_string(),
+                message_count: messages.len(),
+                participant_count: 2,
+                participants: vec![participant("Me", ""), participant(name, "+15551234567")],
+            },
+            messages: messages
+                .iter()
+                .map(|(sender, text)| ExportedMessage {
+                    guid: String::new(),
+                    timestamp: "2024-01-05T18:30:00+00:00".to_string(),
+                    sender: sender.to_string(),
+                    is_from_me: *sender == OWNER_NAME,
+                    text: text.to_string(),
+                    truncated: false,
+                    deleted: false,
+                    metadata: None,
+                    attachments: Vec::new(),
+                    event: None,
+                    urls: Vec::new(),
+                    link_preview: None,
+                })
+                .collect(),
+        }
+    }
+
+    fn participant(name: &str, identifier: &str) -> ExportedParticipant {
+        ExportedParticipant {
+            name: name.to_string(),
+            identifier: identifier.to_string(),
+            is_me: name == OWNER_NAME,
+        }
+    }
+
+    fn link(sharer: &str) -> SharedLink {
+        SharedLink {
+            url: "https://example.com".to_string(),
+            sharer: sharer.to_string(),
+            is_from_me: false,
+            timestamp: "2024-01-05T18:30:00+00:00".to_string(),
+            chat_id: 1,
+            message_guid: "guid".to_string(),
+        }
+    }
+
+    #[test]
+    fn default_config_changes_nothing() {
+        let mut chats = vec![chat("Alice", &[("Alice", "Call +1 555 123 4567")])];
+        assert_eq!(redact(&mut chats, None, &RedactionConfig::default()), None);
+        assert_eq!(chats[0].messages[0].text, "Call +1 555 123 4567");
+    }
+
+    #[test]
+    fn strips_contact_details_but_not_dates_or_short_numbers() {
+        let mut details = ContactDetails::new();
+        let text = details.strip(
+            "Mail ann.lee+trip@example.co.uk or call (555) 123-4567 / +64 21 555 0199. \
+             Flight NZ123 on 2024-03-02, gate 12, 45 minutes",
+        );
+        assert_eq!(
+            text,
+            "Mail [email] or call [phone] / [phone]. \
+             Flight NZ123 on 2024-03-02, gate 12, 45 minutes"
+        );
+        assert_eq!(details.removed, 3);
+    }
+
+    #[test]
+    fn pseudonymizes_senders_and_drops_participants() {
+        let mut chats = vec![
+            chat(
+                "Alice, Bob & Carol",
+                &[
+                    ("Alice", "Booked the hotel"),
+                    ("Carol", "Text me on 021 555 0199"),
+                    ("Me", "Great"),
+                    ("Bob", "Flights are on Tuesday"),
+                ],
+            ),
+            chat("Carol", &[("carol", "Only Carol here")]),
+            chat("Bob", &[("Bob", "See you there"), ("Me", "Bye")]),
+        ];
+        // Dan is in the group but never wrote anything
+        chats[0].meta.participants = vec![
+            participant("Me", ""),
+            participant("Alice", "+15551234567"),
+            participant("Bob", "bob@example.com"),
+            participant("Carol", "carol@example.com"),
+            participant("Dan", "dan@example.com"),
+        ];
+        let mut links = vec![link("Carol"), link("Bob")];
+        let config = RedactionConfig {
+            strip_contact_details: true,
+            pseudonymize_senders: true,
+            drop_senders: vec!["CAROL ".to_string()],
+        };
+
+        let summary = redact(&mut chats, Some(&mut links), &config).unwrap();
+        assert_eq!(
+            summary,
+            RedactionSummary {
+                contact_details_stripped: true,
+                senders_pseudonymized: true,
+                contact_details_removed: 8,
+                pseudonym_count: 3,
+                dropped_sender_count: 1,
+                dropped_message_count: 2,
+                dropped_chat_count: 1,
+            }
+        );
+
+        assert_eq!(chats.len(), 2);
+        let senders: Vec<&str> = chats[0]
+            .messages
+            .iter()
+            .map(|m| m.sender.as_str())
+            .collect();
+        assert_eq!(senders, ["Person A", "Me", "Person B"]);
+        assert_eq!(chats[0].meta.name, "Person A, Person B & Carol");
+        assert_eq!(chats[0].meta.message_count, 3);
+        assert_eq!(chats[0].meta.identifier, "[phone]");
+        assert_eq!(chats[0].meta.guid, "iMessage;-;[phone]");
+        let participants: Vec<(&str, &str)> = chats[0]
+            .meta
+            .participants
+            .iter()
+            .map(|p| (p.name.as_str(), p.identifier.as_str()))
+            .collect();
+        assert_eq!(
+            participants,
+            [
+                ("Me", ""),
+                ("Person A", "[phone]"),
+                ("Person B", "[email]"),
+                ("Person C", "[email]")
+            ]
+        );
+        assert_eq!(chats[1].meta.name, "Person B");
+        assert_eq!(chats[1].messages[0].sender, "Person B");
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].sharer, "Person B");
+        assert_eq!(letters(25), "Z");
+        assert_eq!(letters(26), "AA");
+    }
+}
@@ -0,0 +1,67 @@
+/*!
+ * Deferred uploads: export now, upload at a chosen time (say 2 AM, on a
+ * metered or congested connection).
+ *
+ * A queued export with `upload_at` set builds its zip straight away and
+ * then holds it until that time. The wait is a series of short sleeps
+ * checked against the wall clock, so time the Mac spends asleep counts
+ * towards it; a monotonic timer would upload hours late after a night of
+ * sleep.
+ */
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+
+use crate::heartbeat::{StagePolicy, DEFAULT_STALL_THRESHOLD};
+
+/// Longest single sleep while waiting for `upload_at`
+pub const CLOCK_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How long to sleep before checking the clock again, or `None` once
+/// `upload_at` has arrived
+pub fn next_sleep(now: DateTime<Utc>, upload_at: DateTime<Utc>) -> Option<Duration> {
+    let remaining = (upload_at - now).to_std().ok()?;
+    if remaining.is_zero() {
+        return None;
+    }
+    Some(remaining.min(CLOCK_CHECK_INTERVAL))
+}
+
+/// Stall policy for the wait: [`wait_until`] beats once per clock check
+pub fn waiting_policy() -> StagePolicy {
+    StagePolicy {
+        threshold: DEFAULT_STALL_THRESHOLD + CLOCK_CHECK_INTERVAL,
+        retryable: false,
+    }
+}
+
+/// Sleep until `upload_at`, calling `on_check` each time the clock is
+/// checked (e.g. to keep a stall monitor quiet). Returns at once if the
+/// time has passed.
+pub async fn wait_until(upload_at: DateTime<Utc>, on_check: impl Fn()) {
+    while let Some(sleep) = next_sleep(Utc::now(), upload_at) {
+        on_check();
+        tokio::time::sleep(sleep).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sleeps_in_short_steps_until_the_upload_time() {
+        let now = Utc::now();
+        assert_eq!(
+            next_sleep(now, now + chrono::Duration::hours(3)),
+            Some(CLOCK_CHECK_INTERVAL)
+        );
+        assert_eq!(
+            next_sleep(now, now + chrono::Duration::seconds(5)),
+            Some(Duration::from_secs(5))
+        );
+        assert_eq!(next_sleep(now, now), None);
+        assert_eq!(next_sleep(now, now - chrono::Duration::minutes(1)), None);
+    }
+}
@@ -0,0 +1,110 @@
+/*!
+ * Sender name resolution shared by export, preview and search.
+ */
+
+use std::collections::{BTreeSet, HashMap};
+
+use imessage_database::tables::{chat::Chat, handle::Handle, table::Cacheable};
+use rusqlite::Connection;
+
+use super::ExportedParticipant;
+use crate::{
+    accounts::OwnerAccounts,
+    contacts::{ContactsIndex, Name},
+};
+
+/// Handle and contact lookups needed to name message senders
+pub(crate) struct SenderNames {
+    /// Handle ROWID → raw identifier (phone/email)
+    pub handles: HashMap<i32, String>,
+    /// Handle ROWID → deduplicated person ID
+    pub deduped_handles: HashMap<i32, i32>,
+    /// Deduplicated person ID → contact name
+    pub participants_map: HashMap<i32, Name>,
+    /// The owner's handles on their other accounts, which count as "Me"
+    pub owner: OwnerAccounts,
+}
+
+impl SenderNames {
+    /// Cache handles and resolve them against the contacts index
+    pub fn load(db: &Connection) -> Result<Self, String> {
+        // Build contacts index for name resolution
+        let contacts_index = ContactsIndex::build(None).unwrap_or_default();
+
+        // Cache handles for participant name lookup
+        let handles = Handle::cache(db).map_err(|e| format!("Failed to load handles: {e}"))?;
+        let deduped_handles = crate::identities::dedupe_handles(&handles);
+        let mut participants_map =
+            contacts_index.build_participants_map(&handles, &deduped_handles);
+        crate::business_names::fill_names(&mut participants_map, &crate::business_names::load(db));
+        let owner = OwnerAccounts::load(db, &handles);
+
+        Ok(Self {
+            handles,
+            deduped_handles,
+            participants_map,
+            owner,
+        })
+    }
+
+    /// Whether a message was sent by the owner, on any of their accounts
+    pub fn is_from_me(&self, is_from_me: bool, handle_id: Option<i32>) -> bool {
+        is_from_me || handle_id.is_some_and(|id| self.owner.is_owner(id))
+    }
+
+    /// Display name of `chat` (its own name, or its participants' names),
+    /// given its participant handle IDs
+    pub fn chat_name(&self, chat: &Chat, participants: Option<&BTreeSet<i32>>) -> String {
+        crate::chat_names::resolve_chat_display_name(
+            chat,
+            participants,
+            &self.participants_map,
+            &self.deduped_handles,
+        )
+    }
+
+    /// Get sender name for a message
+    pub fn name(&self, is_from_me: bool, handle_id: Option<i32>) -> String {
+        if self.is_from_me(is_from_me, handle_id) {
+            return "Me".to_string();
+        }
+
+        if let Some(handle_id) = handle_id {
+            // Look up deduped ID first
+            if let Some(&deduped_id) = self.deduped_handles.get(&handle_id) {
+                if let Some(name) = self.participants_map.get(&deduped_id) {
+                    let display = name.get_display_name();
+                    if !display.is_empty() {
+                        return display.to_string();
+                    }
+                }
+            }
+
+            // Fall back to raw handle ID (phone/email)
+            if let Some(handle_id_str) = self.handles.get(&handle_id) {
+                return handle_id_str.clone();
+            }
+        }
+
+        "Unknown".to_string()
+    }
+
+    /// The owner followed by a chat's other participants (handle ROWIDs,
+    /// with the owner's own handles already removed)
+    pub fn participants(&self, handle_ids: Option<&BTreeSet<i32>>) -> Vec<ExportedParticipant> {
+        let me = ExportedParticipant {
+            name: "Me".to_string(),
+            identifier: String::new(),
+            is_me: true,
+        };
+        let others = handle_ids
+            .into_iter()
+            .flatten()
+            .map(|&id| ExportedParticipant {
+                name: self.name(false, Some(id)),
+                identifier: self.handles.get(&id).cloned().unwrap_or_default(),
+                is_me: false,
+            });
+        std::iter::once(me).chain(others).collect()
+    }
+}
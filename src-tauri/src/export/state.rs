@@ -0,0 +1,393 @@
+/*!
+ * Export state machine and concurrent-export guard.
+ *
+ * Only one export may run at a time: a second `export_and_upload` would
+ * interleave its progress events with the first. [`ExportManager::start`]
+ * moves Idle → Exporting or fails with [`ExportError::Busy`]; the returned
+ * [`ExportRun`] carries the export's ID (attached to its progress events and
+ * result), advances through Uploading (after WaitingToUpload, for a
+ * scheduled upload) and Processing, can be cancelled by ID until
+ * processing starts, and puts the manager back to Idle when dropped,
+ * however the export ends. [`ExportManager::subscribe`] follows these
+ * changes (for the tray icon).
+ */
+
+use std::{
+    fmt,
+    future::Future,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::{watch, Notify};
+
+use super::disk_space::InsufficientDiskSpace;
+use crate::upload::{RateLimit, UploadError, UploadTimeout};
+
+/// Where the export pipeline is
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportState {
+    #[default]
+    Idle,
+    Exporting,
+    /// Export done; holding the zip until its scheduled upload time
+    WaitingToUpload,
+    Uploading,
+    Processing,
+}
+
+impl fmt::Display for ExportState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Idle => "idle",
+            Self::Exporting => "exporting",
+            Self::WaitingToUpload => "waiting to upload",
+            Self::Uploading => "uploading",
+            Self::Processing => "processing",
+        })
+    }
+}
+
+/// Snapshot of the pipeline for `get_export_state`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExportStatus {
+    pub state: ExportState,
+    /// ID of the running export, if any
+    pub export_id: Option<String>,
+}
+
+/// Error from an export command, tagged so the frontend can tell a rejected
+/// start or a cancellation from a failed export
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ExportError {
+    /// Another export is already running
+    Busy {
+        state: ExportState,
+        export_id: String,
+    },
+    /// The export was cancelled by the user
+    Cancelled { export_id: String },
+    /// Cancellation was requested for an export that isn't running (or has
+    /// reached the server and can no longer be stopped)
+    NotCancellable { export_id: String },
+    /// The temp volume doesn't have room for the export (checked before
+    /// each step that writes to it)
+    InsufficientDiskSpace {
+        required_bytes: u64,
+        available_bytes: u64,
+    },
+    /// An upload request, or the whole upload, ran out of time (see
+    /// `UploadTimeouts`)
+    Timeout { step: String, seconds: u64 },
+    /// The server kept refusing an upload request for now, and said to
+    /// come back in `retry_after_secs` (see `RateLimit`)
+    RateLimited { step: String, retry_after_secs: u64 },
+    /// The server won't process the export, for the reason in `message`
+    /// (checked before uploading, see `validate_export`)
+    Rejected { code: String, message: String },
+    /// The export itself failed
+    Failed { message: String },
+}
+
+impl fmt::Display for ExportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Busy { state, .. } => write!(f, "An export is already in progress ({state})"),
+            Self::Cancelled { .. } => f.write_str("Export cancelled"),
+            Self::NotCancellable { export_id } => {
+                write!(f, "Export {export_id} can no longer be cancelled")
+            }
+            Self::InsufficientDiskSpace {
+                required_bytes,
+                available_bytes,
+            } => InsufficientDiskSpace {
+                required_bytes: *required_bytes,
+                available_bytes: *available_bytes,
+            }
+            .fmt(f),
+            Self::Timeout { step, seconds } => UploadError::Timeout(UploadTimeout {
+                step: step.clone(),
+                limit: Duration::from_secs(*seconds),
+            })
+            .fmt(f),
+            Self::RateLimited {
+                step,
+                retry_after_secs,
+            } => UploadError::RateLimited(RateLimit {
+                step: step.clone(),
+                retry_after: Duration::from_secs(*retry_after_secs),
+            })
+            .fmt(f),
+            Self::Rejected { message, .. } | Self::Failed { message } => f.write_str(message),
+        }
+    }
+}
+
+impl From<InsufficientDiskSpace> for ExportError {
+    fn from(error: InsufficientDiskSpace) -> Self {
+        Self::InsufficientDiskSpace {
+            required_bytes: error.required_bytes,
+            available_bytes: error.available_bytes,
+        }
+    }
+}
+
+impl From<UploadError> for ExportError {
+    fn from(error: UploadError) -> Self {
+        match error {
+            UploadError::Timeout(timeout) => Self::Timeout {
+                step: timeout.step,
+                seconds: timeout.limit.as_secs(),
+            },
+            UploadError::RateLimited(limit) => Self::RateLimited {
+                step: limit.step,
+                retry_after_secs: limit.retry_after.as_secs(),
+            },
+            UploadError::Rejected(rejection) => Self::Rejected {
+                code: rejection.code,
+                message: rejection.message,
+            },
+            UploadError::Failed(message) => Self::Failed { message },
+        }
+    }
+}
+
+impl From<String> for ExportError {
+    fn from(message: String) -> Self {
+        Self::Failed { message }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Current {
+    state: ExportState,
+    export_id: Option<String>,
+    cancel: Option<Arc<Notify>>,
+}
+
+impl Current {
+    fn status(&self) -> ExportStatus {
+        ExportStatus {
+            state: self.state,
+            export_id: self.export_id.clone(),
+        }
+    }
+}
+
+/// The pipeline, shared by the manager and its run
+#[derive(Debug)]
+struct Shared {
+    current: Mutex<Current>,
+    changes: watch::Sender<ExportStatus>,
+}
+
+impl Shared {
+    /// Change the pipeline, telling subscribers about its new status
+    fn update<T>(&self, change: impl FnOnce(&mut Current) -> T) -> T {
+        let mut current = self.current.lock().unwrap();
+        let result = change(&mut current);
+        self.changes.send_if_modified(|status| {
+            let changed = *status != current.status();
+            *status = current.status();
+            changed
+        });
+        result
+    }
+}
+
+/// Tracks the single running export; lives in app state
+#[derive(Debug)]
+pub struct ExportManager {
+    shared: Arc<Shared>,
+}
+
+impl Default for ExportManager {
+    fn default() -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                current: Mutex::default(),
+                changes: watch::channel(ExportStatus::default()).0,
+            }),
+        }
+    }
+}
+
+impl ExportManager {
+    pub fn status(&self) -> ExportStatus {
+        self.shared.current.lock().unwrap().status()
+    }
+
+    /// Follow the pipeline's status as exports start, advance and end
+    pub fn subscribe(&self) -> watch::Receiver<ExportStatus> {
+        self.shared.changes.subscribe()
+    }
+
+    /// Claim the pipeline for a new export with a fresh ID, unless one is
+    /// already running
+    pub fn start(&self) -> Result<ExportRun, ExportError> {
+        self.start_with_id(uuid::Uuid::new_v4().to_string())
+    }
+
+    /// As [`Self::start`], with the ID chosen by the caller (a queued job's)
+    pub fn start_with_id(&self, export_id: String) -> Result<ExportRun, ExportError> {
+        let cancel = self.shared.update(|current| {
+            if let Some(export_id) = &current.export_id {
+                return Err(ExportError::Busy {
+                    state: current.state,
+                    export_id: export_id.clone(),
+                });
+            }
+            let cancel = Arc::new(Notify::new());
+            *current = Current {
+                state: ExportState::Exporting,
+                export_id: Some(export_id.clone()),
+                cancel: Some(Arc::clone(&cancel)),
+            };
+            Ok(cancel)
+        })?;
+        Ok(ExportRun {
+            export_id,
+            shared: Arc::clone(&self.shared),
+            cancel,
+        })
+    }
+
+    /// Cancel the export with this ID. Only the running export can be
+    /// cancelled, and only before processing starts on the server.
+    pub fn cancel(&self, export_id: &str) -> Result<(), ExportError> {
+        let current = self.shared.current.lock().unwrap();
+        match &current.cancel {
+            Some(cancel)
+                if current.export_id.as_deref() == Some(export_id)
+                    && current.state != ExportState::Processing =>
+            {
+                cancel.notify_one();
+                Ok(())
+            }
+            _ => Err(ExportError::NotCancellable {
+                export_id: export_id.to_string(),
+            }),
+        }
+    }
+}
+
+/// The running export; returns the manager to Idle when dropped
+#[derive(Debug)]
+pub struct ExportRun {
+    export_id: String,
+    shared: Arc<Shared>,
+    cancel: Arc<Notify>,
+}
+
+impl ExportRun {
+    pub fn id(&self) -> &str {
+        &self.export_id
+    }
+
+    pub fn advance(&self, state: ExportState) {
+        self.shared.update(|current| current.state = state);
+    }
+
+    /// Run `stage` unless this export is cancelled first; a cancelled stage
+    /// is dropped where it stands
+    pub async fn until_cancelled<T, E: Into<ExportError>>(
+        &self,
+        stage: impl Future<Output = Result<T, E>>,
+    ) -> Result<T, ExportError> {
+        tokio::select! {
+            result = stage => result.map_err(Into::into),
+            () = self.cancel.notified() => {
+                tracing::info!("[export] Cancelled export {}", self.export_id);
+                Err(ExportError::Cancelled {
+                    export_id: self.export_id.clone(),
+                })
+            }
+        }
+    }
+}
+
+impl Drop for ExportRun {
+    fn drop(&mut self) {
+        self.shared.update(|current| *current = Current::default());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn second_start_is_rejected_until_the_first_run_ends() {
+        let manager = ExportManager::default();
+        let run = manager.start().unwrap();
+        run.advance(ExportState::Uploading);
+
+        let busy = manager.start().unwrap_err();
+        assert_eq!(
+            busy,
+            ExportError::Busy {
+                state: ExportState::Uploading,
+                export_id: run.id().to_string(),
+            }
+        );
+        assert_eq!(
+            serde_json::to_value(&busy).unwrap()["kind"],
+            serde_json::json!("busy")
+        );
+
+        drop(run);
+        assert_eq!(manager.status().state, ExportState::Idle);
+        assert_eq!(manager.status().export_id, None);
+        assert!(manager.start().is_ok());
+    }
+
+    #[tokio::test]
+    async fn cancellation_is_scoped_to_the_export_id() {
+        let manager = ExportManager::default();
+        let run = manager.start().unwrap();
+
+        assert!(manager.cancel("some-other-export").is_err());
+        manager.cancel(run.id()).unwrap();
+        let result = run
+            .until_cancelled(std::future::pending::<Result<(), String>>())
+            .await;
+        assert_eq!(
+            result,
+            Err(ExportError::Cancelled {
+                export_id: run.id().to_string()
+            })
+        );
+
+        run.advance(ExportState::Processing);
+        assert!(manager.cancel(run.id()).is_err());
+    }
+
+    #[test]
+    fn subscribers_see_each_status_change() {
+        let manager = ExportManager::default();
+        let mut changes = manager.subscribe();
+        assert_eq!(changes.borrow_and_update().state, ExportState::Idle);
+
+        let run = manager.start().unwrap();
+        assert!(changes.has_changed().unwrap());
+        assert_eq!(
+            *changes.borrow_and_update(),
+            ExportStatus {
+                state: ExportState::Exporting,
+                export_id: Some(run.id().to_string()),
+            }
+        );
+
+        // A rejected start changes nothing
+        assert!(manager.start().is_err());
+        assert!(!changes.has_changed().unwrap());
+
+        run.advance(ExportState::Uploading);
+        assert_eq!(changes.borrow_and_update().state, ExportState::Uploading);
+        drop(run);
+        assert_eq!(*changes.borrow_and_update(), ExportStatus::default());
+    }
+}
@@ -0,0 +1,445 @@
+/*!
+ * Streaming the selected chats' messages, in parallel.
+ *
+ * Decoding message bodies (typedstream) dominates export time, and one
+ * SQLite connection can't be shared between threads. The selected chats
+ * are split into one batch per worker, balanced by message count, and each
+ * worker streams its batch over its own read-only connection. Chats merged
+ * into one export (`ExportOptions::chat_groups`) stay in the same batch, so
+ * their messages come out of a single date-ordered query. Workers share a
+ * running message count for progress and their results are merged at the
+ * end. A batch whose query is cut short by a lock is read again (see
+ * `crate::db_busy`), skipping the messages it already has.
+ */
+
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    num::NonZeroUsize,
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+};
+
+use imessage_database::{
+    error::table::TableError,
+    tables::{messages::Message, table::Table},
+    util::query_context::QueryContext,
+};
+use rusqlite::Connection;
+
+use super::{
+    cleaning, content::MessageContent, format_timestamp_in, links, recovery::RawMessageRow,
+    truncation, ExportOptions, ExportedMessage, LinkPreview, MessageMetadata, SenderNames,
+    TimestampUnit,
+};
+use crate::{
+    db_busy::{retry_on_busy, BusyError},
+    db_snapshot::ChatDb,
+};
+
+/// How often (in messages) workers report progress
+const PROGRESS_INTERVAL: usize = 100;
+
+/// What every worker needs to turn a row into an `ExportedMessage`
+pub(super) struct StreamContext<'a> {
+    pub options: &'a ExportOptions,
+    pub senders: &'a SenderNames,
+    /// Chat ID → ID of the merged group it is exported under
+    pub canonical_ids: &'a HashMap<i32, i32>,
+    pub unit: TimestampUnit,
+    /// `ExportOptions::since` as an iMessage timestamp
+    pub since: Option<i64>,
+}
+
+impl StreamContext<'_> {
+    /// The chat ID a chat's messages are exported under
+    pub fn export_id(&self, chat_id: i32) -> i32 {
+        self.canonical_ids.get(&chat_id).copied().unwrap_or(chat_id)
+    }
+
+    /// Whether a message sent at `date` is older than `ExportOptions::since`
+    fn too_old(&self, date: i64) -> bool {
+        self.since.is_some_and(|since| date < since)
+    }
+
+    /// An exported message with its text truncated and links listed
+    pub fn to_exported(
+        &self,
+        guid: String,
+        date: i64,
+        is_from_me: bool,
+        handle_id: Option<i32>,
+        text: String,
+        link_preview: Option<LinkPreview>,
+    ) -> ExportedMessage {
+        // Links come from the full text, before any truncation
+        let urls = links::message_urls(Some(&text), link_preview.as_ref());
+        let (text, truncated) = truncation::truncate_text(text, self.options.max_text_length);
+        ExportedMessage {
+            guid,
+            timestamp: format_timestamp_in(date, self.unit, self.options.timezone),
+            sender: self.senders.name(is_from_me, handle_id),
+            is_from_me: self.senders.is_from_me(is_from_me, handle_id),
+            text,
+            truncated,
+            deleted: false,
+            metadata: None,
+            attachments: Vec::new(),
+            event: None,
+            urls,
+            link_preview,
+        }
+    }
+}
+
+/// Messages streamed from the selected chats, by export chat ID
+#[derive(Debug, Default)]
+pub(super) struct Streamed {
+    pub messages_by_chat: HashMap<i32, Vec<ExportedMessage>>,
+    /// Rows read, for finding the ones that failed to load
+    pub seen_rowids: HashSet<i32>,
+    /// Messages read, exported or not
+    pub processed: usize,
+    /// Messages left out by `ExportOptions::filter`
+    pub filtered_out: usize,
+    /// Rows that failed to load
+    pub unreadable_rows: usize,
+    pub warnings: Vec<String>,
+}
+
+impl Streamed {
+    fn merge(&mut self, other: Streamed) {
+        // Batches never share an export chat, so no message list is split
+        self.messages_by_chat.extend(other.messages_by_chat);
+        self.seen_rowids.extend(other.seen_rowids);
+        self.processed += other.processed;
+        self.filtered_out += other.filtered_out;
+        self.unreadable_rows += other.unreadable_rows;
+        self.warnings.extend(other.warnings);
+    }
+
+    /// Add one message from a selected chat
+    fn add(&mut self, db: &Connection, mut message: Message, chat_id: i32, ctx: &StreamContext) {
+        self.seen_rowids.insert(message.rowid);
+        if ctx.too_old(message.date) {
+            return;
+        }
+        self.processed += 1;
+        let content = MessageContent::read(
+            db,
+            &mut message,
+            ctx.options,
+            ctx.senders,
+            &mut self.warnings,
+        );
+
+        // Skip messages with no text, attachments, event or link, counting
+        // those the filter leaves out
+        if content.is_empty() {
+            return;
+        }
+        let sender = ctx.senders.name(message.is_from_me, message.handle_id);
+        let text = content.text.unwrap_or_default();
+        if !ctx.options.filter.matches(&sender, &text) {
+            self.filtered_out += 1;
+            return;
+        }
+        let metadata = ctx
+            .options
+            .include_metadata
+            .then(|| MessageMetadata::from_message(&message, ctx.unit, ctx.options.timezone));
+        let exported = ExportedMessage {
+            metadata,
+            deleted: message.is_deleted(),
+            attachments: content.attachments,
+            event: content.event,
+            ..ctx.to_exported(
+                message.guid,
+                message.date,
+                message.is_from_me,
+                message.handle_id,
+                text,
+                content.link_preview,
+            )
+        };
+        self.messages_by_chat
+            .entry(ctx.export_id(chat_id))
+            .or_default()
+            .push(exported);
+    }
+
+    /// Add a row read from its raw columns (see `super::recovery`),
+    /// returning the export chat ID it went to, if it was kept
+    pub fn add_raw(&mut self, row: RawMessageRow, ctx: &StreamContext) -> Option<i32> {
+        if ctx.too_old(row.date) {
+            return None;
+        }
+        self.processed += 1;
+        let mut text = row.text;
+        if !ctx.options.raw_text {
+            text = text.map(|text| cleaning::clean_text(&text, &[]));
+        }
+        let text = text.filter(|t| !t.is_empty())?;
+        let sender = ctx.senders.name(row.is_from_me, row.handle_id);
+        if !ctx.options.filter.matches(&sender, &text) {
+            self.filtered_out += 1;
+            return None;
+        }
+        let export_id = ctx.export_id(row.chat_id);
+        let exported = ctx.to_exported(
+            row.guid,
+            row.date,
+            row.is_from_me,
+            row.handle_id,
+            text,
+            None,
+        );
+        self.messages_by_chat
+            .entry(export_id)
+            .or_default()
+            .push(exported);
+        Some(export_id)
+    }
+}
+
+/// Stream the messages of `selected_ids`; `on_progress` gets the running
+/// count of messages read every [`PROGRESS_INTERVAL`] messages, from
+/// whichever worker reaches it. `workers` defaults to the number of CPUs.
+pub(super) fn stream_messages(
+    chat_db: &ChatDb,
+    selected_ids: &BTreeSet<i32>,
+    ctx: &StreamContext,
+    workers: Option<usize>,
+    on_progress: &(dyn Fn(usize) + Sync),
+) -> Result<Streamed, String> {
+    let workers =
+        workers.unwrap_or_else(|| thread::available_parallelism().map_or(1, NonZeroUsize::get));
+    let batches = batches(&chat_db.conn, selected_ids, ctx, workers);
+    let read = AtomicUsize::new(0);
+    if batches.len() <= 1 {
+        let Some(batch) = batches.into_iter().next() else {
+            return Ok(Streamed::default());
+        };
+        return stream_batch(&chat_db.conn, batch, ctx, &read, on_progress);
+    }
+
+    // Connections can be sent to a worker but not shared with one
+    let connections = batches
+        .iter()
+        .map(|_| chat_db.connect_again())
+        .collect::<Result<Vec<Connection>, String>>()?;
+    let read = &read;
+    let results: Vec<Result<Streamed, String>> = thread::scope(|scope| {
+        let handles: Vec<_> = batches
+            .into_iter()
+            .zip(connections)
+            .map(|(batch, db)| {
+                scope.spawn(move || stream_batch(&db, batch, ctx, read, on_progress))
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| {
+                handle
+                    .join()
+                    .unwrap_or_else(|_| Err("Export worker panicked".to_string()))
+            })
+            .collect()
+    });
+
+    let mut streamed = Streamed::default();
+    for result in results {
+        streamed.merge(result?);
+    }
+    Ok(streamed)
+}
+
+/// Stream one batch of chats over `db`
+fn stream_batch(
+    db: &Connection,
+    chat_ids: BTreeSet<i32>,
+    ctx: &StreamContext,
+    read: &AtomicUsize,
+    on_progress: &(dyn Fn(usize) + Sync),
+) -> Result<Streamed, String> {
+    let mut query_context = QueryContext::default();
+    query_context.set_selected_chat_ids(chat_ids.clone());
+    let mut streamed = Streamed::default();
+    // Rows added so far, skipped if a lock makes the query run again
+    let mut added: HashSet<(i32, i32)> = HashSet::new();
+    retry_on_busy(|| -> Result<(), TableError> {
+        let mut statement = Message::stream_rows(db, &query_context)?;
+        let rows = statement.query_map([], |row| Ok(Message::from_row(row)))?;
+        let mut unreadable_rows = 0;
+        for row in rows {
+            let message = match Message::extract(row) {
+                Ok(message) => message,
+                Err(e) if e.is_busy() => return Err(e),
+                Err(e) => {
+                    tracing::warn!("Error reading message: {:?}", e);
+                    unreadable_rows += 1;
+                    continue;
+                }
+            };
+            // The query also returns recently deleted messages of these
+            // chats, which no longer belong to any; they are exported (from
+            // the chat they were deleted from) only when asked for
+            let chat_id = match message.chat_id {
+                None if ctx.options.include_recently_deleted => message.deleted_from,
+                chat_id => chat_id,
+            };
+            let Some(chat_id) = chat_id.filter(|id| chat_ids.contains(id)) else {
+                continue;
+            };
+            if !added.insert((message.rowid, chat_id)) {
+                continue;
+            }
+            streamed.add(db, message, chat_id, ctx);
+
+            let count = read.fetch_add(1, Ordering::Relaxed) + 1;
+            if count % PROGRESS_INTERVAL == 0 {
+                on_progress(count);
+            }
+        }
+        streamed.unreadable_rows = unreadable_rows;
+        Ok(())
+    })
+    .map_err(|e| format!("Failed to stream messages: {e}"))?;
+    Ok(streamed)
+}
+
+/// Split `selected_ids` into at most `workers` batches of similar message
+/// counts, keeping merged chats together (largest first, each onto the
+/// lightest batch)
+fn batches(
+    db: &Connection,
+    selected_ids: &BTreeSet<i32>,
+    ctx: &StreamContext,
+    workers: usize,
+) -> Vec<BTreeSet<i32>> {
+    let sizes = chat_sizes(db);
+    let mut groups: HashMap<i32, (usize, BTreeSet<i32>)> = HashMap::new();
+    for &chat_id in selected_ids {
+        let group = groups.entry(ctx.export_id(chat_id)).or_default();
+        group.0 += sizes.get(&chat_id).copied().unwrap_or(0);
+        group.1.insert(chat_id);
+    }
+    let mut groups: Vec<(usize, BTreeSet<i32>)> = groups.into_values().collect();
+    groups.sort_by(|a, b| (b.0, &a.1).cmp(&(a.0, &b.1)));
+
+    let mut batches: Vec<(usize, BTreeSet<i32>)> = Vec::new();
+    for (size, chat_ids) in groups {
+        if batches.len() < workers.max(1) {
+            batches.push((size, chat_ids));
+        } else if let Some(lightest) = batches.iter_mut().min_by_key(|(total, _)| *total) {
+            lightest.0 += size;
+            lightest.1.extend(chat_ids);
+        }
+    }
+    batches.into_iter().map(|(_, chat_ids)| chat_ids).collect()
+}
+
+/// Messages per chat ID; empty if the join table can't be read
+fn chat_sizes(db: &Connection) -> HashMap<i32, usize> {
+    let sizes = || -> rusqlite::Result<HashMap<i32, usize>> {
+        let mut statement =
+            db.prepare("SELECT chat_id, COUNT(*) FROM chat_message_join GROUP BY chat_id")?;
+        let rows = statement.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        rows.collect()
+    };
+    sizes().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    use crate::test_fixtures::{ChatBuilder, MessageBuilder, TestIMessageDb};
+
+    /// Five chats with 6, 1, 4, 1 and 2 messages
+    fn five_chats() -> (TestIMessageDb, Vec<i32>) {
+        let mut db = TestIMessageDb::new().unwrap();
+        let chats: Vec<i32> = (0..5)
+            .map(|i| {
+                db.chat(ChatBuilder::new(format!("+1555000000{i}")))
+                    .unwrap()
+            })
+            .collect();
+        for (chat, count) in chats.iter().zip([6, 1, 4, 1, 2]) {
+            for n in 0..count {
+                let text = format!("Message {n} in chat {chat}");
+                db.message(MessageBuilder::new().text(&text).chat(*chat).date(n))
+                    .unwrap();
+            }
+        }
+        (db, chats)
+    }
+
+    #[test]
+    fn batches_balance_message_counts_and_keep_merged_chats_together() {
+        let (db, chats) = five_chats();
+        let options = ExportOptions::default();
+        let senders = SenderNames::load(db.conn()).unwrap();
+        // The two single-message chats are exported as one
+        let canonical_ids = HashMap::from([(chats[1], chats[1]), (chats[3], chats[1])]);
+        let ctx = StreamContext {
+            options: &options,
+            senders: &senders,
+            canonical_ids: &canonical_ids,
+            unit: TimestampUnit::detect(db.conn()),
+            since: None,
+        };
+        let selected: BTreeSet<i32> = chats.iter().copied().collect();
+
+        assert_eq!(
+            batches(db.conn(), &selected, &ctx, 2),
+            [
+                BTreeSet::from([chats[0], chats[4]]),
+                BTreeSet::from([chats[1], chats[2], chats[3]])
+            ]
+        );
+        assert_eq!(batches(db.conn(), &selected, &ctx, 1), [selected]);
+    }
+
+    #[test]
+    fn parallel_workers_stream_the_same_messages_as_one() {
+        let (db, chats) = five_chats();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("chat.db");
+        db.save_to(&path).unwrap();
+        let chat_db = crate::db_snapshot::open_chat_db(Some(&path)).unwrap();
+        let options = ExportOptions::default();
+        let senders = SenderNames::load(&chat_db.conn).unwrap();
+        let canonical_ids = HashMap::from([(chats[1], chats[1]), (chats[3], chats[1])]);
+        let ctx = StreamContext {
+            options: &options,
+            senders: &senders,
+            canonical_ids: &canonical_ids,
+            unit: TimestampUnit::detect(&chat_db.conn),
+            since: None,
+        };
+        let selected: BTreeSet<i32> = chats.iter().copied().collect();
+        let stream = |workers| {
+            let streamed = stream_messages(&chat_db, &selected, &ctx, Some(workers), &|_| {});
+            let streamed = streamed.unwrap();
+            let messages: BTreeMap<i32, Vec<String>> = streamed
+                .messages_by_chat
+                .into_iter()
+                .map(|(chat, messages)| (chat, messages.into_iter().map(|m| m.text).collect()))
+                .collect();
+            (messages, streamed.processed, streamed.seen_rowids.len())
+        };
+
+        let serial = stream(1);
+        assert_eq!(serial, stream(3));
+        assert_eq!(serial.1, 14);
+        assert_eq!(serial.0.len(), 4);
+        assert_eq!(
+            serial.0[&chats[1]],
+            [
+                format!("Message 0 in chat {}", chats[1]),
+                format!("Message 0 in chat {}", chats[3])
+            ]
+        );
+    }
+}
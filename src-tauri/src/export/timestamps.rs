@@ -0,0 +1,180 @@
+/*!
+ * Timestamp conversion for exports.
+ *
+ * iMessage stores dates since 2001-01-01 (Apple epoch): in nanoseconds
+ * since macOS 10.13.4 / iOS 11, in seconds before that. Which one a
+ * database uses is detected once from its newest message date (see
+ * [`TimestampUnit::detect`]). Exports carry ISO 8601 strings in local time
+ * or UTC ([`ExportTimezone`]); the manifest records which.
+ */
+
+use chrono::{DateTime, Local, TimeZone, Utc};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+/// iMessage timestamp epoch offset (2001-01-01 vs 1970-01-01)
+const APPLE_EPOCH_OFFSET: i64 = 978_307_200;
+
+/// Nanoseconds factor for iMessage timestamps
+const TIMESTAMP_FACTOR: i64 = 1_000_000_000;
+
+/// Dates at or above this are nanoseconds: in nanoseconds it is
+/// 2001-01-01 00:16:40, in seconds it would be the year 33,000
+const NANOSECONDS_THRESHOLD: i64 = 1_000_000_000_000;
+
+/// How a database stores message dates
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum TimestampUnit {
+    Nanoseconds,
+    /// High Sierra and earlier
+    Seconds,
+}
+
+impl TimestampUnit {
+    /// The unit of `db`'s `message.date`, judged by its largest value;
+    /// nanoseconds for an empty or unreadable table
+    pub(crate) fn detect(db: &Connection) -> Self {
+        db.query_row("SELECT MAX(date) FROM message", [], |row| {
+            row.get::<_, Option<i64>>(0)
+        })
+        .ok()
+        .flatten()
+        .map_or(Self::Nanoseconds, |newest| {
+            if newest >= NANOSECONDS_THRESHOLD {
+                Self::Nanoseconds
+            } else {
+                Self::Seconds
+            }
+        })
+    }
+
+    /// A span of `seconds` in this unit
+    pub(crate) fn span(self, seconds: i64) -> i64 {
+        seconds.saturating_mul(self.factor())
+    }
+
+    fn factor(self) -> i64 {
+        match self {
+            Self::Nanoseconds => TIMESTAMP_FACTOR,
+            Self::Seconds => 1,
+        }
+    }
+}
+
+/// The timezone exported timestamps are written in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum ExportTimezone {
+    /// The machine's timezone, e.g. `2024-01-05T18:30:00+13:00`
+    #[default]
+    Local,
+    /// `2024-01-05T05:30:00+00:00`
+    Utc,
+}
+
+impl ExportTimezone {
+    /// IANA name for the manifest ("Pacific/Auckland", "UTC"); `None` if
+    /// the local zone can't be determined
+    pub fn iana_name(self) -> Option<String> {
+        match self {
+            Self::Local => iana_time_zone::get_timezone().ok(),
+            Self::Utc => Some("UTC".to_string()),
+        }
+    }
+
+    /// Convert a Unix timestamp (seconds) to an ISO 8601 string in this zone
+    pub(crate) fn format_unix(self, unix_timestamp: i64) -> String {
+        let Some(dt) = DateTime::from_timestamp(unix_timestamp, 0) else {
+            return Utc::now().to_rfc3339();
+        };
+        match self {
+            Self::Local => Local.from_utc_datetime(&dt.naive_utc()).to_rfc3339(),
+            Self::Utc => dt.to_rfc3339(),
+        }
+    }
+}
+
+/// Convert iMessage timestamp to a local ISO 8601 string
+pub(crate) fn format_timestamp(imessage_timestamp: i64, unit: TimestampUnit) -> String {
+    format_timestamp_in(imessage_timestamp, unit, ExportTimezone::Local)
+}
+
+/// Convert iMessage timestamp to an ISO 8601 string in `timezone`
+pub(crate) fn format_timestamp_in(
+    imessage_timestamp: i64,
+    unit: TimestampUnit,
+    timezone: ExportTimezone,
+) -> String {
+    timezone.format_unix((imessage_timestamp / unit.factor()) + APPLE_EPOCH_OFFSET)
+}
+
+/// Convert a Unix timestamp (seconds) to an iMessage timestamp
+pub(crate) fn to_imessage_timestamp(unix_timestamp: i64, unit: TimestampUnit) -> i64 {
+    unix_timestamp
+        .saturating_sub(APPLE_EPOCH_OFFSET)
+        .saturating_mul(unit.factor())
+}
+
+/// Convert a Unix timestamp (seconds) to a local ISO 8601 string
+pub(crate) fn format_unix_timestamp(unix_timestamp: i64) -> String {
+    ExportTimezone::Local.format_unix(unix_timestamp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{MessageBuilder, TestIMessageDb};
+
+    #[test]
+    fn test_format_timestamp() {
+        // 2024-01-01 00:00:00 UTC in iMessage timestamp format
+        // Unix: 1704067200, iMessage: (1704067200 - 978307200) * 1_000_000_000
+        let imessage_ts = (1704067200_i64 - APPLE_EPOCH_OFFSET) * TIMESTAMP_FACTOR;
+        let result = format_timestamp(imessage_ts, TimestampUnit::Nanoseconds);
+
+        // Should contain 2024-01-01
+        assert!(result.contains("2024-01-01") || result.contains("2023-12-31"));
+        assert_eq!(
+            to_imessage_timestamp(1704067200, TimestampUnit::Nanoseconds),
+            imessage_ts
+        );
+        assert_eq!(
+            format_timestamp_in(imessage_ts, TimestampUnit::Nanoseconds, ExportTimezone::Utc),
+            "2024-01-01T00:00:00+00:00"
+        );
+        assert_eq!(ExportTimezone::Utc.iana_name().as_deref(), Some("UTC"));
+    }
+
+    #[test]
+    fn seconds_timestamps_from_older_databases_are_detected_and_converted() {
+        // 2017-06-01 00:00:00 UTC, as High Sierra stored it
+        let seconds_ts = 1_496_275_200 - APPLE_EPOCH_OFFSET;
+        let result = format_timestamp(seconds_ts, TimestampUnit::Seconds);
+        assert!(result.contains("2017-06-01") || result.contains("2017-05-31"));
+        assert_eq!(
+            to_imessage_timestamp(1_496_275_200, TimestampUnit::Seconds),
+            seconds_ts
+        );
+
+        let mut older = TestIMessageDb::new().unwrap();
+        older
+            .message(MessageBuilder::new().date(seconds_ts))
+            .unwrap();
+        assert_eq!(TimestampUnit::detect(older.conn()), TimestampUnit::Seconds);
+
+        let mut newer = TestIMessageDb::new().unwrap();
+        newer
+            .message(MessageBuilder::new().date(seconds_ts * TIMESTAMP_FACTOR))
+            .unwrap();
+        assert_eq!(
+            TimestampUnit::detect(newer.conn()),
+            TimestampUnit::Nanoseconds
+        );
+        let empty = TestIMessageDb::new().unwrap();
+        assert_eq!(
+            TimestampUnit::detect(empty.conn()),
+            TimestampUnit::Nanoseconds
+        );
+    }
+}
@@ -0,0 +1,47 @@
+/*!
+ * Message text length limits.
+ *
+ * A single pasted log or document can run to hundreds of kilobytes, which
+ * bloats the export and blows through server-side token limits. When
+ * `ExportOptions::max_text_length` is set, longer messages are cut at a
+ * character boundary and flagged with `truncated: true` so the server
+ * knows the text is partial.
+ */
+
+/// Cut `text` to at most `max_chars` characters.
+///
+/// Returns the (possibly shortened) text and whether it was truncated. A
+/// limit of `None` or `0` means "no limit".
+pub(super) fn truncate_text(text: String, max_chars: Option<usize>) -> (String, bool) {
+    let Some(max_chars) = max_chars.filter(|&max| max > 0) else {
+        return (text, false);
+    };
+    match text.char_indices().nth(max_chars) {
+        Some((byte_index, _)) => (text[..byte_index].to_string(), true),
+        None => (text, false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn long_text_is_cut_at_a_char_boundary() {
+        assert_eq!(
+            truncate_text("héllo wörld".to_string(), Some(4)),
+            ("héll".to_string(), true)
+        );
+        assert_eq!(
+            truncate_text("short".to_string(), Some(5)),
+            ("short".to_string(), false)
+        );
+    }
+
+    #[test]
+    fn no_limit_keeps_text() {
+        let text = "x".repeat(10_000);
+        assert_eq!(truncate_text(text.clone(), None), (text.clone(), false));
+        assert_eq!(truncate_text(text.clone(), Some(0)), (text, false));
+    }
+}
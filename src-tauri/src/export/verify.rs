@@ -0,0 +1,440 @@
+/*!
+ * Sanity checks of an export zip, before re-uploading an old one or as a
+ * regression check of the exporter.
+ *
+ * [`verify_export`] checks `manifest.json` has the keys its
+ * `schema_version` requires (and says when that isn't the version this app
+ * writes), that every entry matches its checksum, and that every chat file
+ * the manifest lists parses in the manifest's format, with the message
+ * counts the manifest gives. Unlike reading an archive (see diff.rs), it
+ * carries on past problems and reports them all: errors for what would stop
+ * the export being processed, warnings for inconsistencies that wouldn't.
+ */
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write as _,
+    fs::File,
+    io::Read,
+    path::Path,
+};
+
+use chrono::DateTime;
+use serde::Serialize;
+use serde_json::Value;
+
+use super::{
+    archive::{sha256_hex, MANIFEST_SCHEMA_VERSION},
+    ExportFormat,
+};
+
+/// Manifest keys every export has had
+const BASE_KEYS: [&str; 5] = [
+    "version",
+    "source",
+    "export_date",
+    "chat_count",
+    "total_messages",
+];
+
+/// Manifest keys added by schema version 2
+const SCHEMA_2_KEYS: [&str; 4] = ["schema_version", "app_version", "chat_files", "checksums"];
+
+/// What a chat's files hold
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ChatStats {
+    pub name: String,
+    pub identifier: String,
+    /// Files the chat is written to (more than one when split into parts)
+    pub files: Vec<String>,
+    pub message_count: usize,
+    pub first_message_at: Option<String>,
+    pub last_message_at: Option<String>,
+}
+
+/// The result of checking an export zip
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ExportVerification {
+    pub size_bytes: u64,
+    pub schema_version: Option<u64>,
+    pub source: Option<String>,
+    pub format: ExportFormat,
+    /// Entries whose checksum was checked
+    pub checksummed_files: usize,
+    /// Chats whose files were parsed
+    pub chats: Vec<ChatStats>,
+    pub message_count: usize,
+    /// What would stop the export being processed
+    pub errors: Vec<String>,
+    /// Inconsistencies that wouldn't
+    pub warnings: Vec<String>,
+}
+
+impl ExportVerification {
+    pub fn is_valid(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// Human-readable report
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+        let version = self
+            .schema_version
+            .map_or_else(|| "none".to_string(), |version| version.to_string());
+        let _ = writeln!(
+            out,
+            "Source: {} ({:?} chat files, manifest schema {version})",
+            self.source.as_deref().unwrap_or("unknown"),
+            self.format
+        );
+        let _ = writeln!(
+            out,
+            "{} messages in {} chats, {} bytes; {} checksums verified",
+            self.message_count,
+            self.chats.len(),
+            self.size_bytes,
+            self.checksummed_files
+        );
+        let dates = self
+            .chats
+            .iter()
+            .flat_map(|chat| (chat.first_message_at.iter()).chain(chat.last_message_at.iter()));
+        if let (Some(first), Some(last)) = date_range(dates) {
+            let _ = writeln!(out, "Messages from {first} to {last}");
+        }
+        for (label, problems) in [("Errors", &self.errors), ("Warnings", &self.warnings)] {
+            if problems.is_empty() {
+                continue;
+            }
+            let _ = writeln!(out, "\n{label} ({}):", problems.len());
+            for problem in problems {
+                let _ = writeln!(out, "  {problem}");
+            }
+        }
+        if self.is_valid() {
+            out.push_str("\nThe export is valid\n");
+        }
+        out
+    }
+}
+
+/// Check the export zip at `path`; `Err` only when it isn't a zip at all
+pub fn verify_export(path: &Path) -> Result<ExportVerification, String> {
+    let file = File::open(path).map_err(|e| format!("Failed to open {path:?}: {e}"))?;
+    let mut report = ExportVerification {
+        size_bytes: file.metadata().map_or(0, |metadata| metadata.len()),
+        ..Default::default()
+    };
+    let mut zip =
+        zip::ZipArchive::new(file).map_err(|e| format!("Not an export zip {path:?}: {e}"))?;
+    let manifest = read_entry(&mut zip, "manifest.json").and_then(|bytes| {
+        serde_json::from_slice::<Value>(&bytes).map_err(|e| format!("Invalid manifest.json: {e}"))
+    });
+    let manifest = match manifest {
+        Ok(manifest) if manifest.is_object() => manifest,
+        Ok(_) => {
+            report
+                .errors
+                .push("manifest.json isn't an object".to_string());
+            return Ok(report);
+        }
+        Err(e) => {
+            report.errors.push(e);
+            return Ok(report);
+        }
+    };
+    check_manifest(&manifest, &mut report);
+    check_checksums(&mut zip, &manifest, &mut report);
+    check_chat_files(&mut zip, &manifest, &mut report);
+    Ok(report)
+}
+
+fn check_manifest(manifest: &Value, report: &mut ExportVerification) {
+    report.schema_version = manifest["schema_version"].as_u64();
+    report.source = manifest["source"].as_str().map(str::to_string);
+    report.format = ExportFormat::of_manifest(manifest);
+    let schema_version = report.schema_version.unwrap_or(1);
+    if schema_version != u64::from(MANIFEST_SCHEMA_VERSION) {
+        report.warnings.push(format!(
+            "The manifest has schema version {schema_version}; this app writes \
+             {MANIFEST_SCHEMA_VERSION}"
+        ));
+    }
+    let required = BASE_KEYS
+        .iter()
+        .chain(SCHEMA_2_KEYS.iter().filter(|_| schema_version >= 2));
+    for key in required {
+        if manifest.get(*key).is_none() {
+            report.errors.push(format!("manifest.json has no {key:?}"));
+        }
+    }
+    for key in ["chat_count", "total_messages"] {
+        if manifest.get(key).is_some_and(|value| !value.is_u64()) {
+            report
+                .errors
+                .push(format!("manifest.json {key:?} isn't a count"));
+        }
+    }
+    if manifest.get("format").is_some() && manifest["format"] != serde_json::json!(report.format) {
+        report
+            .errors
+            .push(format!("Unknown chat file format {}", manifest["format"]));
+    }
+}
+
+/// Every listed checksum matches, and every entry has one
+fn check_checksums<R: Read + std::io::Seek>(
+    zip: &mut zip::ZipArchive<R>,
+    manifest: &Value,
+    report: &mut ExportVerification,
+) {
+    let Some(checksums) = manifest["checksums"].as_object() else {
+        return;
+    };
+    for (name, expected) in checksums {
+        match read_entry(zip, name) {
+            Ok(bytes) if expected.as_str() == Some(sha256_hex(&bytes).as_str()) => {
+                report.checksummed_files += 1;
+            }
+            Ok(_) => report.errors.push(format!(
+                "{name} doesn't match its checksum; the export is damaged"
+            )),
+            Err(_) => report
+                .errors
+                .push(format!("{name} has a checksum but isn't in the zip")),
+        }
+    }
+    let unlisted: Vec<String> = zip
+        .file_names()
+        .filter(|name| *name != "manifest.json" && !name.ends_with('/'))
+        .filter(|name| !checksums.contains_key(*name))
+        .map(str::to_string)
+        .collect();
+    for name in unlisted {
+        report.warnings.push(format!("{name} has no checksum"));
+    }
+}
+
+/// Parse every chat file the manifest lists
+fn check_chat_files<R: Read + std::io::Seek>(
+    zip: &mut zip::ZipArchive<R>,
+    manifest: &Value,
+    report: &mut ExportVerification,
+) {
+    let listed = listed_chats(zip, manifest);
+    if matches!(report.format, ExportFormat::Csv | ExportFormat::Txt) {
+        report.warnings.push(format!(
+            "{:?} chat files can't be parsed; only their checksums were checked",
+            report.format
+        ));
+        return;
+    }
+    for (entry, files) in listed {
+        let mut stats = ChatStats {
+            name: entry["name"].as_str().unwrap_or_default().to_string(),
+            files: files.clone(),
+            ..Default::default()
+        };
+        let mut timestamps = Vec::new();
+        for name in &files {
+            let chat = read_entry(zip, name).and_then(|bytes| {
+                let contents = String::from_utf8(bytes).map_err(|e| e.to_string())?;
+                report.format.read_chat(&contents)
+            });
+            match chat {
+                Ok(chat) => {
+                    stats.name = chat.meta.name;
+                    stats.identifier = chat.meta.identifier;
+                    stats.message_count += chat.messages.len();
+                    timestamps.extend(chat.messages.into_iter().map(|m| m.timestamp));
+                }
+                Err(e) => report.errors.push(format!("Invalid {name}: {e}")),
+            }
+        }
+        let unreadable = timestamps
+            .iter()
+            .filter(|timestamp| DateTime::parse_from_rfc3339(timestamp).is_err())
+            .count();
+        if unreadable > 0 {
+            report.warnings.push(format!(
+                "{unreadable} messages in {} have unreadable timestamps",
+                files[0]
+            ));
+        }
+        (stats.first_message_at, stats.last_message_at) = date_range(timestamps.iter());
+        if let Some(expected) = entry["message_count"].as_u64() {
+            if expected as usize != stats.message_count {
+                report.warnings.push(format!(
+                    "The manifest gives {} {expected} messages, but its files have {}",
+                    files[0], stats.message_count
+                ));
+            }
+        }
+        report.message_count += stats.message_count;
+        report.chats.push(stats);
+    }
+
+    for (key, actual) in [
+        ("chat_count", report.chats.len()),
+        ("total_messages", report.message_count),
+    ] {
+        if let Some(expected) = manifest[key].as_u64() {
+            if expected as usize != actual {
+                report.warnings.push(format!(
+                    "The manifest's {key:?} is {expected}, but the zip has {actual}"
+                ));
+            }
+        }
+    }
+}
+
+/// Each chat's `chat_files` entry with its file names; without that list
+/// (older exports), every other JSON file
+fn listed_chats<R: Read + std::io::Seek>(
+    zip: &zip::ZipArchive<R>,
+    manifest: &Value,
+) -> Vec<(Value, Vec<String>)> {
+    let Some(entries) = manifest["chat_files"].as_array() else {
+        let names: BTreeSet<&str> = zip
+            .file_names()
+            .filter(|name| name.ends_with(".json"))
+            .filter(|name| !matches!(*name, "manifest.json" | "shared_links.json"))
+            .collect();
+        return names
+            .into_iter()
+            .map(|name| (Value::Null, vec![name.to_string()]))
+            .collect();
+    };
+    entries
+        .iter()
+        .map(|entry| {
+            let files = match entry["parts"].as_array() {
+                Some(parts) => parts.iter().map(|part| &part["file"]).collect(),
+                None => vec![&entry["file"]],
+            };
+            let files = files
+                .into_iter()
+                .filter_map(|file| file.as_str().map(str::to_string))
+                .collect();
+            (entry.clone(), files)
+        })
+        .filter(|(_, files): &(Value, Vec<String>)| !files.is_empty())
+        .collect()
+}
+
+/// The earliest and latest of `timestamps` that parse
+fn date_range<'a>(
+    timestamps: impl Iterator<Item = &'a String>,
+) -> (Option<String>, Option<String>) {
+    let dates: BTreeMap<_, &String> = timestamps
+        .filter_map(|timestamp| Some((DateTime::parse_from_rfc3339(timestamp).ok()?, timestamp)))
+        .collect();
+    let first = dates.values().next().map(|timestamp| timestamp.to_string());
+    let last = dates
+        .values()
+        .next_back()
+        .map(|timestamp| timestamp.to_string());
+    (first, last)
+}
+
+fn read_entry<R: Read + std::io::Seek>(
+    zip: &mut zip::ZipArchive<R>,
+    name: &str,
+) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+    zip.by_name(name)
+        .map_err(|e| format!("Failed to read {name}: {e}"))?
+        .read_to_end(&mut bytes)
+        .map_err(|e| format!("Failed to read {name}: {e}"))?;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use tempfile::TempDir;
+    use zip::write::SimpleFileOptions;
+
+    use super::*;
+    use crate::{
+        export::{export_chats, ExportOptions},
+        test_fixtures::{ChatBuilder, MessageBuilder, TestIMessageDb},
+    };
+
+    #[test]
+    fn a_fresh_export_is_valid_and_damage_is_reported() {
+        let mut db = TestIMessageDb::new().unwrap();
+        let chat = db
+            .chat(ChatBuilder::new("iMessage;-;+15551234567"))
+            .unwrap();
+        for n in 1..=3 {
+            db.message(MessageBuilder::new().text("hi").chat(chat).date(n * 100))
+                .unwrap();
+        }
+        let dir = TempDir::new().unwrap();
+        let db_path = dir.path().join("chat.db");
+        db.save_to(&db_path).unwrap();
+        let options = ExportOptions {
+            max_messages_per_file: Some(2),
+            ..Default::default()
+        };
+        let result = export_chats(&[chat], &options, None, Some(&db_path)).unwrap();
+
+        let report = verify_export(&result.zip_path).unwrap();
+        assert!(report.is_valid(), "{report:?}");
+        assert!(report.warnings.is_empty(), "{:?}", report.warnings);
+        assert_eq!(report.message_count, 3);
+        assert_eq!(report.chats[0].files.len(), 2);
+        assert!(report.to_text().contains("The export is valid"));
+
+        // Copy the zip, changing a chat part and dropping the README
+        let damaged = dir.path().join("damaged.zip");
+        let mut original = zip::ZipArchive::new(File::open(&result.zip_path).unwrap()).unwrap();
+        let mut copy = zip::ZipWriter::new(File::create(&damaged).unwrap());
+        let part = report.chats[0].files[1].clone();
+        for i in 0..original.len() {
+            let mut entry = original.by_index(i).unwrap();
+            let name = entry.name().to_string();
+            let mut bytes = Vec::new();
+            entry.read_to_end(&mut bytes).unwrap();
+            if name == "README.txt" {
+                continue;
+            }
+            if name == part {
+                bytes = b"{\"meta\": 1}".to_vec();
+            }
+            copy.start_file(name, SimpleFileOptions::default()).unwrap();
+            copy.write_all(&bytes).unwrap();
+        }
+        copy.finish().unwrap();
+
+        let report = verify_export(&damaged).unwrap();
+        assert!(!report.is_valid());
+        let errors = report.errors.join("\n");
+        assert!(
+            errors.contains(&format!("{part} doesn't match its checksum")),
+            "{errors}"
+        );
+        assert!(errors.contains("README.txt has a checksum but isn't in the zip"));
+        assert!(errors.contains(&format!("Invalid {part}")));
+        assert!(report
+            .warnings
+            .iter()
+            .any(|warning| warning.contains("but its files have 2")));
+    }
+
+    #[test]
+    fn missing_manifest_keys_are_errors() {
+        let mut report = ExportVerification::default();
+        check_manifest(
+            &serde_json::json!({"schema_version": 2, "format": "xml", "chat_count": "3"}),
+            &mut report,
+        );
+        assert!(report.warnings[0].contains("schema version 2"));
+        let errors = report.errors.join("\n");
+        assert!(errors.contains("no \"checksums\""), "{errors}");
+        assert!(errors.contains("\"chat_count\" isn't a count"));
+        assert!(errors.contains("Unknown chat file format \"xml\""));
+    }
+}
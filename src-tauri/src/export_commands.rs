@@ -0,0 +1,354 @@
+//! Tauri commands that package chats into an export zip and upload it.
+//!
+//! `export_and_upload` reads iMessage; `import_telegram_export` converts a
+//! Telegram Desktop `result.json`. Both save the finished zip to the outbox
+//! and hand it to `upload_export` (see upload_commands.rs), which runs
+//! presign → PUT → complete and opens the results page.
+//!
+//! Progress updates double as heartbeats: a stage that goes quiet for too
+//! long emits an `export-stalled` event, and presign + PUT (safe to repeat,
+//! since nothing is committed before `complete_upload`) are retried.
+//!
+//! Only one export runs at a time: starting another while one is in
+//! progress fails with [`ExportError::Busy`] (see export/state.rs). Each
+//! export gets an ID, announced in an `export-started` event and carried by
+//! its progress events and result; `cancel_export` takes that ID.
+//!
+//! Every finished run is logged with its throughput and appended to the
+//! local job history (see run_history.rs), and raises a native
+//! notification if the app is in the background (see notifications.rs).
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use chat_to_map_desktop::{
+    export::{
+        self, export_chats,
+        metrics::ExportMetrics,
+        queue::ExportRequest,
+        schedule,
+        state::{ExportError, ExportRun, ExportState, ExportStatus},
+        ExportFormat, ExportOptions, ExportProgress,
+    },
+    heartbeat::{Heartbeat, StagePolicy, StallMonitor},
+    outbox,
+    sources::telegram,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tauri::Emitter;
+
+use crate::{
+    notifications::notify_export_finished,
+    run_history::record_run,
+    settings_commands::{db_path_or_saved, load_settings},
+    upload_commands::{discard_cancelled, upload_export, UploadContext},
+    AppState,
+};
+
+/// Export result returned to the frontend.
+///
+/// `chat_analysis_id` + `job_token` are returned by Convex `uploadComplete` and
+/// together gate access to the results page.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportResult {
+    pub export_id: String,
+    pub success: bool,
+    pub chat_count: usize,
+    pub chat_upload_id: Option<String>,
+    pub chat_analysis_id: Option<String>,
+    pub job_token: Option<String>,
+    pub results_url: Option<String>,
+    pub error: Option<String>,
+    /// Decode, compression and upload throughput
+    pub metrics: ExportMetrics,
+}
+
+/// Start watching for stalls, forwarding them to the window as
+/// `export-stalled` events until the returned monitor is dropped
+pub(crate) fn start_stall_monitor(
+    window: &tauri::Window,
+    stage: &str,
+) -> (Heartbeat, StallMonitor) {
+    let heartbeat = Heartbeat::new(stage, StagePolicy::default());
+    let window = window.clone();
+    let monitor = heartbeat.spawn_monitor(move |event| {
+        let _ = window.emit("export-stalled", event);
+    });
+    (heartbeat, monitor)
+}
+
+/// Payload of the `export-started` event
+#[derive(Debug, Clone, Serialize)]
+struct ExportStarted {
+    export_id: String,
+}
+
+/// Claim the export pipeline and announce the new export's ID
+pub(crate) fn start_export(
+    state: &AppState,
+    window: &tauri::Window,
+) -> Result<ExportRun, ExportError> {
+    let run = state.export_manager().start()?;
+    announce_export(window, &run);
+    Ok(run)
+}
+
+/// Emit `export-started` for a run that has claimed the pipeline
+pub(crate) fn announce_export(window: &tauri::Window, run: &ExportRun) {
+    tracing::info!("[export] Starting export {}", run.id());
+    let _ = window.emit(
+        "export-started",
+        ExportStarted {
+            export_id: run.id().to_string(),
+        },
+    );
+}
+
+/// Emit an `export-progress` event for `export_id` to the window
+pub(crate) fn emit_progress(
+    window: &tauri::Window,
+    export_id: &str,
+    stage: &str,
+    percent: u8,
+    message: &str,
+) {
+    let _ = window.emit(
+        "export-progress",
+        ExportProgress {
+            stage: stage.to_string(),
+            percent,
+            message: message.to_string(),
+            export_id: Some(export_id.to_string()),
+        },
+    );
+}
+
+/// Current state of the export pipeline and the running export's ID
+#[tauri::command]
+pub fn get_export_state(state: tauri::State<'_, AppState>) -> ExportStatus {
+    state.export_manager().status()
+}
+
+/// Cancel the running export, if `export_id` is still the one running
+#[tauri::command]
+pub fn cancel_export(
+    export_id: String,
+    state: tauri::State<'_, AppState>,
+) -> Result<(), ExportError> {
+    state.export_manager().cancel(&export_id)
+}
+
+/// Export selected chats and upload to server
+#[tauri::command]
+pub async fn export_and_upload(
+    chat_ids: Vec<i32>,
+    custom_db_path: Option<String>,
+    options: Option<ExportOptions>,
+    app_handle: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    window: tauri::Window,
+) -> Result<ExportResult, ExportError> {
+    let run = start_export(&state, &window)?;
+    let request = ExportRequest {
+        chat_ids,
+        custom_db_path,
+        options: options.unwrap_or_default(),
+        ..Default::default()
+    };
+    run_export(&app_handle, &state, &window, &run, request).await
+}
+
+/// Run an iMessage export + upload for a run that has claimed the pipeline
+pub(crate) async fn run_export(
+    app_handle: &tauri::AppHandle,
+    state: &AppState,
+    window: &tauri::Window,
+    run: &ExportRun,
+    mut request: ExportRequest,
+) -> Result<ExportResult, ExportError> {
+    let context = UploadContext::capture(app_handle, state)?;
+    let settings = load_settings(app_handle)?;
+    // Kept with the upload, so a `chattomap://job/<job_id>/retry` link can
+    // run it again
+    let rerun = request.clone();
+    let db_path = db_path_or_saved(request.custom_db_path.take(), &settings);
+    let options = &mut request.options;
+    // Exclusion rules always come from saved settings, never the caller
+    options.exclusion_rules = settings.exclusion_rules;
+    // Saved redaction defaults apply unless the caller asked for redaction
+    if !options.redaction.is_enabled() {
+        options.redaction = settings.redaction_defaults;
+    }
+    // The server only reads JSON chat files
+    options.format = ExportFormat::Json;
+
+    let result = run
+        .until_cancelled(export_imessage(
+            request, &rerun, db_path, &context, window, run,
+        ))
+        .await;
+    discard_cancelled(&context, run, &result);
+    record_run(app_handle, run, &result);
+    notify_export_finished(window, &result).await;
+    result
+}
+
+/// The iMessage export pipeline behind `export_and_upload`
+async fn export_imessage(
+    request: ExportRequest,
+    rerun: &ExportRequest,
+    db_path: Option<PathBuf>,
+    context: &UploadContext,
+    window: &tauri::Window,
+    run: &ExportRun,
+) -> Result<ExportResult, ExportError> {
+    let ExportRequest {
+        chat_ids,
+        options,
+        upload_at,
+        keep_in_outbox,
+        ..
+    } = request;
+    let (heartbeat, _monitor) = start_stall_monitor(window, "Exporting");
+
+    // Stage 1: Export messages (0-50%)
+    emit_progress(window, run.id(), "Exporting", 0, "Starting export...");
+
+    let window_clone = window.clone();
+    let export_heartbeat = heartbeat.clone();
+    let export_id = run.id().to_string();
+    let progress_callback = Box::new(move |progress: ExportProgress| {
+        export_heartbeat.beat(&progress.message);
+        // Scale export progress to 0-50%
+        let scaled_percent = progress.percent / 2;
+        let _ = window_clone.emit(
+            "export-progress",
+            ExportProgress {
+                stage: progress.stage,
+                percent: scaled_percent,
+                message: progress.message,
+                export_id: Some(export_id.clone()),
+            },
+        );
+    });
+
+    let metadata = options.metadata.clone();
+    // A cancelled export abandons this task; it finishes in the background
+    // and its progress events still carry the old ID
+    let export_result = tokio::task::spawn_blocking(move || {
+        export_chats(
+            &chat_ids,
+            &options,
+            Some(progress_callback),
+            db_path.as_deref(),
+        )
+    })
+    .await
+    .map_err(|e| format!("Export task failed: {e}"))?
+    .map_err(|e| match e {
+        // Disk space errors reach the frontend as their own kind
+        ExportError::Failed { message } => format!("Export failed: {message}").into(),
+        e => e,
+    })?;
+
+    let pending = outbox::save(
+        &context.outbox,
+        run.id(),
+        &export_result,
+        export::UPLOAD_PLATFORM,
+        &metadata,
+        Some(rerun),
+    )?;
+    if keep_in_outbox {
+        tracing::info!("[export] Export {}: kept in the outbox", run.id());
+        return Ok(ExportResult {
+            export_id: run.id().to_string(),
+            success: true,
+            chat_count: pending.chat_count,
+            chat_upload_id: None,
+            chat_analysis_id: None,
+            job_token: None,
+            results_url: None,
+            error: None,
+            metrics: pending.metrics,
+        });
+    }
+    if let Some(upload_at) = upload_at {
+        wait_for_upload_time(upload_at, window, &heartbeat, run).await;
+    }
+    upload_export(&pending, context, window, &heartbeat, run).await
+}
+
+/// Hold a finished export until its scheduled upload time
+async fn wait_for_upload_time(
+    upload_at: DateTime<Utc>,
+    window: &tauri::Window,
+    heartbeat: &Heartbeat,
+    run: &ExportRun,
+) {
+    if upload_at <= Utc::now() {
+        return;
+    }
+    run.advance(ExportState::WaitingToUpload);
+    let local_time = upload_at
+        .with_timezone(&chrono::Local)
+        .format("%b %-d, %H:%M");
+    let message = format!("Upload scheduled for {local_time}");
+    tracing::info!("[export] Export {}: {message}", run.id());
+    emit_progress(window, run.id(), "Waiting", 50, &message);
+    heartbeat.begin_stage("Waiting", &message, schedule::waiting_policy());
+    schedule::wait_until(upload_at, || heartbeat.beat(&message)).await;
+}
+
+/// Convert a Telegram Desktop `result.json` export and upload it
+#[tauri::command]
+pub async fn import_telegram_export(
+    path: String,
+    app_handle: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    window: tauri::Window,
+) -> Result<ExportResult, ExportError> {
+    let run = start_export(&state, &window)?;
+    let context = UploadContext::capture(&app_handle, &state)?;
+    let result = run
+        .until_cancelled(import_telegram(path, &context, &window, &run))
+        .await;
+    discard_cancelled(&context, &run, &result);
+    record_run(&app_handle, &run, &result);
+    notify_export_finished(&window, &result).await;
+    result
+}
+
+/// The Telegram pipeline behind `import_telegram_export`
+async fn import_telegram(
+    path: String,
+    context: &UploadContext,
+    window: &tauri::Window,
+    run: &ExportRun,
+) -> Result<ExportResult, ExportError> {
+    let (heartbeat, _monitor) = start_stall_monitor(window, "Exporting");
+
+    emit_progress(
+        window,
+        run.id(),
+        "Exporting",
+        0,
+        "Reading Telegram export...",
+    );
+    let export_result =
+        tokio::task::spawn_blocking(move || telegram::import_telegram_export(&PathBuf::from(path)))
+            .await
+            .map_err(|e| format!("Import task failed: {e}"))?
+            .map_err(|e| format!("Import failed: {e}"))?;
+
+    let pending = outbox::save(
+        &context.outbox,
+        run.id(),
+        &export_result,
+        telegram::UPLOAD_PLATFORM,
+        &BTreeMap::new(),
+        None,
+    )?;
+    upload_export(&pending, context, window, &heartbeat, run).await
+}
@@ -0,0 +1,459 @@
+/*!
+ * Tests for what each exported message carries (metadata, timestamps,
+ * attachments, group events, links) and for the chat file formats
+ */
+
+use super::tests::{read_first_chat, read_zip_entry, save_fixture_db};
+use super::*;
+use crate::test_fixtures::{ChatBuilder, HandleBuilder, MessageBuilder, TestIMessageDb};
+
+/// `attributedBody` of an audio message Apple transcribed ("This is a
+/// test"), as Messages saves it: one attachment placeholder whose
+/// attributes carry `IMAudioTranscription`
+const AUDIO_MESSAGE_BODY: &[u8] = include_bytes!("test_fixtures/audio_message.typedstream");
+
+/// `payload_data` of a link Messages previewed (https://chrissardegna.com,
+/// titled "Christopher Sardegna")
+const URL_PREVIEW_PAYLOAD: &[u8] = include_bytes!("test_fixtures/url_preview.plist");
+
+#[test]
+fn test_export_adds_message_metadata_when_enabled() {
+    let mut db = TestIMessageDb::new().unwrap();
+    let handle = db.handle(HandleBuilder::new("+15551234567")).unwrap();
+    let chat = db
+        .chat(ChatBuilder::new("iMessage;-;+15551234567"))
+        .unwrap();
+    db.message(
+        MessageBuilder::new()
+            .text("Are you there?")
+            .handle(handle)
+            .chat(chat)
+            .service("SMS")
+            .date(1_000_000_000_000),
+    )
+    .unwrap();
+    db.raw_message(
+        chat,
+        &[
+            ("text", "'On my way'"),
+            ("is_from_me", "1"),
+            ("service", "'iMessage'"),
+            ("date", "2000000000000"),
+            ("date_delivered", "3000000000000"),
+            ("date_read", "4000000000000"),
+            ("date_edited", "5000000000000"),
+        ],
+    )
+    .unwrap();
+    let dir = TempDir::new().unwrap();
+    let db_path = dir.path().join("chat.db");
+    db.save_to(&db_path).unwrap();
+
+    let plain = export_chats(&[chat], &ExportOptions::default(), None, Some(&db_path)).unwrap();
+    let exported: serde_json::Value =
+        serde_json::from_str(&read_first_chat(&plain.zip_path)).unwrap();
+    assert!(exported["messages"][0].get("service").is_none());
+
+    let options = ExportOptions {
+        include_metadata: true,
+        ..Default::default()
+    };
+    let result = export_chats(&[chat], &options, None, Some(&db_path)).unwrap();
+    let exported: ExportedChat = serde_json::from_str(&read_first_chat(&result.zip_path)).unwrap();
+    let metadata: Vec<MessageMetadata> = exported
+        .messages
+        .into_iter()
+        .map(|message| message.metadata.unwrap())
+        .collect();
+    assert_eq!(
+        metadata,
+        [
+            MessageMetadata {
+                service: "SMS".to_string(),
+                date_delivered: None,
+                date_read: None,
+                is_edited: false,
+            },
+            MessageMetadata {
+                service: "iMessage".to_string(),
+                date_delivered: Some(format_timestamp(
+                    3_000_000_000_000,
+                    TimestampUnit::Nanoseconds
+                )),
+                date_read: Some(format_timestamp(
+                    4_000_000_000_000,
+                    TimestampUnit::Nanoseconds
+                )),
+                is_edited: true,
+            },
+        ]
+    );
+    let manifest: serde_json::Value =
+        serde_json::from_str(&read_zip_entry(&result.zip_path, "manifest.json")).unwrap();
+    assert_eq!(manifest["message_metadata"], true);
+}
+
+#[test]
+fn test_export_writes_utc_timestamps_when_asked() {
+    let dir = TempDir::new().unwrap();
+    let (db_path, chat) = save_fixture_db(&dir);
+    let options = ExportOptions {
+        timezone: ExportTimezone::Utc,
+        ..Default::default()
+    };
+    let result = export_chats(&[chat], &options, None, Some(&db_path)).unwrap();
+    let exported: ExportedChat = serde_json::from_str(&read_first_chat(&result.zip_path)).unwrap();
+    assert_eq!(exported.messages[0].timestamp, "2001-01-01T00:00:00+00:00");
+    let manifest: serde_json::Value =
+        serde_json::from_str(&read_zip_entry(&result.zip_path, "manifest.json")).unwrap();
+    assert_eq!(manifest["timezone"], "UTC");
+}
+
+#[test]
+fn test_export_names_attachments_and_keeps_uncaptioned_ones() {
+    let mut db = TestIMessageDb::new().unwrap();
+    let chat = db
+        .chat(ChatBuilder::new("iMessage;-;+15551234567"))
+        .unwrap();
+    let message = db
+        .message(
+            MessageBuilder::new()
+                .text("\u{FFFC}Beach day\u{200B}")
+                .chat(chat),
+        )
+        .unwrap();
+    // A photo with no caption: no text at all
+    let photo = db.message(MessageBuilder::new().chat(chat)).unwrap();
+    db.conn()
+        .execute_batch(&format!(
+            "INSERT INTO attachment (ROWID, guid, filename, transfer_name)
+             VALUES (1, 'att-1', '~/Library/Messages/Attachments/ab/IMG_0042.jpg', NULL);
+             INSERT INTO attachment (ROWID, guid, mime_type, transfer_name)
+             VALUES (2, 'att-2', 'image/heic', 'IMG_0043.HEIC');
+             INSERT INTO message_attachment_join (message_id, attachment_id)
+             VALUES ({message}, 1), ({photo}, 2);"
+        ))
+        .unwrap();
+    let dir = TempDir::new().unwrap();
+    let db_path = dir.path().join("chat.db");
+    db.save_to(&db_path).unwrap();
+
+    let messages = |options: &ExportOptions| {
+        let result = export_chats(&[chat], options, None, Some(&db_path)).unwrap();
+        let exported: ExportedChat =
+            serde_json::from_str(&read_first_chat(&result.zip_path)).unwrap();
+        exported.messages
+    };
+    let exported = messages(&ExportOptions::default());
+    assert_eq!(exported[0].text, "[attachment: IMG_0042.jpg]Beach day");
+    assert_eq!(exported[1].text, "");
+    assert_eq!(
+        exported[1].attachments,
+        [ExportedAttachment {
+            name: "IMG_0043.HEIC".to_string(),
+            mime_type: Some("image/heic".to_string()),
+            transcription: None,
+        }]
+    );
+    let raw = ExportOptions {
+        raw_text: true,
+        ..Default::default()
+    };
+    assert_eq!(messages(&raw)[0].text, "\u{FFFC}Beach day\u{200B}");
+}
+
+#[test]
+fn test_export_writes_csv_chat_files() {
+    let dir = TempDir::new().unwrap();
+    let (db_path, chat) = save_fixture_db(&dir);
+    let options = ExportOptions {
+        format: ExportFormat::Csv,
+        ..Default::default()
+    };
+
+    let result = export_chats(&[chat], &options, None, Some(&db_path)).unwrap();
+
+    let manifest: serde_json::Value =
+        serde_json::from_str(&read_zip_entry(&result.zip_path, "manifest.json")).unwrap();
+    assert_eq!(manifest["format"], "csv");
+    let filename = manifest["chat_files"][0]["file"].as_str().unwrap();
+    assert!(filename.ends_with(".csv"), "{filename}");
+    let csv = read_first_chat(&result.zip_path);
+    let rows: Vec<&str> = csv.trim_end().split("\r\n").collect();
+    assert_eq!(rows[0], "\u{FEFF}timestamp,sender,is_from_me,text");
+    assert!(
+        rows[1].ends_with(",+15551234567,false,Stay here https://example.com/hotel"),
+        "{csv}"
+    );
+    let readme = read_zip_entry(&result.zip_path, "README.txt");
+    assert!(readme.contains(filename), "{readme}");
+    assert!(diff::read_archive(&result.zip_path).is_err());
+}
+
+#[test]
+fn test_export_writes_ndjson_chat_files_that_read_back() {
+    let dir = TempDir::new().unwrap();
+    let (db_path, chat) = save_fixture_db(&dir);
+    let options = ExportOptions {
+        format: ExportFormat::Ndjson,
+        ..Default::default()
+    };
+
+    let result = export_chats(&[chat], &options, None, Some(&db_path)).unwrap();
+
+    let archive = diff::read_archive(&result.zip_path).unwrap();
+    assert_eq!(archive.manifest["format"], "ndjson");
+    assert_eq!(archive.manifest["version"], "1.1");
+    assert_eq!(archive.chats.len(), 1);
+    assert_eq!(
+        archive.chats[0].messages[0].text,
+        "Stay here https://example.com/hotel"
+    );
+    assert_eq!(read_first_chat(&result.zip_path).lines().count(), 2);
+}
+
+#[test]
+fn test_export_writes_group_events() {
+    let mut db = TestIMessageDb::new().unwrap();
+    let alice = db.handle(HandleBuilder::new("+15551234567")).unwrap();
+    let bob = db.handle(HandleBuilder::new("+15557654321")).unwrap();
+    let chat = db
+        .chat(ChatBuilder::new("chat123456").display_name("Trip").group())
+        .unwrap();
+    let (alice_sql, bob_sql) = (alice.to_string(), bob.to_string());
+    db.raw_message(
+        chat,
+        &[
+            ("item_type", "2"),
+            ("group_title", "'Trip to Portugal 2022'"),
+            ("handle_id", &alice_sql),
+            ("date", "100000000000"),
+        ],
+    )
+    .unwrap();
+    db.raw_message(
+        chat,
+        &[
+            ("item_type", "1"),
+            ("group_action_type", "0"),
+            ("other_handle", &bob_sql),
+            ("is_from_me", "1"),
+            ("date", "200000000000"),
+        ],
+    )
+    .unwrap();
+    let dir = TempDir::new().unwrap();
+    let db_path = dir.path().join("chat.db");
+    db.save_to(&db_path).unwrap();
+
+    let result = export_chats(&[chat], &ExportOptions::default(), None, Some(&db_path)).unwrap();
+
+    let exported: ExportedChat = serde_json::from_str(&read_first_chat(&result.zip_path)).unwrap();
+    let events: Vec<(&str, GroupEvent)> = exported
+        .messages
+        .iter()
+        .map(|m| (m.text.as_str(), m.event.clone().unwrap()))
+        .collect();
+    assert_eq!(
+        events,
+        [
+            (
+                "",
+                GroupEvent {
+                    kind: GroupEventKind::Renamed,
+                    actor: "+15551234567".to_string(),
+                    participant: None,
+                    name: Some("Trip to Portugal 2022".to_string()),
+                }
+            ),
+            (
+                "",
+                GroupEvent {
+                    kind: GroupEventKind::ParticipantAdded,
+                    actor: "Me".to_string(),
+                    participant: Some("+15557654321".to_string()),
+                    name: None,
+                }
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_export_includes_audio_message_transcriptions() {
+    let mut db = TestIMessageDb::new().unwrap();
+    let chat = db
+        .chat(ChatBuilder::new("iMessage;-;+15551234567"))
+        .unwrap();
+    let body = format!("X'{}'", hex::encode(AUDIO_MESSAGE_BODY));
+    let message = db
+        .raw_message(chat, &[("text", "'\u{FFFC}'"), ("attributedBody", &body)])
+        .unwrap();
+    db.conn()
+        .execute_batch(&format!(
+            "INSERT INTO attachment (ROWID, guid, mime_type, transfer_name)
+             VALUES (1, 'att-1', 'audio/x-caf', 'Audio Message.caf');
+             INSERT INTO message_attachment_join (message_id, attachment_id)
+             VALUES ({message}, 1);"
+        ))
+        .unwrap();
+    let dir = TempDir::new().unwrap();
+    let db_path = dir.path().join("chat.db");
+    db.save_to(&db_path).unwrap();
+
+    let result = export_chats(&[chat], &ExportOptions::default(), None, Some(&db_path)).unwrap();
+
+    let exported: ExportedChat = serde_json::from_str(&read_first_chat(&result.zip_path)).unwrap();
+    assert_eq!(exported.messages[0].text, "");
+    assert_eq!(
+        exported.messages[0].attachments,
+        [ExportedAttachment {
+            name: "Audio Message.caf".to_string(),
+            mime_type: Some("audio/x-caf".to_string()),
+            transcription: Some("This is a test".to_string()),
+        }]
+    );
+}
+
+#[test]
+fn test_export_lists_urls_and_link_previews() {
+    let mut db = TestIMessageDb::new().unwrap();
+    let chat = db
+        .chat(ChatBuilder::new("iMessage;-;+15551234567"))
+        .unwrap();
+    let payload = format!("X'{}'", hex::encode(URL_PREVIEW_PAYLOAD));
+    db.raw_message(
+        chat,
+        &[
+            ("text", "'https://chrissardegna.com'"),
+            (
+                "balloon_bundle_id",
+                "'com.apple.messages.URLBalloonProvider'",
+            ),
+            ("payload_data", &payload),
+            ("date", "100000000000"),
+        ],
+    )
+    .unwrap();
+    db.message(
+        MessageBuilder::new()
+            .text("Booked https://airbnb.com/rooms/123 and https://maps.app.goo.gl/xyz!")
+            .chat(chat)
+            .date(200_000_000_000),
+    )
+    .unwrap();
+    let dir = TempDir::new().unwrap();
+    let db_path = dir.path().join("chat.db");
+    db.save_to(&db_path).unwrap();
+
+    let result = export_chats(&[chat], &ExportOptions::default(), None, Some(&db_path)).unwrap();
+
+    let exported: ExportedChat = serde_json::from_str(&read_first_chat(&result.zip_path)).unwrap();
+    let preview = &exported.messages[0];
+    assert_eq!(preview.urls, ["https://chrissardegna.com"]);
+    assert_eq!(
+        preview.link_preview,
+        Some(LinkPreview {
+            url: "https://chrissardegna.com/".to_string(),
+            title: Some("Christopher Sardegna".to_string()),
+            summary: None,
+            site_name: None,
+        })
+    );
+    assert_eq!(
+        exported.messages[1].urls,
+        [
+            "https://airbnb.com/rooms/123",
+            "https://maps.app.goo.gl/xyz"
+        ]
+    );
+    assert_eq!(exported.messages[1].link_preview, None);
+}
+
+#[test]
+fn test_export_includes_recently_deleted_messages_when_enabled() {
+    let mut db = TestIMessageDb::new().unwrap();
+    let handle = db.handle(HandleBuilder::new("+15551234567")).unwrap();
+    let chat = db
+        .chat(ChatBuilder::new("iMessage;-;+15551234567"))
+        .unwrap();
+    db.message(
+        MessageBuilder::new()
+            .text("Kept")
+            .handle(handle)
+            .chat(chat)
+            .date(100),
+    )
+    .unwrap();
+    let deleted = db
+        .message(
+            MessageBuilder::new()
+                .text("Deleted")
+                .handle(handle)
+                .date(200),
+        )
+        .unwrap();
+    db.conn()
+        .execute(
+            "INSERT INTO chat_recoverable_message_join (chat_id, message_id, delete_date)
+             VALUES (?1, ?2, 300)",
+            (chat, deleted),
+        )
+        .unwrap();
+    let dir = TempDir::new().unwrap();
+    let db_path = dir.path().join("chat.db");
+    db.save_to(&db_path).unwrap();
+
+    // Left out by default, and not mistaken for a message that lost its chat
+    let result = export_chats(&[chat], &ExportOptions::default(), None, Some(&db_path)).unwrap();
+    assert_eq!(result.total_messages, 1);
+    assert_eq!(result.deleted_messages, 0);
+    assert!(result.orphans.is_empty());
+
+    let options = ExportOptions {
+        include_recently_deleted: true,
+        ..Default::default()
+    };
+    let result = export_chats(&[chat], &options, None, Some(&db_path)).unwrap();
+    assert_eq!(result.deleted_messages, 1);
+    let exported: serde_json::Value =
+        serde_json::from_str(&read_first_chat(&result.zip_path)).unwrap();
+    assert_eq!(exported["messages"][1]["text"], "Deleted");
+    assert_eq!(exported["messages"][1]["deleted"], true);
+    assert!(exported["messages"][0].get("deleted").is_none());
+    let manifest: serde_json::Value =
+        serde_json::from_str(&read_zip_entry(&result.zip_path, "manifest.json")).unwrap();
+    assert_eq!(manifest["recently_deleted_count"], 1);
+}
+
+#[test]
+fn test_incremental_export_skips_messages_before_since() {
+    let mut db = TestIMessageDb::new().unwrap();
+    let handle = db.handle(HandleBuilder::new("+15551234567")).unwrap();
+    let chat = db
+        .chat(ChatBuilder::new("iMessage;-;+15551234567"))
+        .unwrap();
+    for (date, text) in [(100, "Old news"), (200, "Fresh"), (300, "Fresher")] {
+        let builder = MessageBuilder::new().text(text).handle(handle);
+        db.message(builder.chat(chat).date(date)).unwrap();
+    }
+    let dir = TempDir::new().unwrap();
+    let db_path = dir.path().join("chat.db");
+    db.save_to(&db_path).unwrap();
+
+    // 150 seconds after the Apple epoch (2001-01-01)
+    let since = chrono::DateTime::from_timestamp(978_307_350, 0).unwrap();
+    let options = ExportOptions {
+        since: Some(since),
+        ..Default::default()
+    };
+    let result = export_chats(&[chat], &options, None, Some(&db_path)).unwrap();
+
+    assert_eq!(result.total_messages, 2);
+    let exported: ExportedChat = serde_json::from_str(&read_first_chat(&result.zip_path)).unwrap();
+    let texts: Vec<&str> = exported.messages.iter().map(|m| m.text.as_str()).collect();
+    assert_eq!(texts, ["Fresh", "Fresher"]);
+    let manifest: serde_json::Value =
+        serde_json::from_str(&read_zip_entry(&result.zip_path, "manifest.json")).unwrap();
+    assert_eq!(manifest["since"], since.to_rfc3339());
+}
@@ -0,0 +1,74 @@
+//! Scheduled automatic exports (see the library's auto_export.rs).
+//!
+//! A background task checks the schedule every `CHECK_INTERVAL`. When an
+//! export is due it goes into the export queue (see queue_commands.rs),
+//! so it waits for any manual export, shows in the queue, and raises the
+//! usual notification when it finishes; the next check records how it
+//! ended, from the job history if the job has left the queue.
+
+use chat_to_map_desktop::{
+    auto_export::{AutoExportState, CHECK_INTERVAL},
+    export::queue::JobStatus,
+    settings::Settings,
+};
+use chrono::Utc;
+use tauri::Manager;
+
+use crate::{queue_commands, settings_commands::app_local_data_dir, AppState};
+
+/// Check the schedule now and every `CHECK_INTERVAL` after
+pub(crate) fn start(app_handle: &tauri::AppHandle) {
+    let app_handle = app_handle.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            if let Err(e) = check(&app_handle) {
+                tracing::warn!("[auto-export] {e}");
+            }
+            tokio::time::sleep(CHECK_INTERVAL).await;
+        }
+    });
+}
+
+/// Record how the last run ended, then queue the next if it's due
+fn check(app_handle: &tauri::AppHandle) -> Result<(), String> {
+    let dir = app_local_data_dir(app_handle)?;
+    let settings = Settings::load(&dir);
+    let mut schedule = AutoExportState::load(&dir);
+    let state = app_handle.state::<AppState>();
+
+    if let Some(job_id) = &schedule.running_job_id {
+        let status = state.with_export_queue(|queue| {
+            queue
+                .jobs()
+                .iter()
+                .find(|job| &job.job_id == job_id)
+                .map(|job| job.status.clone())
+        });
+        match status {
+            Some(JobStatus::Queued | JobStatus::Running) => return Ok(()),
+            Some(JobStatus::Completed { .. }) => schedule.finished(true),
+            Some(JobStatus::Failed { .. } | JobStatus::Cancelled) => schedule.finished(false),
+            // Cleared from the queue, or lost to a restart
+            None => schedule.finished_from_history(&dir)?,
+        }
+        schedule.save(&dir)?;
+    }
+
+    let now = Utc::now();
+    if !schedule.is_due(&settings.auto_export, now) {
+        return Ok(());
+    }
+    let Some(request) = schedule.request(&settings) else {
+        return Ok(());
+    };
+    let window = app_handle
+        .get_webview_window("main")
+        .ok_or("No window to run the export in")?;
+    tracing::info!(
+        "[auto-export] Queueing an export since {:?}",
+        request.options.since
+    );
+    let job_id = queue_commands::enqueue(app_handle, &state, window.as_ref().window(), request);
+    schedule.started(job_id, now);
+    schedule.save(&dir)
+}
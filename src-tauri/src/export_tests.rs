@@ -0,0 +1,476 @@
+/*!
+ * Tests for export module
+ */
+
+use std::{collections::BTreeMap, fs::File, io::Read};
+
+use super::*;
+use crate::exclusions::ExclusionRule;
+use crate::test_fixtures::{ChatBuilder, HandleBuilder, MessageBuilder, TestIMessageDb};
+
+#[test]
+fn test_exported_message_serialization() {
+    let msg = ExportedMessage {
+        guid: String::new(),
+        timestamp: "2024-01-01T12:00:00+00:00".to_string(),
+        sender: "Alice".to_string(),
+        is_from_me: false,
+        text: "Hello world".to_string(),
+        truncated: false,
+        deleted: false,
+        metadata: None,
+        attachments: Vec::new(),
+        event: None,
+        urls: Vec::new(),
+        link_preview: None,
+    };
+
+    let json = serde_json::to_string(&msg).unwrap();
+    assert!(json.contains("Alice"));
+    assert!(json.contains("Hello world"));
+    assert!(!json.contains("truncated"));
+}
+
+/// Save a one-chat fixture database with a single "Shared with You" message
+pub(super) fn save_fixture_db(dir: &TempDir) -> (std::path::PathBuf, i32) {
+    let mut db = TestIMessageDb::new().unwrap();
+    let handle = db.handle(HandleBuilder::new("+15551234567")).unwrap();
+    let chat = db
+        .chat(ChatBuilder::new("iMessage;-;+15551234567"))
+        .unwrap();
+    db.message(
+        MessageBuilder::new()
+            .text("Stay here https://example.com/hotel")
+            .handle(handle)
+            .chat(chat)
+            .shared_with_you(),
+    )
+    .unwrap();
+    let db_path = dir.path().join("chat.db");
+    db.save_to(&db_path).unwrap();
+    (db_path, chat)
+}
+
+/// Read a file out of an export zip
+pub(super) fn read_zip_entry(zip_path: &std::path::Path, name: &str) -> String {
+    let mut archive = zip::ZipArchive::new(File::open(zip_path).unwrap()).unwrap();
+    let mut contents = String::new();
+    archive
+        .by_name(name)
+        .unwrap()
+        .read_to_string(&mut contents)
+        .unwrap();
+    contents
+}
+
+/// Read the first chat file listed in an export's manifest
+pub(super) fn read_first_chat(zip_path: &std::path::Path) -> String {
+    let manifest: serde_json::Value =
+        serde_json::from_str(&read_zip_entry(zip_path, "manifest.json")).unwrap();
+    read_zip_entry(
+        zip_path,
+        manifest["chat_files"][0]["file"].as_str().unwrap(),
+    )
+}
+
+#[test]
+fn test_export_includes_shared_links_when_enabled() {
+    let dir = TempDir::new().unwrap();
+    let (db_path, chat) = save_fixture_db(&dir);
+
+    let options = ExportOptions {
+        include_shared_links: true,
+        ..Default::default()
+    };
+    let result = export_chats(&[chat], &options, None, Some(&db_path)).unwrap();
+    assert_eq!(result.total_messages, 1);
+
+    let links_json = read_zip_entry(&result.zip_path, "shared_links.json");
+    assert!(links_json.contains("https://example.com/hotel"));
+}
+
+#[test]
+fn test_export_writes_metadata_to_manifest() {
+    let dir = TempDir::new().unwrap();
+    let (db_path, chat) = save_fixture_db(&dir);
+
+    let options = ExportOptions {
+        metadata: BTreeMap::from([("trip".to_string(), "Italy 2024".to_string())]),
+        ..Default::default()
+    };
+    let result = export_chats(&[chat], &options, None, Some(&db_path)).unwrap();
+
+    let manifest: serde_json::Value =
+        serde_json::from_str(&read_zip_entry(&result.zip_path, "manifest.json")).unwrap();
+    assert_eq!(manifest["metadata"]["trip"], "Italy 2024");
+    assert!(manifest.get("shared_link_count").is_none());
+}
+
+#[test]
+fn test_export_merges_chat_groups_into_one_conversation() {
+    let mut db = TestIMessageDb::new().unwrap();
+    let handle = db.handle(HandleBuilder::new("+15551234567")).unwrap();
+    let sms_chat = db
+        .chat(ChatBuilder::new("SMS;-;+15551234567").service("SMS"))
+        .unwrap();
+    let imessage_chat = db
+        .chat(ChatBuilder::new("+15551234567").guid("iMessage;-;+15551234567"))
+        .unwrap();
+    for (chat, date, text) in [(sms_chat, 100, "first"), (imessage_chat, 200, "second")] {
+        db.message(
+            MessageBuilder::new()
+                .text(text)
+                .handle(handle)
+                .chat(chat)
+                .date(date),
+        )
+        .unwrap();
+    }
+    let dir = TempDir::new().unwrap();
+    let db_path = dir.path().join("chat.db");
+    db.save_to(&db_path).unwrap();
+
+    let options = ExportOptions {
+        chat_groups: vec![vec![imessage_chat, sms_chat]],
+        ..Default::default()
+    };
+    let result = export_chats(&[imessage_chat], &options, None, Some(&db_path)).unwrap();
+    assert_eq!(result.chat_count, 1);
+
+    let chat: serde_json::Value = serde_json::from_str(&read_first_chat(&result.zip_path)).unwrap();
+    assert_eq!(chat["meta"]["message_count"], 2);
+    assert_eq!(chat["meta"]["service"], "iMessage + SMS");
+    assert_eq!(chat["messages"][0]["text"], "first");
+    assert_eq!(chat["messages"][1]["text"], "second");
+
+    // Named after the chat and the group's first (canonical) chat GUID
+    let manifest: serde_json::Value =
+        serde_json::from_str(&read_zip_entry(&result.zip_path, "manifest.json")).unwrap();
+    assert_eq!(
+        manifest["chat_files"][0]["file"],
+        "15551234567.iMessage-+15551234567.json"
+    );
+    assert_eq!(chat["meta"]["guid"], "iMessage;-;+15551234567");
+}
+
+#[test]
+fn test_export_skips_chats_matching_exclusion_rules() {
+    let mut db = TestIMessageDb::new().unwrap();
+    let bank = db.chat(ChatBuilder::new("72345")).unwrap();
+    let friend = db.chat(ChatBuilder::new("+15551234567")).unwrap();
+    for (chat, text) in [(bank, "Your code is 123456"), (friend, "Dinner?")] {
+        db.message(MessageBuilder::new().text(text).chat(chat))
+            .unwrap();
+    }
+    let dir = TempDir::new().unwrap();
+    let db_path = dir.path().join("chat.db");
+    db.save_to(&db_path).unwrap();
+
+    let options = ExportOptions {
+        exclusion_rules: vec![ExclusionRule::ShortCode],
+        ..Default::default()
+    };
+    let result = export_chats(&[bank, friend], &options, None, Some(&db_path)).unwrap();
+    assert_eq!(result.chat_count, 1);
+    assert_eq!(result.total_messages, 1);
+    let manifest: serde_json::Value =
+        serde_json::from_str(&read_zip_entry(&result.zip_path, "manifest.json")).unwrap();
+    assert_eq!(manifest["excluded_chat_count"], 1);
+
+    let error = export_chats(&[bank], &options, None, Some(&db_path)).unwrap_err();
+    assert!(error.to_string().contains("excluded"));
+}
+
+#[test]
+fn test_export_attributes_owner_accounts_to_me() {
+    let dir = TempDir::new().unwrap();
+    let (db, group_chat) = crate::test_fixtures::multi_account_scenario().unwrap();
+    let db_path = dir.path().join("chat.db");
+    db.save_to(&db_path).unwrap();
+
+    let result = export_chats(
+        &[group_chat],
+        &ExportOptions::default(),
+        None,
+        Some(&db_path),
+    );
+    let chat: ExportedChat =
+        serde_json::from_str(&read_first_chat(&result.unwrap().zip_path)).unwrap();
+
+    assert_eq!(chat.meta.participant_count, 1);
+    // The owner's work handle is "Me", not a second participant
+    assert_eq!(
+        chat.meta.participants,
+        [
+            ExportedParticipant {
+                name: "Me".to_string(),
+                identifier: String::new(),
+                is_me: true,
+            },
+            ExportedParticipant {
+                name: "+15551234567".to_string(),
+                identifier: "+15551234567".to_string(),
+                is_me: false,
+            },
+        ]
+    );
+    let message = &chat.messages[0];
+    assert_eq!(message.text, "Sent from my work account");
+    assert_eq!(message.sender, "Me");
+    assert!(message.is_from_me);
+}
+
+#[test]
+fn test_export_recovers_malformed_rows_with_warnings() {
+    let dir = TempDir::new().unwrap();
+    let mut db = TestIMessageDb::new().unwrap();
+    let handle = db.handle(HandleBuilder::new("+15551234567")).unwrap();
+    let chat = db
+        .chat(ChatBuilder::new("iMessage;-;+15551234567"))
+        .unwrap();
+    let handle_sql = handle.to_string();
+    db.message(
+        MessageBuilder::new()
+            .text("Fine")
+            .handle(handle)
+            .chat(chat)
+            .date(200_000_000_000),
+    )
+    .unwrap();
+    // "Caf\xE9" — Latin-1 bytes stored as TEXT
+    db.raw_message(
+        chat,
+        &[
+            ("text", "CAST(X'436166E9' AS TEXT)"),
+            ("handle_id", &handle_sql),
+            ("date", "100000000000"),
+        ],
+    )
+    .unwrap();
+    // Non-integer date makes the row fail to load at all
+    db.raw_message(chat, &[("text", "'Recovered'"), ("date", "'corrupt'")])
+        .unwrap();
+    // Garbage body with no plain text: warned, nothing to export
+    db.raw_message(chat, &[("attributedBody", "X'DEADBEEF'")])
+        .unwrap();
+    let db_path = dir.path().join("chat.db");
+    db.save_to(&db_path).unwrap();
+
+    let result = export_chats(&[chat], &ExportOptions::default(), None, Some(&db_path)).unwrap();
+
+    assert_eq!(result.warnings.len(), 3, "{:?}", result.warnings);
+    let exported: serde_json::Value =
+        serde_json::from_str(&read_first_chat(&result.zip_path)).unwrap();
+    let texts: Vec<&str> = exported["messages"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|m| m["text"].as_str().unwrap())
+        .collect();
+    // The lossily decoded byte's U+FFFD is dropped by the cleaning pass
+    assert_eq!(texts, vec!["Recovered", "Caf", "Fine"]);
+    // Recovered rows keep their GUIDs too
+    let guids: Vec<&str> = exported["messages"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|m| m["guid"].as_str().unwrap())
+        .collect();
+    assert_eq!(guids, vec!["msg-3", "msg-2", "msg-1"]);
+    let manifest: serde_json::Value =
+        serde_json::from_str(&read_zip_entry(&result.zip_path, "manifest.json")).unwrap();
+    assert_eq!(manifest["warning_count"], 3);
+}
+
+#[test]
+fn test_export_truncates_long_messages() {
+    let dir = TempDir::new().unwrap();
+    let (db_path, chat) = save_fixture_db(&dir);
+    let options = ExportOptions {
+        max_text_length: Some(9),
+        ..Default::default()
+    };
+
+    let result = export_chats(&[chat], &options, None, Some(&db_path)).unwrap();
+
+    assert_eq!(result.truncated_messages, 1);
+    let exported: serde_json::Value =
+        serde_json::from_str(&read_first_chat(&result.zip_path)).unwrap();
+    assert_eq!(exported["messages"][0]["text"], "Stay here");
+    assert_eq!(exported["messages"][0]["truncated"], true);
+    let manifest: serde_json::Value =
+        serde_json::from_str(&read_zip_entry(&result.zip_path, "manifest.json")).unwrap();
+    assert_eq!(manifest["max_text_length"], 9);
+    assert_eq!(manifest["truncated_message_count"], 1);
+}
+
+#[test]
+fn test_export_records_database_origin_in_manifest() {
+    let dir = TempDir::new().unwrap();
+    let (db_path, chat) = save_fixture_db(&dir);
+
+    let result = export_chats(&[chat], &ExportOptions::default(), None, Some(&db_path)).unwrap();
+
+    let manifest: serde_json::Value =
+        serde_json::from_str(&read_zip_entry(&result.zip_path, "manifest.json")).unwrap();
+    assert_eq!(manifest["database"]["min_macos_version"], 15);
+    assert!(manifest["database"]["features"]
+        .as_array()
+        .unwrap()
+        .contains(&"shared_with_you".into()));
+}
+
+#[test]
+fn test_export_keeps_only_messages_passing_the_filter() {
+    let mut db = TestIMessageDb::new().unwrap();
+    let mom = db.handle(HandleBuilder::new("+15551234567")).unwrap();
+    let chat = db
+        .chat(ChatBuilder::new("iMessage;-;+15551234567"))
+        .unwrap();
+    for (date, text, from_mom) in [
+        (100, "The house has a garden", true),
+        (200, "Lunch?", true),
+        (300, "Send me the house address", false),
+    ] {
+        let builder = MessageBuilder::new().text(text).chat(chat).date(date);
+        let builder = if from_mom {
+            builder.handle(mom)
+        } else {
+            builder.from_me()
+        };
+        db.message(builder).unwrap();
+    }
+    let dir = TempDir::new().unwrap();
+    let db_path = dir.path().join("chat.db");
+    db.save_to(&db_path).unwrap();
+
+    let options = ExportOptions {
+        filter: filters::MessageFilter {
+            include_senders: vec!["+15551234567".to_string()],
+            keyword: Some("house".to_string()),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let result = export_chats(&[chat], &options, None, Some(&db_path)).unwrap();
+
+    assert_eq!(result.total_messages, 1);
+    let exported: ExportedChat = serde_json::from_str(&read_first_chat(&result.zip_path)).unwrap();
+    let texts: Vec<&str> = exported.messages.iter().map(|m| m.text.as_str()).collect();
+    assert_eq!(texts, ["The house has a garden"]);
+    let manifest: serde_json::Value =
+        serde_json::from_str(&read_zip_entry(&result.zip_path, "manifest.json")).unwrap();
+    assert_eq!(manifest["filters"]["include_sender_count"], 1);
+    assert_eq!(manifest["filters"]["keyword"], true);
+    assert_eq!(manifest["filters"]["filtered_message_count"], 2);
+}
+
+#[test]
+fn test_export_splits_large_chats_into_parts() {
+    let mut db = TestIMessageDb::new().unwrap();
+    let chat = db
+        .chat(ChatBuilder::new("iMessage;-;+15551234567"))
+        .unwrap();
+    for n in 1..=5 {
+        let text = format!("Message {n}");
+        db.message(MessageBuilder::new().text(&text).chat(chat).date(n * 100))
+            .unwrap();
+    }
+    let dir = TempDir::new().unwrap();
+    let db_path = dir.path().join("chat.db");
+    db.save_to(&db_path).unwrap();
+    let options = ExportOptions {
+        max_messages_per_file: Some(2),
+        ..Default::default()
+    };
+
+    let result = export_chats(&[chat], &options, None, Some(&db_path)).unwrap();
+
+    let manifest: serde_json::Value =
+        serde_json::from_str(&read_zip_entry(&result.zip_path, "manifest.json")).unwrap();
+    let entry = &manifest["chat_files"][0];
+    assert!(entry.get("file").is_none());
+    assert_eq!(entry["message_count"], 5);
+    let parts = entry["parts"].as_array().unwrap();
+    let counts: Vec<u64> = parts
+        .iter()
+        .map(|part| part["message_count"].as_u64().unwrap())
+        .collect();
+    assert_eq!(counts, [2, 2, 1]);
+    let last: ExportedChat = serde_json::from_str(&read_zip_entry(
+        &result.zip_path,
+        parts[2]["file"].as_str().unwrap(),
+    ))
+    .unwrap();
+    assert!(parts[2]["file"].as_str().unwrap().ends_with(".part3.json"));
+    assert_eq!(last.messages[0].text, "Message 5");
+    assert_eq!(
+        parts[2]["first_message_at"],
+        last.messages[0].timestamp.as_str()
+    );
+    assert!(manifest["checksums"][parts[0]["file"].as_str().unwrap()].is_string());
+
+    // Reading the export back joins the parts
+    let contents = diff::read_archive(&result.zip_path).unwrap();
+    let texts: Vec<&str> = contents.chats[0]
+        .messages
+        .iter()
+        .map(|m| m.text.as_str())
+        .collect();
+    assert_eq!(
+        texts,
+        [
+            "Message 1",
+            "Message 2",
+            "Message 3",
+            "Message 4",
+            "Message 5"
+        ]
+    );
+}
+
+#[test]
+fn test_export_places_orphaned_messages_back_in_their_chat() {
+    let mut db = TestIMessageDb::new().unwrap();
+    let handle = db.handle(HandleBuilder::new("+15551234567")).unwrap();
+    let chat = db
+        .chat(ChatBuilder::new("iMessage;-;+15551234567"))
+        .unwrap();
+    let date = 700_000_000_000_000_000;
+    db.message(
+        MessageBuilder::new()
+            .text("Joined")
+            .handle(handle)
+            .chat(chat)
+            .date(date),
+    )
+    .unwrap();
+    // No join rows: one from the same handle a minute later, one sent
+    // without a handle
+    let minute = 60_000_000_000;
+    db.message(
+        MessageBuilder::new()
+            .text("Orphan")
+            .handle(handle)
+            .date(date + minute),
+    )
+    .unwrap();
+    db.message(MessageBuilder::new().text("Lost").from_me().date(date))
+        .unwrap();
+    let dir = TempDir::new().unwrap();
+    let db_path = dir.path().join("chat.db");
+    db.save_to(&db_path).unwrap();
+
+    let result = export_chats(&[chat], &ExportOptions::default(), None, Some(&db_path)).unwrap();
+
+    assert_eq!(result.total_messages, 2);
+    assert_eq!(result.orphans.recovered, 1);
+    assert_eq!(result.orphans.unassignable, 1);
+    let exported: ExportedChat = serde_json::from_str(&read_first_chat(&result.zip_path)).unwrap();
+    let texts: Vec<&str> = exported.messages.iter().map(|m| m.text.as_str()).collect();
+    assert_eq!(texts, ["Joined", "Orphan"]);
+    let manifest: serde_json::Value =
+        serde_json::from_str(&read_zip_entry(&result.zip_path, "manifest.json")).unwrap();
+    assert_eq!(manifest["orphaned_messages"]["recovered"], 1);
+    assert_eq!(manifest["orphaned_messages"]["unassignable"], 1);
+}
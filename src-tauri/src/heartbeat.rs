@@ -0,0 +1,281 @@
+/*!
+ * Heartbeat monitor for long-running operations.
+ *
+ * Every progress update is a heartbeat. If a stage goes longer than its
+ * threshold without one (SQLite locked, network hung), the monitor reports
+ * a [`StallEvent`] carrying the stage and the last thing it did, so the UI
+ * can explain a frozen progress bar. Stages that are safe to repeat, such
+ * as presign and upload (nothing is committed until `complete_upload`), are
+ * run through [`retry_on_stall`], which abandons a stalled attempt and
+ * starts over.
+ */
+
+use std::{
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+/// Default time without progress before a stage counts as stalled
+pub const DEFAULT_STALL_THRESHOLD: Duration = Duration::from_secs(30);
+
+/// How often the monitor checks for stalls
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Reported when a stage makes no progress for longer than its threshold
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StallEvent {
+    /// Stage that stopped making progress, e.g. "Uploading"
+    pub stage: String,
+    /// Message from the last heartbeat
+    pub last_activity: String,
+    /// Seconds since the last heartbeat
+    pub idle_secs: u64,
+    /// Whether the stage will be retried automatically
+    pub will_retry: bool,
+}
+
+/// How stall detection treats a stage
+#[derive(Debug, Clone, Copy)]
+pub struct StagePolicy {
+    /// Time without a heartbeat before the stage counts as stalled
+    pub threshold: Duration,
+    /// Whether a stalled attempt may be abandoned and re-run
+    pub retryable: bool,
+}
+
+impl Default for StagePolicy {
+    fn default() -> Self {
+        Self {
+            threshold: DEFAULT_STALL_THRESHOLD,
+            retryable: false,
+        }
+    }
+}
+
+struct Activity {
+    stage: String,
+    message: String,
+    policy: StagePolicy,
+    last_beat: Instant,
+    /// Set once the current stall has been reported, cleared by the next beat
+    reported: bool,
+}
+
+/// Shared record of the pipeline's latest activity; clones share state
+#[derive(Clone)]
+pub struct Heartbeat {
+    activity: Arc<Mutex<Activity>>,
+}
+
+impl Heartbeat {
+    pub fn new(stage: &str, policy: StagePolicy) -> Self {
+        Self {
+            activity: Arc::new(Mutex::new(Activity {
+                stage: stage.to_string(),
+                message: String::new(),
+                policy,
+                last_beat: Instant::now(),
+                reported: false,
+            })),
+        }
+    }
+
+    /// Enter a new stage (counts as a heartbeat)
+    pub fn begin_stage(&self, stage: &str, message: &str, policy: StagePolicy) {
+        let mut activity = self.activity.lock().unwrap();
+        activity.stage = stage.to_string();
+        activity.policy = policy;
+        drop(activity);
+        self.beat(message);
+    }
+
+    /// Record progress in the current stage
+    pub fn beat(&self, message: &str) {
+        let mut activity = self.activity.lock().unwrap();
+        activity.message = message.to_string();
+        activity.last_beat = Instant::now();
+        activity.reported = false;
+    }
+
+    /// A stall event, reported once per stall, if the current stage is stalled
+    pub fn check(&self) -> Option<StallEvent> {
+        self.check_at(Instant::now())
+    }
+
+    fn check_at(&self, now: Instant) -> Option<StallEvent> {
+        let mut activity = self.activity.lock().unwrap();
+        let idle = now.saturating_duration_since(activity.last_beat);
+        if activity.reported || idle <= activity.policy.threshold {
+            return None;
+        }
+        activity.reported = true;
+        Some(StallEvent {
+            stage: activity.stage.clone(),
+            last_activity: activity.message.clone(),
+            idle_secs: idle.as_secs(),
+            will_retry: activity.policy.retryable,
+        })
+    }
+
+    /// Time since the last heartbeat, if past the current stage's threshold.
+    /// Trails the monitor by one poll so a stall is reported before a retry
+    /// resets it.
+    fn overdue(&self) -> Option<Duration> {
+        let activity = self.activity.lock().unwrap();
+        let idle = activity.last_beat.elapsed();
+        (idle > activity.policy.threshold + POLL_INTERVAL).then_some(idle)
+    }
+
+    /// Call `on_stall` for each stall until the returned monitor is dropped
+    pub fn spawn_monitor(&self, on_stall: impl Fn(StallEvent) + Send + 'static) -> StallMonitor {
+        let heartbeat = self.clone();
+        StallMonitor(tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(POLL_INTERVAL).await;
+                if let Some(event) = heartbeat.check() {
+                    tracing::warn!(
+                        "[heartbeat] {} stalled for {}s after: {}",
+                        event.stage,
+                        event.idle_secs,
+                        event.last_activity
+                    );
+                    on_stall(event);
+                }
+            }
+        }))
+    }
+}
+
+/// Background stall check started by [`Heartbeat::spawn_monitor`]; stops
+/// when dropped, so early returns can't leave it running
+pub struct StallMonitor(tokio::task::JoinHandle<()>);
+
+impl Drop for StallMonitor {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// Run a repeatable stage, abandoning and restarting attempts that stall.
+///
+/// Each attempt starts the stage afresh on `heartbeat`; the attempt itself
+/// should call [`Heartbeat::beat`] (directly or via progress callbacks) as
+/// it makes progress. Gives up after `max_retries` stalled retries.
+pub async fn retry_on_stall<T, E, F, Fut>(
+    heartbeat: &Heartbeat,
+    stage: &str,
+    policy: StagePolicy,
+    max_retries: u32,
+    mut attempt: F,
+) -> Result<T, E>
+where
+    E: From<String>,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    for retry in 0..=max_retries {
+        let policy = StagePolicy {
+            retryable: retry < max_retries,
+            ..policy
+        };
+        heartbeat.begin_stage(stage, "Starting...", policy);
+        tokio::select! {
+            result = attempt() => return result,
+            idle = wait_until_overdue(heartbeat) => {
+                if retry == max_retries {
+                    return Err(format!(
+                        "{stage} stalled: no progress for {}s",
+                        idle.as_secs()
+                    )
+                    .into());
+                }
+                tracing::warn!("[heartbeat] Retrying stalled {stage} (retry {})", retry + 1);
+            }
+        }
+    }
+    unreachable!("the final attempt always returns")
+}
+
+async fn wait_until_overdue(heartbeat: &Heartbeat) -> Duration {
+    loop {
+        tokio::time::sleep(POLL_INTERVAL).await;
+        if let Some(idle) = heartbeat.overdue() {
+            return idle;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use super::*;
+
+    fn policy(threshold: Duration) -> StagePolicy {
+        StagePolicy {
+            threshold,
+            retryable: false,
+        }
+    }
+
+    #[test]
+    fn stall_is_reported_once_until_the_next_beat() {
+        let heartbeat = Heartbeat::new("Exporting", policy(Duration::from_secs(10)));
+        heartbeat.beat("Processed 100 of 500 messages...");
+        let start = Instant::now();
+
+        assert_eq!(heartbeat.check_at(start + Duration::from_secs(5)), None);
+        let stall = heartbeat.check_at(start + Duration::from_secs(11)).unwrap();
+        assert_eq!(stall.stage, "Exporting");
+        assert_eq!(stall.last_activity, "Processed 100 of 500 messages...");
+        assert!(!stall.will_retry);
+        assert_eq!(heartbeat.check_at(start + Duration::from_secs(20)), None);
+
+        heartbeat.beat("Processed 200 of 500 messages...");
+        let later = Instant::now() + Duration::from_secs(11);
+        assert!(heartbeat.check_at(later).is_some());
+    }
+
+    #[tokio::test]
+    async fn stalled_attempts_are_retried_until_one_finishes() {
+        let heartbeat = Heartbeat::new("Uploading", StagePolicy::default());
+        let attempts = AtomicU32::new(0);
+
+        let result: Result<_, String> = retry_on_stall(
+            &heartbeat,
+            "Uploading",
+            policy(Duration::from_millis(100)),
+            2,
+            || async {
+                if attempts.fetch_add(1, Ordering::SeqCst) == 0 {
+                    // First attempt hangs without a heartbeat
+                    std::future::pending::<()>().await;
+                }
+                Ok("storage-id")
+            },
+        )
+        .await;
+
+        assert_eq!(result, Ok("storage-id"));
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_retries() {
+        let heartbeat = Heartbeat::new("Uploading", StagePolicy::default());
+
+        let result: Result<(), String> = retry_on_stall(
+            &heartbeat,
+            "Uploading",
+            policy(Duration::from_millis(50)),
+            1,
+            std::future::pending,
+        )
+        .await;
+
+        assert!(result.unwrap_err().contains("Uploading stalled"));
+    }
+}
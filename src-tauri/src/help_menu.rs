@@ -0,0 +1,86 @@
+//! The app's menus: the app menu's Preferences..., and Help for
+//! licenses, logs, diagnostics and updates.
+//!
+//! Preferences... emits `open-preferences` for the frontend to show its
+//! preferences window, which reads and saves them with `get_preferences` /
+//! `set_preferences` (see settings_commands.rs).
+
+use tauri::{
+    menu::{MenuBuilder, MenuEvent, MenuItemBuilder, SubmenuBuilder},
+    Emitter, Manager,
+};
+
+use crate::{create_diagnostics_bundle, open_licenses, open_logs_folder, update_commands};
+
+/// Build the app and Help menus and set them as the menu bar
+pub(crate) fn build(app: &tauri::App) -> tauri::Result<()> {
+    let preferences_item = MenuItemBuilder::new("Preferences...")
+        .id("open_preferences")
+        .accelerator("CmdOrCtrl+,")
+        .build(app)?;
+
+    let app_menu = SubmenuBuilder::new(app, "ChatToMap")
+        .item(&preferences_item)
+        .separator()
+        .quit()
+        .build()?;
+
+    let licenses_item = MenuItemBuilder::new("Open Source Licenses")
+        .id("open_licenses")
+        .build(app)?;
+
+    let logs_item = MenuItemBuilder::new("Open Logs Folder")
+        .id("open_logs_folder")
+        .build(app)?;
+
+    let diagnostics_item = MenuItemBuilder::new("Create Diagnostics Bundle")
+        .id("create_diagnostics_bundle")
+        .build(app)?;
+
+    let updates_item = MenuItemBuilder::new("Check for Updates...")
+        .id("check_for_updates")
+        .build(app)?;
+
+    let help_menu = SubmenuBuilder::new(app, "Help")
+        .item(&licenses_item)
+        .item(&logs_item)
+        .item(&diagnostics_item)
+        .separator()
+        .item(&updates_item)
+        .build()?;
+
+    app.set_menu(
+        MenuBuilder::new(app)
+            .item(&app_menu)
+            .item(&help_menu)
+            .build()?,
+    )?;
+    Ok(())
+}
+
+pub(crate) fn on_menu_event(app: &tauri::AppHandle, event: MenuEvent) {
+    match event.id().as_ref() {
+        "open_preferences" => {
+            if let Err(e) = app.emit("open-preferences", ()) {
+                tracing::error!("Failed to open preferences: {e}");
+            }
+        }
+        "open_licenses" => {
+            if let Err(e) = open_licenses() {
+                tracing::error!("Failed to open licenses: {e}");
+            }
+        }
+        "open_logs_folder" => {
+            if let Err(e) = open_logs_folder(app.clone()) {
+                tracing::error!("Failed to open logs folder: {e}");
+            }
+        }
+        "create_diagnostics_bundle" => {
+            if let Err(e) = create_diagnostics_bundle(None, app.clone(), app.state()) {
+                tracing::error!("Failed to create diagnostics bundle: {e}");
+            }
+        }
+        "check_for_updates" => update_commands::check_from_menu(app),
+        _ => {}
+    }
+}
@@ -0,0 +1,88 @@
+/*!
+ * One identity per person across their handles.
+ *
+ * Messages gives a handle per identifier and service, and links a person's
+ * handles with `handle.person_centric_id`; `Handle::cache` reports each
+ * linked handle as all of the person's identifiers, space-separated
+ * (`"+15551234567 sam@example.com"`). `Handle::dedupe` only groups handles
+ * whose strings are identical, so the person's SMS handle without a
+ * `person_centric_id`, or a second set of handles under another
+ * `person_centric_id`, stayed a different participant. [`dedupe_handles`]
+ * groups every handle that shares any identifier with another.
+ */
+
+use std::collections::{hash_map::Entry, HashMap};
+
+/// Map each handle ROWID to a person ID, shared by handles with a common
+/// identifier
+///
+/// Like `Handle::dedupe`, person IDs count up from 0 in ROWID order, so they
+/// are stable across runs while the handle table is unchanged.
+pub fn dedupe_handles(handles: &HashMap<i32, String>) -> HashMap<i32, i32> {
+    let mut rowids: Vec<i32> = handles.keys().copied().collect();
+    rowids.sort_unstable();
+
+    // Union-find over positions in `rowids`
+    let mut parents: Vec<usize> = (0..rowids.len()).collect();
+    let mut first_with: HashMap<String, usize> = HashMap::new();
+    for (position, rowid) in rowids.iter().enumerate() {
+        for identifier in handles[rowid].split_whitespace() {
+            match first_with.entry(identifier.to_lowercase()) {
+                Entry::Occupied(first) => union(&mut parents, *first.get(), position),
+                Entry::Vacant(slot) => {
+                    slot.insert(position);
+                }
+            }
+        }
+    }
+
+    let mut person_ids: HashMap<usize, i32> = HashMap::new();
+    let mut deduped = HashMap::with_capacity(rowids.len());
+    for (position, rowid) in rowids.iter().enumerate() {
+        let root = find(&mut parents, position);
+        let next_id = person_ids.len() as i32;
+        deduped.insert(*rowid, *person_ids.entry(root).or_insert(next_id));
+    }
+    deduped
+}
+
+fn find(parents: &mut [usize], mut position: usize) -> usize {
+    while parents[position] != position {
+        parents[position] = parents[parents[position]];
+        position = parents[position];
+    }
+    position
+}
+
+/// Join two groups under the one seen first, so IDs follow ROWID order
+fn union(parents: &mut [usize], a: usize, b: usize) {
+    let (a, b) = (find(parents, a), find(parents, b));
+    parents[a.max(b)] = a.min(b);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn handles_sharing_an_identifier_are_one_person() {
+        let handles = HashMap::from([
+            (0, "Me".to_string()),
+            // iMessage handles linked by person_centric_id
+            (1, "+15551234567 sam@example.com".to_string()),
+            (2, "+15551234567 sam@example.com".to_string()),
+            (3, "alex@example.com".to_string()),
+            // Sam's SMS handle, without a person_centric_id
+            (4, "+15551234567".to_string()),
+            // Sam under a second person_centric_id
+            (5, "Sam@Example.com sam.work@example.com".to_string()),
+        ]);
+
+        let deduped = dedupe_handles(&handles);
+        assert_eq!(deduped[&0], 0);
+        for sam in [1, 2, 4, 5] {
+            assert_eq!(deduped[&sam], 1);
+        }
+        assert_eq!(deduped[&3], 2);
+    }
+}
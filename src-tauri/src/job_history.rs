@@ -0,0 +1,192 @@
+/*!
+ * Local history of export runs.
+ *
+ * The app appends one JSON line per finished run (completed, failed or
+ * cancelled) to `<app_local_data_dir>/job_history.jsonl`, with the run's
+ * [`ExportMetrics`] when it produced an archive. `ctm-cli jobs` reads the
+ * same file, so throughput can be compared across runs and app versions.
+ */
+
+use std::{
+    fs::OpenOptions,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::export::{metrics::ExportMetrics, queue::JobStatus};
+
+const HISTORY_FILENAME: &str = "job_history.jsonl";
+
+/// Bundle identifier from tauri.conf.json; names the app's data directory
+const APP_IDENTIFIER: &str = "com.chattomap.desktop";
+
+/// One finished export run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JobRecord {
+    pub export_id: String,
+    /// RFC 3339 timestamp of when the run ended
+    pub finished_at: String,
+    /// App version that ran the export
+    pub app_version: String,
+    #[serde(flatten)]
+    pub status: JobStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metrics: Option<ExportMetrics>,
+}
+
+impl JobRecord {
+    /// A record for a run that just ended
+    pub fn new(export_id: &str, status: JobStatus, metrics: Option<ExportMetrics>) -> Self {
+        Self {
+            export_id: export_id.to_string(),
+            finished_at: chrono::Utc::now().to_rfc3339(),
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            status,
+            metrics,
+        }
+    }
+}
+
+/// The desktop app's local data directory (what Tauri's
+/// `app_local_data_dir` resolves to), for tools running outside the app
+pub fn default_data_dir() -> Option<PathBuf> {
+    dirs::data_local_dir().map(|dir| dir.join(APP_IDENTIFIER))
+}
+
+/// Append `record` to the history in `app_local_data_dir`
+pub fn append(app_local_data_dir: &Path, record: &JobRecord) -> Result<(), String> {
+    append_line(&app_local_data_dir.join(HISTORY_FILENAME), record)
+}
+
+/// All recorded runs, oldest first. A missing file is an empty history;
+/// unreadable lines (say, from a newer app version) are skipped.
+pub fn load(app_local_data_dir: &Path) -> Result<Vec<JobRecord>, String> {
+    read_lines(&app_local_data_dir.join(HISTORY_FILENAME))
+}
+
+/// Append `record` as a JSON line to `path`, creating it (and its
+/// directory) if needed
+pub(crate) fn append_line<T: Serialize>(path: &Path, record: &T) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {dir:?}: {e}"))?;
+    }
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| format!("Failed to open {path:?}: {e}"))?;
+    let line =
+        serde_json::to_string(record).map_err(|e| format!("Failed to encode record: {e}"))?;
+    writeln!(file, "{line}").map_err(|e| format!("Failed to write {path:?}: {e}"))
+}
+
+/// The JSON lines of `path` that parse as `T`, in order; none if it's
+/// missing
+pub(crate) fn read_lines<T: DeserializeOwned>(path: &Path) -> Result<Vec<T>, String> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("Failed to read {path:?}: {e}")),
+    };
+    Ok(contents
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+/// The last `limit` runs (all if `None`) from `data_dir`, defaulting to
+/// the app's own directory
+pub fn load_recent(
+    data_dir: Option<&Path>,
+    limit: Option<usize>,
+) -> Result<Vec<JobRecord>, String> {
+    let mut records = match data_dir {
+        Some(dir) => load(dir)?,
+        None => load(&default_data_dir().ok_or("Could not locate the app data directory")?)?,
+    };
+    if let Some(limit) = limit {
+        records.drain(..records.len().saturating_sub(limit));
+    }
+    Ok(records)
+}
+
+/// Render `records` one per line, with throughput columns if `show_metrics`
+pub fn to_text(records: &[JobRecord], show_metrics: bool) -> String {
+    if records.is_empty() {
+        return "No export runs recorded yet\n".to_string();
+    }
+    let mut out = String::new();
+    for record in records {
+        let status = match &record.status {
+            JobStatus::Completed { .. } => "completed".to_string(),
+            JobStatus::Failed { error } => format!("failed: {error}"),
+            JobStatus::Cancelled => "cancelled".to_string(),
+            JobStatus::Queued | JobStatus::Running => "unfinished".to_string(),
+        };
+        out.push_str(&format!(
+            "{}  v{}  {}  {}\n",
+            record.finished_at, record.app_version, record.export_id, status
+        ));
+        if show_metrics {
+            if let Some(metrics) = &record.metrics {
+                out.push_str(&format!(
+                    "    {} messages, {} bytes: {}\n",
+                    metrics.messages,
+                    metrics.archive_bytes,
+                    metrics.summary()
+                ));
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn history_round_trips_and_skips_unreadable_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(load(dir.path()).unwrap().is_empty());
+
+        let mut metrics = ExportMetrics::compressed(500, 1024 * 1024, Duration::from_secs(1));
+        metrics.record_decode(Duration::from_secs(1));
+        let completed = JobRecord::new(
+            "run-1",
+            JobStatus::Completed { results_url: None },
+            Some(metrics),
+        );
+        let failed = JobRecord::new(
+            "run-2",
+            JobStatus::Failed {
+                error: "Upload failed".to_string(),
+            },
+            None,
+        );
+        append(dir.path(), &completed).unwrap();
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(dir.path().join(HISTORY_FILENAME))
+            .unwrap()
+            .write_all(b"not json\n")
+            .unwrap();
+        append(dir.path(), &failed).unwrap();
+
+        let records = load(dir.path()).unwrap();
+        assert_eq!(records, vec![completed, failed.clone()]);
+        assert_eq!(
+            load_recent(Some(dir.path()), Some(1)).unwrap(),
+            vec![failed]
+        );
+
+        let text = to_text(&records, true);
+        assert!(text.contains("run-1  completed\n    500 messages, 1048576 bytes: 500 msg/s"));
+        assert!(text.contains("run-2  failed: Upload failed\n"));
+        assert!(!to_text(&records, false).contains("msg/s"));
+    }
+}
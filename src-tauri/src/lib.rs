@@ -5,26 +5,63 @@
  * the desktop app (Tauri) and the CLI debugging tool.
  */
 
+pub mod access_tokens;
+pub mod accounts;
+pub mod analysis;
 pub mod api;
+pub mod app_core;
+pub mod app_info;
+pub mod auto_export;
+pub mod automated;
+pub mod business_names;
+pub mod chat_list;
+pub mod chat_list_cache;
+pub mod chat_merge;
+pub mod chat_names;
+pub mod contact_avatar;
 pub mod contacts;
+pub mod contacts_access;
+pub mod db_busy;
+pub mod db_discovery;
+pub mod db_origin;
+pub mod db_snapshot;
+pub mod db_sources;
+pub mod deep_link;
+pub mod diagnostics;
+pub mod exclusions;
 pub mod export;
+pub mod heartbeat;
+pub mod identities;
+pub mod job_history;
+pub mod logging;
+pub mod login_item;
+pub mod outbox;
+pub mod permissions;
+pub mod person_search;
 pub mod screenshot;
+pub mod search;
+pub mod settings;
+pub mod shared_links;
+pub mod shared_names;
+pub mod sources;
+pub mod sql_query;
+pub mod telemetry;
+pub mod updates;
 pub mod upload;
+pub mod upload_history;
+pub mod validation;
 
 #[cfg(test)]
 pub mod test_fixtures;
 
 use std::collections::HashMap;
 
-use contacts::{ContactsIndex, Name};
+use chat_names::{resolve_chat_display_name, resolve_participant_names};
+use contacts::ContactsIndex;
+use db_busy::{retry_on_busy, BusyError};
 use imessage_database::{
-    tables::{
-        chat::Chat,
-        chat_handle::ChatToHandle,
-        handle::Handle,
-        table::{get_connection, Cacheable, Deduplicate},
-    },
-    util::dirs::default_db_path,
+    tables::{chat::Chat, chat_handle::ChatToHandle, handle::Handle, table::Cacheable},
+    util::streamtyped,
 };
 use serde::{Deserialize, Serialize};
 
@@ -32,154 +69,222 @@ use serde::{Deserialize, Serialize};
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChatInfo {
     pub id: i32,
+    /// Every chat ROWID behind this entry; more than one when duplicate
+    /// chats for the same people were merged (see `chat_merge`)
+    pub chat_ids: Vec<i32>,
     /// Resolved contact name or fallback to identifier
     pub display_name: String,
     /// Raw identifier (phone number, email, or group chat ID)
     pub chat_identifier: String,
     pub service: String,
     pub participant_count: usize,
+    /// Resolved participant names (capped, with a trailing "+N more")
+    pub participants: Vec<String>,
     pub message_count: usize,
+    /// ISO 8601 date of the most recent message (empty if the chat has none)
+    pub last_message_date: String,
+    /// Snippet of the most recent message's text
+    pub last_message_preview: Option<String>,
+    /// Matches an exclusion rule; shown in the list but never exported
+    pub excluded: bool,
+    /// Likely a short code, no-reply sender or other one-way sender (see
+    /// `automated`)
+    pub automated: bool,
 }
 
+/// A step of [`list_chats_with_progress`], reported when it finishes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChatListStage {
+    /// The contacts index is built
+    ContactsLoaded,
+    /// Chats, handles and participants are read
+    ChatsLoaded,
+    /// Message counts and last messages are computed
+    StatsComputed,
+}
+
+/// Maximum length (in characters) of `ChatInfo::last_message_preview`
+const PREVIEW_MAX_CHARS: usize = 80;
+
 /// Chat statistics (message count and last message timestamp)
 struct ChatStats {
     message_count: usize,
+    /// Messages from the owner
+    sent_count: usize,
     last_message_date: i64,
+    last_message_preview: Option<String>,
 }
 
-/// Get message counts and last message date per chat using custom SQL
-fn get_chat_stats(
-    db: &rusqlite::Connection,
-) -> Result<HashMap<i32, ChatStats>, imessage_database::error::table::TableError> {
-    let mut stats = HashMap::new();
-
-    let mut stmt = db.prepare(
-        "SELECT cmj.chat_id, COUNT(*) as count, MAX(m.date) as last_date
-         FROM chat_message_join cmj
-         JOIN message m ON cmj.message_id = m.ROWID
-         GROUP BY cmj.chat_id",
-    )?;
-
-    let rows = stmt.query_map([], |row| {
-        Ok((
-            row.get::<_, i32>(0)?,
-            row.get::<_, usize>(1)?,
-            row.get::<_, i64>(2).unwrap_or(0),
-        ))
-    })?;
-
-    for (chat_id, count, last_date) in rows.flatten() {
-        stats.insert(
-            chat_id,
-            ChatStats {
-                message_count: count,
-                last_message_date: last_date,
-            },
-        );
-    }
+/// Get message counts, last message date and preview per chat using custom SQL
+fn get_chat_stats(db: &rusqlite::Connection) -> rusqlite::Result<HashMap<i32, ChatStats>> {
+    retry_on_busy(|| {
+        let mut stats = HashMap::new();
+
+        // With a single MAX() aggregate, SQLite takes the bare `text` and
+        // `attributedBody` columns from the row holding the max date.
+        let mut stmt = db.prepare(
+            "SELECT cmj.chat_id, COUNT(*) as count, MAX(m.date) as last_date,
+                    m.text, m.attributedBody, COALESCE(SUM(m.is_from_me), 0) as sent
+             FROM chat_message_join cmj
+             JOIN message m ON cmj.message_id = m.ROWID
+             GROUP BY cmj.chat_id",
+        )?;
+
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, i32>(0)?,
+                row.get::<_, usize>(1)?,
+                row.get::<_, i64>(2).unwrap_or(0),
+                row.get::<_, Option<String>>(3).unwrap_or(None),
+                row.get::<_, Option<Vec<u8>>>(4).unwrap_or(None),
+                row.get::<_, usize>(5).unwrap_or(0),
+            ))
+        })?;
+
+        for row in rows {
+            // A lock ends the query early, so run it again; skip bad rows
+            let (chat_id, count, last_date, text, body, sent) = match row {
+                Ok(row) => row,
+                Err(e) if e.is_busy() => return Err(e),
+                Err(_) => continue,
+            };
+            stats.insert(
+                chat_id,
+                ChatStats {
+                    message_count: count,
+                    sent_count: sent,
+                    last_message_date: last_date,
+                    last_message_preview: message_preview(text, body),
+                },
+            );
+        }
 
-    Ok(stats)
+        Ok(stats)
+    })
 }
 
-/// Resolve a display name for a chat, using contacts if available
-pub fn resolve_chat_display_name(
-    chat: &Chat,
-    chat_participants: Option<&std::collections::BTreeSet<i32>>,
-    participants_map: &HashMap<i32, Name>,
-    deduped_handles: &HashMap<i32, i32>,
-) -> String {
-    // If chat has a custom display_name, use it
-    if let Some(name) = chat.display_name.as_ref() {
-        if !name.is_empty() {
-            return name.clone();
-        }
+/// Build a single-line preview from a message's text, falling back to the
+/// `attributedBody` blob that macOS 13+ uses instead of `text`
+fn message_preview(text: Option<String>, attributed_body: Option<Vec<u8>>) -> Option<String> {
+    let text = text
+        .filter(|t| !t.trim().is_empty())
+        .or_else(|| attributed_body.and_then(|body| streamtyped::parse(body).ok()))?;
+
+    // Drop attachment placeholders (U+FFFC) and collapse whitespace
+    let cleaned = text
+        .replace('\u{FFFC}', " ")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    if cleaned.is_empty() {
+        return None;
     }
 
-    // For 1:1 chats, try to resolve the participant's name
-    if let Some(participant_ids) = chat_participants {
-        if participant_ids.len() == 1 {
-            if let Some(&handle_id) = participant_ids.iter().next() {
-                // FIX: Translate handle_id to deduped_id before lookup
-                if let Some(&deduped_id) = deduped_handles.get(&handle_id) {
-                    if let Some(name) = participants_map.get(&deduped_id) {
-                        let display = name.get_display_name();
-                        if !display.is_empty() {
-                            return display.to_string();
-                        }
-                    }
-                }
-            }
-        }
+    if cleaned.chars().count() <= PREVIEW_MAX_CHARS {
+        return Some(cleaned);
     }
-
-    // Fallback to chat_identifier
-    chat.chat_identifier.clone()
+    let truncated: String = cleaned.chars().take(PREVIEW_MAX_CHARS - 1).collect();
+    Some(format!("{}…", truncated.trim_end()))
 }
 
 /// List available iMessage chats
-/// If custom_db_path is provided, uses that instead of the default ~/Library/Messages/chat.db
-pub fn list_chats(custom_db_path: Option<&std::path::Path>) -> Result<Vec<ChatInfo>, String> {
-    eprintln!("[list_chats] Starting...");
-
-    // Get database path
-    let db_path = custom_db_path
-        .map(|p| p.to_path_buf())
-        .unwrap_or_else(default_db_path);
-    eprintln!("[list_chats] DB path: {:?}", db_path);
+/// If custom_db_path is provided, uses that instead of the default ~/Library/Messages/chat.db.
+/// Chats matching any of `exclusion_rules` are flagged `excluded`, and
+/// likely automated senders `automated`.
+pub fn list_chats(
+    custom_db_path: Option<&std::path::Path>,
+    exclusion_rules: &[exclusions::ExclusionRule],
+) -> Result<Vec<ChatInfo>, String> {
+    list_chats_with_progress(custom_db_path, exclusion_rules, |_| {})
+}
 
-    // Connect to database
-    let db = get_connection(&db_path).map_err(|e| format!("Failed to connect to database: {e}"))?;
-    eprintln!("[list_chats] Connected to database");
+/// [`list_chats`], calling `on_progress` as each slow step finishes
+pub fn list_chats_with_progress(
+    custom_db_path: Option<&std::path::Path>,
+    exclusion_rules: &[exclusions::ExclusionRule],
+    on_progress: impl Fn(ChatListStage),
+) -> Result<Vec<ChatInfo>, String> {
+    tracing::debug!("[list_chats] Starting...");
+    tracing::debug!("[list_chats] Custom DB path: {:?}", custom_db_path);
+
+    // Connect to database (the live default DB is read through a snapshot)
+    let chat_db = db_snapshot::open_chat_db(custom_db_path)?;
+    let db = &chat_db.conn;
+    let timestamp_unit = export::TimestampUnit::detect(db);
+    tracing::debug!("[list_chats] Connected to database");
 
     // Build contacts index for name resolution
-    eprintln!("[list_chats] Building contacts index...");
+    tracing::debug!("[list_chats] Building contacts index...");
     let contacts_index = ContactsIndex::build(None).unwrap_or_default();
-    eprintln!("[list_chats] Contacts index built");
+    tracing::debug!("[list_chats] Contacts index built");
+    on_progress(ChatListStage::ContactsLoaded);
 
     // Cache all chats
-    eprintln!("[list_chats] Loading chats...");
-    let chats = Chat::cache(&db).map_err(|e| format!("Failed to load chats: {e}"))?;
-    eprintln!("[list_chats] Loaded {} chats", chats.len());
+    tracing::debug!("[list_chats] Loading chats...");
+    let chats = Chat::cache(db).map_err(|e| format!("Failed to load chats: {e}"))?;
+    tracing::debug!("[list_chats] Loaded {} chats", chats.len());
 
     // Cache handles (contacts)
-    eprintln!("[list_chats] Loading handles...");
-    let handles = Handle::cache(&db).map_err(|e| format!("Failed to load handles: {e}"))?;
-    let deduped_handles = Handle::dedupe(&handles);
-    eprintln!("[list_chats] Loaded {} handles", handles.len());
+    tracing::debug!("[list_chats] Loading handles...");
+    let handles = Handle::cache(db).map_err(|e| format!("Failed to load handles: {e}"))?;
+    let deduped_handles = identities::dedupe_handles(&handles);
+    tracing::debug!("[list_chats] Loaded {} handles", handles.len());
 
     // Build participants map with resolved names
-    let participants_map = contacts_index.build_participants_map(&handles, &deduped_handles);
+    let mut participants_map = contacts_index.build_participants_map(&handles, &deduped_handles);
+    business_names::fill_names(&mut participants_map, &business_names::load(db));
 
     // Cache chat participants (chat_id -> set of handle_ids)
-    eprintln!("[list_chats] Loading chat participants...");
-    let chat_participants =
-        ChatToHandle::cache(&db).map_err(|e| format!("Failed to load participants: {e}"))?;
-    eprintln!(
+    tracing::debug!("[list_chats] Loading chat participants...");
+    let mut chat_participants =
+        ChatToHandle::cache(db).map_err(|e| format!("Failed to load participants: {e}"))?;
+    // The owner's other accounts are "Me", not participants
+    accounts::OwnerAccounts::load(db, &handles).remove_from(&mut chat_participants);
+    tracing::debug!(
         "[list_chats] Loaded participants for {} chats",
         chat_participants.len()
     );
+    on_progress(ChatListStage::ChatsLoaded);
 
     // Get chat stats (message counts and last message dates)
-    eprintln!("[list_chats] Getting chat stats...");
-    let chat_stats = get_chat_stats(&db).map_err(|e| format!("Failed to get chat stats: {e}"))?;
-    eprintln!("[list_chats] Got chat stats");
-
-    // Build result with last_message_date for sorting
-    let mut result: Vec<(ChatInfo, i64)> = chats
+    tracing::debug!("[list_chats] Getting chat stats...");
+    let chat_stats = get_chat_stats(db).map_err(|e| format!("Failed to get chat stats: {e}"))?;
+    tracing::debug!("[list_chats] Got chat stats");
+    on_progress(ChatListStage::StatsComputed);
+
+    // Build result with last_message_date for sorting, plus the merge key
+    // used to fold duplicate chats for the same people together
+    let entries: Vec<(ChatInfo, i64, Option<chat_merge::MergeKey>)> = chats
         .into_iter()
         .map(|(id, chat)| {
             let participants = chat_participants.get(&id);
             let participant_count = participants.map(|p| p.len()).unwrap_or(0);
             let stats = chat_stats.get(&id);
             let message_count = stats.map(|s| s.message_count).unwrap_or(0);
+            let sent_count = stats.map(|s| s.sent_count).unwrap_or(0);
             let last_message_date = stats.map(|s| s.last_message_date).unwrap_or(0);
+            let last_message_preview = stats.and_then(|s| s.last_message_preview.clone());
 
             let display_name =
                 resolve_chat_display_name(&chat, participants, &participants_map, &deduped_handles);
+            let participant_names =
+                resolve_participant_names(participants, &participants_map, &deduped_handles);
+            let merge_key =
+                chat_merge::merge_key(chat.display_name.as_deref(), participants, &deduped_handles);
+            let excluded =
+                exclusions::is_excluded(exclusion_rules, &chat.chat_identifier, &display_name);
+            let automated = automated::is_automated(
+                &chat.chat_identifier,
+                participant_count,
+                message_count,
+                sent_count,
+            );
 
             (
                 ChatInfo {
                     id,
+                    chat_ids: vec![id],
                     display_name,
                     chat_identifier: chat.chat_identifier.clone(),
                     service: chat
@@ -188,12 +293,23 @@ pub fn list_chats(custom_db_path: Option<&std::path::Path>) -> Result<Vec<ChatIn
                         .unwrap_or("Unknown")
                         .to_string(),
                     participant_count,
+                    participants: participant_names,
                     message_count,
+                    last_message_date: if message_count > 0 {
+                        export::format_timestamp(last_message_date, timestamp_unit)
+                    } else {
+                        String::new()
+                    },
+                    last_message_preview,
+                    excluded,
+                    automated,
                 },
                 last_message_date,
+                merge_key,
             )
         })
         .collect();
+    let mut result = chat_merge::merge_duplicate_chats(entries);
 
     // Sort by last message date descending (most recent first)
     result.sort_by_key(|item| std::cmp::Reverse(item.1));
@@ -201,46 +317,83 @@ pub fn list_chats(custom_db_path: Option<&std::path::Path>) -> Result<Vec<ChatIn
     // Extract just the ChatInfo
     let result: Vec<ChatInfo> = result.into_iter().map(|(info, _)| info).collect();
 
-    eprintln!("[list_chats] Done! Returning {} chats", result.len());
+    tracing::info!("[list_chats] Done! Returning {} chats", result.len());
     Ok(result)
 }
 
-/// Validate that a file is a valid iMessage chat.db database
-/// Returns true if it can be opened and contains the expected tables
-pub fn validate_chat_db(path: &std::path::Path) -> bool {
-    eprintln!("[validate_chat_db] Validating: {:?}", path);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_fixtures::{ChatBuilder, HandleBuilder, MessageBuilder, TestIMessageDb};
+
+    #[test]
+    fn get_chat_stats_uses_most_recent_message_for_preview() {
+        let mut db = TestIMessageDb::new().unwrap();
+        let handle = db.handle(HandleBuilder::new("+15551234567")).unwrap();
+        let chat = db
+            .chat(ChatBuilder::new("iMessage;-;+15551234567"))
+            .unwrap();
+        for (date, text) in [(300, "newest"), (100, "oldest"), (200, "middle")] {
+            db.message(
+                MessageBuilder::new()
+                    .text(text)
+                    .handle(handle)
+                    .chat(chat)
+                    .date(date),
+            )
+            .unwrap();
+        }
 
-    // Check file exists
-    if !path.exists() {
-        eprintln!("[validate_chat_db] File does not exist");
-        return false;
+        let stats = get_chat_stats(db.conn()).unwrap();
+        let chat_stats = &stats[&chat];
+        assert_eq!(chat_stats.message_count, 3);
+        assert_eq!(chat_stats.sent_count, 0);
+        assert_eq!(chat_stats.last_message_date, 300);
+        assert_eq!(chat_stats.last_message_preview.as_deref(), Some("newest"));
     }
 
-    // Try to open as SQLite database
-    let db = match get_connection(path) {
-        Ok(db) => db,
-        Err(e) => {
-            eprintln!("[validate_chat_db] Failed to open: {e}");
-            return false;
-        }
-    };
+    #[test]
+    fn owner_accounts_are_not_listed_as_participants() {
+        let (db, group_chat) = test_fixtures::multi_account_scenario().unwrap();
+        let dir = tempfile::TempDir::new().unwrap();
+        let db_path = dir.path().join("chat.db");
+        db.save_to(&db_path).unwrap();
+
+        let stages = std::cell::RefCell::new(Vec::new());
+        let chats =
+            list_chats_with_progress(Some(&db_path), &[], |stage| stages.borrow_mut().push(stage))
+                .unwrap();
+        assert_eq!(
+            stages.into_inner(),
+            [
+                ChatListStage::ContactsLoaded,
+                ChatListStage::ChatsLoaded,
+                ChatListStage::StatsComputed
+            ]
+        );
+        let group = chats
+            .iter()
+            .find(|c| c.chat_ids.contains(&group_chat))
+            .unwrap();
+        assert_eq!(group.participant_count, 1);
+        assert!(!group
+            .participants
+            .iter()
+            .any(|p| p.contains("work.example.com")));
+    }
 
-    // Check for expected iMessage tables (chat, message, handle)
-    let result: Result<i64, _> = db.query_row(
-        "SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name IN ('chat', 'message', 'handle')",
-        [],
-        |row| row.get(0),
-    );
+    #[test]
+    fn message_preview_cleans_and_truncates_text() {
+        assert_eq!(
+            message_preview(Some("See\u{FFFC}\n  you there".to_string()), None).as_deref(),
+            Some("See you there")
+        );
+        assert_eq!(message_preview(Some("\u{FFFC}".to_string()), None), None);
+        assert_eq!(message_preview(None, None), None);
 
-    match result {
-        Ok(count) => {
-            let valid = count >= 3;
-            eprintln!("[validate_chat_db] Found {count} expected tables, valid={valid}");
-            valid
-        }
-        Err(e) => {
-            eprintln!("[validate_chat_db] Query failed: {e}");
-            false
-        }
+        let long = "word ".repeat(40);
+        let preview = message_preview(Some(long), None).unwrap();
+        assert_eq!(preview.chars().count(), PREVIEW_MAX_CHARS);
+        assert!(preview.ends_with('…'));
     }
 }
@@ -0,0 +1,272 @@
+/*!
+ * Diagnostics via `tracing`, written to a rotating log file.
+ *
+ * The packaged app has no terminal, so anything printed to stderr is lost.
+ * [`init`] installs a subscriber that appends one line per event to
+ * `<app_local_data_dir>/logs/chat-to-map.log` (and echoes to stderr for
+ * `tauri dev` and the CLI). When the file passes [`MAX_LOG_BYTES`] it is
+ * renamed to `chat-to-map.1.log` and older files shift up, keeping
+ * [`KEPT_LOG_FILES`] in total. The level comes from the `CTM_LOG`
+ * environment variable, else the saved setting, else `info`, and can be
+ * changed at runtime with [`set_level`].
+ */
+
+use std::{
+    fmt::Write as _,
+    fs::{File, OpenOptions},
+    io::Write as _,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+
+use tracing::{
+    field::{Field, Visit},
+    span, Event, Level, Metadata, Subscriber,
+};
+
+/// Environment variable overriding the configured level
+pub const LOG_LEVEL_ENV: &str = "CTM_LOG";
+
+/// Size at which the current log file is rotated
+pub const MAX_LOG_BYTES: u64 = 5 * 1024 * 1024;
+
+/// Log files kept, including the current one
+pub const KEPT_LOG_FILES: usize = 5;
+
+const LOG_FILE_STEM: &str = "chat-to-map";
+
+/// Current maximum level, as an index into [`LEVELS`]
+static MAX_LEVEL: AtomicUsize = AtomicUsize::new(2);
+
+/// Levels from least to most verbose
+const LEVELS: [Level; 5] = [
+    Level::ERROR,
+    Level::WARN,
+    Level::INFO,
+    Level::DEBUG,
+    Level::TRACE,
+];
+
+/// Directory holding the log files
+pub fn log_dir(app_local_data_dir: &Path) -> PathBuf {
+    app_local_data_dir.join("logs")
+}
+
+/// Parse "error", "warn", "info", "debug" or "trace" (case-insensitive)
+pub fn parse_level(level: &str) -> Option<Level> {
+    level.trim().parse().ok()
+}
+
+/// The level to use: `CTM_LOG` if set and valid, else `configured`, else info
+pub fn effective_level(configured: Option<&str>) -> Level {
+    std::env::var(LOG_LEVEL_ENV)
+        .ok()
+        .and_then(|level| parse_level(&level))
+        .or_else(|| configured.and_then(parse_level))
+        .unwrap_or(Level::INFO)
+}
+
+/// Change the maximum level of the installed subscriber
+pub fn set_level(level: Level) {
+    let index = LEVELS.iter().position(|l| *l == level).unwrap_or(2);
+    MAX_LEVEL.store(index, Ordering::Relaxed);
+}
+
+/// Install the global subscriber. With `log_dir`, events also go to the
+/// rotating file there; without it (or if it can't be opened) only stderr.
+pub fn init(log_dir: Option<&Path>, level: Level) -> Result<(), String> {
+    set_level(level);
+    let file = match log_dir.map(RotatingFile::open).transpose() {
+        Ok(file) => file,
+        Err(e) => {
+            eprintln!("[logging] {e}; logging to stderr only");
+            None
+        }
+    };
+    let subscriber = LineSubscriber {
+        file: file.map(Mutex::new),
+        next_span_id: AtomicU64::new(1),
+    };
+    tracing::subscriber::set_global_default(subscriber)
+        .map_err(|e| format!("Failed to install logger: {e}"))
+}
+
+/// Append-only log file that rotates by size
+struct RotatingFile {
+    dir: PathBuf,
+    file: File,
+    size: u64,
+    max_bytes: u64,
+}
+
+impl RotatingFile {
+    fn open(dir: &Path) -> Result<Self, String> {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create log directory {:?}: {e}", dir))?;
+        let path = log_path(dir, 0);
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| format!("Failed to open log file {:?}: {e}", path))?;
+        let size = file.metadata().map(|m| m.len()).unwrap_or(0);
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            file,
+            size,
+            max_bytes: MAX_LOG_BYTES,
+        })
+    }
+
+    fn write_line(&mut self, line: &str) {
+        if self.size > 0 && self.size + line.len() as u64 > self.max_bytes {
+            if let Err(e) = self.rotate() {
+                eprintln!("[logging] Failed to rotate log: {e}");
+            }
+        }
+        if self.file.write_all(line.as_bytes()).is_ok() {
+            self.size += line.len() as u64;
+        }
+    }
+
+    /// Shift `name.N.log` to `name.N+1.log` (dropping the oldest) and start
+    /// a fresh current file
+    fn rotate(&mut self) -> std::io::Result<()> {
+        for index in (1..KEPT_LOG_FILES).rev() {
+            let from = log_path(&self.dir, index - 1);
+            if from.exists() {
+                std::fs::rename(&from, log_path(&self.dir, index))?;
+            }
+        }
+        self.file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(log_path(&self.dir, 0))?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+/// `chat-to-map.log` for the current file, `chat-to-map.N.log` for older ones
+fn log_path(dir: &Path, index: usize) -> PathBuf {
+    match index {
+        0 => dir.join(format!("{LOG_FILE_STEM}.log")),
+        n => dir.join(format!("{LOG_FILE_STEM}.{n}.log")),
+    }
+}
+
+/// Subscriber writing each event as one line; spans are not tracked
+struct LineSubscriber {
+    file: Option<Mutex<RotatingFile>>,
+    next_span_id: AtomicU64,
+}
+
+impl Subscriber for LineSubscriber {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        *metadata.level() <= LEVELS[MAX_LEVEL.load(Ordering::Relaxed)]
+    }
+
+    fn new_span(&self, _span: &span::Attributes<'_>) -> span::Id {
+        span::Id::from_u64(self.next_span_id.fetch_add(1, Ordering::Relaxed))
+    }
+
+    fn record(&self, _span: &span::Id, _values: &span::Record<'_>) {}
+
+    fn record_follows_from(&self, _span: &span::Id, _follows: &span::Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let line = format_event(
+            &chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+            event,
+        );
+        eprint!("{line}");
+        if let Some(file) = &self.file {
+            file.lock().unwrap().write_line(&line);
+        }
+    }
+
+    fn enter(&self, _span: &span::Id) {}
+
+    fn exit(&self, _span: &span::Id) {}
+}
+
+/// `<timestamp> <LEVEL> <target>: <message> key=value...\n`
+fn format_event(timestamp: &str, event: &Event<'_>) -> String {
+    let metadata = event.metadata();
+    let mut fields = FieldFormatter::default();
+    event.record(&mut fields);
+    format!(
+        "{timestamp} {:>5} {}: {}{}\n",
+        metadata.level(),
+        metadata.target(),
+        fields.message,
+        fields.rest
+    )
+}
+
+#[derive(Default)]
+struct FieldFormatter {
+    message: String,
+    rest: String,
+}
+
+impl Visit for FieldFormatter {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message.push_str(value);
+        } else {
+            let _ = write!(self.rest, " {}={value:?}", field.name());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.message, "{value:?}");
+        } else {
+            let _ = write!(self.rest, " {}={value:?}", field.name());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn rotation_shifts_old_files_and_keeps_a_fixed_number() {
+        let dir = TempDir::new().unwrap();
+        let mut log = RotatingFile::open(dir.path()).unwrap();
+        log.max_bytes = 100;
+        let line = "x".repeat(60);
+        for _ in 0..(KEPT_LOG_FILES + 3) {
+            log.write_line(&line);
+        }
+
+        let mut names: Vec<String> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        assert_eq!(names.len(), KEPT_LOG_FILES);
+        assert!(names.contains(&"chat-to-map.log".to_string()));
+        assert!(names.contains(&format!("chat-to-map.{}.log", KEPT_LOG_FILES - 1)));
+    }
+
+    #[test]
+    fn levels_parse_and_fall_back_to_info() {
+        assert_eq!(parse_level("DEBUG"), Some(Level::DEBUG));
+        assert_eq!(parse_level(" warn "), Some(Level::WARN));
+        assert_eq!(parse_level("loud"), None);
+        if std::env::var(LOG_LEVEL_ENV).is_err() {
+            assert_eq!(effective_level(Some("trace")), Level::TRACE);
+            assert_eq!(effective_level(Some("nonsense")), Level::INFO);
+            assert_eq!(effective_level(None), Level::INFO);
+        }
+    }
+}
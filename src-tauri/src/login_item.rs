@@ -0,0 +1,161 @@
+/*!
+ * Opening the app at login, so scheduled exports (see auto_export.rs) run
+ * without the user starting it.
+ *
+ * On macOS 13+ the app registers itself with `SMAppService.mainAppService`
+ * (ServiceManagement), which lists it under System Settings → General →
+ * Login Items, where the user can also turn it off or may first have to
+ * approve it. `Settings::launch_at_login` records what the user chose;
+ * [`login_item_status`] reports what the system has. Older macOS
+ * versions and other platforms can't do this yet.
+ */
+
+use serde::{Deserialize, Serialize};
+
+/// Whether the app opens at login, as the system sees it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LoginItemStatus {
+    NotRegistered,
+    Enabled,
+    /// Registered, but the user has to allow it in System Settings
+    RequiresApproval,
+    /// The system couldn't find the app to register
+    NotFound,
+    /// No login items on this platform (or before macOS 13)
+    Unsupported,
+}
+
+impl LoginItemStatus {
+    /// Map an `SMAppServiceStatus` value
+    pub fn from_service_status(status: isize) -> Self {
+        match status {
+            0 => Self::NotRegistered,
+            1 => Self::Enabled,
+            2 => Self::RequiresApproval,
+            _ => Self::NotFound,
+        }
+    }
+
+    pub fn is_registered(self) -> bool {
+        matches!(self, Self::Enabled | Self::RequiresApproval)
+    }
+}
+
+/// The app's login item status
+pub fn login_item_status() -> LoginItemStatus {
+    #[cfg(target_os = "macos")]
+    {
+        macos::status().map_or(
+            LoginItemStatus::Unsupported,
+            LoginItemStatus::from_service_status,
+        )
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    {
+        LoginItemStatus::Unsupported
+    }
+}
+
+/// Register the app to open at login, or remove it; returns the status
+/// afterwards
+pub fn set_launch_at_login(enabled: bool) -> Result<LoginItemStatus, String> {
+    #[cfg(target_os = "macos")]
+    {
+        if login_item_status().is_registered() != enabled {
+            macos::set_registered(enabled).map_err(|e| {
+                let action = if enabled { "add" } else { "remove" };
+                format!("Failed to {action} the login item: {e}")
+            })?;
+        }
+        Ok(login_item_status())
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    {
+        let _ = enabled;
+        Err("Opening at login isn't supported on this platform yet".to_string())
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod macos {
+    use std::ffi::{c_char, CStr};
+
+    use objc2::{
+        msg_send,
+        rc::Retained,
+        runtime::{AnyClass, AnyObject, Bool},
+    };
+
+    #[link(name = "ServiceManagement", kind = "framework")]
+    extern "C" {}
+
+    /// `SMAppService.mainAppService`; `None` before macOS 13, which has no
+    /// `SMAppService`
+    fn main_app_service() -> Option<Retained<AnyObject>> {
+        let class = AnyClass::get(CStr::from_bytes_with_nul(b"SMAppService\0").ok()?)?;
+        // SAFETY: a class property returning the shared service object
+        unsafe { msg_send![class, mainAppService] }
+    }
+
+    pub fn status() -> Option<isize> {
+        let service = main_app_service()?;
+        // SAFETY: an `SMAppServiceStatus` (NSInteger) property
+        Some(unsafe { msg_send![&service, status] })
+    }
+
+    pub fn set_registered(registered: bool) -> Result<(), String> {
+        let service =
+            main_app_service().ok_or("Opening at login needs macOS 13 or later".to_string())?;
+        let mut error: *mut AnyObject = std::ptr::null_mut();
+        let error_out = &mut error as *mut *mut AnyObject;
+        // SAFETY: both methods take an `NSError **` and return BOOL
+        let ok: Bool = unsafe {
+            if registered {
+                msg_send![&service, registerAndReturnError: error_out]
+            } else {
+                msg_send![&service, unregisterAndReturnError: error_out]
+            }
+        };
+        if ok.as_bool() {
+            return Ok(());
+        }
+        // SAFETY: on failure `error` is null or an autoreleased NSError
+        Err(unsafe { error_description(error) }.unwrap_or_else(|| "unknown error".to_string()))
+    }
+
+    /// `-[NSError localizedDescription]`, as a Rust string
+    unsafe fn error_description(error: *mut AnyObject) -> Option<String> {
+        let error = error.as_ref()?;
+        let description: Option<Retained<AnyObject>> = msg_send![error, localizedDescription];
+        let description = description?;
+        let utf8: *const c_char = msg_send![&description, UTF8String];
+        (!utf8.is_null()).then(|| CStr::from_ptr(utf8).to_string_lossy().into_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn service_status_values_map_to_login_item_status() {
+        assert_eq!(
+            LoginItemStatus::from_service_status(0),
+            LoginItemStatus::NotRegistered
+        );
+        assert!(LoginItemStatus::from_service_status(1).is_registered());
+        assert!(LoginItemStatus::from_service_status(2).is_registered());
+        assert_eq!(
+            LoginItemStatus::from_service_status(3),
+            LoginItemStatus::NotFound
+        );
+        assert!(!LoginItemStatus::Unsupported.is_registered());
+        assert_eq!(
+            serde_json::to_value(LoginItemStatus::RequiresApproval).unwrap(),
+            "requires_approval"
+        );
+    }
+}
@@ -2,22 +2,36 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 use std::path::PathBuf;
-use std::sync::Mutex;
+use std::sync::Arc;
 
 use chat_to_map_desktop::{
-    export::{export_chats, ExportProgress},
-    list_chats as lib_list_chats,
-    screenshot::{capture_window, ScreenshotConfig},
-    upload::{
-        complete_upload, get_presigned_url, get_results_url, read_or_create_visitor_id, upload_file,
+    app_core::AppCore,
+    app_info::{app_info, AppInfo},
+    chat_list::{ChatListQuery, ChatPage},
+    chat_list_cache::list_chats_cached,
+    contact_avatar::contact_avatar,
+    contacts_access::{self, ContactsAccess},
+    db_snapshot::default_chat_db_path,
+    diagnostics::{
+        create_diagnostics_bundle as lib_create_diagnostics_bundle, DiagnosticsInput,
+        PermissionReport,
+    },
+    export::{
+        activity::{chat_activity, ChatActivity},
+        preview::chat_preview,
+        ExportedMessage,
     },
-    validate_chat_db as lib_validate_chat_db, ChatInfo,
+    logging,
+    permissions::{self, FullDiskAccess},
+    person_search::{find_chats_for_contact as lib_find_chats_for_contact, ContactChats},
+    screenshot::{capture_window, ScreenshotConfig},
+    search::{search_messages as lib_search_messages, SearchResult},
+    settings::Settings,
+    validation::{validate_chat_db as lib_validate_chat_db, ValidationResult},
 };
 use clap::Parser;
-use imessage_database::{tables::table::get_connection, util::dirs::default_db_path};
 use serde::{Deserialize, Serialize};
-use tauri::menu::{MenuBuilder, MenuItemBuilder, SubmenuBuilder};
-use tauri::Emitter;
+use tauri::{Emitter, Manager};
 
 /// CLI arguments for the desktop app
 #[derive(Parser, Debug)]
@@ -41,240 +55,154 @@ struct Args {
     output_dir: PathBuf,
 }
 
-/// App state for screenshot configuration and debug settings.
-///
-/// Made `pub` so the sibling `debug_commands` module (which holds the
-/// runtime override commands behind the hidden debug panel) can borrow it.
-pub struct AppState {
-    pub screenshot_config: Mutex<ScreenshotConfig>,
-    /// Override the WEB host URL (results page; chattomap.com) — debug panel only
-    pub server_host_override: Mutex<Option<String>>,
-    /// Override the API host URL (Convex HTTP actions; *.convex.site) — debug panel only
-    pub api_host_override: Mutex<Option<String>>,
-    /// Custom headers to send with API requests (for debugging)
-    pub custom_headers: Mutex<std::collections::HashMap<String, String>>,
-}
+/// State managed by Tauri and shared by every command: screenshot config,
+/// debug overrides, the running export and the export queue. See
+/// `chat_to_map_desktop::app_core` for how it is locked.
+pub type AppState = Arc<AppCore>;
 
 mod debug_commands;
-
-/// Export result returned to the frontend.
-///
-/// `chat_analysis_id` + `job_token` are returned by Convex `uploadComplete` and
-/// together gate access to the results page.
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ExportResult {
-    pub success: bool,
-    pub chat_upload_id: Option<String>,
-    pub chat_analysis_id: Option<String>,
-    pub job_token: Option<String>,
-    pub results_url: Option<String>,
-    pub error: Option<String>,
-}
-
-/// List available iMessage chats
+mod deep_links;
+mod error_reporting;
+mod export_commands;
+mod export_scheduler;
+mod help_menu;
+mod merge_commands;
+mod notifications;
+mod preflight_commands;
+mod queue_commands;
+mod run_history;
+mod settings_commands;
+mod source_commands;
+mod token_commands;
+mod tray;
+mod update_commands;
+mod upload_commands;
+
+/// List available iMessage chats, one page at a time (everything, newest
+/// first, without a query). Runs off the command thread, emitting
+/// `chat-list-progress` with each `ChatListStage` as it finishes. The list
+/// is cached until chat.db or the contacts change; `refresh` rereads it
 #[tauri::command]
-fn list_chats(custom_db_path: Option<String>) -> Result<Vec<ChatInfo>, String> {
-    eprintln!(
-        "[tauri::list_chats] Command invoked, custom_db_path: {:?}",
-        custom_db_path
-    );
-    let path = custom_db_path.as_ref().map(PathBuf::from);
-    let result = lib_list_chats(path.as_deref());
-    eprintln!(
+async fn list_chats(
+    custom_db_path: Option<String>,
+    query: Option<ChatListQuery>,
+    refresh: Option<bool>,
+    window: tauri::Window,
+    app_handle: tauri::AppHandle,
+) -> Result<ChatPage, String> {
+    tracing::debug!("[tauri::list_chats] Command invoked, custom_db_path: {custom_db_path:?}");
+    let settings = settings_commands::load_settings(&app_handle)?;
+    let path = settings_commands::db_path_or_saved(custom_db_path, &settings);
+    let cache_dir = settings_commands::app_local_data_dir(&app_handle)?;
+    let result = tokio::task::spawn_blocking(move || {
+        let on_progress = |stage| {
+            let _ = window.emit("chat-list-progress", stage);
+        };
+        list_chats_cached(
+            &cache_dir,
+            path.as_deref(),
+            &settings.exclusion_rules,
+            refresh.unwrap_or(false),
+            on_progress,
+        )
+        .map(|chats| query.unwrap_or_default().apply(chats))
+    })
+    .await
+    .map_err(|e| format!("Chat listing task failed: {e}"))?;
+    tracing::debug!(
         "[tauri::list_chats] Result: {:?}",
-        result.as_ref().map(|v| v.len())
+        result.as_ref().map(|page| page.total_count)
     );
     result
 }
 
 /// Validate that a file is a valid iMessage chat.db database
 #[tauri::command]
-fn validate_chat_db(path: String) -> bool {
-    eprintln!("[tauri::validate_chat_db] Validating: {}", path);
+fn validate_chat_db(path: String) -> ValidationResult {
+    tracing::info!("[tauri::validate_chat_db] Validating: {}", path);
     lib_validate_chat_db(&PathBuf::from(path))
 }
 
-/// Export selected chats and upload to server
+/// Last messages of a chat, for a quick peek on the selection screen
 #[tauri::command]
-async fn export_and_upload(
-    chat_ids: Vec<i32>,
+fn get_chat_preview(
+    chat_id: i32,
+    limit: Option<usize>,
     custom_db_path: Option<String>,
     app_handle: tauri::AppHandle,
-    state: tauri::State<'_, AppState>,
-    window: tauri::Window,
-) -> Result<ExportResult, String> {
-    use tauri::Manager;
-
-    // Dev panel overrides: web host = results page (chattomap.com); api host
-    // = Convex HTTP actions (*.convex.site). Both default to compile-time
-    // constants (see upload.rs) when no override is set.
-    let web_host_override = state.server_host_override.lock().unwrap().clone();
-    let api_host_override = state.api_host_override.lock().unwrap().clone();
-    let custom_headers = state.custom_headers.lock().unwrap().clone();
-    // Per-install visitor ID lives in app local data so the SaaS can reuse
-    // duplicate-upload detection for return visits.
-    let app_local_data_dir = app_handle
-        .path()
-        .app_local_data_dir()
-        .map_err(|e| format!("Failed to resolve app local data dir: {e}"))?;
-    let visitor_id = read_or_create_visitor_id(&app_local_data_dir);
-    // Helper to emit progress
-    let emit = |stage: &str, percent: u8, message: &str| {
-        let _ = window.emit(
-            "export-progress",
-            ExportProgress {
-                stage: stage.to_string(),
-                percent,
-                message: message.to_string(),
-            },
-        );
-    };
+) -> Result<Vec<ExportedMessage>, String> {
+    let path = settings_commands::saved_db_path(custom_db_path, &app_handle)?;
+    chat_preview(chat_id, limit, path.as_deref())
+}
 
-    // Stage 1: Export messages (0-50%)
-    emit("Exporting", 0, "Starting export...");
-
-    let window_clone = window.clone();
-    let progress_callback = Box::new(move |progress: ExportProgress| {
-        // Scale export progress to 0-50%
-        let scaled_percent = progress.percent / 2;
-        let _ = window_clone.emit(
-            "export-progress",
-            ExportProgress {
-                stage: progress.stage,
-                percent: scaled_percent,
-                message: progress.message,
-            },
-        );
-    });
-
-    let db_path = custom_db_path.map(PathBuf::from);
-    let export_result = tokio::task::spawn_blocking(move || {
-        export_chats(&chat_ids, Some(progress_callback), db_path.as_deref())
-    })
-    .await
-    .map_err(|e| format!("Export task failed: {e}"))?
-    .map_err(|e| format!("Export failed: {e}"))?;
-
-    // Stage 2: Get pre-signed URL (50-55%)
-    emit("Uploading", 50, "Preparing upload...");
-
-    let zip_size = std::fs::metadata(&export_result.zip_path)
-        .map_err(|e| format!("Failed to stat export zip: {e}"))?
-        .len();
-    let presign_response =
-        get_presigned_url(zip_size, api_host_override.as_deref(), &custom_headers)
-            .await
-            .map_err(|e| format!("Failed to get upload URL: {e}"))?;
-
-    // Stage 3: Upload file (55-90%)
-    emit("Uploading", 55, "Uploading to server...");
-
-    let window_clone = window.clone();
-    let upload_callback = Box::new(move |percent: u8, message: String| {
-        // Scale upload progress to 55-90%
-        let scaled_percent = 55 + (percent * 35 / 100);
-        let _ = window_clone.emit(
-            "export-progress",
-            ExportProgress {
-                stage: "Uploading".to_string(),
-                percent: scaled_percent,
-                message,
-            },
-        );
-    });
-
-    let storage_id = upload_file(
-        &export_result.zip_path,
-        &presign_response.upload_url,
-        Some(upload_callback),
-    )
-    .await
-    .map_err(|e| format!("Upload failed: {e}"))?;
-
-    // Stage 4: Complete upload and start processing (90-95%)
-    emit("Processing", 90, "Starting processing...");
-
-    let original_filename = export_result
-        .zip_path
-        .file_name()
-        .and_then(|n| n.to_str())
-        .map(|s| s.to_string());
-
-    let job_response = complete_upload(
-        &storage_id,
-        &visitor_id,
-        original_filename.as_deref(),
-        api_host_override.as_deref(),
-        &custom_headers,
-    )
-    .await
-    .map_err(|e| format!("Failed to start processing: {e}"))?;
+/// Messages per month, per sender and attachments of a chat, for the
+/// activity chart on the selection screen
+#[tauri::command]
+fn get_chat_stats(
+    chat_id: i32,
+    custom_db_path: Option<String>,
+    app_handle: tauri::AppHandle,
+) -> Result<ChatActivity, String> {
+    let path = settings_commands::saved_db_path(custom_db_path, &app_handle)?;
+    chat_activity(chat_id, path.as_deref())
+}
 
-    // Stage 5: Complete (95-100%)
-    let results_url = get_results_url(
-        &job_response.chat_analysis_id,
-        job_response.job_token.as_deref(),
-        web_host_override.as_deref(),
-    );
-    emit("Complete", 100, "Export complete!");
+/// Photo of the person in a one-on-one chat, as a small base64 PNG (`None`
+/// for group chats and contacts without one)
+#[tauri::command]
+fn get_contact_avatar(
+    chat_id: i32,
+    custom_db_path: Option<String>,
+    app_handle: tauri::AppHandle,
+) -> Result<Option<String>, String> {
+    let path = settings_commands::saved_db_path(custom_db_path, &app_handle)?;
+    contact_avatar(chat_id, path.as_deref())
+}
 
-    // Open browser to results page
-    if let Err(e) = open::that(&results_url) {
-        eprintln!("Failed to open browser: {e}");
-    }
+/// People whose contact name matches `name`, with every chat they are in,
+/// for the "search by person" box
+#[tauri::command]
+fn find_chats_for_contact(
+    name: String,
+    custom_db_path: Option<String>,
+    app_handle: tauri::AppHandle,
+) -> Result<Vec<ContactChats>, String> {
+    let path = settings_commands::saved_db_path(custom_db_path, &app_handle)?;
+    lib_find_chats_for_contact(&name, path.as_deref())
+}
 
-    Ok(ExportResult {
-        success: true,
-        chat_upload_id: Some(job_response.chat_upload_id),
-        chat_analysis_id: Some(job_response.chat_analysis_id),
-        job_token: job_response.job_token,
-        results_url: Some(results_url),
-        error: None,
-    })
+/// Search message text across all chats, newest first
+#[tauri::command]
+fn search_messages(
+    query: String,
+    limit: Option<usize>,
+    custom_db_path: Option<String>,
+    app_handle: tauri::AppHandle,
+) -> Result<Vec<SearchResult>, String> {
+    let path = settings_commands::saved_db_path(custom_db_path, &app_handle)?;
+    lib_search_messages(&query, limit, path.as_deref())
 }
 
-/// Check if Full Disk Access is granted (macOS)
-/// Respects the --force-no-fda flag for screenshot testing
+/// Check if Full Disk Access is granted (macOS), or why the database
+/// can't be read. Respects the --force-no-fda flag for screenshot testing
 #[tauri::command]
-fn check_full_disk_access(state: tauri::State<AppState>) -> Result<bool, String> {
-    eprintln!("[check_full_disk_access] Checking...");
+fn check_full_disk_access(state: tauri::State<AppState>) -> Result<FullDiskAccess, String> {
+    Ok(full_disk_access(&state))
+}
 
-    // Check if we're forcing FDA to be denied (for screenshot mode)
-    let config = state.screenshot_config.lock().unwrap();
-    if config.force_no_fda {
-        eprintln!("[check_full_disk_access] Force no FDA enabled");
-        return Ok(false);
-    }
-    drop(config);
+fn full_disk_access(state: &AppState) -> FullDiskAccess {
+    tracing::debug!("[check_full_disk_access] Checking...");
 
-    #[cfg(target_os = "macos")]
-    {
-        // Try to open the database directly. We deliberately do NOT pre-check
-        // `db_path.exists()` first: on macOS, `Path::exists()` calls stat(),
-        // which itself requires Full Disk Access for TCC-protected paths under
-        // ~/Library/Messages. Without FDA, exists() returns false even when
-        // the file is there — so the pre-check would short-circuit BEFORE we
-        // ever try to open the DB, leaving the user stuck on the permissions
-        // screen even after granting access. SQLite's open call is the
-        // authoritative source: it succeeds with FDA, fails without.
-        let db_path = default_db_path();
-        eprintln!("[check_full_disk_access] DB path: {:?}", db_path);
-        match get_connection(&db_path) {
-            Ok(_) => {
-                eprintln!("[check_full_disk_access] FDA granted (can open DB)");
-                Ok(true)
-            }
-            Err(e) => {
-                eprintln!("[check_full_disk_access] cannot open DB: {:?}", e);
-                Ok(false)
-            }
-        }
+    // Check if we're forcing FDA to be denied (for screenshot mode)
+    if state.screenshot_config().force_no_fda {
+        tracing::info!("[check_full_disk_access] Force no FDA enabled");
+        return FullDiskAccess::Denied;
     }
 
-    #[cfg(not(target_os = "macos"))]
-    {
-        Ok(true)
-    }
+    let db_path = default_chat_db_path();
+    let access = permissions::check_full_disk_access(&db_path);
+    tracing::info!("[check_full_disk_access] {:?}: {:?}", db_path, access);
+    access
 }
 
 /// Open System Preferences to Full Disk Access (macOS)
@@ -290,39 +218,30 @@ fn open_full_disk_access_settings() -> Result<(), String> {
     Ok(())
 }
 
-/// Check if Contacts access is granted (macOS)
+/// Check the Contacts permission (TCC status on macOS); never prompts
 #[tauri::command]
-fn check_contacts_access() -> Result<bool, String> {
-    eprintln!("[check_contacts_access] Checking...");
+fn check_contacts_access() -> Result<ContactsAccess, String> {
+    let access = contacts_access::contacts_access();
+    tracing::info!("[check_contacts_access] {:?}", access);
+    Ok(access)
+}
 
-    #[cfg(target_os = "macos")]
-    {
-        use chat_to_map_desktop::contacts::ContactsIndex;
-
-        // Try to build the contacts index - this will fail without Contacts permission
-        match ContactsIndex::build(None) {
-            Ok(index) => {
-                let has_contacts = !index.is_empty();
-                eprintln!(
-                    "[check_contacts_access] Contacts access granted, {} entries",
-                    index.len()
-                );
-                // If the index is empty, it might mean no permission OR no contacts
-                // We return true if we could read the database (even if empty)
-                Ok(has_contacts || index.is_empty())
-            }
-            Err(e) => {
-                eprintln!("[check_contacts_access] Contacts access denied: {:?}", e);
-                Ok(false)
-            }
+/// Show the system Contacts prompt if the user hasn't been asked yet, then
+/// re-check that the contacts index can be read
+#[tauri::command]
+async fn request_contacts_access() -> Result<ContactsAccess, String> {
+    use chat_to_map_desktop::contacts::ContactsIndex;
+
+    let access = contacts_access::request_contacts_access().await;
+    tracing::info!("[request_contacts_access] {:?}", access);
+    if access.is_authorized() {
+        match tokio::task::spawn_blocking(|| ContactsIndex::build(None)).await {
+            Ok(Ok(index)) => tracing::info!("[request_contacts_access] {} contacts", index.len()),
+            Ok(Err(e)) => tracing::warn!("[request_contacts_access] Index unreadable: {:?}", e),
+            Err(e) => tracing::warn!("[request_contacts_access] Index check failed: {e}"),
         }
     }
-
-    #[cfg(not(target_os = "macos"))]
-    {
-        // On non-macOS platforms, contacts aren't available
-        Ok(false)
-    }
+    Ok(access)
 }
 
 /// Open System Preferences to Contacts (macOS)
@@ -350,7 +269,7 @@ pub struct ScreenshotConfigResponse {
 /// Get screenshot configuration (for frontend to detect screenshot mode)
 #[tauri::command]
 fn get_screenshot_config(state: tauri::State<AppState>) -> ScreenshotConfigResponse {
-    let config = state.screenshot_config.lock().unwrap();
+    let config = state.screenshot_config();
     ScreenshotConfigResponse {
         enabled: config.enabled,
         theme: config.theme.clone(),
@@ -359,6 +278,64 @@ fn get_screenshot_config(state: tauri::State<AppState>) -> ScreenshotConfigRespo
     }
 }
 
+/// Get version, build metadata and recent changelog entries
+#[tauri::command]
+fn get_app_info() -> Result<AppInfo, String> {
+    app_info()
+}
+
+/// Zip logs, redacted settings, chat.db schema, Contacts sources and the
+/// permission checks for support, then reveal the zip in the file manager
+#[tauri::command]
+fn create_diagnostics_bundle(
+    custom_db_path: Option<String>,
+    app_handle: tauri::AppHandle,
+    state: tauri::State<AppState>,
+) -> Result<String, String> {
+    let data_dir = settings_commands::app_local_data_dir(&app_handle)?;
+    let settings = Settings::load(&data_dir);
+    let db_path = settings_commands::db_path_or_saved(custom_db_path, &settings)
+        .unwrap_or_else(default_chat_db_path);
+    let custom_headers = state.host_overrides().custom_headers;
+    let log_dir = logging::log_dir(&data_dir);
+    let input = DiagnosticsInput {
+        settings: &settings,
+        custom_headers: &custom_headers,
+        permissions: PermissionReport {
+            full_disk_access: full_disk_access(&state),
+            contacts_access: check_contacts_access()?,
+        },
+        db_path: &db_path,
+        log_dir: Some(&log_dir),
+    };
+
+    let output_dir = data_dir.join("diagnostics");
+    let zip_path = lib_create_diagnostics_bundle(&input, &output_dir)?;
+    if let Err(e) = open::that(&output_dir) {
+        tracing::warn!("Failed to open diagnostics folder: {e}");
+    }
+    Ok(zip_path.to_string_lossy().to_string())
+}
+
+/// Log to `<app_local_data_dir>/logs` at the level from `CTM_LOG` or settings
+fn init_logging(app_handle: &tauri::AppHandle, settings: &Settings) {
+    let log_dir = settings_commands::app_local_data_dir(app_handle)
+        .ok()
+        .map(|dir| logging::log_dir(&dir));
+    let level = logging::effective_level(settings.log_level.as_deref());
+    if let Err(e) = logging::init(log_dir.as_deref(), level) {
+        eprintln!("[main] {e}");
+    }
+}
+
+/// Open the folder holding the log files, so users can attach them to bug reports
+#[tauri::command]
+fn open_logs_folder(app_handle: tauri::AppHandle) -> Result<(), String> {
+    let log_dir = logging::log_dir(&settings_commands::app_local_data_dir(&app_handle)?);
+    std::fs::create_dir_all(&log_dir).map_err(|e| format!("Failed to create logs folder: {e}"))?;
+    open::that(&log_dir).map_err(|e| format!("Failed to open logs folder: {e}"))
+}
+
 /// Open the Open Source Licenses (CREDITS.md)
 #[tauri::command]
 fn open_licenses() -> Result<(), String> {
@@ -389,9 +366,7 @@ fn open_licenses() -> Result<(), String> {
 /// Take a screenshot and save it to the specified filename
 #[tauri::command]
 fn take_screenshot(state: tauri::State<AppState>, filename: String) -> Result<String, String> {
-    let config = state.screenshot_config.lock().unwrap();
-    let output_path = config.output_dir.join(&filename);
-    drop(config);
+    let output_path = state.screenshot_config().output_dir.join(&filename);
 
     // Ensure output directory exists
     if let Some(parent) = output_path.parent() {
@@ -415,61 +390,105 @@ fn main() {
         output_dir: args.output_dir,
     };
 
-    eprintln!("[main] Screenshot mode: {}", screenshot_config.enabled);
-    eprintln!("[main] Theme: {}", screenshot_config.theme);
-    eprintln!("[main] Force no FDA: {}", screenshot_config.force_no_fda);
-
-    let app_state = AppState {
-        screenshot_config: Mutex::new(screenshot_config),
-        server_host_override: Mutex::new(None),
-        api_host_override: Mutex::new(None),
-        custom_headers: Mutex::new(std::collections::HashMap::new()),
-    };
+    let app_state: AppState = AppCore::new(screenshot_config);
 
     tauri::Builder::default()
         .plugin(tauri_plugin_shell::init())
         .manage(app_state)
         .setup(|app| {
-            // Build Help menu with Open Source Licenses item
-            let licenses_item = MenuItemBuilder::new("Open Source Licenses")
-                .id("open_licenses")
-                .build(app)?;
-
-            let help_menu = SubmenuBuilder::new(app, "Help")
-                .item(&licenses_item)
-                .build()?;
-
-            let menu = MenuBuilder::new(app).item(&help_menu).build()?;
+            // Start file logging and restore saved host overrides before
+            // anything else runs
+            let settings = settings_commands::load_settings(app.handle()).unwrap_or_default();
+            init_logging(app.handle(), &settings);
+            error_reporting::init(app.handle(), &settings);
+            {
+                let state = app.state::<AppState>();
+                settings_commands::apply_host_overrides(&state, &settings);
+
+                let config = state.screenshot_config();
+                tracing::info!("[main] Screenshot mode: {}", config.enabled);
+                tracing::info!("[main] Theme: {}", config.theme);
+                tracing::info!("[main] Force no FDA: {}", config.force_no_fda);
+            }
+            upload_commands::retry_pending_on_launch(app.handle(), &settings);
+            export_scheduler::start(app.handle());
 
-            app.set_menu(menu)?;
+            help_menu::build(app)?;
+            tray::build(app)?;
 
             Ok(())
         })
-        .on_menu_event(|_app, event| {
-            if event.id().as_ref() == "open_licenses" {
-                if let Err(e) = open_licenses() {
-                    eprintln!("Failed to open licenses: {e}");
-                }
-            }
-        })
+        .on_menu_event(help_menu::on_menu_event)
         .plugin(tauri_plugin_dialog::init())
+        .plugin(tauri_plugin_notification::init())
+        .plugin(tauri_plugin_updater::Builder::new().build())
         .invoke_handler(tauri::generate_handler![
             list_chats,
             validate_chat_db,
-            export_and_upload,
+            search_messages,
+            get_chat_preview,
+            get_chat_stats,
+            get_contact_avatar,
+            find_chats_for_contact,
+            preflight_commands::preflight_export,
+            preflight_commands::estimate_export,
+            preflight_commands::scan_addresses,
+            export_commands::get_export_state,
+            export_commands::cancel_export,
+            queue_commands::enqueue_export,
+            queue_commands::list_export_queue,
+            queue_commands::cancel_queued_export,
+            queue_commands::retry_queued_export,
+            queue_commands::clear_finished_exports,
+            export_commands::export_and_upload,
+            export_commands::import_telegram_export,
+            upload_commands::list_pending_uploads,
+            upload_commands::retry_upload,
+            upload_commands::discard_pending_upload,
+            merge_commands::merge_exports,
+            upload_commands::get_upload_history,
+            upload_commands::open_job_results,
+            upload_commands::get_account_usage,
+            upload_commands::test_upload_connection,
+            settings_commands::get_settings,
+            settings_commands::set_settings,
+            settings_commands::get_preferences,
+            settings_commands::set_preferences,
+            settings_commands::set_launch_at_login,
+            settings_commands::get_login_item_status,
+            source_commands::list_sources,
+            source_commands::add_source,
+            source_commands::select_source,
+            source_commands::remove_source,
+            source_commands::discover_databases,
+            error_reporting::set_error_reporting,
+            settings_commands::get_exclusion_rules,
+            settings_commands::set_exclusion_rules,
+            settings_commands::list_exclusion_matches,
+            settings_commands::set_contacts_vcf_path,
+            token_commands::list_access_tokens,
+            token_commands::create_access_token,
+            token_commands::revoke_access_token,
             check_full_disk_access,
             open_full_disk_access_settings,
             check_contacts_access,
+            request_contacts_access,
             open_contacts_settings,
             get_screenshot_config,
+            get_app_info,
+            update_commands::check_for_updates,
+            update_commands::install_update,
             take_screenshot,
             open_licenses,
+            open_logs_folder,
+            create_diagnostics_bundle,
             debug_commands::set_server_host,
             debug_commands::get_server_host,
             debug_commands::set_api_host,
             debug_commands::get_api_host,
             debug_commands::set_custom_headers,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(update_commands::app_context())
+        .expect("error while building tauri application")
+        .run(deep_links::on_run_event);
 }
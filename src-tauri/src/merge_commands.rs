@@ -0,0 +1,64 @@
+//! Merging exports from several sources into one upload (see the
+//! library's export/merge.rs).
+//!
+//! `merge_exports` combines pending uploads from the outbox (say, one
+//! export of this Mac's chat.db and one of an iPhone backup) and any other
+//! export zips into a single pending upload, replacing the pending ones it
+//! merged. It's then uploaded like any other, with `retry_upload`.
+
+use std::{collections::BTreeMap, path::PathBuf};
+
+use chat_to_map_desktop::{
+    export::{
+        merge::{self, MERGED_SOURCE},
+        ExportFormat, UPLOAD_PLATFORM,
+    },
+    outbox::{self, PendingUpload},
+};
+
+use crate::upload_commands::{emit_pending_uploads, outbox_dir};
+
+/// Merge the pending uploads `pending_ids` and the export zips `zip_paths`,
+/// in that order, into a new pending upload
+#[tauri::command]
+pub async fn merge_exports(
+    pending_ids: Vec<String>,
+    zip_paths: Vec<String>,
+    app_handle: tauri::AppHandle,
+    window: tauri::Window,
+) -> Result<PendingUpload, String> {
+    let outbox = outbox_dir(&app_handle)?;
+    let pending = pending_ids
+        .iter()
+        .map(|id| outbox::load(&outbox, id))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut paths: Vec<PathBuf> = pending.iter().map(|p| p.zip_path(&outbox)).collect();
+    paths.extend(zip_paths.into_iter().map(PathBuf::from));
+    tracing::info!("[merge_exports] Merging {} exports", paths.len());
+    // The server only reads JSON chat files
+    let merged =
+        tokio::task::spawn_blocking(move || merge::merge_exports(&paths, Some(ExportFormat::Json)))
+            .await
+            .map_err(|e| format!("Merge task failed: {e}"))??;
+
+    let platform = match merged.source.as_str() {
+        MERGED_SOURCE => UPLOAD_PLATFORM,
+        source => source,
+    };
+    let metadata: BTreeMap<String, String> = pending
+        .iter()
+        .flat_map(|upload| upload.metadata.clone())
+        .collect();
+    let id = uuid::Uuid::new_v4().to_string();
+    let saved = outbox::save(&outbox, &id, &merged.result, platform, &metadata, None)?;
+    for upload in &pending {
+        outbox::remove(&outbox, &upload.id)?;
+    }
+    tracing::info!(
+        "[merge_exports] Saved {id}: {} messages, {} duplicates left out",
+        saved.total_messages,
+        merged.duplicate_messages
+    );
+    emit_pending_uploads(&window, &outbox);
+    Ok(saved)
+}
@@ -0,0 +1,48 @@
+//! Native notification when an export finishes while the app is in the
+//! background, so a long export doesn't need watching.
+//!
+//! Posted through tauri-plugin-notification, so it comes from ChatToMap
+//! and clicking it brings the app forward. A successful upload's results
+//! link rides along as the `results_url` extra, which the frontend's
+//! notification action handler opens where the platform reports clicks
+//! (see upload-history.ts); the results page has also been opened in the
+//! browser already (see export_commands.rs).
+
+use chat_to_map_desktop::export::state::ExportError;
+use tauri::Manager;
+use tauri_plugin_notification::NotificationExt;
+
+use crate::export_commands::ExportResult;
+
+/// Notify about a finished run unless the app window has focus (the
+/// progress screen already shows the outcome) or it was cancelled
+pub(crate) async fn notify_export_finished(
+    window: &tauri::Window,
+    result: &Result<ExportResult, ExportError>,
+) {
+    if window.is_focused().unwrap_or(false) {
+        return;
+    }
+    let mut notification = window.app_handle().notification().builder();
+    notification = match result {
+        Ok(result) => match &result.results_url {
+            Some(results_url) => notification
+                .title("Export complete")
+                .body(format!(
+                    "Uploaded {} chats. The results page is open in your browser.",
+                    result.chat_count
+                ))
+                .extra("results_url", results_url),
+            // Kept in the outbox (see `ExportRequest::keep_in_outbox`)
+            None => notification.title("Export complete").body(format!(
+                "Exported {} chats. The export is waiting in Pending Uploads.",
+                result.chat_count
+            )),
+        },
+        Err(ExportError::Cancelled { .. }) => return,
+        Err(e) => notification.title("Export failed").body(e.to_string()),
+    };
+    if let Err(e) = notification.show() {
+        tracing::warn!("[notify] Failed to show notification: {e}");
+    }
+}
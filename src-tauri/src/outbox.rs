@@ -0,0 +1,274 @@
+/*!
+ * Finished exports waiting to be uploaded.
+ *
+ * An export's zip lived in a temp directory that was deleted when the run
+ * ended, so an upload that failed (the network was down, say) threw the
+ * whole export away. Each finished zip is now [`save`]d to
+ * `<app_local_data_dir>/outbox/` before uploading, as `<id>.zip` next to an
+ * `<id>.json` [`PendingUpload`], and [`remove`]d once processing starts.
+ * Whatever is left (failed uploads, or ones interrupted by quitting) can be
+ * listed and uploaded again later, or at the next launch with
+ * `Settings::retry_uploads_on_launch`.
+ */
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::export::{metrics::ExportMetrics, queue::ExportRequest, ExportResult};
+
+const OUTBOX_DIRNAME: &str = "outbox";
+
+/// A saved export zip and what its upload needs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PendingUpload {
+    /// ID of the export that made the zip
+    pub id: String,
+    /// RFC 3339 timestamp of when the zip was saved
+    pub created_at: String,
+    /// Platform reported to the server (`export::UPLOAD_PLATFORM`, or
+    /// Telegram's)
+    pub upload_platform: String,
+    pub chat_count: usize,
+    #[serde(default)]
+    pub total_messages: usize,
+    /// The zip's name in the temp directory, reported to the server
+    #[serde(default)]
+    pub original_filename: Option<String>,
+    /// `ExportResult::zip_sha256`
+    pub zip_sha256: String,
+    pub size_bytes: u64,
+    /// Custom metadata forwarded with `complete_upload`
+    #[serde(default)]
+    pub metadata: BTreeMap<String, String>,
+    /// The export's throughput, completed by the upload's
+    #[serde(default)]
+    pub metrics: ExportMetrics,
+    /// Failed upload attempts so far
+    #[serde(default)]
+    pub attempts: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    /// The iMessage export that made the zip, to run again from a
+    /// `chattomap://job/<job_id>/retry` link (see deep_link.rs)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request: Option<ExportRequest>,
+}
+
+impl PendingUpload {
+    /// The saved zip in `outbox`
+    pub fn zip_path(&self, outbox: &Path) -> PathBuf {
+        outbox.join(format!("{}.zip", self.id))
+    }
+}
+
+/// The outbox directory in `app_local_data_dir`
+pub fn outbox_dir(app_local_data_dir: &Path) -> PathBuf {
+    app_local_data_dir.join(OUTBOX_DIRNAME)
+}
+
+/// Move `export`'s zip into `outbox` under `id`, to be uploaded from there.
+/// `request` is the export that made it, if it can be run again.
+pub fn save(
+    outbox: &Path,
+    id: &str,
+    export: &ExportResult,
+    upload_platform: &str,
+    metadata: &BTreeMap<String, String>,
+    request: Option<&ExportRequest>,
+) -> Result<PendingUpload, String> {
+    check_id(id)?;
+    std::fs::create_dir_all(outbox).map_err(|e| format!("Failed to create {outbox:?}: {e}"))?;
+    let size_bytes = std::fs::metadata(&export.zip_path)
+        .map_err(|e| format!("Failed to stat export zip: {e}"))?
+        .len();
+    let pending = PendingUpload {
+        id: id.to_string(),
+        created_at: chrono::Utc::now().to_rfc3339(),
+        upload_platform: upload_platform.to_string(),
+        chat_count: export.chat_count,
+        total_messages: export.total_messages,
+        original_filename: export
+            .zip_path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .map(str::to_string),
+        zip_sha256: export.zip_sha256.clone(),
+        size_bytes,
+        metadata: metadata.clone(),
+        metrics: export.metrics.clone(),
+        attempts: 0,
+        last_error: None,
+        request: request.cloned(),
+    };
+    move_file(&export.zip_path, &pending.zip_path(outbox))?;
+    write_record(outbox, &pending)?;
+    Ok(pending)
+}
+
+/// Every pending upload in `outbox`, oldest first. Unreadable records and
+/// those whose zip is gone are skipped.
+pub fn list(outbox: &Path) -> Vec<PendingUpload> {
+    let Ok(entries) = std::fs::read_dir(outbox) else {
+        return Vec::new();
+    };
+    let mut pending: Vec<PendingUpload> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|path| read_record(&path).ok())
+        .filter(|upload| upload.zip_path(outbox).exists())
+        .collect();
+    pending.sort_by(|a, b| a.created_at.cmp(&b.created_at));
+    pending
+}
+
+/// The pending upload `id` in `outbox`
+pub fn load(outbox: &Path, id: &str) -> Result<PendingUpload, String> {
+    check_id(id)?;
+    let pending = read_record(&record_path(outbox, id))
+        .map_err(|e| format!("No pending upload {id}: {e}"))?;
+    if !pending.zip_path(outbox).exists() {
+        return Err(format!("The export zip for pending upload {id} is missing"));
+    }
+    Ok(pending)
+}
+
+/// Note a failed attempt to upload `id`
+pub fn record_failure(outbox: &Path, id: &str, error: &str) -> Result<(), String> {
+    let mut pending = load(outbox, id)?;
+    pending.attempts += 1;
+    pending.last_error = Some(error.to_string());
+    write_record(outbox, &pending)
+}
+
+/// Delete the pending upload `id` (its zip and record)
+pub fn remove(outbox: &Path, id: &str) -> Result<(), String> {
+    check_id(id)?;
+    for path in [outbox.join(format!("{id}.zip")), record_path(outbox, id)] {
+        match std::fs::remove_file(&path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(format!("Failed to delete {path:?}: {e}")),
+        }
+    }
+    Ok(())
+}
+
+/// IDs name files, so they can't reach outside the outbox
+fn check_id(id: &str) -> Result<(), String> {
+    let valid = !id.is_empty()
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("Invalid pending upload ID {id:?}"))
+    }
+}
+
+fn record_path(outbox: &Path, id: &str) -> PathBuf {
+    outbox.join(format!("{id}.json"))
+}
+
+fn read_record(path: &Path) -> Result<PendingUpload, String> {
+    let contents =
+        std::fs::read_to_string(path).map_err(|e| format!("Failed to read {path:?}: {e}"))?;
+    serde_json::from_str(&contents).map_err(|e| format!("Failed to parse {path:?}: {e}"))
+}
+
+fn write_record(outbox: &Path, pending: &PendingUpload) -> Result<(), String> {
+    let path = record_path(outbox, &pending.id);
+    let json = serde_json::to_string_pretty(pending)
+        .map_err(|e| format!("Failed to encode pending upload: {e}"))?;
+    std::fs::write(&path, json).map_err(|e| format!("Failed to write {path:?}: {e}"))
+}
+
+/// Rename `from` to `to`, copying instead when they're on different volumes
+fn move_file(from: &Path, to: &Path) -> Result<(), String> {
+    if std::fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    std::fs::copy(from, to).map_err(|e| format!("Failed to save export zip to {to:?}: {e}"))?;
+    let _ = std::fs::remove_file(from);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::orphans::OrphanCounts;
+
+    fn export_result(temp_dir: tempfile::TempDir) -> ExportResult {
+        let zip_path = temp_dir.path().join("export.zip");
+        std::fs::write(&zip_path, b"zip bytes").unwrap();
+        ExportResult {
+            zip_path,
+            zip_sha256: "abc123".to_string(),
+            _temp_dir: temp_dir,
+            total_messages: 10,
+            chat_count: 2,
+            truncated_messages: 0,
+            deleted_messages: 0,
+            warnings: Vec::new(),
+            orphans: OrphanCounts::default(),
+            metrics: ExportMetrics::default(),
+        }
+    }
+
+    #[test]
+    fn saved_exports_wait_in_the_outbox_until_removed() {
+        let data_dir = tempfile::TempDir::new().unwrap();
+        let outbox = outbox_dir(data_dir.path());
+        let export = export_result(tempfile::TempDir::new().unwrap());
+        let metadata = BTreeMap::from([("source".to_string(), "test".to_string())]);
+
+        let request = ExportRequest {
+            chat_ids: vec![4, 2],
+            ..ExportRequest::default()
+        };
+        let pending = save(
+            &outbox,
+            "export-1",
+            &export,
+            "imessage",
+            &metadata,
+            Some(&request),
+        )
+        .unwrap();
+        assert!(!export.zip_path.exists());
+        assert_eq!(
+            std::fs::read(pending.zip_path(&outbox)).unwrap(),
+            b"zip bytes"
+        );
+        assert_eq!(pending.size_bytes, 9);
+        assert_eq!(pending.original_filename.as_deref(), Some("export.zip"));
+        assert_eq!(list(&outbox), std::slice::from_ref(&pending));
+
+        record_failure(&outbox, "export-1", "Upload failed: offline").unwrap();
+        let failed = load(&outbox, "export-1").unwrap();
+        assert_eq!(failed.attempts, 1);
+        assert_eq!(failed.last_error.as_deref(), Some("Upload failed: offline"));
+        assert_eq!(failed.metadata, metadata);
+        assert_eq!(failed.request, Some(request));
+
+        remove(&outbox, "export-1").unwrap();
+        assert!(list(&outbox).is_empty());
+        assert!(load(&outbox, "export-1").is_err());
+        // Removing twice is fine
+        remove(&outbox, "export-1").unwrap();
+    }
+
+    #[test]
+    fn ids_cannot_leave_the_outbox() {
+        let data_dir = tempfile::TempDir::new().unwrap();
+        let outbox = outbox_dir(data_dir.path());
+        assert!(load(&outbox, "../settings").is_err());
+        assert!(remove(&outbox, "../settings").is_err());
+        assert!(list(&outbox).is_empty());
+    }
+}
@@ -0,0 +1,98 @@
+/*!
+ * Full Disk Access detection.
+ *
+ * "Can't read chat.db" has two causes that need different help: macOS
+ * refused access (the user has to grant Full Disk Access), or there is no
+ * database at all (Messages was never used on this Mac, or on Windows no
+ * iPhone backup was found). The io error from opening the file tells them
+ * apart.
+ */
+
+use std::{io, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+/// Result of the Full Disk Access check
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FullDiskAccess {
+    /// The messages database can be read
+    Granted,
+    /// The database can't be read (on macOS, Full Disk Access is missing)
+    Denied,
+    /// There is no messages database at the expected path
+    DatabaseNotFound,
+    /// This platform has no default messages database
+    UnsupportedPlatform,
+}
+
+impl FullDiskAccess {
+    /// Status line and next steps, as shown by `ctm-cli check-access`
+    pub fn describe(self) -> &'static str {
+        match self {
+            Self::Granted => "Full Disk Access GRANTED\nThe messages database can be read.",
+            Self::Denied => {
+                "Full Disk Access DENIED\n\nTo grant access:\n\
+                 1. Open System Preferences > Privacy & Security > Full Disk Access\n\
+                 2. Add your terminal application (Terminal, iTerm2, etc.)"
+            }
+            Self::DatabaseNotFound => {
+                "Database file not found\n\
+                 Messages may never have been used on this Mac, or on Windows there is no \
+                 iPhone backup yet."
+            }
+            Self::UnsupportedPlatform => {
+                "Unsupported platform\nPass a copied chat.db or sms.db with --db instead."
+            }
+        }
+    }
+}
+
+/// Check whether the messages database at `db_path` can be read.
+///
+/// The file is opened without checking `db_path.exists()` first: on macOS,
+/// `exists()` calls stat(), which itself needs Full Disk Access for the
+/// TCC-protected ~/Library/Messages, so it reports a present database as
+/// missing. Opening fails with EPERM without access and ENOENT only when
+/// the file really is missing.
+pub fn check_full_disk_access(db_path: &Path) -> FullDiskAccess {
+    if !cfg!(any(target_os = "macos", target_os = "windows")) {
+        return FullDiskAccess::UnsupportedPlatform;
+    }
+    access_from_open(std::fs::File::open(db_path).map(drop))
+}
+
+fn access_from_open(result: io::Result<()>) -> FullDiskAccess {
+    match result {
+        Ok(()) => FullDiskAccess::Granted,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => FullDiskAccess::DatabaseNotFound,
+        Err(_) => FullDiskAccess::Denied,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn io_error_kind_distinguishes_missing_from_denied() {
+        let dir = TempDir::new().unwrap();
+        let db_path = dir.path().join("chat.db");
+        let open = |path: &Path| access_from_open(std::fs::File::open(path).map(drop));
+
+        assert_eq!(open(&db_path), FullDiskAccess::DatabaseNotFound);
+        std::fs::write(&db_path, b"").unwrap();
+        assert_eq!(open(&db_path), FullDiskAccess::Granted);
+        // What opening a TCC-protected file without access returns
+        assert_eq!(
+            access_from_open(Err(io::Error::from_raw_os_error(1))),
+            FullDiskAccess::Denied
+        );
+        assert_eq!(
+            serde_json::to_value(FullDiskAccess::DatabaseNotFound).unwrap(),
+            "database_not_found"
+        );
+    }
+}
@@ -0,0 +1,201 @@
+/*!
+ * Finding every chat with a person, by name.
+ *
+ * Someone typing "sam" wants the one-on-one thread with Samantha Lee, her
+ * old number's SMS thread and the group chats she is in, without picking
+ * them out of the list one by one. [`find_chats_for_contact`] matches the
+ * typed name against the contact names of every handle (each word of the
+ * query must start a word of the name, allowing one typo in longer words)
+ * and returns each matching person with all the chats they are in.
+ */
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    path::Path,
+};
+
+use imessage_database::tables::{chat_handle::ChatToHandle, table::Cacheable};
+use serde::{Deserialize, Serialize};
+
+use crate::export::SenderNames;
+
+/// People returned at most, best matches first
+const MAX_PEOPLE: usize = 20;
+
+/// Query words this long or longer may be off by one letter
+const TYPO_MIN_CHARS: usize = 4;
+
+/// A person whose name matches, and the chats they are in
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContactChats {
+    /// Contact name
+    pub name: String,
+    /// Chat ROWIDs with this person (see `ChatInfo::chat_ids`)
+    pub chat_ids: Vec<i32>,
+}
+
+/// Every chat with each person whose contact name matches `name`
+pub fn find_chats_for_contact(
+    name: &str,
+    custom_db_path: Option<&Path>,
+) -> Result<Vec<ContactChats>, String> {
+    let chat_db = crate::db_snapshot::open_chat_db(custom_db_path)?;
+    let db = &chat_db.conn;
+
+    let senders = SenderNames::load(db)?;
+    let chat_participants =
+        ChatToHandle::cache(db).map_err(|e| format!("Failed to load participants: {e}"))?;
+    Ok(find_people(name, &senders, &chat_participants))
+}
+
+fn find_people(
+    query: &str,
+    senders: &SenderNames,
+    chat_participants: &HashMap<i32, BTreeSet<i32>>,
+) -> Vec<ContactChats> {
+    let query = words(query);
+    if query.is_empty() {
+        return Vec::new();
+    }
+
+    let mut people: Vec<(u32, i32, &str)> = senders
+        .participants_map
+        .iter()
+        .filter(|(_, name)| !name.full.is_empty())
+        .filter_map(|(&person, name)| {
+            Some((match_score(&query, &name.full)?, person, name.full.as_str()))
+        })
+        .collect();
+    people.sort_by(|a, b| b.0.cmp(&a.0).then(a.2.cmp(b.2)));
+
+    let mut results: Vec<ContactChats> = Vec::new();
+    for (_, person, name) in people {
+        let mut chat_ids: Vec<i32> = chat_participants
+            .iter()
+            .filter(|(_, handles)| {
+                handles
+                    .iter()
+                    .any(|handle| senders.deduped_handles.get(handle) == Some(&person))
+            })
+            .map(|(&chat_id, _)| chat_id)
+            .collect();
+        if chat_ids.is_empty() {
+            continue;
+        }
+        chat_ids.sort_unstable();
+        // Handles no identifier ties together can still share a contact
+        if let Some(found) = results.iter_mut().find(|found| found.name == name) {
+            found.chat_ids.extend(chat_ids);
+            found.chat_ids.sort_unstable();
+            found.chat_ids.dedup();
+        } else if results.len() < MAX_PEOPLE {
+            results.push(ContactChats {
+                name: name.to_string(),
+                chat_ids,
+            });
+        }
+    }
+    results
+}
+
+/// Lowercase words of `text`
+fn words(text: &str) -> Vec<String> {
+    text.split_whitespace().map(str::to_lowercase).collect()
+}
+
+/// How well `query` matches `name`, or `None` if some query word matches
+/// no word of the name; exact words score higher than prefixes and typos
+fn match_score(query: &[String], name: &str) -> Option<u32> {
+    let name = words(name);
+    let mut score = 0;
+    for word in query {
+        score += name
+            .iter()
+            .filter_map(|part| {
+                if part == word {
+                    Some(3)
+                } else if part.starts_with(word.as_str()) {
+                    Some(2)
+                } else {
+                    let typo =
+                        word.chars().count() >= TYPO_MIN_CHARS && within_one_edit(word, part);
+                    typo.then_some(1)
+                }
+            })
+            .max()?;
+    }
+    Some(score)
+}
+
+/// Whether `word` becomes `part`, or a prefix of it, with at most one
+/// letter changed, added or dropped
+fn within_one_edit(word: &str, part: &str) -> bool {
+    let word: Vec<char> = word.chars().collect();
+    let part: Vec<char> = part.chars().collect();
+    let Some(mismatch) = word.iter().zip(&part).position(|(a, b)| a != b) else {
+        // One is a prefix of the other: a dropped last letter at most
+        return word.len() <= part.len() + 1;
+    };
+    let rest = |skip_word: usize, skip_part: usize| {
+        let word_rest = &word[(mismatch + skip_word).min(word.len())..];
+        let part_rest = &part[(mismatch + skip_part).min(part.len())..];
+        part_rest.starts_with(word_rest)
+    };
+    // Changed, added or dropped a letter
+    rest(1, 1) || rest(1, 0) || rest(0, 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{accounts::OwnerAccounts, contacts::Name};
+
+    fn senders(people: &[(i32, i32, &str)]) -> SenderNames {
+        let mut senders = SenderNames {
+            handles: HashMap::new(),
+            deduped_handles: HashMap::new(),
+            participants_map: HashMap::new(),
+            owner: OwnerAccounts::default(),
+        };
+        for &(handle, person, full) in people {
+            senders.deduped_handles.insert(handle, person);
+            let name = Name::from_opt(Some(full.to_string()), None).unwrap();
+            senders.participants_map.insert(person, name);
+        }
+        senders
+    }
+
+    #[test]
+    fn typed_names_find_every_chat_with_the_person() {
+        let senders = senders(&[
+            (1, 10, "Samantha Lee"),
+            (2, 10, "Samantha Lee"),
+            (3, 11, "Sam Climbing"),
+            (4, 12, "Alex Smith"),
+            // An email no identifier links to her phone numbers
+            (5, 13, "Samantha Lee"),
+        ]);
+        let chats = HashMap::from([
+            (100, BTreeSet::from([1])),
+            (101, BTreeSet::from([2])),
+            (102, BTreeSet::from([2, 4])),
+            (103, BTreeSet::from([3])),
+            (104, BTreeSet::from([4])),
+            (105, BTreeSet::from([5])),
+        ]);
+
+        let samantha = ContactChats {
+            name: "Samantha Lee".to_string(),
+            chat_ids: vec![100, 101, 102, 105],
+        };
+        let found = |query: &str| find_people(query, &senders, &chats);
+        assert_eq!(found("samantha lee"), [samantha]);
+        // A typo still finds her
+        assert_eq!(found("Smantha"), found("samantha lee"));
+
+        let names: Vec<String> = found("sam").into_iter().map(|person| person.name).collect();
+        assert_eq!(names, ["Sam Climbing", "Samantha Lee"]);
+        assert!(found("  ").is_empty());
+        assert!(found("jordan").is_empty());
+    }
+}
@@ -0,0 +1,67 @@
+//! Tauri commands that look at a selection before it is exported.
+//!
+//! `preflight_export` warns about chats that would export almost nothing.
+//! `estimate_export` predicts the zip size and how long the export and
+//! upload will take, from counting queries and the throughput of recent
+//! runs in the job history (see run_history.rs). `scan_addresses` counts
+//! likely addresses, places and map links in each chat, so users can pick
+//! the chats that will make the best map.
+
+use chat_to_map_desktop::{
+    analysis::{scan_addresses as lib_scan_addresses, ChatAddressScan},
+    export::{
+        estimate::{estimate_export as lib_estimate_export, DateRange, ExportEstimate},
+        preflight::{preflight_export as lib_preflight_export, ExportPreflight},
+        ExportOptions,
+    },
+};
+
+use crate::{run_history::recent_metrics, settings_commands::saved_db_path};
+
+/// Analyze selected chats before exporting and return content warnings
+#[tauri::command]
+pub async fn preflight_export(
+    chat_ids: Vec<i32>,
+    custom_db_path: Option<String>,
+    options: Option<ExportOptions>,
+    app_handle: tauri::AppHandle,
+) -> Result<ExportPreflight, String> {
+    let db_path = saved_db_path(custom_db_path, &app_handle)?;
+    let options = options.unwrap_or_default();
+    tokio::task::spawn_blocking(move || {
+        lib_preflight_export(&chat_ids, &options, db_path.as_deref())
+    })
+    .await
+    .map_err(|e| format!("Preflight task failed: {e}"))?
+}
+
+/// Estimate the size and duration of exporting the selected chats
+#[tauri::command]
+pub async fn estimate_export(
+    chat_ids: Vec<i32>,
+    custom_db_path: Option<String>,
+    date_range: Option<DateRange>,
+    app_handle: tauri::AppHandle,
+) -> Result<ExportEstimate, String> {
+    let db_path = saved_db_path(custom_db_path, &app_handle)?;
+    let date_range = date_range.unwrap_or_default();
+    let recent_runs = recent_metrics(&app_handle);
+    tokio::task::spawn_blocking(move || {
+        lib_estimate_export(&chat_ids, &date_range, &recent_runs, db_path.as_deref())
+    })
+    .await
+    .map_err(|e| format!("Estimate task failed: {e}"))?
+}
+
+/// Count address candidates in the selected chats, most promising first
+#[tauri::command]
+pub async fn scan_addresses(
+    chat_ids: Vec<i32>,
+    custom_db_path: Option<String>,
+    app_handle: tauri::AppHandle,
+) -> Result<Vec<ChatAddressScan>, String> {
+    let db_path = saved_db_path(custom_db_path, &app_handle)?;
+    tokio::task::spawn_blocking(move || lib_scan_addresses(&chat_ids, db_path.as_deref()))
+        .await
+        .map_err(|e| format!("Address scan task failed: {e}"))?
+}
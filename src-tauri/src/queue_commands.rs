@@ -0,0 +1,145 @@
+//! Tauri commands for the export queue: several export + upload jobs (say,
+//! one per year) that run one after another.
+//!
+//! `enqueue_export` adds a job and starts the worker if none is running.
+//! The worker waits for the pipeline to be free (a manual export may be
+//! running), runs the next job with its job ID as the export ID, so the
+//! usual `export-started` / `export-progress` events identify the job, and
+//! emits `export-queue-changed` with the full list whenever a job changes.
+//!
+//! A job with `upload_at` set exports straight away and then holds its zip
+//! until that time (see export/schedule.rs); later jobs wait behind it.
+
+use std::time::Duration;
+
+use chat_to_map_desktop::export::{
+    queue::{CancelOutcome, ExportRequest, QueuedExport},
+    state::ExportError,
+};
+use tauri::{Emitter, Manager};
+
+use crate::{
+    export_commands::{announce_export, run_export},
+    run_history::job_status,
+    AppState,
+};
+
+/// How often the worker checks whether a manual export has finished
+const BUSY_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Emit the current queue as `export-queue-changed`
+fn emit_queue(window: &tauri::Window, state: &AppState) {
+    let jobs = state.with_export_queue(|queue| queue.jobs().to_vec());
+    let _ = window.emit("export-queue-changed", jobs);
+}
+
+/// Queue an export + upload; returns the job ID
+#[tauri::command]
+pub fn enqueue_export(
+    request: ExportRequest,
+    app_handle: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    window: tauri::Window,
+) -> String {
+    enqueue(&app_handle, &state, window, request)
+}
+
+/// Add a job, starting the worker if none is running; returns the job ID
+pub(crate) fn enqueue(
+    app_handle: &tauri::AppHandle,
+    state: &AppState,
+    window: tauri::Window,
+    request: ExportRequest,
+) -> String {
+    let (job_id, start_worker) =
+        state.with_export_queue(|queue| (queue.enqueue(request), queue.claim_worker()));
+    tracing::info!("[queue] Queued export job {job_id}");
+    emit_queue(&window, state);
+    if start_worker {
+        tauri::async_runtime::spawn(run_queue(app_handle.clone(), window));
+    }
+    job_id
+}
+
+/// All jobs, oldest first
+#[tauri::command]
+pub fn list_export_queue(state: tauri::State<'_, AppState>) -> Vec<QueuedExport> {
+    state.with_export_queue(|queue| queue.jobs().to_vec())
+}
+
+/// Cancel a queued job, or stop it if it's running
+#[tauri::command]
+pub fn cancel_queued_export(
+    job_id: String,
+    state: tauri::State<'_, AppState>,
+    window: tauri::Window,
+) -> Result<(), ExportError> {
+    let outcome = state.with_export_queue(|queue| queue.cancel(&job_id));
+    match outcome {
+        Some(CancelOutcome::Dequeued) => {}
+        // The worker records the job as cancelled when its export stops
+        Some(CancelOutcome::Running) => state.export_manager().cancel(&job_id)?,
+        None => return Err(ExportError::NotCancellable { export_id: job_id }),
+    }
+    emit_queue(&window, &state);
+    Ok(())
+}
+
+/// Run a failed or cancelled job again
+#[tauri::command]
+pub fn retry_queued_export(
+    job_id: String,
+    app_handle: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    window: tauri::Window,
+) -> Result<(), String> {
+    let (retried, start_worker) = state.with_export_queue(|queue| {
+        let retried = queue.retry(&job_id);
+        (retried, retried && queue.claim_worker())
+    });
+    if !retried {
+        return Err(format!("Export job {job_id} can't be retried"));
+    }
+    tracing::info!("[queue] Retrying export job {job_id}");
+    emit_queue(&window, &state);
+    if start_worker {
+        tauri::async_runtime::spawn(run_queue(app_handle, window));
+    }
+    Ok(())
+}
+
+/// Remove finished jobs from the list
+#[tauri::command]
+pub fn clear_finished_exports(state: tauri::State<'_, AppState>, window: tauri::Window) {
+    state.with_export_queue(|queue| queue.clear_finished());
+    emit_queue(&window, &state);
+}
+
+/// Run queued jobs until none are left
+async fn run_queue(app_handle: tauri::AppHandle, window: tauri::Window) {
+    let state = app_handle.state::<AppState>().inner().clone();
+    loop {
+        while state.export_manager().status().export_id.is_some() {
+            tokio::time::sleep(BUSY_POLL_INTERVAL).await;
+        }
+        let Some((job_id, request)) = state.with_export_queue(|queue| queue.take_next()) else {
+            break;
+        };
+        // A manual export may have started since the check above
+        let Ok(run) = state.export_manager().start_with_id(job_id.clone()) else {
+            state.with_export_queue(|queue| queue.requeue(&job_id));
+            continue;
+        };
+        announce_export(&window, &run);
+        emit_queue(&window, &state);
+
+        let result = run_export(&app_handle, &state, &window, &run, request).await;
+        drop(run);
+
+        let status = job_status(&result);
+        tracing::info!("[queue] Export job {job_id} finished: {status:?}");
+        state.with_export_queue(|queue| queue.finish(&job_id, status));
+        emit_queue(&window, &state);
+    }
+    tracing::info!("[queue] Export queue drained");
+}
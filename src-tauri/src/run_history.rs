@@ -0,0 +1,74 @@
+//! Recording finished export runs: a `[metrics]` log line with the run's
+//! throughput and an entry in the local job history that `ctm-cli jobs`
+//! reads (see the library's job_history.rs). Recent runs' metrics also feed
+//! export estimates, and failed runs are reported (see error_reporting.rs).
+
+use chat_to_map_desktop::{
+    export::{
+        metrics::ExportMetrics,
+        queue::JobStatus,
+        state::{ExportError, ExportRun},
+    },
+    job_history::{self, JobRecord},
+};
+use tauri::Manager;
+
+use crate::{error_reporting, export_commands::ExportResult};
+
+/// Runs that export estimates learn throughput from
+const ESTIMATE_RUNS: usize = 10;
+
+/// How a finished run is listed in the queue and job history
+pub(crate) fn job_status(result: &Result<ExportResult, ExportError>) -> JobStatus {
+    match result {
+        Ok(result) => JobStatus::Completed {
+            results_url: result.results_url.clone(),
+        },
+        Err(ExportError::Cancelled { .. }) => JobStatus::Cancelled,
+        Err(e) => JobStatus::Failed {
+            error: e.to_string(),
+        },
+    }
+}
+
+/// Log a finished run's metrics, add it to the local job history and
+/// report a failure (if error reporting is on)
+pub(crate) fn record_run(
+    app_handle: &tauri::AppHandle,
+    run: &ExportRun,
+    result: &Result<ExportResult, ExportError>,
+) {
+    let metrics = result.as_ref().ok().map(|result| result.metrics.clone());
+    if let Some(metrics) = &metrics {
+        tracing::info!("[metrics] Export {}: {}", run.id(), metrics.summary());
+    }
+    let record = JobRecord::new(run.id(), job_status(result), metrics);
+    let appended = app_handle
+        .path()
+        .app_local_data_dir()
+        .map_err(|e| format!("Failed to resolve app local data dir: {e}"))
+        .and_then(|dir| job_history::append(&dir, &record));
+    if let Err(e) = appended {
+        tracing::warn!("[metrics] Failed to record export {}: {e}", run.id());
+    }
+    if let Err(e) = result {
+        error_reporting::report_export_error(app_handle, e);
+    }
+}
+
+/// Metrics of the latest runs that finished an archive, newest first; empty
+/// if the history can't be read
+pub(crate) fn recent_metrics(app_handle: &tauri::AppHandle) -> Vec<ExportMetrics> {
+    let records = app_handle
+        .path()
+        .app_local_data_dir()
+        .map_err(|e| format!("Failed to resolve app local data dir: {e}"))
+        .and_then(|dir| job_history::load(&dir))
+        .unwrap_or_default();
+    records
+        .into_iter()
+        .rev()
+        .filter_map(|record| record.metrics)
+        .take(ESTIMATE_RUNS)
+        .collect()
+}
@@ -0,0 +1,248 @@
+/*!
+ * Message search across all chats.
+ *
+ * Lets users find the chat that mentions "Airbnb in Lisbon" before they
+ * pick what to export. chat.db has no full-text index, so this is a SQL
+ * `LIKE` over `message.text` (case-insensitive for ASCII, like SQLite
+ * itself); messages whose text only lives in `attributedBody` are not
+ * matched. Tapbacks are skipped since they quote the message they react to.
+ *
+ * Snippets come back as plain-text parts with the matches flagged, so the
+ * frontend can highlight them without rendering any message HTML.
+ */
+
+use std::path::Path;
+
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+use crate::export::{format_timestamp, SenderNames, TimestampUnit};
+
+/// Results returned when the caller doesn't pass a limit
+pub const DEFAULT_SEARCH_LIMIT: usize = 50;
+
+/// Upper bound on results, whatever the caller asks for
+const MAX_SEARCH_LIMIT: usize = 500;
+
+/// Characters of context kept on each side of the first match
+const SNIPPET_CONTEXT_CHARS: usize = 40;
+
+/// A piece of a search snippet; `highlight` marks query matches
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnippetPart {
+    pub text: String,
+    pub highlight: bool,
+}
+
+/// A message matching a search query
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchResult {
+    /// Chat ROWID the message belongs to (see `ChatInfo::chat_ids`)
+    pub chat_id: i32,
+    /// Resolved sender name ("Me" for the device owner)
+    pub sender: String,
+    pub is_from_me: bool,
+    /// ISO 8601 timestamp
+    pub timestamp: String,
+    /// Text around the first match, split into highlighted/plain parts
+    pub snippet: Vec<SnippetPart>,
+}
+
+/// Search message text in the iMessage database, newest first
+pub fn search_messages(
+    query: &str,
+    limit: Option<usize>,
+    custom_db_path: Option<&Path>,
+) -> Result<Vec<SearchResult>, String> {
+    let chat_db = crate::db_snapshot::open_chat_db(custom_db_path)?;
+    let db = &chat_db.conn;
+
+    let senders = SenderNames::load(db)?;
+
+    search(
+        db,
+        query,
+        limit.unwrap_or(DEFAULT_SEARCH_LIMIT),
+        |is_from_me, handle_id| senders.name(is_from_me, handle_id),
+    )
+}
+
+/// Run the search against an open database
+fn search(
+    db: &Connection,
+    query: &str,
+    limit: usize,
+    sender_name: impl Fn(bool, Option<i32>) -> String,
+) -> Result<Vec<SearchResult>, String> {
+    let query = query.trim();
+    if query.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut stmt = db
+        .prepare(
+            "SELECT cmj.chat_id, m.date, m.is_from_me, m.handle_id, m.text
+             FROM message m
+             JOIN chat_message_join cmj ON cmj.message_id = m.ROWID
+             WHERE m.text LIKE ?1 ESCAPE '\\'
+               AND COALESCE(m.associated_message_type, 0) NOT BETWEEN 2000 AND 3007
+             ORDER BY m.date DESC
+             LIMIT ?2",
+        )
+        .map_err(|e| format!("Failed to search messages: {e}"))?;
+    let rows = stmt
+        .query_map(
+            (like_pattern(query), limit.min(MAX_SEARCH_LIMIT) as i64),
+            |row| {
+                Ok((
+                    row.get::<_, i32>(0)?,
+                    row.get::<_, i64>(1)?,
+                    row.get::<_, bool>(2)?,
+                    row.get::<_, Option<i32>>(3)?,
+                    row.get::<_, String>(4)?,
+                ))
+            },
+        )
+        .map_err(|e| format!("Failed to search messages: {e}"))?;
+
+    let unit = TimestampUnit::detect(db);
+    let results = rows
+        .flatten()
+        .map(
+            |(chat_id, date, is_from_me, handle_id, text)| SearchResult {
+                chat_id,
+                sender: sender_name(is_from_me, handle_id),
+                is_from_me,
+                timestamp: format_timestamp(date, unit),
+                snippet: snippet(&text, query),
+            },
+        )
+        .collect();
+    Ok(results)
+}
+
+/// `%query%` with LIKE wildcards in the query escaped
+fn like_pattern(query: &str) -> String {
+    let mut pattern = String::from("%");
+    for c in query.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            pattern.push('\\');
+        }
+        pattern.push(c);
+    }
+    pattern.push('%');
+    pattern
+}
+
+/// Cut `text` to the context around the first match of `query` and split
+/// it into parts, flagging every (ASCII case-insensitive) match
+fn snippet(text: &str, query: &str) -> Vec<SnippetPart> {
+    // ASCII lowercasing keeps byte offsets identical to `text`
+    let haystack = text.to_ascii_lowercase();
+    let needle = query.to_ascii_lowercase();
+    let Some(first) = haystack.find(&needle) else {
+        return vec![plain(text.to_string())];
+    };
+
+    let start = text[..first]
+        .char_indices()
+        .rev()
+        .nth(SNIPPET_CONTEXT_CHARS - 1)
+        .map_or(0, |(i, _)| i);
+    let after = first + needle.len();
+    let end = text[after..]
+        .char_indices()
+        .nth(SNIPPET_CONTEXT_CHARS)
+        .map_or(text.len(), |(i, _)| after + i);
+
+    let mut parts = Vec::new();
+    if start > 0 {
+        parts.push(plain("…".to_string()));
+    }
+    let mut cursor = start;
+    for (offset, _) in haystack[start..end].match_indices(&needle) {
+        let match_start = start + offset;
+        if match_start > cursor {
+            parts.push(plain(text[cursor..match_start].to_string()));
+        }
+        cursor = match_start + needle.len();
+        parts.push(SnippetPart {
+            text: text[match_start..cursor].to_string(),
+            highlight: true,
+        });
+    }
+    if end > cursor {
+        parts.push(plain(text[cursor..end].to_string()));
+    }
+    if end < text.len() {
+        parts.push(plain("…".to_string()));
+    }
+    parts
+}
+
+fn plain(text: String) -> SnippetPart {
+    SnippetPart {
+        text,
+        highlight: false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{ChatBuilder, HandleBuilder, MessageBuilder, TestIMessageDb};
+
+    #[test]
+    fn finds_matches_newest_first_with_resolved_senders() {
+        let mut db = TestIMessageDb::new().unwrap();
+        let handle = db.handle(HandleBuilder::new("+15551234567")).unwrap();
+        let chat = db
+            .chat(ChatBuilder::new("iMessage;-;+15551234567"))
+            .unwrap();
+        for (text, date) in [
+            ("Booked the Airbnb in Lisbon!", 100),
+            ("no match here", 200),
+            ("airbnb IN LISBON again", 300),
+            ("100% sure", 400),
+        ] {
+            db.message(
+                MessageBuilder::new()
+                    .text(text)
+                    .handle(handle)
+                    .chat(chat)
+                    .date(date),
+            )
+            .unwrap();
+        }
+
+        let sender = |is_from_me: bool, handle_id: Option<i32>| {
+            format!("{is_from_me}:{}", handle_id.unwrap_or_default())
+        };
+        let results = search(db.conn(), " airbnb in lisbon ", 10, sender).unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].chat_id, chat);
+        assert_eq!(results[0].sender, format!("false:{handle}"));
+        assert_eq!(results[0].snippet[0].text, "airbnb IN LISBON");
+        assert!(results[0].snippet[0].highlight);
+
+        // LIKE wildcards in the query are literal
+        assert_eq!(search(db.conn(), "0%", 10, sender).unwrap().len(), 1);
+        assert!(search(db.conn(), "  ", 10, sender).unwrap().is_empty());
+    }
+
+    #[test]
+    fn snippet_trims_context_and_flags_every_match() {
+        let text = format!("{}Lisbon and lisbon{}", "a".repeat(60), "b".repeat(60));
+        let parts = snippet(&text, "LISBON");
+        let highlighted: Vec<&str> = parts
+            .iter()
+            .filter(|p| p.highlight)
+            .map(|p| p.text.as_str())
+            .collect();
+
+        assert_eq!(highlighted, vec!["Lisbon", "lisbon"]);
+        assert_eq!(parts.first().unwrap().text, "…");
+        assert_eq!(parts.last().unwrap().text, "…");
+        assert_eq!(parts[1].text, "a".repeat(SNIPPET_CONTEXT_CHARS));
+    }
+}
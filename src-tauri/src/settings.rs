@@ -0,0 +1,190 @@
+/*!
+ * Persisted user settings.
+ *
+ * Stored as JSON in `<app_local_data_dir>/settings.json`, next to the
+ * visitor ID. Loading is best-effort: a missing or unreadable file gives
+ * the defaults so a corrupt settings file never blocks the app, and fields
+ * added later default when absent from an older file.
+ */
+
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    auto_export::AutoExportSettings, db_sources::DbSource, exclusions::ExclusionRule,
+    export::redaction::RedactionConfig, updates::UpdateChannel,
+};
+
+const SETTINGS_FILENAME: &str = "settings.json";
+
+/// User settings persisted across launches
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Copied chat.db to read instead of the system database
+    pub custom_db_path: Option<String>,
+    /// UI theme: "light", "dark", or "system" (`None` = follow the system)
+    pub theme: Option<String>,
+    /// WEB host override for the results page (see debug panel)
+    pub server_host_override: Option<String>,
+    /// API host override for Convex HTTP actions (see debug panel)
+    pub api_host_override: Option<String>,
+    /// Log level: "error", "warn", "info", "debug" or "trace" (`None` =
+    /// info); the `CTM_LOG` environment variable overrides it
+    pub log_level: Option<String>,
+    /// Chat list IDs selected at the last export, restored on launch
+    pub last_selected_chat_ids: Vec<i32>,
+    /// Chats matching these rules are flagged in the list and never exported
+    pub exclusion_rules: Vec<ExclusionRule>,
+    /// vCard (`.vcf`) file read for contact names alongside Contacts
+    pub contacts_vcf_path: Option<String>,
+    /// Last digits compared when a phone number matches no contact exactly
+    /// (7 to 9; 0 turns this off; `None` = 9)
+    pub phone_suffix_digits: Option<usize>,
+    /// Seconds to wait for an upload server to accept a connection
+    /// (`None` = 15)
+    pub upload_connect_timeout_secs: Option<u64>,
+    /// Seconds an upload request may wait for more of a response
+    /// (`None` = 60)
+    pub upload_read_timeout_secs: Option<u64>,
+    /// Seconds the whole upload may take (`None` = 2 hours)
+    pub upload_deadline_secs: Option<u64>,
+    /// Proxy for uploads, like `http://proxy.example.com:8080` (`None` =
+    /// the `HTTPS_PROXY` / `HTTP_PROXY` environment variables)
+    pub upload_proxy_url: Option<String>,
+    /// Upload exports left in the outbox (see outbox.rs) when the app starts
+    pub retry_uploads_on_launch: bool,
+    /// Releases to update to (`None` = the channel the build was made for)
+    pub update_channel: Option<UpdateChannel>,
+    /// Redaction for exports that don't ask for their own
+    pub redaction_defaults: RedactionConfig,
+    /// Send anonymous crash and error reports (see telemetry.rs)
+    pub error_reporting: bool,
+    /// Export the last selection by itself every so often (see
+    /// auto_export.rs)
+    pub auto_export: AutoExportSettings,
+    /// Open the app at login (see login_item.rs)
+    pub launch_at_login: bool,
+    /// Other chat.db files to read, by name (see db_sources.rs)
+    pub db_sources: Vec<DbSource>,
+    /// ID of the source the chat list and exports read (`None` = the Mac's
+    /// own chat.db, or `custom_db_path`)
+    pub selected_source_id: Option<String>,
+}
+
+/// The settings the Preferences window edits, read and saved together
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Preferences {
+    pub custom_db_path: Option<String>,
+    pub server_host_override: Option<String>,
+    pub api_host_override: Option<String>,
+    pub theme: Option<String>,
+    pub redaction_defaults: RedactionConfig,
+    pub launch_at_login: bool,
+}
+
+impl Settings {
+    /// The settings the Preferences window edits
+    pub fn preferences(&self) -> Preferences {
+        Preferences {
+            custom_db_path: self.custom_db_path.clone(),
+            server_host_override: self.server_host_override.clone(),
+            api_host_override: self.api_host_override.clone(),
+            theme: self.theme.clone(),
+            redaction_defaults: self.redaction_defaults.clone(),
+            launch_at_login: self.launch_at_login,
+        }
+    }
+
+    /// Replace the settings `preferences` covers, keeping the rest
+    pub fn set_preferences(&mut self, preferences: Preferences) {
+        self.custom_db_path = preferences.custom_db_path;
+        self.server_host_override = preferences.server_host_override;
+        self.api_host_override = preferences.api_host_override;
+        self.theme = preferences.theme;
+        self.redaction_defaults = preferences.redaction_defaults;
+        self.launch_at_login = preferences.launch_at_login;
+    }
+
+    /// Read settings from `app_local_data_dir`, falling back to defaults
+    pub fn load(app_local_data_dir: &Path) -> Self {
+        let path = app_local_data_dir.join(SETTINGS_FILENAME);
+        let Ok(contents) = std::fs::read_to_string(&path) else {
+            return Self::default();
+        };
+        serde_json::from_str(&contents).unwrap_or_else(|e| {
+            tracing::warn!("[settings] Ignoring unreadable {:?}: {e}", path);
+            Self::default()
+        })
+    }
+
+    /// Write settings to `app_local_data_dir`, creating it if needed
+    pub fn save(&self, app_local_data_dir: &Path) -> Result<(), String> {
+        std::fs::create_dir_all(app_local_data_dir)
+            .map_err(|e| format!("Failed to create settings directory: {e}"))?;
+        let contents = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize settings: {e}"))?;
+        std::fs::write(app_local_data_dir.join(SETTINGS_FILENAME), contents)
+            .map_err(|e| format!("Failed to write settings: {e}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn settings_round_trip_and_default_when_missing_or_corrupt() {
+        let dir = TempDir::new().unwrap();
+        assert_eq!(Settings::load(dir.path()), Settings::default());
+
+        let settings = Settings {
+            custom_db_path: Some("/tmp/chat.db".to_string()),
+            last_selected_chat_ids: vec![3, 1],
+            exclusion_rules: vec![ExclusionRule::ShortCode],
+            ..Default::default()
+        };
+        settings.save(&dir.path().join("nested")).unwrap();
+        assert_eq!(Settings::load(&dir.path().join("nested")), settings);
+
+        std::fs::write(dir.path().join(SETTINGS_FILENAME), "{not json").unwrap();
+        assert_eq!(Settings::load(dir.path()), Settings::default());
+    }
+
+    #[test]
+    fn older_settings_files_fill_missing_fields_with_defaults() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(
+            dir.path().join(SETTINGS_FILENAME),
+            r#"{"exclusion_rules":[{"kind":"short_code"}]}"#,
+        )
+        .unwrap();
+
+        let settings = Settings::load(dir.path());
+        assert_eq!(settings.exclusion_rules, vec![ExclusionRule::ShortCode]);
+        assert_eq!(settings.custom_db_path, None);
+        assert!(settings.last_selected_chat_ids.is_empty());
+    }
+
+    #[test]
+    fn preferences_replace_only_their_own_fields() {
+        let mut settings = Settings {
+            theme: Some("dark".to_string()),
+            exclusion_rules: vec![ExclusionRule::ShortCode],
+            ..Default::default()
+        };
+        let mut preferences = settings.preferences();
+        assert_eq!(preferences.theme.as_deref(), Some("dark"));
+
+        preferences.theme = None;
+        preferences.server_host_override = Some("http://localhost:5173".to_string());
+        preferences.redaction_defaults.pseudonymize_senders = true;
+        settings.set_preferences(preferences.clone());
+        assert_eq!(settings.preferences(), preferences);
+        assert_eq!(settings.exclusion_rules, vec![ExclusionRule::ShortCode]);
+    }
+}
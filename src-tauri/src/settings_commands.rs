@@ -0,0 +1,188 @@
+//! Tauri commands for persisted settings.
+//!
+//! Settings live in `<app_local_data_dir>/settings.json` (see
+//! `chat_to_map_desktop::settings`). `list_chats` and `export_and_upload`
+//! read exclusion rules and the saved chat.db path from there, so the
+//! frontend never has to pass them along. Saved host overrides are copied
+//! into `crate::AppState` at startup and whenever settings are saved.
+
+use std::path::PathBuf;
+
+use chat_to_map_desktop::{
+    contacts::ContactsIndex,
+    exclusions::{matching_chats, ExclusionRule},
+    logging,
+    login_item::{self, LoginItemStatus},
+    settings::{Preferences, Settings},
+    telemetry,
+    upload::check_proxy_url,
+    ChatInfo,
+};
+use tauri::Manager;
+
+use crate::AppState;
+
+/// Load settings from the app's local data directory
+pub fn load_settings(app_handle: &tauri::AppHandle) -> Result<Settings, String> {
+    Ok(Settings::load(&app_local_data_dir(app_handle)?))
+}
+
+pub fn app_local_data_dir(app_handle: &tauri::AppHandle) -> Result<PathBuf, String> {
+    app_handle
+        .path()
+        .app_local_data_dir()
+        .map_err(|e| format!("Failed to resolve app local data dir: {e}"))
+}
+
+/// The chat.db to read: the caller's path, else the selected source's or
+/// the saved custom path
+pub fn db_path_or_saved(custom_db_path: Option<String>, settings: &Settings) -> Option<PathBuf> {
+    settings.db_path_or_saved(custom_db_path)
+}
+
+/// [`db_path_or_saved`] with the saved settings, for commands that only
+/// need the path
+pub fn saved_db_path(
+    custom_db_path: Option<String>,
+    app_handle: &tauri::AppHandle,
+) -> Result<Option<PathBuf>, String> {
+    Ok(db_path_or_saved(
+        custom_db_path,
+        &load_settings(app_handle)?,
+    ))
+}
+
+/// Copy saved host overrides into app state, where uploads read them
+pub fn apply_host_overrides(state: &AppState, settings: &Settings) {
+    state.update_host_overrides(|overrides| {
+        overrides.server_host_override = settings.server_host_override.clone();
+        overrides.api_host_override = settings.api_host_override.clone();
+    });
+}
+
+/// Get all saved settings
+#[tauri::command]
+pub fn get_settings(app_handle: tauri::AppHandle) -> Result<Settings, String> {
+    load_settings(&app_handle)
+}
+
+/// Replace all saved settings
+#[tauri::command]
+pub fn set_settings(
+    settings: Settings,
+    app_handle: tauri::AppHandle,
+    state: tauri::State<AppState>,
+) -> Result<(), String> {
+    tracing::info!("[set_settings] Saving settings");
+    if let Some(url) = settings.upload_proxy_url.as_deref() {
+        if !url.trim().is_empty() {
+            check_proxy_url(url)?;
+        }
+    }
+    let dir = app_local_data_dir(&app_handle)?;
+    if settings.launch_at_login != Settings::load(&dir).launch_at_login {
+        login_item::set_launch_at_login(settings.launch_at_login)?;
+    }
+    settings.save(&dir)?;
+    apply_host_overrides(&state, &settings);
+    logging::set_level(logging::effective_level(settings.log_level.as_deref()));
+    telemetry::set_enabled(settings.error_reporting);
+    Ok(())
+}
+
+/// Get the settings the Preferences window edits
+#[tauri::command]
+pub fn get_preferences(app_handle: tauri::AppHandle) -> Result<Preferences, String> {
+    Ok(load_settings(&app_handle)?.preferences())
+}
+
+/// Save the Preferences window's settings, keeping all others
+#[tauri::command]
+pub fn set_preferences(
+    preferences: Preferences,
+    app_handle: tauri::AppHandle,
+    state: tauri::State<AppState>,
+) -> Result<(), String> {
+    tracing::info!("[set_preferences] Saving preferences");
+    let dir = app_local_data_dir(&app_handle)?;
+    let mut settings = Settings::load(&dir);
+    if preferences.launch_at_login != settings.launch_at_login {
+        login_item::set_launch_at_login(preferences.launch_at_login)?;
+    }
+    settings.set_preferences(preferences);
+    settings.save(&dir)?;
+    apply_host_overrides(&state, &settings);
+    Ok(())
+}
+
+/// Open the app at login (or stop), so scheduled exports run; returns the
+/// login item's status afterwards
+#[tauri::command]
+pub fn set_launch_at_login(
+    enabled: bool,
+    app_handle: tauri::AppHandle,
+) -> Result<LoginItemStatus, String> {
+    tracing::info!("[set_launch_at_login] {enabled}");
+    let status = login_item::set_launch_at_login(enabled)?;
+    let dir = app_local_data_dir(&app_handle)?;
+    let mut settings = Settings::load(&dir);
+    settings.launch_at_login = enabled;
+    settings.save(&dir)?;
+    Ok(status)
+}
+
+/// Whether the app opens at login, as the system sees it (the user may
+/// have changed it in System Settings)
+#[tauri::command]
+pub fn get_login_item_status() -> LoginItemStatus {
+    login_item::login_item_status()
+}
+
+/// Get the saved chat exclusion rules
+#[tauri::command]
+pub fn get_exclusion_rules(app_handle: tauri::AppHandle) -> Result<Vec<ExclusionRule>, String> {
+    Ok(load_settings(&app_handle)?.exclusion_rules)
+}
+
+/// Replace the saved chat exclusion rules
+#[tauri::command]
+pub fn set_exclusion_rules(
+    rules: Vec<ExclusionRule>,
+    app_handle: tauri::AppHandle,
+) -> Result<(), String> {
+    tracing::info!("[set_exclusion_rules] Saving {} rules", rules.len());
+    let dir = app_local_data_dir(&app_handle)?;
+    let mut settings = Settings::load(&dir);
+    settings.exclusion_rules = rules;
+    settings.save(&dir)
+}
+
+/// Read contact names from a vCard file as well as Contacts (`None` stops);
+/// returns how many phone numbers and emails it names
+#[tauri::command]
+pub fn set_contacts_vcf_path(
+    path: Option<String>,
+    app_handle: tauri::AppHandle,
+) -> Result<usize, String> {
+    tracing::info!("[set_contacts_vcf_path] Saving {:?}", path);
+    let entries = match &path {
+        Some(path) => ContactsIndex::build_from_vcard(&PathBuf::from(path))?.len(),
+        None => 0,
+    };
+    let dir = app_local_data_dir(&app_handle)?;
+    let mut settings = Settings::load(&dir);
+    settings.contacts_vcf_path = path;
+    settings.save(&dir)?;
+    Ok(entries)
+}
+
+/// List the chats a rule currently matches (to preview it before saving)
+#[tauri::command]
+pub fn list_exclusion_matches(
+    rule: ExclusionRule,
+    custom_db_path: Option<String>,
+    app_handle: tauri::AppHandle,
+) -> Result<Vec<ChatInfo>, String> {
+    let path = saved_db_path(custom_db_path, &app_handle)?;
+    matching_chats(&rule, path.as_deref())
+}
@@ -0,0 +1,191 @@
+/*!
+ * "Shared with You" link extraction.
+ *
+ * macOS promotes links from Messages into the "Shared with You" shelves in
+ * Safari, Maps, etc. Messages records which messages were promoted by
+ * populating `message.syndication_ranges`. That curated list (restaurants,
+ * hotels, Airbnb listings) is exactly what users want mapped, so exports can
+ * optionally include it as a separate `shared_links.json`.
+ *
+ * Databases from before macOS 13 have no `syndication_ranges` column; the
+ * reader returns an empty list for those rather than failing the export.
+ */
+
+use imessage_database::tables::messages::Message;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+/// A single "Shared with You" link
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SharedLink {
+    /// The shared URL
+    pub url: String,
+    /// Resolved name (or phone/email) of whoever shared the link
+    pub sharer: String,
+    /// Whether the device owner shared the link
+    pub is_from_me: bool,
+    /// ISO 8601 timestamp of the message carrying the link
+    pub timestamp: String,
+    /// Chat ROWID the link was shared in
+    pub chat_id: i32,
+    /// GUID of the message carrying the link
+    pub message_guid: String,
+}
+
+/// Read "Shared with You" links for the given chats.
+///
+/// `sender_name` resolves the sharer for a message (the export passes its
+/// contacts-aware resolver) and `format_date` converts the raw Apple
+/// timestamp, so links use the same names and time format as messages.
+pub fn read_shared_links(
+    db: &Connection,
+    chat_ids: &[i32],
+    sender_name: impl Fn(&Message) -> String,
+    format_date: impl Fn(i64) -> String,
+) -> Result<Vec<SharedLink>, String> {
+    if chat_ids.is_empty() || !has_syndication_column(db) {
+        return Ok(Vec::new());
+    }
+
+    let placeholders = vec!["?"; chat_ids.len()].join(", ");
+    let sql = format!(
+        "SELECT m.guid
+         FROM message m
+         JOIN chat_message_join cmj ON cmj.message_id = m.ROWID
+         WHERE cmj.chat_id IN ({placeholders})
+           AND m.syndication_ranges IS NOT NULL
+           AND m.syndication_ranges != ''
+         ORDER BY m.date"
+    );
+    let mut stmt = db
+        .prepare(&sql)
+        .map_err(|e| format!("Failed to query shared links: {e}"))?;
+    let guids: Vec<String> = stmt
+        .query_map(rusqlite::params_from_iter(chat_ids), |row| row.get(0))
+        .map_err(|e| format!("Failed to query shared links: {e}"))?
+        .flatten()
+        .collect();
+
+    let mut links = Vec::new();
+    for guid in guids {
+        let Ok(mut message) = Message::from_guid(&guid, db) else {
+            tracing::warn!("[shared_links] Could not load message {guid}");
+            continue;
+        };
+        let _ = message.generate_text(db);
+        let Some(chat_id) = message.chat_id else {
+            continue;
+        };
+        let sharer = sender_name(&message);
+        let timestamp = format_date(message.date);
+        for url in extract_urls(message.text.as_deref().unwrap_or_default()) {
+            links.push(SharedLink {
+                url,
+                sharer: sharer.clone(),
+                is_from_me: message.is_from_me,
+                timestamp: timestamp.clone(),
+                chat_id,
+                message_guid: message.guid.clone(),
+            });
+        }
+    }
+
+    Ok(links)
+}
+
+/// Extract http(s) URLs from free text, trimming trailing punctuation
+pub fn extract_urls(text: &str) -> Vec<String> {
+    text.split_whitespace()
+        .filter_map(|token| {
+            let start = token.find("https://").or_else(|| token.find("http://"))?;
+            let url = token[start..].trim_end_matches(|c: char| {
+                matches!(
+                    c,
+                    '.' | ',' | ';' | ':' | '!' | '?' | ')' | ']' | '"' | '\''
+                )
+            });
+            (url.len() > "https://".len()).then(|| url.to_string())
+        })
+        .collect()
+}
+
+/// Check whether the message table has the macOS 13+ `syndication_ranges` column
+fn has_syndication_column(db: &Connection) -> bool {
+    db.prepare("SELECT syndication_ranges FROM message LIMIT 0")
+        .is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{ChatBuilder, HandleBuilder, MessageBuilder, TestIMessageDb};
+
+    #[test]
+    fn extract_urls_finds_links_and_trims_punctuation() {
+        let urls = extract_urls("Try https://maps.apple.com/?q=Cafe, or (http://example.com/a).");
+        assert_eq!(
+            urls,
+            vec!["https://maps.apple.com/?q=Cafe", "http://example.com/a"]
+        );
+    }
+
+    #[test]
+    fn extract_urls_ignores_plain_text() {
+        assert!(extract_urls("no links here, just https:// on its own").is_empty());
+    }
+
+    #[test]
+    fn reads_only_promoted_links_from_selected_chats() {
+        let mut db = TestIMessageDb::new().unwrap();
+        let alice = db.handle(HandleBuilder::new("+15551234567")).unwrap();
+        let chat = db
+            .chat(ChatBuilder::new("iMessage;-;+15551234567"))
+            .unwrap();
+        let other_chat = db.chat(ChatBuilder::new("iMessage;-;other")).unwrap();
+        db.message(
+            MessageBuilder::new()
+                .text("Dinner here? https://example.com/restaurant")
+                .handle(alice)
+                .chat(chat)
+                .shared_with_you(),
+        )
+        .unwrap();
+        db.message(
+            MessageBuilder::new()
+                .text("Not promoted https://example.com/ignored")
+                .handle(alice)
+                .chat(chat),
+        )
+        .unwrap();
+        db.message(
+            MessageBuilder::new()
+                .text("https://example.com/other-chat")
+                .handle(alice)
+                .chat(other_chat)
+                .shared_with_you(),
+        )
+        .unwrap();
+
+        let links = read_shared_links(
+            db.conn(),
+            &[chat],
+            |_| "Alice".to_string(),
+            |d| d.to_string(),
+        )
+        .unwrap();
+
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].url, "https://example.com/restaurant");
+        assert_eq!(links[0].sharer, "Alice");
+        assert_eq!(links[0].chat_id, chat);
+    }
+
+    #[test]
+    fn missing_syndication_column_yields_no_links() {
+        let db = Connection::open_in_memory().unwrap();
+        db.execute_batch("CREATE TABLE message (ROWID INTEGER PRIMARY KEY, guid TEXT);")
+            .unwrap();
+        let links = read_shared_links(&db, &[1], |_| String::new(), |d| d.to_string()).unwrap();
+        assert!(links.is_empty());
+    }
+}
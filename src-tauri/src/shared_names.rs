@@ -0,0 +1,247 @@
+/*!
+ * Names people shared with Messages' "Share Name and Photo".
+ *
+ * Someone who isn't in Contacts can still have shared their name with the
+ * user, and Messages keeps it in the key-value stores under
+ * `~/Library/Messages/NickNameCache` (`kvtable`, one archived value per
+ * key). [`ContactsIndex`](crate::contacts::ContactsIndex) falls back to
+ * these names for handles that no contact matches, so an unknown number
+ * shows as "Sam Climbing" rather than +1555….
+ *
+ * Stores differ by macOS version: some key the nickname by handle, others
+ * key a record ID by handle and the nickname by that record ID. Both are
+ * followed; a nickname is any (possibly `NSKeyedArchiver`) dictionary with
+ * a `firstName` or `lastName`.
+ */
+
+use std::{
+    collections::HashMap,
+    fs,
+    io::Cursor,
+    path::{Path, PathBuf},
+};
+
+use imessage_database::util::{dirs::home, plist::parse_ns_keyed_archiver};
+use plist::Value;
+use rusqlite::Connection;
+
+use crate::contacts::{identifier_keys, Name};
+
+/// A name someone shared for their handle
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SharedName {
+    /// Phone number or email the name was shared from
+    pub handle: String,
+    pub first: Option<String>,
+    pub last: Option<String>,
+}
+
+/// The NickNameCache stores on this Mac
+pub fn source_paths() -> Vec<PathBuf> {
+    let dir = PathBuf::from(home()).join("Library/Messages/NickNameCache");
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "db"))
+        .collect();
+    paths.sort();
+    paths
+}
+
+/// Every shared name in the stores at `paths`
+pub fn load(paths: &[PathBuf]) -> Vec<SharedName> {
+    let entries: Vec<(String, Value)> = paths.iter().flat_map(|path| read_store(path)).collect();
+    let names_by_key: HashMap<&str, (Option<String>, Option<String>)> = entries
+        .iter()
+        .filter_map(|(key, value)| Some((key.as_str(), find_name(value)?)))
+        .collect();
+
+    let mut shared = Vec::new();
+    for (key, value) in &entries {
+        if !looks_like_handle(key) {
+            continue;
+        }
+        let name = names_by_key.get(key.as_str()).cloned().or_else(|| {
+            let record_id = find_record_id(value)?;
+            names_by_key.get(record_id).cloned()
+        });
+        if let Some((first, last)) = name {
+            shared.push(SharedName {
+                handle: key.clone(),
+                first,
+                last,
+            });
+        }
+    }
+    shared
+}
+
+/// Add the shared names to a contacts index, for handles it has no name for
+pub(crate) fn fill_gaps(index: &mut HashMap<String, Name>, shared: Vec<SharedName>) {
+    for shared in shared {
+        let Some(name) = Name::from_opt(shared.first, shared.last) else {
+            continue;
+        };
+        for key in identifier_keys(&shared.handle) {
+            index.entry(key).or_insert_with(|| name.clone());
+        }
+    }
+}
+
+/// Every key and decoded value of one store; nothing if it isn't one
+fn read_store(path: &Path) -> Vec<(String, Value)> {
+    let Ok(conn) = Connection::open(path) else {
+        return Vec::new();
+    };
+    let Ok(mut stmt) = conn.prepare("SELECT key, value FROM kvtable") else {
+        return Vec::new();
+    };
+    let rows = stmt.query_map([], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, Option<Vec<u8>>>(1)?))
+    });
+    let Ok(rows) = rows else {
+        return Vec::new();
+    };
+    rows.flatten()
+        .filter_map(|(key, blob)| Some((key, decode(&blob?)?)))
+        .collect()
+}
+
+/// A property list value, with an `NSKeyedArchiver` archive unpacked
+fn decode(blob: &[u8]) -> Option<Value> {
+    let value = Value::from_reader(Cursor::new(blob)).ok()?;
+    let archived = value
+        .as_dictionary()
+        .is_some_and(|dict| dict.contains_key("$archiver"));
+    if archived {
+        parse_ns_keyed_archiver(&value).ok()
+    } else {
+        Some(value)
+    }
+}
+
+/// The first and last name of the first nickname dictionary in `value`
+fn find_name(value: &Value) -> Option<(Option<String>, Option<String>)> {
+    match value {
+        Value::Dictionary(dict) => {
+            let field = |key: &str| {
+                dict.get(key)
+                    .and_then(Value::as_string)
+                    .map(str::trim)
+                    .filter(|s| !s.is_empty())
+                    .map(str::to_string)
+            };
+            let (first, last) = (field("firstName"), field("lastName"));
+            if first.is_some() || last.is_some() {
+                return Some((first, last));
+            }
+            dict.values().find_map(find_name)
+        }
+        Value::Array(items) => items.iter().find_map(find_name),
+        _ => None,
+    }
+}
+
+/// The record ID a handle's entry points to
+fn find_record_id(value: &Value) -> Option<&str> {
+    match value {
+        Value::String(id) => Some(id),
+        Value::Dictionary(dict) => dict.get("recordID").and_then(Value::as_string),
+        _ => None,
+    }
+}
+
+/// Phone numbers and emails, not record IDs (which are UUIDs)
+fn looks_like_handle(key: &str) -> bool {
+    key.contains('@') || key.starts_with('+') || key.bytes().all(|b| b.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use plist::{Dictionary, Uid};
+
+    fn binary(value: &Value) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        value.to_writer_binary(&mut bytes).unwrap();
+        bytes
+    }
+
+    fn nickname(first: &str, last: &str) -> Value {
+        let mut dict = Dictionary::new();
+        dict.insert("firstName".into(), first.into());
+        dict.insert("lastName".into(), last.into());
+        Value::Dictionary(dict)
+    }
+
+    /// `{firstName, lastName}` as NSKeyedArchiver encodes an object
+    fn archived(first: &str, last: &str) -> Value {
+        let mut object = Dictionary::new();
+        object.insert("firstName".into(), Value::Uid(Uid::new(2)));
+        object.insert("lastName".into(), Value::Uid(Uid::new(3)));
+        let mut top = Dictionary::new();
+        top.insert("root".into(), Value::Uid(Uid::new(1)));
+        let mut archive = Dictionary::new();
+        archive.insert("$archiver".into(), "NSKeyedArchiver".into());
+        archive.insert("$top".into(), Value::Dictionary(top));
+        archive.insert(
+            "$objects".into(),
+            Value::Array(vec![
+                "$null".into(),
+                Value::Dictionary(object),
+                first.into(),
+                last.into(),
+            ]),
+        );
+        Value::Dictionary(archive)
+    }
+
+    #[test]
+    fn shared_names_are_found_directly_and_through_record_ids() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("nicknameRecordsStore.db");
+        let conn = Connection::open(&path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE kvtable (rowid INTEGER PRIMARY KEY, key TEXT, value BLOB)",
+        )
+        .unwrap();
+        let rows = [
+            ("+15551234567", nickname("Sam", "Climbing")),
+            ("alex@example.com", "A1B2-C3D4".into()),
+            ("A1B2-C3D4", archived("Alex", "")),
+            ("+15550000000", "missing-record".into()),
+        ];
+        for (key, value) in &rows {
+            conn.execute(
+                "INSERT INTO kvtable (key, value) VALUES (?1, ?2)",
+                (key, binary(value)),
+            )
+            .unwrap();
+        }
+        drop(conn);
+
+        let shared = load(&[path]);
+        let names: Vec<(&str, Option<&str>, Option<&str>)> = shared
+            .iter()
+            .map(|s| (s.handle.as_str(), s.first.as_deref(), s.last.as_deref()))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                ("+15551234567", Some("Sam"), Some("Climbing")),
+                ("alex@example.com", Some("Alex"), None),
+            ]
+        );
+
+        // Contacts win over a shared name
+        let mut index = HashMap::new();
+        let contact = Name::from_opt(Some("Samantha".into()), None).unwrap();
+        index.insert("+15551234567".to_string(), contact);
+        fill_gaps(&mut index, shared);
+        assert_eq!(index["+15551234567"].full, "Samantha");
+        assert_eq!(index["alex@example.com"].full, "Alex");
+    }
+}
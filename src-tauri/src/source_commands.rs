@@ -0,0 +1,102 @@
+//! Tauri commands for named chat.db sources (see the library's
+//! db_sources.rs): other Mac users' databases or archived copies, each
+//! with the vCard naming its contacts.
+//!
+//! The selected source is saved in settings, so every command reading
+//! chat.db (the chat list, previews, search, preflight and exports) reads
+//! it without the frontend passing its path along (see
+//! `settings_commands::db_path_or_saved`). `discover_databases` finds
+//! older copies (Time Machine backups and the like) to offer as sources.
+
+use std::path::PathBuf;
+
+use chat_to_map_desktop::{
+    contacts::ContactsIndex,
+    db_discovery::{self, DiscoveryRoots, FoundDatabase},
+    db_sources::{DbSource, DbSourceList},
+    settings::Settings,
+    validation::validate_chat_db,
+};
+
+use crate::settings_commands::{app_local_data_dir, load_settings};
+
+/// Saved sources and the selected one
+#[tauri::command]
+pub fn list_sources(app_handle: tauri::AppHandle) -> Result<DbSourceList, String> {
+    Ok(load_settings(&app_handle)?.source_list())
+}
+
+/// Save a chat.db (and optionally a vCard for its contact names) as a
+/// named source, after checking both can be read
+#[tauri::command]
+pub fn add_source(
+    name: String,
+    db_path: String,
+    contacts_vcf_path: Option<String>,
+    app_handle: tauri::AppHandle,
+) -> Result<DbSource, String> {
+    tracing::info!("[add_source] Adding {name:?}: {db_path}");
+    let validation = validate_chat_db(&PathBuf::from(&db_path));
+    if !validation.valid {
+        let detail = validation.error.unwrap_or_default();
+        return Err(format!(
+            "{db_path} is not a readable iMessage database: {detail}"
+        ));
+    }
+    if let Some(path) = &contacts_vcf_path {
+        ContactsIndex::build_from_vcard(&PathBuf::from(path))?;
+    }
+    let dir = app_local_data_dir(&app_handle)?;
+    let mut settings = Settings::load(&dir);
+    let source = settings.add_source(&name, db_path, contacts_vcf_path)?;
+    settings.save(&dir)?;
+    Ok(source)
+}
+
+/// Read the source with this ID from now on (`None` = the Mac's own
+/// chat.db)
+#[tauri::command]
+pub fn select_source(
+    source_id: Option<String>,
+    app_handle: tauri::AppHandle,
+) -> Result<(), String> {
+    tracing::info!("[select_source] Selecting {source_id:?}");
+    let dir = app_local_data_dir(&app_handle)?;
+    let mut settings = Settings::load(&dir);
+    settings.select_source(source_id.as_deref())?;
+    settings.save(&dir)
+}
+
+/// Forget a source (its database stays where it is)
+#[tauri::command]
+pub fn remove_source(source_id: String, app_handle: tauri::AppHandle) -> Result<(), String> {
+    tracing::info!("[remove_source] Removing {source_id}");
+    let dir = app_local_data_dir(&app_handle)?;
+    let mut settings = Settings::load(&dir);
+    settings.remove_source(&source_id);
+    settings.save(&dir)
+}
+
+/// Older chat.db copies in Time Machine backups, `~/Library/Messages` and
+/// `folders`, newest first, leaving out those already saved as sources
+#[tauri::command]
+pub async fn discover_databases(
+    folders: Vec<String>,
+    app_handle: tauri::AppHandle,
+) -> Result<Vec<FoundDatabase>, String> {
+    let folders = folders.into_iter().map(PathBuf::from).collect();
+    let roots = DiscoveryRoots::system(folders).ok_or("Couldn't find the home folder")?;
+    let saved: Vec<String> = load_settings(&app_handle)?
+        .db_sources
+        .into_iter()
+        .map(|source| source.db_path)
+        .collect();
+    let found = tokio::task::spawn_blocking(move || db_discovery::discover_databases(&roots))
+        .await
+        .map_err(|e| format!("Database discovery task failed: {e}"))?;
+    tracing::info!("[discover_databases] Found {} databases", found.len());
+    Ok(found
+        .into_iter()
+        .filter(|db| !saved.contains(&db.path))
+        .collect())
+}
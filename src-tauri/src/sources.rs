@@ -0,0 +1,12 @@
+/*!
+ * Chat sources other than the Mac's own chat.db.
+ *
+ * Third-party sources map their export format into the same
+ * [`ExportedChat`](crate::export::ExportedChat) structures the iMessage
+ * exporter produces, so zip packaging and the upload flow are shared.
+ * [`ios_backup`] instead locates an iPhone backup's databases, which the
+ * iMessage exporter reads as-is.
+ */
+
+pub mod ios_backup;
+pub mod telegram;
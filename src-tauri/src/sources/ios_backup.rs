@@ -0,0 +1,143 @@
+/*!
+ * iPhone backups made by iTunes / Apple Devices, the Windows route to
+ * iMessage history.
+ *
+ * Windows has no chat.db, but an unencrypted local backup contains the
+ * phone's `sms.db` and `AddressBook.sqlitedb`, stored under their hashed
+ * names. Backups live under:
+ *
+ * - Windows (iTunes installer): `%APPDATA%\Apple Computer\MobileSync\Backup`
+ * - Windows (Microsoft Store iTunes / Apple Devices): `%USERPROFILE%\Apple\MobileSync\Backup`
+ * - macOS (Finder): `~/Library/Application Support/MobileSync/Backup`
+ *
+ * Each backup is a directory named after the device's UDID. The most
+ * recently modified one is used by default. The databases in an encrypted
+ * backup can't be opened, so such backups fail when read.
+ */
+
+use std::{
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use imessage_database::tables::table::DEFAULT_PATH_IOS;
+
+/// Hashed location of `AddressBook.sqlitedb` inside a backup
+const ADDRESS_BOOK_PATH_IOS: &str = "31/31bb7ba8914766d4ba40d6dfb6113c8b614be442";
+
+/// One device backup directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IosBackup {
+    pub dir: PathBuf,
+}
+
+impl IosBackup {
+    /// The backup's messages database (an iOS `sms.db`)
+    pub fn sms_db(&self) -> PathBuf {
+        self.dir.join(DEFAULT_PATH_IOS)
+    }
+
+    /// The backup's contacts database, if it was backed up
+    pub fn address_book(&self) -> Option<PathBuf> {
+        Some(self.dir.join(ADDRESS_BOOK_PATH_IOS)).filter(|path| path.is_file())
+    }
+}
+
+/// Directories that may hold backups on this platform
+pub fn backup_roots() -> Vec<PathBuf> {
+    let mut roots = Vec::new();
+    if cfg!(target_os = "windows") {
+        // %APPDATA% (Roaming)
+        if let Some(app_data) = dirs::config_dir() {
+            roots.push(
+                app_data
+                    .join("Apple Computer")
+                    .join("MobileSync")
+                    .join("Backup"),
+            );
+        }
+        if let Some(home) = dirs::home_dir() {
+            roots.push(home.join("Apple").join("MobileSync").join("Backup"));
+        }
+    } else if cfg!(target_os = "macos") {
+        if let Some(home) = dirs::home_dir() {
+            roots.push(
+                home.join("Library")
+                    .join("Application Support")
+                    .join("MobileSync")
+                    .join("Backup"),
+            );
+        }
+    }
+    roots
+}
+
+/// Backups under `roots` that contain messages, most recently modified first
+pub fn find_backups(roots: &[PathBuf]) -> Vec<IosBackup> {
+    let mut backups: Vec<(SystemTime, IosBackup)> = roots
+        .iter()
+        .filter_map(|root| std::fs::read_dir(root).ok())
+        .flat_map(|entries| entries.flatten())
+        .map(|entry| IosBackup { dir: entry.path() })
+        .filter(|backup| backup.sms_db().is_file())
+        .map(|backup| (modified(&backup.dir), backup))
+        .collect();
+    backups.sort_by_key(|(modified, _)| std::cmp::Reverse(*modified));
+    backups.into_iter().map(|(_, backup)| backup).collect()
+}
+
+/// The newest backup on this machine, if any
+pub fn default_backup() -> Option<IosBackup> {
+    find_backups(&backup_roots()).into_iter().next()
+}
+
+fn modified(path: &Path) -> SystemTime {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .unwrap_or(SystemTime::UNIX_EPOCH)
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    fn make_backup(root: &Path, udid: &str, with_contacts: bool) -> IosBackup {
+        let backup = IosBackup {
+            dir: root.join(udid),
+        };
+        let sms_db = backup.sms_db();
+        std::fs::create_dir_all(sms_db.parent().unwrap()).unwrap();
+        std::fs::write(&sms_db, b"").unwrap();
+        if with_contacts {
+            let address_book = backup.dir.join(ADDRESS_BOOK_PATH_IOS);
+            std::fs::create_dir_all(address_book.parent().unwrap()).unwrap();
+            std::fs::write(address_book, b"").unwrap();
+        }
+        backup
+    }
+
+    #[test]
+    fn finds_backups_with_messages_newest_first() {
+        let store = TempDir::new().unwrap();
+        let apple_devices = TempDir::new().unwrap();
+        let old = make_backup(store.path(), "00008030-OLD", false);
+        std::fs::create_dir_all(store.path().join("no-messages")).unwrap();
+        // Directory mtimes only have to differ, not by much
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        let new = make_backup(apple_devices.path(), "00008110-NEW", true);
+
+        let roots = [
+            store.path().to_path_buf(),
+            apple_devices.path().to_path_buf(),
+            PathBuf::from("/nonexistent/MobileSync/Backup"),
+        ];
+        assert_eq!(find_backups(&roots), vec![new.clone(), old.clone()]);
+        assert_eq!(
+            new.address_book(),
+            Some(new.dir.join(ADDRESS_BOOK_PATH_IOS))
+        );
+        assert_eq!(old.address_book(), None);
+    }
+}
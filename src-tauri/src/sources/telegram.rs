@@ -0,0 +1,446 @@
+/*!
+ * Telegram Desktop JSON export import.
+ *
+ * Telegram Desktop's "Export chat history" (JSON format) writes a
+ * `result.json`. A full-account export nests chats under `chats.list` and
+ * carries `personal_information.user_id`, which is how we tell the owner's
+ * messages apart; a single-chat export is just one chat object at the top
+ * level.
+ *
+ * Regular messages keep their text (forwarded ones are prefixed with the
+ * original sender), venues and shared locations are rendered as text so the
+ * map pipeline sees them, and service messages (group created, members
+ * added, title changed, ...) become plain-language lines attributed to the
+ * actor.
+ */
+
+use std::{collections::BTreeMap, path::Path};
+
+use chrono::{Local, NaiveDateTime, TimeZone};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::export::{
+    archive, format_unix_timestamp, ExportFormat, ExportResult, ExportTimezone, ExportedChat,
+    ExportedChatMeta, ExportedMessage, ExportedParticipant,
+};
+
+/// Platform name sent to the server for Telegram uploads
+pub const UPLOAD_PLATFORM: &str = "telegram";
+
+// =============================================================================
+// result.json shape (only the fields we use)
+// =============================================================================
+
+#[derive(Debug, Deserialize)]
+struct TelegramExport {
+    #[serde(default)]
+    personal_information: Option<PersonalInformation>,
+    #[serde(default)]
+    chats: Option<ChatList>,
+    /// Present when the file is a single-chat export
+    #[serde(flatten)]
+    single_chat: Option<TelegramChat>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PersonalInformation {
+    user_id: i64,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatList {
+    #[serde(default)]
+    list: Vec<TelegramChat>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TelegramChat {
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(rename = "type", default)]
+    chat_type: String,
+    id: i64,
+    #[serde(default)]
+    messages: Vec<TelegramMessage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TelegramMessage {
+    /// Unique within the chat
+    #[serde(default)]
+    id: i64,
+    #[serde(rename = "type", default)]
+    message_type: String,
+    #[serde(default)]
+    date: String,
+    #[serde(default)]
+    date_unixtime: Option<String>,
+    #[serde(default)]
+    from: Option<String>,
+    #[serde(default)]
+    from_id: Option<String>,
+    #[serde(default)]
+    actor: Option<String>,
+    #[serde(default)]
+    actor_id: Option<String>,
+    #[serde(default)]
+    action: Option<String>,
+    #[serde(default)]
+    title: Option<String>,
+    #[serde(default)]
+    members: Vec<Option<String>>,
+    #[serde(default)]
+    forwarded_from: Option<String>,
+    #[serde(default)]
+    text: Value,
+    #[serde(default)]
+    place_name: Option<String>,
+    #[serde(default)]
+    address: Option<String>,
+    #[serde(default)]
+    location_information: Option<LocationInformation>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LocationInformation {
+    latitude: f64,
+    longitude: f64,
+}
+
+// =============================================================================
+// Public API
+// =============================================================================
+
+/// Parse a Telegram `result.json` into exported chats (empty chats dropped)
+pub fn parse_telegram_export(path: &Path) -> Result<Vec<ExportedChat>, String> {
+    let contents =
+        std::fs::read_to_string(path).map_err(|e| format!("Failed to read {path:?}: {e}"))?;
+    parse_telegram_json(&contents)
+}
+
+/// Parse Telegram export JSON (see [`parse_telegram_export`])
+pub fn parse_telegram_json(contents: &str) -> Result<Vec<ExportedChat>, String> {
+    let export: TelegramExport =
+        serde_json::from_str(contents).map_err(|e| format!("Not a Telegram JSON export: {e}"))?;
+    let owner_id = export
+        .personal_information
+        .map(|info| format!("user{}", info.user_id));
+
+    let chats = match (export.chats, export.single_chat) {
+        (Some(list), _) => list.list,
+        (None, Some(chat)) => vec![chat],
+        (None, None) => return Err("Telegram export contains no chats".to_string()),
+    };
+
+    let mut exported: Vec<ExportedChat> = chats
+        .into_iter()
+        .map(|chat| convert_chat(chat, owner_id.as_deref()))
+        .filter(|chat| !chat.messages.is_empty())
+        .collect();
+    exported.sort_by_key(|c| std::cmp::Reverse(c.messages.len()));
+    Ok(exported)
+}
+
+/// Parse a Telegram export and package it like an iMessage export
+pub fn import_telegram_export(path: &Path) -> Result<ExportResult, String> {
+    let started = std::time::Instant::now();
+    let chats = parse_telegram_export(path)?;
+    let decode = started.elapsed();
+    let mut result = export_chats(&chats)?;
+    result.metrics.record_decode(decode);
+    Ok(result)
+}
+
+/// Package already parsed Telegram chats like an iMessage export
+pub fn export_chats(chats: &[ExportedChat]) -> Result<ExportResult, String> {
+    let total_messages = chats.iter().map(|c| c.messages.len()).sum();
+    let manifest = archive::new_manifest(
+        UPLOAD_PLATFORM,
+        chats,
+        total_messages,
+        ExportFormat::Json,
+        ExportTimezone::Local,
+    );
+    archive::write_archive(&manifest, chats, &[], total_messages)
+}
+
+// =============================================================================
+// Conversion
+// =============================================================================
+
+fn convert_chat(chat: TelegramChat, owner_id: Option<&str>) -> ExportedChat {
+    // Sender ID → name, for everyone but the owner
+    let mut others: BTreeMap<String, String> = BTreeMap::new();
+    let mut messages = Vec::new();
+
+    for message in &chat.messages {
+        let sender_id = message.from_id.as_ref().or(message.actor_id.as_ref());
+        let is_from_me = owner_id.is_some() && sender_id.map(String::as_str) == owner_id;
+        if let (Some(id), false) = (sender_id, is_from_me) {
+            let name = message.from.as_ref().or(message.actor.as_ref());
+            let entry = others.entry(id.clone()).or_default();
+            if let (true, Some(name)) = (entry.is_empty(), name) {
+                entry.clone_from(name);
+            }
+        }
+        if let Some(exported) = convert_message(message, chat.id, is_from_me) {
+            messages.push(exported);
+        }
+    }
+
+    let name = chat
+        .name
+        .filter(|n| !n.is_empty())
+        .unwrap_or_else(|| format!("Telegram chat {}", chat.id));
+    let participant_count = if chat.chat_type == "personal_chat" {
+        1
+    } else {
+        others.len()
+    };
+
+    ExportedChat {
+        meta: ExportedChatMeta {
+            name,
+            identifier: format!("telegram:{}", chat.id),
+            guid: String::new(),
+            service: "Telegram".to_string(),
+            message_count: messages.len(),
+            participant_count,
+            participants: participants(others),
+        },
+        messages,
+    }
+}
+
+/// The owner, then everyone else by Telegram user ID (the sender ID, e.g.
+/// `user123456`, is the identifier; the name falls back to it)
+fn participants(others: BTreeMap<String, String>) -> Vec<ExportedParticipant> {
+    let me = ExportedParticipant {
+        name: "Me".to_string(),
+        identifier: String::new(),
+        is_me: true,
+    };
+    let others = others.into_iter().map(|(id, name)| ExportedParticipant {
+        name: if name.is_empty() { id.clone() } else { name },
+        identifier: id,
+        is_me: false,
+    });
+    std::iter::once(me).chain(others).collect()
+}
+
+fn convert_message(
+    message: &TelegramMessage,
+    chat_id: i64,
+    is_from_me: bool,
+) -> Option<ExportedMessage> {
+    let (sender, text) = if message.message_type == "service" {
+        let actor = message
+            .actor
+            .clone()
+            .unwrap_or_else(|| "Someone".to_string());
+        let text = describe_service_action(&actor, message)?;
+        (actor, text)
+    } else {
+        let sender = message
+            .from
+            .clone()
+            .or_else(|| message.from_id.clone())
+            .unwrap_or_else(|| "Unknown".to_string());
+        (sender, message_text(message)?)
+    };
+
+    Some(ExportedMessage {
+        guid: format!("telegram:{chat_id}:{}", message.id),
+        timestamp: message_timestamp(message),
+        sender: if is_from_me { "Me".to_string() } else { sender },
+        is_from_me,
+        text,
+        truncated: false,
+        deleted: false,
+        metadata: None,
+        attachments: Vec::new(),
+        event: None,
+        urls: Vec::new(),
+        link_preview: None,
+    })
+}
+
+/// Flatten the message text (plus venue/location details) into one string
+fn message_text(message: &TelegramMessage) -> Option<String> {
+    let mut parts = Vec::new();
+    let text = flatten_text(&message.text);
+    if !text.trim().is_empty() {
+        parts.push(text);
+    }
+    let venue: Vec<&str> = [&message.place_name, &message.address]
+        .into_iter()
+        .filter_map(|v| v.as_deref())
+        .filter(|v| !v.is_empty())
+        .collect();
+    if !venue.is_empty() {
+        parts.push(venue.join(", "));
+    } else if let Some(location) = &message.location_information {
+        parts.push(format!(
+            "[location: {:.6}, {:.6}]",
+            location.latitude, location.longitude
+        ));
+    }
+    if parts.is_empty() {
+        return None;
+    }
+
+    let text = parts.join("\n");
+    Some(match &message.forwarded_from {
+        Some(origin) => format!("[Forwarded from {origin}] {text}"),
+        None => text,
+    })
+}
+
+/// Telegram stores text either as a string or as an array of plain strings
+/// and `{ "type": ..., "text": ... }` entity objects
+fn flatten_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Array(parts) => parts.iter().map(flatten_text).collect(),
+        Value::Object(entity) => entity
+            .get("text")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string(),
+        _ => String::new(),
+    }
+}
+
+/// Render a service message (group created, members added, ...) as text
+fn describe_service_action(actor: &str, message: &TelegramMessage) -> Option<String> {
+    let title = message.title.as_deref().unwrap_or_default();
+    let members = message
+        .members
+        .iter()
+        .flatten()
+        .cloned()
+        .collect::<Vec<_>>()
+        .join(", ");
+    let text = match message.action.as_deref()? {
+        "create_group" | "create_channel" => format!("{actor} created group \"{title}\""),
+        "edit_group_title" => format!("{actor} changed the group name to \"{title}\""),
+        "invite_members" => format!("{actor} added {members}"),
+        "remove_members" => format!("{actor} removed {members}"),
+        "join_group_by_link" => format!("{actor} joined the group via invite link"),
+        "edit_group_photo" => format!("{actor} changed the group photo"),
+        "pin_message" => format!("{actor} pinned a message"),
+        other => format!("{actor}: {}", other.replace('_', " ")),
+    };
+    Some(text)
+}
+
+/// Prefer the exact `date_unixtime`; fall back to `date`, which Telegram
+/// writes in the exporting machine's local time without an offset
+fn message_timestamp(message: &TelegramMessage) -> String {
+    if let Some(unix) = message
+        .date_unixtime
+        .as_deref()
+        .and_then(|s| s.parse::<i64>().ok())
+    {
+        return format_unix_timestamp(unix);
+    }
+    NaiveDateTime::parse_from_str(&message.date, "%Y-%m-%dT%H:%M:%S")
+        .ok()
+        .and_then(|naive| Local.from_local_datetime(&naive).earliest())
+        .map(|dt| dt.to_rfc3339())
+        .unwrap_or_else(|| message.date.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FULL_EXPORT: &str = r#"{
+        "personal_information": { "user_id": 1 },
+        "chats": { "list": [
+            {
+                "name": "Trip crew",
+                "type": "private_group",
+                "id": 42,
+                "messages": [
+                    { "id": 1, "type": "service", "date": "2024-01-01T10:00:00",
+                      "date_unixtime": "1704103200", "actor": "Alice", "actor_id": "user2",
+                      "action": "create_group", "title": "Trip crew", "members": ["Bob"] },
+                    { "id": 2, "type": "message", "date": "2024-01-01T10:01:00",
+                      "date_unixtime": "1704103260", "from": "Alice", "from_id": "user2",
+                      "text": ["Book ", { "type": "link", "text": "https://example.com/hotel" }] },
+                    { "id": 3, "type": "message", "date": "2024-01-01T10:02:00",
+                      "date_unixtime": "1704103320", "from": "Me Myself", "from_id": "user1",
+                      "forwarded_from": "Carol", "text": "Try the cafe" },
+                    { "id": 4, "type": "message", "date": "2024-01-01T10:03:00",
+                      "from": "Bob", "from_id": "user3", "text": "",
+                      "place_name": "Time Out Market", "address": "Av. 24 de Julho, Lisboa" },
+                    { "id": 5, "type": "message", "date": "2024-01-01T10:04:00",
+                      "from": "Bob", "from_id": "user3", "text": "", "photo": "photos/1.jpg" }
+                ]
+            },
+            { "name": "Empty", "type": "personal_chat", "id": 7, "messages": [] }
+        ]}
+    }"#;
+
+    #[test]
+    fn parses_full_account_export() {
+        let chats = parse_telegram_json(FULL_EXPORT).unwrap();
+        assert_eq!(chats.len(), 1, "empty chats are dropped");
+
+        let chat = &chats[0];
+        assert_eq!(chat.meta.name, "Trip crew");
+        assert_eq!(chat.meta.identifier, "telegram:42");
+        assert_eq!(chat.meta.service, "Telegram");
+        assert_eq!(chat.meta.participant_count, 2);
+        let participants: Vec<(&str, &str)> = chat
+            .meta
+            .participants
+            .iter()
+            .map(|p| (p.name.as_str(), p.identifier.as_str()))
+            .collect();
+        assert_eq!(
+            participants,
+            [("Me", ""), ("Alice", "user2"), ("Bob", "user3")]
+        );
+        // The caption-less photo is skipped, like empty iMessage rows
+        assert_eq!(chat.meta.message_count, 4);
+
+        let texts: Vec<&str> = chat.messages.iter().map(|m| m.text.as_str()).collect();
+        assert_eq!(texts[0], "Alice created group \"Trip crew\"");
+        assert_eq!(texts[1], "Book https://example.com/hotel");
+        assert_eq!(texts[2], "[Forwarded from Carol] Try the cafe");
+        assert_eq!(texts[3], "Time Out Market, Av. 24 de Julho, Lisboa");
+        assert_eq!(chat.messages[0].guid, "telegram:42:1");
+    }
+
+    #[test]
+    fn owner_messages_are_marked_from_me() {
+        let chats = parse_telegram_json(FULL_EXPORT).unwrap();
+        let mine: Vec<_> = chats[0].messages.iter().filter(|m| m.is_from_me).collect();
+        assert_eq!(mine.len(), 1);
+        assert_eq!(mine[0].sender, "Me");
+    }
+
+    #[test]
+    fn parses_single_chat_export() {
+        let json = r#"{ "name": "Bob", "type": "personal_chat", "id": 9, "messages": [
+            { "id": 1, "type": "message", "date": "2024-03-05T18:30:00", "from": "Bob",
+              "from_id": "user3", "text": "See you at 12 Oak Street" }
+        ]}"#;
+        let chats = parse_telegram_json(json).unwrap();
+        assert_eq!(chats.len(), 1);
+        assert_eq!(chats[0].meta.participant_count, 1);
+        assert!(!chats[0].messages[0].is_from_me);
+        assert!(chats[0].messages[0]
+            .timestamp
+            .starts_with("2024-03-05T18:30:00"));
+    }
+
+    #[test]
+    fn rejects_non_telegram_json() {
+        assert!(parse_telegram_json(r#"{ "hello": "world" }"#).is_err());
+        assert!(parse_telegram_json("not json").is_err());
+    }
+}
@@ -0,0 +1,163 @@
+/*!
+ * Read-only ad-hoc SQL for support diagnostics.
+ *
+ * Backs `ctm-cli --debug sql`, so odd databases can be inspected without
+ * asking users to install sqlite3 or fight Full Disk Access for it. Only
+ * statements SQLite reports as read-only are accepted, on top of the
+ * connection itself being opened read-only.
+ */
+
+use rusqlite::{types::ValueRef, Connection};
+use serde::Serialize;
+use serde_json::Value;
+
+/// Default number of rows returned when no limit is given
+pub const DEFAULT_ROW_LIMIT: usize = 100;
+
+/// Columns and rows returned by [`run_query`]
+#[derive(Debug, Clone, Serialize)]
+pub struct QueryResult {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Value>>,
+    /// True when more rows were available than the limit allowed
+    pub truncated: bool,
+}
+
+/// Run a single read-only statement, returning at most `limit` rows
+pub fn run_query(db: &Connection, sql: &str, limit: usize) -> Result<QueryResult, String> {
+    let mut stmt = db.prepare(sql).map_err(|e| format!("Invalid query: {e}"))?;
+    if !stmt.readonly() {
+        return Err("Only read-only statements (SELECT, PRAGMA reads) are allowed".to_string());
+    }
+
+    let columns: Vec<String> = stmt.column_names().iter().map(|c| c.to_string()).collect();
+    let mut rows_iter = stmt.query([]).map_err(|e| format!("Query failed: {e}"))?;
+
+    let mut rows = Vec::new();
+    let mut truncated = false;
+    while let Some(row) = rows_iter.next().map_err(|e| format!("Query failed: {e}"))? {
+        if rows.len() == limit {
+            truncated = true;
+            break;
+        }
+        let values = (0..columns.len())
+            .map(|i| row.get_ref(i).map(value_to_json))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to read row: {e}"))?;
+        rows.push(values);
+    }
+
+    Ok(QueryResult {
+        columns,
+        rows,
+        truncated,
+    })
+}
+
+/// Render a result as CSV (RFC 4180 quoting), header row first
+pub fn to_csv(result: &QueryResult) -> String {
+    let mut out = String::new();
+    let header: Vec<String> = result.columns.iter().map(|c| csv_field(c)).collect();
+    out.push_str(&header.join(","));
+    out.push('\n');
+    for row in &result.rows {
+        let fields: Vec<String> = row
+            .iter()
+            .map(|value| match value {
+                Value::Null => String::new(),
+                Value::String(s) => csv_field(s),
+                other => csv_field(&other.to_string()),
+            })
+            .collect();
+        out.push_str(&fields.join(","));
+        out.push('\n');
+    }
+    out
+}
+
+/// Render a result as a JSON array of `{column: value}` objects
+pub fn to_json(result: &QueryResult) -> Value {
+    Value::Array(
+        result
+            .rows
+            .iter()
+            .map(|row| {
+                Value::Object(
+                    result
+                        .columns
+                        .iter()
+                        .cloned()
+                        .zip(row.iter().cloned())
+                        .collect(),
+                )
+            })
+            .collect(),
+    )
+}
+
+/// Convert a SQLite value; blobs become `0x`-prefixed hex
+fn value_to_json(value: ValueRef<'_>) -> Value {
+    match value {
+        ValueRef::Null => Value::Null,
+        ValueRef::Integer(i) => Value::from(i),
+        ValueRef::Real(f) => Value::from(f),
+        ValueRef::Text(t) => Value::String(String::from_utf8_lossy(t).into_owned()),
+        ValueRef::Blob(b) => Value::String(format!("0x{}", hex::encode(b))),
+    }
+}
+
+/// Quote a CSV field if it contains a delimiter, quote or newline
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_db() -> Connection {
+        let db = Connection::open_in_memory().unwrap();
+        db.execute_batch(
+            "CREATE TABLE t (id INTEGER, name TEXT, data BLOB);
+             INSERT INTO t VALUES (1, 'plain', NULL), (2, 'has, comma', x'0102'), (3, 'x', NULL);",
+        )
+        .unwrap();
+        db
+    }
+
+    #[test]
+    fn run_query_applies_row_limit() {
+        let result = run_query(&test_db(), "SELECT id FROM t ORDER BY id", 2).unwrap();
+        assert_eq!(result.columns, vec!["id"]);
+        assert_eq!(result.rows.len(), 2);
+        assert!(result.truncated);
+
+        let all = run_query(&test_db(), "SELECT id FROM t", 10).unwrap();
+        assert!(!all.truncated);
+    }
+
+    #[test]
+    fn run_query_rejects_writes() {
+        let err = run_query(&test_db(), "DELETE FROM t", 10).unwrap_err();
+        assert!(err.contains("read-only"));
+    }
+
+    #[test]
+    fn csv_output_quotes_fields_and_hex_encodes_blobs() {
+        let result = run_query(&test_db(), "SELECT * FROM t WHERE id = 2", 10).unwrap();
+        assert_eq!(to_csv(&result), "id,name,data\n2,\"has, comma\",0x0102\n");
+    }
+
+    #[test]
+    fn json_output_uses_column_names() {
+        let result = run_query(&test_db(), "SELECT id, data FROM t WHERE id = 1", 10).unwrap();
+        assert_eq!(
+            to_json(&result),
+            serde_json::json!([{ "id": 1, "data": null }])
+        );
+    }
+}
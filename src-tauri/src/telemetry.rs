@@ -0,0 +1,247 @@
+/*!
+ * Anonymous crash and error reporting, off unless the user opts in.
+ *
+ * With `Settings::error_reporting` on, panics and failed exports are
+ * queued as [`ErrorReport`]s in `<app_local_data_dir>/error_reports.jsonl`
+ * and [`send_pending`] posts them to `/api/telemetry/errors`. A report
+ * only says what went wrong: the [`ExportError`] kind (with the step that
+ * timed out or was rate limited, or the server's rejection code), or where
+ * the code panicked, plus the app version and platform. Error messages and
+ * panic payloads are never included, since they can quote chat names,
+ * paths or message text, and neither is the visitor ID.
+ *
+ * Panics queue their report synchronously from the hook, so they're sent
+ * on the next launch if the app doesn't survive them.
+ */
+
+use std::{
+    collections::HashMap,
+    panic::Location,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    export::state::ExportError,
+    job_history::{append_line, read_lines},
+    upload::{build_client, UploadError, UploadTimeouts},
+};
+
+const REPORTS_FILENAME: &str = "error_reports.jsonl";
+
+/// Reports kept while they can't be sent; older ones are dropped
+pub const MAX_QUEUED_REPORTS: usize = 50;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Serializes changes to the queue file between the panic hook and senders
+static QUEUE: Mutex<()> = Mutex::new(());
+
+/// Turn reporting on or off (from the saved setting)
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// What went wrong, without any content
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ErrorReport {
+    /// `panic`, or the `kind` of the [`ExportError`]
+    pub kind: String,
+    /// The export step, for timeouts and rate limits
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub step: Option<String>,
+    /// The server's rejection code, for rejected exports
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+    /// Source file and line, for panics
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<String>,
+    pub app_version: String,
+    pub os: String,
+    pub arch: String,
+    /// RFC 3339 timestamp
+    pub occurred_at: String,
+}
+
+impl ErrorReport {
+    fn new(kind: &str) -> Self {
+        Self {
+            kind: kind.to_string(),
+            step: None,
+            code: None,
+            location: None,
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            os: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+            occurred_at: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+
+    /// A panic at `location`
+    pub fn panic(location: Option<&Location<'_>>) -> Self {
+        Self {
+            location: location.map(|location| format!("{}:{}", location.file(), location.line())),
+            ..Self::new("panic")
+        }
+    }
+
+    /// A failed export; `None` for the user's own doing (cancelling, or
+    /// starting a second export)
+    pub fn export_error(error: &ExportError) -> Option<Self> {
+        let report = match error {
+            ExportError::Busy { .. }
+            | ExportError::Cancelled { .. }
+            | ExportError::NotCancellable { .. } => return None,
+            ExportError::InsufficientDiskSpace { .. } => Self::new("insufficient_disk_space"),
+            ExportError::Timeout { step, .. } => Self {
+                step: Some(step.clone()),
+                ..Self::new("timeout")
+            },
+            ExportError::RateLimited { step, .. } => Self {
+                step: Some(step.clone()),
+                ..Self::new("rate_limited")
+            },
+            ExportError::Rejected { code, .. } => Self {
+                code: Some(code.clone()),
+                ..Self::new("rejected")
+            },
+            ExportError::Failed { .. } => Self::new("failed"),
+        };
+        Some(report)
+    }
+}
+
+/// Queue `report` for sending, if reporting is on
+pub fn record(app_local_data_dir: &Path, report: &ErrorReport) -> Result<(), String> {
+    if !is_enabled() {
+        return Ok(());
+    }
+    let _queue = QUEUE.lock().unwrap_or_else(|e| e.into_inner());
+    let path = app_local_data_dir.join(REPORTS_FILENAME);
+    let mut reports: Vec<ErrorReport> = read_lines(&path)?;
+    if reports.len() >= MAX_QUEUED_REPORTS {
+        reports.drain(..=reports.len() - MAX_QUEUED_REPORTS);
+        rewrite(&path, &reports)?;
+    }
+    append_line(&path, report)
+}
+
+/// Queue a report for every panic, then run the previous hook
+pub fn install_panic_hook(app_local_data_dir: PathBuf) {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let _ = record(&app_local_data_dir, &ErrorReport::panic(info.location()));
+        previous(info);
+    }));
+}
+
+/// Send the queued reports; returns how many were sent. Reports queued
+/// while sending stay queued.
+pub async fn send_pending(
+    app_local_data_dir: &Path,
+    api_host_override: Option<&str>,
+    custom_headers: &HashMap<String, String>,
+    timeouts: &UploadTimeouts,
+) -> Result<usize, UploadError> {
+    let path = app_local_data_dir.join(REPORTS_FILENAME);
+    let reports: Vec<ErrorReport> = {
+        let _queue = QUEUE.lock().unwrap_or_else(|e| e.into_inner());
+        read_lines(&path)?
+    };
+    if reports.is_empty() || !is_enabled() {
+        return Ok(0);
+    }
+    build_client(api_host_override, custom_headers, timeouts)
+        .report_errors(&reports)
+        .await?;
+
+    let _queue = QUEUE.lock().unwrap_or_else(|e| e.into_inner());
+    let mut queued: Vec<ErrorReport> = read_lines(&path)?;
+    queued.drain(..reports.len().min(queued.len()));
+    rewrite(&path, &queued)?;
+    Ok(reports.len())
+}
+
+/// Drop every queued report
+pub fn clear_pending(app_local_data_dir: &Path) -> Result<(), String> {
+    let _queue = QUEUE.lock().unwrap_or_else(|e| e.into_inner());
+    rewrite(&app_local_data_dir.join(REPORTS_FILENAME), &[])
+}
+
+/// Replace the queue with `reports`
+fn rewrite(path: &Path, reports: &[ErrorReport]) -> Result<(), String> {
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            return Err(format!("Failed to clear {path:?}: {e}"));
+        }
+        _ => {}
+    }
+    for report in reports {
+        append_line(path, report)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn reports_carry_the_error_kind_but_no_messages() {
+        let failed = ExportError::Failed {
+            message: "Failed to read /Users/alice/chat.db".to_string(),
+        };
+        let report = ErrorReport::export_error(&failed).unwrap();
+        assert_eq!(report.kind, "failed");
+        assert!(!serde_json::to_string(&report).unwrap().contains("alice"));
+
+        let timeout = ExportError::Timeout {
+            step: "upload".to_string(),
+            seconds: 60,
+        };
+        let report = ErrorReport::export_error(&timeout).unwrap();
+        assert_eq!(
+            (report.kind.as_str(), report.step.as_deref()),
+            ("timeout", Some("upload"))
+        );
+
+        let cancelled = ExportError::Cancelled {
+            export_id: "e1".to_string(),
+        };
+        assert_eq!(ErrorReport::export_error(&cancelled), None);
+    }
+
+    #[test]
+    fn reports_are_only_queued_when_enabled_and_the_queue_is_capped() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join(REPORTS_FILENAME);
+        let report = ErrorReport::panic(Some(Location::caller()));
+        assert!(report.location.as_deref().unwrap().contains("telemetry.rs"));
+
+        set_enabled(false);
+        record(dir.path(), &report).unwrap();
+        assert!(!path.exists());
+
+        set_enabled(true);
+        for _ in 0..MAX_QUEUED_REPORTS + 3 {
+            record(dir.path(), &report).unwrap();
+        }
+        set_enabled(false);
+        let queued: Vec<ErrorReport> = read_lines(&path).unwrap();
+        assert_eq!(queued.len(), MAX_QUEUED_REPORTS);
+
+        clear_pending(dir.path()).unwrap();
+        assert!(read_lines::<ErrorReport>(&path).unwrap().is_empty());
+    }
+}
@@ -49,8 +49,8 @@ impl TestAddressBookDb {
             ),
         )?;
 
-        for phone in builder.phones {
-            self.phone(id, phone)?;
+        for (number, label) in builder.phones {
+            self.phone(id, number, label)?;
         }
 
         for email in builder.emails {
@@ -60,13 +60,14 @@ impl TestAddressBookDb {
         Ok(id)
     }
 
-    fn phone(&mut self, owner_id: i32, number: String) -> Result<i32> {
+    fn phone(&mut self, owner_id: i32, number: String, label: Option<String>) -> Result<i32> {
         let id = self.next_phone_id;
         self.next_phone_id += 1;
 
         self.conn.execute(
-            "INSERT INTO ZABCDPHONENUMBER (Z_PK, ZOWNER, ZFULLNUMBER) VALUES (?1, ?2, ?3)",
-            (id, owner_id, &number),
+            "INSERT INTO ZABCDPHONENUMBER (Z_PK, ZOWNER, ZFULLNUMBER, ZLABEL)
+             VALUES (?1, ?2, ?3, ?4)",
+            (id, owner_id, &number, &label),
         )?;
 
         Ok(id)
@@ -109,7 +110,8 @@ pub struct ContactBuilder {
     pub middle_name: Option<String>,
     pub nickname: Option<String>,
     pub organization: Option<String>,
-    pub phones: Vec<String>,
+    /// Numbers with their raw `ZLABEL` (e.g. `_$!<Mobile>!$_`)
+    pub phones: Vec<(String, Option<String>)>,
     pub emails: Vec<String>,
 }
 
@@ -149,7 +151,13 @@ impl ContactBuilder {
     }
 
     pub fn phone<S: Into<String>>(mut self, number: S) -> Self {
-        self.phones.push(number.into());
+        self.phones.push((number.into(), None));
+        self
+    }
+
+    /// Add a number with an AddressBook label such as `_$!<WorkFAX>!$_`
+    pub fn labeled_phone<S: Into<String>, L: Into<String>>(mut self, number: S, label: L) -> Self {
+        self.phones.push((number.into(), Some(label.into())));
         self
     }
 
@@ -2,6 +2,8 @@
  * iMessage database test fixtures
  */
 
+use std::path::Path;
+
 use rusqlite::{Connection, Result};
 
 /// Test iMessage database builder
@@ -95,8 +97,9 @@ impl TestIMessageDb {
         let guid = builder.guid.unwrap_or_else(|| format!("msg-{}", id));
 
         self.conn.execute(
-            "INSERT INTO message (ROWID, guid, text, handle_id, service, date, is_from_me)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            "INSERT INTO message (ROWID, guid, text, handle_id, service, date, is_from_me,
+                                  syndication_ranges, destination_caller_id)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
             (
                 id,
                 &guid,
@@ -105,6 +108,8 @@ impl TestIMessageDb {
                 &builder.service,
                 builder.date,
                 builder.is_from_me,
+                &builder.syndication_ranges,
+                &builder.destination_caller_id,
             ),
         )?;
 
@@ -119,10 +124,57 @@ impl TestIMessageDb {
         Ok(id)
     }
 
+    /// Add a message row from raw SQL expressions, bypassing the builder's
+    /// typing — for deliberately malformed rows, e.g.
+    /// `("text", "CAST(X'FF' AS TEXT)")` or `("date", "'corrupt'")`.
+    /// `guid` and `date` default as in [`Self::message`] unless given.
+    pub fn raw_message(&mut self, chat_id: i32, columns: &[(&str, &str)]) -> Result<i32> {
+        let id = self.next_message_id;
+        self.next_message_id += 1;
+
+        let guid = format!("'msg-{}'", id);
+        let mut names = vec!["ROWID"];
+        let mut values = vec![id.to_string()];
+        for (name, default) in [("guid", guid.as_str()), ("date", "0")] {
+            if !columns.iter().any(|(column, _)| *column == name) {
+                names.push(name);
+                values.push(default.to_string());
+            }
+        }
+        for (name, value) in columns {
+            names.push(name);
+            values.push(value.to_string());
+        }
+
+        self.conn.execute(
+            &format!(
+                "INSERT INTO message ({}) VALUES ({})",
+                names.join(", "),
+                values.join(", ")
+            ),
+            [],
+        )?;
+        self.conn.execute(
+            "INSERT INTO chat_message_join (chat_id, message_id, message_date)
+             SELECT ?1, ROWID, CASE WHEN typeof(date) = 'integer' THEN date ELSE 0 END
+             FROM message WHERE ROWID = ?2",
+            (chat_id, id),
+        )?;
+
+        Ok(id)
+    }
+
     /// Get the underlying connection for queries
     pub fn conn(&self) -> &Connection {
         &self.conn
     }
+
+    /// Write the database to a file, for code paths that open chat.db by path
+    pub fn save_to(&self, path: &Path) -> Result<()> {
+        self.conn
+            .execute("VACUUM INTO ?1", [path.to_string_lossy().as_ref()])?;
+        Ok(())
+    }
 }
 
 impl Default for TestIMessageDb {
@@ -244,6 +296,8 @@ pub struct MessageBuilder {
     pub date: i64,
     pub is_from_me: bool,
     pub chat_id: Option<i32>,
+    pub syndication_ranges: Option<String>,
+    pub destination_caller_id: Option<String>,
 }
 
 impl MessageBuilder {
@@ -256,6 +310,8 @@ impl MessageBuilder {
             date: 0,
             is_from_me: false,
             chat_id: None,
+            syndication_ranges: None,
+            destination_caller_id: None,
         }
     }
 
@@ -296,6 +352,18 @@ impl MessageBuilder {
         self.chat_id = Some(chat_id);
         self
     }
+
+    /// The owner's account the message was sent from or received on
+    pub fn account<S: Into<String>>(mut self, address: S) -> Self {
+        self.destination_caller_id = Some(address.into());
+        self
+    }
+
+    /// Mark the message as promoted to "Shared with You"
+    pub fn shared_with_you(mut self) -> Self {
+        self.syndication_ranges = Some("[{\"location\":0,\"length\":1}]".to_string());
+        self
+    }
 }
 
 impl Default for MessageBuilder {
@@ -26,14 +26,62 @@ CREATE TABLE chat_handle_join (
     UNIQUE(chat_id, handle_id)
 );
 
+-- Carries every column imessage-database's message query selects, so
+-- `Message::stream` / `Message::from_guid` work against fixtures.
 CREATE TABLE message (
     ROWID INTEGER PRIMARY KEY AUTOINCREMENT,
     guid TEXT UNIQUE NOT NULL,
     text TEXT,
     handle_id INTEGER DEFAULT 0,
+    subject TEXT,
+    attributedBody BLOB,
     service TEXT,
     date INTEGER,
-    is_from_me INTEGER DEFAULT 0
+    date_read INTEGER,
+    date_delivered INTEGER,
+    is_from_me INTEGER DEFAULT 0,
+    is_read INTEGER DEFAULT 0,
+    item_type INTEGER DEFAULT 0,
+    other_handle INTEGER DEFAULT 0,
+    group_title TEXT,
+    group_action_type INTEGER DEFAULT 0,
+    share_status INTEGER DEFAULT 0,
+    share_direction INTEGER DEFAULT 0,
+    associated_message_guid TEXT,
+    associated_message_type INTEGER DEFAULT 0,
+    balloon_bundle_id TEXT,
+    payload_data BLOB,
+    expressive_send_style_id TEXT,
+    message_summary_info BLOB,
+    destination_caller_id TEXT,
+    thread_originator_guid TEXT,
+    thread_originator_part TEXT,
+    syndication_ranges TEXT,
+    date_edited INTEGER,
+    associated_message_emoji TEXT DEFAULT NULL
+);
+
+CREATE TABLE attachment (
+    ROWID INTEGER PRIMARY KEY AUTOINCREMENT,
+    guid TEXT UNIQUE NOT NULL,
+    filename TEXT,
+    uti TEXT,
+    mime_type TEXT,
+    transfer_name TEXT,
+    total_bytes INTEGER DEFAULT 0
+);
+
+CREATE TABLE message_attachment_join (
+    message_id INTEGER REFERENCES message (ROWID) ON DELETE CASCADE,
+    attachment_id INTEGER REFERENCES attachment (ROWID) ON DELETE CASCADE,
+    UNIQUE(message_id, attachment_id)
+);
+
+CREATE TABLE chat_recoverable_message_join (
+    chat_id INTEGER REFERENCES chat (ROWID) ON DELETE CASCADE,
+    message_id INTEGER REFERENCES message (ROWID) ON DELETE CASCADE,
+    delete_date INTEGER,
+    PRIMARY KEY (chat_id, message_id)
 );
 
 CREATE TABLE chat_message_join (
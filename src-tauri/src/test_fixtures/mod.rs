@@ -93,6 +93,50 @@ pub fn standard_test_scenario() -> Result<(TestIMessageDb, TestAddressBookDb)> {
     Ok((imessage_db, contacts_db))
 }
 
+/// Owner signed in with two accounts (me@icloud.com, me@work.example.com)
+/// whose work handle sits in a group chat with Bob. Returns the group chat.
+#[allow(dead_code)]
+pub fn multi_account_scenario() -> Result<(TestIMessageDb, i32)> {
+    let mut db = TestIMessageDb::new()?;
+    let bob = db.handle(HandleBuilder::new("+15551234567"))?;
+    let owner_work = db.handle(HandleBuilder::new("me@work.example.com"))?;
+
+    // Each 1:1 chat runs on a different one of the owner's accounts
+    let personal_chat = db.chat(ChatBuilder::new("iMessage;-;+15551234567"))?;
+    db.chat_handle(personal_chat, bob)?;
+    db.message(
+        MessageBuilder::new()
+            .text("Hi from Bob")
+            .handle(bob)
+            .chat(personal_chat)
+            .account("me@icloud.com")
+            .date(1_000_000),
+    )?;
+    let work_chat = db.chat(ChatBuilder::new("iMessage;-;bob@work.example.com"))?;
+    db.message(
+        MessageBuilder::new()
+            .text("Work hello")
+            .chat(work_chat)
+            .from_me()
+            .account("me@work.example.com")
+            .date(2_000_000),
+    )?;
+
+    let group_chat = db.chat(ChatBuilder::new("chat900").group())?;
+    db.chat_handle(group_chat, bob)?;
+    db.chat_handle(group_chat, owner_work)?;
+    db.message(
+        MessageBuilder::new()
+            .text("Sent from my work account")
+            .handle(owner_work)
+            .chat(group_chat)
+            .account("me@icloud.com")
+            .date(3_000_000),
+    )?;
+
+    Ok((db, group_chat))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
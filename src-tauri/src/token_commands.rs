@@ -0,0 +1,64 @@
+//! Tauri commands for managing automation access tokens in settings (see
+//! `chat_to_map_desktop::access_tokens`). A new token is returned once, by
+//! `create_access_token`; after that only its name and scopes are listed.
+
+use std::collections::BTreeSet;
+
+use chat_to_map_desktop::access_tokens::{Scope, TokenRecord, TokenStore};
+use serde::Serialize;
+
+use crate::settings_commands::app_local_data_dir;
+
+/// A newly created token and its record
+#[derive(Debug, Clone, Serialize)]
+pub struct CreatedToken {
+    pub record: TokenRecord,
+    /// The token itself; it can't be shown again
+    pub token: String,
+}
+
+/// List the automation tokens (without the tokens themselves)
+#[tauri::command]
+pub fn list_access_tokens(app_handle: tauri::AppHandle) -> Result<Vec<TokenRecord>, String> {
+    Ok(TokenStore::load(&app_local_data_dir(&app_handle)?)?
+        .tokens()
+        .to_vec())
+}
+
+/// Create a token allowed to do only `scopes`
+#[tauri::command]
+pub fn create_access_token(
+    name: String,
+    scopes: BTreeSet<Scope>,
+    app_handle: tauri::AppHandle,
+) -> Result<CreatedToken, String> {
+    if name.trim().is_empty() {
+        return Err("Give the token a name".to_string());
+    }
+    if scopes.is_empty() {
+        return Err("Choose at least one thing the token may do".to_string());
+    }
+    let data_dir = app_local_data_dir(&app_handle)?;
+    let mut store = TokenStore::load(&data_dir)?;
+    let (record, token) = store.create(&name, scopes);
+    store.save(&data_dir)?;
+    tracing::info!(
+        "[access_tokens] Created token {} with scopes {:?}",
+        record.id,
+        record.scopes
+    );
+    Ok(CreatedToken { record, token })
+}
+
+/// Revoke a token; scripts using it are refused from then on
+#[tauri::command]
+pub fn revoke_access_token(id: String, app_handle: tauri::AppHandle) -> Result<(), String> {
+    let data_dir = app_local_data_dir(&app_handle)?;
+    let mut store = TokenStore::load(&data_dir)?;
+    if !store.revoke(&id) {
+        return Err(format!("No access token {id}"));
+    }
+    store.save(&data_dir)?;
+    tracing::info!("[access_tokens] Revoked token {id}");
+    Ok(())
+}
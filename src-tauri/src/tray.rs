@@ -0,0 +1,121 @@
+//! The tray icon: what the export pipeline is doing, and quick actions.
+//!
+//! The first (disabled) menu item and the tooltip follow the
+//! `ExportManager` status (see the library's export/state.rs) as exports
+//! start, advance and end. The menu opens the window, cancels the running
+//! export, or opens the results page of the latest upload.
+
+use chat_to_map_desktop::{
+    export::state::{ExportState, ExportStatus},
+    upload_history,
+};
+use tauri::{
+    menu::{MenuBuilder, MenuEvent, MenuItemBuilder},
+    tray::TrayIconBuilder,
+    Manager,
+};
+
+use crate::{settings_commands::app_local_data_dir, AppState};
+
+/// Add the tray icon and keep it in step with the running export
+pub(crate) fn build(app: &tauri::App) -> tauri::Result<()> {
+    let status_item = MenuItemBuilder::new(status_label(ExportState::Idle))
+        .id("tray_status")
+        .enabled(false)
+        .build(app)?;
+
+    let open_item = MenuItemBuilder::new("Open ChatToMap")
+        .id("tray_open")
+        .build(app)?;
+
+    let cancel_item = MenuItemBuilder::new("Cancel Current Export")
+        .id("tray_cancel")
+        .enabled(false)
+        .build(app)?;
+
+    let results_item = MenuItemBuilder::new("View Last Results")
+        .id("tray_last_results")
+        .build(app)?;
+
+    let menu = MenuBuilder::new(app)
+        .item(&status_item)
+        .separator()
+        .item(&open_item)
+        .item(&cancel_item)
+        .item(&results_item)
+        .build()?;
+
+    let mut tray = TrayIconBuilder::with_id("main")
+        .menu(&menu)
+        .tooltip("ChatToMap")
+        .on_menu_event(on_menu_event);
+    if let Some(icon) = app.default_window_icon() {
+        tray = tray.icon(icon.clone());
+    }
+    let tray = tray.build(app)?;
+
+    let mut changes = app.state::<AppState>().export_manager().subscribe();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let status = changes.borrow_and_update().clone();
+            let label = status_label(status.state);
+            let _ = status_item.set_text(label);
+            let _ = cancel_item.set_enabled(cancellable(&status));
+            let _ = tray.set_tooltip(Some(format!("ChatToMap: {label}")));
+            if changes.changed().await.is_err() {
+                break;
+            }
+        }
+    });
+    Ok(())
+}
+
+fn on_menu_event(app: &tauri::AppHandle, event: MenuEvent) {
+    match event.id().as_ref() {
+        "tray_open" => {
+            if let Some(window) = app.get_webview_window("main") {
+                let _ = window.unminimize();
+                let _ = window.show();
+                let _ = window.set_focus();
+            }
+        }
+        "tray_cancel" => {
+            let state = app.state::<AppState>();
+            let manager = state.export_manager();
+            if let Some(export_id) = manager.status().export_id {
+                if let Err(e) = manager.cancel(&export_id) {
+                    tracing::warn!("[tray] Failed to cancel export {export_id}: {e}");
+                }
+            }
+        }
+        "tray_last_results" => {
+            if let Err(e) = open_last_results(app) {
+                tracing::warn!("[tray] {e}");
+            }
+        }
+        _ => {}
+    }
+}
+
+fn status_label(state: ExportState) -> &'static str {
+    match state {
+        ExportState::Idle => "No export running",
+        ExportState::Exporting => "Exporting...",
+        ExportState::WaitingToUpload => "Waiting to upload",
+        ExportState::Uploading => "Uploading...",
+        ExportState::Processing => "Processing on the server...",
+    }
+}
+
+/// Exports can be cancelled until processing starts (see `ExportManager::cancel`)
+fn cancellable(status: &ExportStatus) -> bool {
+    status.export_id.is_some() && status.state != ExportState::Processing
+}
+
+fn open_last_results(app: &tauri::AppHandle) -> Result<(), String> {
+    let record = upload_history::load(&app_local_data_dir(app)?)?
+        .into_iter()
+        .next()
+        .ok_or("No uploads yet")?;
+    open::that(&record.results_url).map_err(|e| format!("Failed to open results page: {e}"))
+}
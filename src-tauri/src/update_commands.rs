@@ -0,0 +1,137 @@
+//! In-app updates through tauri-plugin-updater.
+//!
+//! `check_for_updates` looks for a newer release on the channel in settings
+//! (see the library's updates.rs), and `install_update` downloads and
+//! installs it, then restarts the app. Help → Check for Updates... does
+//! both, asking before installing.
+
+use chat_to_map_desktop::updates::{UpdateChannel, UPDATER_PUBKEY};
+use serde::{Deserialize, Serialize};
+use tauri_plugin_dialog::{DialogExt, MessageDialogButtons};
+use tauri_plugin_updater::{Update, UpdaterExt};
+
+use crate::settings_commands::load_settings;
+
+/// The app's context, with the updater configured from the build rather
+/// than tauri.conf.json, so only release builds carry a public key
+pub fn app_context() -> tauri::Context<tauri::Wry> {
+    let mut context = tauri::generate_context!();
+    // The plugin won't start without a config; without a key, `find_update`
+    // stops before it's used
+    context.config_mut().plugins.0.insert(
+        "updater".to_string(),
+        serde_json::json!({ "pubkey": UPDATER_PUBKEY.unwrap_or_default() }),
+    );
+    context
+}
+
+/// A newer release than the running one
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AvailableUpdate {
+    pub version: String,
+    pub current_version: String,
+    /// Release notes
+    pub notes: Option<String>,
+    /// When the release was published
+    pub date: Option<String>,
+}
+
+impl From<&Update> for AvailableUpdate {
+    fn from(update: &Update) -> Self {
+        Self {
+            version: update.version.clone(),
+            current_version: update.current_version.clone(),
+            notes: update.body.clone(),
+            date: update.date.map(|date| date.to_string()),
+        }
+    }
+}
+
+/// The newest release on the update channel, if it's newer than this one
+#[tauri::command]
+pub async fn check_for_updates(
+    app_handle: tauri::AppHandle,
+) -> Result<Option<AvailableUpdate>, String> {
+    Ok(find_update(&app_handle).await?.as_ref().map(Into::into))
+}
+
+/// Download and install the newest release, then restart into it
+#[tauri::command]
+pub async fn install_update(app_handle: tauri::AppHandle) -> Result<(), String> {
+    let update = find_update(&app_handle)
+        .await?
+        .ok_or("ChatToMap is already up to date")?;
+    install(&app_handle, update).await
+}
+
+/// Check for an update from the Help menu, offering to install it
+pub(crate) fn check_from_menu(app_handle: &tauri::AppHandle) {
+    let app_handle = app_handle.clone();
+    tauri::async_runtime::spawn(async move {
+        let dialog = app_handle.dialog();
+        let update = match find_update(&app_handle).await {
+            Ok(Some(update)) => update,
+            Ok(None) => {
+                dialog
+                    .message("You're running the latest version of ChatToMap.")
+                    .title("No Updates")
+                    .show(|_| {});
+                return;
+            }
+            Err(e) => {
+                dialog.message(e).title("Update Check Failed").show(|_| {});
+                return;
+            }
+        };
+        let prompt = format!(
+            "ChatToMap {} is available (you have {}). Install it and restart?",
+            update.version, update.current_version
+        );
+        let installer = app_handle.clone();
+        dialog
+            .message(prompt)
+            .title("Update Available")
+            .buttons(MessageDialogButtons::OkCancelCustom(
+                "Install".to_string(),
+                "Later".to_string(),
+            ))
+            .show(move |accepted| {
+                if accepted {
+                    tauri::async_runtime::spawn(async move {
+                        if let Err(e) = install(&installer, update).await {
+                            tracing::error!("[updates] {e}");
+                        }
+                    });
+                }
+            });
+    });
+}
+
+async fn find_update(app_handle: &tauri::AppHandle) -> Result<Option<Update>, String> {
+    let pubkey = UPDATER_PUBKEY
+        .ok_or("This build can't update itself. Download new versions from GitHub.")?;
+    let channel = UpdateChannel::resolve(load_settings(app_handle)?.update_channel);
+    let manifest_url = channel
+        .manifest_url()
+        .parse::<tauri::Url>()
+        .map_err(|e| format!("Invalid update URL: {e}"))?;
+    tracing::info!("[updates] Checking the {channel:?} channel");
+    app_handle
+        .updater_builder()
+        .pubkey(pubkey)
+        .endpoints(vec![manifest_url])
+        .and_then(|builder| builder.build())
+        .map_err(|e| format!("Failed to set up updates: {e}"))?
+        .check()
+        .await
+        .map_err(|e| format!("Failed to check for updates: {e}"))
+}
+
+async fn install(app_handle: &tauri::AppHandle, update: Update) -> Result<(), String> {
+    tracing::info!("[updates] Installing {}", update.version);
+    update
+        .download_and_install(|_, _| {}, || {})
+        .await
+        .map_err(|e| format!("Failed to install the update: {e}"))?;
+    app_handle.restart()
+}
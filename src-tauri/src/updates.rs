@@ -0,0 +1,90 @@
+/*!
+ * Where in-app updates come from.
+ *
+ * Releases are GitHub releases, and a tag with a `-` (`v0.5.0-beta.1`) is
+ * a prerelease (see release.yml). Every release attaches a `latest.json`
+ * and copies it to the prerelease tagged `beta`, so the updater reads, for
+ * the chosen [`UpdateChannel`], the latest release's for stable or the
+ * `beta` release's (the newest of any kind) for beta. Settings pick the
+ * channel, defaulting to the one the build was made for
+ * (`CTM_UPDATE_CHANNEL`, see build.rs).
+ *
+ * Updates are signed, and checked against the public key embedded at
+ * compile time from `CTM_UPDATER_PUBKEY`, which release.yml sets. A build
+ * without one (a local or dev build) can't update itself.
+ */
+
+use serde::{Deserialize, Serialize};
+
+/// Public key update bundles are signed with, if this build has one
+pub const UPDATER_PUBKEY: Option<&str> = option_env!("CTM_UPDATER_PUBKEY");
+
+const RELEASES_URL: &str = "https://github.com/DocSpring/chat_to_map_desktop/releases";
+
+/// Which releases the app updates to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UpdateChannel {
+    #[default]
+    Stable,
+    /// Prereleases as well
+    Beta,
+}
+
+impl UpdateChannel {
+    /// The channel picked in settings, else the build's
+    pub fn resolve(setting: Option<Self>) -> Self {
+        Self::resolve_for(setting, env!("CTM_UPDATE_CHANNEL"))
+    }
+
+    /// [`Self::resolve`] for a build made for the channel named `build`
+    fn resolve_for(setting: Option<Self>, build: &str) -> Self {
+        setting.unwrap_or_else(|| Self::parse(build))
+    }
+
+    /// The updater manifest (`latest.json`) listing this channel's release
+    pub fn manifest_url(self) -> String {
+        match self {
+            Self::Stable => format!("{RELEASES_URL}/latest/download/latest.json"),
+            Self::Beta => format!("{RELEASES_URL}/download/beta/latest.json"),
+        }
+    }
+
+    fn parse(name: &str) -> Self {
+        if name.eq_ignore_ascii_case("beta") {
+            Self::Beta
+        } else {
+            Self::Stable
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings_pick_the_channel_over_the_builds() {
+        assert_eq!(UpdateChannel::parse("Beta"), UpdateChannel::Beta);
+        assert_eq!(UpdateChannel::parse("nightly"), UpdateChannel::Stable);
+        assert_eq!(
+            UpdateChannel::resolve_for(Some(UpdateChannel::Stable), "beta"),
+            UpdateChannel::Stable
+        );
+        assert_eq!(
+            UpdateChannel::resolve_for(None, "beta"),
+            UpdateChannel::Beta
+        );
+        assert_eq!(
+            UpdateChannel::resolve_for(None, "stable"),
+            UpdateChannel::Stable
+        );
+        assert_eq!(
+            serde_json::to_string(&UpdateChannel::Beta).unwrap(),
+            "\"beta\""
+        );
+        assert!(UpdateChannel::Stable
+            .manifest_url()
+            .ends_with("/releases/latest/download/latest.json"));
+    }
+}
@@ -9,21 +9,50 @@
  * Each presign / complete request carries an HMAC signature so the SaaS
  * backend can skip Turnstile (the desktop app cannot run a Turnstile widget).
  * See `src/api.rs` for the signing helper.
+ *
+ * The zip's SHA-256 (computed when the export is written) travels with both
+ * the storage upload, as a `Digest` header that Convex verifies, and the
+ * complete request, so a corrupted transfer fails before processing starts.
+ *
+ * Requests and the upload as a whole are time-limited (see
+ * upload_timeouts.rs), and go through the proxy set in settings, if any
+ * (see upload_proxy.rs). A rate-limited request says when to try again
+ * (see upload_rate_limit.rs). The plan's limits can be checked before
+ * uploading (see upload_usage.rs), and the export's manifest with the
+ * server (see upload_validate.rs).
  */
 
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     fs::File,
     io::{Read, Write},
     path::Path,
 };
 
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
 use uuid::Uuid;
 
 use crate::api::{
     ApiClient, ClientLocale, ConvexStorageUploadResponse, UploadCompleteData, UploadCompleteRequest,
 };
 
+#[path = "upload_proxy.rs"]
+mod proxy;
+#[path = "upload_rate_limit.rs"]
+mod rate_limit;
+#[path = "upload_timeouts.rs"]
+mod timeouts;
+#[path = "upload_usage.rs"]
+mod usage;
+#[path = "upload_validate.rs"]
+mod validate;
+pub use proxy::{check_proxy_url, test_connection};
+pub(crate) use rate_limit::check_rate_limit;
+pub use rate_limit::{retry_rate_limited, RateLimit, MAX_RATE_LIMIT_RETRIES};
+pub use timeouts::{UploadDeadline, UploadError, UploadTimeout, UploadTimeouts};
+pub use usage::{get_account_usage, AccountUsage};
+pub use validate::{validate_export, UploadRejection, ValidateData};
+
 // =============================================================================
 // System locale detection
 // =============================================================================
@@ -49,6 +78,16 @@ pub struct PresignResponse {
     pub upload_url: String,
 }
 
+/// The stored zip, as reported to `/api/upload/complete`
+#[derive(Debug, Clone, Copy)]
+pub struct UploadedZip<'a> {
+    /// Returned by [`upload_file`]
+    pub storage_id: &'a str,
+    pub original_filename: Option<&'a str>,
+    /// `ExportResult::zip_sha256`
+    pub sha256: &'a str,
+}
+
 /// Result of completing the upload — the IDs we need to build the results URL.
 #[derive(Debug, Clone)]
 pub struct CreateJobResponse {
@@ -140,12 +179,15 @@ pub fn read_or_create_visitor_id(app_local_data_dir: &Path) -> String {
 // Client builder
 // =============================================================================
 
-fn build_client(
+pub(crate) fn build_client(
     api_host_override: Option<&str>,
     custom_headers: &HashMap<String, String>,
+    timeouts: &UploadTimeouts,
 ) -> ApiClient {
     let base_url = api_host_override.unwrap_or(API_BASE_URL);
-    ApiClient::new(base_url).with_extra_headers(custom_headers)
+    ApiClient::new(base_url)
+        .with_extra_headers(custom_headers)
+        .with_timeouts(timeouts)
 }
 
 pub fn results_base_url(web_host_override: Option<&str>) -> String {
@@ -160,8 +202,9 @@ pub async fn get_presigned_url(
     content_length: u64,
     api_host_override: Option<&str>,
     custom_headers: &HashMap<String, String>,
-) -> Result<PresignResponse, String> {
-    let client = build_client(api_host_override, custom_headers);
+    timeouts: &UploadTimeouts,
+) -> Result<PresignResponse, UploadError> {
+    let client = build_client(api_host_override, custom_headers, timeouts);
     let data = client.upload_presign(content_length).await?;
     Ok(PresignResponse {
         upload_url: data.upload_url,
@@ -173,8 +216,10 @@ pub async fn get_presigned_url(
 pub async fn upload_file(
     zip_path: &Path,
     upload_url: &str,
+    zip_sha256: &str,
     progress_callback: Option<UploadProgressCallback>,
-) -> Result<String, String> {
+    timeouts: &UploadTimeouts,
+) -> Result<String, UploadError> {
     let emit_progress = |percent: u8, message: String| {
         if let Some(ref cb) = progress_callback {
             cb(percent, message);
@@ -189,26 +234,25 @@ pub async fn upload_file(
         .map_err(|e| format!("Failed to read zip file: {e}"))?;
 
     let file_size = buffer.len();
+    let digest = digest_header(zip_sha256)?;
     emit_progress(10, format!("Uploading {}...", format_size(file_size)));
 
-    let http_client = reqwest::Client::new();
-    let response = http_client
+    let response = timeouts
+        .client()
         .post(upload_url)
         .header("Content-Type", "application/zip")
         .header("Content-Length", file_size)
+        .header("Digest", digest)
         .body(buffer)
         .send()
         .await
-        .map_err(|e| format!("Failed to upload file: {e}"))?;
+        .map_err(|e| UploadError::request(e, "upload", timeouts))?;
+    check_rate_limit(&response, "upload")?;
 
     if !response.status().is_success() {
         let status = response.status();
         let body = response.text().await.unwrap_or_default();
-        return Err(format!(
-            "Upload failed {}: {}",
-            status,
-            sanitize_error_body(&body)
-        ));
+        return Err(format!("Upload failed {}: {}", status, sanitize_error_body(&body)).into());
     }
 
     let body = response
@@ -227,13 +271,15 @@ pub async fn upload_file(
 }
 
 pub async fn complete_upload(
-    storage_id: &str,
+    zip: &UploadedZip<'_>,
+    upload_platform: &str,
     visitor_id: &str,
-    original_filename: Option<&str>,
+    metadata: &BTreeMap<String, String>,
     api_host_override: Option<&str>,
     custom_headers: &HashMap<String, String>,
-) -> Result<CreateJobResponse, String> {
-    let client = build_client(api_host_override, custom_headers);
+    timeouts: &UploadTimeouts,
+) -> Result<CreateJobResponse, UploadError> {
+    let client = build_client(api_host_override, custom_headers, timeouts);
     let locale = detect_system_locale();
     let client_locale = if locale.timezone.is_some() || locale.language.is_some() {
         Some(locale)
@@ -241,11 +287,13 @@ pub async fn complete_upload(
         None
     };
     let req = UploadCompleteRequest {
-        storage_id: storage_id.to_string(),
-        upload_platform: "imessage".to_string(),
-        original_filename: original_filename.map(|s| s.to_string()),
+        storage_id: zip.storage_id.to_string(),
+        upload_platform: upload_platform.to_string(),
+        original_filename: zip.original_filename.map(|s| s.to_string()),
         client_locale,
         visitor_id: visitor_id.to_string(),
+        zip_sha256: zip.sha256.to_string(),
+        metadata: metadata.clone(),
     };
     let data = client.upload_complete(req).await?;
     Ok(data.into())
@@ -324,6 +372,13 @@ fn sanitize_error_body(body: &str) -> String {
     truncate(trimmed, 200)
 }
 
+/// `Digest: sha-256=<base64>` for a hex SHA-256 (RFC 3230, as Convex
+/// storage expects)
+fn digest_header(sha256_hex: &str) -> Result<String, String> {
+    let bytes = hex::decode(sha256_hex).map_err(|e| format!("Invalid zip checksum: {e}"))?;
+    Ok(format!("sha-256={}", BASE64.encode(bytes)))
+}
+
 fn format_size(bytes: usize) -> String {
     const KB: usize = 1024;
     const MB: usize = KB * 1024;
@@ -352,6 +407,17 @@ mod tests {
         assert_eq!(format_size(1024 * 1024), "1.0 MB");
     }
 
+    #[test]
+    fn digest_header_is_base64_of_the_hash() {
+        // SHA-256 of no bytes
+        let empty = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+        assert_eq!(
+            digest_header(empty).unwrap(),
+            "sha-256=47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU="
+        );
+        assert!(digest_header("not hex").is_err());
+    }
+
     #[test]
     fn results_url_is_built_with_token() {
         let url = get_results_url(
@@ -0,0 +1,492 @@
+//! Uploading finished exports, right after the export or later from the
+//! outbox (see the library's outbox.rs).
+//!
+//! Exports save their zip to the outbox and hand it to [`upload_export`],
+//! which runs presign → PUT → complete (see upload.rs), opens the results
+//! page, and only then deletes the zip, so a failed upload can be retried.
+//! `list_pending_uploads`, `retry_upload` and `discard_pending_upload` work
+//! through what's left, and with `Settings::retry_uploads_on_launch` the app
+//! retries it all at startup. Each change to the outbox is announced with a
+//! `pending-uploads-changed` event.
+//!
+//! Uploads that reach the server are added to the upload history (see the
+//! library's upload_history.rs); `get_upload_history` lists them and
+//! `open_job_results` reopens one's results page.
+//!
+//! `get_account_usage` reports the plan's limits, which uploads are checked
+//! against before starting. `test_upload_connection` checks the upload
+//! server can be reached, through the proxy in settings (or one about to be
+//! saved).
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use chat_to_map_desktop::{
+    export::{
+        state::{ExportError, ExportRun, ExportState},
+        ExportProgress,
+    },
+    heartbeat::{retry_on_stall, Heartbeat, StagePolicy, DEFAULT_STALL_THRESHOLD},
+    outbox::{self, PendingUpload},
+    settings::Settings,
+    upload::{
+        check_proxy_url, complete_upload, get_presigned_url, get_results_url,
+        read_or_create_visitor_id, retry_rate_limited, test_connection, upload_file,
+        validate_export, AccountUsage, UploadDeadline, UploadError, UploadProgressCallback,
+        UploadTimeouts, UploadedZip, API_BASE_URL,
+    },
+    upload_history::{self, UploadRecord},
+};
+use tauri::{Emitter, Manager};
+
+use crate::{
+    export_commands::{emit_progress, start_export, start_stall_monitor, ExportResult},
+    notifications::notify_export_finished,
+    run_history::record_run,
+    settings_commands::{app_local_data_dir, load_settings},
+    AppState,
+};
+
+/// Retries of a stalled presign + upload before giving up
+const MAX_UPLOAD_RETRIES: u32 = 2;
+
+/// Slowest upload speed (bytes/s) treated as progress. The PUT reports no
+/// byte-level progress, so its stall allowance grows with the zip size.
+const MIN_UPLOAD_BYTES_PER_SEC: u64 = 50 * 1024;
+
+/// Everything the upload stages need from app state, captured up front so
+/// no lock is held across an `.await`.
+pub(crate) struct UploadContext {
+    web_host_override: Option<String>,
+    api_host_override: Option<String>,
+    custom_headers: HashMap<String, String>,
+    visitor_id: String,
+    timeouts: UploadTimeouts,
+    /// App local data, where the upload history is kept
+    data_dir: PathBuf,
+    /// Where zips wait for their upload (see the library's outbox.rs)
+    pub(crate) outbox: PathBuf,
+}
+
+impl UploadContext {
+    pub(crate) fn capture(app_handle: &tauri::AppHandle, state: &AppState) -> Result<Self, String> {
+        // Dev panel overrides: web host = results page (chattomap.com); api host
+        // = Convex HTTP actions (*.convex.site). Both default to compile-time
+        // constants (see upload.rs) when no override is set.
+        let overrides = state.host_overrides();
+        // Per-install visitor ID lives in app local data so the SaaS can reuse
+        // duplicate-upload detection for return visits.
+        let app_local_data_dir = app_handle
+            .path()
+            .app_local_data_dir()
+            .map_err(|e| format!("Failed to resolve app local data dir: {e}"))?;
+        Ok(Self {
+            web_host_override: overrides.server_host_override,
+            api_host_override: overrides.api_host_override,
+            custom_headers: overrides.custom_headers,
+            visitor_id: read_or_create_visitor_id(&app_local_data_dir),
+            timeouts: UploadTimeouts::from_settings(&load_settings(app_handle)?),
+            outbox: outbox::outbox_dir(&app_local_data_dir),
+            data_dir: app_local_data_dir,
+        })
+    }
+
+    /// The plan limits of this install's account (see the library's
+    /// upload_usage.rs)
+    async fn account_usage(&self) -> Result<AccountUsage, UploadError> {
+        chat_to_map_desktop::upload::get_account_usage(
+            &self.visitor_id,
+            self.api_host_override.as_deref(),
+            &self.custom_headers,
+            &self.timeouts,
+        )
+        .await
+    }
+}
+
+/// Upload a zip from the outbox and start processing (50-100% of
+/// progress). The zip leaves the outbox once processing starts; a failed
+/// upload is noted on it, to be retried.
+pub(crate) async fn upload_export(
+    pending: &PendingUpload,
+    context: &UploadContext,
+    window: &tauri::Window,
+    heartbeat: &Heartbeat,
+    run: &ExportRun,
+) -> Result<ExportResult, ExportError> {
+    let result = upload_pending(pending, context, window, heartbeat, run).await;
+    let updated = match &result {
+        Ok(uploaded) => {
+            record_upload(context, pending, uploaded);
+            outbox::remove(&context.outbox, &pending.id)
+        }
+        Err(e) => outbox::record_failure(&context.outbox, &pending.id, &e.to_string()),
+    };
+    if let Err(e) = updated {
+        tracing::warn!(
+            "[outbox] Failed to update pending upload {}: {e}",
+            pending.id
+        );
+    }
+    emit_pending_uploads(window, &context.outbox);
+    result
+}
+
+async fn upload_pending(
+    pending: &PendingUpload,
+    context: &UploadContext,
+    window: &tauri::Window,
+    heartbeat: &Heartbeat,
+    run: &ExportRun,
+) -> Result<ExportResult, ExportError> {
+    run.advance(ExportState::Uploading);
+    let zip_size = pending.size_bytes;
+    let zip_path = &pending.zip_path(&context.outbox);
+    check_with_server(pending, zip_path, context).await?;
+    let upload_policy = StagePolicy {
+        threshold: DEFAULT_STALL_THRESHOLD
+            + Duration::from_secs(zip_size / MIN_UPLOAD_BYTES_PER_SEC),
+        retryable: true,
+    };
+
+    let upload_started = Instant::now();
+    let deadline = UploadDeadline::start(&context.timeouts);
+    let presign_and_put = retry_on_stall(
+        heartbeat,
+        "Uploading",
+        upload_policy,
+        MAX_UPLOAD_RETRIES,
+        move || async move {
+            // Stage 2: Get pre-signed URL (50-55%)
+            emit_progress(window, run.id(), "Uploading", 50, "Preparing upload...");
+            let presign = || {
+                get_presigned_url(
+                    zip_size,
+                    context.api_host_override.as_deref(),
+                    &context.custom_headers,
+                    &context.timeouts,
+                )
+            };
+            let presign_response = retry_rate_limited(
+                presign,
+                server_busy(window, run, heartbeat, "Uploading", 50),
+            )
+            .await
+            .map_err(|e| e.context("Failed to get upload URL"))?;
+
+            // Stage 3: Upload file (55-90%)
+            emit_progress(window, run.id(), "Uploading", 55, "Uploading to server...");
+            heartbeat.beat("Uploading to server...");
+
+            let put = || {
+                upload_file(
+                    zip_path,
+                    &presign_response.upload_url,
+                    &pending.zip_sha256,
+                    Some(upload_progress(window, heartbeat, run.id())),
+                    &context.timeouts,
+                )
+            };
+            retry_rate_limited(put, server_busy(window, run, heartbeat, "Uploading", 55))
+                .await
+                .map_err(|e| e.context("Upload failed"))
+        },
+    );
+    let storage_id = deadline.run(presign_and_put).await?;
+    let mut metrics = pending.metrics.clone();
+    metrics.record_upload(upload_started.elapsed());
+
+    // Stage 4: Complete upload and start processing (90-95%). Not retried
+    // on a stall: a repeated request could start a second processing job.
+    // One the server refused (rate limited) is safe to repeat.
+    run.advance(ExportState::Processing);
+    emit_progress(window, run.id(), "Processing", 90, "Starting processing...");
+    heartbeat.begin_stage(
+        "Processing",
+        "Starting processing...",
+        StagePolicy::default(),
+    );
+
+    let uploaded = UploadedZip {
+        storage_id: &storage_id,
+        original_filename: pending.original_filename.as_deref(),
+        sha256: &pending.zip_sha256,
+    };
+    let complete = || {
+        complete_upload(
+            &uploaded,
+            &pending.upload_platform,
+            &context.visitor_id,
+            &pending.metadata,
+            context.api_host_override.as_deref(),
+            &context.custom_headers,
+            &context.timeouts,
+        )
+    };
+    let job_response = deadline
+        .run(retry_rate_limited(
+            complete,
+            server_busy(window, run, heartbeat, "Processing", 90),
+        ))
+        .await
+        .map_err(|e| e.context("Failed to start processing"))?;
+
+    // Stage 5: Complete (95-100%)
+    let results_url = get_results_url(
+        &job_response.chat_analysis_id,
+        job_response.job_token.as_deref(),
+        context.web_host_override.as_deref(),
+    );
+    emit_progress(window, run.id(), "Complete", 100, "Export complete!");
+
+    // Open browser to results page
+    if let Err(e) = open::that(&results_url) {
+        tracing::warn!("Failed to open browser: {e}");
+    }
+
+    Ok(ExportResult {
+        export_id: run.id().to_string(),
+        success: true,
+        chat_count: pending.chat_count,
+        chat_upload_id: Some(job_response.chat_upload_id),
+        chat_analysis_id: Some(job_response.chat_analysis_id),
+        job_token: job_response.job_token,
+        results_url: Some(results_url),
+        error: None,
+        metrics,
+    })
+}
+
+/// Add an upload that started processing to the upload history
+fn record_upload(context: &UploadContext, pending: &PendingUpload, uploaded: &ExportResult) {
+    let ids = (
+        &uploaded.chat_analysis_id,
+        &uploaded.chat_upload_id,
+        &uploaded.results_url,
+    );
+    let (Some(job_id), Some(chat_upload_id), Some(results_url)) = ids else {
+        return;
+    };
+    let record = UploadRecord {
+        job_id: job_id.clone(),
+        chat_upload_id: chat_upload_id.clone(),
+        uploaded_at: chrono::Utc::now().to_rfc3339(),
+        upload_platform: pending.upload_platform.clone(),
+        chat_count: pending.chat_count,
+        message_count: pending.total_messages,
+        results_url: results_url.clone(),
+        request: pending.request.clone(),
+    };
+    if let Err(e) = upload_history::append(&context.data_dir, &record) {
+        tracing::warn!("[history] Failed to record upload {job_id}: {e}");
+    }
+}
+
+/// Completed uploads, newest first
+#[tauri::command]
+pub fn get_upload_history(app_handle: tauri::AppHandle) -> Result<Vec<UploadRecord>, String> {
+    upload_history::load(&app_local_data_dir(&app_handle)?)
+}
+
+/// Open the results page of a past upload's job in the browser
+#[tauri::command]
+pub fn open_job_results(job_id: String, app_handle: tauri::AppHandle) -> Result<(), String> {
+    let record = upload_history::find(&app_local_data_dir(&app_handle)?, &job_id)?;
+    open::that(&record.results_url).map_err(|e| format!("Failed to open results page: {e}"))
+}
+
+/// The plan limits uploads from this install count against, so the app can
+/// warn about an export over them before uploading it
+#[tauri::command]
+pub async fn get_account_usage(
+    app_handle: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+) -> Result<AccountUsage, String> {
+    let context = UploadContext::capture(&app_handle, &state)?;
+    context.account_usage().await.map_err(|e| e.to_string())
+}
+
+/// Request the upload server the way uploads would, through `proxy_url`
+/// (else the saved proxy); returns the HTTP status it answered with
+#[tauri::command]
+pub async fn test_upload_connection(
+    proxy_url: Option<String>,
+    app_handle: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+) -> Result<u16, String> {
+    let mut timeouts = UploadTimeouts::from_settings(&load_settings(&app_handle)?);
+    if let Some(url) = proxy_url {
+        check_proxy_url(&url)?;
+        timeouts.proxy = Some(url);
+    }
+    let api_host = state.host_overrides().api_host_override;
+    let api_base_url = api_host.as_deref().unwrap_or(API_BASE_URL);
+    tracing::info!("[test_upload_connection] Requesting {api_base_url}");
+    test_connection(api_base_url, &timeouts)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Refuse up front what the plan won't take or the server can't process.
+/// Limits and verdicts the server can't give don't hold the upload up.
+async fn check_with_server(
+    pending: &PendingUpload,
+    zip_path: &Path,
+    context: &UploadContext,
+) -> Result<(), ExportError> {
+    match context.account_usage().await {
+        Ok(usage) => usage.check_upload(pending.size_bytes)?,
+        Err(e) => tracing::debug!("[upload] Plan limits unknown: {e}"),
+    }
+    let validated = validate_export(
+        zip_path,
+        &pending.upload_platform,
+        context.api_host_override.as_deref(),
+        &context.custom_headers,
+        &context.timeouts,
+    )
+    .await;
+    match validated {
+        Err(rejected @ UploadError::Rejected(_)) => Err(rejected.into()),
+        Err(e) => {
+            tracing::debug!("[upload] Export not validated: {e}");
+            Ok(())
+        }
+        Ok(()) => Ok(()),
+    }
+}
+
+/// Progress of the PUT, scaled to 55-90%, as `export-progress` events
+fn upload_progress(
+    window: &tauri::Window,
+    heartbeat: &Heartbeat,
+    export_id: &str,
+) -> UploadProgressCallback {
+    let window = window.clone();
+    let heartbeat = heartbeat.clone();
+    let export_id = export_id.to_string();
+    Box::new(move |percent: u8, message: String| {
+        heartbeat.beat(&message);
+        let _ = window.emit(
+            "export-progress",
+            ExportProgress {
+                stage: "Uploading".to_string(),
+                percent: 55 + (percent * 35 / 100),
+                message,
+                export_id: Some(export_id.clone()),
+            },
+        );
+    })
+}
+
+/// Count down a rate limit's wait in the progress message, which also
+/// keeps the stall monitor from taking the wait for a stall
+fn server_busy<'a>(
+    window: &'a tauri::Window,
+    run: &'a ExportRun,
+    heartbeat: &'a Heartbeat,
+    stage: &'a str,
+    percent: u8,
+) -> impl FnMut(u64) + 'a {
+    move |seconds| {
+        let message = format!("Server busy, retrying in {seconds}s...");
+        heartbeat.beat(&message);
+        emit_progress(window, run.id(), stage, percent, &message);
+    }
+}
+
+/// Emit the outbox's contents as `pending-uploads-changed`
+pub(crate) fn emit_pending_uploads(window: &tauri::Window, outbox: &Path) {
+    let _ = window.emit("pending-uploads-changed", outbox::list(outbox));
+}
+
+pub(crate) fn outbox_dir(app_handle: &tauri::AppHandle) -> Result<PathBuf, String> {
+    Ok(outbox::outbox_dir(&app_local_data_dir(app_handle)?))
+}
+
+/// Exports whose upload hasn't gone through, oldest first
+#[tauri::command]
+pub fn list_pending_uploads(app_handle: tauri::AppHandle) -> Result<Vec<PendingUpload>, String> {
+    Ok(outbox::list(&outbox_dir(&app_handle)?))
+}
+
+/// Upload a pending export again
+#[tauri::command]
+pub async fn retry_upload(
+    id: String,
+    app_handle: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    window: tauri::Window,
+) -> Result<ExportResult, ExportError> {
+    retry(&id, &app_handle, &state, &window).await
+}
+
+async fn retry(
+    id: &str,
+    app_handle: &tauri::AppHandle,
+    state: &AppState,
+    window: &tauri::Window,
+) -> Result<ExportResult, ExportError> {
+    let run = start_export(state, window)?;
+    let context = UploadContext::capture(app_handle, state)?;
+    let pending = outbox::load(&context.outbox, id)?;
+    tracing::info!("[outbox] Retrying upload {id} as export {}", run.id());
+    let (heartbeat, _monitor) = start_stall_monitor(window, "Uploading");
+    let result = run
+        .until_cancelled(upload_export(&pending, &context, window, &heartbeat, &run))
+        .await;
+    record_run(app_handle, &run, &result);
+    notify_export_finished(window, &result).await;
+    result
+}
+
+/// Delete a pending export without uploading it
+#[tauri::command]
+pub fn discard_pending_upload(
+    id: String,
+    app_handle: tauri::AppHandle,
+    window: tauri::Window,
+) -> Result<(), String> {
+    let outbox = outbox_dir(&app_handle)?;
+    outbox::remove(&outbox, &id)?;
+    tracing::info!("[outbox] Discarded pending upload {id}");
+    emit_pending_uploads(&window, &outbox);
+    Ok(())
+}
+
+/// Drop the zip of an export cancelled before its upload finished: the
+/// user asked for it not to be uploaded
+pub(crate) fn discard_cancelled(
+    context: &UploadContext,
+    run: &ExportRun,
+    result: &Result<ExportResult, ExportError>,
+) {
+    if matches!(result, Err(ExportError::Cancelled { .. })) {
+        let _ = outbox::remove(&context.outbox, run.id());
+    }
+}
+
+/// With `Settings::retry_uploads_on_launch`, upload everything left in the
+/// outbox, one export at a time, in the background
+pub(crate) fn retry_pending_on_launch(app_handle: &tauri::AppHandle, settings: &Settings) {
+    if !settings.retry_uploads_on_launch {
+        return;
+    }
+    let Some(window) = app_handle.get_webview_window("main") else {
+        return;
+    };
+    let app_handle = app_handle.clone();
+    tauri::async_runtime::spawn(async move {
+        let window = window.as_ref().window();
+        let state = app_handle.state::<AppState>().inner().clone();
+        let pending = outbox_dir(&app_handle)
+            .map(|outbox| outbox::list(&outbox))
+            .unwrap_or_default();
+        for upload in pending {
+            if let Err(e) = retry(&upload.id, &app_handle, &state, &window).await {
+                tracing::warn!("[outbox] Upload {} failed again: {e}", upload.id);
+            }
+        }
+    });
+}
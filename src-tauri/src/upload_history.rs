@@ -0,0 +1,134 @@
+/*!
+ * Local history of completed uploads.
+ *
+ * A map's results page is only reachable through its job ID and token,
+ * which the app showed once, right after the upload. Each upload that
+ * starts processing is now appended to
+ * `<app_local_data_dir>/upload_history.jsonl` (one JSON line per upload,
+ * like job_history.rs), so past maps can be listed and opened again. An
+ * upload from an iMessage export also keeps its [`ExportRequest`], so the
+ * website can ask for the export to be run again by the job ID it knows
+ * ([`rerun_request`]).
+ */
+
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    export::queue::ExportRequest,
+    job_history::{append_line, read_lines},
+};
+
+const HISTORY_FILENAME: &str = "upload_history.jsonl";
+
+/// One upload that reached the server
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UploadRecord {
+    /// `chat_analysis_id`: the processing job the results page shows
+    pub job_id: String,
+    pub chat_upload_id: String,
+    /// RFC 3339 timestamp of when processing started
+    pub uploaded_at: String,
+    /// Platform reported to the server (`imessage`, `telegram`)
+    pub upload_platform: String,
+    pub chat_count: usize,
+    pub message_count: usize,
+    /// Results page, with the job token that opens it
+    pub results_url: String,
+    /// The export that made the upload (iMessage exports only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request: Option<ExportRequest>,
+}
+
+/// Append `record` to the history in `app_local_data_dir`
+pub fn append(app_local_data_dir: &Path, record: &UploadRecord) -> Result<(), String> {
+    append_line(&app_local_data_dir.join(HISTORY_FILENAME), record)
+}
+
+/// Every recorded upload, newest first; unreadable lines are skipped
+pub fn load(app_local_data_dir: &Path) -> Result<Vec<UploadRecord>, String> {
+    let mut records: Vec<UploadRecord> = read_lines(&app_local_data_dir.join(HISTORY_FILENAME))?;
+    records.reverse();
+    Ok(records)
+}
+
+/// The recorded upload whose job is `job_id`
+pub fn find(app_local_data_dir: &Path, job_id: &str) -> Result<UploadRecord, String> {
+    load(app_local_data_dir)?
+        .into_iter()
+        .find(|record| record.job_id == job_id)
+        .ok_or_else(|| format!("No upload recorded for job {job_id}"))
+}
+
+/// The export behind the upload whose job is `job_id`, to run again and
+/// upload
+pub fn rerun_request(app_local_data_dir: &Path, job_id: &str) -> Result<ExportRequest, String> {
+    find(app_local_data_dir, job_id)?
+        .request
+        .map(|request| request.rerun())
+        .ok_or_else(|| format!("The export behind job {job_id} can't be run again"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(job_id: &str) -> UploadRecord {
+        UploadRecord {
+            job_id: job_id.to_string(),
+            chat_upload_id: format!("upload-{job_id}"),
+            uploaded_at: "2026-10-14T09:30:00+00:00".to_string(),
+            upload_platform: "imessage".to_string(),
+            chat_count: 3,
+            message_count: 1200,
+            results_url: format!("https://chattomap.com/processing/{job_id}?token=t"),
+            request: None,
+        }
+    }
+
+    #[test]
+    fn uploads_are_listed_newest_first_and_found_by_job() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(load(dir.path()).unwrap().is_empty());
+
+        append(dir.path(), &record("job-1")).unwrap();
+        append(dir.path(), &record("job-2")).unwrap();
+
+        let jobs: Vec<String> = load(dir.path())
+            .unwrap()
+            .into_iter()
+            .map(|record| record.job_id)
+            .collect();
+        assert_eq!(jobs, ["job-2", "job-1"]);
+        assert_eq!(find(dir.path(), "job-1").unwrap(), record("job-1"));
+        assert!(find(dir.path(), "job-3").is_err());
+    }
+
+    #[test]
+    fn exports_behind_uploads_can_be_run_again() {
+        let dir = tempfile::tempdir().unwrap();
+        let request = ExportRequest {
+            chat_ids: vec![4, 2],
+            upload_at: Some(chrono::Utc::now()),
+            keep_in_outbox: true,
+            ..ExportRequest::default()
+        };
+        append(
+            dir.path(),
+            &UploadRecord {
+                request: Some(request),
+                ..record("job-1")
+            },
+        )
+        .unwrap();
+        append(dir.path(), &record("telegram-job")).unwrap();
+
+        let rerun = rerun_request(dir.path(), "job-1").unwrap();
+        assert_eq!(rerun.chat_ids, [4, 2]);
+        assert_eq!(rerun.upload_at, None);
+        assert!(!rerun.keep_in_outbox);
+        assert!(rerun_request(dir.path(), "telegram-job").is_err());
+        assert!(rerun_request(dir.path(), "job-3").is_err());
+    }
+}
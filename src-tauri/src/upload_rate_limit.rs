@@ -0,0 +1,187 @@
+/*!
+ * Waiting out the server's rate limits.
+ *
+ * A server that is rate-limiting (429) or briefly unavailable (503) says
+ * when to come back in `Retry-After`, but uploads just failed with the
+ * status. Each upload request now turns those answers into an
+ * [`UploadError::RateLimited`] with the wait, and [`retry_rate_limited`]
+ * waits and tries again, up to [`MAX_RATE_LIMIT_RETRIES`] times, reporting
+ * the time left each second so the UI can count down. A wait too long to
+ * sit through, or one more refusal, is returned with its wait time.
+ */
+
+use std::{future::Future, time::Duration};
+
+use chrono::{DateTime, Utc};
+use reqwest::{header::RETRY_AFTER, StatusCode};
+
+use super::UploadError;
+
+/// Times a rate-limited request is tried again
+pub const MAX_RATE_LIMIT_RETRIES: u32 = 3;
+
+/// Longest `Retry-After` waited out; a longer wait is returned instead
+const MAX_RETRY_WAIT: Duration = Duration::from_secs(2 * 60);
+
+/// The wait when a refusal doesn't say (or says something unreadable)
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(5);
+
+/// A request the server refused for now, and when to try again
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RateLimit {
+    /// `presign`, `upload`, `complete`, `usage` or `validate`
+    pub step: String,
+    pub retry_after: Duration,
+}
+
+/// A rate limit for a 429 or 503 `response`, with its `Retry-After`
+pub(crate) fn check_rate_limit(
+    response: &reqwest::Response,
+    step: &str,
+) -> Result<(), UploadError> {
+    let status = response.status();
+    if status != StatusCode::TOO_MANY_REQUESTS && status != StatusCode::SERVICE_UNAVAILABLE {
+        return Ok(());
+    }
+    let retry_after = response
+        .headers()
+        .get(RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| parse_retry_after(value, Utc::now()))
+        .unwrap_or(DEFAULT_RETRY_AFTER);
+    Err(UploadError::RateLimited(RateLimit {
+        step: step.to_string(),
+        retry_after,
+    }))
+}
+
+/// Run `attempt`, waiting out and retrying rate limits; `on_wait` is given
+/// the seconds left, once a second while waiting
+pub async fn retry_rate_limited<T, F, Fut>(
+    mut attempt: F,
+    mut on_wait: impl FnMut(u64),
+) -> Result<T, UploadError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, UploadError>>,
+{
+    let mut retries = 0;
+    loop {
+        match attempt().await {
+            Err(UploadError::RateLimited(limit))
+                if retries < MAX_RATE_LIMIT_RETRIES && limit.retry_after <= MAX_RETRY_WAIT =>
+            {
+                retries += 1;
+                tracing::warn!(
+                    "[upload] {} rate limited, retrying in {}s ({retries}/{MAX_RATE_LIMIT_RETRIES})",
+                    limit.step,
+                    limit.retry_after.as_secs()
+                );
+                wait_out(limit.retry_after, &mut on_wait).await;
+            }
+            result => return result,
+        }
+    }
+}
+
+async fn wait_out(wait: Duration, on_wait: &mut impl FnMut(u64)) {
+    let until = tokio::time::Instant::now() + wait;
+    loop {
+        let remaining = until.saturating_duration_since(tokio::time::Instant::now());
+        if remaining.is_zero() {
+            return;
+        }
+        on_wait(remaining.as_secs_f64().ceil() as u64);
+        tokio::time::sleep(remaining.min(Duration::from_secs(1))).await;
+    }
+}
+
+/// `Retry-After` as seconds (`120`) or an HTTP date, counted from `now`
+fn parse_retry_after(value: &str, now: DateTime<Utc>) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let at = DateTime::parse_from_rfc2822(value).ok()?;
+    Some(
+        (at.with_timezone(&Utc) - now)
+            .to_std()
+            .unwrap_or(Duration::ZERO),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    fn refusal<T>(retry_after: Duration) -> Result<T, UploadError> {
+        Err(UploadError::RateLimited(RateLimit {
+            step: "presign".to_string(),
+            retry_after,
+        }))
+    }
+
+    #[test]
+    fn retry_after_is_seconds_or_a_date() {
+        let now = DateTime::parse_from_rfc3339("2026-10-14T07:28:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let parse = |value| parse_retry_after(value, now);
+        assert_eq!(parse(" 120 "), Some(Duration::from_secs(120)));
+        assert_eq!(
+            parse("Wed, 14 Oct 2026 07:28:30 GMT"),
+            Some(Duration::from_secs(30))
+        );
+        // A date already past means now
+        assert_eq!(parse("Wed, 14 Oct 2026 07:00:00 GMT"), Some(Duration::ZERO));
+        assert_eq!(parse("soon"), None);
+    }
+
+    #[tokio::test]
+    async fn rate_limits_are_waited_out_until_retries_run_out() {
+        let attempts = Cell::new(0);
+        let mut waits = Vec::new();
+        let result = retry_rate_limited(
+            || {
+                attempts.set(attempts.get() + 1);
+                let result = match attempts.get() {
+                    1 => refusal(Duration::from_millis(10)),
+                    _ => Ok("uploaded"),
+                };
+                async move { result }
+            },
+            |seconds| waits.push(seconds),
+        )
+        .await;
+        assert_eq!(result, Ok("uploaded"));
+        assert_eq!(waits, [1]);
+
+        // Refused every time: the last refusal is returned
+        attempts.set(0);
+        let result: Result<(), _> = retry_rate_limited(
+            || {
+                attempts.set(attempts.get() + 1);
+                async { refusal(Duration::ZERO) }
+            },
+            |_| {},
+        )
+        .await;
+        assert_eq!(result, refusal(Duration::ZERO));
+        assert_eq!(attempts.get(), 1 + MAX_RATE_LIMIT_RETRIES);
+
+        // Too long to wait: returned straight away
+        attempts.set(0);
+        let result: Result<(), _> = retry_rate_limited(
+            || {
+                attempts.set(attempts.get() + 1);
+                async { refusal(Duration::from_secs(3600)) }
+            },
+            |_| {},
+        )
+        .await;
+        assert_eq!(result, refusal(Duration::from_secs(3600)));
+        assert_eq!(attempts.get(), 1);
+    }
+}
@@ -0,0 +1,234 @@
+/*!
+ * Time limits on uploads.
+ *
+ * reqwest waits forever by default, so a connection that stalled mid-upload
+ * hung the export. Each upload request now gives up on a server that won't
+ * connect or stops responding ([`UploadTimeouts::connect`] and
+ * [`UploadTimeouts::read`]), and [`UploadDeadline`] bounds presign, PUT and
+ * complete together. All three are configurable in settings, and running
+ * into one is an [`UploadError::Timeout`], not just a failure message.
+ *
+ * The client built here also goes through the proxy set in settings (see
+ * upload_proxy.rs).
+ */
+
+use std::{fmt, future::Future, time::Duration};
+
+use super::{RateLimit, UploadRejection};
+use crate::settings::Settings;
+
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(15);
+const DEFAULT_READ_TIMEOUT: Duration = Duration::from_secs(60);
+/// Room for a large zip on a slow connection
+const DEFAULT_UPLOAD_DEADLINE: Duration = Duration::from_secs(2 * 60 * 60);
+
+/// How long upload requests, and the upload as a whole, may take, and the
+/// proxy the requests go through
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UploadTimeouts {
+    /// Connecting to a server
+    pub connect: Duration,
+    /// Waiting for more of a response
+    pub read: Duration,
+    /// Presign, PUT and complete together
+    pub deadline: Duration,
+    /// `Settings::upload_proxy_url` (`None` = the environment's proxy)
+    pub proxy: Option<String>,
+}
+
+impl Default for UploadTimeouts {
+    fn default() -> Self {
+        Self {
+            connect: DEFAULT_CONNECT_TIMEOUT,
+            read: DEFAULT_READ_TIMEOUT,
+            deadline: DEFAULT_UPLOAD_DEADLINE,
+            proxy: None,
+        }
+    }
+}
+
+impl UploadTimeouts {
+    /// The limits set in settings, defaulting those unset (or 0)
+    pub fn from_settings(settings: &Settings) -> Self {
+        let secs = |value: Option<u64>, default: Duration| {
+            value
+                .filter(|&secs| secs > 0)
+                .map_or(default, Duration::from_secs)
+        };
+        Self {
+            connect: secs(
+                settings.upload_connect_timeout_secs,
+                DEFAULT_CONNECT_TIMEOUT,
+            ),
+            read: secs(settings.upload_read_timeout_secs, DEFAULT_READ_TIMEOUT),
+            deadline: secs(settings.upload_deadline_secs, DEFAULT_UPLOAD_DEADLINE),
+            proxy: settings
+                .upload_proxy_url
+                .clone()
+                .filter(|url| !url.trim().is_empty()),
+        }
+    }
+
+    /// An HTTP client that gives up on a server after these limits
+    pub(crate) fn client(&self) -> reqwest::Client {
+        let builder = reqwest::Client::builder()
+            .connect_timeout(self.connect)
+            .read_timeout(self.read);
+        super::proxy::apply(builder, self.proxy.as_deref())
+            .build()
+            .unwrap_or_default()
+    }
+}
+
+/// The time left for an upload, started when the upload begins
+#[derive(Debug, Clone, Copy)]
+pub struct UploadDeadline {
+    at: tokio::time::Instant,
+    limit: Duration,
+}
+
+impl UploadDeadline {
+    pub fn start(timeouts: &UploadTimeouts) -> Self {
+        Self {
+            at: tokio::time::Instant::now() + timeouts.deadline,
+            limit: timeouts.deadline,
+        }
+    }
+
+    /// Run an upload step, failing with a timeout if the deadline passes
+    /// first
+    pub async fn run<T>(
+        &self,
+        step: impl Future<Output = Result<T, UploadError>>,
+    ) -> Result<T, UploadError> {
+        tokio::time::timeout_at(self.at, step)
+            .await
+            .unwrap_or_else(|_| {
+                Err(UploadError::Timeout(UploadTimeout {
+                    step: "deadline".to_string(),
+                    limit: self.limit,
+                }))
+            })
+    }
+}
+
+/// The limit an upload ran into
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UploadTimeout {
+    /// `presign`, `upload`, `complete`, or `deadline` for the whole upload
+    pub step: String,
+    pub limit: Duration,
+}
+
+/// Why an upload step failed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UploadError {
+    /// A request or the whole upload took too long
+    Timeout(UploadTimeout),
+    /// The server refused a request for now (see upload_rate_limit.rs)
+    RateLimited(RateLimit),
+    /// The server won't process the export (see upload_validate.rs)
+    Rejected(UploadRejection),
+    Failed(String),
+}
+
+impl UploadError {
+    /// A request that failed, as a timeout if it ran into one of `timeouts`
+    pub(crate) fn request(error: reqwest::Error, step: &str, timeouts: &UploadTimeouts) -> Self {
+        if !error.is_timeout() {
+            // Not the proxy's URL: it can hold a password
+            let through = match &timeouts.proxy {
+                Some(_) if error.is_connect() => " (through the upload proxy)",
+                _ => "",
+            };
+            return Self::Failed(format!("{step} request failed{through}: {error}"));
+        }
+        let limit = if error.is_connect() {
+            timeouts.connect
+        } else {
+            timeouts.read
+        };
+        Self::Timeout(UploadTimeout {
+            step: step.to_string(),
+            limit,
+        })
+    }
+
+    /// Prefix a failure's message with `context` (a timeout, rate limit or
+    /// rejection describes itself)
+    pub fn context(self, context: &str) -> Self {
+        match self {
+            Self::Failed(message) => Self::Failed(format!("{context}: {message}")),
+            timeout => timeout,
+        }
+    }
+}
+
+impl fmt::Display for UploadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Timeout(UploadTimeout { step, limit }) if step == "deadline" => {
+                write!(f, "Upload did not finish within {}s", limit.as_secs())
+            }
+            Self::Timeout(UploadTimeout { step, limit }) => {
+                write!(f, "{step} request timed out after {}s", limit.as_secs())
+            }
+            Self::RateLimited(RateLimit { step, retry_after }) => write!(
+                f,
+                "The server is busy ({step}); try again in {}s",
+                retry_after.as_secs()
+            ),
+            Self::Rejected(UploadRejection { message, .. }) | Self::Failed(message) => {
+                f.write_str(message)
+            }
+        }
+    }
+}
+
+impl From<String> for UploadError {
+    fn from(message: String) -> Self {
+        Self::Failed(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timeouts_come_from_settings() {
+        let settings = Settings {
+            upload_connect_timeout_secs: Some(5),
+            upload_deadline_secs: Some(0),
+            ..Settings::default()
+        };
+        let timeouts = UploadTimeouts::from_settings(&settings);
+        assert_eq!(timeouts.connect, Duration::from_secs(5));
+        assert_eq!(timeouts.read, DEFAULT_READ_TIMEOUT);
+        assert_eq!(timeouts.deadline, DEFAULT_UPLOAD_DEADLINE);
+    }
+
+    #[tokio::test]
+    async fn a_passed_deadline_is_a_timeout() {
+        let timeouts = UploadTimeouts {
+            deadline: Duration::from_millis(10),
+            ..UploadTimeouts::default()
+        };
+        let deadline = UploadDeadline::start(&timeouts);
+        let stalled = deadline.run(async {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            Ok(())
+        });
+        let error = stalled.await.unwrap_err();
+        assert_eq!(
+            error,
+            UploadError::Timeout(UploadTimeout {
+                step: "deadline".to_string(),
+                limit: Duration::from_millis(10),
+            })
+        );
+        // A timeout keeps its own message
+        assert_eq!(error.clone().context("Upload failed"), error);
+        assert_eq!(error.to_string(), "Upload did not finish within 0s");
+    }
+}
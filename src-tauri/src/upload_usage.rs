@@ -0,0 +1,97 @@
+/*!
+ * The plan limits uploads count against.
+ *
+ * An export over the plan's zip size limit, or one made after the plan's
+ * uploads ran out, was only refused once the upload reached the server.
+ * [`get_account_usage`] asks the API (`/api/upload/usage`, for this
+ * install's visitor ID) for the limits up front, and
+ * [`AccountUsage::check_upload`] says why an export can't be uploaded, so
+ * the app can warn before spending the time on it. A server without the
+ * route (or an unreachable one) means the limits are unknown, and uploads
+ * go ahead.
+ */
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::{build_client, UploadError, UploadTimeouts};
+
+const MB: u64 = 1024 * 1024;
+
+/// What the account's plan still allows (`None` = no limit)
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccountUsage {
+    /// Plan name, for messages (`free`, `pro`)
+    #[serde(default)]
+    pub plan: Option<String>,
+    /// Uploads left in the current period
+    #[serde(default)]
+    pub uploads_remaining: Option<u32>,
+    /// Largest zip an upload may be
+    #[serde(default)]
+    pub max_zip_bytes: Option<u64>,
+}
+
+impl AccountUsage {
+    /// Why an export zip of `size_bytes` can't be uploaded on this plan
+    pub fn check_upload(&self, size_bytes: u64) -> Result<(), String> {
+        let plan = match &self.plan {
+            Some(plan) => format!("your {plan} plan's"),
+            None => "your plan's".to_string(),
+        };
+        if self.uploads_remaining == Some(0) {
+            return Err(format!("You have used all of {plan} uploads"));
+        }
+        match self.max_zip_bytes {
+            Some(max) if size_bytes > max => Err(format!(
+                "This export is {} MB, over {plan} {} MB limit. Select fewer chats or a shorter date range.",
+                (size_bytes + MB - 1) / MB,
+                max / MB
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The limits left on the plan of the account behind `visitor_id`
+pub async fn get_account_usage(
+    visitor_id: &str,
+    api_host_override: Option<&str>,
+    custom_headers: &HashMap<String, String>,
+    timeouts: &UploadTimeouts,
+) -> Result<AccountUsage, UploadError> {
+    let client = build_client(api_host_override, custom_headers, timeouts);
+    client.upload_usage(visitor_id).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exports_over_the_plan_limits_are_refused() {
+        let usage = AccountUsage {
+            plan: Some("free".to_string()),
+            uploads_remaining: Some(2),
+            max_zip_bytes: Some(500 * MB),
+        };
+        assert!(usage.check_upload(500 * MB).is_ok());
+        assert_eq!(
+            usage.check_upload(612 * MB + 1).unwrap_err(),
+            "This export is 613 MB, over your free plan's 500 MB limit. \
+             Select fewer chats or a shorter date range."
+        );
+
+        let used_up = AccountUsage {
+            uploads_remaining: Some(0),
+            ..AccountUsage::default()
+        };
+        assert_eq!(
+            used_up.check_upload(MB).unwrap_err(),
+            "You have used all of your plan's uploads"
+        );
+        // Unknown limits don't stop uploads
+        assert!(AccountUsage::default().check_upload(10_000 * MB).is_ok());
+    }
+}
@@ -0,0 +1,107 @@
+/*!
+ * Checking an export with the server before uploading it.
+ *
+ * The server only looked at an export once the whole zip was uploaded, so
+ * one it couldn't process (a format or source it doesn't support, or a job
+ * too big for it) failed after the longest step. [`validate_export`] first
+ * sends just the zip's `manifest.json` and size to `/api/upload/validate`;
+ * a refusal comes back as an [`UploadError::Rejected`] with the server's
+ * reason, for the UI to show. Uploads go ahead when the server can't be
+ * asked (one without the route, say).
+ */
+
+use std::{collections::HashMap, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use super::{build_client, UploadError, UploadTimeouts};
+use crate::export::archive::read_manifest;
+
+/// An export the server said it won't process
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UploadRejection {
+    /// Machine-readable reason (`unsupported_format`, `too_large`, ...)
+    pub code: String,
+    /// The reason, worded for the user
+    pub message: String,
+}
+
+/// The server's verdict on an export's manifest
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ValidateData {
+    pub accepted: bool,
+    #[serde(default)]
+    pub code: Option<String>,
+    #[serde(default)]
+    pub message: Option<String>,
+}
+
+impl ValidateData {
+    /// A refusal as an error
+    fn into_result(self) -> Result<(), UploadError> {
+        if self.accepted {
+            return Ok(());
+        }
+        Err(UploadError::Rejected(UploadRejection {
+            code: self.code.unwrap_or_else(|| "rejected".to_string()),
+            message: self
+                .message
+                .unwrap_or_else(|| "The server can't process this export".to_string()),
+        }))
+    }
+}
+
+/// Ask the server whether it will process the export zip at `zip_path`,
+/// before uploading it
+pub async fn validate_export(
+    zip_path: &Path,
+    upload_platform: &str,
+    api_host_override: Option<&str>,
+    custom_headers: &HashMap<String, String>,
+    timeouts: &UploadTimeouts,
+) -> Result<(), UploadError> {
+    let manifest = read_manifest(zip_path)?;
+    let size_bytes = std::fs::metadata(zip_path)
+        .map_err(|e| format!("Failed to stat export zip: {e}"))?
+        .len();
+    let client = build_client(api_host_override, custom_headers, timeouts);
+    client
+        .upload_validate(&manifest, size_bytes, upload_platform)
+        .await?
+        .into_result()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refusals_carry_the_servers_reason() {
+        let accepted = ValidateData {
+            accepted: true,
+            ..ValidateData::default()
+        };
+        assert_eq!(accepted.into_result(), Ok(()));
+
+        let refused: ValidateData = serde_json::from_value(serde_json::json!({
+            "accepted": false,
+            "code": "unsupported_format",
+            "message": "CSV exports can't be mapped yet",
+        }))
+        .unwrap();
+        assert_eq!(
+            refused.into_result(),
+            Err(UploadError::Rejected(UploadRejection {
+                code: "unsupported_format".to_string(),
+                message: "CSV exports can't be mapped yet".to_string(),
+            }))
+        );
+        assert_eq!(
+            ValidateData::default()
+                .into_result()
+                .unwrap_err()
+                .to_string(),
+            "The server can't process this export"
+        );
+    }
+}
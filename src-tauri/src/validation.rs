@@ -0,0 +1,260 @@
+/*!
+ * Validation of user-selected message databases.
+ *
+ * When a user picks a chat.db (or an iOS backup's sms.db) by hand, the
+ * frontend needs more than yes/no: it shows what was found ("This looks
+ * like an iOS sms.db with 42,113 messages from 2015–2024") or why the file
+ * was rejected, keyed off a machine-readable [`ValidationFailure`].
+ */
+
+use std::path::Path;
+
+use imessage_database::tables::table::{get_connection, DEFAULT_PATH_IOS};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    db_origin::{detect_origin, DatabaseOrigin},
+    export::{format_timestamp, TimestampUnit},
+};
+
+/// Why a database failed validation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ValidationFailure {
+    /// The file does not exist
+    NotFound,
+    /// The file could not be opened as a SQLite database
+    NotSqlite,
+    /// One or more of the chat/message/handle tables is missing
+    MissingTables,
+    /// The tables exist but could not be queried
+    QueryFailed,
+}
+
+/// Where a message database comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DatabasePlatform {
+    /// `~/Library/Messages/chat.db` on a Mac
+    Macos,
+    /// `sms.db` from an iPhone/iPad backup
+    Ios,
+}
+
+/// Result of validating a message database
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ValidationResult {
+    pub valid: bool,
+    /// Set when `valid` is false
+    pub failure_reason: Option<ValidationFailure>,
+    /// Human-readable detail for logs / support
+    pub error: Option<String>,
+    pub platform: DatabasePlatform,
+    /// SQLite `user_version` of the database
+    pub schema_version: Option<i64>,
+    /// Which Messages / macOS version the schema comes from
+    pub origin: Option<DatabaseOrigin>,
+    pub has_chat_table: bool,
+    pub has_message_table: bool,
+    pub has_handle_table: bool,
+    pub message_count: usize,
+    /// ISO 8601 date of the oldest message
+    pub first_message_date: Option<String>,
+    /// ISO 8601 date of the newest message
+    pub last_message_date: Option<String>,
+}
+
+impl ValidationResult {
+    fn new(path: &Path) -> Self {
+        Self {
+            valid: false,
+            failure_reason: None,
+            error: None,
+            platform: detect_platform(path),
+            schema_version: None,
+            origin: None,
+            has_chat_table: false,
+            has_message_table: false,
+            has_handle_table: false,
+            message_count: 0,
+            first_message_date: None,
+            last_message_date: None,
+        }
+    }
+
+    fn fail(mut self, reason: ValidationFailure, error: String) -> Self {
+        tracing::warn!("[validate_chat_db] {reason:?}: {error}");
+        self.valid = false;
+        self.failure_reason = Some(reason);
+        self.error = Some(error);
+        self
+    }
+}
+
+/// Validate that a file is an iMessage chat.db (or iOS sms.db) database
+pub fn validate_chat_db(path: &Path) -> ValidationResult {
+    tracing::info!("[validate_chat_db] Validating: {:?}", path);
+    let result = ValidationResult::new(path);
+
+    if !path.exists() {
+        return result.fail(
+            ValidationFailure::NotFound,
+            format!("File does not exist: {}", path.display()),
+        );
+    }
+
+    let db = match get_connection(path) {
+        Ok(db) => db,
+        Err(e) => return result.fail(ValidationFailure::NotSqlite, format!("Failed to open: {e}")),
+    };
+
+    validate_connection(&db, result)
+}
+
+/// Inspect an open database, filling in `result`
+fn validate_connection(db: &Connection, mut result: ValidationResult) -> ValidationResult {
+    // Opening succeeds lazily; the first real read tells us if it's SQLite
+    let tables = match existing_tables(db) {
+        Ok(tables) => tables,
+        Err(e) => {
+            return result.fail(
+                ValidationFailure::NotSqlite,
+                format!("Not a SQLite database: {e}"),
+            )
+        }
+    };
+    result.has_chat_table = tables.iter().any(|t| t == "chat");
+    result.has_message_table = tables.iter().any(|t| t == "message");
+    result.has_handle_table = tables.iter().any(|t| t == "handle");
+    result.schema_version = db
+        .query_row("PRAGMA user_version", [], |row| row.get(0))
+        .ok();
+
+    if !(result.has_chat_table && result.has_message_table && result.has_handle_table) {
+        return result.fail(
+            ValidationFailure::MissingTables,
+            "Expected chat, message and handle tables".to_string(),
+        );
+    }
+    result.origin = Some(detect_origin(db));
+
+    let stats: rusqlite::Result<(usize, Option<i64>, Option<i64>)> = db.query_row(
+        "SELECT COUNT(*), MIN(NULLIF(date, 0)), MAX(date) FROM message",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+    );
+    match stats {
+        Ok((count, first, last)) => {
+            result.message_count = count;
+            let unit = TimestampUnit::detect(db);
+            let format = |date| format_timestamp(date, unit);
+            result.first_message_date = first.map(format);
+            result.last_message_date = last.filter(|d| *d > 0).map(format);
+            result.valid = true;
+            tracing::info!(
+                "[validate_chat_db] Valid {:?} database with {count} messages from {}",
+                result.platform,
+                result
+                    .origin
+                    .as_ref()
+                    .map(|o| o.describe())
+                    .unwrap_or_default()
+            );
+            result
+        }
+        Err(e) => result.fail(
+            ValidationFailure::QueryFailed,
+            format!("Failed to read messages: {e}"),
+        ),
+    }
+}
+
+/// Names of all tables in the database
+fn existing_tables(db: &Connection) -> rusqlite::Result<Vec<String>> {
+    let mut stmt = db.prepare("SELECT name FROM sqlite_master WHERE type = 'table'")?;
+    let names = stmt
+        .query_map([], |row| row.get(0))?
+        .collect::<rusqlite::Result<Vec<String>>>()?;
+    Ok(names)
+}
+
+/// iOS databases are named `sms.db` (or stored under their hashed name
+/// inside a backup); macOS uses `chat.db`
+fn detect_platform(path: &Path) -> DatabasePlatform {
+    if path.ends_with(DEFAULT_PATH_IOS) {
+        return DatabasePlatform::Ios;
+    }
+    match path.file_name().and_then(|n| n.to_str()) {
+        Some(name) if name.eq_ignore_ascii_case("sms.db") => DatabasePlatform::Ios,
+        _ => DatabasePlatform::Macos,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::test_fixtures::{ChatBuilder, HandleBuilder, MessageBuilder, TestIMessageDb};
+
+    #[test]
+    fn valid_database_reports_counts_and_date_range() {
+        let mut db = TestIMessageDb::new().unwrap();
+        let handle = db.handle(HandleBuilder::new("+15551234567")).unwrap();
+        let chat = db
+            .chat(ChatBuilder::new("iMessage;-;+15551234567"))
+            .unwrap();
+        for date in [200_000_000_000_000_000, 500_000_000_000_000_000] {
+            db.message(
+                MessageBuilder::new()
+                    .text("hi")
+                    .handle(handle)
+                    .chat(chat)
+                    .date(date),
+            )
+            .unwrap();
+        }
+
+        let result = validate_connection(db.conn(), ValidationResult::new(Path::new("chat.db")));
+        assert!(result.valid);
+        assert_eq!(result.failure_reason, None);
+        assert_eq!(result.platform, DatabasePlatform::Macos);
+        assert_eq!(result.message_count, 2);
+        assert!(result.first_message_date.unwrap().starts_with("2007-"));
+        assert!(result.last_message_date.unwrap().starts_with("2016-"));
+        assert_eq!(result.origin.unwrap().min_macos_version, Some(15));
+    }
+
+    #[test]
+    fn missing_tables_are_reported() {
+        let db = Connection::open_in_memory().unwrap();
+        db.execute_batch("CREATE TABLE message (ROWID INTEGER PRIMARY KEY);")
+            .unwrap();
+
+        let result = validate_connection(&db, ValidationResult::new(Path::new("sms.db")));
+        assert!(!result.valid);
+        assert_eq!(
+            result.failure_reason,
+            Some(ValidationFailure::MissingTables)
+        );
+        assert_eq!(result.platform, DatabasePlatform::Ios);
+        assert!(result.has_message_table);
+        assert!(!result.has_chat_table);
+    }
+
+    #[test]
+    fn missing_and_non_sqlite_files_fail() {
+        let dir = TempDir::new().unwrap();
+        let missing = validate_chat_db(&dir.path().join("chat.db"));
+        assert_eq!(missing.failure_reason, Some(ValidationFailure::NotFound));
+
+        let text_file = dir.path().join("notes.db");
+        std::fs::write(&text_file, "definitely not sqlite, just some text padding").unwrap();
+        let not_sqlite = validate_chat_db(&text_file);
+        assert_eq!(
+            not_sqlite.failure_reason,
+            Some(ValidationFailure::NotSqlite)
+        );
+    }
+}
@@ -0,0 +1,334 @@
+/*!
+ * End-to-end test of the export pipeline: fixture chat.db → `list_chats`
+ * → `export_chats` → presign, storage upload and complete against a mock
+ * ChatToMap server → job history.
+ *
+ * The unit tests cover each module alone; this one catches regressions in
+ * how they fit together (IDs from `list_chats` that `export_chats` can't
+ * find, a zip the upload doesn't send as-is, signatures the server would
+ * reject, a job record that doesn't match the upload).
+ */
+
+#[path = "../src/test_fixtures/mod.rs"]
+#[allow(dead_code, clippy::wrong_self_convention)]
+mod test_fixtures;
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::Read,
+    sync::{Arc, Mutex},
+};
+
+use chat_to_map_desktop::{
+    api::{sign_payload, DESKTOP_UPLOAD_SHARED_SECRET},
+    export::{
+        archive, diff::read_archive, export_chats, queue::JobStatus, readme::README_FILENAME,
+        ExportOptions, UPLOAD_PLATFORM,
+    },
+    job_history::{self, JobRecord},
+    list_chats,
+    upload::{
+        complete_upload, get_presigned_url, get_results_url, upload_file, UploadTimeouts,
+        UploadedZip,
+    },
+};
+use serde_json::{json, Value};
+use tempfile::TempDir;
+use test_fixtures::{ChatBuilder, HandleBuilder, MessageBuilder, TestIMessageDb};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+/// A request the mock server received
+#[derive(Debug, Clone)]
+struct Received {
+    path: String,
+    headers: HashMap<String, String>,
+    body: Vec<u8>,
+}
+
+/// Minimal stand-in for the ChatToMap API and Convex storage: answers
+/// presign, storage upload and complete, and records every request
+struct MockServer {
+    base_url: String,
+    received: Arc<Mutex<Vec<Received>>>,
+}
+
+impl MockServer {
+    async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let received = Arc::new(Mutex::new(Vec::new()));
+
+        let storage_url = format!("{base_url}/storage/upload");
+        let log = Arc::clone(&received);
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let storage_url = storage_url.clone();
+                let log = Arc::clone(&log);
+                tokio::spawn(async move { serve(stream, &storage_url, &log).await });
+            }
+        });
+
+        Self { base_url, received }
+    }
+
+    fn requests(&self) -> Vec<Received> {
+        self.received.lock().unwrap().clone()
+    }
+}
+
+/// Answer one request (each client connection is closed after a response)
+async fn serve(mut stream: TcpStream, storage_url: &str, log: &Mutex<Vec<Received>>) {
+    let request = read_request(&mut stream).await;
+    let response = match request.path.as_str() {
+        "/api/upload/presign" => json!({ "success": true, "data": { "upload_url": storage_url } }),
+        "/storage/upload" => json!({ "storageId": "storage-123" }),
+        "/api/upload/complete" => json!({
+            "success": true,
+            "data": {
+                "chat_upload_id": "upload-1",
+                "chat_analysis_id": "analysis-1",
+                "status": "pending",
+                "job_token": "job token"
+            }
+        }),
+        _ => json!({ "success": false, "error": "not found" }),
+    };
+    log.lock().unwrap().push(request);
+
+    let body = response.to_string();
+    let head = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+         Connection: close\r\n\r\n",
+        body.len()
+    );
+    stream.write_all(head.as_bytes()).await.unwrap();
+    stream.write_all(body.as_bytes()).await.unwrap();
+    stream.shutdown().await.unwrap();
+}
+
+async fn read_request(stream: &mut TcpStream) -> Received {
+    let mut buffer = Vec::new();
+    let header_end = loop {
+        let mut chunk = [0u8; 4096];
+        let read = stream.read(&mut chunk).await.unwrap();
+        assert!(
+            read > 0,
+            "connection closed before the request headers ended"
+        );
+        buffer.extend_from_slice(&chunk[..read]);
+        if let Some(end) = buffer.windows(4).position(|w| w == b"\r\n\r\n") {
+            break end;
+        }
+    };
+
+    let head = String::from_utf8_lossy(&buffer[..header_end]).to_string();
+    let mut lines = head.lines();
+    let path = lines
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .unwrap()
+        .to_string();
+    let headers: HashMap<String, String> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_string()))
+        .collect();
+
+    let content_length: usize = headers
+        .get("content-length")
+        .map_or(0, |value| value.parse().unwrap());
+    let mut body = buffer[header_end + 4..].to_vec();
+    while body.len() < content_length {
+        let mut chunk = [0u8; 4096];
+        let read = stream.read(&mut chunk).await.unwrap();
+        assert!(read > 0, "connection closed before the request body ended");
+        body.extend_from_slice(&chunk[..read]);
+    }
+
+    Received {
+        path,
+        headers,
+        body,
+    }
+}
+
+/// Assert `request` carries a signature the server would accept for `bound_value`
+fn assert_signed(request: &Received, bound_value: &str) {
+    let timestamp = &request.headers["x-desktop-timestamp"];
+    let expected = sign_payload(
+        DESKTOP_UPLOAD_SHARED_SECRET,
+        &format!("{timestamp}:{bound_value}"),
+    )
+    .unwrap();
+    assert_eq!(request.headers["x-desktop-signature"], expected);
+}
+
+#[tokio::test]
+async fn fixture_chats_are_exported_uploaded_and_recorded() {
+    let dir = TempDir::new().unwrap();
+
+    // A one-on-one chat and a group chat
+    let mut db = TestIMessageDb::new().unwrap();
+    let alice = db.handle(HandleBuilder::new("+15551234567")).unwrap();
+    let bob = db.handle(HandleBuilder::new("bob@example.com")).unwrap();
+    let direct = db
+        .chat(ChatBuilder::new("iMessage;-;+15551234567"))
+        .unwrap();
+    let group = db
+        .chat(
+            ChatBuilder::new("chat123456")
+                .group()
+                .display_name("Trip Planning"),
+        )
+        .unwrap();
+    db.chat_handle(group, alice).unwrap();
+    db.chat_handle(group, bob).unwrap();
+    for (text, handle, chat) in [
+        ("Dinner at Lupa on Friday?", alice, direct),
+        ("Booked the hotel in Rome", alice, group),
+        ("Flights are on Tuesday", bob, group),
+    ] {
+        db.message(MessageBuilder::new().text(text).handle(handle).chat(chat))
+            .unwrap();
+    }
+    let db_path = dir.path().join("chat.db");
+    db.save_to(&db_path).unwrap();
+
+    // list_chats
+    let chats = list_chats(Some(&db_path), &[]).unwrap();
+    assert_eq!(chats.len(), 2);
+    let listed_group = chats.iter().find(|chat| chat.id == group).unwrap();
+    assert_eq!(listed_group.display_name, "Trip Planning");
+    assert_eq!(listed_group.message_count, 2);
+
+    // export_chats with the listed IDs
+    let chat_ids: Vec<i32> = chats.iter().map(|chat| chat.id).collect();
+    let options = ExportOptions {
+        metadata: BTreeMap::from([("trip".to_string(), "Italy 2024".to_string())]),
+        ..Default::default()
+    };
+    let export = export_chats(&chat_ids, &options, None, Some(&db_path)).unwrap();
+    assert_eq!(export.chat_count, 2);
+    assert_eq!(export.total_messages, 3);
+
+    let archive = read_archive(&export.zip_path).unwrap();
+    assert_eq!(archive.chats.len(), 2);
+    assert_eq!(archive.manifest["metadata"]["trip"], "Italy 2024");
+    let mut zip = zip::ZipArchive::new(std::fs::File::open(&export.zip_path).unwrap()).unwrap();
+    let mut readme = String::new();
+    zip.by_name(README_FILENAME)
+        .unwrap()
+        .read_to_string(&mut readme)
+        .unwrap();
+    assert!(readme.contains("2 chats, 3 messages"), "{readme}");
+    assert!(readme.contains("- trip: Italy 2024"), "{readme}");
+    let mut texts: Vec<&str> = archive
+        .chats
+        .iter()
+        .flat_map(|chat| chat.messages.iter().map(|m| m.text.as_str()))
+        .collect();
+    texts.sort_unstable();
+    assert_eq!(
+        texts,
+        [
+            "Booked the hotel in Rome",
+            "Dinner at Lupa on Friday?",
+            "Flights are on Tuesday"
+        ]
+    );
+
+    // Upload flow against the mock server
+    let server = MockServer::start().await;
+    let zip_bytes = std::fs::read(&export.zip_path).unwrap();
+    let no_headers = HashMap::new();
+    let timeouts = UploadTimeouts::default();
+    let presign = get_presigned_url(
+        zip_bytes.len() as u64,
+        Some(&server.base_url),
+        &no_headers,
+        &timeouts,
+    )
+    .await
+    .unwrap();
+    let storage_id = upload_file(
+        &export.zip_path,
+        &presign.upload_url,
+        &export.zip_sha256,
+        None,
+        &timeouts,
+    )
+    .await
+    .unwrap();
+    assert_eq!(storage_id, "storage-123");
+    let uploaded = UploadedZip {
+        storage_id: &storage_id,
+        original_filename: None,
+        sha256: &export.zip_sha256,
+    };
+    let job = complete_upload(
+        &uploaded,
+        UPLOAD_PLATFORM,
+        "visitor-1",
+        &options.metadata,
+        Some(&server.base_url),
+        &no_headers,
+        &timeouts,
+    )
+    .await
+    .unwrap();
+    let results_url = get_results_url(
+        &job.chat_analysis_id,
+        job.job_token.as_deref(),
+        Some("https://chattomap.test"),
+    );
+    assert_eq!(
+        results_url,
+        "https://chattomap.test/processing/analysis-1?token=job%20token"
+    );
+
+    let requests = server.requests();
+    let paths: Vec<&str> = requests.iter().map(|r| r.path.as_str()).collect();
+    assert_eq!(
+        paths,
+        [
+            "/api/upload/presign",
+            "/storage/upload",
+            "/api/upload/complete"
+        ]
+    );
+    let (presign_request, storage_request, complete_request) =
+        (&requests[0], &requests[1], &requests[2]);
+    assert_signed(presign_request, &zip_bytes.len().to_string());
+    assert_eq!(storage_request.body, zip_bytes, "the zip is uploaded as-is");
+    let zip_sha256 = archive::sha256_hex(&zip_bytes);
+    assert_eq!(export.zip_sha256, zip_sha256);
+    assert!(storage_request.headers["digest"].starts_with("sha-256="));
+    assert_signed(complete_request, &storage_id);
+    let complete_body: Value = serde_json::from_slice(&complete_request.body).unwrap();
+    assert_eq!(complete_body["storage_id"], "storage-123");
+    assert_eq!(complete_body["upload_platform"], UPLOAD_PLATFORM);
+    assert_eq!(complete_body["metadata"]["trip"], "Italy 2024");
+    assert_eq!(complete_body["zip_sha256"], zip_sha256.as_str());
+
+    // Job history, as the app records a finished run
+    let data_dir = dir.path().join("app-data");
+    let record = JobRecord::new(
+        "export-1",
+        JobStatus::Completed {
+            results_url: Some(results_url.clone()),
+        },
+        Some(export.metrics.clone()),
+    );
+    job_history::append(&data_dir, &record).unwrap();
+    let history = job_history::load(&data_dir).unwrap();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].export_id, record.export_id);
+    assert_eq!(history[0].status, record.status);
+    let metrics = history[0].metrics.as_ref().unwrap();
+    assert_eq!(metrics.messages, 3);
+    assert_eq!(metrics.archive_bytes, zip_bytes.len() as u64);
+    assert!(job_history::to_text(&history, true).contains("export-1"));
+}
@@ -0,0 +1,107 @@
+/**
+ * Automation access tokens - scoped tokens for scripts, revocable here
+ */
+
+import { invoke } from '@tauri-apps/api/core'
+import { escapeHtml } from './html'
+import type { AccessScope, AccessToken } from './types'
+
+const SCOPE_LABELS: Record<AccessScope, string> = {
+  list_chats: 'list chats',
+  export: 'export',
+  upload: 'upload'
+}
+
+// Elements (initialized in setup)
+let panel: HTMLElement
+let tokensList: HTMLElement
+let nameInput: HTMLInputElement
+let createdBox: HTMLElement
+let createdValue: HTMLElement
+
+function findElement<T extends HTMLElement>(selector: string): T {
+  const el = panel.querySelector<T>(selector)
+  if (!el) {
+    throw new Error(`Required element ${selector} not found`)
+  }
+  return el
+}
+
+async function renderTokens(): Promise<void> {
+  let tokens: AccessToken[]
+  try {
+    tokens = await invoke<AccessToken[]>('list_access_tokens')
+  } catch (error) {
+    tokensList.innerHTML = `<div class="loading">Could not load tokens: ${escapeHtml(String(error))}</div>`
+    return
+  }
+  if (tokens.length === 0) {
+    tokensList.innerHTML = '<div class="loading">No access tokens</div>'
+    return
+  }
+  tokensList.innerHTML = tokens
+    .map((token) => {
+      const scopes = token.scopes.map((scope) => SCOPE_LABELS[scope]).join(', ')
+      return `
+        <div class="access-token">
+          <span>${escapeHtml(token.name)}</span>
+          <span class="chat-meta">${scopes}</span>
+          <button class="btn btn-small" data-revoke="${escapeHtml(token.id)}">Revoke</button>
+        </div>
+      `
+    })
+    .join('')
+}
+
+async function createToken(): Promise<void> {
+  const scopes = Array.from(panel.querySelectorAll<HTMLInputElement>('input[data-scope]'))
+    .filter((input) => input.checked)
+    .map((input) => input.dataset['scope'])
+  try {
+    const created = await invoke<{ record: AccessToken; token: string }>('create_access_token', {
+      name: nameInput.value,
+      scopes
+    })
+    nameInput.value = ''
+    createdValue.textContent = created.token
+    createdBox.classList.remove('hidden')
+    await renderTokens()
+  } catch (error) {
+    alert(`Could not create token: ${error}`)
+  }
+}
+
+export function setupAccessTokens(elements: { accessTokensBtn: HTMLButtonElement }): void {
+  const found = document.getElementById('access-tokens-panel')
+  if (!found) {
+    throw new Error('Access tokens panel not found')
+  }
+  panel = found
+  tokensList = findElement('#access-tokens-list')
+  nameInput = findElement('#access-token-name')
+  createdBox = findElement('#access-token-created')
+  createdValue = findElement('#access-token-value')
+
+  elements.accessTokensBtn.addEventListener('click', () => {
+    panel.classList.remove('hidden')
+    renderTokens()
+  })
+  findElement<HTMLButtonElement>('#access-tokens-close-btn').addEventListener('click', () => {
+    panel.classList.add('hidden')
+    // The new token is only shown until the panel is closed
+    createdBox.classList.add('hidden')
+    createdValue.textContent = ''
+  })
+  findElement<HTMLButtonElement>('#access-token-create-btn').addEventListener('click', createToken)
+
+  tokensList.addEventListener('click', async (e) => {
+    const id = (e.target as HTMLElement).closest('button')?.dataset['revoke']
+    if (!id || !confirm('Revoke this token? Scripts using it will stop working.')) return
+    try {
+      await invoke('revoke_access_token', { id })
+    } catch (error) {
+      alert(`Could not revoke token: ${error}`)
+    }
+    await renderTokens()
+  })
+}
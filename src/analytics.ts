@@ -27,9 +27,9 @@ declare global {
 
 /**
  * Initialize Google Analytics
- * Called once on app startup
+ * Called once on app startup with the version reported by `get_app_info`
  */
-export function initAnalytics(): void {
+export function initAnalytics(appVersion: string): void {
   if (!GA_MEASUREMENT_ID) {
     console.log('[Analytics] No measurement ID configured, analytics disabled')
     return
@@ -54,7 +54,7 @@ export function initAnalytics(): void {
     send_page_view: false,
     // Desktop app identifier
     app_name: 'ChatToMap Desktop',
-    app_version: '0.1.0'
+    app_version: appVersion
   })
 
   console.log('[Analytics] Initialized with ID:', GA_MEASUREMENT_ID)
@@ -0,0 +1,88 @@
+/**
+ * Chat peek - show the last few messages of a chat before exporting it
+ */
+
+import { invoke } from '@tauri-apps/api/core'
+import { escapeHtml } from './html'
+import type { ChatInfo, ExportedMessage } from './types'
+
+// Constants
+const PREVIEW_LIMIT = 20
+
+// Elements and accessors (initialized in setup)
+let chatPreviewPanel: HTMLElement
+let chatPreviewTitle: HTMLElement
+let chatPreviewMessages: HTMLElement
+let getChats: () => ChatInfo[]
+let getCustomDbPath: () => string | null
+
+function renderMessages(messages: ExportedMessage[]): void {
+  if (messages.length === 0) {
+    chatPreviewMessages.innerHTML = '<div class="loading">No text messages in this chat</div>'
+    return
+  }
+
+  chatPreviewMessages.innerHTML = messages
+    .map(
+      (message) => `
+        <div class="preview-message ${message.is_from_me ? 'from-me' : ''}">
+          <div class="chat-meta">${escapeHtml(message.sender)} · ${new Date(message.timestamp).toLocaleString()}</div>
+          <div class="preview-text">${escapeHtml(message.text)}</div>
+        </div>
+      `
+    )
+    .join('')
+  chatPreviewMessages.scrollTop = chatPreviewMessages.scrollHeight
+}
+
+async function showChatPreview(chat: ChatInfo): Promise<void> {
+  chatPreviewTitle.textContent = chat.display_name
+  chatPreviewMessages.innerHTML = '<div class="loading">Loading messages...</div>'
+  chatPreviewPanel.classList.remove('hidden')
+
+  try {
+    const messages = await invoke<ExportedMessage[]>('get_chat_preview', {
+      chatId: chat.id,
+      limit: PREVIEW_LIMIT,
+      customDbPath: getCustomDbPath()
+    })
+    renderMessages(messages)
+  } catch (error) {
+    console.error('Preview error:', error)
+    chatPreviewMessages.innerHTML = `<div class="loading">Could not load messages: ${escapeHtml(String(error))}</div>`
+  }
+}
+
+export function setupChatPreview(
+  elements: {
+    chatList: HTMLElement
+    chatPreviewPanel: HTMLElement
+    chatPreviewTitle: HTMLElement
+    chatPreviewMessages: HTMLElement
+    chatPreviewCloseBtn: HTMLButtonElement
+  },
+  chats: () => ChatInfo[],
+  customDbPath: () => string | null
+): void {
+  // Store element references
+  chatPreviewPanel = elements.chatPreviewPanel
+  chatPreviewTitle = elements.chatPreviewTitle
+  chatPreviewMessages = elements.chatPreviewMessages
+  getChats = chats
+  getCustomDbPath = customDbPath
+
+  // "Peek" buttons in the chat list (selection clicks ignore them)
+  elements.chatList.addEventListener('click', (e) => {
+    const target = e.target as HTMLElement
+    const button = target.closest('.chat-peek-btn') as HTMLElement | null
+    const id = Number.parseInt(button?.dataset['id'] ?? '', 10)
+    const chat = getChats().find((c) => c.id === id)
+    if (chat) {
+      showChatPreview(chat)
+    }
+  })
+
+  elements.chatPreviewCloseBtn.addEventListener('click', () => {
+    chatPreviewPanel.classList.add('hidden')
+  })
+}
@@ -3,6 +3,8 @@
  */
 
 import { invoke } from '@tauri-apps/api/core'
+import { escapeHtml } from './html'
+import { updateSettings } from './settings'
 
 // Constants
 const DEBUG_HOST_KEY = 'chattomap_debug_host'
@@ -31,12 +33,6 @@ let debugSaveBtn: HTMLButtonElement
 let debugCloseBtn: HTMLButtonElement
 let headerLogo: HTMLImageElement
 
-function escapeHtml(text: string): string {
-  const div = document.createElement('div')
-  div.textContent = text
-  return div.innerHTML
-}
-
 export function getDebugHeaders(): DebugHeader[] {
   const saved = localStorage.getItem(DEBUG_HEADERS_KEY)
   if (!saved) return []
@@ -159,6 +155,7 @@ async function saveDebugSettings(): Promise<void> {
   await invoke('set_server_host', { host: webUrl || null })
   await invoke('set_api_host', { host: apiUrl || null })
   await invoke('set_custom_headers', { headers: headersObj })
+  await updateSettings({ server_host_override: webUrl || null, api_host_override: apiUrl || null })
 
   // Close panel and show confirmation
   debugPanel.classList.add('hidden')
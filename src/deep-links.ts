@@ -0,0 +1,28 @@
+/**
+ * chattomap:// links opened from the website. The backend parses them and
+ * runs any command they stand for (e.g. re-queueing an export) first, and
+ * reports a link it couldn't follow with `deep-link-error`.
+ */
+
+import { listen } from '@tauri-apps/api/event'
+import type { DeepLink } from './types'
+
+export async function setupDeepLinks(
+  showChatSelection: () => void,
+  showError: (message: string) => void
+): Promise<void> {
+  await listen<DeepLink>('deep-link', (event) => {
+    switch (event.payload.kind) {
+      case 'select_chats':
+        showChatSelection()
+        break
+      case 'retry_job':
+        // The export is back in the queue, which is listed on this screen
+        showChatSelection()
+        break
+    }
+  })
+  await listen<string>('deep-link-error', (event) => {
+    showError(event.payload)
+  })
+}
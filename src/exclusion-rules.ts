@@ -0,0 +1,168 @@
+/**
+ * Exclusion rules - chats that should never be exported (OTP senders, banks, a particular person)
+ */
+
+import { invoke } from '@tauri-apps/api/core'
+import { escapeHtml } from './html'
+import type { ChatInfo, ExclusionRule } from './types'
+
+// Elements and accessors (initialized in setup)
+let panel: HTMLElement
+let rulesList: HTMLElement
+let kindSelect: HTMLSelectElement
+let valueInput: HTMLInputElement
+let matchesList: HTMLElement
+let getCustomDbPath: () => string | null
+let onRulesSaved: () => Promise<void>
+let rules: ExclusionRule[] = []
+
+function findElement<T extends HTMLElement>(selector: string): T {
+  const el = panel.querySelector<T>(selector)
+  if (!el) {
+    throw new Error(`Required element ${selector} not found`)
+  }
+  return el
+}
+
+function describeRule(rule: ExclusionRule): string {
+  switch (rule.kind) {
+    case 'short_code':
+      return 'Short-code senders'
+    case 'name_contains':
+      return `Name contains "${rule.text}"`
+    case 'identifier':
+      return `Identifier matches ${rule.pattern}`
+  }
+}
+
+function ruleFromForm(): ExclusionRule | null {
+  const value = valueInput.value.trim()
+  switch (kindSelect.value) {
+    case 'short_code':
+      return { kind: 'short_code' }
+    case 'name_contains':
+      return value ? { kind: 'name_contains', text: value } : null
+    case 'identifier':
+      return value ? { kind: 'identifier', pattern: value } : null
+    default:
+      return null
+  }
+}
+
+function renderRules(): void {
+  if (rules.length === 0) {
+    rulesList.innerHTML = '<div class="loading">No exclusion rules</div>'
+    return
+  }
+
+  rulesList.innerHTML = rules
+    .map(
+      (rule, index) => `
+        <div class="exclusion-rule">
+          <span>${escapeHtml(describeRule(rule))}</span>
+          <button class="btn btn-small" data-matches="${index}">Matches</button>
+          <button class="btn btn-small" data-remove="${index}">Remove</button>
+        </div>
+      `
+    )
+    .join('')
+}
+
+async function showMatches(rule: ExclusionRule): Promise<void> {
+  matchesList.innerHTML = '<div class="loading">Finding matching chats...</div>'
+  try {
+    const chats = await invoke<ChatInfo[]>('list_exclusion_matches', {
+      rule,
+      customDbPath: getCustomDbPath()
+    })
+    matchesList.innerHTML =
+      chats.length === 0
+        ? '<div class="loading">No chats match this rule</div>'
+        : chats
+            .map((chat) => `<div class="chat-meta">${escapeHtml(chat.display_name)}</div>`)
+            .join('')
+  } catch (error) {
+    console.error('Exclusion match error:', error)
+    matchesList.innerHTML = `<div class="loading">Could not list matches: ${escapeHtml(String(error))}</div>`
+  }
+}
+
+async function saveRules(updated: ExclusionRule[]): Promise<void> {
+  try {
+    await invoke('set_exclusion_rules', { rules: updated })
+    rules = updated
+    renderRules()
+    await onRulesSaved()
+  } catch (error) {
+    console.error('Failed to save exclusion rules:', error)
+    alert(`Could not save exclusion rules: ${error}`)
+  }
+}
+
+async function openPanel(): Promise<void> {
+  panel.classList.remove('hidden')
+  matchesList.innerHTML = ''
+  try {
+    rules = await invoke<ExclusionRule[]>('get_exclusion_rules')
+  } catch (error) {
+    console.error('Failed to load exclusion rules:', error)
+    rules = []
+  }
+  renderRules()
+}
+
+export function setupExclusionRules(
+  elements: {
+    exclusionRulesBtn: HTMLButtonElement
+    exclusionRulesPanel: HTMLElement
+  },
+  customDbPath: () => string | null,
+  onSaved: () => Promise<void>
+): void {
+  // Store element references
+  panel = elements.exclusionRulesPanel
+  rulesList = findElement('#exclusion-rules-list')
+  kindSelect = findElement('#exclusion-rule-kind')
+  valueInput = findElement('#exclusion-rule-value')
+  matchesList = findElement('#exclusion-rule-matches')
+  getCustomDbPath = customDbPath
+  onRulesSaved = onSaved
+
+  elements.exclusionRulesBtn.addEventListener('click', openPanel)
+
+  const closeBtn = findElement<HTMLButtonElement>('#exclusion-rules-close-btn')
+  closeBtn.addEventListener('click', () => {
+    panel.classList.add('hidden')
+  })
+
+  // Short codes need no value
+  kindSelect.addEventListener('change', () => {
+    valueInput.disabled = kindSelect.value === 'short_code'
+  })
+  valueInput.disabled = kindSelect.value === 'short_code'
+
+  const addBtn = findElement<HTMLButtonElement>('#exclusion-rule-add-btn')
+  addBtn.addEventListener('click', async () => {
+    const rule = ruleFromForm()
+    if (!rule) {
+      alert('Enter some text or a pattern for this rule.')
+      return
+    }
+    valueInput.value = ''
+    await saveRules([...rules, rule])
+    await showMatches(rule)
+  })
+
+  // Per-rule buttons
+  rulesList.addEventListener('click', (e) => {
+    const button = (e.target as HTMLElement).closest('button')
+    const matchIndex = Number.parseInt(button?.dataset['matches'] ?? '', 10)
+    const removeIndex = Number.parseInt(button?.dataset['remove'] ?? '', 10)
+    const matchRule = rules[matchIndex]
+    if (matchRule) {
+      showMatches(matchRule)
+    } else if (rules[removeIndex]) {
+      saveRules(rules.filter((_, index) => index !== removeIndex))
+    }
+  })
+}
@@ -0,0 +1,137 @@
+/**
+ * Export queue - several exports (e.g. one per year) that run one after another
+ */
+
+import { invoke } from '@tauri-apps/api/core'
+import { listen } from '@tauri-apps/api/event'
+import { escapeHtml } from './html'
+import { describeExportError } from './progress'
+import type { ExportProgress, QueuedExport } from './types'
+
+interface QueueRequest {
+  chatIds: number[]
+  options: object
+  customDbPath: string | null
+}
+
+// Elements and state (initialized in setup)
+let panel: HTMLElement
+let jobsList: HTMLElement
+let uploadAtInput: HTMLInputElement
+let jobs: QueuedExport[] = []
+// Latest progress per running job
+const progressByJob = new Map<string, ExportProgress>()
+
+/** Next occurrence of the time in the "Upload at" input; null to upload right away */
+function scheduledUploadAt(): string | null {
+  const [hours, minutes] = uploadAtInput.value.split(':').map(Number)
+  if (hours === undefined || minutes === undefined || Number.isNaN(hours + minutes)) return null
+  const at = new Date()
+  at.setHours(hours, minutes, 0, 0)
+  if (at <= new Date()) at.setDate(at.getDate() + 1)
+  return at.toISOString()
+}
+
+function describeStatus(job: QueuedExport): string {
+  switch (job.status) {
+    case 'queued': {
+      if (!job.upload_at) return 'Queued'
+      const time = new Date(job.upload_at).toLocaleTimeString([], { timeStyle: 'short' })
+      return `Queued, uploads at ${time}`
+    }
+    case 'running': {
+      const progress = progressByJob.get(job.job_id)
+      return progress ? `${progress.stage} ${progress.percent}%` : 'Starting...'
+    }
+    case 'completed':
+      return 'Complete'
+    case 'failed':
+      return `Failed: ${job.error}`
+    case 'cancelled':
+      return 'Cancelled'
+  }
+}
+
+function renderJobs(): void {
+  panel.classList.toggle('hidden', jobs.length === 0)
+  jobsList.innerHTML = jobs
+    .map((job) => {
+      const label = job.label ?? `${job.chat_count} chats`
+      const cancellable = job.status === 'queued' || job.status === 'running'
+      const cancel = cancellable
+        ? `<button class="btn btn-small" data-cancel="${escapeHtml(job.job_id)}">Cancel</button>`
+        : ''
+      return `
+        <div class="export-queue-job ${job.status}">
+          <span>${escapeHtml(label)}</span>
+          <span class="chat-meta">${escapeHtml(describeStatus(job))}</span>
+          ${cancel}
+        </div>
+      `
+    })
+    .join('')
+}
+
+async function enqueue(request: QueueRequest): Promise<void> {
+  if (request.chatIds.length === 0) {
+    alert('Please select at least one chat to queue.')
+    return
+  }
+  try {
+    await invoke<string>('enqueue_export', {
+      request: {
+        chat_ids: request.chatIds,
+        custom_db_path: request.customDbPath,
+        options: request.options,
+        upload_at: scheduledUploadAt()
+      }
+    })
+  } catch (error) {
+    console.error('Failed to queue export:', error)
+    alert(`Could not queue export: ${error}`)
+  }
+}
+
+export async function setupExportQueue(getRequest: () => QueueRequest): Promise<void> {
+  const found = document.getElementById('export-queue-panel')
+  const list = document.getElementById('export-queue-list')
+  const uploadAt = document.getElementById('queue-upload-at') as HTMLInputElement | null
+  if (!found || !list || !uploadAt) {
+    throw new Error('Export queue elements not found')
+  }
+  panel = found
+  jobsList = list
+  uploadAtInput = uploadAt
+
+  document
+    .getElementById('queue-export-btn')
+    ?.addEventListener('click', () => enqueue(getRequest()))
+  document.getElementById('export-queue-clear-btn')?.addEventListener('click', () => {
+    invoke('clear_finished_exports')
+  })
+
+  jobsList.addEventListener('click', async (e) => {
+    const jobId = (e.target as HTMLElement).closest('button')?.dataset['cancel']
+    if (!jobId) return
+    try {
+      await invoke('cancel_queued_export', { jobId })
+    } catch (error) {
+      alert(describeExportError(error))
+    }
+  })
+
+  await listen<QueuedExport[]>('export-queue-changed', (event) => {
+    jobs = event.payload
+    renderJobs()
+  })
+  await listen<ExportProgress>('export-progress', (event) => {
+    const progress = event.payload
+    if (progress.export_id && jobs.some((job) => job.job_id === progress.export_id)) {
+      progressByJob.set(progress.export_id, progress)
+      renderJobs()
+    }
+  })
+
+  jobs = await invoke<QueuedExport[]>('list_export_queue')
+  renderJobs()
+}
@@ -0,0 +1,9 @@
+/**
+ * HTML helpers shared by the UI modules
+ */
+
+export function escapeHtml(text: string): string {
+  const div = document.createElement('div')
+  div.textContent = text
+  return div.innerHTML
+}
@@ -5,9 +5,46 @@ import { open as openShell } from '@tauri-apps/plugin-shell'
 import tippy from 'tippy.js'
 import 'tippy.js/dist/tippy.css'
 import { FunnelEvents, initAnalytics, trackPageView } from './analytics'
+import { setupAccessTokens } from './access-tokens'
+import { setupChatPreview } from './chat-preview'
+import { setupDeepLinks } from './deep-links'
+import { setupExclusionRules } from './exclusion-rules'
+import { setupExportQueue } from './export-queue'
+import { setupPendingUploads } from './pending-uploads'
+import { setupUploadHistory } from './upload-history'
 import { initDebugSettingsOnStartup, setupDebugPanel } from './debug'
+import { escapeHtml } from './html'
+import {
+  canReadDatabase,
+  promptForContacts,
+  setupPermissionButtons,
+  showPermissionHelp,
+  updatePermissionStatus
+} from './permissions'
+import { confirmPreflightWarnings } from './preflight'
+import { describeExportError, isExportCancelled, setupProgressListener } from './progress'
 import { runScreenshotMode } from './screenshot'
-import type { ChatInfo, ExportProgress, ExportResult, ScreenshotConfig } from './types'
+import { restoreSettings, updateSettings } from './settings'
+import { setupSearch } from './search'
+import type {
+  AppInfo,
+  ChatInfo,
+  ChatListStage,
+  ChatPage,
+  ContactsAccess,
+  ExportResult,
+  FullDiskAccess,
+  ScreenshotConfig,
+  ValidationResult
+} from './types'
+import { describeDatabase, describeValidationFailure } from './validation'
+
+// What list_chats is doing after each stage it reports
+const CHAT_LIST_NEXT_STEP: Record<ChatListStage, string> = {
+  contacts_loaded: 'Reading chats...',
+  chats_loaded: 'Counting messages...',
+  stats_computed: 'Sorting chats...'
+}
 
 // State
 const state = {
@@ -36,11 +73,23 @@ const elements = {
   errorScreen: getElement<HTMLElement>('error-screen'),
 
   filterInput: getElement<HTMLInputElement>('filter-input'),
+  messageSearchInput: getElement<HTMLInputElement>('message-search-input'),
+  messageSearchResults: getElement<HTMLElement>('message-search-results'),
+  personSearchInput: getElement<HTMLInputElement>('person-search-input'),
+  personSearchResults: getElement<HTMLElement>('person-search-results'),
   chatList: getElement<HTMLElement>('chat-list'),
+  chatPreviewPanel: getElement<HTMLElement>('chat-preview-panel'),
+  chatPreviewTitle: getElement<HTMLElement>('chat-preview-title'),
+  chatPreviewMessages: getElement<HTMLElement>('chat-preview-messages'),
+  chatPreviewCloseBtn: getElement<HTMLButtonElement>('chat-preview-close-btn'),
+  exclusionRulesBtn: getElement<HTMLButtonElement>('exclusion-rules-btn'),
+  exclusionRulesPanel: getElement<HTMLElement>('exclusion-rules-panel'),
+  accessTokensBtn: getElement<HTMLButtonElement>('access-tokens-btn'),
   selectedCount: getElement<HTMLElement>('selected-count'),
 
   selectAllBtn: getElement<HTMLButtonElement>('select-all-btn'),
   selectNoneBtn: getElement<HTMLButtonElement>('select-none-btn'),
+  refreshChatsBtn: getElement<HTMLButtonElement>('refresh-chats-btn'),
   exportBtn: getElement<HTMLButtonElement>('export-btn'),
 
   // Permission screen elements
@@ -82,9 +131,7 @@ function showScreen(screen: HTMLElement): void {
     elements.errorScreen
   ]
 
-  for (const s of screens) {
-    s.classList.add('hidden')
-  }
+  for (const s of screens) s.classList.add('hidden')
   screen.classList.remove('hidden')
 
   // Track page view for analytics
@@ -95,7 +142,11 @@ function showScreen(screen: HTMLElement): void {
 function getFilteredChats(): ChatInfo[] {
   return state.chats.filter((chat) => {
     if (!state.filter) return true
-    return chat.display_name.toLowerCase().includes(state.filter.toLowerCase())
+    const filter = state.filter.toLowerCase()
+    return (
+      chat.display_name.toLowerCase().includes(filter) ||
+      chat.participants.some((name) => name.toLowerCase().includes(filter))
+    )
   })
 }
 
@@ -112,12 +163,14 @@ function renderChatList(): void {
     .map((chat) => {
       const selected = state.selectedIds.has(chat.id)
       return `
-        <div class="chat-item ${selected ? 'selected' : ''}" data-id="${chat.id}">
+        <div class="chat-item ${selected ? 'selected' : ''} ${chat.excluded ? 'excluded' : ''}" data-id="${chat.id}">
           <div class="chat-checkbox">${selected ? '✓' : ''}</div>
           <div class="chat-info">
-            <div class="chat-name">${escapeHtml(chat.display_name)}</div>
-            <div class="chat-meta">${chat.message_count} messages · ${escapeHtml(chat.service)}</div>
+            <div class="chat-name">${escapeHtml(chat.display_name)}${chat.excluded ? ' <span class="excluded-badge">Excluded</span>' : ''}</div>
+            <div class="chat-meta">${chat.chat_ids.length > 1 ? `${chat.chat_ids.length} threads · ` : ''}${chat.message_count} messages · ${escapeHtml(chat.service)}${formatLastMessageDate(chat)}</div>
+            ${chat.last_message_preview ? `<div class="chat-preview">${escapeHtml(chat.last_message_preview)}</div>` : ''}
           </div>
+          <button class="btn btn-small chat-peek-btn" data-id="${chat.id}">Peek</button>
         </div>
       `
     })
@@ -126,17 +179,16 @@ function renderChatList(): void {
   updateSelectedCount()
 }
 
+function formatLastMessageDate(chat: ChatInfo): string {
+  if (!chat.last_message_date) return ''
+  return ` · ${new Date(chat.last_message_date).toLocaleDateString()}`
+}
+
 function updateSelectedCount(): void {
   const count = state.selectedIds.size
   elements.selectedCount.textContent = `${count} chat${count === 1 ? '' : 's'} selected`
 }
 
-function escapeHtml(text: string): string {
-  const div = document.createElement('div')
-  div.textContent = text
-  return div.innerHTML
-}
-
 // Event handlers
 function setupEventListeners(): void {
   // Filter input
@@ -145,9 +197,31 @@ function setupEventListeners(): void {
     renderChatList()
   })
 
-  // Chat list clicks
+  // Message and person search
+  setupSearch(elements, {
+    chats: () => state.chats,
+    customDbPath: () => state.customDbPath,
+    selectChat: (id) => {
+      state.selectedIds.add(id)
+      renderChatList()
+    }
+  })
+
+  // Queued exports run one after another; failed uploads wait in the outbox
+  setupExportQueue(() => ({ ...selectedExportRequest(), customDbPath: state.customDbPath }))
+  setupPendingUploads()
+  setupUploadHistory()
+  setupDeepLinks(() => showScreen(elements.chatSelectionScreen), showError)
+
+  // Exclusion rules (saving reloads the list so flags update)
+  setupExclusionRules(elements, () => state.customDbPath, loadChats)
+  setupAccessTokens(elements)
+
+  // Chat list clicks (Peek buttons are handled by the chat preview)
+  setupChatPreview(elements, () => state.chats, () => state.customDbPath)
   elements.chatList.addEventListener('click', (e) => {
     const target = e.target as HTMLElement
+    if (target.closest('.chat-peek-btn')) return
     const chatItem = target.closest('.chat-item') as HTMLElement | null
     if (!chatItem) return
 
@@ -165,10 +239,10 @@ function setupEventListeners(): void {
     renderChatList()
   })
 
-  // Select all/none
+  // Select all/none, and reread the chats past the cache
   elements.selectAllBtn.addEventListener('click', () => {
     for (const chat of getFilteredChats()) {
-      state.selectedIds.add(chat.id)
+      if (!chat.excluded) state.selectedIds.add(chat.id)
     }
     renderChatList()
   })
@@ -179,19 +253,12 @@ function setupEventListeners(): void {
     }
     renderChatList()
   })
+  elements.refreshChatsBtn.addEventListener('click', () => loadChats(true))
 
   // Export button
   elements.exportBtn.addEventListener('click', handleExport)
 
-  // Permission screen buttons
-  elements.openFdaSettingsBtn.addEventListener('click', async () => {
-    FunnelEvents.openedSystemPreferences()
-    await invoke('open_full_disk_access_settings')
-  })
-
-  elements.openContactsSettingsBtn.addEventListener('click', async () => {
-    await invoke('open_contacts_settings')
-  })
+  setupPermissionButtons(elements)
 
   elements.retryPermissionBtn.addEventListener('click', () => {
     FunnelEvents.retriedPermission()
@@ -249,16 +316,15 @@ async function handleSelectCustomDb(): Promise<void> {
 
     if (selected && typeof selected === 'string') {
       // Validate it's a chat.db file
-      const isValid = await invoke<boolean>('validate_chat_db', { path: selected })
-      if (!isValid) {
-        alert(
-          'This does not appear to be a valid iMessage database.\n\n' +
-            'The file should be named "chat.db" and contain iMessage tables.'
-        )
+      const validation = await invoke<ValidationResult>('validate_chat_db', { path: selected })
+      if (!validation.valid) {
+        alert(describeValidationFailure(validation))
         return
       }
+      console.log('[validate_chat_db]', describeDatabase(validation))
 
       state.customDbPath = selected
+      await updateSettings({ custom_db_path: selected })
       FunnelEvents.selectedCustomDb()
       showScreen(elements.chatSelectionScreen)
       await loadChats()
@@ -269,40 +335,26 @@ async function handleSelectCustomDb(): Promise<void> {
   }
 }
 
-// Update permission status indicators in the UI
-function updatePermissionStatus(element: HTMLElement, granted: boolean | null): void {
-  const icon = element.querySelector('.status-icon')
-  if (!icon) return
-
-  icon.classList.remove('status-pending', 'status-granted', 'status-denied')
-  if (granted === null) {
-    icon.classList.add('status-pending')
-    icon.textContent = '○'
-  } else if (granted) {
-    icon.classList.add('status-granted')
-    icon.textContent = '✓'
-  } else {
-    icon.classList.add('status-denied')
-    icon.textContent = '✗'
-  }
-}
-
 async function checkPermissionAndLoadChats(): Promise<void> {
   console.log('[checkPermissionAndLoadChats] Starting...')
   try {
     // Check Full Disk Access
     console.log('[checkPermissionAndLoadChats] Invoking check_full_disk_access...')
-    const hasFdaAccess = await invoke<boolean>('check_full_disk_access')
-    console.log('[checkPermissionAndLoadChats] hasFdaAccess:', hasFdaAccess)
+    const fdaAccess = await invoke<FullDiskAccess>('check_full_disk_access')
+    console.log('[checkPermissionAndLoadChats] fdaAccess:', fdaAccess)
+    const hasFdaAccess = canReadDatabase(fdaAccess)
 
     // Check Contacts access
     console.log('[checkPermissionAndLoadChats] Invoking check_contacts_access...')
-    const hasContactsAccess = await invoke<boolean>('check_contacts_access')
-    console.log('[checkPermissionAndLoadChats] hasContactsAccess:', hasContactsAccess)
+    const contactsAccess = await promptForContacts(
+      await invoke<ContactsAccess>('check_contacts_access')
+    )
+    console.log('[checkPermissionAndLoadChats] contactsAccess:', contactsAccess)
 
     // Update permission status UI
     updatePermissionStatus(elements.fdaStatus, hasFdaAccess)
-    updatePermissionStatus(elements.contactsStatus, hasContactsAccess)
+    updatePermissionStatus(elements.contactsStatus, contactsAccess === 'authorized')
+    showPermissionHelp(elements.permissionScreen, fdaAccess)
 
     // FDA is required - if not granted, show permission screen
     if (!hasFdaAccess) {
@@ -321,18 +373,28 @@ async function checkPermissionAndLoadChats(): Promise<void> {
   }
 }
 
-async function loadChats(): Promise<void> {
-  elements.chatList.innerHTML = '<div class="loading">Loading chats...</div>'
+async function loadChats(refresh = false): Promise<void> {
+  elements.chatList.innerHTML = '<div class="loading">Loading contacts...</div>'
+  const unlisten = await listen<ChatListStage>('chat-list-progress', (event) => {
+    elements.chatList.innerHTML = `<div class="loading">${CHAT_LIST_NEXT_STEP[event.payload]}</div>`
+  })
 
   try {
-    state.chats = await invoke<ChatInfo[]>('list_chats', {
-      customDbPath: state.customDbPath
+    const page = await invoke<ChatPage>('list_chats', {
+      customDbPath: state.customDbPath,
+      refresh
     })
+    state.chats = page.chats
     FunnelEvents.chatsLoaded(state.chats.length)
+    // Drop restored selections for chats that no longer exist
+    const ids = new Set(state.chats.map((chat) => chat.id))
+    state.selectedIds = new Set([...state.selectedIds].filter((id) => ids.has(id)))
     renderChatList()
   } catch (error) {
     console.error('Error loading chats:', error)
     elements.chatList.innerHTML = `<div class="loading">Error loading chats: ${escapeHtml(String(error))}</div>`
+  } finally {
+    unlisten()
   }
 }
 
@@ -342,12 +404,18 @@ async function handleExport(): Promise<void> {
     return
   }
 
+  const preflight = { ...selectedExportRequest(), customDbPath: state.customDbPath }
+  if (!(await confirmPreflightWarnings(preflight))) {
+    return
+  }
+
   FunnelEvents.exportStarted(state.selectedIds.size)
+  await updateSettings({ last_selected_chat_ids: [...state.selectedIds] })
   showScreen(elements.progressScreen)
 
   try {
     const result = await invoke<ExportResult>('export_and_upload', {
-      chatIds: Array.from(state.selectedIds),
+      ...selectedExportRequest(),
       customDbPath: state.customDbPath
     })
 
@@ -362,9 +430,28 @@ async function handleExport(): Promise<void> {
       showError(errorMsg)
     }
   } catch (error) {
+    if (isExportCancelled(error)) {
+      showScreen(elements.chatSelectionScreen)
+      return
+    }
     console.error('Export error:', error)
-    FunnelEvents.exportFailed(String(error))
-    showError(String(error))
+    const errorMsg = describeExportError(error)
+    FunnelEvents.exportFailed(errorMsg)
+    showError(errorMsg)
+  }
+}
+
+// Expand the selection into underlying chat ROWIDs; merged duplicates are
+// passed as chat_groups so they export as a single conversation
+function selectedExportRequest(): { chatIds: number[]; options: { chat_groups: number[][] } } {
+  const selected = state.chats.filter((chat) => state.selectedIds.has(chat.id))
+  return {
+    chatIds: selected.flatMap((chat) => chat.chat_ids),
+    options: {
+      chat_groups: selected
+        .filter((chat) => chat.chat_ids.length > 1)
+        .map((chat) => chat.chat_ids)
+    }
   }
 }
 
@@ -373,16 +460,6 @@ function showError(message: string): void {
   showScreen(elements.errorScreen)
 }
 
-// Listen for progress updates from Rust
-async function setupProgressListener(): Promise<void> {
-  await listen<ExportProgress>('export-progress', (event) => {
-    const progress = event.payload
-    elements.progressStage.textContent = progress.stage
-    elements.progressFill.style.width = `${progress.percent}%`
-    elements.progressMessage.textContent = progress.message
-  })
-}
-
 // Initialize tooltips
 function initTooltips(): void {
   tippy('[data-tippy-content]', {
@@ -394,23 +471,28 @@ function initTooltips(): void {
 
 // Initialize
 async function init(): Promise<void> {
-  initAnalytics()
+  const appInfo = await invoke<AppInfo>('get_app_info')
+  initAnalytics(appInfo.version)
   initTooltips()
   setupEventListeners()
-  await setupProgressListener()
+  await setupProgressListener(elements)
   await initDebugSettingsOnStartup()
 
   const config = await invoke<ScreenshotConfig>('get_screenshot_config')
 
   if (config.enabled) {
-    await runScreenshotMode(config, {
-      elements,
-      state,
-      showScreen,
-      renderChatList
-    })
+    await runScreenshotMode(config, { elements, state, showScreen, renderChatList })
   } else {
-    await checkPermissionAndLoadChats()
+    const restored = await restoreSettings()
+    state.customDbPath = restored.customDbPath
+    state.selectedIds = new Set(restored.selectedIds)
+    if (state.customDbPath) {
+      // A saved chat.db doesn't need Full Disk Access
+      showScreen(elements.chatSelectionScreen)
+      await loadChats()
+    } else {
+      await checkPermissionAndLoadChats()
+    }
   }
 }
 
@@ -0,0 +1,94 @@
+/**
+ * Pending uploads - finished exports whose upload failed, kept in the outbox
+ * to retry or discard
+ */
+
+import { invoke } from '@tauri-apps/api/core'
+import { listen } from '@tauri-apps/api/event'
+import { escapeHtml } from './html'
+import { describeExportError } from './progress'
+import type { PendingUpload } from './types'
+
+// Elements and state (initialized in setup)
+let panel: HTMLElement
+let uploadsList: HTMLElement
+let uploads: PendingUpload[] = []
+// Pending uploads being retried right now
+const retrying = new Set<string>()
+
+function describeUpload(upload: PendingUpload): string {
+  if (retrying.has(upload.id)) return 'Uploading...'
+  const saved = new Date(upload.created_at).toLocaleString([], {
+    dateStyle: 'medium',
+    timeStyle: 'short'
+  })
+  const mb = (upload.size_bytes / (1024 * 1024)).toFixed(1)
+  const error = upload.last_error ? ` - ${upload.last_error}` : ''
+  return `Saved ${saved}, ${mb} MB${error}`
+}
+
+function renderUploads(): void {
+  panel.classList.toggle('hidden', uploads.length === 0)
+  uploadsList.innerHTML = uploads
+    .map((upload) => {
+      const id = escapeHtml(upload.id)
+      const busy = retrying.has(upload.id) ? 'disabled' : ''
+      return `
+        <div class="export-queue-job ${upload.last_error ? 'failed' : ''}">
+          <span>${escapeHtml(`${upload.chat_count} chats`)}</span>
+          <span class="chat-meta">${escapeHtml(describeUpload(upload))}</span>
+          <button class="btn btn-small" data-retry="${id}" ${busy}>Retry</button>
+          <button class="btn btn-small" data-discard="${id}" ${busy}>Discard</button>
+        </div>
+      `
+    })
+    .join('')
+}
+
+async function retry(id: string): Promise<void> {
+  retrying.add(id)
+  renderUploads()
+  try {
+    await invoke('retry_upload', { id })
+  } catch (error) {
+    alert(describeExportError(error))
+  } finally {
+    retrying.delete(id)
+    renderUploads()
+  }
+}
+
+async function discard(id: string): Promise<void> {
+  if (!confirm('Delete this export without uploading it?')) return
+  try {
+    await invoke('discard_pending_upload', { id })
+  } catch (error) {
+    alert(`Could not discard export: ${error}`)
+  }
+}
+
+export async function setupPendingUploads(): Promise<void> {
+  const found = document.getElementById('pending-uploads-panel')
+  const list = document.getElementById('pending-uploads-list')
+  if (!found || !list) {
+    throw new Error('Pending uploads elements not found')
+  }
+  panel = found
+  uploadsList = list
+
+  uploadsList.addEventListener('click', (e) => {
+    const button = (e.target as HTMLElement).closest('button')
+    const retryId = button?.dataset['retry']
+    const discardId = button?.dataset['discard']
+    if (retryId) retry(retryId)
+    if (discardId) discard(discardId)
+  })
+
+  await listen<PendingUpload[]>('pending-uploads-changed', (event) => {
+    uploads = event.payload
+    renderUploads()
+  })
+
+  uploads = await invoke<PendingUpload[]>('list_pending_uploads')
+  renderUploads()
+}
@@ -0,0 +1,70 @@
+/**
+ * Permission status indicators on the permission screen
+ */
+
+import { invoke } from '@tauri-apps/api/core'
+import { FunnelEvents } from './analytics'
+import type { ContactsAccess, FullDiskAccess } from './types'
+
+// Whether chats can be loaded after the Full Disk Access check. Platforms
+// without a default database can still read one picked by hand.
+export function canReadDatabase(access: FullDiskAccess): boolean {
+  return access === 'granted' || access === 'unsupported_platform'
+}
+
+// Show the native Contacts prompt the first time the app runs, rather than
+// leaving users to find System Settings themselves
+export async function promptForContacts(access: ContactsAccess): Promise<ContactsAccess> {
+  return access === 'not_determined' ? invoke<ContactsAccess>('request_contacts_access') : access
+}
+
+// Ask for Contacts access: the system prompt if the user hasn't been asked
+// yet, otherwise System Settings (macOS never prompts twice)
+async function grantContactsAccess(): Promise<ContactsAccess> {
+  const access = await invoke<ContactsAccess>('request_contacts_access')
+  if (access === 'denied' || access === 'restricted') {
+    await invoke('open_contacts_settings')
+  }
+  return access
+}
+
+// With no database at all, granting Full Disk Access won't help: show the
+// "no database found" help instead
+export function showPermissionHelp(screen: HTMLElement, access: FullDiskAccess): void {
+  screen.classList.toggle('database-not-found', access === 'database_not_found')
+}
+
+// Update permission status indicators in the UI
+export function updatePermissionStatus(element: HTMLElement, granted: boolean | null): void {
+  const icon = element.querySelector('.status-icon')
+  if (!icon) return
+
+  icon.classList.remove('status-pending', 'status-granted', 'status-denied')
+  if (granted === null) {
+    icon.classList.add('status-pending')
+    icon.textContent = '○'
+  } else if (granted) {
+    icon.classList.add('status-granted')
+    icon.textContent = '✓'
+  } else {
+    icon.classList.add('status-denied')
+    icon.textContent = '✗'
+  }
+}
+
+// Permission screen "Open Settings" buttons
+export function setupPermissionButtons(elements: {
+  openFdaSettingsBtn: HTMLButtonElement
+  openContactsSettingsBtn: HTMLButtonElement
+  contactsStatus: HTMLElement
+}): void {
+  elements.openFdaSettingsBtn.addEventListener('click', async () => {
+    FunnelEvents.openedSystemPreferences()
+    await invoke('open_full_disk_access_settings')
+  })
+
+  elements.openContactsSettingsBtn.addEventListener('click', async () => {
+    const access = await grantContactsAccess()
+    updatePermissionStatus(elements.contactsStatus, access === 'authorized')
+  })
+}
@@ -0,0 +1,66 @@
+/**
+ * Checks before an export starts: content warnings for chats that would
+ * export almost nothing, and a heads-up when the export will be large or slow
+ */
+
+import { invoke } from '@tauri-apps/api/core'
+import type { ExportEstimate, ExportPreflight } from './types'
+
+// Exports at least this big or slow get a size/time warning
+const LARGE_EXPORT_BYTES = 100 * 1024 * 1024
+const SLOW_EXPORT_SECS = 60
+
+interface PreflightRequest {
+  chatIds: number[]
+  options: object
+  customDbPath: string | null
+}
+
+// "~1.2 GB", "~350 MB"
+function formatSize(bytes: number): string {
+  const mb = bytes / (1024 * 1024)
+  return mb >= 1024 ? `~${(mb / 1024).toFixed(1)} GB` : `~${Math.max(1, Math.round(mb))} MB`
+}
+
+// "~6 minutes", "~2 hours"
+function formatDuration(secs: number): string {
+  const minutes = Math.max(1, Math.round(secs / 60))
+  if (minutes < 90) return `~${minutes} minute${minutes === 1 ? '' : 's'}`
+  return `~${Math.round(minutes / 60)} hours`
+}
+
+async function estimateWarning(request: PreflightRequest): Promise<string | null> {
+  const estimate = await invoke<ExportEstimate>('estimate_export', {
+    chatIds: request.chatIds,
+    customDbPath: request.customDbPath
+  })
+  const secs = estimate.export_secs + estimate.upload_secs
+  if (estimate.archive_bytes < LARGE_EXPORT_BYTES && secs < SLOW_EXPORT_SECS) {
+    return null
+  }
+  const messages = estimate.message_count.toLocaleString()
+  const size = formatSize(estimate.archive_bytes)
+  return `These ${messages} messages will be ${size} and take ${formatDuration(secs)} to export and upload.`
+}
+
+// Warn before exporting chats that would produce a nearly empty export, or
+// an export large enough to take a while. Returns false if the user chose to
+// go back and adjust the selection.
+export async function confirmPreflightWarnings(request: PreflightRequest): Promise<boolean> {
+  try {
+    const [preflight, estimate] = await Promise.all([
+      invoke<ExportPreflight>('preflight_export', request),
+      estimateWarning(request)
+    ])
+    const lines = preflight.warnings.map((w) => `• ${w.message}`)
+    if (estimate) lines.push(`• ${estimate}`)
+    if (lines.length === 0) {
+      return true
+    }
+    return confirm(`Heads up before exporting:\n\n${lines.join('\n')}\n\nExport anyway?`)
+  } catch (error) {
+    // Analysis is advisory; never block an export on it
+    console.error('Preflight error:', error)
+    return true
+  }
+}
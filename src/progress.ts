@@ -0,0 +1,111 @@
+/**
+ * Export progress - progress bar updates and stall notices from the Rust pipeline
+ *
+ * Each export has an ID (from the `export-started` event); progress from any
+ * other export, such as one that was cancelled, is ignored.
+ */
+
+import { invoke } from '@tauri-apps/api/core'
+import { listen } from '@tauri-apps/api/event'
+import type { ExportError, ExportProgress, StallEvent } from './types'
+
+// Message for an export command rejection (tagged ExportError or plain string)
+export function describeExportError(error: unknown): string {
+  const exportError = error as Partial<ExportError> | null
+  if (exportError?.kind === 'busy') {
+    const state = exportError.state?.replaceAll('_', ' ')
+    return `An export is already in progress (${state}). Please wait for it to finish.`
+  }
+  if (exportError?.kind === 'cancelled') {
+    return 'Export cancelled'
+  }
+  if (exportError?.kind === 'not_cancellable') {
+    return 'The export has already reached the server and can no longer be cancelled.'
+  }
+  if (exportError?.kind === 'insufficient_disk_space') {
+    const mb = (bytes: number | undefined) => Math.ceil((bytes ?? 0) / (1024 * 1024))
+    const required = mb(exportError.required_bytes)
+    const available = mb(exportError.available_bytes)
+    return `Not enough disk space for this export: it needs about ${required} MB, but only ${available} MB is free.`
+  }
+  if (exportError?.kind === 'timeout') {
+    return describeTimeout(exportError.step, exportError.seconds ?? 0)
+  }
+  if (exportError?.kind === 'rate_limited') {
+    return `The server is busy right now. Please try again in ${describeWait(exportError.retry_after_secs ?? 0)}.`
+  }
+  // A rejection's message is the server's reason
+  if (exportError?.kind === 'failed' || exportError?.kind === 'rejected') {
+    return exportError.message ?? 'Unknown error occurred'
+  }
+  return String(error)
+}
+
+const TIMEOUT_STEPS: Record<string, string> = {
+  presign: 'preparing the upload',
+  upload: 'the upload',
+  complete: 'starting processing',
+}
+
+function describeWait(seconds: number): string {
+  return seconds >= 120 ? `${Math.round(seconds / 60)} minutes` : `${seconds}s`
+}
+
+function describeTimeout(step: string | undefined, seconds: number): string {
+  if (step === 'deadline') {
+    return `The upload did not finish within ${describeWait(seconds)}. Please check your connection and try again.`
+  }
+  const during = TIMEOUT_STEPS[step ?? ''] ?? step
+  return `The server stopped responding during ${during} (nothing for ${seconds}s). Please check your connection and try again.`
+}
+
+export function isExportCancelled(error: unknown): boolean {
+  return (error as Partial<ExportError> | null)?.kind === 'cancelled'
+}
+
+let currentExportId: string | null = null
+
+function describeStall(stall: StallEvent): string {
+  const last = stall.last_activity ? ` (last: ${stall.last_activity})` : ''
+  const next = stall.will_retry ? 'Retrying...' : 'Still waiting...'
+  return `No progress for ${stall.idle_secs}s while ${stall.stage.toLowerCase()}${last}. ${next}`
+}
+
+// Listen for progress updates and stall notices from Rust
+export async function setupProgressListener(elements: {
+  progressStage: HTMLElement
+  progressFill: HTMLElement
+  progressMessage: HTMLElement
+}): Promise<void> {
+  await listen<{ export_id: string }>('export-started', (event) => {
+    currentExportId = event.payload.export_id
+  })
+
+  await listen<ExportProgress>('export-progress', (event) => {
+    const progress = event.payload
+    if (progress.export_id && progress.export_id !== currentExportId) {
+      return
+    }
+    elements.progressStage.textContent = progress.stage
+    elements.progressFill.style.width = `${progress.percent}%`
+    elements.progressMessage.textContent = progress.message
+    elements.progressMessage.classList.remove('stalled')
+  })
+
+  // Cleared by the next progress update
+  await listen<StallEvent>('export-stalled', (event) => {
+    elements.progressMessage.textContent = describeStall(event.payload)
+    elements.progressMessage.classList.add('stalled')
+  })
+
+  document.getElementById('cancel-export-btn')?.addEventListener('click', async () => {
+    if (!currentExportId) {
+      return
+    }
+    try {
+      await invoke('cancel_export', { exportId: currentExportId })
+    } catch (error) {
+      elements.progressMessage.textContent = describeExportError(error)
+    }
+  })
+}
@@ -1,32 +1,50 @@
 import { invoke } from '@tauri-apps/api/core'
-import type { ChatInfo, ScreenshotConfig } from './types'
+import type { ChatInfo, ChatPage, ScreenshotConfig } from './types'
 
 // Mock data for screenshot mode
 export function getMockChats(): ChatInfo[] {
   return [
     {
       id: 1,
+      chat_ids: [1],
       display_name: 'Alice Johnson',
       chat_identifier: '+15551234567',
       service: 'iMessage',
       participant_count: 1,
-      message_count: 1542
+      participants: ['Alice Johnson'],
+      message_count: 1542,
+      last_message_date: '2024-06-01T18:42:00Z',
+      last_message_preview: 'See you at the trattoria at 8!',
+      excluded: false,
+      automated: false
     },
     {
       id: 2,
+      chat_ids: [2],
       display_name: 'Travel Planning Group',
       chat_identifier: 'chat123',
       service: 'iMessage',
       participant_count: 5,
-      message_count: 823
+      participants: ['Alice Johnson', 'Bob Williams', 'Carol Davis', 'Dan Evans', '+1 more'],
+      message_count: 823,
+      last_message_date: '2024-05-28T09:15:00Z',
+      last_message_preview: 'I booked the Airbnb in Lisbon',
+      excluded: false,
+      automated: false
     },
     {
       id: 3,
+      chat_ids: [3],
       display_name: 'Bob Williams',
       chat_identifier: '+15559876543',
       service: 'iMessage',
       participant_count: 1,
-      message_count: 456
+      participants: ['Bob Williams'],
+      message_count: 456,
+      last_message_date: '2024-05-20T21:03:00Z',
+      last_message_preview: null,
+      excluded: false,
+      automated: false
     }
   ]
 }
@@ -80,7 +98,7 @@ export async function runScreenshotMode(
 
   if (!config.force_no_fda) {
     try {
-      ctx.state.chats = await invoke<ChatInfo[]>('list_chats')
+      ctx.state.chats = (await invoke<ChatPage>('list_chats')).chats
     } catch {
       ctx.state.chats = getMockChats()
     }
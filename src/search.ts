@@ -0,0 +1,190 @@
+/**
+ * Message search - find the chat that mentions "Airbnb in Lisbon" before exporting
+ * Person search - find every chat with someone and select them all at once
+ */
+
+import { invoke } from '@tauri-apps/api/core'
+import { escapeHtml } from './html'
+import type { ChatInfo, ContactChats, SearchResult } from './types'
+
+// Constants
+const SEARCH_LIMIT = 50
+
+// What the search needs from the main app
+interface SearchContext {
+  chats: () => ChatInfo[]
+  customDbPath: () => string | null
+  selectChat: (id: number) => void
+}
+
+// Elements and context (initialized in setup)
+let searchInput: HTMLInputElement
+let searchResults: HTMLElement
+let personInput: HTMLInputElement
+let personResults: HTMLElement
+let people: ContactChats[] = []
+let context: SearchContext
+
+// Results carry the underlying chat ROWID; merged chats list several
+function chatForResult(result: SearchResult): ChatInfo | undefined {
+  return context.chats().find((chat) => chat.chat_ids.includes(result.chat_id))
+}
+
+function renderSnippet(result: SearchResult): string {
+  return result.snippet
+    .map((part) =>
+      part.highlight ? `<mark>${escapeHtml(part.text)}</mark>` : escapeHtml(part.text)
+    )
+    .join('')
+}
+
+function renderResults(results: SearchResult[]): void {
+  if (results.length === 0) {
+    searchResults.innerHTML = '<div class="loading">No messages found</div>'
+    return
+  }
+
+  searchResults.innerHTML = results
+    .map((result) => {
+      const chat = chatForResult(result)
+      const date = new Date(result.timestamp).toLocaleDateString()
+      return `
+        <div class="search-result" data-id="${chat?.id ?? ''}">
+          <div class="chat-name">${escapeHtml(chat?.display_name ?? 'Unknown chat')}</div>
+          <div class="chat-meta">${escapeHtml(result.sender)} · ${date}</div>
+          <div class="search-snippet">${renderSnippet(result)}</div>
+        </div>
+      `
+    })
+    .join('')
+}
+
+async function runSearch(): Promise<void> {
+  const query = searchInput.value.trim()
+  if (!query) {
+    searchResults.classList.add('hidden')
+    searchResults.innerHTML = ''
+    return
+  }
+
+  searchResults.classList.remove('hidden')
+  searchResults.innerHTML = '<div class="loading">Searching...</div>'
+  try {
+    const results = await invoke<SearchResult[]>('search_messages', {
+      query,
+      limit: SEARCH_LIMIT,
+      customDbPath: context.customDbPath()
+    })
+    renderResults(results)
+  } catch (error) {
+    console.error('Search error:', error)
+    searchResults.innerHTML = `<div class="loading">Search failed: ${escapeHtml(String(error))}</div>`
+  }
+}
+
+function renderPeople(): void {
+  if (people.length === 0) {
+    personResults.innerHTML = '<div class="loading">No contacts found</div>'
+    return
+  }
+
+  personResults.innerHTML = people
+    .map((person, index) => {
+      const count = person.chat_ids.length
+      return `
+        <div class="search-result" data-person="${index}">
+          <div class="chat-name">${escapeHtml(person.name)}</div>
+          <div class="chat-meta">${count} chat${count === 1 ? '' : 's'} · click to select all</div>
+        </div>
+      `
+    })
+    .join('')
+}
+
+async function runPersonSearch(): Promise<void> {
+  const name = personInput.value.trim()
+  if (!name) {
+    personResults.classList.add('hidden')
+    personResults.innerHTML = ''
+    return
+  }
+
+  personResults.classList.remove('hidden')
+  personResults.innerHTML = '<div class="loading">Searching...</div>'
+  try {
+    people = await invoke<ContactChats[]>('find_chats_for_contact', {
+      name,
+      customDbPath: context.customDbPath()
+    })
+    renderPeople()
+  } catch (error) {
+    console.error('Person search error:', error)
+    personResults.innerHTML = `<div class="loading">Search failed: ${escapeHtml(String(error))}</div>`
+  }
+}
+
+function setupPersonSearch(): void {
+  personInput.addEventListener('keydown', (e) => {
+    if (e.key === 'Enter') {
+      runPersonSearch()
+    }
+  })
+  personInput.addEventListener('input', () => {
+    if (!personInput.value.trim()) {
+      runPersonSearch()
+    }
+  })
+
+  // Clicking a person selects every list entry holding one of their chats
+  personResults.addEventListener('click', (e) => {
+    const target = e.target as HTMLElement
+    const result = target.closest('.search-result') as HTMLElement | null
+    const person = people[Number.parseInt(result?.dataset['person'] ?? '', 10)]
+    if (!person) return
+    for (const chat of context.chats()) {
+      if (chat.chat_ids.some((id) => person.chat_ids.includes(id))) {
+        context.selectChat(chat.id)
+      }
+    }
+  })
+}
+
+export function setupSearch(
+  elements: {
+    messageSearchInput: HTMLInputElement
+    messageSearchResults: HTMLElement
+    personSearchInput: HTMLInputElement
+    personSearchResults: HTMLElement
+  },
+  searchContext: SearchContext
+): void {
+  // Store element references
+  searchInput = elements.messageSearchInput
+  searchResults = elements.messageSearchResults
+  personInput = elements.personSearchInput
+  personResults = elements.personSearchResults
+  context = searchContext
+  setupPersonSearch()
+
+  // Search on Enter; clearing the box hides the results
+  searchInput.addEventListener('keydown', (e) => {
+    if (e.key === 'Enter') {
+      runSearch()
+    }
+  })
+  searchInput.addEventListener('input', () => {
+    if (!searchInput.value.trim()) {
+      runSearch()
+    }
+  })
+
+  // Clicking a result selects its chat for export
+  searchResults.addEventListener('click', (e) => {
+    const target = e.target as HTMLElement
+    const result = target.closest('.search-result') as HTMLElement | null
+    const id = Number.parseInt(result?.dataset['id'] ?? '', 10)
+    if (!Number.isNaN(id)) {
+      context.selectChat(id)
+    }
+  })
+}
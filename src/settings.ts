@@ -0,0 +1,49 @@
+/**
+ * Persisted app settings - custom chat.db path, theme, host overrides, last selection
+ */
+
+import { invoke } from '@tauri-apps/api/core'
+import { setTheme } from './screenshot'
+import type { AppSettings, ValidationResult } from './types'
+import { describeValidationFailure } from './validation'
+
+async function loadSettings(): Promise<AppSettings | null> {
+  try {
+    return await invoke<AppSettings>('get_settings')
+  } catch (error) {
+    console.error('Failed to load settings:', error)
+    return null
+  }
+}
+
+// Merge changes into the saved settings (read first so other fields survive)
+export async function updateSettings(changes: Partial<AppSettings>): Promise<void> {
+  const current = await loadSettings()
+  if (!current) return
+  try {
+    await invoke('set_settings', { settings: { ...current, ...changes } })
+  } catch (error) {
+    console.error('Failed to save settings:', error)
+  }
+}
+
+// Apply the saved theme and return the saved chat.db (if still valid) and selection
+export async function restoreSettings(): Promise<{
+  customDbPath: string | null
+  selectedIds: number[]
+}> {
+  const settings = await loadSettings()
+  if (settings?.theme) setTheme(settings.theme)
+
+  // A selected source (see db_sources.rs) is read by default, so don't pass a path over it
+  let customDbPath = settings?.selected_source_id ? null : (settings?.custom_db_path ?? null)
+  if (customDbPath) {
+    const validation = await invoke<ValidationResult>('validate_chat_db', { path: customDbPath })
+    if (!validation.valid) {
+      const reason = describeValidationFailure(validation)
+      console.warn('[restoreSettings] Ignoring saved chat.db:', reason)
+      customDbPath = null
+    }
+  }
+  return { customDbPath, selectedIds: settings?.last_selected_chat_ids ?? [] }
+}
@@ -1,24 +1,289 @@
 // Types matching Rust structs
 export interface ChatInfo {
   id: number
+  chat_ids: number[]
   display_name: string
   chat_identifier: string
   service: string
   participant_count: number
+  participants: string[]
   message_count: number
+  last_message_date: string
+  last_message_preview: string | null
+  excluded: boolean
+  /** Likely a short code, no-reply sender or other one-way sender */
+  automated: boolean
+}
+
+/** A finished step of `list_chats`, sent as `chat-list-progress` */
+export type ChatListStage = 'contacts_loaded' | 'chats_loaded' | 'stats_computed'
+
+export type ChatSort = 'recency' | 'name' | 'message_count'
+
+/** The `query` argument of `list_chats`; without it, every chat comes back newest first */
+export interface ChatListQuery {
+  sort?: ChatSort
+  filter?: string | null
+  hide_automated?: boolean
+  /** One entry per person; `chat_ids` lists their threads */
+  group_by_contact?: boolean
+  offset?: number
+  limit?: number | null
+}
+
+export interface ChatPage {
+  chats: ChatInfo[]
+  /** Chats matching the filter, across all pages */
+  total_count: number
+}
+
+export interface AppSettings {
+  custom_db_path: string | null
+  theme: 'light' | 'dark' | 'system' | null
+  server_host_override: string | null
+  api_host_override: string | null
+  log_level: 'error' | 'warn' | 'info' | 'debug' | 'trace' | null
+  last_selected_chat_ids: number[]
+  exclusion_rules: ExclusionRule[]
+  contacts_vcf_path: string | null
+  phone_suffix_digits: number | null
+  upload_connect_timeout_secs: number | null
+  upload_read_timeout_secs: number | null
+  upload_deadline_secs: number | null
+  upload_proxy_url: string | null
+  retry_uploads_on_launch: boolean
+  update_channel: 'stable' | 'beta' | null
+  redaction_defaults: RedactionConfig
+  error_reporting: boolean
+  auto_export: AutoExportSettings
+  launch_at_login: boolean
+  db_sources: DbSource[]
+  selected_source_id: string | null
+}
+
+/** A named chat.db to read instead of the Mac's own (another user's, or an archive) */
+export interface DbSource {
+  id: string
+  name: string
+  db_path: string
+  contacts_vcf_path: string | null
+}
+
+/** Result of `list_sources` */
+export interface DbSourceList {
+  sources: DbSource[]
+  /** null = the Mac's own chat.db */
+  selected_id: string | null
+}
+
+/** A chat.db found by `discover_databases`, to offer as a source */
+export interface FoundDatabase {
+  path: string
+  location: 'time_machine' | 'messages_folder' | 'folder'
+  /** e.g. "Time Machine (2023-05-01)" */
+  suggested_name: string
+  /** Message count and date range */
+  validation: ValidationResult
+}
+
+/** Scheduled incremental exports of the last selection */
+export interface AutoExportSettings {
+  /** Days between exports (null or 0 = off) */
+  interval_days: number | null
+  /** Upload each export, else keep it in Pending Uploads */
+  upload: boolean
+}
+
+export interface RedactionConfig {
+  strip_contact_details: boolean
+  pseudonymize_senders: boolean
+  drop_senders: string[]
+}
+
+/** The settings the Preferences window edits (app menu → Preferences...) */
+export type Preferences = Pick<
+  AppSettings,
+  | 'custom_db_path'
+  | 'server_host_override'
+  | 'api_host_override'
+  | 'theme'
+  | 'redaction_defaults'
+  | 'launch_at_login'
+>
+
+/** Whether the app opens at login, as macOS sees it (`get_login_item_status`) */
+export type LoginItemStatus =
+  | 'not_registered'
+  | 'enabled'
+  | 'requires_approval'
+  | 'not_found'
+  | 'unsupported'
+
+export type ExclusionRule =
+  | { kind: 'short_code' }
+  | { kind: 'name_contains'; text: string }
+  | { kind: 'identifier'; pattern: string }
+
+export interface ExportedMessage {
+  timestamp: string
+  sender: string
+  is_from_me: boolean
+  text: string
+  truncated?: boolean
+  deleted?: boolean
+}
+
+export interface ChatActivity {
+  message_count: number
+  first_message_at: string | null
+  last_message_at: string | null
+  /** Oldest first, `YYYY-MM`, with empty months included */
+  messages_per_month: { month: string; message_count: number }[]
+  /** Busiest sender first */
+  senders: { name: string; is_from_me: boolean; message_count: number }[]
+  attachment_count: number
 }
 
 export interface ExportProgress {
   stage: string
   percent: number
   message: string
+  export_id?: string
+}
+
+export interface StallEvent {
+  stage: string
+  last_activity: string
+  idle_secs: number
+  will_retry: boolean
+}
+
+export type ExportState = 'idle' | 'exporting' | 'waiting_to_upload' | 'uploading' | 'processing'
+
+// Error from export_and_upload / import_telegram_export
+export type ExportError =
+  | { kind: 'busy'; state: ExportState; export_id: string }
+  | { kind: 'cancelled'; export_id: string }
+  | { kind: 'not_cancellable'; export_id: string }
+  | { kind: 'insufficient_disk_space'; required_bytes: number; available_bytes: number }
+  | { kind: 'timeout'; step: string; seconds: number }
+  | { kind: 'rate_limited'; step: string; retry_after_secs: number }
+  | { kind: 'rejected'; code: string; message: string }
+  | { kind: 'failed'; message: string }
+
+export type QueuedExport = {
+  job_id: string
+  label: string | null
+  chat_count: number
+  queued_at: string
+  upload_at: string | null
+} & (
+  | { status: 'queued' | 'running' | 'cancelled' }
+  | { status: 'completed'; results_url: string | null }
+  | { status: 'failed'; error: string }
+)
+
+/** An export whose upload hasn't gone through, saved in the outbox */
+export interface PendingUpload {
+  id: string
+  created_at: string
+  upload_platform: string
+  chat_count: number
+  size_bytes: number
+  attempts: number
+  last_error?: string
+}
+
+/** What the account's plan still allows (`null` = no limit) */
+export interface AccountUsage {
+  plan: string | null
+  uploads_remaining: number | null
+  max_zip_bytes: number | null
+}
+
+/** A completed upload, kept so its map can be opened again */
+export interface UploadRecord {
+  job_id: string
+  chat_upload_id: string
+  uploaded_at: string
+  upload_platform: string
+  chat_count: number
+  message_count: number
+  results_url: string
 }
 
 export interface ExportResult {
+  export_id: string
   success: boolean
+  chat_count: number
   job_id: string | null
   results_url: string | null
   error: string | null
+  metrics: ExportMetrics
+}
+
+/** Throughput of one export run (also kept in the local job history) */
+interface ExportMetrics {
+  messages: number
+  archive_bytes: number
+  decode_secs: number
+  compress_secs: number
+  upload_secs: number | null
+  messages_per_sec: number
+  compressed_mb_per_sec: number
+  uploaded_mb_per_sec: number | null
+}
+
+interface ExportWarning {
+  chat_id: number
+  kind: 'no_exportable_messages' | 'mostly_unsupported_content' | 'shared_links_available'
+  message: string
+}
+
+export interface ExportPreflight {
+  warnings: ExportWarning[]
+}
+
+export interface ExportEstimate {
+  message_count: number
+  chat_count: number
+  archive_bytes: number
+  export_secs: number
+  upload_secs: number
+  based_on_runs: number
+}
+
+interface SnippetPart {
+  text: string
+  highlight: boolean
+}
+
+/** A person matching a "search by person" query, with their chat ROWIDs */
+export interface ContactChats {
+  name: string
+  chat_ids: number[]
+}
+
+export interface SearchResult {
+  chat_id: number
+  sender: string
+  is_from_me: boolean
+  timestamp: string
+  snippet: SnippetPart[]
+}
+
+interface ChangelogEntry {
+  version: string
+  date: string
+  changes: string[]
+}
+
+export interface AppInfo {
+  version: string
+  build_date: string
+  git_commit: string
+  update_channel: string
+  changelog: ChangelogEntry[]
 }
 
 export interface ScreenshotConfig {
@@ -27,3 +292,43 @@ export interface ScreenshotConfig {
   force_no_fda: boolean
   output_dir: string
 }
+
+interface DatabaseOrigin {
+  min_macos_version: number | null
+  client_version: string | null
+  features: string[]
+}
+
+/** Contacts permission, from the system's authorization status */
+export type ContactsAccess = 'not_determined' | 'restricted' | 'denied' | 'authorized' | 'unavailable'
+
+/** Outcome of the Full Disk Access check */
+export type FullDiskAccess = 'granted' | 'denied' | 'database_not_found' | 'unsupported_platform'
+
+export interface ValidationResult {
+  valid: boolean
+  failure_reason: 'not_found' | 'not_sqlite' | 'missing_tables' | 'query_failed' | null
+  error: string | null
+  platform: 'macos' | 'ios'
+  schema_version: number | null
+  origin: DatabaseOrigin | null
+  has_chat_table: boolean
+  has_message_table: boolean
+  has_handle_table: boolean
+  message_count: number
+  first_message_date: string | null
+  last_message_date: string | null
+}
+
+/** What an automation access token may do */
+export type AccessScope = 'list_chats' | 'export' | 'upload'
+
+export interface AccessToken {
+  id: string
+  name: string
+  scopes: AccessScope[]
+  created_at: string
+}
+
+/** A chattomap:// link, as parsed by the backend */
+export type DeepLink = { kind: 'select_chats' } | { kind: 'retry_job'; job_id: string }
@@ -0,0 +1,87 @@
+/**
+ * Upload history - past uploads, so their maps can be opened again. Also
+ * opens the map from a click on the "Export complete" notification.
+ */
+
+import { addPluginListener, invoke } from '@tauri-apps/api/core'
+import { listen } from '@tauri-apps/api/event'
+import { open as openShell } from '@tauri-apps/plugin-shell'
+import { escapeHtml } from './html'
+import type { UploadRecord } from './types'
+
+// Elements (initialized in setup)
+let panel: HTMLElement
+let historyList: HTMLElement
+
+function describeUpload(record: UploadRecord): string {
+  const uploaded = new Date(record.uploaded_at).toLocaleString([], {
+    dateStyle: 'medium',
+    timeStyle: 'short'
+  })
+  const chats = `${record.chat_count} chat${record.chat_count === 1 ? '' : 's'}`
+  return `${uploaded} · ${chats} · ${record.message_count} messages`
+}
+
+async function refresh(): Promise<void> {
+  try {
+    const records = await invoke<UploadRecord[]>('get_upload_history')
+    panel.classList.toggle('hidden', records.length === 0)
+    historyList.innerHTML = records
+      .map(
+        (record) => `
+          <div class="export-queue-job">
+            <span>${escapeHtml(describeUpload(record))}</span>
+            <button class="btn btn-small" data-job="${escapeHtml(record.job_id)}">Open Map</button>
+          </div>
+        `
+      )
+      .join('')
+  } catch (error) {
+    console.error('Failed to load upload history:', error)
+  }
+}
+
+interface NotificationAction {
+  notification: { extra?: Record<string, unknown> }
+}
+
+// The backend attaches the results page as the notification's `results_url`
+// extra. Platforms whose notifications don't report clicks just bring the
+// app forward.
+async function setupNotificationClicks(): Promise<void> {
+  try {
+    await addPluginListener<NotificationAction>('notification', 'actionPerformed', (action) => {
+      const resultsUrl = action.notification.extra?.['results_url']
+      if (typeof resultsUrl === 'string') {
+        openShell(resultsUrl)
+      }
+    })
+  } catch {
+    // No click events on this platform
+  }
+}
+
+export async function setupUploadHistory(): Promise<void> {
+  const found = document.getElementById('upload-history-panel')
+  const list = document.getElementById('upload-history-list')
+  if (!found || !list) {
+    throw new Error('Upload history elements not found')
+  }
+  panel = found
+  historyList = list
+
+  historyList.addEventListener('click', async (e) => {
+    const jobId = (e.target as HTMLElement).closest('button')?.dataset['job']
+    if (!jobId) return
+    try {
+      await invoke('open_job_results', { jobId })
+    } catch (error) {
+      alert(`Could not open the map: ${error}`)
+    }
+  })
+
+  // Each finished upload leaves the outbox
+  await listen('pending-uploads-changed', refresh)
+  await setupNotificationClicks()
+  await refresh()
+}
@@ -0,0 +1,31 @@
+/**
+ * Describing validation results for a manually selected chat.db / sms.db
+ */
+
+import type { ValidationResult } from './types'
+
+// Summarize a validated database, e.g.
+// "an iOS sms.db with 42,113 messages from 2015–2024 (macOS 13+ schema)"
+export function describeDatabase(validation: ValidationResult): string {
+  const kind = validation.platform === 'ios' ? 'an iOS sms.db' : 'a macOS chat.db'
+  const first = validation.first_message_date?.slice(0, 4)
+  const last = validation.last_message_date?.slice(0, 4)
+  const range = first && last ? ` from ${first}–${last}` : ''
+  const version = validation.origin?.min_macos_version
+  const schema = version ? ` (macOS ${version}+ schema)` : ''
+  return `This looks like ${kind} with ${validation.message_count.toLocaleString()} messages${range}${schema}`
+}
+
+export function describeValidationFailure(validation: ValidationResult): string {
+  const intro = 'This does not appear to be a valid iMessage database.\n\n'
+  switch (validation.failure_reason) {
+    case 'not_found':
+      return `${intro}The selected file could not be found.`
+    case 'missing_tables':
+      return `${intro}The file is a SQLite database but has no iMessage tables.`
+    case 'query_failed':
+      return `${intro}The file has iMessage tables, but they could not be read.`
+    default:
+      return `${intro}The file should be named "chat.db" and contain iMessage tables.`
+  }
+}